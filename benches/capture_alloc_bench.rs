@@ -0,0 +1,106 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use vox_gui::common::encoder::EncoderTuning;
+use vox_gui::server::frame_source::{FrameSource, TestPatternSource};
+use vox_gui::server::pixel_format::{to_rgb, to_rgb_into, PixelFormat};
+use vox_gui::server::screen_capture::ScreenCapture;
+
+const WIDTH: usize = 1920;
+const HEIGHT: usize = 1080;
+const FRAMES: usize = 60;
+
+// Counts every allocation (and reallocation) and the bytes it asked for
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+    
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+    
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+// Allocations and bytes allocated while `f` runs
+fn count<T>(f: impl FnOnce() -> T) -> (T, usize, usize) {
+    let (allocations, bytes) = (ALLOCATIONS.load(Ordering::Relaxed), ALLOCATED_BYTES.load(Ordering::Relaxed));
+    let result = f();
+    (
+        result,
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+        ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes,
+    )
+}
+
+fn report(name: &str, allocations: usize, bytes: usize, frames: usize) {
+    println!(
+        "{:<32} {:>10.1} {:>14.1}",
+        name,
+        allocations as f64 / frames as f64,
+        bytes as f64 / frames as f64 / (1024.0 * 1024.0),
+    );
+}
+
+/// Heap allocations per captured 1080p frame: the raw capture and RGB conversion with
+/// fresh buffers against reused ones, and a whole software-path `capture_frame`. Run
+/// with `cargo bench --bench capture_alloc_bench`.
+fn main() {
+    println!("{:<32} {:>10} {:>14}", "Stage", "allocs", "MiB allocated");
+    
+    let mut source = TestPatternSource::new(WIDTH, HEIGHT);
+    let (_, allocations, bytes) = count(|| {
+        for _ in 0..FRAMES {
+            let frame = source.frame().unwrap();
+            to_rgb(&frame, WIDTH, HEIGHT, PixelFormat::Bgra8).unwrap();
+        }
+    });
+    report("capture + convert, fresh", allocations, bytes, FRAMES);
+    
+    // Warmed up by one frame, as ScreenCapture's buffers are after its first capture
+    let (mut raw, mut rgb) = (Vec::new(), Vec::new());
+    source.frame_into(&mut raw).unwrap();
+    to_rgb_into(&raw, WIDTH, HEIGHT, PixelFormat::Bgra8, &mut rgb).unwrap();
+    let (_, allocations, bytes) = count(|| {
+        for _ in 0..FRAMES {
+            source.frame_into(&mut raw).unwrap();
+            to_rgb_into(&raw, WIDTH, HEIGHT, PixelFormat::Bgra8, &mut rgb).unwrap();
+        }
+    });
+    report("capture + convert, reused", allocations, bytes, FRAMES);
+    
+    let source = Box::new(TestPatternSource::new(WIDTH, HEIGHT));
+    let mut capture = ScreenCapture::from_source(source, 240, 5_000_000, EncoderTuning::default()).unwrap();
+    capture.set_hardware_encoding(false);
+    let mut captured = 0;
+    let (mut allocations, mut bytes) = (0, 0);
+    while captured < FRAMES + 1 {
+        let (frame, frame_allocations, frame_bytes) = count(|| capture.capture_frame().unwrap());
+        if frame.is_none() {
+            std::thread::sleep(Duration::from_millis(1));
+            continue;
+        }
+        // The first frame sizes the buffers and is a keyframe
+        if captured > 0 {
+            allocations += frame_allocations;
+            bytes += frame_bytes;
+        }
+        captured += 1;
+    }
+    report("capture_frame (software path)", allocations, bytes, FRAMES);
+}
@@ -0,0 +1,27 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use vox_gui::common::crypto::{aes_accelerated, Cipher, CryptoSession};
+
+// An input event, a batch of tiles and a keyframe chunk
+const SIZES: [usize; 3] = [256, 64 * 1024, 1024 * 1024];
+
+/// Encrypting and decrypting one message, as each frame costs the server and client
+/// together. Run with `cargo bench --bench cipher_bench`.
+fn seal_and_open(c: &mut Criterion) {
+    eprintln!("AES instructions: {}", if aes_accelerated() { "yes" } else { "no" });
+    
+    let mut group = c.benchmark_group("seal_and_open");
+    for cipher in [Cipher::Aes256Gcm, Cipher::ChaCha20Poly1305] {
+        let session = CryptoSession::from_shared_secret(&[7u8; 32]).unwrap().with_cipher(cipher);
+        for size in SIZES {
+            let message = vec![0x5a; size];
+            group.throughput(Throughput::Bytes(size as u64));
+            group.bench_with_input(BenchmarkId::new(cipher.name(), size), &message, |b, message| {
+                b.iter(|| session.decrypt(&session.encrypt(message).unwrap()).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, seal_and_open);
+criterion_main!(benches);
@@ -1,9 +1,32 @@
+use clap::Parser;
 use eframe::egui;
 use vox_gui::client::app::VoxApp;
+use vox_gui::common::link::ConnectionLink;
+use vox_gui::common::socket::SocketBuffers;
+
+#[derive(Parser)]
+#[command(name = "vox_client")]
+#[command(about = "Remote desktop client")]
+struct Args {
+    /// Connection link from a server, e.g. vox://192.168.1.20:8080?code=123456; fills in
+    /// the address and access code
+    #[arg(value_parser = ConnectionLink::parse)]
+    link: Option<ConnectionLink>,
+    
+    /// Socket send buffer size in bytes, for fast links with a long round trip (system default if unset)
+    #[arg(long, value_name = "BYTES")]
+    send_buffer: Option<u32>,
+    
+    /// Socket receive buffer size in bytes, for fast links with a long round trip (system default if unset)
+    #[arg(long, value_name = "BYTES")]
+    recv_buffer: Option<u32>,
+}
 
 fn main() -> Result<(), eframe::Error> {
     env_logger::init();
     
+    let args = Args::parse();
+    
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_title("Vox Remote Desktop Client")
@@ -16,6 +39,15 @@ fn main() -> Result<(), eframe::Error> {
     eframe::run_native(
         "Vox Remote Desktop",
         options,
-        Box::new(|cc| Ok(Box::new(VoxApp::new(cc)))),
+        Box::new(move |cc| {
+            let app = VoxApp::new(cc).with_socket_buffers(SocketBuffers {
+                send: args.send_buffer,
+                recv: args.recv_buffer,
+            });
+            Ok(Box::new(match args.link {
+                Some(link) => app.with_link(link),
+                None => app,
+            }))
+        }),
     )
 }
\ No newline at end of file
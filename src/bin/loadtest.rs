@@ -0,0 +1,102 @@
+use vox_gui::client::loadtest::{run_connection, ConnectionReport, LatencySummary};
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+use clap::Parser;
+use tracing::info;
+
+#[derive(Parser)]
+#[command(name = "vox_loadtest")]
+#[command(about = "Open many client sessions against a server and report how it holds up")]
+struct Args {
+    /// Server address to connect to
+    #[arg(short, long, default_value = "127.0.0.1:8080")]
+    address: String,
+    
+    /// Access code shown by the server
+    #[arg(short, long)]
+    code: String,
+    
+    /// Concurrent sessions to open
+    #[arg(short = 'n', long, default_value = "10")]
+    connections: usize,
+    
+    /// How long each session consumes frames, in seconds
+    #[arg(short, long, default_value = "10")]
+    duration: u64,
+    
+    /// Delay between starting sessions in milliseconds (0 = all at once)
+    #[arg(long, default_value = "0")]
+    ramp_ms: u64,
+    
+    /// Skip encryption; needs a loopback server started with --no-encryption
+    #[arg(long)]
+    no_encryption: bool,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt::init();
+    
+    let args = Args::parse();
+    let duration = Duration::from_secs(args.duration);
+    info!("Opening {} sessions to {} for {:?}", args.connections, args.address, duration);
+    
+    let started = Instant::now();
+    let mut tasks = Vec::with_capacity(args.connections);
+    for index in 0..args.connections {
+        if index > 0 && args.ramp_ms > 0 {
+            tokio::time::sleep(Duration::from_millis(args.ramp_ms)).await;
+        }
+        let address = args.address.clone();
+        let code = args.code.clone();
+        let encryption = !args.no_encryption;
+        tasks.push(tokio::spawn(async move {
+            run_connection(&address, &code, duration, encryption).await
+        }));
+    }
+    
+    let mut reports = Vec::with_capacity(tasks.len());
+    for task in tasks {
+        reports.push(task.await.unwrap_or_else(|e| ConnectionReport {
+            error: Some(format!("Task failed: {}", e)),
+            ..ConnectionReport::default()
+        }));
+    }
+    
+    print_report(&reports, started.elapsed());
+    Ok(())
+}
+
+fn print_report(reports: &[ConnectionReport], elapsed: Duration) {
+    let connected = reports.iter().filter(|report| report.connect.is_some()).count();
+    let frames: u64 = reports.iter().map(|report| report.frames).sum();
+    let bytes: u64 = reports.iter().map(|report| report.bytes).sum();
+    let seconds = elapsed.as_secs_f64().max(f64::EPSILON);
+    
+    println!("Sessions:    {} of {} connected", connected, reports.len());
+    println!("Frames:      {} ({:.1} fps aggregate)", frames, frames as f64 / seconds);
+    println!("Throughput:  {:.2} Mbps aggregate", bytes as f64 * 8.0 / seconds / 1_000_000.0);
+    
+    let connect = LatencySummary::from_samples(reports.iter().filter_map(|report| report.connect).collect());
+    let first_frame = LatencySummary::from_samples(reports.iter().filter_map(|report| report.first_frame).collect());
+    match connect {
+        Some(summary) => println!("Connect:     {}", summary),
+        None => println!("Connect:     no session connected"),
+    }
+    match first_frame {
+        Some(summary) => println!("First frame: {}", summary),
+        None => println!("First frame: no session received a frame"),
+    }
+    
+    // The same failure hitting many sessions is reported once, with a count
+    let mut errors: BTreeMap<&str, usize> = BTreeMap::new();
+    for error in reports.iter().filter_map(|report| report.error.as_deref()) {
+        *errors.entry(error).or_default() += 1;
+    }
+    if !errors.is_empty() {
+        println!("Errors:");
+        for (error, count) in errors {
+            println!("  {}x {}", count, error);
+        }
+    }
+}
@@ -1,28 +1,190 @@
-use vox_gui::server::server::Server;
-// use vox_gui::server::quic_server::QuicServer;
-use vox_gui::common::auth::AccessCode;
+use vox_gui::server::server::{Server, ServerControl};
+use vox_gui::server::approval::{ApprovalPolicy, DEFAULT_APPROVAL_TIMEOUT};
+use vox_gui::common::auth::{AccessCode, AccessCodes};
+use vox_gui::common::link::{shareable_address, ConnectionLink};
 use vox_gui::common::metrics::PerformanceMetrics;
+use vox_gui::server::frame_source::{CaptureBackend, FrameSource, FrameSourceFactory, TestPatternSource};
+use vox_gui::server::benchmark::run_encoder_benchmark;
+use vox_gui::server::metrics_endpoint;
+use vox_gui::server::notifications::{open_notification_source, NotificationSourceFactory};
+use vox_gui::common::encoder::{EncoderPreset, EncoderTuning};
+use vox_gui::common::color::{ColorMatrix, ColorPreference, ColorRange};
+use vox_gui::common::quality::QualityMode;
+use vox_gui::common::crypto::RekeyPolicy;
+use vox_gui::common::socket::SocketBuffers;
+use vox_gui::server::config::ServerConfig;
+use vox_gui::server::power::PowerSaveMode;
+use vox_gui::server::screen_capture::DEFAULT_BITRATE;
+use vox_gui::server::vsync::vsync_supported;
+use std::collections::HashSet;
+use std::io::{IsTerminal, Write};
+use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use tracing::{info, error};
+use tracing::{info, warn, error};
 use tracing_subscriber;
-use clap::{Parser, ValueEnum};
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, ValueEnum};
+use clap::parser::ValueSource;
 
 #[derive(Parser)]
 #[command(name = "vox_server")]
 #[command(about = "High-performance remote desktop server")]
 struct Args {
+    /// Read settings from a TOML (or .json) file; flags given here override it
+    #[arg(short, long, value_name = "PATH")]
+    config: Option<PathBuf>,
+    
     /// Server address to bind to
     #[arg(short, long, default_value = "0.0.0.0:8080")]
     address: String,
     
-    /// Transport protocol to use
+    /// Transport protocol to use; QUIC is experimental and not yet spoken by vox_client
     #[arg(short, long, value_enum, default_value = "tcp")]
     transport: Transport,
     
     /// Enable performance metrics
     #[arg(short, long)]
     metrics: bool,
+    
+    /// Serve Prometheus metrics over HTTP at this address, e.g. 127.0.0.1:9100
+    #[arg(long, value_name = "ADDR")]
+    metrics_addr: Option<String>,
+    
+    /// Allow --metrics-addr to listen on a non-loopback address
+    #[arg(long)]
+    metrics_allow_remote: bool,
+    
+    /// Disconnect sessions with no mouse/keyboard input for this many seconds (view-only sessions are exempt)
+    #[arg(long, value_name = "SECONDS")]
+    idle_timeout: Option<u64>,
+    
+    /// Also accept this fixed code with its own permissions, e.g. 482913:view-only or 771204:full-control (repeatable)
+    #[arg(long = "access-code", value_name = "CODE:PERMISSIONS")]
+    access_codes: Vec<String>,
+    
+    /// Ask on this console (y/n) before letting in each client that has the access code; leave off for unattended access
+    #[arg(long)]
+    require_approval: bool,
+    
+    /// Seconds a client waits for --require-approval to be answered before it is turned away
+    #[arg(long, value_name = "SECONDS", default_value_t = DEFAULT_APPROVAL_TIMEOUT.as_secs())]
+    approval_timeout: u64,
+    
+    /// Screen capture backend (auto picks PipeWire on Wayland sessions)
+    #[arg(long, value_enum, default_value = "auto")]
+    capture_backend: CaptureBackendArg,
+    
+    /// H.264 encoder preset: low-latency disables lookahead and frame buffering
+    #[arg(long, value_enum, default_value = "low-latency")]
+    encoder_preset: EncoderPresetArg,
+    
+    /// H.264 encoder threads (0 = let the codec decide)
+    #[arg(long, default_value = "0")]
+    encoder_threads: usize,
+    
+    /// H.264 frames allowed in flight in the encoder (0 = one in, one out, lowest latency)
+    #[arg(long, value_name = "FRAMES", default_value = "0")]
+    encode_queue_depth: usize,
+    
+    /// H.264 YUV matrix (auto = BT.709 from 720p up, BT.601 below)
+    #[arg(long, value_enum, default_value = "auto")]
+    color_matrix: ColorMatrixArg,
+    
+    /// H.264 sample range: limited (16-235, what most players expect) or full (0-255)
+    #[arg(long, value_enum, default_value = "auto")]
+    color_range: ColorRangeArg,
+    
+    /// Screen capture rate
+    #[arg(long, default_value = "30")]
+    fps: u32,
+    
+    /// H.264 target bitrate in bits per second at High quality; other quality modes scale it
+    #[arg(long, default_value_t = DEFAULT_BITRATE)]
+    bitrate: u32,
+    
+    /// Refuse new clients once this many sessions are connected
+    #[arg(long)]
+    max_sessions: Option<usize>,
+    
+    /// Lowest quality mode sessions may drop to
+    #[arg(long, value_enum, default_value = "minimal")]
+    min_quality: QualityArg,
+    
+    /// Highest quality mode sessions may use
+    #[arg(long, value_enum, default_value = "ultra")]
+    max_quality: QualityArg,
+    
+    /// Keep full quality only within this many pixels of the remote cursor (foveated encoding)
+    #[arg(long, value_name = "PIXELS")]
+    foveation_radius: Option<u32>,
+    
+    /// Leave the cursor out of captured frames and have clients draw it (when every client can)
+    #[arg(long)]
+    cursor_overlay: bool,
+    
+    /// Save battery on a quiet screen: lower frame rate and fewer keyframes (auto = while on battery)
+    #[arg(long, value_enum, default_value = "auto")]
+    power_save: PowerSaveArg,
+    
+    /// Also encode a half-resolution layer while several clients watch a display, and send each the one its bandwidth allows
+    #[arg(long)]
+    simulcast: bool,
+    
+    /// Send every frame as a whole software WebP image, without hardware encoders or deltas; slow, but for when the normal pipeline crashes
+    #[arg(long)]
+    safe_mode: bool,
+    
+    /// Capture each frame just after the display refreshes to avoid tearing (macOS and Windows; a 60 Hz timer elsewhere)
+    #[arg(long)]
+    vsync: bool,
+    
+    /// Capture and encode only when the screen changes instead of polling on a timer (Wayland/PipeWire; other backends keep polling)
+    #[arg(long)]
+    capture_on_damage: bool,
+    
+    /// Checksum each lossless software frame so clients can flag frames they rebuild differently (debugging; one extra pass over each frame)
+    #[arg(long)]
+    frame_checksums: bool,
+    
+    /// Socket send buffer size in bytes, for fast links with a long round trip (system default if unset)
+    #[arg(long, value_name = "BYTES")]
+    send_buffer: Option<u32>,
+    
+    /// Socket receive buffer size in bytes, for fast links with a long round trip (system default if unset)
+    #[arg(long, value_name = "BYTES")]
+    recv_buffer: Option<u32>,
+    
+    /// Show clients the notifications that pop up on this machine (Linux, needs dbus-monitor)
+    #[arg(long)]
+    forward_notifications: bool,
+    
+    /// Stream a synthetic moving pattern instead of capturing a display (for headless hosts)
+    #[arg(long)]
+    test_pattern: bool,
+    
+    /// Rotate each session key after this many messages
+    #[arg(long, value_name = "MESSAGES", default_value_t = RekeyPolicy::default().max_messages, value_parser = clap::value_parser!(u64).range(1..))]
+    rekey_messages: u64,
+    
+    /// Rotate each session key after this many bytes
+    #[arg(long, value_name = "BYTES", default_value_t = RekeyPolicy::default().max_bytes, value_parser = clap::value_parser!(u64).range(1..))]
+    rekey_bytes: u64,
+    
+    /// Let loopback clients turn off encryption to profile raw streaming cost (loopback binds only)
+    #[arg(long)]
+    no_encryption: bool,
+    
+    /// Send video unencrypted to authenticated clients that ask, for networks already encrypted by a VPN
+    #[arg(long)]
+    plaintext_video: bool,
+    
+    /// Benchmark the available encoders on a synthetic frame sequence and exit
+    #[arg(long)]
+    benchmark: bool,
+    
+    /// Number of frames to encode per encoder in benchmark mode
+    #[arg(long, default_value = "120")]
+    benchmark_frames: usize,
 }
 
 #[derive(Clone, ValueEnum)]
@@ -31,11 +193,314 @@ enum Transport {
     Quic,
 }
 
+#[derive(Clone, ValueEnum)]
+enum EncoderPresetArg {
+    LowLatency,
+    Quality,
+}
+
+impl From<EncoderPresetArg> for EncoderPreset {
+    fn from(arg: EncoderPresetArg) -> Self {
+        match arg {
+            EncoderPresetArg::LowLatency => EncoderPreset::LowLatency,
+            EncoderPresetArg::Quality => EncoderPreset::Quality,
+        }
+    }
+}
+
+#[derive(Clone, ValueEnum)]
+enum ColorMatrixArg {
+    Auto,
+    Bt601,
+    Bt709,
+}
+
+impl From<ColorMatrixArg> for Option<ColorMatrix> {
+    fn from(arg: ColorMatrixArg) -> Self {
+        match arg {
+            ColorMatrixArg::Auto => None,
+            ColorMatrixArg::Bt601 => Some(ColorMatrix::Bt601),
+            ColorMatrixArg::Bt709 => Some(ColorMatrix::Bt709),
+        }
+    }
+}
+
+#[derive(Clone, ValueEnum)]
+enum ColorRangeArg {
+    Auto,
+    Limited,
+    Full,
+}
+
+impl From<ColorRangeArg> for Option<ColorRange> {
+    fn from(arg: ColorRangeArg) -> Self {
+        match arg {
+            ColorRangeArg::Auto => None,
+            ColorRangeArg::Limited => Some(ColorRange::Limited),
+            ColorRangeArg::Full => Some(ColorRange::Full),
+        }
+    }
+}
+
+#[derive(Clone, ValueEnum)]
+enum QualityArg {
+    Ultra,
+    High,
+    Medium,
+    Low,
+    Minimal,
+}
+
+impl From<QualityArg> for QualityMode {
+    fn from(arg: QualityArg) -> Self {
+        match arg {
+            QualityArg::Ultra => QualityMode::Ultra,
+            QualityArg::High => QualityMode::High,
+            QualityArg::Medium => QualityMode::Medium,
+            QualityArg::Low => QualityMode::Low,
+            QualityArg::Minimal => QualityMode::Minimal,
+        }
+    }
+}
+
+#[derive(Clone, ValueEnum)]
+enum CaptureBackendArg {
+    Auto,
+    Scrap,
+    Pipewire,
+}
+
+#[derive(Clone, ValueEnum)]
+enum PowerSaveArg {
+    Auto,
+    On,
+    Off,
+}
+
+impl From<PowerSaveArg> for PowerSaveMode {
+    fn from(arg: PowerSaveArg) -> Self {
+        match arg {
+            PowerSaveArg::Auto => PowerSaveMode::Auto,
+            PowerSaveArg::On => PowerSaveMode::On,
+            PowerSaveArg::Off => PowerSaveMode::Off,
+        }
+    }
+}
+
+impl From<CaptureBackendArg> for CaptureBackend {
+    fn from(arg: CaptureBackendArg) -> Self {
+        match arg {
+            CaptureBackendArg::Auto => CaptureBackend::Auto,
+            CaptureBackendArg::Scrap => CaptureBackend::Scrap,
+            CaptureBackendArg::Pipewire => CaptureBackend::PipeWire,
+        }
+    }
+}
+
+impl Args {
+    /// Parse the command line, filling in anything not given there from `--config`.
+    fn load() -> anyhow::Result<Self> {
+        let matches = Args::command().get_matches();
+        let mut args = Args::from_arg_matches(&matches)?;
+        
+        if let Some(path) = args.config.clone() {
+            let config = ServerConfig::load(&path)?;
+            args.merge_config(config, &matches)
+                .map_err(|e| anyhow::anyhow!("Invalid config file {}: {}", path.display(), e))?;
+        }
+        
+        Ok(args)
+    }
+    
+    fn merge_config(&mut self, config: ServerConfig, matches: &ArgMatches) -> anyhow::Result<()> {
+        let from_file = |id: &str| matches.value_source(id) != Some(ValueSource::CommandLine);
+        
+        if let (Some(address), true) = (config.address, from_file("address")) {
+            self.address = address;
+        }
+        if let (Some(transport), true) = (config.transport, from_file("transport")) {
+            self.transport = parse_value("transport", &transport)?;
+        }
+        if let (Some(metrics), true) = (config.metrics, from_file("metrics")) {
+            self.metrics = metrics;
+        }
+        if let (Some(addr), true) = (config.metrics_addr, from_file("metrics_addr")) {
+            self.metrics_addr = Some(addr);
+        }
+        if let (Some(allow), true) = (config.metrics_allow_remote, from_file("metrics_allow_remote")) {
+            self.metrics_allow_remote = allow;
+        }
+        if let (Some(secs), true) = (config.idle_timeout, from_file("idle_timeout")) {
+            self.idle_timeout = Some(secs);
+        }
+        if let (Some(codes), true) = (config.access_codes, from_file("access_codes")) {
+            self.access_codes = codes;
+        }
+        if let (Some(require), true) = (config.require_approval, from_file("require_approval")) {
+            self.require_approval = require;
+        }
+        if let (Some(secs), true) = (config.approval_timeout, from_file("approval_timeout")) {
+            self.approval_timeout = secs;
+        }
+        if let (Some(backend), true) = (config.capture_backend, from_file("capture_backend")) {
+            self.capture_backend = parse_value("capture_backend", &backend)?;
+        }
+        if let (Some(preset), true) = (config.encoder_preset, from_file("encoder_preset")) {
+            self.encoder_preset = parse_value("encoder_preset", &preset)?;
+        }
+        if let (Some(threads), true) = (config.encoder_threads, from_file("encoder_threads")) {
+            self.encoder_threads = threads;
+        }
+        if let (Some(depth), true) = (config.encode_queue_depth, from_file("encode_queue_depth")) {
+            self.encode_queue_depth = depth;
+        }
+        if let (Some(matrix), true) = (config.color_matrix, from_file("color_matrix")) {
+            self.color_matrix = parse_value("color_matrix", &matrix)?;
+        }
+        if let (Some(range), true) = (config.color_range, from_file("color_range")) {
+            self.color_range = parse_value("color_range", &range)?;
+        }
+        if let (Some(fps), true) = (config.fps, from_file("fps")) {
+            self.fps = fps;
+        }
+        if let (Some(bitrate), true) = (config.bitrate, from_file("bitrate")) {
+            self.bitrate = bitrate;
+        }
+        if let (Some(max), true) = (config.max_sessions, from_file("max_sessions")) {
+            self.max_sessions = Some(max);
+        }
+        if let (Some(quality), true) = (config.min_quality, from_file("min_quality")) {
+            self.min_quality = parse_value("min_quality", &quality)?;
+        }
+        if let (Some(quality), true) = (config.max_quality, from_file("max_quality")) {
+            self.max_quality = parse_value("max_quality", &quality)?;
+        }
+        if let (Some(radius), true) = (config.foveation_radius, from_file("foveation_radius")) {
+            self.foveation_radius = Some(radius);
+        }
+        if let (Some(overlay), true) = (config.cursor_overlay, from_file("cursor_overlay")) {
+            self.cursor_overlay = overlay;
+        }
+        if let (Some(mode), true) = (config.power_save, from_file("power_save")) {
+            self.power_save = parse_value("power_save", &mode)?;
+        }
+        if let (Some(simulcast), true) = (config.simulcast, from_file("simulcast")) {
+            self.simulcast = simulcast;
+        }
+        if let (Some(safe_mode), true) = (config.safe_mode, from_file("safe_mode")) {
+            self.safe_mode = safe_mode;
+        }
+        if let (Some(vsync), true) = (config.vsync, from_file("vsync")) {
+            self.vsync = vsync;
+        }
+        if let (Some(size), true) = (config.send_buffer, from_file("send_buffer")) {
+            self.send_buffer = Some(size);
+        }
+        if let (Some(size), true) = (config.recv_buffer, from_file("recv_buffer")) {
+            self.recv_buffer = Some(size);
+        }
+        if let (Some(forward), true) = (config.forward_notifications, from_file("forward_notifications")) {
+            self.forward_notifications = forward;
+        }
+        if let (Some(pattern), true) = (config.test_pattern, from_file("test_pattern")) {
+            self.test_pattern = pattern;
+        }
+        if let (Some(messages), true) = (config.rekey_messages, from_file("rekey_messages")) {
+            self.rekey_messages = messages;
+        }
+        if let (Some(bytes), true) = (config.rekey_bytes, from_file("rekey_bytes")) {
+            self.rekey_bytes = bytes;
+        }
+        
+        Ok(())
+    }
+}
+
+/// Parse a config file value the same way clap parses the matching flag.
+fn parse_value<T: ValueEnum>(field: &str, value: &str) -> anyhow::Result<T> {
+    T::from_str(value, true).map_err(|_| {
+        let expected: Vec<String> = T::value_variants().iter()
+            .filter_map(|v| v.to_possible_value())
+            .map(|v| v.get_name().to_string())
+            .collect();
+        anyhow::anyhow!("{}: invalid value '{}', expected one of: {}", field, value, expected.join(", "))
+    })
+}
+
+/// `kill -USR1 <pid>` hands out a fresh access code; connected sessions are unaffected.
+#[cfg(unix)]
+async fn regenerate_on_signal(control: ServerControl, link_address: String) {
+    use tokio::signal::unix::{signal, SignalKind};
+    
+    let mut signals = match signal(SignalKind::user_defined1()) {
+        Ok(signals) => signals,
+        Err(e) => {
+            warn!("Can't listen for SIGUSR1, access codes won't be regenerated on demand: {}", e);
+            return;
+        }
+    };
+    while signals.recv().await.is_some() {
+        let code = control.regenerate_access_code().await;
+        info!("Link: {}", ConnectionLink::new(link_address.clone(), code.code));
+    }
+}
+
+/// Ask on the console about each client waiting to be let in, one at a time.
+async fn approve_on_console(control: ServerControl) {
+    let mut asked = HashSet::new();
+    loop {
+        control.connection_requested().await;
+        for request in control.pending_connections() {
+            if !asked.insert(request.id) {
+                continue;
+            }
+            let peer = request.peer.map_or_else(|| "an unknown address".to_string(), |peer| peer.to_string());
+            print!("Allow a connection from {}? [y/N] ", peer);
+            let _ = std::io::stdout().flush();
+            let answer = tokio::task::spawn_blocking(|| {
+                let mut line = String::new();
+                std::io::stdin().read_line(&mut line).map(|_| line)
+            })
+            .await;
+            let approve = matches!(answer, Ok(Ok(line)) if matches!(line.trim().to_lowercase().as_str(), "y" | "yes"));
+            if !control.answer_connection(request.id, approve) {
+                warn!("The connection from {} stopped waiting before it was answered", peer);
+            }
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
     
-    let args = Args::parse();
+    let args = Args::load()?;
+    
+    let min_quality: QualityMode = args.min_quality.clone().into();
+    let max_quality: QualityMode = args.max_quality.clone().into();
+    if min_quality.bandwidth_requirement() > max_quality.bandwidth_requirement() {
+        return Err(anyhow::anyhow!("min_quality {:?} is higher than max_quality {:?}", min_quality, max_quality));
+    }
+    if args.fps == 0 {
+        return Err(anyhow::anyhow!("fps must be at least 1"));
+    }
+    if args.approval_timeout == 0 {
+        return Err(anyhow::anyhow!("approval_timeout must be at least 1 second"));
+    }
+    
+    if args.benchmark {
+        info!("Benchmarking encoders at 1920x1080, {} frames each", args.benchmark_frames);
+        let results = run_encoder_benchmark(1920, 1080, 30, args.benchmark_frames)?;
+        
+        println!(
+            "{:<10} {:>12} {:>8} {:>14} {:>14} {:>14} {:>14}",
+            "Encoder", "Preset", "Frames", "ms/frame", "delay (frames)", "bytes/frame", "Mbps @30fps"
+        );
+        for result in &results {
+            println!("{}", result);
+        }
+        return Ok(());
+    }
     
     info!("Starting Vox Remote Desktop Server");
     
@@ -43,14 +508,17 @@ async fn main() -> anyhow::Result<()> {
     let access_code = AccessCode::generate();
     info!("=================================");
     info!("Access Code: {}", access_code.code);
+    info!("Link: {}", ConnectionLink::new(shareable_address(&args.address), access_code.code.clone()));
     info!("Code expires in 5 minutes");
     info!("=================================");
     
     let access_code = Arc::new(RwLock::new(Some(access_code)));
     
-    // Start metrics collection if enabled
-    let _metrics = if args.metrics {
-        let metrics = Arc::new(PerformanceMetrics::new());
+    // The server records into this whether or not anything reads it
+    let metrics = Arc::new(PerformanceMetrics::new());
+    
+    // Start metrics logging if enabled
+    if args.metrics {
         let metrics_clone = metrics.clone();
         
         // Print metrics every 5 seconds
@@ -61,28 +529,136 @@ async fn main() -> anyhow::Result<()> {
                 info!("\n{}", metrics_clone.get_stats());
             }
         });
-        
-        Some(metrics)
-    } else {
-        None
+    }
+    
+    if let Some(addr) = &args.metrics_addr {
+        let listener = metrics_endpoint::bind(addr, args.metrics_allow_remote).await?;
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            if let Err(e) = metrics_endpoint::serve(listener, metrics).await {
+                error!("Metrics endpoint error: {}", e);
+            }
+        });
+    }
+    
+    let color = ColorPreference {
+        matrix: args.color_matrix.into(),
+        range: args.color_range.into(),
     };
     
-    // Start server with selected transport
+    // Every option applies whichever transport clients connect over
+    let mut server = Server::new(access_code)
+        .with_metrics(metrics)
+        .with_capture_backend(args.capture_backend.into())
+        .with_encoder_tuning(EncoderTuning {
+            preset: args.encoder_preset.into(),
+            threads: args.encoder_threads,
+            queue_depth: args.encode_queue_depth,
+            color,
+            ..EncoderTuning::default()
+        })
+        .with_frame_rate(args.fps, args.bitrate)
+        .with_rekey_policy(RekeyPolicy {
+            max_messages: args.rekey_messages,
+            max_bytes: args.rekey_bytes,
+        })
+        .with_quality_bounds(min_quality, max_quality);
+    if !args.access_codes.is_empty() {
+        let mut codes = AccessCodes::new();
+        for spec in &args.access_codes {
+            codes.add_spec(spec).map_err(|e| anyhow::anyhow!("--access-code: {}", e))?;
+        }
+        info!("Accepting {} fixed access code(s) besides the rotating one", codes.len());
+        server = server.with_access_codes(codes);
+    }
+    if args.no_encryption {
+        warn!("=================================");
+        warn!("--no-encryption: clients may stream UNENCRYPTED");
+        warn!("For local benchmarking only");
+        warn!("=================================");
+        server = server.with_unencrypted_sessions();
+    }
+    if args.plaintext_video {
+        warn!("=================================");
+        warn!("--plaintext-video: clients may receive video UNENCRYPTED");
+        warn!("Only use this on a network that is encrypted already (VPN, WireGuard)");
+        warn!("=================================");
+        server = server.with_plaintext_video();
+    }
+    if let Some(max) = args.max_sessions {
+        server = server.with_max_sessions(max);
+    }
+    if let Some(radius) = args.foveation_radius {
+        info!("Foveated encoding: full quality within {}px of the cursor", radius);
+        server = server.with_foveation(radius);
+    }
+    if args.cursor_overlay {
+        server = server.with_cursor_overlay();
+    }
+    server = server.with_power_save(args.power_save.into());
+    if args.simulcast {
+        server = server.with_simulcast();
+    }
+    if args.safe_mode {
+        warn!("Safe mode: every frame is sent whole as software WebP, hardware encoding is off");
+        server = server.with_safe_mode();
+    }
+    if args.vsync {
+        if !vsync_supported() {
+            warn!("--vsync: this platform has no refresh signal to follow, capturing on a 60 Hz timer");
+        }
+        server = server.with_vsync();
+    }
+    if args.capture_on_damage {
+        server = server.with_capture_on_damage();
+    }
+    if args.frame_checksums {
+        info!("Frame checksums on: clients will report frames that don't match the captured screen");
+        server = server.with_frame_checksums();
+    }
+    server = server.with_socket_buffers(SocketBuffers {
+        send: args.send_buffer,
+        recv: args.recv_buffer,
+    });
+    if args.forward_notifications {
+        let sources: NotificationSourceFactory = Arc::new(open_notification_source);
+        server = server.with_notifications(sources);
+    }
+    if args.test_pattern {
+        info!("Streaming a test pattern instead of capturing the screen");
+        let pattern: FrameSourceFactory = Arc::new(|_| Ok(Box::new(TestPatternSource::new(1920, 1080)) as Box<dyn FrameSource>));
+        server = server.with_frame_sources(pattern);
+    }
+    if let Some(secs) = args.idle_timeout {
+        server = server.with_idle_timeout(std::time::Duration::from_secs(secs));
+    }
+    if args.require_approval {
+        if !std::io::stdin().is_terminal() {
+            warn!("--require-approval asks on the console, which isn't a terminal; every client will be turned away");
+        }
+        server = server.with_approval(ApprovalPolicy::Ask {
+            timeout: std::time::Duration::from_secs(args.approval_timeout),
+        });
+        tokio::spawn(approve_on_console(server.control()));
+    }
+    #[cfg(unix)]
+    tokio::spawn(regenerate_on_signal(server.control(), shareable_address(&args.address)));
+    
+    // Startup errors such as a missing display end the process with a non-zero exit
     match args.transport {
         Transport::Tcp => {
             info!("Starting TCP server on {}", args.address);
-            let server = Server::new(access_code);
-            match server.run(&args.address).await {
-                Ok(_) => info!("Server stopped"),
-                Err(e) => error!("Server error: {}", e),
-            }
+            server.run(&args.address).await?;
         }
         Transport::Quic => {
-            error!("QUIC transport is temporarily disabled due to dependency issues");
-            error!("Please use TCP transport for now: --transport tcp");
-            return Err(anyhow::anyhow!("QUIC not available"));
+            warn!("QUIC is experimental: vox_client connects over TCP, and --send-buffer and --recv-buffer don't apply");
+            let addr = args.address.parse()
+                .map_err(|e| anyhow::anyhow!("QUIC needs an IP address and port, not {}: {}", args.address, e))?;
+            info!("Starting QUIC server on {}", addr);
+            server.run_quic(addr).await?;
         }
     }
+    info!("Server stopped");
     
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,65 @@
+use eframe::egui;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use clap::Parser;
+use vox_gui::common::auth::AccessCode;
+use vox_gui::server::frame_source::{FrameSource, FrameSourceFactory, TestPatternSource};
+use vox_gui::server::approval::{ApprovalPolicy, DEFAULT_APPROVAL_TIMEOUT};
+use vox_gui::server::panel::ServerPanel;
+use vox_gui::server::server::Server;
+
+#[derive(Parser)]
+#[command(name = "vox_server_gui")]
+#[command(about = "Remote desktop server with a control panel")]
+struct Args {
+    /// Server address to bind to
+    #[arg(short, long, default_value = "0.0.0.0:8080")]
+    address: String,
+    
+    /// Stream a synthetic moving pattern instead of capturing a display
+    #[arg(long)]
+    test_pattern: bool,
+    
+    /// Let clients that have the access code in only once they are allowed from the panel
+    #[arg(long)]
+    require_approval: bool,
+    
+    /// Seconds a client waits to be allowed in before it is turned away
+    #[arg(long, value_name = "SECONDS", default_value_t = DEFAULT_APPROVAL_TIMEOUT.as_secs())]
+    approval_timeout: u64,
+}
+
+fn main() -> Result<(), eframe::Error> {
+    tracing_subscriber::fmt::init();
+    
+    let args = Args::parse();
+    let runtime = Arc::new(tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime"));
+    
+    let access_code = Arc::new(RwLock::new(Some(AccessCode::generate())));
+    let mut server = Server::new(access_code);
+    if args.test_pattern {
+        let pattern: FrameSourceFactory = Arc::new(|_| Ok(Box::new(TestPatternSource::new(1920, 1080)) as Box<dyn FrameSource>));
+        server = server.with_frame_sources(pattern);
+    }
+    if args.require_approval {
+        server = server.with_approval(ApprovalPolicy::Ask {
+            timeout: Duration::from_secs(args.approval_timeout.max(1)),
+        });
+    }
+    
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title("Vox Remote Desktop Server")
+            .with_inner_size([640.0, 720.0])
+            .with_min_inner_size([480.0, 420.0])
+            .with_icon(egui::IconData::default()),
+        ..Default::default()
+    };
+    
+    eframe::run_native(
+        "Vox Remote Desktop Server",
+        options,
+        Box::new(move |cc| Ok(Box::new(ServerPanel::new(cc, server, args.address, runtime)))),
+    )
+}
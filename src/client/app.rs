@@ -2,17 +2,63 @@ use eframe::egui;
 use egui::{CentralPanel, TopBottomPanel, Context, TextureHandle, ColorImage, Margin};
 use std::sync::{Arc, Mutex};
 use tokio::sync::mpsc;
-use crate::common::protocol::{Message, MouseButton, Modifiers};
+use crate::common::protocol::{DisplayAction, DisplaySettings, ErrorKind, Message, Modifiers};
 use crate::client::connection::Connection;
-use crate::common::quality::{QualityMode, QualityMetrics};
-use crate::common::frame_processor::{FrameProcessor, ProcessedFrame, FrameType};
+use crate::common::delivery::DeliveryMode;
+use crate::common::link::ConnectionLink;
+use crate::common::quality::{ContentProfile, QualityMode, QualityMetrics, QualityChangeReason};
+use crate::common::shortcuts::{SpecialKeyCombo, MENU_VIRTUAL_TERMINALS};
+use crate::common::frame_processor::{ProcessedFrame, FrameType, decompress_tiles, rgb_frame_len};
 use zstd::stream::decode_all;
 use crate::client::h264_decoder::H264Decoder;
+use crate::client::stream_decoder::StreamDecoder;
+use crate::client::toasts::Toasts;
+use crate::client::interpolation::FrameInterpolator;
+use crate::client::mouse_throttle::{MouseMoveThrottle, DEFAULT_MOUSE_RATE_HZ};
+use crate::client::screenshot::{default_screenshot_dir, save_screenshot};
+use crate::client::view::{ClientPreferences, PointerMode, ViewMode, local_position, move_locked_pointer, paint_cursor, paint_region, remote_button, remote_position, remote_touch_phase};
+use crate::client::history::ConnectionHistory;
+use crate::client::security::SecurityInfo;
+use crate::client::modifiers::ModifierTracker;
+use crate::client::reconnect::{ReconnectBudget, DEFAULT_MAX_ATTEMPTS};
+use crate::client::frame_order::FrameOrder;
+use crate::client::refresh::RefreshRequest;
+use crate::client::stream_pause::StreamPause;
+use crate::client::desync::DesyncDetector;
+use crate::client::touch::TouchInput;
+use crate::client::code_entry::CodeEntry;
+use crate::common::auth::CODE_LENGTH;
+use crate::client::region::RegionPicker;
+use crate::common::foveation::Region;
+use crate::client::keymap::{format_key, hid_usage, KeyboardMode};
+use crate::common::h264::{annex_b, is_idr};
+use crate::common::encoder::decode_webp;
+use crate::client::backlog::skip_stale_frames;
+use crate::common::socket::SocketBuffers;
+use crate::common::display_layout::{DisplayLayout, MonitorRect};
+use crate::common::viewport::{ViewportReporter, VIEWPORT_DEBOUNCE};
+
+// How long a status notice (automatic quality change, saved screenshot) stays in the toolbar
+const NOTICE_DURATION: std::time::Duration = std::time::Duration::from_secs(5);
 
 pub struct VoxApp {
     state: AppState,
     access_code: String,
+    // What's wrong with the access code as entered, shown under the field
+    access_code_hint: Option<String>,
     server_address: String,
+    // Servers connected to before, offered next to the address field
+    history: ConnectionHistory,
+    // Ask a loopback server started with --no-encryption to skip encryption
+    disable_encryption: bool,
+    // Ask a server started with --plaintext-video to send video unencrypted
+    plaintext_video: bool,
+    // Server display to stream, for multi-monitor hosts
+    display_index: u32,
+    // Tries to get a dropped connection back before showing an error
+    max_reconnect_attempts: u32,
+    // Kernel buffer sizes for the connection's socket, from the command line
+    socket_buffers: SocketBuffers,
     
     // Connection state
     connection: Option<Arc<Mutex<Connection>>>,
@@ -24,14 +70,76 @@ pub struct VoxApp {
     screen_texture: Option<TextureHandle>,
     screen_size: (u32, u32),
     last_mouse_pos: egui::Pos2,
-    current_frame: Option<Vec<u8>>,
-    frame_processor: Option<FrameProcessor>,
-    h264_decoder: Option<H264Decoder>,
+    // Where to draw the remote cursor, when the server's frames leave it out
+    remote_cursor: Option<(i32, i32)>,
+    // Remote pixels per logical point on the streamed display
+    remote_scale: f32,
+    // Where the remote's monitors sit on its desktop, empty until the server says
+    display_layout: DisplayLayout,
+    // Desktop position of the remote pointer while the local one is locked
+    locked_pointer: Option<egui::Pos2>,
+    // Modifier keys the remote machine has been told are held
+    modifiers: ModifierTracker,
+    mouse_throttle: MouseMoveThrottle,
+    // Fingers and pens on the remote screen
+    touch: TouchInput,
+    // Tells the server how big the screen is shown, once a resize settles
+    viewport: ViewportReporter,
+    // Decoded frame, tile processor and H.264 decoder of the current session
+    stream: StreamDecoder,
+    // Drops frames that arrive after a newer one
+    frame_order: FrameOrder,
+    // SPS/PPS from the server's last CodecConfig, fed to every new decoder
+    h264_config: Option<Vec<u8>>,
+    // The user's last "refresh screen" request, rate limited and awaiting its keyframe
+    refresh: RefreshRequest,
+    // Stops the stream while the window is minimized
+    stream_pause: StreamPause,
+    // Part of the screen kept exact under foveation, and the tool for drawing it
+    lossless_region: Option<Region>,
+    region_picker: RegionPicker,
+    // The host display's settings as last reported by the server
+    host_display: Option<DisplaySettings>,
     
     // Quality control
     current_quality: QualityMode,
+    // What the screen shows, as picked by the user (saved across runs) and as the server runs it
+    content_profile: ContentProfile,
+    applied_profile: Option<ContentProfile>,
+    // Whether the server drops late frames, saved across runs
+    delivery: DeliveryMode,
     quality_metrics: Option<QualityMetrics>,
+    // Frames skipped this connection because decoding fell behind
+    frames_skipped: u64,
+    // Frames that differed from the server's checksum this connection
+    desync: DesyncDetector,
     show_quality_menu: bool,
+    show_connection_info: bool,
+    // Cached from the connection, which is locked while (re)connecting
+    security_info: Option<SecurityInfo>,
+    // Short-lived status message shown in the toolbar
+    toolbar_notice: Option<(String, std::time::Instant)>,
+    // Why the server can't inject our input, shown until dismissed
+    input_denied: Option<String>,
+    // Notifications forwarded from the remote machine
+    toasts: Toasts,
+    
+    // How the remote screen is sized in the window; saved across runs
+    view_mode: ViewMode,
+    // Characters for the local layout, or physical keys for the remote's; saved across runs
+    keyboard_mode: KeyboardMode,
+    // Whether the pointer follows the local one or is locked to reach every monitor; saved across runs
+    pointer_mode: PointerMode,
+    
+    // Where screenshots of the remote screen are written
+    screenshot_dir: String,
+    
+    // Render keyframe tiles as they arrive instead of waiting for the whole frame
+    progressive_rendering: bool,
+    
+    // Blend between frames at low FPS; adds a frame of latency so it's never used in Ultra
+    frame_interpolation: bool,
+    frame_interpolator: FrameInterpolator,
     
     // Runtime handle
     runtime: Arc<tokio::runtime::Runtime>,
@@ -41,6 +149,8 @@ pub struct VoxApp {
 enum AppState {
     Disconnected,
     Connecting,
+    // The connection dropped and is being retried
+    Reconnecting { attempt: u32, max_attempts: u32 },
     Connected,
     Error(String),
 }
@@ -54,7 +164,14 @@ impl Default for VoxApp {
         Self {
             state: AppState::Disconnected,
             access_code: String::new(),
+            access_code_hint: None,
             server_address: "127.0.0.1:8080".to_string(),
+            history: ConnectionHistory::default(),
+            disable_encryption: false,
+            plaintext_video: false,
+            display_index: 0,
+            max_reconnect_attempts: DEFAULT_MAX_ATTEMPTS,
+            socket_buffers: SocketBuffers::default(),
             connection: None,
             tx: None,
             rx: None,
@@ -62,12 +179,42 @@ impl Default for VoxApp {
             screen_texture: None,
             screen_size: (1920, 1080),
             last_mouse_pos: egui::Pos2::ZERO,
-            current_frame: None,
-            frame_processor: None,
-            h264_decoder: None,
+            remote_cursor: None,
+            remote_scale: 1.0,
+            display_layout: DisplayLayout::default(),
+            locked_pointer: None,
+            modifiers: ModifierTracker::new(),
+            mouse_throttle: MouseMoveThrottle::new(DEFAULT_MOUSE_RATE_HZ),
+            touch: TouchInput::new(),
+            viewport: ViewportReporter::new(),
+            stream: StreamDecoder::new(),
+            frame_order: FrameOrder::new(),
+            h264_config: None,
+            refresh: RefreshRequest::new(),
+            stream_pause: StreamPause::new(),
+            lossless_region: None,
+            region_picker: RegionPicker::new(),
+            host_display: None,
             current_quality: QualityMode::High,
+            content_profile: ContentProfile::default(),
+            applied_profile: None,
+            delivery: DeliveryMode::default(),
             quality_metrics: None,
+            frames_skipped: 0,
+            desync: DesyncDetector::new(),
             show_quality_menu: false,
+            show_connection_info: false,
+            security_info: None,
+            toolbar_notice: None,
+            input_denied: None,
+            toasts: Toasts::new(),
+            view_mode: ViewMode::default(),
+            keyboard_mode: KeyboardMode::default(),
+            pointer_mode: PointerMode::default(),
+            screenshot_dir: default_screenshot_dir().display().to_string(),
+            progressive_rendering: false,
+            frame_interpolation: false,
+            frame_interpolator: FrameInterpolator::new(QualityMode::High.target_fps()),
             runtime,
         }
     }
@@ -77,7 +224,52 @@ impl VoxApp {
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
         // Apply custom theme
         super::theme::apply_custom_theme(&cc.egui_ctx);
-        Self::default()
+        
+        let preferences = ClientPreferences::load();
+        let history = ConnectionHistory::load(cc.storage);
+        let defaults = Self::default();
+        Self {
+            view_mode: preferences.view_mode,
+            keyboard_mode: preferences.keyboard_mode,
+            pointer_mode: preferences.pointer_mode,
+            content_profile: preferences.content_profile,
+            delivery: preferences.delivery,
+            server_address: history.most_recent().map_or(defaults.server_address.clone(), str::to_string),
+            history,
+            ..defaults
+        }
+    }
+    
+    /// Fill in the address and access code from a connection link, e.g. one passed on
+    /// the command line.
+    pub fn with_link(mut self, link: ConnectionLink) -> Self {
+        self.apply_link(link);
+        self
+    }
+    
+    /// Tune the socket's kernel buffers for a link with a high bandwidth-delay product.
+    pub fn with_socket_buffers(mut self, buffers: SocketBuffers) -> Self {
+        self.socket_buffers = buffers;
+        self
+    }
+    
+    fn apply_link(&mut self, link: ConnectionLink) {
+        self.server_address = link.address;
+        self.access_code = link.code;
+        self.access_code_hint = None;
+    }
+    
+    fn save_preferences(&self) {
+        let preferences = ClientPreferences {
+            view_mode: self.view_mode,
+            keyboard_mode: self.keyboard_mode,
+            pointer_mode: self.pointer_mode,
+            content_profile: self.content_profile,
+            delivery: self.delivery,
+        };
+        if let Err(e) = preferences.save() {
+            tracing::warn!("Failed to save preferences: {:#}", e);
+        }
     }
     
     fn show_connection_ui(&mut self, ctx: &Context) {
@@ -125,8 +317,17 @@ impl VoxApp {
                             );
                             
                             if response.changed() {
-                                self.access_code.retain(|c| c.is_ascii_digit());
-                                self.access_code.truncate(6);
+                                let entry = CodeEntry::parse(&self.access_code);
+                                self.access_code_hint = entry.hint();
+                                self.access_code = entry.digits;
+                            }
+                            if let Some(hint) = &self.access_code_hint {
+                                ui.add_space(6.0);
+                                ui.label(
+                                    egui::RichText::new(hint)
+                                        .size(12.0)
+                                        .color(egui::Color32::from_rgb(255, 196, 87))
+                                );
                             }
                             
                             ui.add_space(20.0);
@@ -137,18 +338,87 @@ impl VoxApp {
                                     egui::RichText::new("Server:")
                                         .color(egui::Color32::from_rgb(150, 160, 170))
                                 );
-                                ui.add(
+                                let response = ui.add(
                                     egui::TextEdit::singleline(&mut self.server_address)
                                         .desired_width(180.0)
+                                        .hint_text("host:port or vox:// link")
                                         .margin(egui::Vec2::new(8.0, 4.0))
                                 );
+                                // A pasted link fills in both fields
+                                if response.changed() {
+                                    if let Ok(link) = ConnectionLink::parse(&self.server_address) {
+                                        self.apply_link(link);
+                                    }
+                                }
+                                
+                                if !self.history.is_empty() {
+                                    ui.menu_button("⏷", |ui| {
+                                        let mut picked = None;
+                                        for address in self.history.addresses() {
+                                            if ui.selectable_label(*address == self.server_address, address).clicked() {
+                                                picked = Some(address.clone());
+                                            }
+                                        }
+                                        if let Some(address) = picked {
+                                            self.server_address = address;
+                                            ui.close_menu();
+                                        }
+                                        
+                                        ui.separator();
+                                        if ui.button("Clear history").clicked() {
+                                            self.history.clear();
+                                            ui.close_menu();
+                                        }
+                                    })
+                                    .response
+                                    .on_hover_text("Recent servers");
+                                }
+                            });
+                            
+                            if ConnectionLink::looks_like_link(&self.server_address) {
+                                if let Err(e) = ConnectionLink::parse(&self.server_address) {
+                                    ui.colored_label(egui::Color32::from_rgb(255, 88, 88), format!("Invalid link: {:#}", e));
+                                }
+                            }
+                            
+                            ui.add_space(8.0);
+                            ui.horizontal(|ui| {
+                                ui.label(
+                                    egui::RichText::new("Display:")
+                                        .color(egui::Color32::from_rgb(150, 160, 170))
+                                );
+                                ui.add(egui::DragValue::new(&mut self.display_index).range(0..=15))
+                                    .on_hover_text("Which of the server's monitors to show, 0 being the first");
+                            });
+                            
+                            ui.add_space(8.0);
+                            ui.horizontal(|ui| {
+                                ui.label(
+                                    egui::RichText::new("Reconnect attempts:")
+                                        .color(egui::Color32::from_rgb(150, 160, 170))
+                                );
+                                ui.add(egui::DragValue::new(&mut self.max_reconnect_attempts).range(0..=50))
+                                    .on_hover_text("How often to retry a dropped connection before giving up");
                             });
                             
+                            // Benchmarking aid, only offered for servers on this machine
+                            let loopback = self.server_address.parse::<std::net::SocketAddr>()
+                                .map_or(false, |addr| addr.ip().is_loopback());
+                            if loopback {
+                                ui.add_space(8.0);
+                                ui.checkbox(&mut self.disable_encryption, "Disable encryption (local benchmarking)")
+                                    .on_hover_text("Needs a server started with --no-encryption");
+                            } else {
+                                self.disable_encryption = false;
+                            }
+                            ui.checkbox(&mut self.plaintext_video, "Unencrypted video (trusted network)")
+                                .on_hover_text("Saves decrypting every frame over a VPN or WireGuard tunnel; needs a server started with --plaintext-video. Input stays encrypted.");
+                            
                             ui.add_space(25.0);
                             
                             // Connect button with custom styling
-                            let connect_enabled = self.access_code.len() == 6 && 
-                                                self.state != AppState::Connecting;
+                            let connect_enabled = self.access_code.len() == CODE_LENGTH && 
+                                                !self.is_connecting();
                             
                             let button = egui::Button::new(
                                 egui::RichText::new("Connect").size(16.0)
@@ -180,7 +450,17 @@ impl VoxApp {
                             );
                         });
                     }
+                    AppState::Reconnecting { attempt, max_attempts } => {
+                        ui.horizontal(|ui| {
+                            ui.spinner();
+                            ui.label(
+                                egui::RichText::new(format!("Reconnecting (attempt {}/{})...", attempt, max_attempts))
+                                    .color(egui::Color32::from_rgb(88, 166, 255))
+                            );
+                        });
+                    }
                     AppState::Error(msg) => {
+                        let mut retry = false;
                         egui::Frame::none()
                             .fill(egui::Color32::from_rgb(255, 88, 88).linear_multiply(0.2))
                             .rounding(egui::Rounding::same(4.0))
@@ -190,7 +470,13 @@ impl VoxApp {
                                     egui::Color32::from_rgb(255, 88, 88),
                                     format!("⚠ {}", msg)
                                 );
+                                if self.access_code.len() == CODE_LENGTH {
+                                    retry = ui.button("Retry").clicked();
+                                }
                             });
+                        if retry {
+                            self.connect();
+                        }
                     }
                     _ => {}
                 }
@@ -228,125 +514,536 @@ impl VoxApp {
                         
                         ui.separator();
                         
+                        if ui.button("🔒 Connection info").clicked() {
+                            self.show_connection_info = !self.show_connection_info;
+                        }
+                        
                         // Quality selector
                         let quality_text = format!("Quality: {:?}", self.current_quality);
                         if ui.button(quality_text).clicked() {
                             self.show_quality_menu = !self.show_quality_menu;
                         }
                         
+                        ui.menu_button(format!("View: {}", self.view_mode.label()), |ui| {
+                            for mode in ViewMode::ALL {
+                                if ui.radio_value(&mut self.view_mode, mode, mode.label()).clicked() {
+                                    self.save_preferences();
+                                    ui.close_menu();
+                                }
+                            }
+                        });
+                        
+                        ui.menu_button("Keyboard", |ui| {
+                            for mode in KeyboardMode::ALL {
+                                if ui.radio_value(&mut self.keyboard_mode, mode, mode.label()).clicked() {
+                                    self.save_preferences();
+                                    ui.close_menu();
+                                }
+                            }
+                        })
+                        .response
+                        .on_hover_text("Send scancodes when the two machines use different keyboard layouts");
+                        
+                        ui.menu_button("Pointer", |ui| {
+                            for mode in PointerMode::ALL {
+                                if ui.radio_value(&mut self.pointer_mode, mode, mode.label()).clicked() {
+                                    self.save_preferences();
+                                    ui.close_menu();
+                                }
+                            }
+                        })
+                        .response
+                        .on_hover_text("A locked pointer reaches every remote monitor; click the screen to lock it, Shift+F12 releases it");
+                        
+                        // Shortcuts the local OS would intercept before we see them
+                        ui.menu_button("Send keys", |ui| {
+                            for combo in SpecialKeyCombo::ALL {
+                                let mut button = ui.button(combo.label());
+                                if combo.requires_elevation() {
+                                    button = button.on_hover_text("Needs the server to run as a Windows service");
+                                }
+                                if button.clicked() {
+                                    for event in combo.key_events() {
+                                        self.send_message(event);
+                                    }
+                                    ui.close_menu();
+                                }
+                            }
+                            
+                            ui.menu_button("Switch terminal (Linux)", |ui| {
+                                for terminal in MENU_VIRTUAL_TERMINALS {
+                                    let combo = SpecialKeyCombo::SwitchVirtualTerminal(terminal);
+                                    let button = ui.button(combo.label()).on_hover_text(
+                                        "The stream stops while the host shows a text console; may need console access on the host"
+                                    );
+                                    if button.clicked() {
+                                        for event in combo.key_events() {
+                                            self.send_message(event);
+                                        }
+                                        ui.close_menu();
+                                    }
+                                }
+                            });
+                        });
+                        
+                        ui.menu_button("Lossless region", |ui| {
+                            if self.region_picker.is_armed() {
+                                if ui.button("Cancel drawing").clicked() {
+                                    self.region_picker.cancel();
+                                    ui.close_menu();
+                                }
+                            } else if ui.button("Draw region").on_hover_text("Drag over the screen to mark it").clicked() {
+                                self.region_picker.arm();
+                                ui.close_menu();
+                            }
+                            if ui.add_enabled(self.lossless_region.is_some(), egui::Button::new("Clear")).clicked() {
+                                self.set_lossless_region(None);
+                                ui.close_menu();
+                            }
+                        })
+                        .response
+                        .on_hover_text("Keep part of the screen, such as an editor, exact while foveation reduces the rest");
+                        
+                        ui.menu_button("Host display", |ui| {
+                            if ui.button("Check settings").clicked() {
+                                self.send_message(Message::DisplayControl { action: DisplayAction::Query });
+                                ui.close_menu();
+                            }
+                            ui.menu_button("Brightness", |ui| {
+                                for percent in [25, 50, 75, 100] {
+                                    if ui.button(format!("{}%", percent)).clicked() {
+                                        let level = percent as f32 / 100.0;
+                                        self.send_message(Message::DisplayControl { action: DisplayAction::SetBrightness { level } });
+                                        ui.close_menu();
+                                    }
+                                }
+                            });
+                            let modes = self.host_display.as_ref().map(|d| d.modes.clone()).unwrap_or_default();
+                            ui.add_enabled_ui(!modes.is_empty(), |ui| {
+                                ui.menu_button("Resolution", |ui| {
+                                    for (width, height) in modes {
+                                        if ui.button(format!("{}×{}", width, height)).clicked() {
+                                            self.send_message(Message::DisplayControl { action: DisplayAction::SetResolution { width, height } });
+                                            ui.close_menu();
+                                        }
+                                    }
+                                })
+                                .response
+                                .on_disabled_hover_text("Check settings first to list the host's resolutions");
+                            });
+                            if ui.button("Sleep").clicked() {
+                                self.send_message(Message::DisplayControl { action: DisplayAction::Sleep });
+                                ui.close_menu();
+                            }
+                            if ui.button("Wake").clicked() {
+                                self.send_message(Message::DisplayControl { action: DisplayAction::Wake });
+                                ui.close_menu();
+                            }
+                        })
+                        .response
+                        .on_hover_text("Needs an access code with the display-control permission; Linux hosts running X11 only");
+                        
                         // Show quality metrics if available
                         if let Some(metrics) = &self.quality_metrics {
                             ui.separator();
                             ui.label(format!("{:.1} Mbps", metrics.bandwidth_mbps));
                             ui.label(format!("{:.0}ms", metrics.average_rtt.as_millis()));
                         }
+                        if self.frames_skipped > 0 {
+                            ui.label(format!("{} skipped", self.frames_skipped))
+                                .on_hover_text("Frames skipped to stay current because this computer couldn't decode them fast enough");
+                        }
+                        if self.desync.is_desynced() {
+                            ui.label(egui::RichText::new(format!("{} desynced", self.desync.mismatches())).color(egui::Color32::from_rgb(255, 120, 100)))
+                                .on_hover_text(format!(
+                                    "{} of {} frames checked differ from the server's, the first was frame {}; see the log",
+                                    self.desync.mismatches(),
+                                    self.desync.checked(),
+                                    self.desync.first_mismatch().unwrap_or_default(),
+                                ));
+                        }
+                        
+                        if ui.button("Screenshot").on_hover_text("Save the remote screen as PNG (F12)").clicked() {
+                            self.take_screenshot();
+                        }
+                        
+                        let now = std::time::Instant::now();
+                        if self.refresh.is_waiting(now) {
+                            ui.spinner();
+                            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+                        } else if ui.add_enabled(self.refresh.can_request(now), egui::Button::new("⟳ Refresh"))
+                            .on_hover_text("Ask the server for a complete frame, clearing stale or garbled areas (Ctrl+F12)")
+                            .clicked()
+                        {
+                            self.request_refresh();
+                        }
+                        
+                        // Status notices (quality changes, screenshots) for a few seconds
+                        if let Some((notice, shown_at)) = &self.toolbar_notice {
+                            if shown_at.elapsed() < NOTICE_DURATION {
+                                ui.separator();
+                                ui.label(
+                                    egui::RichText::new(notice)
+                                        .color(egui::Color32::from_rgb(255, 196, 87))
+                                );
+                            } else {
+                                self.toolbar_notice = None;
+                            }
+                        }
                     });
                 });
             });
         
+        // Mouse and keyboard do nothing until the host grants the permission, so say why
+        let mut dismissed = false;
+        if let Some(message) = &self.input_denied {
+            TopBottomPanel::top("input_denied_banner")
+                .frame(egui::Frame::none()
+                    .fill(egui::Color32::from_rgb(120, 36, 36))
+                    .inner_margin(Margin::symmetric(16.0, 10.0)))
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(
+                            egui::RichText::new("⚠ Remote input is blocked")
+                                .strong()
+                                .color(egui::Color32::WHITE)
+                        );
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            dismissed = ui.button("Dismiss").clicked();
+                        });
+                    });
+                    ui.label(egui::RichText::new(message).color(egui::Color32::from_rgb(255, 220, 220)));
+                });
+        }
+        if dismissed {
+            self.input_denied = None;
+        }
+        
         CentralPanel::default().show(ctx, |ui| {
-            let available_size = ui.available_size();
+            // Send a move parked by the rate limit, even if the pointer has left the image
+            let now = std::time::Instant::now();
+            if let Some((x, y)) = self.mouse_throttle.poll(now) {
+                self.send_message(Message::MouseMove { x, y });
+            }
             
-            // Calculate scaled image dimensions and position
-            let image_size = egui::Vec2::new(
-                self.screen_size.0 as f32,
-                self.screen_size.1 as f32,
-            );
-            let scale = (available_size.x / image_size.x).min(available_size.y / image_size.y);
-            let scaled_size = image_size * scale;
+            // Frames larger than the window would be shown scaled down anyway
+            let viewport = self.view_mode.viewport(ui.available_size(), ctx.pixels_per_point());
+            if let Some((width, height)) = self.viewport.update(viewport, now) {
+                self.send_message(Message::ViewportResize { width, height });
+                // Kept for reconnects, which start a session that sends full size frames
+                if let Some(mut connection) = self.connection.as_ref().and_then(|c| c.try_lock().ok()) {
+                    connection.set_viewport(viewport);
+                }
+            }
+            if self.viewport.is_pending() {
+                ctx.request_repaint_after(VIEWPORT_DEBOUNCE);
+            }
             
-            // Calculate the centered image rect
-            let image_rect = egui::Rect::from_center_size(
-                ui.available_rect_before_wrap().center(),
-                scaled_size,
-            );
+            // Display the remote screen, remembering where it landed and which part is visible
+            let placement = if let Some(texture) = &self.screen_texture {
+                let image_size = egui::Vec2::new(
+                    self.screen_size.0 as f32,
+                    self.screen_size.1 as f32,
+                );
+                let scale = self.view_mode.scale(image_size, ui.available_size(), ctx.pixels_per_point());
+                let image = egui::Image::new((texture.id(), image_size * scale))
+                    .fit_to_exact_size(image_size * scale);
+                
+                if self.view_mode == ViewMode::ActualSize {
+                    let output = egui::ScrollArea::both()
+                        .drag_to_scroll(false)
+                        .show(ui, |ui| ui.add(image).rect);
+                    Some((output.inner, output.inner_rect))
+                } else {
+                    // Centered; in Fill mode the overflow is clipped by the panel
+                    let image_rect = egui::Rect::from_center_size(
+                        ui.available_rect_before_wrap().center(),
+                        image_size * scale,
+                    );
+                    image.paint_at(ui, image_rect);
+                    Some((image_rect, ui.clip_rect()))
+                }
+            } else {
+                ui.centered_and_justified(|ui| {
+                    ui.label("Waiting for screen data...");
+                });
+                None
+            };
             
-            // Handle mouse input
-            if let Some(hover_pos) = ui.input(|i| i.pointer.hover_pos()) {
-                if image_rect.contains(hover_pos) {
-                    // Convert UI coordinates to screen coordinates relative to the actual image
-                    let relative_x = ((hover_pos.x - image_rect.left()) / image_rect.width()).clamp(0.0, 1.0);
-                    let relative_y = ((hover_pos.y - image_rect.top()) / image_rect.height()).clamp(0.0, 1.0);
-                    
-                    let screen_x = (relative_x * self.screen_size.0 as f32).round() as i32;
-                    let screen_y = (relative_y * self.screen_size.1 as f32).round() as i32;
-                    
+            // Draw the remote cursor in place of the local one, which would only lag ahead of it
+            if let (Some(cursor), Some((image_rect, visible))) = (self.remote_cursor, placement) {
+                let tip = local_position(cursor, image_rect, self.screen_size);
+                let scale = self.remote_scale * image_rect.width() / self.screen_size.0.max(1) as f32;
+                paint_cursor(&ui.painter().with_clip_rect(visible), tip, scale);
+            }
+            
+            if let (Some(region), Some((image_rect, visible))) = (self.lossless_region, placement) {
+                paint_region(&ui.painter().with_clip_rect(visible), region, image_rect, self.screen_size);
+            }
+            
+            // Handle mouse input over the visible part of the image
+            let hover_pos = ui.input(|i| i.pointer.hover_pos())
+                .filter(|pos| placement.map_or(false, |(_, visible)| visible.contains(*pos)));
+            if hover_pos.is_some() && self.remote_cursor.is_some() {
+                ctx.set_cursor_icon(egui::CursorIcon::None);
+            }
+            let touches: Vec<(u64, egui::TouchPhase, egui::Pos2, Option<f32>)> = ui.input(|i| {
+                i.events.iter()
+                    .filter_map(|event| match event {
+                        egui::Event::Touch { id, phase, pos, force, .. } => Some((id.0, *phase, *pos, *force)),
+                        _ => None,
+                    })
+                    .collect()
+            });
+            let touching = !touches.is_empty() || self.touch.is_touching();
+            if let (true, Some((image_rect, visible))) = (self.region_picker.is_armed(), placement) {
+                // Drags mark the lossless region instead of reaching the remote machine
+                self.pick_region(ui, image_rect, visible);
+            } else if let (Some(pointer), Some((image_rect, _))) = (self.locked_pointer, placement) {
+                self.drive_locked_pointer(ctx, pointer, image_rect, now);
+            } else if let (true, Some((image_rect, visible))) = (touching, placement) {
+                // egui also reports the first finger as the pointer; touches go on their own
+                // so that a pinch doesn't drag or click as well
+                self.mouse_throttle.cancel_pending();
+                for (id, phase, pos, pressure) in touches {
+                    if phase == egui::TouchPhase::Start && !visible.contains(pos) {
+                        continue;
+                    }
+                    let Some(at) = remote_position(image_rect.clamp(pos), image_rect, self.screen_size) else {
+                        continue;
+                    };
+                    for message in self.touch.event(id, remote_touch_phase(phase), at, pressure) {
+                        self.send_message(message);
+                    }
+                }
+            } else if let (Some(hover_pos), Some((image_rect, _))) = (hover_pos, placement) {
+                if let Some((screen_x, screen_y)) = remote_position(hover_pos, image_rect, self.screen_size) {
                     // Send mouse move if position changed significantly
                     let new_pos = egui::Pos2::new(screen_x as f32, screen_y as f32);
                     if (new_pos - self.last_mouse_pos).length() > 1.0 {
                         self.last_mouse_pos = new_pos;
-                        self.send_message(Message::MouseMove { x: screen_x, y: screen_y });
+                        if let Some((x, y)) = self.mouse_throttle.move_to(screen_x, screen_y, now) {
+                            self.send_message(Message::MouseMove { x, y });
+                        }
                     }
                     
-                    // Handle mouse clicks
-                    ui.input(|i| {
-                        if i.pointer.primary_pressed() {
-                            self.send_message(Message::MouseClick {
-                                button: MouseButton::Left,
-                                pressed: true,
-                                x: screen_x,
-                                y: screen_y,
-                            });
-                        }
-                        if i.pointer.primary_released() {
-                            self.send_message(Message::MouseClick {
-                                button: MouseButton::Left,
-                                pressed: false,
-                                x: screen_x,
-                                y: screen_y,
-                            });
-                        }
-                        if i.pointer.secondary_pressed() {
-                            self.send_message(Message::MouseClick {
-                                button: MouseButton::Right,
-                                pressed: true,
-                                x: screen_x,
-                                y: screen_y,
-                            });
-                        }
-                        if i.pointer.secondary_released() {
+                    // Clicks carry their own position, so a parked move would only drag the pointer back
+                    if ui.input(|i| i.pointer.any_pressed() || i.pointer.any_released()) {
+                        self.mouse_throttle.cancel_pending();
+                    }
+                    
+                    // Handle mouse clicks, including middle and the back/forward side buttons
+                    let clicks: Vec<(egui::PointerButton, bool)> = ui.input(|i| {
+                        i.events.iter()
+                            .filter_map(|event| match event {
+                                egui::Event::PointerButton { button, pressed, .. } => Some((*button, *pressed)),
+                                _ => None,
+                            })
+                            .collect()
+                    });
+                    // In locked mode a click on the screen locks the pointer rather than clicking
+                    let locks = self.pointer_mode == PointerMode::Locked
+                        && clicks.contains(&(egui::PointerButton::Primary, true));
+                    if locks {
+                        let (x, y) = self.input_layout().to_desktop(
+                            self.display_index as usize,
+                            (screen_x, screen_y),
+                            self.remote_scale as f64,
+                        );
+                        self.lock_pointer(ctx, egui::Pos2::new(x as f32, y as f32));
+                    } else {
+                        for (button, pressed) in clicks {
                             self.send_message(Message::MouseClick {
-                                button: MouseButton::Right,
-                                pressed: false,
+                                button: remote_button(button),
+                                pressed,
                                 x: screen_x,
                                 y: screen_y,
                             });
                         }
-                    });
+                    }
+                }
+            }
+            
+            // Switching to another window must not leave the pointer captured or a finger down
+            if ctx.input(|i| i.events.iter().any(|event| matches!(event, egui::Event::WindowFocused(false)))) {
+                self.release_pointer(ctx);
+                for message in self.touch.release_all() {
+                    self.send_message(message);
+                }
+            }
+            
+            // F12 is never forwarded to the remote machine, so it's free for local hotkeys
+            if ctx.input(|i| i.key_pressed(egui::Key::F12)) {
+                if self.locked_pointer.is_some() && ctx.input(|i| i.modifiers.shift) {
+                    self.release_pointer(ctx);
+                } else if ctx.input(|i| i.modifiers.command) {
+                    self.request_refresh();
+                } else {
+                    self.take_screenshot();
                 }
             }
             
             // Handle keyboard input
-            ctx.input(|i| {
+            let modifier_tracker = &mut self.modifiers;
+            let keyboard_mode = self.keyboard_mode;
+            let key_messages = ctx.input(|i| {
+                let mut messages = Vec::new();
                 for event in &i.events {
-                    if let egui::Event::Key { key, physical_key: _, pressed, repeat: _, modifiers } = event {
-                        if let Some(key_str) = format_key(*key) {
-                            self.send_message(Message::KeyEvent {
+                    if let egui::Event::WindowFocused(false) = event {
+                        messages.extend(modifier_tracker.release_all());
+                    }
+                    if let egui::Event::Key { key, physical_key, pressed, repeat, modifiers } = event {
+                        // Modifiers first, so a held Shift applies to this key on the remote side
+                        messages.extend(modifier_tracker.update(*modifiers));
+                        let remote_modifiers = Modifiers {
+                            shift: modifiers.shift,
+                            ctrl: modifiers.ctrl || modifiers.command,
+                            alt: modifiers.alt,
+                            meta: modifiers.command,
+                        };
+                        // Keys without a known position still go as characters; F12 stays local
+                        if *key == egui::Key::F12 {
+                            continue;
+                        }
+                        let usage = physical_key
+                            .filter(|_| keyboard_mode == KeyboardMode::Scancodes)
+                            .and_then(hid_usage);
+                        // Auto-repeats of a held key go too, marked so the server can
+                        // leave repeating to the remote system where it does its own
+                        if let Some(usage) = usage {
+                            messages.push(Message::PhysicalKeyEvent {
+                                usage,
+                                pressed: *pressed,
+                                repeat: *repeat,
+                                modifiers: remote_modifiers,
+                            });
+                        } else if let Some(key_str) = format_key(*key) {
+                            messages.push(Message::KeyEvent {
                                 key: key_str,
                                 pressed: *pressed,
-                                modifiers: Modifiers {
-                                    shift: modifiers.shift,
-                                    ctrl: modifiers.ctrl || modifiers.command,
-                                    alt: modifiers.alt,
-                                    meta: modifiers.command,
-                                },
+                                repeat: *repeat,
+                                modifiers: remote_modifiers,
                             });
                         }
                     }
                 }
+                
+                // Modifiers pressed or released on their own don't produce a key event
+                if i.focused {
+                    messages.extend(modifier_tracker.update(i.modifiers));
+                }
+                messages
             });
-            
-            // Display the remote screen
-            if let Some(texture) = &self.screen_texture {
-                ui.centered_and_justified(|ui| {
-                    ui.image((texture.id(), scaled_size));
-                });
-            } else {
-                ui.centered_and_justified(|ui| {
-                    ui.label("Waiting for screen data...");
-                });
+            for message in key_messages {
+                self.send_message(message);
+            }
+        });
+    }
+    
+    /// Move the remote pointer by the raw motion of the captured local one. Positions are
+    /// sent relative to the viewed display and may lie on any other monitor.
+    fn drive_locked_pointer(&mut self, ctx: &Context, pointer: egui::Pos2, image_rect: egui::Rect, now: std::time::Instant) {
+        let (motion, clicks) = ctx.input(|i| {
+            let mut motion = egui::Vec2::ZERO;
+            let mut clicks = Vec::new();
+            for event in &i.events {
+                match event {
+                    egui::Event::MouseMoved(delta) => motion += *delta,
+                    egui::Event::PointerButton { button, pressed, .. } => clicks.push((*button, *pressed)),
+                    _ => {}
+                }
             }
+            (motion, clicks)
         });
+        
+        // Raw motion is in physical pixels, which cover fewer remote pixels when the image is enlarged
+        let scale = if self.remote_scale > 0.0 { self.remote_scale } else { 1.0 };
+        let desktop_per_pixel = self.screen_size.0 as f32 / (image_rect.width() * ctx.pixels_per_point()) / scale;
+        let layout = self.input_layout();
+        let pointer = move_locked_pointer(pointer, motion * desktop_per_pixel, &layout);
+        self.locked_pointer = Some(pointer);
+        
+        let desktop = (pointer.x.round() as i32, pointer.y.round() as i32);
+        let (x, y) = layout.to_display(self.display_index as usize, desktop, scale as f64);
+        if motion != egui::Vec2::ZERO {
+            if let Some((x, y)) = self.mouse_throttle.move_to(x, y, now) {
+                self.send_message(Message::MouseMove { x, y });
+            }
+        }
+        if !clicks.is_empty() {
+            self.mouse_throttle.cancel_pending();
+        }
+        for (button, pressed) in clicks {
+            self.send_message(Message::MouseClick { button: remote_button(button), pressed, x, y });
+        }
+    }
+    
+    /// The layout pointer positions are mapped through. A server that sent none is taken
+    /// to have only the viewed display.
+    fn input_layout(&self) -> DisplayLayout {
+        if !self.display_layout.monitors.is_empty() {
+            return self.display_layout.clone();
+        }
+        let scale = if self.remote_scale > 0.0 { self.remote_scale } else { 1.0 };
+        DisplayLayout::new(vec![MonitorRect::new(
+            0,
+            0,
+            (self.screen_size.0 as f32 / scale) as u32,
+            (self.screen_size.1 as f32 / scale) as u32,
+        )])
+    }
+    
+    fn lock_pointer(&mut self, ctx: &Context, at: egui::Pos2) {
+        self.locked_pointer = Some(at);
+        ctx.send_viewport_cmd(egui::ViewportCommand::CursorGrab(egui::CursorGrab::Locked));
+        ctx.send_viewport_cmd(egui::ViewportCommand::CursorVisible(false));
+        self.toolbar_notice = Some(("Pointer locked, Shift+F12 releases it".to_string(), std::time::Instant::now()));
+    }
+    
+    fn release_pointer(&mut self, ctx: &Context) {
+        if self.locked_pointer.take().is_some() {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CursorGrab(egui::CursorGrab::None));
+            ctx.send_viewport_cmd(egui::ViewportCommand::CursorVisible(true));
+        }
+    }
+    
+    fn show_connection_info_window(&mut self, ctx: &Context) {
+        if let Some(info) = self.connection.as_ref()
+            .and_then(|connection| connection.try_lock().ok())
+            .and_then(|connection| connection.security_info())
+        {
+            self.security_info = Some(info);
+        }
+        
+        let mut open = true;
+        egui::Window::new("Connection Info")
+            .open(&mut open)
+            .collapsible(false)
+            .auto_sized()
+            .show(ctx, |ui| {
+                let Some(info) = &self.security_info else {
+                    ui.label("Waiting for the connection...");
+                    return;
+                };
+                egui::Grid::new("connection_info_grid")
+                    .num_columns(2)
+                    .spacing([16.0, 6.0])
+                    .show(ui, |ui| {
+                        for (label, value) in info.rows() {
+                            ui.label(label);
+                            ui.label(egui::RichText::new(value).monospace());
+                            ui.end_row();
+                        }
+                    });
+                if !info.encrypted {
+                    ui.separator();
+                    ui.colored_label(
+                        egui::Color32::from_rgb(255, 88, 88),
+                        "This session is not encrypted",
+                    );
+                }
+            });
+        self.show_connection_info = open;
     }
     
     fn connect(&mut self) {
@@ -355,7 +1052,13 @@ impl VoxApp {
         let addr = self.server_address.clone();
         let code = self.access_code.clone();
         
-        let (connection, _, _) = Connection::new();
+        let (mut connection, _, _) = Connection::new();
+        connection.set_encryption(!self.disable_encryption);
+        connection.set_plaintext_video(self.plaintext_video);
+        connection.set_display(self.display_index);
+        connection.set_socket_buffers(self.socket_buffers);
+        connection.set_content_profile(self.content_profile);
+        connection.set_delivery(self.delivery);
         let connection = Arc::new(Mutex::new(connection));
         self.connection = Some(connection.clone());
         
@@ -370,59 +1073,214 @@ impl VoxApp {
         let (conn_tx, mut conn_rx) = mpsc::unbounded_channel::<Message>();
         self.tx = Some(conn_tx);
         
+        // Queued until the connection is up, then forwarded with other messages
+        if self.progressive_rendering {
+            self.send_message(Message::SetProgressiveRendering { enabled: true });
+        }
+        
         // Spawn connection task
         let runtime = self.runtime.clone();
+        let mut budget = ReconnectBudget::new(self.max_reconnect_attempts);
         
         std::thread::spawn(move || {
             runtime.block_on(async move {
-                let mut conn = connection.lock().unwrap();
-                match conn.connect(&addr, &code).await {
-                    Ok((mut rx, tx)) => {
-                        tracing::info!("Connected successfully");
-                        state_tx.send(AppState::Connected).ok();
-                        
-                        // Drop the connection lock
-                        drop(conn);
-                        
-                        // Spawn task to forward outgoing messages
-                        let tx_clone = tx.clone();
-                        tokio::spawn(async move {
-                            while let Some(msg) = conn_rx.recv().await {
-                                if tx_clone.send(msg).is_err() {
-                                    break;
+                let mut reconnecting = false;
+                loop {
+                    let result = connection.lock().unwrap().connect(&addr, &code).await;
+                    match result {
+                        Ok((mut rx, tx)) => {
+                            tracing::info!("Connected successfully");
+                            state_tx.send(AppState::Connected).ok();
+                            reconnecting = true;
+                            budget.reset();
+                            
+                            // Forward both ways until the connection drops or either end closes the session
+                            let closed = loop {
+                                tokio::select! {
+                                    incoming = rx.recv() => match incoming {
+                                        Some(msg) => {
+                                            // A server that says goodbye (e.g. idle timeout) isn't reconnected to
+                                            let goodbye = matches!(msg, Message::Disconnect);
+                                            if msg_tx.send(msg).is_err() || goodbye {
+                                                break true;
+                                            }
+                                        }
+                                        None => break false,
+                                    },
+                                    outgoing = conn_rx.recv() => match outgoing {
+                                        Some(msg) => {
+                                            let _ = tx.send(msg);
+                                        }
+                                        None => break true,
+                                    },
                                 }
-                            }
-                        });
-                        
-                        // Forward incoming messages
-                        while let Some(msg) = rx.recv().await {
-                            if msg_tx.send(msg).is_err() {
+                            };
+                            
+                            // Dropping the sender lets the connection keep undelivered input for the next attempt
+                            drop(tx);
+                            if closed {
                                 break;
                             }
+                            tracing::warn!("Connection lost, reconnecting");
                         }
-                        
-                        state_tx.send(AppState::Disconnected).ok();
+                        Err(e) if !reconnecting => {
+                            tracing::error!("Connection failed: {}", e);
+                            state_tx.send(AppState::Error(format!("Connection failed: {}", e))).ok();
+                            break;
+                        }
+                        Err(e) => tracing::warn!("Reconnect failed: {}", e),
                     }
-                    Err(e) => {
-                        tracing::error!("Connection failed: {}", e);
-                        state_tx.send(AppState::Error(format!("Connection failed: {}", e))).ok();
+                    
+                    // The app drops its receivers when the user disconnects
+                    if msg_tx.is_closed() {
+                        break;
+                    }
+                    let Some(attempt) = budget.next_attempt() else {
+                        tracing::error!("Giving up after {} reconnect attempts", budget.max_attempts());
+                        let message = format!("Connection lost, gave up after {} reconnect attempts", budget.max_attempts());
+                        state_tx.send(AppState::Error(message)).ok();
+                        break;
+                    };
+                    let state = AppState::Reconnecting { attempt: attempt.number, max_attempts: budget.max_attempts() };
+                    if state_tx.send(state).is_err() {
+                        break;
                     }
+                    tokio::time::sleep(attempt.delay).await;
                 }
             });
         });
     }
     
+    fn is_connecting(&self) -> bool {
+        matches!(self.state, AppState::Connecting | AppState::Reconnecting { .. })
+    }
+    
     fn disconnect(&mut self) {
         if let Some(tx) = &self.tx {
             let _ = tx.send(Message::Disconnect);
         }
         self.state = AppState::Disconnected;
         self.connection = None;
+        self.modifiers = ModifierTracker::new();
+        self.viewport.reset();
+        self.remote_cursor = None;
+        self.remote_scale = 1.0;
+        self.display_layout = DisplayLayout::default();
+        self.applied_profile = None;
+        self.lossless_region = None;
+        self.region_picker.cancel();
+        self.host_display = None;
+        self.h264_config = None;
+        self.security_info = None;
+        self.show_connection_info = false;
+        self.input_denied = None;
         self.tx = None;
         self.rx = None;
         self.state_rx = None;
         self.screen_texture = None;
+        self.stream.reset();
+        self.toasts.clear();
         self.access_code.clear();
+        self.access_code_hint = None;
+    }
+    
+    // Remote notifications, stacked in the top right corner below the toolbar
+    fn show_toasts(&mut self, ctx: &Context) {
+        self.toasts.expire(std::time::Instant::now());
+        
+        let mut dismissed = None;
+        for (index, toast) in self.toasts.iter().enumerate() {
+            egui::Window::new(&toast.title)
+                .id(egui::Id::new(("toast", toast.id)))
+                .title_bar(false)
+                .resizable(false)
+                .collapsible(false)
+                .anchor(egui::Align2::RIGHT_TOP, [-12.0, 48.0 + index as f32 * 96.0])
+                .fixed_size([280.0, 72.0])
+                .show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.strong(&toast.title);
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            if ui.small_button("✕").clicked() {
+                                dismissed = Some(toast.id);
+                            }
+                        });
+                    });
+                    if !toast.body.is_empty() {
+                        ui.label(&toast.body);
+                    }
+                    if !toast.app.is_empty() {
+                        ui.small(&toast.app);
+                    }
+                });
+        }
+        
+        if let Some(id) = dismissed {
+            self.toasts.dismiss(id);
+        }
+    }
+    
+    fn take_screenshot(&mut self) {
+        let notice = match &self.stream.current_frame {
+            Some(frame) => {
+                let (width, height) = self.screen_size;
+                match save_screenshot(std::path::Path::new(&self.screenshot_dir), width, height, frame) {
+                    Ok(path) => format!("Saved {}", path.display()),
+                    Err(e) => {
+                        tracing::error!("Failed to save screenshot: {:#}", e);
+                        format!("Screenshot failed: {}", e)
+                    }
+                }
+            }
+            None => "No frame received yet".to_string(),
+        };
+        
+        self.toolbar_notice = Some((notice, std::time::Instant::now()));
+    }
+    
+    fn request_refresh(&mut self) {
+        if self.refresh.request(std::time::Instant::now()) {
+            tracing::info!("Requesting a full refresh of the remote screen");
+            self.send_message(Message::RequestKeyframe);
+        } else {
+            self.toolbar_notice = Some(("Refresh already requested, try again shortly".to_string(), std::time::Instant::now()));
+        }
+    }
+    
+    // Follows the pointer while the lossless region is dragged out, then sends it
+    fn pick_region(&mut self, ui: &egui::Ui, image_rect: egui::Rect, visible: egui::Rect) {
+        ui.ctx().set_cursor_icon(egui::CursorIcon::Crosshair);
+        let (pointer, pressed, released) = ui.input(|i| {
+            (i.pointer.interact_pos(), i.pointer.primary_pressed(), i.pointer.primary_released())
+        });
+        let Some(pointer) = pointer.filter(|pos| !pressed || visible.contains(*pos)) else {
+            return;
+        };
+        let Some((x, y)) = remote_position(image_rect.clamp(pointer), image_rect, self.screen_size) else {
+            return;
+        };
+        let at = (x as u32, y as u32);
+        
+        if pressed {
+            self.region_picker.press(at);
+        }
+        if let Some(region) = self.region_picker.preview(at) {
+            paint_region(&ui.painter().with_clip_rect(visible), region, image_rect, self.screen_size);
+        }
+        if released {
+            if let Some(region) = self.region_picker.release(at) {
+                self.set_lossless_region(Some(region));
+            }
+        }
+    }
+    
+    fn set_lossless_region(&mut self, region: Option<Region>) {
+        self.lossless_region = region;
+        self.send_message(Message::LosslessRegion { rect: region });
+        // Kept for reconnects, which start a session without one
+        if let Some(mut connection) = self.connection.as_ref().and_then(|c| c.try_lock().ok()) {
+            connection.set_lossless_region(region);
+        }
     }
     
     fn send_message(&self, msg: Message) {
@@ -453,6 +1311,15 @@ impl eframe::App for VoxApp {
         if let Some(state_rx) = &self.state_rx {
             if let Ok(mut rx) = state_rx.try_lock() {
                 if let Ok(new_state) = rx.try_recv() {
+                    // Only addresses that actually worked go into the history
+                    if new_state == AppState::Connected && self.state != AppState::Connected {
+                        self.history.record(&self.server_address);
+                        self.frame_order.reset();
+                        self.frames_skipped = 0;
+                        self.desync.reset();
+                        self.stream_pause.reset();
+                        self.touch = TouchInput::new();
+                    }
                     self.state = new_state;
                 }
             }
@@ -460,12 +1327,31 @@ impl eframe::App for VoxApp {
         
         // Process incoming messages
         let mut screen_update = None;
+        let mut server_disconnected = false;
         
         if let Some(rx) = &self.rx {
             if let Ok(mut rx) = rx.try_lock() {
+                let mut received = Vec::new();
                 while let Ok(msg) = rx.try_recv() {
+                    received.push(msg);
+                }
+                // Frames pile up when decoding can't keep up; skip to the newest one
+                let (messages, skipped) = skip_stale_frames(received);
+                if skipped > 0 {
+                    tracing::debug!("Decoding fell behind, skipped {} frame(s)", skipped);
+                    self.frames_skipped += skipped as u64;
+                }
+                for msg in messages {
                     match msg {
-                        Message::ScreenFrame { timestamp, width, height, data, encoding } => {
+                        Message::ScreenFrame { sequence, timestamp, width, height, data, encoding, checksum } => {
+                            if !self.frame_order.admit(sequence) {
+                                tracing::debug!("Dropping frame {} that arrived out of order", sequence);
+                                continue;
+                            }
+                            
+                            // Raw, zstd and WebP frames are always complete; H.264 only from its next IDR on
+                            let complete = encoding != crate::common::protocol::EncodingType::H264 || is_idr(&data);
+                            
                             // Decode based on encoding type
                             let rgb_data = match encoding {
                                 crate::common::protocol::EncodingType::Raw => data,
@@ -480,9 +1366,16 @@ impl eframe::App for VoxApp {
                                 }
                                 crate::common::protocol::EncodingType::H264 => {
                                     // Initialize H.264 decoder if needed
-                                    if self.h264_decoder.is_none() {
+                                    if self.stream.h264_decoder.is_none() {
                                         match H264Decoder::new(width, height) {
-                                            Ok(decoder) => self.h264_decoder = Some(decoder),
+                                            Ok(mut decoder) => {
+                                                if let Some(config) = &self.h264_config {
+                                                    if let Err(e) = decoder.configure(config) {
+                                                        tracing::warn!("Failed to apply H.264 parameter sets: {}", e);
+                                                    }
+                                                }
+                                                self.stream.h264_decoder = Some(decoder);
+                                            }
                                             Err(e) => {
                                                 tracing::error!("Failed to create H.264 decoder: {}", e);
                                                 continue;
@@ -491,7 +1384,7 @@ impl eframe::App for VoxApp {
                                     }
                                     
                                     // Decode H.264 frame
-                                    if let Some(decoder) = &mut self.h264_decoder {
+                                    if let Some(decoder) = &mut self.stream.h264_decoder {
                                         match decoder.decode(&data) {
                                             Ok(Some(rgb_data)) => rgb_data,
                                             Ok(None) => {
@@ -508,19 +1401,29 @@ impl eframe::App for VoxApp {
                                     }
                                 }
                                 crate::common::protocol::EncodingType::WebP => {
-                                    // WebP is handled by software encoder
-                                    tracing::error!("WebP decoding not yet implemented");
-                                    continue;
+                                    // Whole frames from a server in safe mode
+                                    match decode_webp(&data) {
+                                        Ok((rgb_data, ..)) => rgb_data,
+                                        Err(e) => {
+                                            tracing::error!("Failed to decode WebP frame: {}", e);
+                                            continue;
+                                        }
+                                    }
                                 }
                             };
                             
-                            // Initialize frame processor if needed
-                            if self.frame_processor.is_none() {
-                                self.frame_processor = Some(FrameProcessor::new(width, height));
+                            // A mismatch here points at encoding or decoding
+                            self.desync.check(sequence, checksum, &rgb_data);
+                            
+                            // Size the frame processor for this stream, replacing a stale one
+                            self.stream.resize(width, height);
+                            
+                            if complete {
+                                self.refresh.frame_received();
                             }
                             
                             // Store as current frame
-                            self.current_frame = Some(rgb_data.clone());
+                            self.stream.current_frame = Some(rgb_data.clone());
                             screen_update = Some((width, height, rgb_data));
                             
                             // Send acknowledgment
@@ -532,19 +1435,26 @@ impl eframe::App for VoxApp {
                                     .as_millis() as u64,
                             });
                         }
-                        Message::DeltaFrame { timestamp, tiles } => {
+                        Message::DeltaFrame { sequence, timestamp, tiles, checksum } => {
+                            if !self.frame_order.admit(sequence) {
+                                tracing::debug!("Dropping delta {} that arrived out of order", sequence);
+                                continue;
+                            }
+                            
                             // Apply delta to current frame
-                            if let Some(current) = &mut self.current_frame {
+                            if let Some(current) = &mut self.stream.current_frame {
                                 let delta = ProcessedFrame {
                                     frame_type: FrameType::DeltaFrame,
                                     data: bytes::Bytes::new(),
                                     width: self.screen_size.0,
                                     height: self.screen_size.1,
-                                    tiles: Some(tiles.clone()),
+                                    tiles: Some(decompress_tiles(tiles, self.screen_size.0, self.screen_size.1)),
                                 };
                                 
-                                if let Some(processor) = &self.frame_processor {
+                                if let Some(processor) = &self.stream.frame_processor {
                                     if processor.apply_delta(current, &delta).is_ok() {
+                                        // Only deltas since the last matching frame can explain a mismatch here
+                                        self.desync.check(sequence, checksum, current);
                                         screen_update = Some((self.screen_size.0, self.screen_size.1, current.clone()));
                                     }
                                 }
@@ -559,18 +1469,132 @@ impl eframe::App for VoxApp {
                                     .as_millis() as u64,
                             });
                         }
-                        Message::QualityChange { mode } => {
+                        Message::KeyFrameChunk { sequence, timestamp, width, height, tiles, is_last } => {
+                            if !self.frame_order.admit_chunk(sequence) {
+                                tracing::debug!("Dropping keyframe chunk {} that arrived out of order", sequence);
+                                continue;
+                            }
+                            
+                            let Some(frame_len) = rgb_frame_len(width, height) else {
+                                tracing::warn!("Dropping keyframe chunk for an impossible {}x{} frame", width, height);
+                                continue;
+                            };
+                            
+                            // Start a fresh buffer when the resolution changes
+                            if self.screen_size != (width, height)
+                                || self.stream.current_frame.as_ref().map(|f| f.len()) != Some(frame_len)
+                            {
+                                self.screen_size = (width, height);
+                                self.stream.current_frame = Some(vec![0u8; frame_len]);
+                                self.stream.resize(width, height);
+                            }
+                            
+                            let decompressed_tiles = decompress_tiles(tiles, width, height);
+                            
+                            if let (Some(current), Some(processor)) = (&mut self.stream.current_frame, &self.stream.frame_processor) {
+                                let chunk = ProcessedFrame {
+                                    frame_type: FrameType::KeyFrame,
+                                    data: bytes::Bytes::new(),
+                                    width,
+                                    height,
+                                    tiles: Some(decompressed_tiles),
+                                };
+                                
+                                if processor.apply_delta(current, &chunk).is_ok()
+                                    && (self.progressive_rendering || is_last)
+                                {
+                                    screen_update = Some((width, height, current.clone()));
+                                }
+                            }
+                            
+                            if is_last {
+                                self.refresh.frame_received();
+                                self.send_message(Message::FrameAck {
+                                    timestamp,
+                                    received_at: std::time::SystemTime::now()
+                                        .duration_since(std::time::UNIX_EPOCH)
+                                        .unwrap()
+                                        .as_millis() as u64,
+                                });
+                            }
+                        }
+                        Message::ContentProfileApplied { profile } => {
+                            if profile != self.content_profile {
+                                self.toolbar_notice = Some((
+                                    format!("Another viewer of this display wants a different profile, using {}", profile.label()),
+                                    std::time::Instant::now(),
+                                ));
+                            }
+                            self.applied_profile = Some(profile);
+                        }
+                        Message::DeliveryModeApplied { mode } => {
+                            tracing::info!("Server is using {} frame delivery", mode.label());
+                        }
+                        Message::QualityChange { mode, reason } => {
                             self.current_quality = mode;
-                            tracing::info!("Quality changed to: {:?}", mode);
+                            tracing::info!("Quality changed to: {:?} ({})", mode, reason.description());
+                            
+                            if !matches!(reason, QualityChangeReason::Initial | QualityChangeReason::Manual) {
+                                let verb = if reason == QualityChangeReason::NetworkImproved { "raised" } else { "lowered" };
+                                self.toolbar_notice = Some((
+                                    format!("Quality {} to {:?} due to {}", verb, mode, reason.description()),
+                                    std::time::Instant::now(),
+                                ));
+                            }
                         }
                         Message::QualityMetricsReport { metrics } => {
                             self.quality_metrics = Some(metrics);
                         }
+                        Message::CursorUpdate { x, y, visible } => {
+                            self.remote_cursor = visible.then_some((x, y));
+                        }
+                        Message::DisplayScale { factor } => {
+                            tracing::info!("Remote display is scaled {}x", factor);
+                            self.remote_scale = factor;
+                        }
+                        Message::DisplayLayout { monitors } => {
+                            tracing::info!("Remote desktop has {} monitor(s): {:?}", monitors.len(), monitors);
+                            self.display_layout = DisplayLayout::new(monitors);
+                        }
+                        Message::CodecConfig { sps, pps } => {
+                            let config = annex_b(&sps, &pps);
+                            if let Some(decoder) = &mut self.stream.h264_decoder {
+                                if let Err(e) = decoder.configure(&config) {
+                                    tracing::warn!("Failed to apply H.264 parameter sets: {}", e);
+                                }
+                            }
+                            self.h264_config = Some(config);
+                        }
+                        Message::EncodingSelected { encoding } => {
+                            tracing::info!("Server selected {:?} encoding", encoding);
+                        }
                         Message::AuthResponse { success, session_token: _ } => {
                             if !success {
                                 self.state = AppState::Error("Authentication failed".to_string());
                             }
                         }
+                        Message::Error { message, kind: ErrorKind::InputNotPermitted } => {
+                            tracing::warn!("Server can't inject input: {}", message);
+                            self.input_denied = Some(message);
+                        }
+                        Message::Error { message, .. } => {
+                            tracing::error!("Server error: {}", message);
+                            self.toolbar_notice = Some((message, std::time::Instant::now()));
+                        }
+                        Message::DisplayStatus { settings } => {
+                            let brightness = settings.brightness
+                                .map(|level| format!(", brightness {:.0}%", level * 100.0))
+                                .unwrap_or_default();
+                            let notice = format!("Host display {}×{}{}", settings.width, settings.height, brightness);
+                            self.toolbar_notice = Some((notice, std::time::Instant::now()));
+                            self.host_display = Some(settings);
+                        }
+                        Message::Notification { title, body, app } => {
+                            self.toasts.push(title, body, app, std::time::Instant::now());
+                        }
+                        Message::Disconnect => {
+                            server_disconnected = true;
+                        }
                         _ => {}
                     }
                 }
@@ -578,15 +1602,45 @@ impl eframe::App for VoxApp {
         }
         
         // Apply screen update outside of the lock
-        if let Some((width, height, rgb_data)) = screen_update {
-            self.update_screen(ctx, width, height, &rgb_data);
+        let interpolate = self.frame_interpolation && self.current_quality != QualityMode::Ultra;
+        if interpolate {
+            let now = std::time::Instant::now();
+            if let Some((width, height, rgb_data)) = screen_update {
+                self.frame_interpolator.push(width, height, rgb_data, now);
+            }
+            if let Some((width, height, rgb_data)) = self.frame_interpolator.next_frame(now) {
+                self.update_screen(ctx, width, height, &rgb_data);
+            }
+        } else {
+            // Interpolation was just switched off: show the newest frame instead of a stale blend
+            let leftover = self.frame_interpolator.take_latest();
+            if let Some((width, height, rgb_data)) = screen_update.or(leftover) {
+                self.update_screen(ctx, width, height, &rgb_data);
+            }
+        }
+        
+        if server_disconnected {
+            self.disconnect();
+            self.state = AppState::Error("Disconnected by server".to_string());
         }
         
         match self.state {
-            AppState::Disconnected | AppState::Connecting | AppState::Error(_) => {
+            AppState::Disconnected | AppState::Connecting | AppState::Reconnecting { .. } | AppState::Error(_) => {
+                self.release_pointer(ctx);
                 self.show_connection_ui(ctx);
             }
             AppState::Connected => {
+                // Nobody watches a minimized window, so the server needn't stream to it
+                let minimized = ctx.input(|i| i.viewport().minimized).unwrap_or(false);
+                let now = std::time::Instant::now();
+                if let Some(message) = self.stream_pause.observe(!minimized, now) {
+                    tracing::info!("Window {}, sending {:?}", if minimized { "minimized" } else { "restored" }, message);
+                    self.send_message(message);
+                }
+                if let Some(wait) = self.stream_pause.pause_due_in(now) {
+                    ctx.request_repaint_after(wait);
+                }
+                
                 self.show_remote_screen(ctx);
                 
                 // Show quality menu if requested
@@ -612,69 +1666,78 @@ impl eframe::App for VoxApp {
                                 }
                             }
                             
+                            ui.separator();
+                            ui.label("Content:");
+                            for profile in ContentProfile::ALL {
+                                let mut label = profile.label().to_string();
+                                if self.applied_profile == Some(profile) && profile != self.content_profile {
+                                    label.push_str(" (in use)");
+                                }
+                                if ui.selectable_label(self.content_profile == profile, label).clicked() {
+                                    self.content_profile = profile;
+                                    self.save_preferences();
+                                    self.send_message(Message::SetContentProfile { profile });
+                                    // Kept for reconnects; a busy connection gets it from the resend queue
+                                    if let Some(mut connection) = self.connection.as_ref().and_then(|c| c.try_lock().ok()) {
+                                        connection.set_content_profile(profile);
+                                    }
+                                }
+                            }
+                            
+                            ui.separator();
+                            ui.label("Delivery:");
+                            for mode in DeliveryMode::ALL {
+                                if ui.selectable_label(self.delivery == mode, mode.label()).clicked() {
+                                    self.delivery = mode;
+                                    self.save_preferences();
+                                    self.send_message(Message::SetDeliveryMode { mode });
+                                    if let Some(mut connection) = self.connection.as_ref().and_then(|c| c.try_lock().ok()) {
+                                        connection.set_delivery(mode);
+                                    }
+                                }
+                            }
+                            
+                            ui.separator();
+                            if ui.checkbox(&mut self.progressive_rendering, "Progressive rendering").changed() {
+                                self.send_message(Message::SetProgressiveRendering {
+                                    enabled: self.progressive_rendering,
+                                });
+                            }
+                            
+                            let can_interpolate = self.current_quality != QualityMode::Ultra;
+                            ui.add_enabled(
+                                can_interpolate,
+                                egui::Checkbox::new(&mut self.frame_interpolation, "Frame interpolation"),
+                            )
+                            .on_hover_text("Smooths motion at low FPS by blending frames. Adds one frame of latency.")
+                            .on_disabled_hover_text("Not available in Ultra, where latency matters most");
+                            
+                            ui.separator();
+                            ui.horizontal(|ui| {
+                                ui.label("Screenshot folder:");
+                                ui.text_edit_singleline(&mut self.screenshot_dir);
+                            });
+                            
                             ui.separator();
                             if ui.button("Close").clicked() {
                                 self.show_quality_menu = false;
                             }
                         });
                 }
+                
+                if self.show_connection_info {
+                    self.show_connection_info_window(ctx);
+                }
+                
+                self.show_toasts(ctx);
             }
         }
         
         // Request repaint for smooth updates
         ctx.request_repaint();
     }
-}
-
-fn format_key(key: egui::Key) -> Option<String> {
-    use egui::Key;
     
-    Some(match key {
-        Key::A => "a".to_string(),
-        Key::B => "b".to_string(),
-        Key::C => "c".to_string(),
-        Key::D => "d".to_string(),
-        Key::E => "e".to_string(),
-        Key::F => "f".to_string(),
-        Key::G => "g".to_string(),
-        Key::H => "h".to_string(),
-        Key::I => "i".to_string(),
-        Key::J => "j".to_string(),
-        Key::K => "k".to_string(),
-        Key::L => "l".to_string(),
-        Key::M => "m".to_string(),
-        Key::N => "n".to_string(),
-        Key::O => "o".to_string(),
-        Key::P => "p".to_string(),
-        Key::Q => "q".to_string(),
-        Key::R => "r".to_string(),
-        Key::S => "s".to_string(),
-        Key::T => "t".to_string(),
-        Key::U => "u".to_string(),
-        Key::V => "v".to_string(),
-        Key::W => "w".to_string(),
-        Key::X => "x".to_string(),
-        Key::Y => "y".to_string(),
-        Key::Z => "z".to_string(),
-        Key::Num0 => "0".to_string(),
-        Key::Num1 => "1".to_string(),
-        Key::Num2 => "2".to_string(),
-        Key::Num3 => "3".to_string(),
-        Key::Num4 => "4".to_string(),
-        Key::Num5 => "5".to_string(),
-        Key::Num6 => "6".to_string(),
-        Key::Num7 => "7".to_string(),
-        Key::Num8 => "8".to_string(),
-        Key::Num9 => "9".to_string(),
-        Key::Space => " ".to_string(),
-        Key::Enter => "Return".to_string(),
-        Key::Escape => "Escape".to_string(),
-        Key::Backspace => "BackSpace".to_string(),
-        Key::Tab => "Tab".to_string(),
-        Key::ArrowDown => "Down".to_string(),
-        Key::ArrowLeft => "Left".to_string(),
-        Key::ArrowRight => "Right".to_string(),
-        Key::ArrowUp => "Up".to_string(),
-        _ => return None,
-    })
-}
\ No newline at end of file
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        self.history.save(storage);
+    }
+}
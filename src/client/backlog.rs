@@ -0,0 +1,61 @@
+use crate::common::frame_processor::merge_tiles;
+use crate::common::h264::is_idr;
+use crate::common::protocol::{EncodingType, Message};
+
+/// Cut a batch of received messages down to what it takes to show the newest frame, for
+/// when decoding can't keep up and frames queue behind it. Everything before the last
+/// complete frame (raw, zstd or WebP, or an H.264 IDR) is skipped, since nothing after
+/// it depends on them; H.264 frames after it are all kept, as each one builds on the
+/// last. Runs of deltas are merged into one, the newer tile winning where they overlap.
+/// Messages that aren't frames are never skipped and keep their order.
+///
+/// Returns the messages to handle and how many frames were skipped.
+pub fn skip_stale_frames(messages: Vec<Message>) -> (Vec<Message>, usize) {
+    let newest_complete = messages.iter().rposition(is_complete_frame);
+    let mut kept = Vec::with_capacity(messages.len());
+    let mut skipped = 0;
+    // Where the delta since the last full frame or chunk is in `kept`
+    let mut open_delta: Option<usize> = None;
+    
+    for (index, message) in messages.into_iter().enumerate() {
+        if newest_complete.is_some_and(|newest| index < newest) && is_frame(&message) {
+            skipped += 1;
+            continue;
+        }
+        match message {
+            Message::DeltaFrame { sequence, timestamp, tiles, checksum } => {
+                let merged = match open_delta.map(|at| kept.remove(at)) {
+                    Some(Message::DeltaFrame { tiles: mut earlier, .. }) => {
+                        skipped += 1;
+                        merge_tiles(&mut earlier, &tiles);
+                        earlier
+                    }
+                    _ => tiles,
+                };
+                open_delta = Some(kept.len());
+                // The merged tiles reach the newest delta's frame, so its checksum applies
+                kept.push(Message::DeltaFrame { sequence, timestamp, tiles: merged, checksum });
+            }
+            message => {
+                if is_frame(&message) {
+                    open_delta = None;
+                }
+                kept.push(message);
+            }
+        }
+    }
+    (kept, skipped)
+}
+
+fn is_frame(message: &Message) -> bool {
+    matches!(message, Message::ScreenFrame { .. } | Message::DeltaFrame { .. } | Message::KeyFrameChunk { .. })
+}
+
+// A frame that replaces the whole picture and the decoder's state with it
+fn is_complete_frame(message: &Message) -> bool {
+    match message {
+        Message::ScreenFrame { encoding: EncodingType::H264, data, .. } => is_idr(data),
+        Message::ScreenFrame { .. } => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,55 @@
+use crate::common::auth::CODE_LENGTH;
+
+/// Whether `c` is something people group a code's digits with, as in `123-456` or
+/// `123 456`. Pasting a code often brings these along.
+pub fn is_code_separator(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '-' | '.' | '_' | '–' | '—')
+}
+
+/// The access code field's text after an edit, cleaned up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeEntry {
+    /// Every digit entered, including any beyond `CODE_LENGTH` so they can be fixed
+    pub digits: String,
+    // Characters other than digits and separators were removed
+    dropped_other: bool,
+}
+
+impl CodeEntry {
+    /// Clean up `input`: separators are stripped, anything else that isn't a digit is
+    /// dropped and remembered for `hint`. Extra digits are kept rather than cut off, since
+    /// which of them is the mistake can't be told.
+    pub fn parse(input: &str) -> Self {
+        let mut digits = String::with_capacity(input.len());
+        let mut dropped_other = false;
+        for c in input.chars() {
+            if c.is_ascii_digit() {
+                digits.push(c);
+            } else if !is_code_separator(c) {
+                dropped_other = true;
+            }
+        }
+        Self { digits, dropped_other }
+    }
+    
+    /// Whether the entry is a whole code, ready to connect with.
+    pub fn is_complete(&self) -> bool {
+        self.digits.len() == CODE_LENGTH
+    }
+    
+    /// What to tell the user about the entry, None while it is fine or still being typed.
+    pub fn hint(&self) -> Option<String> {
+        if self.digits.len() > CODE_LENGTH {
+            Some(format!(
+                "Access codes are {} digits, this has {}; remove {}",
+                CODE_LENGTH,
+                self.digits.len(),
+                self.digits.len() - CODE_LENGTH
+            ))
+        } else if self.dropped_other {
+            Some("Access codes are digits only; other characters were removed".to_string())
+        } else {
+            None
+        }
+    }
+}
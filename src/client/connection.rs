@@ -1,20 +1,59 @@
 use crate::common::{
-    protocol::Message,
-    crypto::{CryptoSession, KeyExchange},
+    protocol::{Message, EncodingType},
+    crypto::{is_corrupt, open_message, seal_message, Cipher, CryptoSession, Encryptor, KeyExchange, NullEncryptor, PlaintextVideo, MAX_CORRUPT_MESSAGES},
+    capabilities::Capabilities,
+    batch::{collect_batch, pack, unpack, BatchPolicy},
+    framing::{frame_len, FrameDecoder, ReadBuffer, DEFAULT_READ_CAPACITY, LENGTH_PREFIX, MAX_FRAME_LEN},
+    quality::ContentProfile,
+    delivery::DeliveryMode,
+    socket::{self, SocketBuffers},
+    foveation::Region,
 };
+use crate::client::h264_decoder::H264Decoder;
+use crate::client::resend::ResendQueue;
+use crate::client::security::SecurityInfo;
 use tokio::net::TcpStream;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::sync::{mpsc, Mutex};
-use bytes::{BytesMut, Buf};
+use tokio::task::JoinHandle;
 use anyhow::{Result, Context};
 use std::sync::Arc;
-use tracing::{info, debug, error};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tracing::{info, debug, warn, error};
+
+// How long a reconnect waits for the previous writer to hand over what it couldn't send
+const WRITER_HANDOVER_TIMEOUT: Duration = Duration::from_secs(1);
 
 pub struct Connection {
     #[allow(dead_code)]
     stream: Option<TcpStream>,
-    crypto: Option<Arc<Mutex<CryptoSession>>>,
+    crypto: Option<Arc<Mutex<Box<dyn Encryptor>>>>,
     session_token: Option<String>,
+    // Cleared to ask a loopback server for an unencrypted benchmarking session
+    encryption: bool,
+    // Set to ask for video unencrypted once authenticated, on networks encrypted already
+    plaintext_video: bool,
+    // Offered to the server, most preferred first
+    ciphers: Vec<Cipher>,
+    // Which of the server's displays to stream
+    display: u32,
+    // Messages the last connection couldn't deliver, replayed by the next one
+    resend: Arc<std::sync::Mutex<ResendQueue>>,
+    writer: Option<JoinHandle<()>>,
+    // What protects the current session, for the connection info window
+    security: Option<SecurityInfo>,
+    // Coalesces queued messages into one frame; `None` sends each on its own
+    batching: Option<BatchPolicy>,
+    // Asked for again on every connect
+    content_profile: ContentProfile,
+    delivery: DeliveryMode,
+    viewport: Option<(u32, u32)>,
+    lossless_region: Option<Region>,
+    // Kernel buffer sizes for the socket, system defaults unless set
+    socket_buffers: SocketBuffers,
+    // Read buffer size the last connection's frames called for, so a reconnect starts with it
+    read_capacity: Arc<AtomicUsize>,
 }
 
 impl Connection {
@@ -24,51 +63,108 @@ impl Connection {
             stream: None,
             crypto: None,
             session_token: None,
+            encryption: true,
+            plaintext_video: false,
+            ciphers: Cipher::preferred(),
+            display: 0,
+            resend: Arc::new(std::sync::Mutex::new(ResendQueue::new())),
+            writer: None,
+            security: None,
+            batching: Some(BatchPolicy::default()),
+            content_profile: ContentProfile::default(),
+            delivery: DeliveryMode::default(),
+            viewport: None,
+            lossless_region: None,
+            socket_buffers: SocketBuffers::default(),
+            read_capacity: Arc::new(AtomicUsize::new(DEFAULT_READ_CAPACITY)),
         };
         (connection, rx, tx)
     }
     
+    /// Disabling encryption only works against a loopback server started with `--no-encryption`.
+    pub fn set_encryption(&mut self, enabled: bool) {
+        self.encryption = enabled;
+    }
+    
+    /// Ask for video unencrypted after authenticating, to save the cost of decrypting
+    /// frames on a network that is already encrypted. Only servers started with
+    /// `--plaintext-video` agree; otherwise the session stays fully encrypted.
+    pub fn set_plaintext_video(&mut self, enabled: bool) {
+        self.plaintext_video = enabled;
+    }
+    
+    /// Ciphers to offer, most preferred first; by default the fastest on this machine leads.
+    pub fn set_ciphers(&mut self, ciphers: Vec<Cipher>) {
+        self.ciphers = ciphers;
+    }
+    
+    /// How outgoing messages are batched, `None` to send each in its own frame.
+    pub fn set_batching(&mut self, policy: Option<BatchPolicy>) {
+        self.batching = policy;
+    }
+    
+    /// Stream display `index` of a multi-monitor server instead of the first one.
+    pub fn set_display(&mut self, index: u32) {
+        self.display = index;
+    }
+    
+    /// What the screen shows, so the server can tune encoding for it.
+    pub fn set_content_profile(&mut self, profile: ContentProfile) {
+        self.content_profile = profile;
+    }
+    
+    /// Whether the server should send late frames anyway or drop them.
+    pub fn set_delivery(&mut self, mode: DeliveryMode) {
+        self.delivery = mode;
+    }
+    
+    /// Physical pixels the remote screen is shown in, so the server can send smaller
+    /// frames; `None` for frames at full size.
+    pub fn set_viewport(&mut self, viewport: Option<(u32, u32)>) {
+        self.viewport = viewport;
+    }
+    
+    /// Part of the frame to keep exact while foveation reduces the rest.
+    pub fn set_lossless_region(&mut self, region: Option<Region>) {
+        self.lossless_region = region;
+    }
+    
+    /// Kernel send and receive buffer sizes, applied from the next connect on.
+    pub fn set_socket_buffers(&mut self, buffers: SocketBuffers) {
+        self.socket_buffers = buffers;
+    }
+    
+    /// How the current session is protected; `None` until authenticated.
+    pub fn security_info(&self) -> Option<SecurityInfo> {
+        self.security.clone()
+    }
+    
+    /// Connect (or reconnect) and authenticate. Input the previous connection couldn't
+    /// deliver is sent first, once the old sender has been dropped.
     pub async fn connect(
         &mut self,
         addr: &str,
         code: &str,
     ) -> Result<(mpsc::UnboundedReceiver<Message>, mpsc::UnboundedSender<Message>)> {
-        let mut stream = TcpStream::connect(addr).await
+        let mut stream = socket::connect(addr, self.socket_buffers).await
             .context("Failed to connect to server")?;
         
         info!("Connected to server at {}", addr);
         
-        // Start key exchange
-        let key_exchange = KeyExchange::new();
-        let our_public = key_exchange.public_key_bytes();
-        
-        // Send key exchange
-        let key_msg = Message::KeyExchange {
-            public_key: our_public.to_vec(),
-        };
-        
-        send_raw_message(&mut stream, &key_msg).await?;
-        
-        // Read server's public key
-        let their_key_msg = read_raw_message(&mut stream).await?;
-        
-        let their_public_key = if let Message::KeyExchangeAck { public_key } = their_key_msg {
-            public_key
+        self.security = None;
+        let (crypto, mut security): (Arc<Mutex<Box<dyn Encryptor>>>, _) = if self.encryption {
+            let (session, server_public_key) = key_exchange(&mut stream, &self.ciphers).await?;
+            let security = SecurityInfo::encrypted(&server_public_key).with_cipher(session.cipher());
+            (Arc::new(Mutex::new(Box::new(session))), security)
         } else {
-            return Err(anyhow::anyhow!("Expected KeyExchangeAck"));
+            (Arc::new(Mutex::new(Box::new(request_plaintext(&mut stream).await?))), SecurityInfo::plaintext())
         };
-        
-        // Compute shared secret
-        let their_public = x25519_dalek::PublicKey::from(
-            <[u8; 32]>::try_from(&their_public_key[..]).context("Invalid public key")?
-        );
-        let shared_secret = key_exchange.compute_shared_secret(&their_public);
-        
-        // Create crypto session
-        let crypto = Arc::new(Mutex::new(CryptoSession::from_shared_secret(&shared_secret)?));
         self.crypto = Some(crypto.clone());
         
-        debug!("Key exchange completed");
+        // Pick the display before the session exists, so no frame comes from another one
+        if self.display != 0 {
+            send_encrypted_message(&mut stream, &Message::SelectDisplay { index: self.display }, &crypto).await?;
+        }
         
         // Send authentication
         let auth_msg = Message::AuthRequest {
@@ -90,14 +186,76 @@ impl Connection {
         }
         
         info!("Authentication successful");
+        security.authenticated = true;
+        
+        // Whatever the server sends before answering is passed on with the rest
+        let mut early = Vec::new();
+        if self.plaintext_video && self.encryption {
+            send_encrypted_message(&mut stream, &Message::PlaintextVideo, &crypto).await?;
+            loop {
+                match read_encrypted_message(&mut stream, &crypto).await? {
+                    Message::PlaintextVideoAck { accepted: true } => {
+                        warn!("Server sends video UNENCRYPTED; input and everything else stays encrypted");
+                        let mut crypto = crypto.lock().await;
+                        let inner = std::mem::replace(&mut *crypto, Box::new(NullEncryptor));
+                        *crypto = Box::new(PlaintextVideo::receiver(inner));
+                        security.plaintext_video = true;
+                        break;
+                    }
+                    Message::PlaintextVideoAck { accepted: false } => {
+                        warn!("Server refused unencrypted video (it needs --plaintext-video), staying encrypted");
+                        break;
+                    }
+                    message => early.extend(unpack(message)),
+                }
+            }
+        }
+        self.security = Some(security);
         
         // Create channels for message passing
         let (tx_in, rx_in) = mpsc::unbounded_channel();
         let (tx_out, rx_out) = mpsc::unbounded_channel();
+        for message in early {
+            tx_out.send(message)?;
+        }
+        
+        // The old writer queues whatever it still holds once its sender is gone
+        if let Some(mut writer) = self.writer.take() {
+            if tokio::time::timeout(WRITER_HANDOVER_TIMEOUT, &mut writer).await.is_err() {
+                warn!("Previous connection's writer did not finish, some input may be lost");
+                writer.abort();
+            }
+        }
+        let replay = self.resend.lock().unwrap().take_replay(Instant::now());
+        if !replay.is_empty() {
+            info!("Replaying {} messages the previous connection didn't deliver", replay.len());
+        }
+        for message in replay {
+            tx_in.send(message)?;
+        }
         
         // Start message handling loops
         self.start_message_loops(stream, crypto, tx_out, rx_in).await?;
         
+        // Tell the server what we can decode before frames start flowing
+        tx_in.send(Message::Capabilities {
+            encodings: client_capabilities().encodings,
+        })?;
+        // We draw the remote cursor when the server's frames leave it out
+        tx_in.send(Message::SetCursorOverlay { enabled: true })?;
+        if self.content_profile != ContentProfile::default() {
+            tx_in.send(Message::SetContentProfile { profile: self.content_profile })?;
+        }
+        if self.delivery != DeliveryMode::default() {
+            tx_in.send(Message::SetDeliveryMode { mode: self.delivery })?;
+        }
+        if let Some((width, height)) = self.viewport {
+            tx_in.send(Message::ViewportResize { width, height })?;
+        }
+        if self.lossless_region.is_some() {
+            tx_in.send(Message::LosslessRegion { rect: self.lossless_region })?;
+        }
+        
         // Request stream start
         tx_in.send(Message::StartStream)?;
         
@@ -105,54 +263,80 @@ impl Connection {
     }
     
     async fn start_message_loops(
-        &self,
+        &mut self,
         stream: TcpStream,
-        crypto: Arc<Mutex<CryptoSession>>,
+        crypto: Arc<Mutex<Box<dyn Encryptor>>>,
         tx_out: mpsc::UnboundedSender<Message>,
         mut rx_in: mpsc::UnboundedReceiver<Message>,
     ) -> Result<()> {
         let (mut reader, mut writer) = stream.into_split();
         
+        // Set once either side fails; anything sent after that is kept for the next connection
+        let link_down = Arc::new(AtomicBool::new(false));
+        
         // Spawn reader task
         let reader_crypto = crypto.clone();
+        let reader_link_down = link_down.clone();
+        let read_capacity = self.read_capacity.clone();
+        let display = self.display as usize;
         tokio::spawn(async move {
-            let mut buffer = BytesMut::with_capacity(65536);
+            let mut buffer = ReadBuffer::with_capacity(read_capacity.load(Ordering::Relaxed));
+            let mut frames = FrameDecoder::new();
+            // What the server has said about its frames: the streamed display's size, then
+            // the encoding it picked
+            let mut frame_size: Option<(u32, u32)> = None;
+            let mut frame_encoding = EncodingType::ZstdCompressed;
+            let mut corrupt_messages = 0u32;
             
-            loop {
-                match reader.read_buf(&mut buffer).await {
+            'read: loop {
+                match reader.read_buf(buffer.for_read()).await {
                     Ok(0) => {
                         error!("Server disconnected");
                         break;
                     }
                     Ok(_) => {
-                        while buffer.len() >= 4 {
-                            let len = u32::from_be_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as usize;
-                            
-                            if buffer.len() < 4 + len {
-                                break;
-                            }
-                            
-                            buffer.advance(4);
-                            let msg_data = buffer.split_to(len).freeze();
-                            
-                            // Decrypt
-                            let decrypted = match reader_crypto.lock().await.decrypt(&msg_data) {
-                                Ok(data) => data,
+                        loop {
+                            let msg_data = match frames.decode(buffer.received()) {
+                                Ok(Some(data)) => data,
+                                Ok(None) => break,
                                 Err(e) => {
-                                    error!("Decryption error: {}", e);
-                                    continue;
+                                    error!("Closing connection: {}", e);
+                                    break 'read;
                                 }
                             };
                             
-                            // Parse and send
-                            match Message::deserialize(&decrypted) {
-                                Ok(msg) => {
-                                    if tx_out.send(msg).is_err() {
-                                        return;
+                            // Decrypt, parse and send; key rotations are applied along the way
+                            let opened = open_message(&**reader_crypto.lock().await, &msg_data);
+                            match opened {
+                                Ok(Some(msg)) => {
+                                    corrupt_messages = 0;
+                                    for msg in unpack(msg) {
+                                        // Sized for keyframes before the first one arrives
+                                        let (size, encoding) = frame_format(&msg, display);
+                                        if size.is_some() || encoding.is_some() {
+                                            frame_size = size.or(frame_size);
+                                            frame_encoding = encoding.unwrap_or(frame_encoding);
+                                            if let Some((width, height)) = frame_size {
+                                                buffer.fit_frames(width, height, frame_encoding);
+                                                read_capacity.store(buffer.capacity(), Ordering::Relaxed);
+                                            }
+                                        }
+                                        if tx_out.send(msg).is_err() {
+                                            return;
+                                        }
                                     }
                                 }
+                                Ok(None) => debug!("Server rotated its session key"),
+                                Err(e) if is_corrupt(&e) => {
+                                    corrupt_messages += 1;
+                                    if corrupt_messages > MAX_CORRUPT_MESSAGES {
+                                        error!("Too many undecryptable messages, closing connection: {}", e);
+                                        break 'read;
+                                    }
+                                    warn!("Dropping message from server: {}", e);
+                                }
                                 Err(e) => {
-                                    error!("Failed to deserialize message: {}", e);
+                                    error!("Failed to decode message: {}", e);
                                 }
                             }
                         }
@@ -163,11 +347,28 @@ impl Connection {
                     }
                 }
             }
+            reader_link_down.store(true, Ordering::SeqCst);
         });
         
         // Spawn writer task
-        tokio::spawn(async move {
-            while let Some(msg) = rx_in.recv().await {
+        let resend = self.resend.clone();
+        let batching = self.batching;
+        self.writer = Some(tokio::spawn(async move {
+            while let Some(first) = rx_in.recv().await {
+                let messages = match &batching {
+                    Some(policy) => collect_batch(first, &mut rx_in, policy).await,
+                    None => vec![first],
+                };
+                
+                if link_down.load(Ordering::SeqCst) {
+                    let mut resend = resend.lock().unwrap();
+                    for msg in messages {
+                        resend.push(msg, Instant::now());
+                    }
+                    continue;
+                }
+                
+                let msg = pack(messages);
                 let serialized = match msg.serialize() {
                     Ok(data) => data,
                     Err(e) => {
@@ -176,9 +377,9 @@ impl Connection {
                     }
                 };
                 
-                // Encrypt
-                let encrypted = match crypto.lock().await.encrypt(&serialized) {
-                    Ok(data) => data,
+                // Encrypt, preceded by a key rotation when one is due
+                let frames = match seal_message(&**crypto.lock().await, &serialized) {
+                    Ok(frames) => frames,
                     Err(e) => {
                         error!("Encryption error: {}", e);
                         continue;
@@ -186,26 +387,104 @@ impl Connection {
                 };
                 
                 // Send
-                if let Err(e) = send_message(&mut writer, &encrypted).await {
-                    error!("Failed to send message: {}", e);
-                    break;
+                for frame in frames {
+                    if let Err(e) = send_message(&mut writer, &frame).await {
+                        error!("Failed to send message: {}", e);
+                        link_down.store(true, Ordering::SeqCst);
+                        let mut resend = resend.lock().unwrap();
+                        for msg in unpack(msg) {
+                            resend.push(msg, Instant::now());
+                        }
+                        break;
+                    }
                 }
             }
-        });
+        }));
         
         Ok(())
     }
 }
 
+/// The frame size and encoding `message` tells about the stream of display `display`, if
+/// anything. The display's size in the layout is in input units, a first guess until frames
+/// arrive.
+pub fn frame_format(message: &Message, display: usize) -> (Option<(u32, u32)>, Option<EncodingType>) {
+    match message {
+        Message::DisplayLayout { monitors } => (monitors.get(display).map(|monitor| (monitor.width, monitor.height)), None),
+        Message::EncodingSelected { encoding } => (None, Some(*encoding)),
+        Message::ScreenFrame { width, height, encoding, .. } => (Some((*width, *height)), Some(*encoding)),
+        _ => (None, None),
+    }
+}
+
+/// Cipher negotiation and X25519 key exchange, giving the session for everything after
+/// the handshake. Returns the session and the server's public key.
+async fn key_exchange(stream: &mut TcpStream, ciphers: &[Cipher]) -> Result<(CryptoSession, Vec<u8>)> {
+    send_raw_message(stream, &Message::CipherOffer { ciphers: ciphers.to_vec() }).await?;
+    let cipher = match read_raw_message(stream).await? {
+        Message::CipherSelected { cipher } if ciphers.contains(&cipher) => cipher,
+        Message::CipherSelected { cipher } => {
+            return Err(anyhow::anyhow!("Server picked {}, which wasn't offered", cipher.name()));
+        }
+        _ => return Err(anyhow::anyhow!("Expected CipherSelected")),
+    };
+    debug!("Using {} encryption", cipher.name());
+    
+    let key_exchange = KeyExchange::new();
+    let our_public = key_exchange.public_key_bytes();
+    
+    // Send key exchange
+    let key_msg = Message::KeyExchange {
+        public_key: our_public.to_vec(),
+    };
+    
+    send_raw_message(stream, &key_msg).await?;
+    
+    // Read server's public key
+    let their_key_msg = read_raw_message(stream).await?;
+    
+    let their_public_key = if let Message::KeyExchangeAck { public_key } = their_key_msg {
+        public_key
+    } else {
+        return Err(anyhow::anyhow!("Expected KeyExchangeAck"));
+    };
+    
+    // Compute shared secret
+    let shared_secret = key_exchange.agree(&their_public_key).context("Server's key exchange refused")?;
+    
+    debug!("Key exchange completed");
+    Ok((CryptoSession::from_shared_secret(&shared_secret)?.with_cipher(cipher), their_public_key))
+}
+
+/// Ask the server to skip encryption. Refused locally for anything but loopback.
+async fn request_plaintext(stream: &mut TcpStream) -> Result<NullEncryptor> {
+    if !stream.peer_addr()?.ip().is_loopback() {
+        return Err(anyhow::anyhow!("Encryption can only be disabled for loopback connections"));
+    }
+    
+    send_raw_message(stream, &Message::DisableEncryption).await?;
+    
+    match read_raw_message(stream).await? {
+        Message::DisableEncryptionAck { accepted: true } => {
+            warn!("Connection is NOT encrypted; use this for local benchmarking only");
+            Ok(NullEncryptor)
+        }
+        Message::DisableEncryptionAck { accepted: false } => Err(anyhow::anyhow!(
+            "Server refused an unencrypted session (start it with --no-encryption on a loopback address)"
+        )),
+        _ => Err(anyhow::anyhow!("Expected DisableEncryptionAck")),
+    }
+}
+
 async fn send_raw_message(stream: &mut TcpStream, msg: &Message) -> Result<()> {
     let data = msg.serialize()?;
     send_message(stream, &data).await
 }
 
 async fn read_raw_message(stream: &mut TcpStream) -> Result<Message> {
-    let mut len_buf = [0u8; 4];
+    let mut len_buf = [0u8; LENGTH_PREFIX];
     stream.read_exact(&mut len_buf).await?;
-    let len = u32::from_be_bytes(len_buf) as usize;
+    let len = frame_len(len_buf, MAX_FRAME_LEN)?;
     
     let mut data = vec![0u8; len];
     stream.read_exact(&mut data).await?;
@@ -216,26 +495,32 @@ async fn read_raw_message(stream: &mut TcpStream) -> Result<Message> {
 async fn send_encrypted_message(
     stream: &mut TcpStream,
     msg: &Message,
-    crypto: &Arc<Mutex<CryptoSession>>,
+    crypto: &Arc<Mutex<Box<dyn Encryptor>>>,
 ) -> Result<()> {
     let data = msg.serialize()?;
-    let encrypted = crypto.lock().await.encrypt(&data)?;
-    send_message(stream, &encrypted).await
+    let frames = seal_message(&**crypto.lock().await, &data)?;
+    for frame in frames {
+        send_message(stream, &frame).await?;
+    }
+    Ok(())
 }
 
 async fn read_encrypted_message(
     stream: &mut TcpStream,
-    crypto: &Arc<Mutex<CryptoSession>>,
+    crypto: &Arc<Mutex<Box<dyn Encryptor>>>,
 ) -> Result<Message> {
-    let mut len_buf = [0u8; 4];
-    stream.read_exact(&mut len_buf).await?;
-    let len = u32::from_be_bytes(len_buf) as usize;
-    
-    let mut encrypted = vec![0u8; len];
-    stream.read_exact(&mut encrypted).await?;
-    
-    let decrypted = crypto.lock().await.decrypt(&encrypted)?;
-    Ok(Message::deserialize(&decrypted)?)
+    loop {
+        let mut len_buf = [0u8; LENGTH_PREFIX];
+        stream.read_exact(&mut len_buf).await?;
+        let len = frame_len(len_buf, MAX_FRAME_LEN)?;
+        
+        let mut encrypted = vec![0u8; len];
+        stream.read_exact(&mut encrypted).await?;
+        
+        if let Some(message) = open_message(&**crypto.lock().await, &encrypted)? {
+            return Ok(message);
+        }
+    }
 }
 
 async fn send_message<W: AsyncWriteExt + Unpin>(writer: &mut W, data: &[u8]) -> Result<()> {
@@ -244,4 +529,16 @@ async fn send_message<W: AsyncWriteExt + Unpin>(writer: &mut W, data: &[u8]) ->
     writer.write_all(data).await?;
     writer.flush().await?;
     Ok(())
+}
+
+/// Encodings this client can decode, most preferred first.
+pub fn client_capabilities() -> Capabilities {
+    let mut encodings = Vec::new();
+    if H264Decoder::is_available() {
+        encodings.push(EncodingType::H264);
+    }
+    encodings.push(EncodingType::ZstdCompressed);
+    encodings.push(EncodingType::Raw);
+    encodings.push(EncodingType::WebP);
+    Capabilities::new(encodings)
 }
\ No newline at end of file
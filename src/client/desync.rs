@@ -0,0 +1,59 @@
+use crate::common::checksum::frame_checksum;
+
+/// Compares each frame the client reconstructs with the checksum the server sent for it
+/// (`--frame-checksums`), to tell whether a picture that differs from the host's went
+/// wrong in encoding, delta application or decoding.
+#[derive(Debug, Default)]
+pub struct DesyncDetector {
+    checked: u64,
+    mismatches: u64,
+    // Sequence number of the first frame that didn't match
+    first_mismatch: Option<u64>,
+}
+
+impl DesyncDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Check the frame with `sequence` against the server's `expected` checksum. Returns
+    /// whether it matched, or `None` when the server sent none.
+    pub fn check(&mut self, sequence: u64, expected: Option<u32>, frame: &[u8]) -> Option<bool> {
+        let expected = expected?;
+        self.checked += 1;
+        let actual = frame_checksum(frame);
+        if actual == expected {
+            return Some(true);
+        }
+        
+        self.mismatches += 1;
+        self.first_mismatch.get_or_insert(sequence);
+        tracing::warn!(
+            "Frame {} differs from the server's: checksum {:08x}, expected {:08x} ({} of {} frames checked)",
+            sequence, actual, expected, self.mismatches, self.checked,
+        );
+        Some(false)
+    }
+    
+    /// Whether any checked frame has differed from the server's since the last reset.
+    pub fn is_desynced(&self) -> bool {
+        self.mismatches > 0
+    }
+    
+    pub fn mismatches(&self) -> u64 {
+        self.mismatches
+    }
+    
+    pub fn checked(&self) -> u64 {
+        self.checked
+    }
+    
+    pub fn first_mismatch(&self) -> Option<u64> {
+        self.first_mismatch
+    }
+    
+    /// Start counting again, e.g. for a new connection.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
@@ -0,0 +1,40 @@
+/// Keeps the picture moving forward: frames that arrive after a newer one (reordered
+/// datagrams) are dropped instead of briefly showing an older screen.
+///
+/// Frames are ordered by the sequence number the server gives each one, not by their
+/// wall-clock timestamp, which can jump backwards when the server's clock is adjusted.
+#[derive(Debug, Default)]
+pub struct FrameOrder {
+    latest: Option<u64>,
+}
+
+impl FrameOrder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Whether to apply a whole frame; it becomes the latest if so.
+    pub fn admit(&mut self, sequence: u64) -> bool {
+        match self.latest {
+            Some(latest) if sequence <= latest => false,
+            _ => {
+                self.latest = Some(sequence);
+                true
+            }
+        }
+    }
+    
+    /// Like `admit`, but every chunk of the latest keyframe is let through.
+    pub fn admit_chunk(&mut self, sequence: u64) -> bool {
+        self.latest == Some(sequence) || self.admit(sequence)
+    }
+    
+    /// A new connection numbers its frames from the start again.
+    pub fn reset(&mut self) {
+        self.latest = None;
+    }
+    
+    pub fn latest(&self) -> Option<u64> {
+        self.latest
+    }
+}
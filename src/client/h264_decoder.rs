@@ -1,6 +1,10 @@
-use anyhow::{Result, Context};
+use anyhow::{Result, Context, anyhow};
 use ffmpeg_next as ffmpeg;
 use ffmpeg::{codec, decoder, frame, Packet};
+use ffmpeg::format::Pixel;
+use ffmpeg::software::scaling;
+
+use crate::common::color::ColorSpace;
 
 pub struct H264Decoder {
     decoder: decoder::Video,
@@ -8,6 +12,8 @@ pub struct H264Decoder {
     packet: Packet,
     width: u32,
     height: u32,
+    // libswscale context for frames in a format converted by it, with its input format and size
+    scaler: Option<(Pixel, u32, u32, scaling::Context)>,
 }
 
 impl H264Decoder {
@@ -27,9 +33,20 @@ impl H264Decoder {
             packet: Packet::empty(),
             width,
             height,
+            scaler: None,
         })
     }
     
+    /// Whether FFmpeg on this machine has an H.264 decoder at all.
+    pub fn is_available() -> bool {
+        ffmpeg::init().is_ok() && decoder::find(codec::Id::H264).is_some()
+    }
+    
+    /// Feed SPS/PPS (Annex B) ahead of a stream that may start without them.
+    pub fn configure(&mut self, parameter_sets: &[u8]) -> Result<()> {
+        self.decode(parameter_sets).map(|_| ())
+    }
+    
     pub fn decode(&mut self, h264_data: &[u8]) -> Result<Option<Vec<u8>>> {
         // Create packet from H.264 data
         self.packet = Packet::copy(h264_data);
@@ -56,45 +73,63 @@ impl H264Decoder {
         }
     }
     
-    fn yuv_to_rgb(&self) -> Result<Vec<u8>> {
+    // Packed RGB for the decoded frame. The 4:2:0 layouts H.264 decoders produce are
+    // converted directly; anything else goes through libswscale.
+    fn yuv_to_rgb(&mut self) -> Result<Vec<u8>> {
         let width = self.frame.width() as usize;
         let height = self.frame.height() as usize;
-        let mut rgb = Vec::with_capacity(width * height * 3);
+        let color_space = self.color_space();
         
-        // Get YUV planes
-        let y_plane = self.frame.data(0);
-        let u_plane = self.frame.data(1);
-        let v_plane = self.frame.data(2);
+        match self.frame.format() {
+            Pixel::YUV420P | Pixel::YUVJ420P => yuv420p_to_rgb(
+                [self.frame.data(0), self.frame.data(1), self.frame.data(2)],
+                [self.frame.stride(0), self.frame.stride(1), self.frame.stride(2)],
+                width,
+                height,
+                &color_space,
+            ),
+            // Hardware decoders hand back interleaved chroma
+            Pixel::NV12 => nv12_to_rgb(
+                [self.frame.data(0), self.frame.data(1)],
+                [self.frame.stride(0), self.frame.stride(1)],
+                width,
+                height,
+                &color_space,
+            ),
+            format => self.scale_to_rgb(format),
+        }
+    }
+    
+    // Any other format (4:2:2, 4:4:4, 10-bit) converted by libswscale
+    fn scale_to_rgb(&mut self, format: Pixel) -> Result<Vec<u8>> {
+        let (width, height) = (self.frame.width(), self.frame.height());
+        if !matches!(&self.scaler, Some((f, w, h, _)) if *f == format && *w == width && *h == height) {
+            tracing::debug!("Converting {:?} frames with libswscale", format);
+            let context = scaling::Context::get(format, width, height, Pixel::RGB24, width, height, scaling::Flags::BILINEAR)
+                .with_context(|| format!("Can't convert {:?} frames to RGB", format))?;
+            self.scaler = Some((format, width, height, context));
+        }
+        let scaler = &mut self.scaler.as_mut().expect("scaler created above").3;
         
-        let y_stride = self.frame.stride(0);
-        let u_stride = self.frame.stride(1);
-        let v_stride = self.frame.stride(2);
+        let mut output = frame::Video::new(Pixel::RGB24, width, height);
+        scaler.run(&self.frame, &mut output)?;
         
-        // Convert YUV420P to RGB
-        for y in 0..height {
-            for x in 0..width {
-                let y_idx = y * y_stride + x;
-                let u_idx = (y / 2) * u_stride + (x / 2);
-                let v_idx = (y / 2) * v_stride + (x / 2);
-                
-                let y_val = y_plane[y_idx] as f32;
-                let u_val = u_plane[u_idx] as f32 - 128.0;
-                let v_val = v_plane[v_idx] as f32 - 128.0;
-                
-                // BT.601 conversion
-                let r = (y_val + 1.402 * v_val).clamp(0.0, 255.0) as u8;
-                let g = (y_val - 0.344 * u_val - 0.714 * v_val).clamp(0.0, 255.0) as u8;
-                let b = (y_val + 1.772 * u_val).clamp(0.0, 255.0) as u8;
-                
-                rgb.push(r);
-                rgb.push(g);
-                rgb.push(b);
-            }
+        let row = width as usize * 3;
+        let (data, stride) = (output.data(0), output.stride(0));
+        check_plane("RGB", data, stride, row, height as usize)?;
+        let mut rgb = Vec::with_capacity(row * height as usize);
+        for y in 0..height as usize {
+            rgb.extend_from_slice(&data[y * stride..y * stride + row]);
         }
-        
         Ok(rgb)
     }
     
+    /// The color space signaled by the stream, or the resolution-based default the encoder uses
+    fn color_space(&self) -> ColorSpace {
+        ColorSpace::from_ffmpeg(self.frame.color_space(), self.frame.color_range())
+            .unwrap_or_else(|| ColorSpace::for_resolution(self.frame.width(), self.frame.height()))
+    }
+    
     pub fn dimensions(&self) -> (u32, u32) {
         (self.width, self.height)
     }
@@ -114,4 +149,75 @@ impl H264Decoder {
         
         Ok(frames)
     }
-}
\ No newline at end of file
+}
+
+// Fails unless `plane` holds `rows` rows of `row_bytes` each, `stride` bytes apart
+fn check_plane(name: &str, plane: &[u8], stride: usize, row_bytes: usize, rows: usize) -> Result<()> {
+    if stride < row_bytes {
+        return Err(anyhow!("{} plane stride {} is shorter than a {}-byte row", name, stride, row_bytes));
+    }
+    let needed = (rows - 1) * stride + row_bytes;
+    if plane.len() < needed {
+        return Err(anyhow!("{} plane is {} bytes, {} rows need {}", name, plane.len(), rows, needed));
+    }
+    Ok(())
+}
+
+/// Packed RGB from planar YUV 4:2:0 (`[Y, U, V]`), each plane `strides` bytes per row.
+/// Rows may be padded past the picture; planes too small for the size are an error.
+pub fn yuv420p_to_rgb(
+    planes: [&[u8]; 3],
+    strides: [usize; 3],
+    width: usize,
+    height: usize,
+    color_space: &ColorSpace,
+) -> Result<Vec<u8>> {
+    if width == 0 || height == 0 {
+        return Err(anyhow!("Decoded frame is {}x{}", width, height));
+    }
+    let [y_plane, u_plane, v_plane] = planes;
+    let [y_stride, u_stride, v_stride] = strides;
+    let (chroma_width, chroma_height) = ((width + 1) / 2, (height + 1) / 2);
+    check_plane("Y", y_plane, y_stride, width, height)?;
+    check_plane("U", u_plane, u_stride, chroma_width, chroma_height)?;
+    check_plane("V", v_plane, v_stride, chroma_width, chroma_height)?;
+    
+    let mut rgb = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            let u = u_plane[(y / 2) * u_stride + x / 2];
+            let v = v_plane[(y / 2) * v_stride + x / 2];
+            let (r, g, b) = color_space.yuv_to_rgb(y_plane[y * y_stride + x], u, v);
+            rgb.extend_from_slice(&[r, g, b]);
+        }
+    }
+    Ok(rgb)
+}
+
+/// Packed RGB from NV12: a Y plane and one plane of interleaved U/V pairs at half size.
+pub fn nv12_to_rgb(
+    planes: [&[u8]; 2],
+    strides: [usize; 2],
+    width: usize,
+    height: usize,
+    color_space: &ColorSpace,
+) -> Result<Vec<u8>> {
+    if width == 0 || height == 0 {
+        return Err(anyhow!("Decoded frame is {}x{}", width, height));
+    }
+    let [y_plane, uv_plane] = planes;
+    let [y_stride, uv_stride] = strides;
+    let (chroma_width, chroma_height) = ((width + 1) / 2, (height + 1) / 2);
+    check_plane("Y", y_plane, y_stride, width, height)?;
+    check_plane("UV", uv_plane, uv_stride, chroma_width * 2, chroma_height)?;
+    
+    let mut rgb = Vec::with_capacity(width * height * 3);
+    for y in 0..height {
+        for x in 0..width {
+            let uv = (y / 2) * uv_stride + (x / 2) * 2;
+            let (r, g, b) = color_space.yuv_to_rgb(y_plane[y * y_stride + x], uv_plane[uv], uv_plane[uv + 1]);
+            rgb.extend_from_slice(&[r, g, b]);
+        }
+    }
+    Ok(rgb)
+}
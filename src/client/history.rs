@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+
+/// How many server addresses are remembered
+pub const MAX_HISTORY: usize = 10;
+
+/// Key the history is kept under in eframe's storage
+pub const HISTORY_KEY: &str = "connection_history";
+
+/// Servers the client has successfully connected to, most recent first.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConnectionHistory {
+    addresses: Vec<String>,
+}
+
+impl ConnectionHistory {
+    /// The saved history, or an empty one when there is no storage or nothing readable in it.
+    pub fn load(storage: Option<&dyn eframe::Storage>) -> Self {
+        let mut history: Self = storage
+            .and_then(|storage| storage.get_string(HISTORY_KEY))
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        history.addresses.truncate(MAX_HISTORY);
+        history
+    }
+    
+    pub fn save(&self, storage: &mut dyn eframe::Storage) {
+        match serde_json::to_string(self) {
+            Ok(contents) => storage.set_string(HISTORY_KEY, contents),
+            Err(e) => tracing::warn!("Failed to save connection history: {}", e),
+        }
+    }
+    
+    /// Moves `address` to the front, dropping any earlier copy and the oldest entry past the cap.
+    pub fn record(&mut self, address: &str) {
+        let address = address.trim();
+        if address.is_empty() {
+            return;
+        }
+        
+        self.addresses.retain(|existing| existing != address);
+        self.addresses.insert(0, address.to_string());
+        self.addresses.truncate(MAX_HISTORY);
+    }
+    
+    pub fn clear(&mut self) {
+        self.addresses.clear();
+    }
+    
+    pub fn most_recent(&self) -> Option<&str> {
+        self.addresses.first().map(String::as_str)
+    }
+    
+    pub fn addresses(&self) -> &[String] {
+        &self.addresses
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+    }
+}
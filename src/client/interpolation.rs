@@ -0,0 +1,103 @@
+use std::time::{Duration, Instant};
+
+/// Synthesizes in-between frames by linearly blending the last two decoded frames.
+///
+/// Each new frame is faded in over the measured interval between server frames, so the
+/// view runs one frame behind the stream. That's a good trade at 10-15 FPS, not at 60.
+pub struct FrameInterpolator {
+    width: u32,
+    height: u32,
+    previous: Option<Vec<u8>>,
+    latest: Option<Vec<u8>>,
+    latest_at: Option<Instant>,
+    frame_interval: Duration,
+    settled: bool,
+}
+
+impl FrameInterpolator {
+    pub fn new(fps: u32) -> Self {
+        Self {
+            width: 0,
+            height: 0,
+            previous: None,
+            latest: None,
+            latest_at: None,
+            frame_interval: Duration::from_secs(1) / fps.max(1),
+            settled: true,
+        }
+    }
+
+    /// Feed a newly decoded frame.
+    pub fn push(&mut self, width: u32, height: u32, rgb_data: Vec<u8>, now: Instant) {
+        if (width, height) != (self.width, self.height) {
+            // Can't blend across a resolution change
+            self.width = width;
+            self.height = height;
+            self.previous = None;
+        } else {
+            // Start the fade from whatever is on screen right now
+            self.previous = match self.current_blend(now) {
+                Some(blend) => Some(blend),
+                None => self.latest.take(),
+            };
+        }
+
+        if let Some(last) = self.latest_at {
+            // Smooth the interval estimate so a single late frame doesn't cause a stall
+            let measured = now.duration_since(last).clamp(Duration::from_millis(1), Duration::from_secs(1));
+            self.frame_interval = self.frame_interval.mul_f32(0.8) + measured.mul_f32(0.2);
+        }
+
+        self.latest = Some(rgb_data);
+        self.latest_at = Some(now);
+        self.settled = false;
+    }
+
+    /// The frame to display at `now`, or `None` if the screen already shows the latest frame.
+    pub fn next_frame(&mut self, now: Instant) -> Option<(u32, u32, Vec<u8>)> {
+        if self.settled {
+            return None;
+        }
+
+        if let Some(blend) = self.current_blend(now) {
+            return Some((self.width, self.height, blend));
+        }
+
+        self.settled = true;
+        self.latest.clone().map(|frame| (self.width, self.height, frame))
+    }
+
+    /// Drop all state, returning the most recent frame so it can be shown as-is.
+    pub fn take_latest(&mut self) -> Option<(u32, u32, Vec<u8>)> {
+        self.previous = None;
+        self.latest_at = None;
+        self.settled = true;
+        self.latest.take().map(|frame| (self.width, self.height, frame))
+    }
+
+    pub fn frame_interval(&self) -> Duration {
+        self.frame_interval
+    }
+
+    /// Blend of previous and latest at `now`, or `None` once the fade has finished.
+    fn current_blend(&self, now: Instant) -> Option<Vec<u8>> {
+        let (previous, latest, latest_at) = match (&self.previous, &self.latest, self.latest_at) {
+            (Some(previous), Some(latest), Some(at)) if previous.len() == latest.len() => (previous, latest, at),
+            _ => return None,
+        };
+
+        let t = now.duration_since(latest_at).as_secs_f32() / self.frame_interval.as_secs_f32();
+        if t >= 1.0 {
+            return None;
+        }
+
+        let weight = (t.max(0.0) * 256.0) as u32;
+        Some(
+            previous
+                .iter()
+                .zip(latest.iter())
+                .map(|(&a, &b)| ((a as u32 * (256 - weight) + b as u32 * weight) >> 8) as u8)
+                .collect(),
+        )
+    }
+}
@@ -0,0 +1,170 @@
+use egui::Key;
+use serde::{Deserialize, Serialize};
+
+/// What the client sends for a key press.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum KeyboardMode {
+    /// The character the local layout produces; the remote types that character
+    #[default]
+    Characters,
+    /// Which physical key was pressed; the remote applies its own layout
+    Scancodes,
+}
+
+impl KeyboardMode {
+    pub const ALL: [KeyboardMode; 2] = [KeyboardMode::Characters, KeyboardMode::Scancodes];
+    
+    pub fn label(&self) -> &'static str {
+        match self {
+            KeyboardMode::Characters => "Send characters",
+            KeyboardMode::Scancodes => "Send scancodes",
+        }
+    }
+}
+
+/// USB HID usage (keyboard page) of the key at `physical_key`'s position. egui names
+/// physical keys after what they produce on a US layout, so `Key::Q` is the key right
+/// of Tab whatever it types locally.
+pub fn hid_usage(physical_key: Key) -> Option<u16> {
+    Some(match physical_key {
+        Key::A => 0x04,
+        Key::B => 0x05,
+        Key::C => 0x06,
+        Key::D => 0x07,
+        Key::E => 0x08,
+        Key::F => 0x09,
+        Key::G => 0x0A,
+        Key::H => 0x0B,
+        Key::I => 0x0C,
+        Key::J => 0x0D,
+        Key::K => 0x0E,
+        Key::L => 0x0F,
+        Key::M => 0x10,
+        Key::N => 0x11,
+        Key::O => 0x12,
+        Key::P => 0x13,
+        Key::Q => 0x14,
+        Key::R => 0x15,
+        Key::S => 0x16,
+        Key::T => 0x17,
+        Key::U => 0x18,
+        Key::V => 0x19,
+        Key::W => 0x1A,
+        Key::X => 0x1B,
+        Key::Y => 0x1C,
+        Key::Z => 0x1D,
+        Key::Num1 => 0x1E,
+        Key::Num2 => 0x1F,
+        Key::Num3 => 0x20,
+        Key::Num4 => 0x21,
+        Key::Num5 => 0x22,
+        Key::Num6 => 0x23,
+        Key::Num7 => 0x24,
+        Key::Num8 => 0x25,
+        Key::Num9 => 0x26,
+        Key::Num0 => 0x27,
+        Key::Enter => 0x28,
+        Key::Escape => 0x29,
+        Key::Backspace => 0x2A,
+        Key::Tab => 0x2B,
+        Key::Space => 0x2C,
+        Key::Minus => 0x2D,
+        Key::Equals => 0x2E,
+        Key::OpenBracket => 0x2F,
+        Key::CloseBracket => 0x30,
+        Key::Backslash => 0x31,
+        Key::Semicolon => 0x33,
+        Key::Quote => 0x34,
+        Key::Backtick => 0x35,
+        Key::Comma => 0x36,
+        Key::Period => 0x37,
+        Key::Slash => 0x38,
+        Key::F1 => 0x3A,
+        Key::F2 => 0x3B,
+        Key::F3 => 0x3C,
+        Key::F4 => 0x3D,
+        Key::F5 => 0x3E,
+        Key::F6 => 0x3F,
+        Key::F7 => 0x40,
+        Key::F8 => 0x41,
+        Key::F9 => 0x42,
+        Key::F10 => 0x43,
+        Key::F11 => 0x44,
+        Key::F12 => 0x45,
+        Key::Insert => 0x49,
+        Key::Home => 0x4A,
+        Key::PageUp => 0x4B,
+        Key::Delete => 0x4C,
+        Key::End => 0x4D,
+        Key::PageDown => 0x4E,
+        Key::ArrowRight => 0x4F,
+        Key::ArrowLeft => 0x50,
+        Key::ArrowDown => 0x51,
+        Key::ArrowUp => 0x52,
+        _ => return None,
+    })
+}
+
+/// Name of `key` as the server's `InputHandler` knows it, for keys sent as characters.
+pub fn format_key(key: Key) -> Option<String> {
+    Some(match key {
+        Key::A => "a".to_string(),
+        Key::B => "b".to_string(),
+        Key::C => "c".to_string(),
+        Key::D => "d".to_string(),
+        Key::E => "e".to_string(),
+        Key::F => "f".to_string(),
+        Key::G => "g".to_string(),
+        Key::H => "h".to_string(),
+        Key::I => "i".to_string(),
+        Key::J => "j".to_string(),
+        Key::K => "k".to_string(),
+        Key::L => "l".to_string(),
+        Key::M => "m".to_string(),
+        Key::N => "n".to_string(),
+        Key::O => "o".to_string(),
+        Key::P => "p".to_string(),
+        Key::Q => "q".to_string(),
+        Key::R => "r".to_string(),
+        Key::S => "s".to_string(),
+        Key::T => "t".to_string(),
+        Key::U => "u".to_string(),
+        Key::V => "v".to_string(),
+        Key::W => "w".to_string(),
+        Key::X => "x".to_string(),
+        Key::Y => "y".to_string(),
+        Key::Z => "z".to_string(),
+        Key::Num0 => "0".to_string(),
+        Key::Num1 => "1".to_string(),
+        Key::Num2 => "2".to_string(),
+        Key::Num3 => "3".to_string(),
+        Key::Num4 => "4".to_string(),
+        Key::Num5 => "5".to_string(),
+        Key::Num6 => "6".to_string(),
+        Key::Num7 => "7".to_string(),
+        Key::Num8 => "8".to_string(),
+        Key::Num9 => "9".to_string(),
+        Key::Space => " ".to_string(),
+        Key::Enter => "Return".to_string(),
+        Key::Escape => "Escape".to_string(),
+        Key::Backspace => "BackSpace".to_string(),
+        Key::Tab => "Tab".to_string(),
+        Key::ArrowDown => "Down".to_string(),
+        Key::ArrowLeft => "Left".to_string(),
+        Key::ArrowRight => "Right".to_string(),
+        Key::ArrowUp => "Up".to_string(),
+        Key::F1 => "F1".to_string(),
+        Key::F2 => "F2".to_string(),
+        Key::F3 => "F3".to_string(),
+        Key::F4 => "F4".to_string(),
+        Key::F5 => "F5".to_string(),
+        Key::F6 => "F6".to_string(),
+        Key::F7 => "F7".to_string(),
+        Key::F8 => "F8".to_string(),
+        Key::F9 => "F9".to_string(),
+        Key::F10 => "F10".to_string(),
+        Key::F11 => "F11".to_string(),
+        Key::F12 => "F12".to_string(),
+        _ => return None,
+    })
+}
@@ -0,0 +1,138 @@
+use std::time::{Duration, Instant};
+use anyhow::Result;
+
+use crate::client::connection::Connection;
+use crate::common::protocol::Message;
+
+/// What one load-test connection saw.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionReport {
+    /// From opening the socket to a successful authentication
+    pub connect: Option<Duration>,
+    /// From opening the socket to the first frame
+    pub first_frame: Option<Duration>,
+    pub frames: u64,
+    /// Encoded frame data received, excluding protocol overhead
+    pub bytes: u64,
+    pub error: Option<String>,
+}
+
+/// Connect like the client does, then consume frames for `duration`.
+pub async fn run_connection(addr: &str, code: &str, duration: Duration, encryption: bool) -> ConnectionReport {
+    let mut report = ConnectionReport::default();
+    if let Err(e) = drive_connection(addr, code, duration, encryption, &mut report).await {
+        report.error = Some(format!("{:#}", e));
+    }
+    report
+}
+
+async fn drive_connection(
+    addr: &str,
+    code: &str,
+    duration: Duration,
+    encryption: bool,
+    report: &mut ConnectionReport,
+) -> Result<()> {
+    let started = Instant::now();
+    let (mut connection, _, _) = Connection::new();
+    connection.set_encryption(encryption);
+    let (mut rx, tx) = connection.connect(addr, code).await?;
+    report.connect = Some(started.elapsed());
+    
+    let deadline = tokio::time::Instant::now() + duration;
+    loop {
+        let message = match tokio::time::timeout_at(deadline, rx.recv()).await {
+            Ok(Some(message)) => message,
+            Ok(None) => anyhow::bail!("Server closed the connection"),
+            Err(_) => break,
+        };
+        
+        if let Message::Error { message, .. } = &message {
+            anyhow::bail!("Server error: {}", message);
+        }
+        if matches!(message, Message::Disconnect) {
+            anyhow::bail!("Server disconnected the session");
+        }
+        
+        if let Some(bytes) = frame_payload_bytes(&message) {
+            report.first_frame.get_or_insert_with(|| started.elapsed());
+            report.frames += 1;
+            report.bytes += bytes as u64;
+            let _ = tx.send(frame_ack(&message));
+        }
+    }
+    
+    let _ = tx.send(Message::Disconnect);
+    Ok(())
+}
+
+/// Encoded frame data in a frame message, `None` for anything else. Keyframe chunks
+/// count as frames of their own.
+pub fn frame_payload_bytes(message: &Message) -> Option<usize> {
+    match message {
+        Message::ScreenFrame { data, .. } => Some(data.len()),
+        Message::DeltaFrame { tiles, .. } | Message::KeyFrameChunk { tiles, .. } => {
+            Some(tiles.iter().map(|tile| tile.data.len()).sum())
+        }
+        _ => None,
+    }
+}
+
+// Acks keep the server's quality controller measuring round trips, as with a real client
+fn frame_ack(message: &Message) -> Message {
+    let timestamp = match message {
+        Message::ScreenFrame { timestamp, .. }
+        | Message::DeltaFrame { timestamp, .. }
+        | Message::KeyFrameChunk { timestamp, .. } => *timestamp,
+        _ => 0,
+    };
+    let received_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    Message::FrameAck { timestamp, received_at }
+}
+
+/// Percentiles of a set of latencies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LatencySummary {
+    pub count: usize,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl LatencySummary {
+    /// `None` when there is nothing to summarize.
+    pub fn from_samples(mut samples: Vec<Duration>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        samples.sort();
+        
+        // Nearest-rank: the smallest sample at or above the given share of samples
+        let rank = |percent: usize| samples[(samples.len() * percent).div_ceil(100).max(1) - 1];
+        Some(Self {
+            count: samples.len(),
+            p50: rank(50),
+            p90: rank(90),
+            p99: rank(99),
+            max: samples[samples.len() - 1],
+        })
+    }
+}
+
+impl std::fmt::Display for LatencySummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "n={} p50={:.1}ms p90={:.1}ms p99={:.1}ms max={:.1}ms",
+            self.count,
+            self.p50.as_secs_f64() * 1000.0,
+            self.p90.as_secs_f64() * 1000.0,
+            self.p99.as_secs_f64() * 1000.0,
+            self.max.as_secs_f64() * 1000.0,
+        )
+    }
+}
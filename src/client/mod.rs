@@ -2,4 +2,25 @@ pub mod app;
 pub mod connection;
 pub mod renderer;
 pub mod theme;
-pub mod h264_decoder;
\ No newline at end of file
+pub mod h264_decoder;
+pub mod interpolation;
+pub mod mouse_throttle;
+pub mod screenshot;
+pub mod view;
+pub mod history;
+pub mod resend;
+pub mod security;
+pub mod modifiers;
+pub mod reconnect;
+pub mod frame_order;
+pub mod loadtest;
+pub mod keymap;
+pub mod stream_decoder;
+pub mod toasts;
+pub mod refresh;
+pub mod region;
+pub mod backlog;
+pub mod stream_pause;
+pub mod desync;
+pub mod touch;
+pub mod code_entry;
@@ -0,0 +1,73 @@
+use crate::common::protocol::{Message, Modifiers};
+
+/// Key names the server maps to enigo's modifier keys, in press order.
+pub const MODIFIER_KEYS: [&str; 4] = ["Control", "Alt", "Shift", "Meta"];
+
+/// egui reports modifiers as state on every event rather than as key events of
+/// their own, so a Shift pressed alone never reaches the remote machine. This
+/// tracks what the remote side has been told is held and turns changes into
+/// `KeyEvent`s for the modifier keys.
+#[derive(Debug, Default)]
+pub struct ModifierTracker {
+    held: Modifiers,
+}
+
+impl ModifierTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Modifiers the remote side currently has held down.
+    pub fn held(&self) -> Modifiers {
+        self.held.clone()
+    }
+    
+    /// Presses and releases that bring the remote side in line with `current`.
+    /// Releases come first so a quick Shift-to-Ctrl switch never has both held.
+    pub fn update(&mut self, current: egui::Modifiers) -> Vec<Message> {
+        let wanted = Modifiers {
+            shift: current.shift,
+            ctrl: current.ctrl,
+            alt: current.alt,
+            // On macOS Cmd is its own key; elsewhere egui reports Ctrl as both ctrl and command
+            meta: current.mac_cmd,
+        };
+        
+        let mut events = Vec::new();
+        for pressed in [false, true] {
+            for key in MODIFIER_KEYS {
+                let (was, now) = (is_set(&self.held, key), is_set(&wanted, key));
+                if was != now && now == pressed {
+                    events.push(key_event(key, pressed, &wanted));
+                }
+            }
+        }
+        self.held = wanted;
+        events
+    }
+    
+    /// Releases for everything still held, e.g. when the window loses focus and
+    /// would otherwise never see the key come back up.
+    pub fn release_all(&mut self) -> Vec<Message> {
+        self.update(egui::Modifiers::NONE)
+    }
+}
+
+fn is_set(modifiers: &Modifiers, key: &str) -> bool {
+    match key {
+        "Control" => modifiers.ctrl,
+        "Alt" => modifiers.alt,
+        "Shift" => modifiers.shift,
+        "Meta" => modifiers.meta,
+        _ => false,
+    }
+}
+
+fn key_event(key: &str, pressed: bool, modifiers: &Modifiers) -> Message {
+    Message::KeyEvent {
+        key: key.to_string(),
+        pressed,
+        repeat: false,
+        modifiers: modifiers.clone(),
+    }
+}
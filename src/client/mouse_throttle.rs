@@ -0,0 +1,47 @@
+use std::time::{Duration, Instant};
+
+/// Default cap on how often pointer positions are sent to the server
+pub const DEFAULT_MOUSE_RATE_HZ: u32 = 120;
+
+/// Limits `MouseMove` traffic to a fixed rate while never losing the final position.
+///
+/// Moves that arrive too soon after the last one sent are parked; only the most recent
+/// parked position is sent once the interval has elapsed.
+pub struct MouseMoveThrottle {
+    min_interval: Duration,
+    last_sent: Option<Instant>,
+    pending: Option<(i32, i32)>,
+}
+
+impl MouseMoveThrottle {
+    pub fn new(rate_hz: u32) -> Self {
+        Self {
+            min_interval: Duration::from_secs(1) / rate_hz.max(1),
+            last_sent: None,
+            pending: None,
+        }
+    }
+    
+    /// Record a new pointer position. Returns the position if it should be sent right away.
+    pub fn move_to(&mut self, x: i32, y: i32, now: Instant) -> Option<(i32, i32)> {
+        self.pending = Some((x, y));
+        self.poll(now)
+    }
+    
+    /// Release the parked position once the rate limit allows it. Call this every UI tick.
+    pub fn poll(&mut self, now: Instant) -> Option<(i32, i32)> {
+        let due = self.last_sent.map_or(true, |sent| now.duration_since(sent) >= self.min_interval);
+        if !due {
+            return None;
+        }
+        
+        let position = self.pending.take()?;
+        self.last_sent = Some(now);
+        Some(position)
+    }
+    
+    /// Drop any parked move, e.g. because a click just carried the pointer position.
+    pub fn cancel_pending(&mut self) {
+        self.pending = None;
+    }
+}
@@ -0,0 +1,68 @@
+use std::time::Duration;
+
+/// Attempts to get a dropped connection back before giving up
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 6;
+
+const INITIAL_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectAttempt {
+    /// 1 for the first attempt after the connection dropped
+    pub number: u32,
+    /// How long to wait before making it
+    pub delay: Duration,
+}
+
+/// How many times a dropped connection is retried, with the wait doubling after each
+/// failure. A successful reconnect restores the full budget.
+#[derive(Debug, Clone)]
+pub struct ReconnectBudget {
+    max_attempts: u32,
+    attempts: u32,
+    initial_delay: Duration,
+    max_delay: Duration,
+}
+
+impl ReconnectBudget {
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            attempts: 0,
+            initial_delay: INITIAL_DELAY,
+            max_delay: MAX_DELAY,
+        }
+    }
+    
+    pub fn with_delays(mut self, initial: Duration, max: Duration) -> Self {
+        self.initial_delay = initial;
+        self.max_delay = max;
+        self
+    }
+    
+    /// The next attempt to make, or `None` once the budget is spent.
+    pub fn next_attempt(&mut self) -> Option<ReconnectAttempt> {
+        if self.attempts >= self.max_attempts {
+            return None;
+        }
+        
+        let delay = self.initial_delay
+            .checked_mul(1 << self.attempts.min(16))
+            .map_or(self.max_delay, |delay| delay.min(self.max_delay));
+        self.attempts += 1;
+        Some(ReconnectAttempt { number: self.attempts, delay })
+    }
+    
+    /// The connection is back; the next drop starts over.
+    pub fn reset(&mut self) {
+        self.attempts = 0;
+    }
+    
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+    
+    pub fn is_exhausted(&self) -> bool {
+        self.attempts >= self.max_attempts
+    }
+}
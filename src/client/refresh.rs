@@ -0,0 +1,53 @@
+use std::time::{Duration, Instant};
+
+/// Least time between two refresh requests, so holding the hotkey can't flood the server
+/// with keyframes.
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// How long the spinner waits for the fresh frame before giving up on it.
+pub const REFRESH_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// The user's "refresh screen" action: asks the server for a complete frame when deltas or
+/// a decode glitch left stale regions behind, at most once per `REFRESH_INTERVAL`.
+#[derive(Debug)]
+pub struct RefreshRequest {
+    last_sent: Option<Instant>,
+    waiting: bool,
+}
+
+impl Default for RefreshRequest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RefreshRequest {
+    pub fn new() -> Self {
+        Self { last_sent: None, waiting: false }
+    }
+    
+    /// Record a refresh at `now`. Returns whether to send `RequestKeyframe`, which is
+    /// not the case while the previous request is too recent.
+    pub fn request(&mut self, now: Instant) -> bool {
+        if !self.can_request(now) {
+            return false;
+        }
+        self.last_sent = Some(now);
+        self.waiting = true;
+        true
+    }
+    
+    pub fn can_request(&self, now: Instant) -> bool {
+        self.last_sent.map_or(true, |sent| now.duration_since(sent) >= REFRESH_INTERVAL)
+    }
+    
+    /// Whether to show the spinner: a request is out and its frame hasn't arrived yet.
+    pub fn is_waiting(&self, now: Instant) -> bool {
+        self.waiting && self.last_sent.is_some_and(|sent| now.duration_since(sent) < REFRESH_TIMEOUT)
+    }
+    
+    /// A complete frame arrived, so whatever was requested is on screen.
+    pub fn frame_received(&mut self) {
+        self.waiting = false;
+    }
+}
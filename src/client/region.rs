@@ -0,0 +1,54 @@
+use crate::common::foveation::Region;
+
+/// Drawing the lossless region: armed from the toolbar, after which a drag over the remote
+/// screen marks it. Mouse input isn't forwarded while it's armed.
+#[derive(Debug, Default)]
+pub struct RegionPicker {
+    armed: bool,
+    // Where the drag started, in remote frame pixels
+    start: Option<(u32, u32)>,
+}
+
+impl RegionPicker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// The next drag over the screen marks the region.
+    pub fn arm(&mut self) {
+        self.armed = true;
+        self.start = None;
+    }
+    
+    pub fn cancel(&mut self) {
+        self.armed = false;
+        self.start = None;
+    }
+    
+    pub fn is_armed(&self) -> bool {
+        self.armed
+    }
+    
+    /// The button went down at `at`.
+    pub fn press(&mut self, at: (u32, u32)) {
+        if self.armed {
+            self.start = Some(at);
+        }
+    }
+    
+    /// The rectangle dragged out so far with the pointer at `at`, to draw while the button is held.
+    pub fn preview(&self, at: (u32, u32)) -> Option<Region> {
+        Region::from_corners(self.start?, at)
+    }
+    
+    /// The button came up at `at`. Gives the finished region, which disarms the picker; a
+    /// click that dragged out no area leaves it armed.
+    pub fn release(&mut self, at: (u32, u32)) -> Option<Region> {
+        let region = self.preview(at);
+        self.start = None;
+        if region.is_some() {
+            self.armed = false;
+        }
+        region
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crate::common::protocol::{Message, TouchPhase};
+
+/// Presses older than this are not replayed; releases always are, so nothing stays held.
+pub const RESEND_WINDOW: Duration = Duration::from_secs(10);
+
+/// Most messages held for replay; the oldest presses go first when it fills up.
+pub const RESEND_CAPACITY: usize = 256;
+
+/// Messages that are fine to lose with the connection: superseded by the next one, or
+/// re-sent by every connect anyway.
+pub fn is_droppable(message: &Message) -> bool {
+    matches!(
+        message,
+        Message::MouseMove { .. }
+            | Message::MouseScroll { .. }
+            | Message::Touch { phase: TouchPhase::Move, .. }
+            | Message::Gesture { .. }
+            | Message::FrameAck { .. }
+            | Message::Ping { .. }
+            | Message::Pong { .. }
+            | Message::NetworkStats { .. }
+            | Message::Capabilities { .. }
+            | Message::SetCursorOverlay { .. }
+            | Message::SetContentProfile { .. }
+            | Message::SetDeliveryMode { .. }
+            | Message::ViewportResize { .. }
+            | Message::LosslessRegion { .. }
+            | Message::StartStream
+            | Message::RequestKeyframe
+    )
+}
+
+// Releasing something held down; replaying these late is always safe
+fn is_release(message: &Message) -> bool {
+    matches!(
+        message,
+        Message::KeyEvent { pressed: false, .. }
+            | Message::PhysicalKeyEvent { pressed: false, .. }
+            | Message::MouseClick { pressed: false, .. }
+            | Message::Touch { phase: TouchPhase::End | TouchPhase::Cancel, .. }
+    )
+}
+
+/// Input and control messages that never reached the server, kept until the next connection
+/// so a network blip can't leave a key stuck down or swallow a click.
+#[derive(Debug, Default)]
+pub struct ResendQueue {
+    pending: VecDeque<(Instant, Message)>,
+}
+
+impl ResendQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Hold `message` for replay unless it's droppable.
+    pub fn push(&mut self, message: Message, now: Instant) {
+        if is_droppable(&message) {
+            return;
+        }
+        
+        if self.pending.len() >= RESEND_CAPACITY {
+            let oldest_press = self.pending.iter().position(|(_, m)| !is_release(m)).unwrap_or(0);
+            self.pending.remove(oldest_press);
+        }
+        self.pending.push_back((now, message));
+    }
+    
+    /// Everything to send on the new connection, oldest first. Presses held longer than
+    /// [`RESEND_WINDOW`] are dropped rather than surprising the user.
+    pub fn take_replay(&mut self, now: Instant) -> Vec<Message> {
+        self.pending
+            .drain(..)
+            .filter(|(queued_at, message)| is_release(message) || now.duration_since(*queued_at) <= RESEND_WINDOW)
+            .map(|(_, message)| message)
+            .collect()
+    }
+    
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
@@ -0,0 +1,40 @@
+use anyhow::{Result, Context, anyhow};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// `~/Pictures` if it exists, otherwise the home directory, otherwise the working directory.
+pub fn default_screenshot_dir() -> PathBuf {
+    let home = std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from);
+    
+    match home {
+        Some(home) if home.join("Pictures").is_dir() => home.join("Pictures"),
+        Some(home) => home,
+        None => PathBuf::from("."),
+    }
+}
+
+/// File name stamped with the capture time in milliseconds, so repeated shots never collide.
+pub fn screenshot_filename(time: SystemTime) -> String {
+    let millis = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+    format!("vox_screenshot_{}.png", millis)
+}
+
+/// Write an RGB frame to a timestamped PNG in `dir`, returning the file path.
+pub fn save_screenshot(dir: &Path, width: u32, height: u32, rgb_data: &[u8]) -> Result<PathBuf> {
+    let image = image::RgbImage::from_raw(width, height, rgb_data.to_vec())
+        .ok_or_else(|| anyhow!(
+            "Frame is {} bytes, expected {} for {}x{}",
+            rgb_data.len(), width as usize * height as usize * 3, width, height
+        ))?;
+    
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create {}", dir.display()))?;
+    
+    let path = dir.join(screenshot_filename(SystemTime::now()));
+    image.save_with_format(&path, image::ImageFormat::Png)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    
+    Ok(path)
+}
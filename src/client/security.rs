@@ -0,0 +1,86 @@
+use sha2::{Digest, Sha256};
+use crate::common::crypto::Cipher;
+
+/// The cipher of a session that didn't negotiate one
+pub const CIPHER: &str = "AES-256-GCM";
+pub const KEY_EXCHANGE: &str = "X25519";
+
+/// What protects the current connection, shown in the client's connection info window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecurityInfo {
+    /// False for a `--no-encryption` loopback benchmarking session
+    pub encrypted: bool,
+    /// What the session negotiated, when encrypted
+    pub cipher: Cipher,
+    /// Fingerprint of the server's key-exchange public key, when encrypted
+    pub server_key_fingerprint: Option<String>,
+    /// Whether the server accepted our access code
+    pub authenticated: bool,
+    /// Whether video arrives unencrypted, as agreed for a network that is encrypted already
+    pub plaintext_video: bool,
+}
+
+impl SecurityInfo {
+    pub fn encrypted(server_public_key: &[u8]) -> Self {
+        Self {
+            encrypted: true,
+            cipher: Cipher::Aes256Gcm,
+            server_key_fingerprint: Some(fingerprint(server_public_key)),
+            authenticated: false,
+            plaintext_video: false,
+        }
+    }
+    
+    pub fn plaintext() -> Self {
+        Self {
+            encrypted: false,
+            cipher: Cipher::Aes256Gcm,
+            server_key_fingerprint: None,
+            authenticated: false,
+            plaintext_video: false,
+        }
+    }
+    
+    pub fn with_cipher(mut self, cipher: Cipher) -> Self {
+        self.cipher = cipher;
+        self
+    }
+    
+    /// Label/value pairs in display order.
+    pub fn rows(&self) -> Vec<(&'static str, String)> {
+        let mut rows = Vec::new();
+        if self.encrypted {
+            rows.push(("Cipher", self.cipher.name().to_string()));
+            rows.push(("Key exchange", KEY_EXCHANGE.to_string()));
+            if self.plaintext_video {
+                rows.push(("Video", "Unencrypted (trusted network)".to_string()));
+            }
+        } else {
+            rows.push(("Cipher", "None (unencrypted benchmarking session)".to_string()));
+            rows.push(("Key exchange", "None".to_string()));
+        }
+        rows.push((
+            "Server key",
+            self.server_key_fingerprint.clone().unwrap_or_else(|| "-".to_string()),
+        ));
+        // Keys are ephemeral and nothing is pinned, so the access code is the only check
+        rows.push((
+            "Authentication",
+            if self.authenticated { "Access code" } else { "Not authenticated" }.to_string(),
+        ));
+        rows.push((
+            "Client build",
+            if cfg!(debug_assertions) { "Debug" } else { "Release" }.to_string(),
+        ));
+        rows
+    }
+}
+
+/// SHA-256 of a public key as colon-separated hex, like SSH and TLS fingerprints.
+pub fn fingerprint(public_key: &[u8]) -> String {
+    Sha256::digest(public_key)
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(":")
+}
@@ -0,0 +1,43 @@
+use crate::client::h264_decoder::H264Decoder;
+use crate::common::frame_processor::FrameProcessor;
+
+/// What the client has decoded of one session's stream: the last full frame, the tile
+/// processor that patches it and the H.264 decoder. None of it may outlive the session,
+/// since the next server can stream a different resolution.
+#[derive(Default)]
+pub struct StreamDecoder {
+    pub current_frame: Option<Vec<u8>>,
+    pub frame_processor: Option<FrameProcessor>,
+    pub h264_decoder: Option<H264Decoder>,
+}
+
+impl StreamDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Makes sure the frame processor is sized for `width`x`height` frames.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        let current = self.frame_processor.as_ref().map(FrameProcessor::dimensions);
+        if current != Some((width, height)) {
+            self.frame_processor = Some(FrameProcessor::new(width, height));
+        }
+    }
+    
+    /// Forgets the stream. The H.264 decoder is flushed first so FFmpeg lets go of its
+    /// buffered frames now rather than whenever the next session replaces it.
+    pub fn reset(&mut self) {
+        if let Some(mut decoder) = self.h264_decoder.take() {
+            match decoder.flush() {
+                Ok(frames) => tracing::debug!("Discarded {} buffered H.264 frame(s)", frames.len()),
+                Err(e) => tracing::debug!("Failed to flush H.264 decoder: {}", e),
+            }
+        }
+        self.frame_processor = None;
+        self.current_frame = None;
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.current_frame.is_none() && self.frame_processor.is_none() && self.h264_decoder.is_none()
+    }
+}
@@ -0,0 +1,62 @@
+use std::time::{Duration, Instant};
+
+use crate::common::protocol::Message;
+
+/// How long the window has to stay minimized before the stream is paused, so minimizing
+/// it for a moment or flicking between windows doesn't stop and restart the stream.
+pub const PAUSE_DELAY: Duration = Duration::from_secs(2);
+
+/// Stops the stream while the client window is minimized and nobody can see it, saving
+/// bandwidth and the server's capture and encode work, and resumes it on restore.
+#[derive(Debug)]
+pub struct StreamPause {
+    hidden_since: Option<Instant>,
+    paused: bool,
+}
+
+impl Default for StreamPause {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StreamPause {
+    pub fn new() -> Self {
+        Self { hidden_since: None, paused: false }
+    }
+    
+    /// Whether the window is `visible` at `now`. Returns `StopStream` once it has been
+    /// hidden for `PAUSE_DELAY`, and `StartStream` as soon as it is back after a pause;
+    /// the server follows that with a keyframe.
+    pub fn observe(&mut self, visible: bool, now: Instant) -> Option<Message> {
+        if visible {
+            self.hidden_since = None;
+            return std::mem::replace(&mut self.paused, false).then_some(Message::StartStream);
+        }
+        
+        let hidden_since = *self.hidden_since.get_or_insert(now);
+        if !self.paused && now.duration_since(hidden_since) >= PAUSE_DELAY {
+            self.paused = true;
+            return Some(Message::StopStream);
+        }
+        None
+    }
+    
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+    
+    /// Time left until a hidden window pauses the stream, to wake up for it; `None` when
+    /// no pause is pending.
+    pub fn pause_due_in(&self, now: Instant) -> Option<Duration> {
+        match self.hidden_since {
+            Some(since) if !self.paused => Some(PAUSE_DELAY.saturating_sub(now.duration_since(since))),
+            _ => None,
+        }
+    }
+    
+    /// A new connection streams from the start.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
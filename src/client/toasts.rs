@@ -0,0 +1,75 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How long a toast stays up unless dismissed.
+pub const TOAST_DURATION: Duration = Duration::from_secs(6);
+
+/// Most toasts shown at once; a new one pushes out the oldest.
+pub const MAX_TOASTS: usize = 3;
+
+/// A notification forwarded from the remote machine.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Toast {
+    /// Unique for the lifetime of the `Toasts`, so each keeps its own window
+    pub id: u64,
+    pub title: String,
+    pub body: String,
+    pub app: String,
+    pub shown_at: Instant,
+}
+
+/// The notification toasts on screen, newest last. They dismiss themselves after
+/// `TOAST_DURATION`, and never more than `MAX_TOASTS` are up, however fast they arrive.
+#[derive(Debug, Default)]
+pub struct Toasts {
+    toasts: VecDeque<Toast>,
+    next_id: u64,
+    // Pushed out by newer ones before they expired
+    overflowed: u64,
+}
+
+impl Toasts {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn push(&mut self, title: String, body: String, app: String, now: Instant) {
+        self.expire(now);
+        if self.toasts.len() == MAX_TOASTS {
+            self.toasts.pop_front();
+            self.overflowed += 1;
+        }
+        self.toasts.push_back(Toast { id: self.next_id, title, body, app, shown_at: now });
+        self.next_id += 1;
+    }
+    
+    /// Drop toasts that have been up for `TOAST_DURATION`.
+    pub fn expire(&mut self, now: Instant) {
+        self.toasts.retain(|toast| now.saturating_duration_since(toast.shown_at) < TOAST_DURATION);
+    }
+    
+    pub fn dismiss(&mut self, id: u64) {
+        self.toasts.retain(|toast| toast.id != id);
+    }
+    
+    pub fn clear(&mut self) {
+        self.toasts.clear();
+    }
+    
+    pub fn iter(&self) -> impl Iterator<Item = &Toast> {
+        self.toasts.iter()
+    }
+    
+    pub fn len(&self) -> usize {
+        self.toasts.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.toasts.is_empty()
+    }
+    
+    /// Toasts pushed out by newer ones before anyone could read them.
+    pub fn overflowed(&self) -> u64 {
+        self.overflowed
+    }
+}
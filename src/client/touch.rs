@@ -0,0 +1,152 @@
+use std::collections::BTreeMap;
+
+use crate::common::protocol::{Message, TouchGesture, TouchPhase};
+
+/// Remote pixels both fingers have to move together before a two-finger scroll is sent
+pub const SCROLL_STEP: f32 = 20.0;
+
+/// How much the distance between two fingers has to change, as a fraction, before a
+/// pinch is sent
+pub const PINCH_STEP: f32 = 0.1;
+
+// Where the two fingers of a gesture were when the last scroll or pinch went out
+#[derive(Debug, Clone, Copy)]
+struct GestureAnchor {
+    fingers: (u64, u64),
+    center: (f32, f32),
+    spread: f32,
+}
+
+/// Turns touches on the remote screen into input messages.
+///
+/// A single finger or pen goes as `Touch` events, which hosts without touch injection play
+/// as the left mouse button. When a second finger lands the first one is cancelled, and the
+/// pair sends `Gesture`s until every finger has lifted, so a pinch never drags or taps.
+#[derive(Debug, Default)]
+pub struct TouchInput {
+    // Fingers down, at their latest remote position
+    contacts: BTreeMap<u64, (i32, i32)>,
+    // The finger being sent as a touch
+    single: Option<u64>,
+    gesture: Option<GestureAnchor>,
+}
+
+impl TouchInput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Messages for a touch event at remote pixel `position`. `pressure` is 0.0 to 1.0 where
+    /// the device reports it.
+    pub fn event(&mut self, id: u64, phase: TouchPhase, position: (i32, i32), pressure: Option<f32>) -> Vec<Message> {
+        let touch = |phase| Message::Touch {
+            id,
+            phase,
+            x: position.0,
+            y: position.1,
+            pressure: pressure.map(|pressure| pressure.clamp(0.0, 1.0)),
+        };
+        
+        match phase {
+            TouchPhase::Start => {
+                self.contacts.insert(id, position);
+                // Fingers beyond the first two take no part
+                if self.gesture.is_some() {
+                    return Vec::new();
+                }
+                let Some(first) = self.single else {
+                    self.single = Some(id);
+                    return vec![touch(TouchPhase::Start)];
+                };
+                self.start_gesture(first, id)
+            }
+            TouchPhase::Move => {
+                // A finger that landed off the screen is never followed
+                let Some(contact) = self.contacts.get_mut(&id) else {
+                    return Vec::new();
+                };
+                *contact = position;
+                if self.single == Some(id) {
+                    return vec![touch(TouchPhase::Move)];
+                }
+                self.gesture_step()
+            }
+            TouchPhase::End | TouchPhase::Cancel => {
+                if self.contacts.remove(&id).is_none() {
+                    return Vec::new();
+                }
+                // Lifting one finger of a gesture doesn't go back to touching
+                if self.contacts.is_empty() {
+                    self.gesture = None;
+                }
+                if self.single == Some(id) {
+                    self.single = None;
+                    return vec![touch(phase)];
+                }
+                Vec::new()
+            }
+        }
+    }
+    
+    /// Whether any finger is on the remote screen.
+    pub fn is_touching(&self) -> bool {
+        !self.contacts.is_empty()
+    }
+    
+    /// Cancel whatever is still down, e.g. when the window loses focus mid-touch.
+    pub fn release_all(&mut self) -> Vec<Message> {
+        let cancel = self.single.take()
+            .and_then(|id| self.contacts.get(&id).map(|&(x, y)| (id, x, y)))
+            .map(|(id, x, y)| Message::Touch { id, phase: TouchPhase::Cancel, x, y, pressure: None });
+        *self = Self::new();
+        cancel.into_iter().collect()
+    }
+    
+    fn start_gesture(&mut self, first: u64, second: u64) -> Vec<Message> {
+        self.single = None;
+        let (a, b) = (self.contacts[&first], self.contacts[&second]);
+        self.gesture = Some(GestureAnchor {
+            fingers: (first, second),
+            center: center(a, b),
+            spread: spread(a, b),
+        });
+        vec![Message::Touch { id: first, phase: TouchPhase::Cancel, x: a.0, y: a.1, pressure: None }]
+    }
+    
+    fn gesture_step(&mut self) -> Vec<Message> {
+        let Some(anchor) = self.gesture.as_mut() else {
+            return Vec::new();
+        };
+        let (Some(&a), Some(&b)) = (self.contacts.get(&anchor.fingers.0), self.contacts.get(&anchor.fingers.1)) else {
+            return Vec::new();
+        };
+        
+        let (now_center, now_spread) = (center(a, b), spread(a, b));
+        let at = (now_center.0.round() as i32, now_center.1.round() as i32);
+        let mut messages = Vec::new();
+        
+        let (delta_x, delta_y) = (now_center.0 - anchor.center.0, now_center.1 - anchor.center.1);
+        if delta_x.abs() >= SCROLL_STEP || delta_y.abs() >= SCROLL_STEP {
+            anchor.center = now_center;
+            messages.push(Message::Gesture { gesture: TouchGesture::Scroll { delta_x, delta_y }, x: at.0, y: at.1 });
+        }
+        
+        // Fingers that landed on the same pixel have no spread to compare with yet
+        if anchor.spread <= 0.0 {
+            anchor.spread = now_spread;
+        } else if (now_spread / anchor.spread - 1.0).abs() >= PINCH_STEP {
+            let scale = now_spread / anchor.spread;
+            anchor.spread = now_spread;
+            messages.push(Message::Gesture { gesture: TouchGesture::Pinch { scale }, x: at.0, y: at.1 });
+        }
+        messages
+    }
+}
+
+fn center(a: (i32, i32), b: (i32, i32)) -> (f32, f32) {
+    ((a.0 + b.0) as f32 / 2.0, (a.1 + b.1) as f32 / 2.0)
+}
+
+fn spread(a: (i32, i32), b: (i32, i32)) -> f32 {
+    ((a.0 - b.0) as f32).hypot((a.1 - b.1) as f32)
+}
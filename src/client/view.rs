@@ -0,0 +1,201 @@
+use anyhow::{Result, Context};
+use egui::{PointerButton, Pos2, Rect, Vec2};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use crate::client::keymap::KeyboardMode;
+use crate::common::display_layout::DisplayLayout;
+use crate::common::foveation::Region;
+use crate::common::protocol::{MouseButton, TouchPhase};
+use crate::common::delivery::DeliveryMode;
+use crate::common::quality::ContentProfile;
+
+/// How the remote screen is sized inside the client window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ViewMode {
+    /// Whole screen visible, scaled down (or up) to fit
+    #[default]
+    Fit,
+    /// One remote pixel per physical pixel, scrolling when larger than the window
+    ActualSize,
+    /// Cover the whole window, cropping the edges that don't fit
+    Fill,
+}
+
+impl ViewMode {
+    pub const ALL: [ViewMode; 3] = [ViewMode::Fit, ViewMode::ActualSize, ViewMode::Fill];
+    
+    pub fn label(&self) -> &'static str {
+        match self {
+            ViewMode::Fit => "Fit to window",
+            ViewMode::ActualSize => "Actual size (1:1)",
+            ViewMode::Fill => "Fill window",
+        }
+    }
+    
+    /// Points per remote pixel for an `image` of remote pixels shown in `available` points.
+    pub fn scale(&self, image: Vec2, available: Vec2, pixels_per_point: f32) -> f32 {
+        match self {
+            ViewMode::Fit => (available.x / image.x).min(available.y / image.y),
+            ViewMode::ActualSize => 1.0 / pixels_per_point,
+            ViewMode::Fill => (available.x / image.x).max(available.y / image.y),
+        }
+    }
+    
+    /// Physical pixels the remote screen is shown in when `available` points are free:
+    /// frames bigger than that only waste bandwidth. `None` at actual size, and when
+    /// filling, since frames fitted to the window would be too small to cover it.
+    pub fn viewport(&self, available: Vec2, pixels_per_point: f32) -> Option<(u32, u32)> {
+        match self {
+            ViewMode::Fit => {
+                let physical = available * pixels_per_point;
+                Some((physical.x.round().max(1.0) as u32, physical.y.round().max(1.0) as u32))
+            }
+            ViewMode::ActualSize | ViewMode::Fill => None,
+        }
+    }
+}
+
+/// How local pointer input reaches the remote desktop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PointerMode {
+    /// The remote pointer goes where the local one is over the image, on the viewed monitor
+    #[default]
+    Absolute,
+    /// The local pointer is captured and its motion moves the remote one, across all monitors
+    Locked,
+}
+
+impl PointerMode {
+    pub const ALL: [PointerMode; 2] = [PointerMode::Absolute, PointerMode::Locked];
+    
+    pub fn label(&self) -> &'static str {
+        match self {
+            PointerMode::Absolute => "Follow local pointer",
+            PointerMode::Locked => "Lock pointer (all monitors)",
+        }
+    }
+}
+
+/// A locked pointer at desktop point `pointer` moved by `motion` desktop units, kept
+/// inside the virtual desktop.
+pub fn move_locked_pointer(pointer: Pos2, motion: Vec2, layout: &DisplayLayout) -> Pos2 {
+    let moved = pointer + motion;
+    match layout.bounds() {
+        Some(bounds) if bounds.width > 0 && bounds.height > 0 => Pos2::new(
+            moved.x.clamp(bounds.x as f32, (bounds.right() - 1) as f32),
+            moved.y.clamp(bounds.y as f32, (bounds.bottom() - 1) as f32),
+        ),
+        _ => moved,
+    }
+}
+
+/// The remote pixel under `pointer` when the remote screen is drawn into `image_rect`.
+pub fn remote_position(pointer: Pos2, image_rect: Rect, remote_size: (u32, u32)) -> Option<(i32, i32)> {
+    if !image_rect.contains(pointer) || remote_size.0 == 0 || remote_size.1 == 0 {
+        return None;
+    }
+    
+    let relative_x = (pointer.x - image_rect.left()) / image_rect.width();
+    let relative_y = (pointer.y - image_rect.top()) / image_rect.height();
+    
+    let x = ((relative_x * remote_size.0 as f32) as u32).min(remote_size.0 - 1);
+    let y = ((relative_y * remote_size.1 as f32) as u32).min(remote_size.1 - 1);
+    Some((x as i32, y as i32))
+}
+
+/// Where remote pixel `remote` lands when the remote screen is drawn into `image_rect`.
+pub fn local_position(remote: (i32, i32), image_rect: Rect, remote_size: (u32, u32)) -> Pos2 {
+    let scale_x = image_rect.width() / remote_size.0.max(1) as f32;
+    let scale_y = image_rect.height() / remote_size.1.max(1) as f32;
+    Pos2::new(
+        image_rect.left() + remote.0 as f32 * scale_x,
+        image_rect.top() + remote.1 as f32 * scale_y,
+    )
+}
+
+/// Outline `region` (remote frame pixels) over the image shown at `image_rect`.
+pub fn paint_region(painter: &egui::Painter, region: Region, image_rect: Rect, remote_size: (u32, u32)) {
+    let corner = |x: u32, y: u32| local_position((x as i32, y as i32), image_rect, remote_size);
+    let outline = Rect::from_min_max(corner(region.x, region.y), corner(region.x + region.width, region.y + region.height));
+    painter.rect_stroke(outline, 0.0, egui::Stroke::new(1.5, egui::Color32::from_rgb(87, 196, 255)));
+}
+
+/// Draw an arrow pointer with its tip at `tip`, for servers whose frames leave the cursor out.
+/// `scale` is how many local points one remote logical point covers, so the arrow is as
+/// big as the remote one would be in the image.
+pub fn paint_cursor(painter: &egui::Painter, tip: Pos2, scale: f32) {
+    const ARROW: [(f32, f32); 7] = [
+        (0.0, 0.0), (0.0, 17.0), (4.5, 13.0), (7.5, 19.5), (10.0, 18.5), (7.0, 12.0), (12.5, 12.0),
+    ];
+    let points: Vec<Pos2> = ARROW.iter().map(|(x, y)| tip + Vec2::new(*x, *y) * scale).collect();
+    painter.add(egui::Shape::convex_polygon(
+        points.clone(),
+        egui::Color32::WHITE,
+        egui::Stroke::NONE,
+    ));
+    painter.add(egui::Shape::closed_line(points, egui::Stroke::new(1.0, egui::Color32::BLACK)));
+}
+
+/// The remote button for a local one; egui's extra buttons are the usual back/forward pair.
+pub fn remote_button(button: PointerButton) -> MouseButton {
+    match button {
+        PointerButton::Primary => MouseButton::Left,
+        PointerButton::Secondary => MouseButton::Right,
+        PointerButton::Middle => MouseButton::Middle,
+        PointerButton::Extra1 => MouseButton::Back,
+        PointerButton::Extra2 => MouseButton::Forward,
+    }
+}
+
+/// The protocol's phase for an egui touch phase.
+pub fn remote_touch_phase(phase: egui::TouchPhase) -> TouchPhase {
+    match phase {
+        egui::TouchPhase::Start => TouchPhase::Start,
+        egui::TouchPhase::Move => TouchPhase::Move,
+        egui::TouchPhase::End => TouchPhase::End,
+        egui::TouchPhase::Cancel => TouchPhase::Cancel,
+    }
+}
+
+/// Client settings kept between runs.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClientPreferences {
+    pub view_mode: ViewMode,
+    pub keyboard_mode: KeyboardMode,
+    pub pointer_mode: PointerMode,
+    pub content_profile: ContentProfile,
+    pub delivery: DeliveryMode,
+}
+
+impl ClientPreferences {
+    /// Saved preferences, or defaults when there are none or they can't be read.
+    pub fn load() -> Self {
+        std::fs::read_to_string(preferences_path())
+            .ok()
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+    
+    pub fn save(&self) -> Result<()> {
+        let path = preferences_path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// `$XDG_CONFIG_HOME/vox/client.json`, falling back to `%APPDATA%` and then `~/.config`.
+pub fn preferences_path() -> PathBuf {
+    let config_dir = std::env::var_os("XDG_CONFIG_HOME")
+        .or_else(|| std::env::var_os("APPDATA"))
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(|| PathBuf::from("."));
+    
+    config_dir.join("vox").join("client.json")
+}
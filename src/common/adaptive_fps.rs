@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+/// Capture rate used while the screen is static
+pub const MIN_FPS: u32 = 2;
+
+/// Fraction of changed tiles at which a frame counts as fully active
+pub const ACTIVE_TILE_RATIO: f32 = 0.02;
+
+/// Capture rate a static screen drops to while saving power
+pub const POWER_SAVE_MIN_FPS: u32 = 1;
+
+/// `ACTIVE_TILE_RATIO` while saving power, so a blinking caret or clock doesn't keep
+/// capture at full rate
+pub const POWER_SAVE_ACTIVE_TILE_RATIO: f32 = 0.08;
+
+// Smoothing factors for the activity level; rising faster than falling so
+// typing or scrolling gets back to full rate within a few frames while a
+// briefly quiet screen doesn't immediately drop to the floor.
+const RISE: f32 = 0.5;
+const FALL: f32 = 0.1;
+// Saving power, a quiet screen reaches the floor in a handful of frames instead
+const POWER_SAVE_FALL: f32 = 0.3;
+
+/// Paces capture by how much the screen is changing. Each captured frame
+/// reports its changed-tile count; the smoothed activity level maps linearly
+/// onto a rate between a floor (`MIN_FPS` unless raised) and the quality mode's target FPS.
+#[derive(Debug, Clone)]
+pub struct AdaptiveFps {
+    target_fps: u32,
+    min_fps: u32,
+    activity: f32,
+    power_save: bool,
+}
+
+impl AdaptiveFps {
+    /// Starts fully active, so the first frames after connecting go out at the target rate.
+    pub fn new(target_fps: u32) -> Self {
+        Self {
+            target_fps: target_fps.max(MIN_FPS),
+            min_fps: MIN_FPS,
+            activity: 1.0,
+            power_save: false,
+        }
+    }
+    
+    pub fn set_target_fps(&mut self, target_fps: u32) {
+        self.target_fps = target_fps.max(MIN_FPS);
+    }
+    
+    pub fn target_fps(&self) -> u32 {
+        self.target_fps
+    }
+    
+    /// Rate kept up on a static screen, `MIN_FPS` at the least and the target at the most.
+    pub fn set_min_fps(&mut self, min_fps: u32) {
+        self.min_fps = min_fps.max(MIN_FPS);
+    }
+    
+    /// Trade responsiveness for battery: slow down sooner and further on a quiet screen,
+    /// down to `POWER_SAVE_MIN_FPS` whatever the floor, and only count bigger changes as
+    /// activity. Busy screens still get the target rate.
+    pub fn set_power_save(&mut self, enabled: bool) {
+        self.power_save = enabled;
+    }
+    
+    pub fn power_save(&self) -> bool {
+        self.power_save
+    }
+    
+    /// Smoothed activity level between 0.0 (static) and 1.0 (busy).
+    pub fn activity(&self) -> f32 {
+        self.activity
+    }
+    
+    /// Feed the outcome of diffing a frame against the previous one.
+    pub fn record_frame(&mut self, changed_tiles: usize, total_tiles: usize) {
+        let (active_ratio, fall) = if self.power_save {
+            (POWER_SAVE_ACTIVE_TILE_RATIO, POWER_SAVE_FALL)
+        } else {
+            (ACTIVE_TILE_RATIO, FALL)
+        };
+        let sample = if total_tiles == 0 {
+            1.0
+        } else {
+            (changed_tiles as f32 / total_tiles as f32 / active_ratio).min(1.0)
+        };
+        let alpha = if sample > self.activity { RISE } else { fall };
+        self.activity += (sample - self.activity) * alpha;
+    }
+    
+    /// Feed a frame that replaced the whole screen (keyframe or large change).
+    pub fn record_full_change(&mut self) {
+        self.record_frame(1, 1);
+    }
+    
+    pub fn current_fps(&self) -> f32 {
+        let floor = if self.power_save { POWER_SAVE_MIN_FPS } else { self.min_fps };
+        let min = floor.min(self.target_fps) as f32;
+        min + (self.target_fps as f32 - min) * self.activity
+    }
+    
+    /// Time to wait between captures at the current rate.
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs_f32(1.0 / self.current_fps())
+    }
+}
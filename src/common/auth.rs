@@ -1,9 +1,10 @@
 use rand::{thread_rng, Rng};
 use sha2::{Sha256, Digest};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use serde::{Deserialize, Serialize};
+use anyhow::{anyhow, Result};
 
-const CODE_LENGTH: usize = 6;
+pub const CODE_LENGTH: usize = 6;
 const CODE_VALIDITY_SECS: u64 = 300; // 5 minutes
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -25,6 +26,102 @@ pub struct AuthResponse {
     pub success: bool,
     pub session_token: Option<String>,
     pub message: String,
+    // What the code the client used lets it do, None when it was refused
+    pub permissions: Option<Permissions>,
+}
+
+/// What a client may do once it's in, decided by the code it authenticated with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Permissions {
+    /// Mouse and keyboard input is injected on the host
+    pub input: bool,
+    /// Files may be sent to and from the host
+    pub file_transfer: bool,
+    /// The host display's brightness, resolution and sleep may be changed
+    pub display_control: bool,
+}
+
+impl Permissions {
+    /// Watch the screen, nothing else.
+    pub const VIEW_ONLY: Self = Permissions { input: false, file_transfer: false, display_control: false };
+    /// Everything; what the rotating access code grants.
+    pub const FULL_CONTROL: Self = Permissions { input: true, file_transfer: true, display_control: true };
+    
+    /// Parse `view-only`, `full-control`, or `input`, `file-transfer` and `display-control`
+    /// joined by `+`, e.g. `view-only+file-transfer`.
+    pub fn parse(value: &str) -> Result<Self> {
+        let mut permissions = Self::VIEW_ONLY;
+        for part in value.split('+') {
+            match part.trim().to_ascii_lowercase().as_str() {
+                "view-only" => {}
+                "input" => permissions.input = true,
+                "file-transfer" => permissions.file_transfer = true,
+                "display-control" => permissions.display_control = true,
+                "full-control" => permissions = Self::FULL_CONTROL,
+                other => return Err(anyhow!(
+                    "unknown permission '{}', expected view-only, input, file-transfer, display-control or full-control", other
+                )),
+            }
+        }
+        Ok(permissions)
+    }
+}
+
+/// Fail unless `code` is exactly `CODE_LENGTH` digits, the only codes the client's code
+/// field lets through.
+pub fn check_code_format(code: &str) -> Result<()> {
+    if code.len() != CODE_LENGTH || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(anyhow!("access code '{}' must be {} digits (0-9), which is all clients can enter", code, CODE_LENGTH));
+    }
+    Ok(())
+}
+
+/// Fixed codes the host hands out besides the rotating one, each granting its own
+/// permissions, e.g. a view-only code for an audience. They don't expire; only their
+/// hashes are kept.
+#[derive(Debug, Clone, Default)]
+pub struct AccessCodes {
+    codes: Vec<(String, Permissions)>,
+}
+
+impl AccessCodes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Accept `code`, granting `permissions`. A code given twice keeps the last permissions.
+    pub fn add(&mut self, code: &str, permissions: Permissions) {
+        let hashed = AccessCode::hash_code(code);
+        self.codes.retain(|(stored, _)| *stored != hashed);
+        self.codes.push((hashed, permissions));
+    }
+    
+    /// Add a code written as `CODE:PERMISSIONS`, e.g. `482913:view-only`.
+    pub fn add_spec(&mut self, spec: &str) -> Result<()> {
+        let (code, permissions) = spec.rsplit_once(':')
+            .ok_or_else(|| anyhow!("'{}' should be CODE:PERMISSIONS, e.g. 482913:view-only", spec))?;
+        let code = code.trim();
+        if code.is_empty() {
+            return Err(anyhow!("'{}' has an empty code", spec));
+        }
+        check_code_format(code)?;
+        self.add(code, Permissions::parse(permissions)?);
+        Ok(())
+    }
+    
+    /// What `code` grants, None if it isn't one of these.
+    pub fn permissions_for(&self, code: &str) -> Option<Permissions> {
+        let hashed = AccessCode::hash_code(code);
+        self.codes.iter().find(|(stored, _)| *stored == hashed).map(|(_, permissions)| *permissions)
+    }
+    
+    pub fn len(&self) -> usize {
+        self.codes.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.codes.is_empty()
+    }
 }
 
 impl AccessCode {
@@ -75,6 +172,15 @@ impl AccessCode {
             .as_secs();
         now > self.expires_at
     }
+    
+    /// How long until the code expires, zero once it has.
+    pub fn time_left(&self) -> Duration {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        Duration::from_secs(self.expires_at.saturating_sub(now))
+    }
 }
 
 #[derive(Debug)]
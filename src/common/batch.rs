@@ -0,0 +1,71 @@
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use crate::common::protocol::{Message, TouchPhase};
+
+/// When a sender coalesces queued messages into one `Message::Batch`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchPolicy {
+    /// How long a batch waits for more messages after its first. Zero only takes what is
+    /// already queued, so batching never delays anything.
+    pub window: Duration,
+    pub max_messages: usize,
+}
+
+impl Default for BatchPolicy {
+    fn default() -> Self {
+        Self {
+            window: Duration::ZERO,
+            max_messages: 64,
+        }
+    }
+}
+
+/// Messages the user is waiting on; a batch is sent as soon as one joins it.
+pub fn is_urgent(message: &Message) -> bool {
+    matches!(
+        message,
+        Message::KeyEvent { .. }
+            | Message::PhysicalKeyEvent { .. }
+            | Message::MouseClick { .. }
+            | Message::Touch { phase: TouchPhase::Start | TouchPhase::End | TouchPhase::Cancel, .. }
+            | Message::Disconnect
+    )
+}
+
+/// The next messages to send together: `first` plus whatever follows on `rx` within the
+/// policy's window, ending early after an urgent message or at `max_messages`.
+pub async fn collect_batch(first: Message, rx: &mut UnboundedReceiver<Message>, policy: &BatchPolicy) -> Vec<Message> {
+    let deadline = tokio::time::Instant::now() + policy.window;
+    let mut batch = vec![first];
+    
+    while batch.len() < policy.max_messages && !is_urgent(batch.last().unwrap()) {
+        let next = match rx.try_recv() {
+            Ok(message) => Some(message),
+            Err(_) if policy.window.is_zero() => None,
+            Err(_) => tokio::time::timeout_at(deadline, rx.recv()).await.ok().flatten(),
+        };
+        match next {
+            Some(message) => batch.push(message),
+            None => break,
+        }
+    }
+    batch
+}
+
+/// One message to put on the wire for `messages`, which must not be empty.
+pub fn pack(mut messages: Vec<Message>) -> Message {
+    if messages.len() == 1 {
+        messages.pop().unwrap()
+    } else {
+        Message::Batch { messages }
+    }
+}
+
+/// The messages `message` carries, in order, with nested batches flattened.
+pub fn unpack(message: Message) -> Vec<Message> {
+    match message {
+        Message::Batch { messages } => messages.into_iter().flat_map(unpack).collect(),
+        other => vec![other],
+    }
+}
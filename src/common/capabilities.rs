@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+use crate::common::protocol::EncodingType;
+
+/// Frame encodings one side can produce (server) or decode (client), most preferred first.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Capabilities {
+    pub encodings: Vec<EncodingType>,
+}
+
+impl Capabilities {
+    pub fn new(encodings: Vec<EncodingType>) -> Self {
+        Self { encodings }
+    }
+    
+    /// What every peer handles: zstd-compressed RGB. Assumed until a client says otherwise.
+    pub fn baseline() -> Self {
+        Self::new(vec![EncodingType::ZstdCompressed])
+    }
+    
+    pub fn supports(&self, encoding: EncodingType) -> bool {
+        self.encodings.contains(&encoding)
+    }
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::baseline()
+    }
+}
+
+/// Pick the server's most preferred encoding that the client can decode.
+pub fn negotiate(server: &Capabilities, client: &Capabilities) -> Option<EncodingType> {
+    server.encodings.iter().copied().find(|&encoding| client.supports(encoding))
+}
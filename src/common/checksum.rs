@@ -0,0 +1,6 @@
+/// Checksum of a whole RGB frame, the same on every machine and build. The server sends
+/// it with each lossless frame when run with `--frame-checksums`, and the client compares
+/// it with the frame it reconstructed to find out where the two drifted apart.
+pub fn frame_checksum(rgb: &[u8]) -> u32 {
+    crc32fast::hash(rgb)
+}
@@ -0,0 +1,144 @@
+use serde::{Deserialize, Serialize};
+use ffmpeg_next as ffmpeg;
+
+/// YCbCr matrix used for RGB <-> YUV conversion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorMatrix {
+    Bt601,
+    Bt709,
+}
+
+impl ColorMatrix {
+    /// Luma weights (Kr, Kb) for this matrix; Kg = 1 - Kr - Kb
+    fn coefficients(self) -> (f32, f32) {
+        match self {
+            ColorMatrix::Bt601 => (0.299, 0.114),
+            ColorMatrix::Bt709 => (0.2126, 0.0722),
+        }
+    }
+}
+
+/// Quantization range of the YUV samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ColorRange {
+    /// "TV" range: Y in 16..=235, chroma in 16..=240
+    Limited,
+    /// "PC" range: every component uses 0..=255
+    Full,
+}
+
+/// The host's choice of matrix and range for H.264 streams; None leaves it to the
+/// stream's resolution (see `ColorSpace::for_resolution`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ColorPreference {
+    pub matrix: Option<ColorMatrix>,
+    pub range: Option<ColorRange>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ColorSpace {
+    pub matrix: ColorMatrix,
+    pub range: ColorRange,
+}
+
+impl ColorSpace {
+    pub fn new(matrix: ColorMatrix, range: ColorRange) -> Self {
+        Self { matrix, range }
+    }
+
+    /// BT.709 for HD and above, BT.601 for SD, both in limited range.
+    pub fn for_resolution(_width: u32, height: u32) -> Self {
+        let matrix = if height >= 720 {
+            ColorMatrix::Bt709
+        } else {
+            ColorMatrix::Bt601
+        };
+        Self::new(matrix, ColorRange::Limited)
+    }
+
+    /// The matrix and range of `preference`, falling back to `for_resolution` for
+    /// whichever it leaves unset.
+    pub fn preferred(preference: ColorPreference, width: u32, height: u32) -> Self {
+        let automatic = Self::for_resolution(width, height);
+        Self::new(
+            preference.matrix.unwrap_or(automatic.matrix),
+            preference.range.unwrap_or(automatic.range),
+        )
+    }
+
+    /// Build a color space from what the H.264 stream signals, if it says anything useful.
+    pub fn from_ffmpeg(space: ffmpeg::color::Space, range: ffmpeg::color::Range) -> Option<Self> {
+        let matrix = match space {
+            ffmpeg::color::Space::BT709 => ColorMatrix::Bt709,
+            ffmpeg::color::Space::BT470BG | ffmpeg::color::Space::SMPTE170M => ColorMatrix::Bt601,
+            _ => return None,
+        };
+        let range = match range {
+            ffmpeg::color::Range::JPEG => ColorRange::Full,
+            _ => ColorRange::Limited,
+        };
+        Some(Self::new(matrix, range))
+    }
+
+    pub fn ffmpeg_space(&self) -> ffmpeg::color::Space {
+        match self.matrix {
+            ColorMatrix::Bt601 => ffmpeg::color::Space::SMPTE170M,
+            ColorMatrix::Bt709 => ffmpeg::color::Space::BT709,
+        }
+    }
+
+    pub fn ffmpeg_range(&self) -> ffmpeg::color::Range {
+        match self.range {
+            ColorRange::Limited => ffmpeg::color::Range::MPEG,
+            ColorRange::Full => ffmpeg::color::Range::JPEG,
+        }
+    }
+
+    /// (luma scale, luma offset, chroma scale)
+    fn quantization(&self) -> (f32, f32, f32) {
+        match self.range {
+            ColorRange::Limited => (219.0, 16.0, 224.0),
+            ColorRange::Full => (255.0, 0.0, 255.0),
+        }
+    }
+
+    pub fn luma(&self, r: u8, g: u8, b: u8) -> u8 {
+        let (kr, kb) = self.matrix.coefficients();
+        let (y_scale, y_offset, _) = self.quantization();
+        let y = (kr * r as f32 + (1.0 - kr - kb) * g as f32 + kb * b as f32) / 255.0;
+        (y * y_scale + y_offset).round().clamp(0.0, 255.0) as u8
+    }
+
+    pub fn chroma(&self, r: u8, g: u8, b: u8) -> (u8, u8) {
+        let (kr, kb) = self.matrix.coefficients();
+        let (_, _, c_scale) = self.quantization();
+        let y = kr * r as f32 + (1.0 - kr - kb) * g as f32 + kb * b as f32;
+        let pb = (b as f32 - y) / (2.0 * (1.0 - kb)) / 255.0;
+        let pr = (r as f32 - y) / (2.0 * (1.0 - kr)) / 255.0;
+        let u = (pb * c_scale + 128.0).round().clamp(0.0, 255.0) as u8;
+        let v = (pr * c_scale + 128.0).round().clamp(0.0, 255.0) as u8;
+        (u, v)
+    }
+
+    pub fn rgb_to_yuv(&self, r: u8, g: u8, b: u8) -> (u8, u8, u8) {
+        let (u, v) = self.chroma(r, g, b);
+        (self.luma(r, g, b), u, v)
+    }
+
+    pub fn yuv_to_rgb(&self, y: u8, u: u8, v: u8) -> (u8, u8, u8) {
+        let (kr, kb) = self.matrix.coefficients();
+        let kg = 1.0 - kr - kb;
+        let (y_scale, y_offset, c_scale) = self.quantization();
+
+        let y = (y as f32 - y_offset) / y_scale;
+        let pb = (u as f32 - 128.0) / c_scale;
+        let pr = (v as f32 - 128.0) / c_scale;
+
+        let r = y + 2.0 * (1.0 - kr) * pr;
+        let b = y + 2.0 * (1.0 - kb) * pb;
+        let g = (y - kr * r - kb * b) / kg;
+
+        let to_u8 = |c: f32| (c * 255.0).round().clamp(0.0, 255.0) as u8;
+        (to_u8(r), to_u8(g), to_u8(b))
+    }
+}
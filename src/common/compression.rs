@@ -0,0 +1,21 @@
+use anyhow::Result;
+use bytes::Bytes;
+use crate::common::protocol::EncodingType;
+
+/// Whether frame payloads of `encoding` are worth running through zstd. Only raw RGB is:
+/// H.264, WebP and zstd output are already compressed, so another pass costs CPU and
+/// usually makes them bigger.
+pub fn should_compress(encoding: EncodingType) -> bool {
+    encoding == EncodingType::Raw
+}
+
+/// Prepare a frame payload for the wire, returning it with the encoding it now has. This
+/// is the one place frames are zstd-compressed; anything `should_compress` turns down is
+/// passed through untouched.
+pub fn compress_payload(data: Bytes, encoding: EncodingType, level: i32) -> Result<(Bytes, EncodingType)> {
+    if !should_compress(encoding) {
+        return Ok((data, encoding));
+    }
+    let compressed = zstd::stream::encode_all(&data[..], level)?;
+    Ok((Bytes::from(compressed), EncodingType::ZstdCompressed))
+}
@@ -1,50 +1,453 @@
 use aes_gcm::{
-    aead::{Aead, AeadCore, KeyInit, OsRng},
+    aead::{self, Aead, AeadCore, KeyInit, OsRng},
     Aes256Gcm, Key, Nonce,
 };
 use anyhow::{Result, anyhow};
+use chacha20poly1305::ChaCha20Poly1305;
+use hmac::{Hmac, Mac};
+use parking_lot::Mutex;
 use x25519_dalek::{EphemeralSecret, PublicKey};
+use serde::{Deserialize, Serialize};
 use sha2::{Sha256, Digest};
 
+use crate::common::protocol::Message;
+
+/// AEAD nonce, sent in front of each ciphertext; the same for both ciphers
+pub const NONCE_LEN: usize = 12;
+
+/// AEAD authentication tag, at the end of each ciphertext
+pub const TAG_LEN: usize = 16;
+
+/// Undecryptable messages in a row a session tolerates before giving up on the peer.
+pub const MAX_CORRUPT_MESSAGES: u32 = 8;
+
+/// The AEAD protecting a session. Both sides must use the same one, so the client offers
+/// what it supports with `Message::CipherOffer` and the server picks (see `negotiate_cipher`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Cipher {
+    /// Fastest where the CPU has AES instructions; used when nothing is negotiated
+    Aes256Gcm,
+    /// Fast in software, for CPUs without AES instructions (many ARM and embedded boards)
+    ChaCha20Poly1305,
+}
+
+impl Cipher {
+    pub fn name(self) -> &'static str {
+        match self {
+            Cipher::Aes256Gcm => "AES-256-GCM",
+            Cipher::ChaCha20Poly1305 => "ChaCha20-Poly1305",
+        }
+    }
+    
+    /// Both ciphers, fastest on this machine first.
+    pub fn preferred() -> Vec<Cipher> {
+        if aes_accelerated() {
+            vec![Cipher::Aes256Gcm, Cipher::ChaCha20Poly1305]
+        } else {
+            vec![Cipher::ChaCha20Poly1305, Cipher::Aes256Gcm]
+        }
+    }
+}
+
+/// Whether this CPU has AES instructions, without which AES-GCM runs in much slower software.
+pub fn aes_accelerated() -> bool {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        std::arch::is_x86_feature_detected!("aes") && std::arch::is_x86_feature_detected!("pclmulqdq")
+    }
+    
+    #[cfg(target_arch = "aarch64")]
+    {
+        std::arch::is_aarch64_feature_detected!("aes")
+    }
+    
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        false
+    }
+}
+
+/// Pick the cipher for a session from the server's preference (`ours`) and the client's
+/// offer (`theirs`). ChaCha20-Poly1305 wins when either side would rather have it, since
+/// one side without AES instructions slows the whole stream down; otherwise the server's
+/// first choice the client supports.
+pub fn negotiate_cipher(ours: &[Cipher], theirs: &[Cipher]) -> Option<Cipher> {
+    let chacha = Cipher::ChaCha20Poly1305;
+    let wants_chacha = ours.first() == Some(&chacha) || theirs.first() == Some(&chacha);
+    if wants_chacha && ours.contains(&chacha) && theirs.contains(&chacha) {
+        return Some(chacha);
+    }
+    ours.iter().copied().find(|cipher| theirs.contains(cipher))
+}
+
+/// One message failed to decrypt (too short, or its tag didn't verify). The framing is
+/// intact, so the receiver can drop it and carry on with the next one.
+#[derive(Debug)]
+pub struct CorruptMessage(String);
+
+impl std::fmt::Display for CorruptMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Corrupt encrypted message: {}", self.0)
+    }
+}
+
+impl std::error::Error for CorruptMessage {}
+
+/// Whether `error` came from a single corrupt message rather than a broken session.
+pub fn is_corrupt(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<CorruptMessage>().is_some()
+}
+
+/// Encrypts and decrypts every message on a session once the handshake is done.
+pub trait Encryptor: Send + Sync {
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>>;
+    fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>>;
+    
+    /// True once the sending key has protected enough traffic that it should be replaced.
+    fn rekey_due(&self) -> bool {
+        false
+    }
+    
+    /// Epoch of the sending key; 0 is the key from the handshake.
+    fn send_epoch(&self) -> u32 {
+        0
+    }
+    
+    /// Move the sending side to the next key.
+    fn rotate_send_key(&self) {}
+    
+    /// Follow the peer to its sending key `epoch`, announced with `Message::Rekey`.
+    fn rotate_receive_key(&self, _epoch: u32) -> Result<()> {
+        Ok(())
+    }
+    
+    /// Seal a video frame. The same as `encrypt` unless the session agreed to plaintext video.
+    fn encrypt_video(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        self.encrypt(plaintext)
+    }
+    
+    /// Whether `data` came in the clear under a plaintext video agreement, so may only
+    /// hold video.
+    fn is_clear(&self, _data: &[u8]) -> bool {
+        false
+    }
+}
+
+/// When a session key is retired. Random 96-bit GCM nonces are safe for far more
+/// messages than this, rotating early just keeps each key's exposure small.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RekeyPolicy {
+    pub max_messages: u64,
+    pub max_bytes: u64,
+}
+
+impl Default for RekeyPolicy {
+    fn default() -> Self {
+        Self {
+            max_messages: 1 << 22,
+            max_bytes: 16 << 30, // 16 GiB
+        }
+    }
+}
+
+// A session key set up for its cipher
+enum SessionCipher {
+    Aes(Aes256Gcm),
+    ChaCha(ChaCha20Poly1305),
+}
+
+impl SessionCipher {
+    fn new(cipher: Cipher, key: &[u8; 32]) -> Self {
+        match cipher {
+            Cipher::Aes256Gcm => Self::Aes(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key))),
+            Cipher::ChaCha20Poly1305 => Self::ChaCha(ChaCha20Poly1305::new(chacha20poly1305::Key::from_slice(key))),
+        }
+    }
+    
+    fn cipher(&self) -> Cipher {
+        match self {
+            Self::Aes(_) => Cipher::Aes256Gcm,
+            Self::ChaCha(_) => Cipher::ChaCha20Poly1305,
+        }
+    }
+    
+    // A fresh random nonce followed by the ciphertext and tag
+    fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>, aead::Error> {
+        let (nonce, ciphertext) = match self {
+            Self::Aes(cipher) => {
+                let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+                (nonce, cipher.encrypt(&nonce, plaintext)?)
+            }
+            Self::ChaCha(cipher) => {
+                let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+                (nonce, cipher.encrypt(&nonce, plaintext)?)
+            }
+        };
+        
+        let mut result = nonce.to_vec();
+        result.extend_from_slice(&ciphertext);
+        Ok(result)
+    }
+    
+    fn open(&self, nonce: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, aead::Error> {
+        let nonce = Nonce::from_slice(nonce);
+        match self {
+            Self::Aes(cipher) => cipher.decrypt(nonce, ciphertext),
+            Self::ChaCha(cipher) => cipher.decrypt(nonce, ciphertext),
+        }
+    }
+}
+
+// One direction of a session: its current key and how much it has protected
+struct DirectionKey {
+    key: [u8; 32],
+    cipher: SessionCipher,
+    epoch: u32,
+    messages: u64,
+    bytes: u64,
+}
+
+impl DirectionKey {
+    fn new(key: [u8; 32], cipher: Cipher) -> Self {
+        Self {
+            cipher: SessionCipher::new(cipher, &key),
+            key,
+            epoch: 0,
+            messages: 0,
+            bytes: 0,
+        }
+    }
+    
+    // HMAC-SHA256 ratchet; the old key can't be recovered from the new one
+    fn advance(&mut self) {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.key).expect("HMAC accepts any key length");
+        mac.update(b"vox rekey");
+        mac.update(&(self.epoch + 1).to_be_bytes());
+        let epoch = self.epoch + 1;
+        *self = Self::new(mac.finalize().into_bytes().into(), self.cipher.cipher());
+        self.epoch = epoch;
+    }
+}
+
+/// AES-256-GCM (or ChaCha20-Poly1305, see `with_cipher`) keyed from the X25519 shared
+/// secret; the normal session encryptor.
+///
+/// Each direction ratchets its key independently: the sender announces the switch with
+/// `Message::Rekey` under the old key (see `seal_message`), and the receiver follows when
+/// it reads that message (see `open_message`), so nothing in flight is lost.
 pub struct CryptoSession {
-    cipher: Aes256Gcm,
+    send: Mutex<DirectionKey>,
+    receive: Mutex<DirectionKey>,
+    policy: RekeyPolicy,
 }
 
 impl CryptoSession {
     pub fn from_shared_secret(shared_secret: &[u8]) -> Result<Self> {
         let mut hasher = Sha256::new();
         hasher.update(shared_secret);
-        let key_bytes = hasher.finalize();
-        
-        let key = Key::<Aes256Gcm>::from_slice(&key_bytes);
-        let cipher = Aes256Gcm::new(key);
+        let key: [u8; 32] = hasher.finalize().into();
         
-        Ok(CryptoSession { cipher })
+        Ok(CryptoSession {
+            send: Mutex::new(DirectionKey::new(key, Cipher::Aes256Gcm)),
+            receive: Mutex::new(DirectionKey::new(key, Cipher::Aes256Gcm)),
+            policy: RekeyPolicy::default(),
+        })
+    }
+    
+    pub fn with_rekey_policy(mut self, policy: RekeyPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+    
+    /// Use `cipher` instead of AES-256-GCM. Only before anything has been encrypted.
+    pub fn with_cipher(mut self, cipher: Cipher) -> Self {
+        for direction in [self.send.get_mut(), self.receive.get_mut()] {
+            *direction = DirectionKey::new(direction.key, cipher);
+        }
+        self
+    }
+    
+    pub fn cipher(&self) -> Cipher {
+        self.send.lock().cipher.cipher()
     }
     
     pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
-        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
-        let ciphertext = self.cipher
-            .encrypt(&nonce, plaintext)
-            .map_err(|e| anyhow!("Encryption failed: {}", e))?;
+        let mut send = self.send.lock();
+        send.messages += 1;
+        send.bytes += plaintext.len() as u64;
         
-        // Prepend nonce to ciphertext
-        let mut result = nonce.to_vec();
-        result.extend_from_slice(&ciphertext);
-        Ok(result)
+        send.cipher
+            .seal(plaintext)
+            .map_err(|e| anyhow!("Encryption failed: {}", e))
     }
     
     pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
-        if data.len() < 12 {
-            return Err(anyhow!("Invalid encrypted data"));
+        if data.len() < NONCE_LEN + TAG_LEN {
+            return Err(CorruptMessage(format!(
+                "{} bytes is shorter than the {}-byte nonce and {}-byte tag", data.len(), NONCE_LEN, TAG_LEN
+            )).into());
         }
         
-        let (nonce_bytes, ciphertext) = data.split_at(12);
-        let nonce = Nonce::from_slice(nonce_bytes);
-        
-        self.cipher
-            .decrypt(nonce, ciphertext)
-            .map_err(|e| anyhow!("Decryption failed: {}", e))
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        self.receive.lock().cipher
+            .open(nonce, ciphertext)
+            .map_err(|_| CorruptMessage("authentication tag did not verify".to_string()).into())
+    }
+}
+
+impl Encryptor for CryptoSession {
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        CryptoSession::encrypt(self, plaintext)
+    }
+    
+    fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        CryptoSession::decrypt(self, data)
+    }
+    
+    fn rekey_due(&self) -> bool {
+        let send = self.send.lock();
+        send.messages >= self.policy.max_messages || send.bytes >= self.policy.max_bytes
+    }
+    
+    fn send_epoch(&self) -> u32 {
+        self.send.lock().epoch
+    }
+    
+    fn rotate_send_key(&self) {
+        self.send.lock().advance();
+    }
+    
+    fn rotate_receive_key(&self, epoch: u32) -> Result<()> {
+        let mut receive = self.receive.lock();
+        if epoch != receive.epoch + 1 {
+            return Err(anyhow!("Rekey to epoch {} out of sequence (at epoch {})", epoch, receive.epoch));
+        }
+        receive.advance();
+        Ok(())
+    }
+}
+
+/// Encrypt one serialized message for the wire. When the sending key is due for rotation,
+/// a `Message::Rekey` under the old key comes first and the message itself uses the new key.
+///
+/// Send the returned frames in order, before anything else is encrypted with `crypto`.
+pub fn seal_message(crypto: &dyn Encryptor, serialized: &[u8]) -> Result<Vec<Vec<u8>>> {
+    seal(crypto, serialized, false)
+}
+
+/// Like `seal_message`, for a serialized video frame (`Message::is_video`).
+pub fn seal_video(crypto: &dyn Encryptor, serialized: &[u8]) -> Result<Vec<Vec<u8>>> {
+    seal(crypto, serialized, true)
+}
+
+fn seal(crypto: &dyn Encryptor, serialized: &[u8], video: bool) -> Result<Vec<Vec<u8>>> {
+    let mut frames = Vec::with_capacity(1);
+    if crypto.rekey_due() {
+        let rekey = Message::Rekey { epoch: crypto.send_epoch() + 1 };
+        frames.push(crypto.encrypt(&rekey.serialize()?)?);
+        crypto.rotate_send_key();
+    }
+    frames.push(if video { crypto.encrypt_video(serialized)? } else { crypto.encrypt(serialized)? });
+    Ok(frames)
+}
+
+/// Decrypt and decode one frame from the wire. `Rekey` announcements are applied here and
+/// give `None`; everything else is returned.
+pub fn open_message(crypto: &dyn Encryptor, data: &[u8]) -> Result<Option<Message>> {
+    let message = Message::deserialize(&crypto.decrypt(data)?)?;
+    // Anyone on the path can write plaintext; only video is expected that way
+    if crypto.is_clear(data) && !message.is_video() {
+        return Err(CorruptMessage("only video may be sent without encryption".to_string()).into());
+    }
+    if let Message::Rekey { epoch } = message {
+        crypto.rotate_receive_key(epoch)?;
+        return Ok(None);
+    }
+    Ok(Some(message))
+}
+
+/// Passes data through untouched. Only for measuring capture/encode/transport cost
+/// over loopback (`--no-encryption`); never negotiated on other addresses.
+pub struct NullEncryptor;
+
+impl Encryptor for NullEncryptor {
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        Ok(plaintext.to_vec())
+    }
+    
+    fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        Ok(data.to_vec())
+    }
+}
+
+const SEALED: u8 = 0;
+const CLEAR: u8 = 1;
+
+/// A session's encryption after both sides agreed to plaintext video, for networks that
+/// are already encrypted (a VPN or WireGuard tunnel). Video frames go out in the clear;
+/// everything else, input included, stays sealed by the session's own encryptor. Each
+/// frame starts with a byte saying which it is.
+pub struct PlaintextVideo {
+    inner: Box<dyn Encryptor>,
+    // Whether the peer may send video in the clear; only the server does
+    accept_clear: bool,
+}
+
+impl PlaintextVideo {
+    /// Wrap a session's encryptor on the side that sends video.
+    pub fn sender(inner: Box<dyn Encryptor>) -> Self {
+        Self { inner, accept_clear: false }
+    }
+    
+    /// Wrap a session's encryptor on the side that receives video.
+    pub fn receiver(inner: Box<dyn Encryptor>) -> Self {
+        Self { inner, accept_clear: true }
+    }
+}
+
+impl Encryptor for PlaintextVideo {
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let sealed = self.inner.encrypt(plaintext)?;
+        let mut frame = Vec::with_capacity(1 + sealed.len());
+        frame.push(SEALED);
+        frame.extend_from_slice(&sealed);
+        Ok(frame)
+    }
+    
+    fn encrypt_video(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let mut frame = Vec::with_capacity(1 + plaintext.len());
+        frame.push(CLEAR);
+        frame.extend_from_slice(plaintext);
+        Ok(frame)
+    }
+    
+    fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        match data.split_first() {
+            Some((&SEALED, sealed)) => self.inner.decrypt(sealed),
+            Some((&CLEAR, plaintext)) if self.accept_clear => Ok(plaintext.to_vec()),
+            Some((&CLEAR, _)) => Err(CorruptMessage("unexpected unencrypted message".to_string()).into()),
+            _ => Err(CorruptMessage("unknown framing".to_string()).into()),
+        }
+    }
+    
+    fn is_clear(&self, data: &[u8]) -> bool {
+        data.first() == Some(&CLEAR)
+    }
+    
+    fn rekey_due(&self) -> bool {
+        self.inner.rekey_due()
+    }
+    
+    fn send_epoch(&self) -> u32 {
+        self.inner.send_epoch()
+    }
+    
+    fn rotate_send_key(&self) {
+        self.inner.rotate_send_key()
+    }
+    
+    fn rotate_receive_key(&self, epoch: u32) -> Result<()> {
+        self.inner.rotate_receive_key(epoch)
     }
 }
 
@@ -73,4 +476,60 @@ impl KeyExchange {
         let shared_secret = self.secret.diffie_hellman(their_public);
         shared_secret.to_bytes()
     }
+    
+    /// The shared secret with a peer that sent `their_public` during the handshake,
+    /// refusing keys that would make the secret predictable.
+    pub fn agree(self, their_public: &[u8]) -> Result<[u8; 32]> {
+        let their_public = parse_public_key(their_public)?;
+        let shared_secret = self.secret.diffie_hellman(&their_public);
+        // Catches any low-order point the list below misses
+        if !shared_secret.was_contributory() {
+            return Err(anyhow!("Public key doesn't contribute to the shared secret"));
+        }
+        Ok(shared_secret.to_bytes())
+    }
+}
+
+/// X25519 public keys of small order, plus their non-canonical encodings. Any of them
+/// gives a shared secret the sender knows without holding a private key, so a peer
+/// offering one is attacking the handshake.
+const SMALL_ORDER_KEYS: [[u8; 32]; 7] = [
+    // 0 and 1
+    [0; 32],
+    [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+    // Points of order 8
+    [
+        0xe0, 0xeb, 0x7a, 0x7c, 0x3b, 0x41, 0xb8, 0xae, 0x16, 0x56, 0xe3, 0xfa, 0xf1, 0x9f, 0xc4, 0x6a,
+        0xda, 0x09, 0x8d, 0xeb, 0x9c, 0x32, 0xb1, 0xfd, 0x86, 0x62, 0x05, 0x16, 0x5f, 0x49, 0xb8, 0x00,
+    ],
+    [
+        0x5f, 0x9c, 0x95, 0xbc, 0xa3, 0x50, 0x8c, 0x24, 0xb1, 0xd0, 0xb1, 0x55, 0x9c, 0x83, 0xef, 0x5b,
+        0x04, 0x44, 0x5c, 0xc4, 0x58, 0x1c, 0x8e, 0x86, 0xd8, 0x22, 0x4e, 0xdd, 0xd0, 0x9f, 0x11, 0x57,
+    ],
+    // p - 1, p and p + 1, with p = 2^255 - 19
+    [
+        0xec, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f,
+    ],
+    [
+        0xed, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f,
+    ],
+    [
+        0xee, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f,
+    ],
+];
+
+/// A peer's public key from the handshake: 32 bytes and not one of the small-order points.
+pub fn parse_public_key(bytes: &[u8]) -> Result<PublicKey> {
+    let key = <[u8; 32]>::try_from(bytes)
+        .map_err(|_| anyhow!("Invalid public key: {} bytes instead of 32", bytes.len()))?;
+    // X25519 ignores the top bit, so keys differing only there are the same point
+    let mut masked = key;
+    masked[31] &= 0x7f;
+    if SMALL_ORDER_KEYS.contains(&masked) {
+        return Err(anyhow!("Invalid public key: a small-order point"));
+    }
+    Ok(PublicKey::from(key))
 }
\ No newline at end of file
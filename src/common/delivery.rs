@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How long a frame may wait for the network in real-time delivery before it is
+/// considered late and dropped.
+pub const LATE_FRAME_AGE: Duration = Duration::from_millis(100);
+
+/// What the server does with frames when the network can't keep up. Chosen by the
+/// client when it connects, and can be changed mid-session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DeliveryMode {
+    /// Every frame is delivered in order; under loss they wait and latency grows
+    #[default]
+    Smooth,
+    /// Late frames are dropped and the client resyncs on a keyframe, keeping latency low
+    RealTime,
+}
+
+impl DeliveryMode {
+    pub const ALL: [DeliveryMode; 2] = [DeliveryMode::Smooth, DeliveryMode::RealTime];
+    
+    pub fn label(&self) -> &'static str {
+        match self {
+            DeliveryMode::Smooth => "Smooth",
+            DeliveryMode::RealTime => "Real-time",
+        }
+    }
+    
+    /// How long a frame may sit in the send queue, `None` for as long as it takes.
+    pub fn max_frame_age(&self) -> Option<Duration> {
+        match self {
+            DeliveryMode::Smooth => None,
+            DeliveryMode::RealTime => Some(LATE_FRAME_AGE),
+        }
+    }
+}
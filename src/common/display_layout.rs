@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+
+/// Where one monitor sits on the remote desktop, in the coordinates input is placed in
+/// (points on macOS, pixels elsewhere). The primary monitor is at 0,0, so monitors left of
+/// or above it have negative origins.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MonitorRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl MonitorRect {
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Self { x, y, width, height }
+    }
+    
+    pub fn right(&self) -> i32 {
+        self.x.saturating_add(self.width as i32)
+    }
+    
+    pub fn bottom(&self) -> i32 {
+        self.y.saturating_add(self.height as i32)
+    }
+    
+    pub fn contains(&self, (x, y): (i32, i32)) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+}
+
+/// The remote's monitors, indexed like the displays a session can select.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DisplayLayout {
+    pub monitors: Vec<MonitorRect>,
+}
+
+impl DisplayLayout {
+    pub fn new(monitors: Vec<MonitorRect>) -> Self {
+        Self { monitors }
+    }
+    
+    /// The virtual desktop: the smallest rect holding every monitor.
+    pub fn bounds(&self) -> Option<MonitorRect> {
+        let left = self.monitors.iter().map(|m| m.x).min()?;
+        let top = self.monitors.iter().map(|m| m.y).min()?;
+        let right = self.monitors.iter().map(|m| m.right()).max()?;
+        let bottom = self.monitors.iter().map(|m| m.bottom()).max()?;
+        Some(MonitorRect::new(left, top, (right - left) as u32, (bottom - top) as u32))
+    }
+    
+    /// Top-left corner of `display` on the desktop; 0,0 for a display the layout doesn't know.
+    pub fn origin(&self, display: usize) -> (i32, i32) {
+        self.monitors.get(display).map_or((0, 0), |m| (m.x, m.y))
+    }
+    
+    /// The monitor showing desktop point `point`, if any; arrangements that aren't
+    /// rectangular leave gaps inside the bounds.
+    pub fn monitor_at(&self, point: (i32, i32)) -> Option<usize> {
+        self.monitors.iter().position(|m| m.contains(point))
+    }
+    
+    /// `point` moved inside the virtual desktop, unchanged when the layout is empty.
+    pub fn clamp(&self, (x, y): (i32, i32)) -> (i32, i32) {
+        match self.bounds() {
+            Some(b) if b.width > 0 && b.height > 0 => (x.clamp(b.x, b.right() - 1), y.clamp(b.y, b.bottom() - 1)),
+            _ => (x, y),
+        }
+    }
+    
+    /// Desktop coordinates of `position`, given in captured pixels of `display` scaled by
+    /// `scale_factor`. The position may lie off the display, reaching its neighbours.
+    pub fn to_desktop(&self, display: usize, position: (i32, i32), scale_factor: f64) -> (i32, i32) {
+        let (x, y) = self.origin(display);
+        let scale = if scale_factor > 0.0 { scale_factor } else { 1.0 };
+        (
+            x + (position.0 as f64 / scale).round() as i32,
+            y + (position.1 as f64 / scale).round() as i32,
+        )
+    }
+    
+    /// The inverse of `to_desktop`: where desktop point `point` is, in captured pixels of `display`.
+    pub fn to_display(&self, display: usize, point: (i32, i32), scale_factor: f64) -> (i32, i32) {
+        let (x, y) = self.origin(display);
+        let scale = if scale_factor > 0.0 { scale_factor } else { 1.0 };
+        (
+            ((point.0 - x) as f64 * scale).round() as i32,
+            ((point.1 - y) as f64 * scale).round() as i32,
+        )
+    }
+}
@@ -1,22 +1,99 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use bytes::Bytes;
 
+use crate::common::color::{ColorPreference, ColorSpace};
+use crate::common::protocol::EncodingType;
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum EncoderType {
     Software,
     Hardware,
 }
 
-#[derive(Debug, Clone, Copy)]
+impl EncoderType {
+    /// What frames from encoders of this type are sent as.
+    pub fn encoding(&self) -> EncodingType {
+        match self {
+            EncoderType::Software => EncodingType::WebP,
+            EncoderType::Hardware => EncodingType::H264,
+        }
+    }
+}
+
+/// Speed/latency trade-off for encoders that support it (the FFmpeg H.264 encoders).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncoderPreset {
+    /// No lookahead and no frame buffering, each frame comes out as soon as it goes in
+    LowLatency,
+    /// Spend more time per frame for better compression; may hold frames back
+    Quality,
+}
+
+/// How the software encoder compresses frames.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum WebpMode {
+    /// Bit-exact; larger, but small text stays crisp
+    #[default]
+    Lossless,
+    /// Lossy at a WebP quality between 0 and 100
+    Lossy { quality: f32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EncoderTuning {
+    pub preset: EncoderPreset,
+    /// Encoder threads, 0 lets the codec decide
+    pub threads: usize,
+    /// Only the software (WebP) encoder uses this
+    pub webp_mode: WebpMode,
+    /// Frames a hardware encoder may hold before it has to hand one back. 0 waits for
+    /// each frame (lowest latency); N lets N frames overlap capture, adding up to N
+    /// frames of delay for better throughput.
+    pub queue_depth: usize,
+    /// H.264 color matrix and range, overriding the choice by resolution
+    pub color: ColorPreference,
+}
+
+impl Default for EncoderTuning {
+    fn default() -> Self {
+        Self {
+            preset: EncoderPreset::LowLatency,
+            threads: 0,
+            webp_mode: WebpMode::default(),
+            queue_depth: 0,
+            color: ColorPreference::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct EncoderSettings {
     pub width: u32,
     pub height: u32,
     pub fps: u32,
     pub bitrate: u32,
     pub keyframe_interval: u32,
+    pub color_space: ColorSpace,
+    pub tuning: EncoderTuning,
+}
+
+/// Checks that `rgb_data` holds exactly one `width`x`height` RGB frame. Every encoder
+/// indexes the buffer by its configured size, so a frame captured around a resolution
+/// change is refused here instead of panicking inside the conversion.
+pub fn check_frame_len(rgb_data: &[u8], width: u32, height: u32) -> Result<()> {
+    let expected = (width as usize).checked_mul(height as usize).and_then(|pixels| pixels.checked_mul(3));
+    if expected != Some(rgb_data.len()) {
+        return Err(anyhow!(
+            "RGB buffer is {} bytes, expected {} for {}x{}",
+            rgb_data.len(), width as u128 * height as u128 * 3, width, height
+        ));
+    }
+    Ok(())
 }
 
 pub trait VideoEncoder: Send + Sync {
+    /// Fails without touching the encoder's state when `rgb_data` isn't one frame of the
+    /// configured size (see `check_frame_len`).
     fn encode_frame(&mut self, rgb_data: &[u8], force_keyframe: bool) -> Result<EncodedFrame>;
     fn get_type(&self) -> EncoderType;
     fn update_settings(&mut self, settings: EncoderSettings) -> Result<()>;
@@ -46,18 +123,21 @@ impl SoftwareEncoder {
 
 impl VideoEncoder for SoftwareEncoder {
     fn encode_frame(&mut self, rgb_data: &[u8], force_keyframe: bool) -> Result<EncodedFrame> {
+        check_frame_len(rgb_data, self.settings.width, self.settings.height)?;
+        
         let is_keyframe = force_keyframe || self.frame_count % self.settings.keyframe_interval as u64 == 0;
         self.frame_count += 1;
         
-        // Use WebP lossless encoding for better quality
         let encoder = webp::Encoder::from_rgb(
             rgb_data,
             self.settings.width,
             self.settings.height,
         );
         
-        // Use lossless encoding to avoid compression artifacts
-        let encoded = encoder.encode_lossless();
+        let encoded = match self.settings.tuning.webp_mode {
+            WebpMode::Lossless => encoder.encode_lossless(),
+            WebpMode::Lossy { quality } => encoder.encode(quality.clamp(0.0, 100.0)),
+        };
         
         Ok(EncodedFrame {
             data: Bytes::from(encoded.to_vec()),
@@ -79,59 +159,156 @@ impl VideoEncoder for SoftwareEncoder {
     }
 }
 
+/// The packed RGB pixels of a frame from `SoftwareEncoder`, with its width and height.
+pub fn decode_webp(data: &[u8]) -> Result<(Vec<u8>, u32, u32)> {
+    let image = webp::Decoder::new(data).decode()
+        .ok_or_else(|| anyhow!("Invalid WebP frame of {} bytes", data.len()))?;
+    let rgb = if image.is_alpha() {
+        image.chunks_exact(4).flat_map(|pixel| [pixel[0], pixel[1], pixel[2]]).collect()
+    } else {
+        image.to_vec()
+    };
+    Ok((rgb, image.width(), image.height()))
+}
+
 // Use FFmpeg for hardware encoding on all platforms
 pub use crate::common::ffmpeg_encoder::FFmpegHardwareEncoder as HardwareEncoder;
 
+/// A named way to build an encoder, for `select_encoder` to try in order.
+#[derive(Clone, Copy)]
+pub struct EncoderCandidate {
+    pub name: &'static str,
+    pub build: fn(EncoderSettings) -> Result<Box<dyn VideoEncoder>>,
+}
+
+/// An encoder passed over while selecting one, and why.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncoderRejection {
+    pub name: &'static str,
+    pub reason: String,
+}
+
+/// The encoder `select_encoder` settled on, and why each one preferred over it wasn't used.
+pub struct EncoderSelection {
+    pub encoder: Box<dyn VideoEncoder>,
+    pub name: &'static str,
+    pub rejected: Vec<EncoderRejection>,
+}
+
+impl EncoderSelection {
+    /// Whether a preferred encoder had to be passed over.
+    pub fn fell_back(&self) -> bool {
+        !self.rejected.is_empty()
+    }
+    
+    /// One line for the log, e.g. "hardware H.264 unavailable (no device); using software WebP".
+    pub fn summary(&self) -> String {
+        let mut parts: Vec<String> = self.rejected.iter()
+            .map(|rejection| format!("{} unavailable ({})", rejection.name, rejection.reason))
+            .collect();
+        parts.push(format!("using {}", self.name));
+        parts.join("; ")
+    }
+}
+
+/// Build the first of `candidates` that works, logging why each one before it failed.
+pub fn select_encoder(candidates: &[EncoderCandidate], settings: EncoderSettings) -> Result<EncoderSelection> {
+    let mut rejected = Vec::new();
+    for candidate in candidates {
+        match (candidate.build)(settings) {
+            Ok(encoder) => {
+                tracing::debug!("Selected the {} encoder", candidate.name);
+                return Ok(EncoderSelection { encoder, name: candidate.name, rejected });
+            }
+            Err(e) => {
+                tracing::warn!("{} encoder unavailable: {:#}", candidate.name, e);
+                rejected.push(EncoderRejection { name: candidate.name, reason: format!("{:#}", e) });
+            }
+        }
+    }
+    
+    let reasons: Vec<String> = rejected.iter().map(|r| format!("{}: {}", r.name, r.reason)).collect();
+    Err(anyhow!("No encoder available ({})", reasons.join("; ")))
+}
+
 // Encoder factory
 pub struct EncoderFactory;
 
 impl EncoderFactory {
+    /// H.264 through FFmpeg, on the platforms where it has a hardware encoder to use.
+    pub const HARDWARE: EncoderCandidate = EncoderCandidate { name: "hardware H.264", build: build_hardware };
+    pub const SOFTWARE: EncoderCandidate = EncoderCandidate { name: "software WebP", build: build_software };
+    
+    /// What to try for `encoder_type`, most preferred first; hardware falls back to software.
+    pub fn candidates(encoder_type: EncoderType) -> Vec<EncoderCandidate> {
+        match encoder_type {
+            EncoderType::Software => vec![Self::SOFTWARE],
+            EncoderType::Hardware => vec![Self::HARDWARE, Self::SOFTWARE],
+        }
+    }
+    
+    /// What `--safe-mode` tries: the software encoder alone, so a crashing hardware
+    /// driver is never loaded, not even to probe it.
+    pub fn safe_mode_candidates() -> Vec<EncoderCandidate> {
+        vec![Self::SOFTWARE]
+    }
+    
+    /// Build an encoder of `encoder_type`, or the next best, recording what was passed over.
+    pub fn select(encoder_type: EncoderType, settings: EncoderSettings) -> Result<EncoderSelection> {
+        select_encoder(&Self::candidates(encoder_type), settings)
+    }
+    
     pub fn create_encoder(
         encoder_type: EncoderType,
         settings: EncoderSettings,
     ) -> Result<Box<dyn VideoEncoder>> {
-        match encoder_type {
-            EncoderType::Software => {
-                Ok(Box::new(SoftwareEncoder::new(settings)?))
-            }
-            EncoderType::Hardware => {
-                match HardwareEncoder::new(settings) {
-                    Ok(encoder) => Ok(Box::new(encoder)),
-                    Err(e) => {
-                        tracing::warn!("Hardware encoder failed: {}, falling back to software", e);
-                        Ok(Box::new(SoftwareEncoder::new(settings)?))
-                    }
-                }
-            }
-        }
+        Self::select(encoder_type, settings).map(|selection| selection.encoder)
+    }
+    
+    /// What a capture at a typical size would end up with, for reporting at startup.
+    pub fn probe() -> Result<EncoderSelection> {
+        Self::select(EncoderType::Hardware, probe_settings())
     }
     
     pub fn is_hardware_available() -> bool {
-        #[cfg(any(target_os = "macos", windows))]
-        {
-            // Try to create a test encoder
-            let test_settings = EncoderSettings {
-                width: 1920,
-                height: 1080,
-                fps: 30,
-                bitrate: 5_000_000,
-                keyframe_interval: 60,
-            };
-            match HardwareEncoder::new(test_settings) {
-                Ok(_) => {
-                    tracing::info!("Hardware encoder is available");
-                    true
-                }
-                Err(e) => {
-                    tracing::info!("Hardware encoder not available: {}", e);
-                    false
-                }
+        match build_hardware(probe_settings()) {
+            Ok(_) => {
+                tracing::debug!("Hardware encoder is available");
+                true
+            }
+            Err(e) => {
+                tracing::debug!("Hardware encoder not available: {:#}", e);
+                false
             }
         }
-        
-        #[cfg(not(any(target_os = "macos", windows)))]
-        {
-            false
-        }
     }
-}
\ No newline at end of file
+}
+
+fn probe_settings() -> EncoderSettings {
+    EncoderSettings {
+        width: 1920,
+        height: 1080,
+        fps: 30,
+        bitrate: 5_000_000,
+        keyframe_interval: 60,
+        color_space: ColorSpace::for_resolution(1920, 1080),
+        tuning: EncoderTuning::default(),
+    }
+}
+
+fn build_hardware(settings: EncoderSettings) -> Result<Box<dyn VideoEncoder>> {
+    #[cfg(any(target_os = "macos", windows))]
+    {
+        Ok(Box::new(HardwareEncoder::new(settings)?))
+    }
+    
+    #[cfg(not(any(target_os = "macos", windows)))]
+    {
+        let _ = settings;
+        Err(anyhow!("hardware encoding is only used on macOS and Windows"))
+    }
+}
+
+fn build_software(settings: EncoderSettings) -> Result<Box<dyn VideoEncoder>> {
+    Ok(Box::new(SoftwareEncoder::new(settings)?))
+}
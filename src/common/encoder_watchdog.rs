@@ -0,0 +1,223 @@
+use anyhow::{anyhow, Result};
+use bytes::Bytes;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::{error, warn};
+
+use crate::common::encoder::{EncodedFrame, EncoderSettings, EncoderType, VideoEncoder};
+
+/// How long a single encode may take before the encoder is considered wedged
+pub const DEFAULT_ENCODE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Builds a fresh encoder, used at start-up and after every recovery.
+pub type EncoderBuilder = Box<dyn Fn(EncoderSettings) -> Result<Box<dyn VideoEncoder>> + Send + Sync>;
+
+enum Job {
+    Encode { frame: Vec<u8>, force_keyframe: bool },
+    Update(EncoderSettings),
+}
+
+enum Reply {
+    Encoded(Result<EncodedFrame>),
+    Updated(Result<()>),
+}
+
+// An encoder running on its own thread
+struct Worker {
+    jobs: Sender<Job>,
+    replies: Mutex<Receiver<Reply>>,
+}
+
+impl Worker {
+    fn spawn(mut encoder: Box<dyn VideoEncoder>) -> Result<Self> {
+        let (jobs, job_rx) = mpsc::channel::<Job>();
+        let (reply_tx, replies) = mpsc::channel::<Reply>();
+        
+        std::thread::Builder::new()
+            .name("video-encoder".to_string())
+            .spawn(move || {
+                // Ends when the watchdog drops us; a wedged encoder keeps its thread until it returns
+                while let Ok(job) = job_rx.recv() {
+                    let reply = match job {
+                        Job::Encode { frame, force_keyframe } => Reply::Encoded(encoder.encode_frame(&frame, force_keyframe)),
+                        Job::Update(settings) => Reply::Updated(encoder.update_settings(settings)),
+                    };
+                    if reply_tx.send(reply).is_err() {
+                        break;
+                    }
+                }
+            })?;
+        
+        Ok(Self { jobs, replies: Mutex::new(replies) })
+    }
+    
+    fn submit(&self, job: Job) -> Result<()> {
+        self.jobs.send(job).map_err(|_| anyhow!("Encoder thread exited"))
+    }
+    
+    /// The oldest outstanding reply, or `None` if it doesn't come within `timeout`.
+    fn wait(&self, timeout: Duration) -> Result<Option<Reply>> {
+        match self.replies.lock().unwrap().recv_timeout(timeout) {
+            Ok(reply) => Ok(Some(reply)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(anyhow!("Encoder thread exited")),
+        }
+    }
+    
+    /// The oldest outstanding reply if it is already there.
+    fn poll(&self) -> Result<Option<Reply>> {
+        match self.replies.lock().unwrap().try_recv() {
+            Ok(reply) => Ok(Some(reply)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(anyhow!("Encoder thread exited")),
+        }
+    }
+    
+    fn run(&self, job: Job, timeout: Duration) -> Result<Option<Reply>> {
+        self.submit(job)?;
+        self.wait(timeout)
+    }
+}
+
+/// Runs an encoder on a worker thread and replaces it when a call hangs.
+///
+/// Some hardware encoders occasionally never return from `encode_frame` (VideoToolbox,
+/// wedged Media Foundation transforms, ffmpeg deadlocks). When a call takes longer than
+/// the timeout the stuck encoder is abandoned, a fresh one is built, and the next frame
+/// is forced to be a keyframe. The frame that timed out is reported as an empty frame,
+/// which callers already treat as "nothing to send yet".
+///
+/// With a `queue_depth` above 0 in the tuning, up to that many frames stay in flight on
+/// the worker while capture carries on, and each call returns the oldest finished frame
+/// (or an empty one while the queue fills).
+pub struct WatchdogEncoder {
+    worker: Worker,
+    builder: EncoderBuilder,
+    settings: EncoderSettings,
+    encoder_type: EncoderType,
+    timeout: Duration,
+    force_keyframe: bool,
+    recoveries: u64,
+    // Frames submitted to the worker whose reply hasn't been taken yet
+    in_flight: usize,
+}
+
+impl WatchdogEncoder {
+    pub fn new(settings: EncoderSettings, timeout: Duration, builder: EncoderBuilder) -> Result<Self> {
+        let encoder = builder(settings)?;
+        let encoder_type = encoder.get_type();
+        
+        Ok(Self {
+            worker: Worker::spawn(encoder)?,
+            builder,
+            settings,
+            encoder_type,
+            timeout,
+            force_keyframe: false,
+            recoveries: 0,
+            in_flight: 0,
+        })
+    }
+    
+    /// Frames handed to the encoder that haven't come back yet.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight
+    }
+    
+    /// How many times a stalled encoder has been replaced.
+    pub fn recoveries(&self) -> u64 {
+        self.recoveries
+    }
+    
+    fn recover(&mut self, stalled: &str) -> Result<()> {
+        warn!("Encoder stalled in {} for more than {:?}, recreating it", stalled, self.timeout);
+        
+        let encoder = (self.builder)(self.settings).map_err(|e| {
+            error!("Failed to recreate stalled encoder: {}", e);
+            e
+        })?;
+        self.encoder_type = encoder.get_type();
+        self.worker = Worker::spawn(encoder)?;
+        self.in_flight = 0;
+        self.force_keyframe = true;
+        self.recoveries += 1;
+        
+        warn!("Encoder recovered ({} recoveries so far), next frame is a keyframe", self.recoveries);
+        Ok(())
+    }
+}
+
+impl VideoEncoder for WatchdogEncoder {
+    fn encode_frame(&mut self, rgb_data: &[u8], force_keyframe: bool) -> Result<EncodedFrame> {
+        let job = Job::Encode {
+            frame: rgb_data.to_vec(),
+            force_keyframe: force_keyframe || self.force_keyframe,
+        };
+        
+        self.worker.submit(job)?;
+        self.in_flight += 1;
+        
+        // Only block once more frames are queued than the tuning allows
+        let queue_full = self.in_flight > self.settings.tuning.queue_depth;
+        let reply = if queue_full {
+            self.worker.wait(self.timeout)?
+        } else {
+            self.worker.poll()?
+        };
+        
+        match reply {
+            Some(Reply::Encoded(result)) => {
+                self.in_flight -= 1;
+                let frame = result?;
+                if frame.is_keyframe {
+                    self.force_keyframe = false;
+                }
+                Ok(frame)
+            }
+            Some(Reply::Updated(_)) => Err(anyhow!("Encoder thread replied out of order")),
+            None => {
+                if queue_full {
+                    self.recover("encode_frame")?;
+                }
+                Ok(empty_frame())
+            }
+        }
+    }
+    
+    fn get_type(&self) -> EncoderType {
+        self.encoder_type
+    }
+    
+    fn update_settings(&mut self, settings: EncoderSettings) -> Result<()> {
+        // Frames still queued were encoded for the old settings; let them finish and drop them
+        while self.in_flight > 0 {
+            match self.worker.wait(self.timeout)? {
+                Some(_) => self.in_flight -= 1,
+                None => {
+                    self.settings = settings;
+                    return self.recover("update_settings");
+                }
+            }
+        }
+        self.settings = settings;
+        
+        match self.worker.run(Job::Update(settings), self.timeout)? {
+            Some(Reply::Updated(result)) => result,
+            Some(Reply::Encoded(_)) => Err(anyhow!("Encoder thread replied out of order")),
+            // The replacement is built with the new settings
+            None => self.recover("update_settings"),
+        }
+    }
+}
+
+fn empty_frame() -> EncodedFrame {
+    EncodedFrame {
+        data: Bytes::new(),
+        is_keyframe: false,
+        timestamp: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64,
+    }
+}
@@ -1,9 +1,9 @@
-use anyhow::{Result, Context};
+use anyhow::{Result, Context, anyhow};
 use bytes::Bytes;
 use ffmpeg_next as ffmpeg;
-use ffmpeg::{codec, encoder, format, frame, Rational};
+use ffmpeg::{codec, encoder, format, frame, Dictionary, Rational};
 
-use crate::common::encoder::{VideoEncoder, EncoderType, EncoderSettings, EncodedFrame};
+use crate::common::encoder::{check_frame_len, VideoEncoder, EncoderType, EncoderSettings, EncodedFrame, EncoderPreset, EncoderTuning};
 
 pub struct FFmpegHardwareEncoder {
     encoder: encoder::Video,
@@ -59,13 +59,29 @@ impl FFmpegHardwareEncoder {
         encoder.set_max_b_frames(0); // No B-frames for low latency
         encoder.set_gop(settings.keyframe_interval);
         
-        // Set quality/speed tradeoff (removed for now, ffmpeg-next doesn't expose priv_data)
+        // Signal the color matrix and range in the stream so the decoder converts back the same way
+        encoder.set_colorspace(settings.color_space.ffmpeg_space());
+        encoder.set_color_range(settings.color_space.ffmpeg_range());
         
-        let encoder = encoder.open()?;
+        // Slice threads split each frame across cores; frame threads add a frame of delay per thread
+        let thread_kind = match settings.tuning.preset {
+            EncoderPreset::LowLatency => codec::threading::Type::Slice,
+            EncoderPreset::Quality => codec::threading::Type::Frame,
+        };
+        encoder.set_threading(codec::threading::Config {
+            kind: thread_kind,
+            count: settings.tuning.threads,
+            ..Default::default()
+        });
+        
+        // Codec-private options (preset, tune, ...) go through the options dictionary
+        let encoder = encoder.open_with(encoder_options(codec.name(), settings.tuning))?;
         
         // Create frame for input
         let mut frame = frame::Video::new(format::Pixel::YUV420P, settings.width, settings.height);
         frame.set_pts(Some(0));
+        frame.set_color_space(settings.color_space.ffmpeg_space());
+        frame.set_color_range(settings.color_space.ffmpeg_range());
         
         Ok(Self {
             encoder,
@@ -81,25 +97,33 @@ impl FFmpegHardwareEncoder {
     fn rgb_to_yuv420p(&mut self, rgb_data: &[u8]) -> Result<()> {
         let width = self.settings.width as usize;
         let height = self.settings.height as usize;
+        let color_space = self.settings.color_space;
+        // rgb_data was checked against the configured size in encode_frame
         
         // Get strides first
         let y_stride = self.frame.stride(0);
         let u_stride = self.frame.stride(1);
         let v_stride = self.frame.stride(2);
         
+        // The frame must be large enough for the configured dimensions
+        let chroma_width = (width + 1) / 2;
+        let chroma_height = (height + 1) / 2;
+        if width == 0 || height == 0
+            || self.frame.data(0).len() < (height - 1) * y_stride + width
+            || self.frame.data(1).len() < (chroma_height - 1) * u_stride + chroma_width
+            || self.frame.data(2).len() < (chroma_height - 1) * v_stride + chroma_width
+        {
+            return Err(anyhow!("YUV frame planes are too small for {}x{}", width, height));
+        }
+        
         // Process Y plane
         {
             let y_plane = self.frame.data_mut(0);
             for y in 0..height {
                 for x in 0..width {
                     let rgb_idx = (y * width + x) * 3;
-                    let r = rgb_data[rgb_idx] as f32;
-                    let g = rgb_data[rgb_idx + 1] as f32;
-                    let b = rgb_data[rgb_idx + 2] as f32;
-                    
-                    // BT.601 conversion
-                    let y_val = (0.299 * r + 0.587 * g + 0.114 * b) as u8;
-                    y_plane[y * y_stride + x] = y_val;
+                    let (r, g, b) = (rgb_data[rgb_idx], rgb_data[rgb_idx + 1], rgb_data[rgb_idx + 2]);
+                    y_plane[y * y_stride + x] = color_space.luma(r, g, b);
                 }
             }
         }
@@ -110,12 +134,8 @@ impl FFmpegHardwareEncoder {
             for y in (0..height).step_by(2) {
                 for x in (0..width).step_by(2) {
                     let rgb_idx = (y * width + x) * 3;
-                    let r = rgb_data[rgb_idx] as f32;
-                    let g = rgb_data[rgb_idx + 1] as f32;
-                    let b = rgb_data[rgb_idx + 2] as f32;
-                    
-                    let u_idx = (y / 2) * u_stride + (x / 2);
-                    u_plane[u_idx] = ((-0.169 * r - 0.331 * g + 0.500 * b) + 128.0) as u8;
+                    let (r, g, b) = (rgb_data[rgb_idx], rgb_data[rgb_idx + 1], rgb_data[rgb_idx + 2]);
+                    u_plane[(y / 2) * u_stride + (x / 2)] = color_space.chroma(r, g, b).0;
                 }
             }
         }
@@ -126,12 +146,8 @@ impl FFmpegHardwareEncoder {
             for y in (0..height).step_by(2) {
                 for x in (0..width).step_by(2) {
                     let rgb_idx = (y * width + x) * 3;
-                    let r = rgb_data[rgb_idx] as f32;
-                    let g = rgb_data[rgb_idx + 1] as f32;
-                    let b = rgb_data[rgb_idx + 2] as f32;
-                    
-                    let v_idx = (y / 2) * v_stride + (x / 2);
-                    v_plane[v_idx] = ((0.500 * r - 0.419 * g - 0.081 * b) + 128.0) as u8;
+                    let (r, g, b) = (rgb_data[rgb_idx], rgb_data[rgb_idx + 1], rgb_data[rgb_idx + 2]);
+                    v_plane[(y / 2) * v_stride + (x / 2)] = color_space.chroma(r, g, b).1;
                 }
             }
         }
@@ -140,6 +156,58 @@ impl FFmpegHardwareEncoder {
     }
 }
 
+/// Map a preset onto the private options of the codec we ended up with.
+/// Unknown codecs get an empty dictionary and run with their defaults.
+fn encoder_options(codec_name: &str, tuning: EncoderTuning) -> Dictionary<'static> {
+    let low_latency = tuning.preset == EncoderPreset::LowLatency;
+    let mut options = Dictionary::new();
+    
+    match codec_name {
+        "libx264" => {
+            options.set("preset", if low_latency { "ultrafast" } else { "veryfast" });
+            if low_latency {
+                options.set("tune", "zerolatency");
+            }
+        }
+        "h264_nvenc" | "hevc_nvenc" => {
+            options.set("preset", if low_latency { "p1" } else { "p4" });
+            options.set("tune", if low_latency { "ull" } else { "ll" });
+            if low_latency {
+                options.set("zerolatency", "1");
+                options.set("rc-lookahead", "0");
+            }
+            // How many frames NVENC may hold back before output
+            if low_latency || tuning.queue_depth > 0 {
+                options.set("delay", &tuning.queue_depth.to_string());
+            }
+        }
+        "h264_qsv" => {
+            options.set("preset", if low_latency { "veryfast" } else { "medium" });
+            if low_latency {
+                options.set("look_ahead", "0");
+            }
+            if low_latency || tuning.queue_depth > 0 {
+                options.set("async_depth", &(tuning.queue_depth + 1).to_string());
+            }
+        }
+        "h264_amf" => {
+            options.set("usage", if low_latency { "ultralowlatency" } else { "lowlatency" });
+            options.set("quality", if low_latency { "speed" } else { "balanced" });
+        }
+        "h264_videotoolbox" | "hevc_videotoolbox" => {
+            options.set("realtime", if low_latency { "1" } else { "0" });
+        }
+        "h264_vaapi" => {
+            if low_latency || tuning.queue_depth > 0 {
+                options.set("async_depth", &(tuning.queue_depth + 1).to_string());
+            }
+        }
+        _ => {}
+    }
+    
+    options
+}
+
 impl Drop for FFmpegHardwareEncoder {
     fn drop(&mut self) {
         // Flush encoder
@@ -152,6 +220,8 @@ impl Drop for FFmpegHardwareEncoder {
 
 impl VideoEncoder for FFmpegHardwareEncoder {
     fn encode_frame(&mut self, rgb_data: &[u8], force_keyframe: bool) -> Result<EncodedFrame> {
+        check_frame_len(rgb_data, self.settings.width, self.settings.height)?;
+        
         // Convert RGB to YUV420P
         self.rgb_to_yuv420p(rgb_data)?;
         
@@ -0,0 +1,146 @@
+use crate::common::frame_processor::TileData;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Pixels around the cursor kept at full quality by default
+pub const DEFAULT_FOVEA_RADIUS: u32 = 256;
+/// Bits kept per color channel outside the fovea
+pub const DEFAULT_PERIPHERY_BITS: u32 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TileQuality {
+    Full,
+    Reduced,
+}
+
+/// A rectangle of frame pixels, such as a code editor the user wants sent without loss.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Region {
+    /// The region between two opposite corners, `None` when it has no area.
+    pub fn from_corners(a: (u32, u32), b: (u32, u32)) -> Option<Self> {
+        let width = a.0.abs_diff(b.0);
+        let height = a.1.abs_diff(b.1);
+        (width > 0 && height > 0).then_some(Self { x: a.0.min(b.0), y: a.1.min(b.1), width, height })
+    }
+    
+    /// Whether any pixel of the `width`x`height` rectangle at (`x`, `y`) lies inside.
+    pub fn intersects(&self, x: u32, y: u32, width: u32, height: u32) -> bool {
+        let overlaps = |start: u32, len: u32, other_start: u32, other_len: u32| {
+            (start as u64) < other_start as u64 + other_len as u64 && (other_start as u64) < start as u64 + len as u64
+        };
+        overlaps(self.x, self.width, x, width) && overlaps(self.y, self.height, y, height)
+    }
+}
+
+/// Foveated encoding: tiles near the user's cursor are sent exactly, tiles further away
+/// have their color precision reduced so they compress much better.
+///
+/// Reduced tiles are remembered, and re-sent at full quality once the cursor comes close,
+/// so nothing stays blurry where the user is looking. The client needs no changes; reduced
+/// tiles are ordinary RGB tiles. Tiles touching a lossless region are never reduced,
+/// wherever the cursor is.
+pub struct Foveation {
+    radius: u32,
+    periphery_bits: u32,
+    focus: Option<(u32, u32)>,
+    lossless: Vec<Region>,
+    // Tiles the client currently has at reduced quality: top-left corner -> size
+    reduced: HashMap<(u32, u32), (u32, u32)>,
+}
+
+impl Foveation {
+    pub fn new(radius: u32, periphery_bits: u32) -> Self {
+        Self {
+            radius,
+            periphery_bits: periphery_bits.clamp(1, 8),
+            focus: None,
+            lossless: Vec::new(),
+            reduced: HashMap::new(),
+        }
+    }
+    
+    /// The remote cursor position; with no focus every tile is full quality.
+    pub fn set_focus(&mut self, focus: Option<(u32, u32)>) {
+        self.focus = focus;
+    }
+    
+    /// Regions viewers asked to be sent exactly; reduced tiles inside them are re-sent.
+    pub fn set_lossless_regions(&mut self, regions: Vec<Region>) {
+        self.lossless = regions;
+    }
+    
+    pub fn tile_quality(&self, tile: &TileData) -> TileQuality {
+        self.quality_at(tile.x, tile.y, tile.width, tile.height)
+    }
+    
+    fn quality_at(&self, x: u32, y: u32, width: u32, height: u32) -> TileQuality {
+        if self.lossless.iter().any(|region| region.intersects(x, y, width, height)) {
+            return TileQuality::Full;
+        }
+        let Some((fx, fy)) = self.focus else {
+            return TileQuality::Full;
+        };
+        
+        // Distance from the focus to the nearest point of the tile
+        let dx = fx.saturating_sub(x + width.max(1) - 1).max(x.saturating_sub(fx)) as u64;
+        let dy = fy.saturating_sub(y + height.max(1) - 1).max(y.saturating_sub(fy)) as u64;
+        
+        if dx * dx + dy * dy <= self.radius as u64 * self.radius as u64 {
+            TileQuality::Full
+        } else {
+            TileQuality::Reduced
+        }
+    }
+    
+    /// Reduce periphery tiles in place (uncompressed RGB), tracking what the client will have.
+    pub fn apply(&mut self, tiles: &mut [TileData]) {
+        for tile in tiles {
+            match self.tile_quality(tile) {
+                TileQuality::Full => {
+                    self.reduced.remove(&(tile.x, tile.y));
+                }
+                TileQuality::Reduced => {
+                    let mut data = tile.data.to_vec();
+                    quantize(&mut data, self.periphery_bits);
+                    tile.data = data.into();
+                    self.reduced.insert((tile.x, tile.y), (tile.width, tile.height));
+                }
+            }
+        }
+    }
+    
+    /// Tiles sent reduced that are now inside the fovea and should be re-sent in full.
+    pub fn tiles_to_refresh(&self) -> Vec<(u32, u32)> {
+        let mut refresh: Vec<(u32, u32)> = self.reduced.iter()
+            .filter(|(&(x, y), &(width, height))| self.quality_at(x, y, width, height) == TileQuality::Full)
+            .map(|(&corner, _)| corner)
+            .collect();
+        refresh.sort_unstable();
+        refresh
+    }
+    
+    /// Forget what the client has, e.g. before a keyframe replaces everything.
+    pub fn reset(&mut self) {
+        self.reduced.clear();
+    }
+}
+
+/// Keep the top `bits` of every byte, filling the dropped bits with their midpoint so
+/// the image doesn't darken.
+pub fn quantize(data: &mut [u8], bits: u32) {
+    if bits >= 8 {
+        return;
+    }
+    let mask = 0xFFu8 << (8 - bits);
+    let midpoint = 1u8 << (7 - bits);
+    for value in data {
+        *value = (*value & mask) | midpoint;
+    }
+}
@@ -1,12 +1,22 @@
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use bytes::Bytes;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
 use parking_lot::RwLock;
+use crate::common::framing::MAX_FRAME_LEN;
 
 const TILE_SIZE: usize = 64; // Process in 64x64 tiles for better cache locality
 
+/// Delta frames in a row before a keyframe is sent anyway; `QualityMode::High`'s interval.
+pub const DEFAULT_MAX_DELTA_RUN: u32 = 60;
+
 pub struct FrameProcessor {
-    last_frame: Arc<RwLock<Option<Vec<u8>>>>,
+    /// Hash of each tile as last sent, row by row; empty until the first keyframe
+    sent_hashes: Arc<RwLock<Vec<u64>>>,
+    /// Delta frames since the last keyframe, including ones with nothing in them
+    delta_run: AtomicU32,
+    /// Cap on `delta_run`, so a client that missed a delta resyncs within this many frames
+    max_delta_run: u32,
     width: u32,
     height: u32,
     tile_width: u32,
@@ -38,6 +48,40 @@ pub struct TileData {
     pub data: Bytes,
 }
 
+impl TileData {
+    /// Bytes of RGB data a tile of this size carries, or `None` if that overflows.
+    pub fn expected_len(&self) -> Option<usize> {
+        (self.width as usize).checked_mul(self.height as usize)?.checked_mul(3)
+    }
+    
+    /// Checks that the tile lies inside a `frame_width`x`frame_height` frame.
+    pub fn check_bounds(&self, frame_width: u32, frame_height: u32) -> Result<()> {
+        let fits_x = self.x.checked_add(self.width).map_or(false, |right| right <= frame_width);
+        let fits_y = self.y.checked_add(self.height).map_or(false, |bottom| bottom <= frame_height);
+        if !fits_x || !fits_y {
+            return Err(anyhow!(
+                "Tile {}x{} at ({}, {}) does not fit in {}x{} frame",
+                self.width, self.height, self.x, self.y, frame_width, frame_height
+            ));
+        }
+        Ok(())
+    }
+    
+    /// Checks the tile's position and that its (uncompressed) data matches its size.
+    pub fn validate(&self, frame_width: u32, frame_height: u32) -> Result<()> {
+        self.check_bounds(frame_width, frame_height)?;
+        
+        let expected = self.expected_len().unwrap_or(usize::MAX);
+        if self.data.len() != expected {
+            return Err(anyhow!(
+                "Tile data is {} bytes, expected {} for {}x{} tile",
+                self.data.len(), expected, self.width, self.height
+            ));
+        }
+        Ok(())
+    }
+}
+
 mod bytes_serde {
     use bytes::Bytes;
     use serde::{Deserialize, Deserializer, Serialize, Serializer};
@@ -64,7 +108,9 @@ impl FrameProcessor {
         let tile_height = (height + TILE_SIZE as u32 - 1) / TILE_SIZE as u32;
         
         Self {
-            last_frame: Arc::new(RwLock::new(None)),
+            sent_hashes: Arc::new(RwLock::new(Vec::new())),
+            delta_run: AtomicU32::new(0),
+            max_delta_run: DEFAULT_MAX_DELTA_RUN,
             width,
             height,
             tile_width,
@@ -72,162 +118,318 @@ impl FrameProcessor {
         }
     }
     
-    pub fn process_frame(&self, frame: &[u8], force_keyframe: bool) -> Result<ProcessedFrame> {
-        let mut last_frame = self.last_frame.write();
+    fn frame_len(&self) -> usize {
+        self.width as usize * self.height as usize * 3 // RGB
+    }
+    
+    fn check_frame_len(&self, len: usize) -> Result<()> {
+        if len != self.frame_len() {
+            return Err(anyhow!(
+                "Frame buffer is {} bytes, expected {} for {}x{} RGB",
+                len, self.frame_len(), self.width, self.height
+            ));
+        }
+        Ok(())
+    }
+    
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+    
+    /// Send a keyframe after `frames` deltas in a row, however little the screen changed.
+    pub fn set_max_delta_run(&mut self, frames: u32) {
+        self.max_delta_run = frames.max(1);
+    }
+    
+    pub fn max_delta_run(&self) -> u32 {
+        self.max_delta_run
+    }
+    
+    /// Delta frames produced since the last keyframe.
+    pub fn delta_run(&self) -> u32 {
+        self.delta_run.load(Ordering::Relaxed)
+    }
+    
+    /// Number of tiles covering the whole frame.
+    pub fn tile_count(&self) -> usize {
+        (self.tile_width * self.tile_height) as usize
+    }
+    
+    /// Encode `frame` against what was last sent: a keyframe, a delta of the changed tiles,
+    /// or `None` when no tile changed and there is nothing to send.
+    pub fn process_frame(&self, frame: &[u8], force_keyframe: bool) -> Result<Option<ProcessedFrame>> {
+        self.check_frame_len(frame.len())?;
+        let hashes = self.tile_hashes(frame);
+        let mut sent_hashes = self.sent_hashes.write();
         
-        // First frame or forced keyframe
-        if last_frame.is_none() || force_keyframe {
-            *last_frame = Some(frame.to_vec());
-            
-            return Ok(ProcessedFrame {
-                frame_type: FrameType::KeyFrame,
-                data: Bytes::copy_from_slice(frame),
-                width: self.width,
-                height: self.height,
-                tiles: None,
-            });
+        // First frame, forced keyframe, or deltas have run long enough that a lost one
+        // could have left the client drifting
+        if sent_hashes.is_empty() || force_keyframe || self.delta_run() >= self.max_delta_run {
+            *sent_hashes = hashes;
+            return Ok(Some(self.keyframe(frame)));
         }
         
         // Delta encoding - find changed tiles
-        let previous = last_frame.as_ref().unwrap();
-        let changed_tiles = self.find_changed_tiles(previous, frame);
+        let changed_tiles = self.find_changed_tiles(frame, &hashes, &sent_hashes)?;
+        *sent_hashes = hashes;
         
         // If more than 60% of tiles changed, send keyframe
-        let total_tiles = (self.tile_width * self.tile_height) as usize;
+        let total_tiles = self.tile_count();
         if changed_tiles.len() > total_tiles * 6 / 10 {
-            *last_frame = Some(frame.to_vec());
-            
-            return Ok(ProcessedFrame {
-                frame_type: FrameType::KeyFrame,
-                data: Bytes::copy_from_slice(frame),
-                width: self.width,
-                height: self.height,
-                tiles: None,
-            });
+            return Ok(Some(self.keyframe(frame)));
         }
         
-        // Update last frame with changed tiles
-        let mut new_frame = previous.clone();
-        for tile in &changed_tiles {
-            self.copy_tile_to_frame(&mut new_frame, tile);
+        // An unchanged frame still counts toward the run, so a static screen resyncs too
+        self.delta_run.fetch_add(1, Ordering::Relaxed);
+        if changed_tiles.is_empty() {
+            return Ok(None);
         }
-        *last_frame = Some(new_frame);
         
         // Return delta frame with only changed tiles
-        Ok(ProcessedFrame {
+        Ok(Some(self.delta_frame(changed_tiles)))
+    }
+    
+    /// A delta frame carrying `tiles`.
+    pub fn delta_frame(&self, tiles: Vec<TileData>) -> ProcessedFrame {
+        ProcessedFrame {
             frame_type: FrameType::DeltaFrame,
             data: Bytes::new(), // No full data for delta frames
             width: self.width,
             height: self.height,
-            tiles: Some(changed_tiles),
-        })
+            tiles: Some(tiles),
+        }
     }
     
-    fn find_changed_tiles(&self, previous: &[u8], current: &[u8]) -> Vec<TileData> {
+    /// Tiles of `current` that differ from what the client was last sent. Comparing with
+    /// what was sent rather than the previous capture means content that flickers away
+    /// and back between two frames costs nothing.
+    fn find_changed_tiles(&self, current: &[u8], hashes: &[u64], sent_hashes: &[u64]) -> Result<Vec<TileData>> {
         let mut changed_tiles = Vec::new();
-        let bytes_per_pixel = 3; // RGB
-        
-        // Use rayon for parallel processing if available
-        for tile_y in 0..self.tile_height {
-            for tile_x in 0..self.tile_width {
-                let x = tile_x * TILE_SIZE as u32;
-                let y = tile_y * TILE_SIZE as u32;
-                let w = TILE_SIZE.min((self.width - x) as usize) as u32;
-                let h = TILE_SIZE.min((self.height - y) as usize) as u32;
-                
-                if self.is_tile_changed(previous, current, x, y, w, h, bytes_per_pixel) {
-                    let tile_data = self.extract_tile(current, x, y, w, h, bytes_per_pixel);
-                    changed_tiles.push(TileData {
-                        x,
-                        y,
-                        width: w,
-                        height: h,
-                        data: Bytes::from(tile_data),
-                    });
-                }
+        for (index, (hash, sent)) in hashes.iter().zip(sent_hashes).enumerate() {
+            if hash == sent {
+                continue;
             }
+            let (x, y, w, h) = self.tile_rect(index);
+            let tile_data = self.extract_tile(current, x, y, w, h, 3)?;
+            changed_tiles.push(TileData {
+                x,
+                y,
+                width: w,
+                height: h,
+                data: Bytes::from(tile_data),
+            });
         }
-        
-        changed_tiles
+        Ok(changed_tiles)
     }
     
-    fn is_tile_changed(&self, prev: &[u8], curr: &[u8], x: u32, y: u32, w: u32, h: u32, bpp: usize) -> bool {
-        // Quick sampling first - check corners and center
-        let sample_points = [
-            (0, 0),
-            (w - 1, 0),
-            (0, h - 1),
-            (w - 1, h - 1),
-            (w / 2, h / 2),
-        ];
-        
-        for (sx, sy) in sample_points {
-            let px = x + sx;
-            let py = y + sy;
-            
-            if px >= self.width || py >= self.height {
-                continue;
-            }
-            
-            let offset = ((py * self.width + px) * bpp as u32) as usize;
-            if offset + bpp <= prev.len() && offset + bpp <= curr.len() {
-                if prev[offset..offset + bpp] != curr[offset..offset + bpp] {
-                    return true;
-                }
-            }
+    fn keyframe(&self, frame: &[u8]) -> ProcessedFrame {
+        self.delta_run.store(0, Ordering::Relaxed);
+        ProcessedFrame {
+            frame_type: FrameType::KeyFrame,
+            data: Bytes::copy_from_slice(frame),
+            width: self.width,
+            height: self.height,
+            tiles: None,
         }
-        
-        false
     }
     
-    fn extract_tile(&self, frame: &[u8], x: u32, y: u32, w: u32, h: u32, bpp: usize) -> Vec<u8> {
-        let mut tile_data = Vec::with_capacity((w * h * bpp as u32) as usize);
+    /// Position and size of tile `index`, counting row by row.
+    fn tile_rect(&self, index: usize) -> (u32, u32, u32, u32) {
+        let x = (index as u32 % self.tile_width) * TILE_SIZE as u32;
+        let y = (index as u32 / self.tile_width) * TILE_SIZE as u32;
+        let w = TILE_SIZE.min((self.width - x) as usize) as u32;
+        let h = TILE_SIZE.min((self.height - y) as usize) as u32;
+        (x, y, w, h)
+    }
+    
+    /// A hash of every tile in a frame whose length has been checked, row by row.
+    fn tile_hashes(&self, frame: &[u8]) -> Vec<u64> {
+        let bpp = 3; // RGB
+        (0..self.tile_count())
+            .map(|index| {
+                let (x, y, w, h) = self.tile_rect(index);
+                let row_len = w as usize * bpp;
+                let mut hash = TileHash::new();
+                for py in y as usize..(y + h) as usize {
+                    let row_start = (py * self.width as usize + x as usize) * bpp;
+                    hash.write(&frame[row_start..row_start + row_len]);
+                }
+                hash.finish()
+            })
+            .collect()
+    }
+    
+    fn extract_tile(&self, frame: &[u8], x: u32, y: u32, w: u32, h: u32, bpp: usize) -> Result<Vec<u8>> {
+        let row_len = w as usize * bpp;
+        let mut tile_data = Vec::with_capacity(row_len * h as usize);
         
         for ty in 0..h {
-            let py = y + ty;
-            if py >= self.height {
+            let py = (y + ty) as usize;
+            if py >= self.height as usize {
                 break;
             }
             
-            let row_start = ((py * self.width + x) * bpp as u32) as usize;
-            let row_end = row_start + (w * bpp as u32) as usize;
-            
-            if row_end <= frame.len() {
-                tile_data.extend_from_slice(&frame[row_start..row_end]);
-            }
+            let row_start = (py * self.width as usize + x as usize) * bpp;
+            let row = frame
+                .get(row_start..row_start + row_len)
+                .ok_or_else(|| anyhow!("Tile row at ({}, {}) is outside the frame buffer", x, py))?;
+            tile_data.extend_from_slice(row);
         }
         
-        tile_data
+        Ok(tile_data)
     }
     
-    fn copy_tile_to_frame(&self, frame: &mut [u8], tile: &TileData) {
+    fn copy_tile_to_frame(&self, frame: &mut [u8], tile: &TileData) -> Result<()> {
         let bpp = 3; // RGB
-        let mut tile_offset = 0;
+        self.check_frame_len(frame.len())?;
         
-        for ty in 0..tile.height {
-            let py = tile.y + ty;
-            if py >= self.height {
-                break;
-            }
-            
-            let frame_offset = ((py * self.width + tile.x) * bpp) as usize;
-            let copy_len = (tile.width * bpp) as usize;
-            
-            if frame_offset + copy_len <= frame.len() && tile_offset + copy_len <= tile.data.len() {
-                frame[frame_offset..frame_offset + copy_len]
-                    .copy_from_slice(&tile.data[tile_offset..tile_offset + copy_len]);
+        // Tiles can come off the network, so validate them before touching the frame
+        tile.validate(self.width, self.height)?;
+        
+        let row_len = tile.width as usize * bpp;
+        for ty in 0..tile.height as usize {
+            let frame_offset = ((tile.y as usize + ty) * self.width as usize + tile.x as usize) * bpp;
+            let tile_offset = ty * row_len;
+            frame[frame_offset..frame_offset + row_len]
+                .copy_from_slice(&tile.data[tile_offset..tile_offset + row_len]);
+        }
+        
+        Ok(())
+    }
+    
+    /// The grid tile whose top-left corner is at (`x`, `y`), cut from a full frame.
+    pub fn tile_at(&self, frame: &[u8], x: u32, y: u32) -> Result<TileData> {
+        self.check_frame_len(frame.len())?;
+        if x >= self.width || y >= self.height || x % TILE_SIZE as u32 != 0 || y % TILE_SIZE as u32 != 0 {
+            return Err(anyhow!("No tile starts at ({}, {})", x, y));
+        }
+        
+        let w = TILE_SIZE.min((self.width - x) as usize) as u32;
+        let h = TILE_SIZE.min((self.height - y) as usize) as u32;
+        let data = self.extract_tile(frame, x, y, w, h, 3)?;
+        Ok(TileData { x, y, width: w, height: h, data: Bytes::from(data) })
+    }
+    
+    /// Split a full frame into tiles so a keyframe can be sent and rendered progressively.
+    pub fn split_into_tiles(&self, frame: &[u8]) -> Result<Vec<TileData>> {
+        self.check_frame_len(frame.len())?;
+        let mut tiles = Vec::with_capacity((self.tile_width * self.tile_height) as usize);
+        let bytes_per_pixel = 3; // RGB
+        
+        for tile_y in 0..self.tile_height {
+            for tile_x in 0..self.tile_width {
+                let x = tile_x * TILE_SIZE as u32;
+                let y = tile_y * TILE_SIZE as u32;
+                let w = TILE_SIZE.min((self.width - x) as usize) as u32;
+                let h = TILE_SIZE.min((self.height - y) as usize) as u32;
+                
+                let tile_data = self.extract_tile(frame, x, y, w, h, bytes_per_pixel)?;
+                tiles.push(TileData {
+                    x,
+                    y,
+                    width: w,
+                    height: h,
+                    data: Bytes::from(tile_data),
+                });
             }
-            
-            tile_offset += copy_len;
         }
+        
+        Ok(tiles)
     }
     
     pub fn apply_delta(&self, base_frame: &mut [u8], delta: &ProcessedFrame) -> Result<()> {
         if let Some(tiles) = &delta.tiles {
             for tile in tiles {
-                self.copy_tile_to_frame(base_frame, tile);
+                self.copy_tile_to_frame(base_frame, tile)?;
             }
         }
         Ok(())
     }
+}
+
+/// A fast non-cryptographic hash for spotting tiles that haven't changed. Screen content
+/// isn't adversarial, and a collision only leaves one tile stale until it changes again.
+struct TileHash(u64);
+
+impl TileHash {
+    const SEED: u64 = 0x9e37_79b9_7f4a_7c15;
+    const MULTIPLIER: u64 = 0xff51_afd7_ed55_8ccd;
+    
+    fn new() -> Self {
+        Self(Self::SEED)
+    }
+    
+    fn write(&mut self, bytes: &[u8]) {
+        let mut words = bytes.chunks_exact(8);
+        for word in &mut words {
+            self.mix(u64::from_le_bytes(word.try_into().unwrap()));
+        }
+        let mut tail = [0u8; 8];
+        let remainder = words.remainder();
+        tail[..remainder.len()].copy_from_slice(remainder);
+        // The length keeps rows that differ only in trailing zeros apart
+        self.mix(u64::from_le_bytes(tail) ^ ((bytes.len() as u64) << 56));
+    }
+    
+    fn mix(&mut self, word: u64) {
+        self.0 = (self.0 ^ word).wrapping_mul(Self::MULTIPLIER).rotate_left(29);
+    }
+    
+    fn finish(&self) -> u64 {
+        let mut hash = self.0;
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(Self::MULTIPLIER);
+        hash ^ (hash >> 33)
+    }
+}
+
+/// Bytes in a `width`x`height` RGB frame, None if that is more than any message can
+/// carry, so a corrupt or hostile size is never allocated.
+pub fn rgb_frame_len(width: u32, height: u32) -> Option<usize> {
+    (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(3))
+        .filter(|&len| len <= MAX_FRAME_LEN)
+}
+
+/// Fold `newer` tiles into `pending`, replacing tiles at the same position, so several
+/// deltas can be sent as one without losing changes.
+pub fn merge_tiles(pending: &mut Vec<TileData>, newer: &[TileData]) {
+    for tile in newer {
+        match pending.iter_mut().find(|t| t.x == tile.x && t.y == tile.y) {
+            Some(existing) => *existing = tile.clone(),
+            None => pending.push(tile.clone()),
+        }
+    }
+}
+
+/// Decompress zstd-compressed tiles received for a `frame_width`x`frame_height` frame,
+/// dropping any that are out of bounds or don't decompress to exactly their size.
+///
+/// Bounds are checked first and decompression is capped at the tile's size, so a
+/// malicious tile costs no more than a legitimate one.
+pub fn decompress_tiles(tiles: Vec<TileData>, frame_width: u32, frame_height: u32) -> Vec<TileData> {
+    let mut decompressed = Vec::with_capacity(tiles.len());
+    for tile in tiles {
+        if let Err(e) = tile.check_bounds(frame_width, frame_height) {
+            tracing::warn!("Dropping tile: {}", e);
+            continue;
+        }
+        let Some(expected) = tile.expected_len() else {
+            continue;
+        };
+        
+        let tile = match zstd::bulk::decompress(&tile.data, expected) {
+            Ok(data) => TileData { data: Bytes::from(data), ..tile },
+            Err(e) => {
+                tracing::warn!("Dropping tile at ({}, {}): failed to decompress: {}", tile.x, tile.y, e);
+                continue;
+            }
+        };
+        match tile.validate(frame_width, frame_height) {
+            Ok(()) => decompressed.push(tile),
+            Err(e) => tracing::warn!("Dropping tile: {}", e),
+        }
+    }
+    decompressed
 }
\ No newline at end of file
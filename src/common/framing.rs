@@ -0,0 +1,155 @@
+use anyhow::Result;
+use bytes::{Bytes, BytesMut, Buf};
+
+use crate::common::protocol::EncodingType;
+
+/// Length of the big-endian prefix in front of every message on a TCP stream.
+pub const LENGTH_PREFIX: usize = 4;
+
+/// Largest message body accepted; a raw 8K frame fits with room to spare. Anything
+/// bigger is a corrupt or hostile length, and the stream can't be resynchronized after it.
+pub const MAX_FRAME_LEN: usize = 256 * 1024 * 1024;
+
+/// Read buffer capacity for a stream whose frames aren't known yet
+pub const DEFAULT_READ_CAPACITY: usize = 64 * 1024;
+
+/// Read buffer capacity for what clients send a server: input and control messages only
+pub const CONTROL_READ_CAPACITY: usize = 4096;
+
+// Message header, encryption and length prefix around a frame's pixels
+const FRAME_OVERHEAD: usize = 4096;
+
+// Free space below which a read first reclaims the room of messages already decoded
+const MIN_READ_SPACE: usize = 16 * 1024;
+
+/// Room for the largest message of a stream of `width`x`height` frames in `encoding`: a
+/// keyframe. Raw frames take 3 bytes a pixel, about 6 MB at 1080p. Compressed ones get a
+/// quarter of that, which screen content rarely exceeds; a larger one grows the buffer once.
+pub fn frame_buffer_capacity(width: u32, height: u32, encoding: EncodingType) -> usize {
+    let raw = width as usize * height as usize * 3;
+    let frame = match encoding {
+        EncodingType::Raw => raw,
+        EncodingType::ZstdCompressed | EncodingType::H264 | EncodingType::WebP => raw / 4,
+    };
+    (frame + FRAME_OVERHEAD).clamp(DEFAULT_READ_CAPACITY, MAX_FRAME_LEN + FRAME_OVERHEAD)
+}
+
+/// The body length announced by a length prefix, checked against `max`.
+pub fn frame_len(prefix: [u8; LENGTH_PREFIX], max: usize) -> Result<usize> {
+    let len = usize::try_from(u32::from_be_bytes(prefix))
+        .map_err(|_| anyhow::anyhow!("Message length does not fit in memory"))?;
+    if len > max {
+        return Err(anyhow::anyhow!("Message of {} bytes exceeds the {} byte limit", len, max));
+    }
+    Ok(len)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    // Waiting for the four length bytes
+    Length,
+    // Length consumed, waiting for this many body bytes
+    Body(usize),
+}
+
+/// Splits a stream of length-prefixed messages back into messages, however the bytes
+/// were fragmented on the way. Once a length is read, room for the whole body is
+/// reserved so the next reads fill it in large pieces.
+#[derive(Debug)]
+pub struct FrameDecoder {
+    state: State,
+    max_frame_len: usize,
+}
+
+impl Default for FrameDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Self::with_max_frame_len(MAX_FRAME_LEN)
+    }
+    
+    pub fn with_max_frame_len(max_frame_len: usize) -> Self {
+        Self { state: State::Length, max_frame_len }
+    }
+    
+    /// The next complete message body at the front of `buffer`, removed from it, or `None`
+    /// until more bytes arrive. An over-long length is an error; the stream is unusable after it.
+    pub fn decode(&mut self, buffer: &mut BytesMut) -> Result<Option<Bytes>> {
+        if self.state == State::Length {
+            if buffer.len() < LENGTH_PREFIX {
+                return Ok(None);
+            }
+            let prefix = [buffer[0], buffer[1], buffer[2], buffer[3]];
+            let len = frame_len(prefix, self.max_frame_len)?;
+            buffer.advance(LENGTH_PREFIX);
+            buffer.reserve(len.saturating_sub(buffer.len()));
+            self.state = State::Body(len);
+        }
+        
+        let State::Body(len) = self.state else {
+            return Ok(None);
+        };
+        if buffer.len() < len {
+            return Ok(None);
+        }
+        self.state = State::Length;
+        Ok(Some(buffer.split_to(len).freeze()))
+    }
+}
+
+/// A connection's read buffer, kept for its whole life. It is sized for the stream's
+/// keyframes, and before each read the space of messages already taken out is reused, so
+/// a steady stream doesn't allocate and a large keyframe doesn't regrow the buffer.
+#[derive(Debug)]
+pub struct ReadBuffer {
+    buffer: BytesMut,
+    capacity: usize,
+}
+
+impl ReadBuffer {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self { buffer: BytesMut::with_capacity(capacity), capacity }
+    }
+    
+    /// A buffer with room for keyframes of `width`x`height` in `encoding`.
+    pub fn for_frames(width: u32, height: u32, encoding: EncodingType) -> Self {
+        Self::with_capacity(frame_buffer_capacity(width, height, encoding))
+    }
+    
+    /// Grow to hold keyframes of `width`x`height` in `encoding`, e.g. once the first frame
+    /// shows the stream's size. Never shrinks.
+    pub fn fit_frames(&mut self, width: u32, height: u32, encoding: EncodingType) {
+        let capacity = frame_buffer_capacity(width, height, encoding);
+        if capacity > self.capacity {
+            self.capacity = capacity;
+            self.buffer.reserve(capacity.saturating_sub(self.buffer.len()));
+        }
+    }
+    
+    /// Bytes the buffer is sized for.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    
+    /// The buffer to read into next. Once the messages in it have been decoded, the room
+    /// they took is reclaimed for the next read, rather than a read finding no room and
+    /// growing the buffer a few bytes at a time. A message still arriving already has
+    /// room reserved by `FrameDecoder`.
+    pub fn for_read(&mut self) -> &mut BytesMut {
+        let min_space = MIN_READ_SPACE.min(self.capacity);
+        let spare = self.buffer.capacity() - self.buffer.len();
+        if spare < min_space && self.buffer.len() < min_space {
+            self.buffer.reserve(self.capacity - self.buffer.len());
+        }
+        &mut self.buffer
+    }
+    
+    /// The bytes received and not decoded yet, for `FrameDecoder::decode`.
+    pub fn received(&mut self) -> &mut BytesMut {
+        &mut self.buffer
+    }
+}
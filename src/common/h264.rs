@@ -0,0 +1,109 @@
+use crate::common::protocol::Message;
+
+pub const NAL_IDR: u8 = 5;
+pub const NAL_SPS: u8 = 7;
+pub const NAL_PPS: u8 = 8;
+
+const START_CODE: [u8; 4] = [0, 0, 0, 1];
+
+/// The NAL units of an Annex B stream, without their start codes.
+pub fn nal_units(data: &[u8]) -> Vec<&[u8]> {
+    let mut starts = Vec::new();
+    let mut i = 0;
+    while i + 3 <= data.len() {
+        if data[i] == 0 && data[i + 1] == 0 && data[i + 2] == 1 {
+            starts.push(i + 3);
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+    
+    starts
+        .iter()
+        .enumerate()
+        .map(|(index, &start)| {
+            let end = starts.get(index + 1).map_or(data.len(), |&next| next - 3);
+            // A four-byte start code leaves its leading zero on the end of the previous unit
+            let mut unit = &data[start..end];
+            while let [rest @ .., 0] = unit {
+                unit = rest;
+            }
+            unit
+        })
+        .filter(|unit| !unit.is_empty())
+        .collect()
+}
+
+pub fn nal_type(unit: &[u8]) -> u8 {
+    unit.first().map_or(0, |header| header & 0x1f)
+}
+
+/// Whether `frame` holds an IDR slice, which decodes without any earlier frame.
+pub fn is_idr(frame: &[u8]) -> bool {
+    nal_units(frame).iter().any(|unit| nal_type(unit) == NAL_IDR)
+}
+
+/// Whether `frame` carries its own SPS and PPS, as the encoder's IDR frames usually do.
+pub fn has_parameter_sets(frame: &[u8]) -> bool {
+    let types: Vec<u8> = nal_units(frame).iter().map(|unit| nal_type(unit)).collect();
+    types.contains(&NAL_SPS) && types.contains(&NAL_PPS)
+}
+
+/// The latest SPS and PPS seen in an H.264 stream. A decoder can't start without them,
+/// so a viewer that joins (or recovers) on a frame that doesn't carry them is sent these
+/// first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParameterSets {
+    sps: Option<Vec<u8>>,
+    pps: Option<Vec<u8>>,
+}
+
+impl ParameterSets {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Remember any parameter sets in an encoded frame. Returns whether they changed.
+    pub fn observe(&mut self, frame: &[u8]) -> bool {
+        let mut changed = false;
+        for unit in nal_units(frame) {
+            let slot = match nal_type(unit) {
+                NAL_SPS => &mut self.sps,
+                NAL_PPS => &mut self.pps,
+                _ => continue,
+            };
+            if slot.as_deref() != Some(unit) {
+                *slot = Some(unit.to_vec());
+                changed = true;
+            }
+        }
+        changed
+    }
+    
+    pub fn is_complete(&self) -> bool {
+        self.sps.is_some() && self.pps.is_some()
+    }
+    
+    /// The `CodecConfig` to send ahead of `frame` to a viewer starting on it, if `frame`
+    /// lacks the parameter sets and they are known.
+    pub fn config_for(&self, frame: &[u8]) -> Option<Message> {
+        if has_parameter_sets(frame) {
+            return None;
+        }
+        Some(Message::CodecConfig {
+            sps: self.sps.clone()?,
+            pps: self.pps.clone()?,
+        })
+    }
+}
+
+/// SPS and PPS as an Annex B stream to feed a decoder.
+pub fn annex_b(sps: &[u8], pps: &[u8]) -> Vec<u8> {
+    let mut stream = Vec::with_capacity(sps.len() + pps.len() + 2 * START_CODE.len());
+    stream.extend_from_slice(&START_CODE);
+    stream.extend_from_slice(sps);
+    stream.extend_from_slice(&START_CODE);
+    stream.extend_from_slice(pps);
+    stream
+}
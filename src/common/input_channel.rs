@@ -0,0 +1,215 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use anyhow::Result;
+use bytes::Bytes;
+use tokio::sync::Mutex;
+
+use crate::common::crypto::{open_message, Encryptor};
+use crate::common::protocol::{Message, TouchPhase};
+use crate::common::transport::{QuicConnection, Transport};
+
+/// How long a sequenced input event may go unacknowledged before it is sent again
+pub const RETRANSMIT_AFTER: Duration = Duration::from_millis(50);
+
+/// Out-of-order events held while waiting for an earlier one; more than this and the
+/// newest are dropped (the sender retransmits them anyway)
+pub const REORDER_WINDOW: usize = 256;
+
+/// Input the low-latency channel carries instead of the control stream.
+pub fn is_input(message: &Message) -> bool {
+    matches!(
+        message,
+        Message::MouseMove { .. }
+            | Message::MouseScroll { .. }
+            | Message::MouseClick { .. }
+            | Message::KeyEvent { .. }
+            | Message::PhysicalKeyEvent { .. }
+            | Message::Touch { .. }
+            | Message::Gesture { .. }
+    )
+}
+
+/// Events that change what is held down on the remote machine. Losing one would leave
+/// a key, button or finger stuck (or never pressed), so they are acked and delivered in order.
+pub fn is_sequenced(message: &Message) -> bool {
+    matches!(
+        message,
+        Message::MouseClick { .. }
+            | Message::KeyEvent { .. }
+            | Message::PhysicalKeyEvent { .. }
+            | Message::Touch { phase: TouchPhase::Start | TouchPhase::End | TouchPhase::Cancel, .. }
+    )
+}
+
+struct Unacked {
+    datagram: Message,
+    sent_at: Instant,
+}
+
+/// Sending half of the input channel: numbers clicks and key events, keeps them until
+/// acknowledged and says when to send them again. Moves go out unnumbered.
+#[derive(Default)]
+pub struct InputSender {
+    next_seq: u64,
+    unacked: BTreeMap<u64, Unacked>,
+}
+
+impl InputSender {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Wrap `event` for sending now.
+    pub fn wrap(&mut self, event: Message, now: Instant) -> Message {
+        if !is_sequenced(&event) {
+            return Message::InputDatagram { seq: None, event: Box::new(event) };
+        }
+        
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let datagram = Message::InputDatagram { seq: Some(seq), event: Box::new(event) };
+        self.unacked.insert(seq, Unacked { datagram: datagram.clone(), sent_at: now });
+        datagram
+    }
+    
+    /// The receiver has everything below `next`.
+    pub fn acked(&mut self, next: u64) {
+        self.unacked = self.unacked.split_off(&next);
+    }
+    
+    /// Events not acknowledged within `RETRANSMIT_AFTER`, oldest first.
+    pub fn retransmits(&mut self, now: Instant) -> Vec<Message> {
+        self.unacked
+            .values_mut()
+            .filter(|unacked| now.duration_since(unacked.sent_at) >= RETRANSMIT_AFTER)
+            .map(|unacked| {
+                unacked.sent_at = now;
+                unacked.datagram.clone()
+            })
+            .collect()
+    }
+    
+    pub fn unacked(&self) -> usize {
+        self.unacked.len()
+    }
+}
+
+/// Receiving half: passes moves straight through, puts sequenced events back in order,
+/// drops duplicates, and produces the acknowledgement to send back.
+#[derive(Default)]
+pub struct InputReceiver {
+    next_seq: u64,
+    early: BTreeMap<u64, Message>,
+}
+
+impl InputReceiver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Events to apply now, and the ack to return for sequenced datagrams.
+    pub fn receive(&mut self, seq: Option<u64>, event: Message) -> (Vec<Message>, Option<Message>) {
+        let Some(seq) = seq else {
+            return (vec![event], None);
+        };
+        
+        if seq >= self.next_seq && self.early.len() < REORDER_WINDOW {
+            self.early.entry(seq).or_insert(event);
+        }
+        
+        let mut ready = Vec::new();
+        while let Some(event) = self.early.remove(&self.next_seq) {
+            ready.push(event);
+            self.next_seq += 1;
+        }
+        
+        // Duplicates are acked again, the first ack may have been the one lost
+        (ready, Some(Message::InputAck { next: self.next_seq }))
+    }
+}
+
+/// Client side of input over QUIC: sends each event as an encrypted datagram, so a click
+/// never waits behind a keyframe on a stream. `handle_ack` takes the `InputAck`s read from
+/// the connection's datagrams, and `retransmit` should run about every `RETRANSMIT_AFTER`.
+///
+/// Datagrams are sealed with `Encryptor::encrypt` directly: a `Rekey` announcement could
+/// itself be lost, so datagram keys are never rotated.
+pub struct QuicInputSender {
+    connection: QuicConnection,
+    crypto: Arc<Mutex<Box<dyn Encryptor>>>,
+    sender: std::sync::Mutex<InputSender>,
+}
+
+impl QuicInputSender {
+    pub fn new(connection: QuicConnection, crypto: Arc<Mutex<Box<dyn Encryptor>>>) -> Self {
+        Self {
+            connection,
+            crypto,
+            sender: std::sync::Mutex::new(InputSender::new()),
+        }
+    }
+    
+    pub async fn send(&self, event: Message) -> Result<()> {
+        let datagram = self.sender.lock().unwrap().wrap(event, Instant::now());
+        self.send_datagram(&datagram).await
+    }
+    
+    pub fn handle_ack(&self, next: u64) {
+        self.sender.lock().unwrap().acked(next);
+    }
+    
+    pub async fn retransmit(&self) -> Result<()> {
+        let due = self.sender.lock().unwrap().retransmits(Instant::now());
+        for datagram in due {
+            self.send_datagram(&datagram).await?;
+        }
+        Ok(())
+    }
+    
+    /// Sequenced events still waiting for an ack.
+    pub fn unacked(&self) -> usize {
+        self.sender.lock().unwrap().unacked()
+    }
+    
+    async fn send_datagram(&self, message: &Message) -> Result<()> {
+        let encrypted = self.crypto.lock().await.encrypt(&message.serialize()?)?;
+        self.connection.send_datagram(Bytes::from(encrypted)).await
+    }
+}
+
+/// Server side of input over QUIC: decrypts a datagram, acks sequenced events and gives
+/// back the events to apply, in order. Anything that isn't an `InputDatagram` is returned
+/// untouched for the caller's other datagram handling.
+pub struct QuicInputReceiver<T: Transport> {
+    connection: Arc<T>,
+    crypto: Arc<Mutex<Box<dyn Encryptor>>>,
+    receiver: std::sync::Mutex<InputReceiver>,
+}
+
+impl<T: Transport> QuicInputReceiver<T> {
+    pub fn new(connection: Arc<T>, crypto: Arc<Mutex<Box<dyn Encryptor>>>) -> Self {
+        Self {
+            connection,
+            crypto,
+            receiver: std::sync::Mutex::new(InputReceiver::new()),
+        }
+    }
+    
+    pub async fn handle_datagram(&self, data: &[u8]) -> Result<Vec<Message>> {
+        // Refuses anything sent in the clear, even where video may be
+        let Some(message) = open_message(&**self.crypto.lock().await, data)? else {
+            return Ok(Vec::new());
+        };
+        let Message::InputDatagram { seq, event } = message else {
+            return Ok(vec![message]);
+        };
+        
+        let (events, ack) = self.receiver.lock().unwrap().receive(seq, *event);
+        if let Some(ack) = ack {
+            let encrypted = self.crypto.lock().await.encrypt(&ack.serialize()?)?;
+            self.connection.send_datagram(&encrypted).await?;
+        }
+        Ok(events)
+    }
+}
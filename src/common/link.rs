@@ -0,0 +1,122 @@
+use anyhow::{anyhow, bail, Result};
+use std::fmt;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::str::FromStr;
+use crate::common::auth::CODE_LENGTH;
+
+/// URI scheme of connection links, as in `vox://192.168.1.20:8080?code=123456`.
+pub const SCHEME: &str = "vox";
+
+/// Everything a client needs to connect: the server address and an access code. Servers
+/// show it as a `vox://` link (and a QR code of one) so nobody has to type either by hand.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionLink {
+    pub address: String,
+    pub code: String,
+}
+
+impl ConnectionLink {
+    pub fn new(address: impl Into<String>, code: impl Into<String>) -> Self {
+        Self { address: address.into(), code: code.into() }
+    }
+    
+    /// Parse a `vox://host:port?code=NNNNNN` link. The host may be a name, an IPv4
+    /// address or a bracketed IPv6 address; query parameters other than `code` are ignored.
+    pub fn parse(text: &str) -> Result<Self> {
+        let text = text.trim();
+        let rest = strip_scheme(text).ok_or_else(|| anyhow!("not a {}:// link", SCHEME))?;
+        let (authority, query) = rest.split_once('?').unwrap_or((rest, ""));
+        let address = authority.strip_suffix('/').unwrap_or(authority);
+        check_address(address)?;
+        
+        let code = query.split('&')
+            .find_map(|pair| pair.strip_prefix("code="))
+            .ok_or_else(|| anyhow!("link has no access code"))?;
+        if code.len() != CODE_LENGTH || !code.bytes().all(|b| b.is_ascii_digit()) {
+            bail!("access code must be {} digits, got '{}'", CODE_LENGTH, code);
+        }
+        
+        Ok(Self::new(address, code))
+    }
+    
+    /// Whether `text` is meant as a link rather than a plain address, so input fields can
+    /// tell a pasted link apart from someone typing a host name.
+    pub fn looks_like_link(text: &str) -> bool {
+        strip_scheme(text.trim()).is_some()
+    }
+    
+    pub fn to_uri(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for ConnectionLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://{}?code={}", SCHEME, self.address, self.code)
+    }
+}
+
+impl FromStr for ConnectionLink {
+    type Err = anyhow::Error;
+    
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+fn strip_scheme(text: &str) -> Option<&str> {
+    let (scheme, rest) = text.split_once("://")?;
+    scheme.eq_ignore_ascii_case(SCHEME).then_some(rest)
+}
+
+fn check_address(address: &str) -> Result<()> {
+    let (host, port) = if let Some(bracketed) = address.strip_prefix('[') {
+        let (host, port) = bracketed.split_once("]:")
+            .ok_or_else(|| anyhow!("IPv6 address '{}' needs a port after the brackets", address))?;
+        host.parse::<std::net::Ipv6Addr>().map_err(|_| anyhow!("'{}' is not an IPv6 address", host))?;
+        (host, port)
+    } else {
+        address.rsplit_once(':').ok_or_else(|| anyhow!("address '{}' has no port", address))?
+    };
+    
+    if host.is_empty() || host.contains(|c: char| c.is_whitespace() || matches!(c, '/' | '@' | '?' | '#')) {
+        bail!("'{}' is not a valid host", host);
+    }
+    if !address.starts_with('[') && host.contains(':') {
+        bail!("IPv6 address '{}' must be in brackets", host);
+    }
+    match port.parse::<u16>() {
+        Ok(port) if port != 0 => Ok(()),
+        _ => bail!("'{}' is not a valid port", port),
+    }
+}
+
+/// The address to put in links for a server bound to `bind`. Clients can't connect to
+/// an unspecified address like `0.0.0.0`, so that is swapped for the machine's LAN
+/// address, falling back to loopback when there is none.
+pub fn shareable_address(bind: &str) -> String {
+    let Ok(addr) = bind.parse::<SocketAddr>() else {
+        return bind.to_string();
+    };
+    if !addr.ip().is_unspecified() {
+        return addr.to_string();
+    }
+    let ip = lan_ip(addr.ip()).unwrap_or(match addr.ip() {
+        IpAddr::V4(_) => IpAddr::from([127, 0, 0, 1]),
+        IpAddr::V6(_) => IpAddr::from(std::net::Ipv6Addr::LOCALHOST),
+    });
+    SocketAddr::new(ip, addr.port()).to_string()
+}
+
+/// The local address the OS would route outside traffic from. Connecting a UDP socket
+/// only picks a route; nothing is sent.
+fn lan_ip(family: IpAddr) -> Option<IpAddr> {
+    let (local, remote) = match family {
+        IpAddr::V4(_) => ("0.0.0.0:0", "192.0.2.1:9"),
+        IpAddr::V6(_) => ("[::]:0", "[2001:db8::1]:9"),
+    };
+    let socket = UdpSocket::bind(local).ok()?;
+    socket.connect(remote).ok()?;
+    let ip = socket.local_addr().ok()?.ip();
+    (!ip.is_unspecified()).then_some(ip)
+}
@@ -1,7 +1,10 @@
+use std::collections::HashMap;
+use std::fmt::Write;
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use parking_lot::RwLock;
+use crate::common::quality::QualityMode;
 
 #[derive(Debug, Clone)]
 pub struct PerformanceMetrics {
@@ -20,6 +23,10 @@ pub struct PerformanceMetrics {
     bytes_sent: Arc<AtomicUsize>,
     bytes_received: Arc<AtomicUsize>,
     
+    // Session metrics, keyed by session id
+    sessions: Arc<RwLock<HashMap<String, SessionStats>>>,
+    sessions_started: Arc<AtomicU64>,
+    
     // Start time
     start_time: Instant,
 }
@@ -36,6 +43,8 @@ impl PerformanceMetrics {
             network_time: Arc::new(RwLock::new(MovingAverage::new(100))),
             bytes_sent: Arc::new(AtomicUsize::new(0)),
             bytes_received: Arc::new(AtomicUsize::new(0)),
+            sessions: Arc::new(RwLock::new(HashMap::new())),
+            sessions_started: Arc::new(AtomicU64::new(0)),
             start_time: Instant::now(),
         }
     }
@@ -76,6 +85,80 @@ impl PerformanceMetrics {
         self.bytes_received.fetch_add(bytes, Ordering::Relaxed);
     }
     
+    pub fn session_started(&self, id: &str) {
+        self.sessions.write().insert(id.to_string(), SessionStats::default());
+        self.sessions_started.fetch_add(1, Ordering::Relaxed);
+    }
+    
+    pub fn session_ended(&self, id: &str) {
+        self.sessions.write().remove(id);
+    }
+    
+    /// A frame of `bytes` went to session `id` at `quality`.
+    pub fn session_frame_sent(&self, id: &str, quality: QualityMode, bytes: usize) {
+        if let Some(stats) = self.sessions.write().get_mut(id) {
+            stats.quality = Some(quality);
+            stats.frames_sent += 1;
+            stats.bytes_sent += bytes as u64;
+        }
+    }
+    
+    pub fn active_sessions(&self) -> usize {
+        self.sessions.read().len()
+    }
+    
+    /// Connected sessions and their stats, ordered by id.
+    pub fn session_stats(&self) -> Vec<(String, SessionStats)> {
+        let mut sessions: Vec<_> = self.sessions.read()
+            .iter()
+            .map(|(id, stats)| (id.clone(), stats.clone()))
+            .collect();
+        sessions.sort_by(|a, b| a.0.cmp(&b.0));
+        sessions
+    }
+    
+    /// Everything above in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            let _ = writeln!(out, "{} {}", name, value);
+        };
+        
+        let counter = |value: &AtomicU64| value.load(Ordering::Relaxed).to_string();
+        metric("vox_uptime_seconds", "gauge", "Seconds since the server started", format!("{:.3}", self.start_time.elapsed().as_secs_f64()));
+        metric("vox_frames_captured_total", "counter", "Frames captured from the screen", counter(&self.frames_captured));
+        metric("vox_frames_encoded_total", "counter", "Frames encoded for sending", counter(&self.frames_encoded));
+        metric("vox_frames_sent_total", "counter", "Frames sent to clients, counting each client", counter(&self.frames_sent));
+        metric("vox_frames_dropped_total", "counter", "Frames skipped for a client by frame pacing", counter(&self.frames_dropped));
+        metric("vox_bytes_sent_total", "counter", "Bytes of frame data sent to clients", self.bytes_sent.load(Ordering::Relaxed).to_string());
+        metric("vox_bytes_received_total", "counter", "Bytes received from clients", self.bytes_received.load(Ordering::Relaxed).to_string());
+        metric("vox_sessions_started_total", "counter", "Sessions that authenticated", counter(&self.sessions_started));
+        metric("vox_sessions_active", "gauge", "Sessions currently connected", self.active_sessions().to_string());
+        
+        let sessions = self.session_stats();
+        let _ = writeln!(out, "# HELP vox_session_quality Current quality of each session (0 = minimal .. 4 = ultra)");
+        let _ = writeln!(out, "# TYPE vox_session_quality gauge");
+        for (id, stats) in &sessions {
+            if let Some(quality) = stats.quality {
+                let _ = writeln!(out, "vox_session_quality{{session=\"{}\",mode=\"{:?}\"}} {}", id, quality, quality_level(quality));
+            }
+        }
+        let _ = writeln!(out, "# HELP vox_session_frames_sent_total Frames sent to each session");
+        let _ = writeln!(out, "# TYPE vox_session_frames_sent_total counter");
+        for (id, stats) in &sessions {
+            let _ = writeln!(out, "vox_session_frames_sent_total{{session=\"{}\"}} {}", id, stats.frames_sent);
+        }
+        let _ = writeln!(out, "# HELP vox_session_bytes_sent_total Bytes of frame data sent to each session");
+        let _ = writeln!(out, "# TYPE vox_session_bytes_sent_total counter");
+        for (id, stats) in &sessions {
+            let _ = writeln!(out, "vox_session_bytes_sent_total{{session=\"{}\"}} {}", id, stats.bytes_sent);
+        }
+        
+        out
+    }
+    
     pub fn get_stats(&self) -> PerformanceStats {
         let elapsed = self.start_time.elapsed().as_secs_f64();
         let frames_captured = self.frames_captured.load(Ordering::Relaxed);
@@ -98,6 +181,26 @@ impl PerformanceMetrics {
     }
 }
 
+/// What has been sent to one session.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionStats {
+    /// Quality of the latest frame, `None` until one is sent
+    pub quality: Option<QualityMode>,
+    pub frames_sent: u64,
+    pub bytes_sent: u64,
+}
+
+// Quality as a number for the gauge, higher is better
+fn quality_level(mode: QualityMode) -> u8 {
+    match mode {
+        QualityMode::Minimal => 0,
+        QualityMode::Low => 1,
+        QualityMode::Medium => 2,
+        QualityMode::High => 3,
+        QualityMode::Ultra => 4,
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PerformanceStats {
     pub fps_captured: f64,
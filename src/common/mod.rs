@@ -3,7 +3,25 @@ pub mod protocol;
 pub mod crypto;
 pub mod quality;
 pub mod frame_processor;
-// pub mod transport; // TODO: Fix rustls/quinn version compatibility
+pub mod transport;
 pub mod encoder;
 pub mod metrics;
-pub mod ffmpeg_encoder;
\ No newline at end of file
+pub mod ffmpeg_encoder;
+pub mod color;
+pub mod shortcuts;
+pub mod capabilities;
+pub mod foveation;
+pub mod encoder_watchdog;
+pub mod adaptive_fps;
+pub mod input_channel;
+pub mod h264;
+pub mod batch;
+pub mod display_layout;
+pub mod framing;
+pub mod delivery;
+pub mod viewport;
+pub mod compression;
+pub mod link;
+pub mod qr;
+pub mod socket;
+pub mod checksum;
\ No newline at end of file
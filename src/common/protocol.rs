@@ -1,7 +1,11 @@
 use serde::{Deserialize, Serialize};
 use bytes::Bytes;
-use crate::common::quality::{QualityMode, QualityMetrics};
+use crate::common::crypto::Cipher;
+use crate::common::delivery::DeliveryMode;
+use crate::common::quality::{ContentProfile, QualityMode, QualityMetrics, QualityChangeReason};
 use crate::common::frame_processor::TileData;
+use crate::common::display_layout::MonitorRect;
+use crate::common::foveation::Region;
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub enum EncodingType {
@@ -11,6 +15,15 @@ pub enum EncodingType {
     WebP,            // WebP image
 }
 
+/// What a `Message::Error` is about, for the ones the client handles specially.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorKind {
+    #[default]
+    Other,
+    // The server isn't allowed to inject input, so the session is view-only
+    InputNotPermitted,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum Message {
     // Authentication
@@ -20,39 +33,126 @@ pub enum Message {
     // Key exchange for encryption
     KeyExchange { public_key: Vec<u8> },
     KeyExchangeAck { public_key: Vec<u8> },
+    // Sent before KeyExchange with the client's ciphers, most preferred first; without it
+    // the session uses AES-256-GCM
+    CipherOffer { ciphers: Vec<Cipher> },
+    CipherSelected { cipher: Cipher },
+    // Sent instead of KeyExchange to skip encryption; only loopback servers started with --no-encryption accept
+    DisableEncryption,
+    DisableEncryptionAck { accepted: bool },
+    // Sent under the old key; everything after it in the same direction uses key `epoch`
+    Rekey { epoch: u32 },
+    // Asked for once authenticated; a server started with --plaintext-video sends video in
+    // the clear from its acknowledgement on (see `PlaintextVideo` in crypto)
+    PlaintextVideo,
+    PlaintextVideoAck { accepted: bool },
     
-    // Screen data
+    // Screen data. `sequence` counts up with every frame sent to a session (all chunks of
+    // a keyframe share one), so clients can drop frames that arrive after newer ones.
     ScreenFrame { 
+        sequence: u64,
         timestamp: u64,
         width: u32,
         height: u32,
         data: Vec<u8>,
         encoding: EncodingType,
+        // `frame_checksum` of the RGB frame before encoding, from a server run with
+        // --frame-checksums; only on lossless frames
+        checksum: Option<u32>,
     },
     
     // Optimized frame with delta encoding
     DeltaFrame {
+        sequence: u64,
+        timestamp: u64,
+        tiles: Vec<TileData>,
+        // Of the whole frame the tiles bring the client to, as for ScreenFrame
+        checksum: Option<u32>,
+    },
+    
+    // Keyframe sent as a sequence of tile chunks for progressive rendering
+    KeyFrameChunk {
+        sequence: u64,
         timestamp: u64,
+        width: u32,
+        height: u32,
         tiles: Vec<TileData>,
+        is_last: bool,
     },
     
     // Quality control
-    QualityChange { mode: QualityMode },
+    QualityChange { mode: QualityMode, reason: QualityChangeReason },
     QualityMetricsReport { metrics: QualityMetrics },
     RequestQualityChange { mode: QualityMode },
+    // What the client's screen mostly shows; the server answers with the profile its
+    // display runs, which is Balanced while viewers of one display disagree
+    SetContentProfile { profile: ContentProfile },
+    ContentProfileApplied { profile: ContentProfile },
+    // Whether late frames are sent anyway or dropped; confirmed with the mode now in effect
+    SetDeliveryMode { mode: DeliveryMode },
+    DeliveryModeApplied { mode: DeliveryMode },
     
     // Input events
     MouseMove { x: i32, y: i32 },
     MouseClick { button: MouseButton, pressed: bool, x: i32, y: i32 },
     MouseScroll { delta_x: f64, delta_y: f64 },
-    KeyEvent { key: String, pressed: bool, modifiers: Modifiers },
+    // `repeat` marks the client's auto-repeat of a key it holds down
+    KeyEvent { key: String, pressed: bool, repeat: bool, modifiers: Modifiers },
+    // A key by position (USB HID usage), for the remote's own layout to interpret
+    PhysicalKeyEvent { usage: u16, pressed: bool, repeat: bool, modifiers: Modifiers },
+    // One finger or pen on a touchscreen, placed like mouse events. `pressure` is 0.0 to 1.0
+    // where the device reports it. Hosts that can't inject touch play it as the left button
+    Touch { id: u64, phase: TouchPhase, x: i32, y: i32, pressure: Option<f32> },
+    // A two-finger gesture centered on (x, y), played as scrolling or zooming
+    Gesture { gesture: TouchGesture, x: i32, y: i32 },
+    // An input event sent as a QUIC datagram. Clicks and keys carry `seq` and are acked
+    // and delivered in order; moves and scrolls carry none and may be lost.
+    InputDatagram { seq: Option<u64>, event: Box<Message> },
+    // Every sequenced input event below `next` has arrived
+    InputAck { next: u64 },
+    
+    // Encoding negotiation: the client lists what it can decode, the server answers with its pick
+    Capabilities { encodings: Vec<EncodingType> },
+    EncodingSelected { encoding: EncodingType },
+    // H.264 parameter sets (NAL units without start codes), sent ahead of the first frame
+    // of a viewer whose stream starts on a frame that doesn't carry them
+    CodecConfig { sps: Vec<u8>, pps: Vec<u8> },
     
     // Control messages
     StartStream,
     StopStream,
+    SetProgressiveRendering { enabled: bool },
+    // The user asked for a complete frame to clear stale or garbled regions
+    RequestKeyframe,
+    // The client can draw the remote cursor itself from CursorUpdate messages
+    SetCursorOverlay { enabled: bool },
+    // Where to draw the remote cursor; not visible when frames already show it
+    CursorUpdate { x: i32, y: i32, visible: bool },
+    // Captured pixels per logical point on the streamed display, 2.0 on Retina screens
+    DisplayScale { factor: f32 },
+    // Physical pixels the client shows the streamed display in; the server scales frames
+    // down to fit. 0x0 lifts the limit, e.g. while the client views at actual size
+    ViewportResize { width: u32, height: u32 },
+    // Part of the frame, in frame pixels, to keep exact while the rest is foveated; `None`
+    // clears it
+    LosslessRegion { rect: Option<Region> },
+    // Where each of the server's displays sits on its desktop, indexed like SelectDisplay
+    DisplayLayout { monitors: Vec<MonitorRect> },
+    // Which of the server's displays this session streams, 0 being the first
+    SelectDisplay { index: u32 },
+    // Query or change the host's display itself, for sessions whose code allows it; answered
+    // with DisplayStatus, or an Error when refused or unsupported
+    DisplayControl { action: DisplayAction },
+    DisplayStatus { settings: DisplaySettings },
     Ping { timestamp: u64 },
     Pong { timestamp: u64 },
     Disconnect,
+    // Server-side problem the user should know about (e.g. screen capture failed)
+    Error { message: String, kind: ErrorKind },
+    // A notification shown on the server's desktop, forwarded only when the host opted in
+    Notification { title: String, body: String, app: String },
+    // Several messages sent as one frame; the receiver handles them in order
+    Batch { messages: Vec<Message> },
     
     // Performance metrics
     FrameAck { timestamp: u64, received_at: u64 },
@@ -64,6 +164,50 @@ pub enum MouseButton {
     Left,
     Right,
     Middle,
+    // Appended so older peers keep decoding the buttons above the same way
+    Back,
+    Forward,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum TouchPhase {
+    Start,
+    Move,
+    End,
+    // The contact went away without lifting, e.g. because a second finger turned it into a gesture
+    Cancel,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum TouchGesture {
+    // The distance between the fingers changed by `scale` since the last one, above 1.0 when spreading
+    Pinch { scale: f32 },
+    // Both fingers moved by this many remote pixels since the last one
+    Scroll { delta_x: f32, delta_y: f32 },
+}
+
+/// A change to the host's display asked for with `Message::DisplayControl`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum DisplayAction {
+    // Only report the current settings
+    Query,
+    // 0.0 (darkest) to 1.0 (full brightness)
+    SetBrightness { level: f32 },
+    // One of the modes `DisplaySettings` lists
+    SetResolution { width: u32, height: u32 },
+    Sleep,
+    Wake,
+}
+
+/// The host display's settings, as reported after every `DisplayAction`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub struct DisplaySettings {
+    pub width: u32,
+    pub height: u32,
+    // 0.0 to 1.0, None where the host can't tell
+    pub brightness: Option<f32>,
+    // Resolutions the display can be switched to, largest first
+    pub modes: Vec<(u32, u32)>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
@@ -90,4 +234,9 @@ impl Message {
     pub fn deserialize(data: &[u8]) -> Result<Self, bincode::Error> {
         bincode::deserialize(data)
     }
+    
+    /// Screen data, the only messages that may travel unencrypted under plaintext video.
+    pub fn is_video(&self) -> bool {
+        matches!(self, Message::ScreenFrame { .. } | Message::DeltaFrame { .. } | Message::KeyFrameChunk { .. })
+    }
 }
\ No newline at end of file
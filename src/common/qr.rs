@@ -0,0 +1,337 @@
+use anyhow::{bail, Result};
+
+/// Data codewords per version at error correction level L. Versions 1-5 use a single
+/// Reed-Solomon block, which keeps the encoder small; that is 106 bytes, plenty for a link.
+const DATA_CODEWORDS: [usize; 5] = [19, 34, 55, 80, 108];
+const EC_CODEWORDS: [usize; 5] = [7, 10, 15, 20, 26];
+
+/// Largest payload `QrCode::encode` accepts, in bytes.
+pub const MAX_BYTES: usize = 106;
+
+/// Error correction level L, as encoded in the format bits.
+const EC_LEVEL_L: u32 = 0b01;
+
+/// A QR code as a square grid of modules, `true` for dark. Byte mode, level L only: just
+/// enough to put a connection link on screen for a phone or another machine to scan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QrCode {
+    version: usize,
+    size: usize,
+    modules: Vec<bool>,
+    function: Vec<bool>,
+}
+
+impl QrCode {
+    /// Encode `data` in the smallest version it fits, with the least penalised mask.
+    pub fn encode(data: &[u8]) -> Result<Self> {
+        let Some(version) = (1..=DATA_CODEWORDS.len()).find(|&v| data.len() + 2 <= DATA_CODEWORDS[v - 1]) else {
+            bail!("{} bytes is too long for a QR code, at most {} fit", data.len(), MAX_BYTES);
+        };
+        
+        let mut qr = Self::blank(version);
+        qr.draw_function_patterns();
+        let codewords = add_error_correction(&pack_data(data, DATA_CODEWORDS[version - 1]), EC_CODEWORDS[version - 1]);
+        qr.draw_codewords(&codewords);
+        
+        let mask = (0..8)
+            .min_by_key(|&mask| {
+                let mut candidate = qr.clone();
+                candidate.apply_mask(mask);
+                candidate.draw_format_bits(mask);
+                candidate.penalty()
+            })
+            .unwrap_or(0);
+        qr.apply_mask(mask);
+        qr.draw_format_bits(mask);
+        Ok(qr)
+    }
+    
+    pub fn version(&self) -> usize {
+        self.version
+    }
+    
+    /// Modules per side, not counting the quiet zone.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+    
+    /// Whether the module at column `x`, row `y` is dark.
+    pub fn module(&self, x: usize, y: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+    
+    fn blank(version: usize) -> Self {
+        let size = 17 + 4 * version;
+        Self { version, size, modules: vec![false; size * size], function: vec![false; size * size] }
+    }
+    
+    fn set_function(&mut self, x: usize, y: usize, dark: bool) {
+        self.modules[y * self.size + x] = dark;
+        self.function[y * self.size + x] = true;
+    }
+    
+    fn draw_function_patterns(&mut self) {
+        let size = self.size;
+        for i in 0..size {
+            self.set_function(6, i, i % 2 == 0);
+            self.set_function(i, 6, i % 2 == 0);
+        }
+        
+        self.draw_finder(3, 3);
+        self.draw_finder(size - 4, 3);
+        self.draw_finder(3, size - 4);
+        if self.version > 1 {
+            self.draw_alignment(size - 7, size - 7);
+        }
+        
+        // Reserve the format areas now so data skips them; the bits come after masking
+        self.draw_format_bits(0);
+    }
+    
+    /// A finder pattern centred on (`cx`, `cy`), including its light separator.
+    fn draw_finder(&mut self, cx: usize, cy: usize) {
+        for dy in -4i32..=4 {
+            for dx in -4i32..=4 {
+                let (x, y) = (cx as i32 + dx, cy as i32 + dy);
+                if x < 0 || y < 0 || x >= self.size as i32 || y >= self.size as i32 {
+                    continue;
+                }
+                let ring = dx.abs().max(dy.abs());
+                self.set_function(x as usize, y as usize, ring != 2 && ring != 4);
+            }
+        }
+    }
+    
+    fn draw_alignment(&mut self, cx: usize, cy: usize) {
+        for dy in -2i32..=2 {
+            for dx in -2i32..=2 {
+                let ring = dx.abs().max(dy.abs());
+                self.set_function((cx as i32 + dx) as usize, (cy as i32 + dy) as usize, ring != 1);
+            }
+        }
+    }
+    
+    fn draw_format_bits(&mut self, mask: u32) {
+        let bits = format_bits(mask);
+        let bit = |i: u32| (bits >> i) & 1 != 0;
+        let size = self.size;
+        
+        for i in 0..=5 {
+            self.set_function(8, i, bit(i as u32));
+        }
+        self.set_function(8, 7, bit(6));
+        self.set_function(8, 8, bit(7));
+        self.set_function(7, 8, bit(8));
+        for i in 9..15 {
+            self.set_function(14 - i, 8, bit(i as u32));
+        }
+        
+        for i in 0..8 {
+            self.set_function(size - 1 - i, 8, bit(i as u32));
+        }
+        for i in 8..15 {
+            self.set_function(8, size - 15 + i, bit(i as u32));
+        }
+        self.set_function(8, size - 8, true);
+    }
+    
+    /// Lay codewords out in the standard zigzag, two columns at a time from the right,
+    /// skipping the vertical timing pattern. Leftover remainder modules stay light.
+    fn draw_codewords(&mut self, codewords: &[u8]) {
+        let size = self.size;
+        let total_bits = codewords.len() * 8;
+        let mut bit = 0;
+        let mut right = size as i32 - 1;
+        while right >= 1 {
+            if right == 6 {
+                right = 5;
+            }
+            let upward = (right + 1) & 2 == 0;
+            for vert in 0..size {
+                let y = if upward { size - 1 - vert } else { vert };
+                for dx in 0..2 {
+                    let x = right as usize - dx;
+                    if self.function[y * size + x] || bit >= total_bits {
+                        continue;
+                    }
+                    self.modules[y * size + x] = (codewords[bit / 8] >> (7 - bit % 8)) & 1 != 0;
+                    bit += 1;
+                }
+            }
+            right -= 2;
+        }
+    }
+    
+    /// XOR data modules with `mask`; applying it twice undoes it.
+    fn apply_mask(&mut self, mask: u32) {
+        for y in 0..self.size {
+            for x in 0..self.size {
+                let index = y * self.size + x;
+                if !self.function[index] && mask_bit(mask, x, y) {
+                    self.modules[index] = !self.modules[index];
+                }
+            }
+        }
+    }
+    
+    /// The specification's mask penalty: long runs, 2x2 blocks, finder-like patterns and
+    /// dark/light imbalance all make a code harder to scan.
+    fn penalty(&self) -> usize {
+        let size = self.size;
+        let mut penalty = 0;
+        
+        for transpose in [false, true] {
+            for a in 0..size {
+                let line: Vec<bool> = (0..size)
+                    .map(|b| if transpose { self.module(a, b) } else { self.module(b, a) })
+                    .collect();
+                
+                let mut run = 1;
+                for i in 1..=size {
+                    if i < size && line[i] == line[i - 1] {
+                        run += 1;
+                        continue;
+                    }
+                    if run >= 5 {
+                        penalty += run - 2;
+                    }
+                    run = 1;
+                }
+                
+                const FINDER_LIKE: [bool; 11] = [true, false, true, true, true, false, true, false, false, false, false];
+                for window in line.windows(11) {
+                    if window == FINDER_LIKE || window.iter().rev().eq(FINDER_LIKE.iter()) {
+                        penalty += 40;
+                    }
+                }
+            }
+        }
+        
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let dark = self.module(x, y);
+                if dark == self.module(x + 1, y) && dark == self.module(x, y + 1) && dark == self.module(x + 1, y + 1) {
+                    penalty += 3;
+                }
+            }
+        }
+        
+        let dark = self.modules.iter().filter(|&&m| m).count();
+        let total = self.modules.len();
+        let deviation = (dark * 20).abs_diff(total * 10);
+        penalty + deviation.div_ceil(total).saturating_sub(1) * 10
+    }
+}
+
+fn mask_bit(mask: u32, x: usize, y: usize) -> bool {
+    match mask {
+        0 => (x + y) % 2 == 0,
+        1 => y % 2 == 0,
+        2 => x % 3 == 0,
+        3 => (x + y) % 3 == 0,
+        4 => (x / 3 + y / 2) % 2 == 0,
+        5 => x * y % 2 + x * y % 3 == 0,
+        6 => (x * y % 2 + x * y % 3) % 2 == 0,
+        _ => ((x + y) % 2 + x * y % 3) % 2 == 0,
+    }
+}
+
+/// The 15 format bits for level L and `mask`: five data bits, a BCH(15,5) remainder,
+/// XORed with the fixed pattern so they are never all light.
+pub fn format_bits(mask: u32) -> u32 {
+    let data = (EC_LEVEL_L << 3) | mask;
+    let mut rem = data;
+    for _ in 0..10 {
+        rem = (rem << 1) ^ ((rem >> 9) * 0x537);
+    }
+    ((data << 10) | rem) ^ 0x5412
+}
+
+/// Byte-mode segment, terminator and padding, filling `capacity` codewords.
+fn pack_data(data: &[u8], capacity: usize) -> Vec<u8> {
+    let mut bits = BitWriter::default();
+    bits.push(0b0100, 4);
+    bits.push(data.len() as u32, 8);
+    for &byte in data {
+        bits.push(byte as u32, 8);
+    }
+    let terminator = (capacity * 8 - bits.len).min(4);
+    bits.push(0, terminator);
+    let mut bytes = bits.finish();
+    for pad in [0xEC, 0x11].into_iter().cycle() {
+        if bytes.len() >= capacity {
+            break;
+        }
+        bytes.push(pad);
+    }
+    bytes
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    len: usize,
+}
+
+impl BitWriter {
+    fn push(&mut self, value: u32, count: usize) {
+        for i in (0..count).rev() {
+            if self.len % 8 == 0 {
+                self.bytes.push(0);
+            }
+            if (value >> i) & 1 != 0 {
+                *self.bytes.last_mut().unwrap() |= 0x80 >> (self.len % 8);
+            }
+            self.len += 1;
+        }
+    }
+    
+    fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// `data` followed by `ec_len` Reed-Solomon codewords over GF(256).
+pub fn add_error_correction(data: &[u8], ec_len: usize) -> Vec<u8> {
+    let generator = rs_generator(ec_len);
+    let mut remainder = vec![0u8; ec_len];
+    for &byte in data {
+        let factor = byte ^ remainder[0];
+        remainder.remove(0);
+        remainder.push(0);
+        for (r, &g) in remainder.iter_mut().zip(&generator) {
+            *r ^= gf_mul(g, factor);
+        }
+    }
+    let mut codewords = data.to_vec();
+    codewords.extend(remainder);
+    codewords
+}
+
+/// Coefficients of (x - α^0)(x - α^1)...(x - α^(degree-1)), highest power first with the
+/// leading 1 dropped.
+fn rs_generator(degree: usize) -> Vec<u8> {
+    let mut coefficients = vec![0u8; degree];
+    coefficients[degree - 1] = 1;
+    let mut root = 1u8;
+    for _ in 0..degree {
+        for j in 0..degree {
+            coefficients[j] = gf_mul(coefficients[j], root);
+            if j + 1 < degree {
+                coefficients[j] ^= coefficients[j + 1];
+            }
+        }
+        root = gf_mul(root, 0x02);
+    }
+    coefficients
+}
+
+/// Multiplication in GF(2^8) modulo x^8 + x^4 + x^3 + x^2 + 1.
+pub fn gf_mul(a: u8, b: u8) -> u8 {
+    let mut product = 0u16;
+    for i in (0..8).rev() {
+        product = (product << 1) ^ ((product >> 7) * 0x11D);
+        product ^= ((b as u16 >> i) & 1) * a as u16;
+    }
+    product as u8
+}
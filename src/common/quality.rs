@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use std::time::{Duration, Instant};
 use std::collections::VecDeque;
+use crate::common::adaptive_fps::MIN_FPS;
+use crate::common::encoder::{EncoderSettings, WebpMode};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum QualityMode {
@@ -12,6 +14,15 @@ pub enum QualityMode {
 }
 
 impl QualityMode {
+    /// Every mode, best first.
+    pub const ALL: [QualityMode; 5] = [
+        QualityMode::Ultra,
+        QualityMode::High,
+        QualityMode::Medium,
+        QualityMode::Low,
+        QualityMode::Minimal,
+    ];
+    
     pub fn resolution_scale(&self) -> f32 {
         match self {
             QualityMode::Ultra => 1.0,
@@ -32,6 +43,12 @@ impl QualityMode {
         }
     }
     
+    /// Frames a client may have been sent but not yet acknowledged before the server
+    /// waits for it: about a quarter of a second at the mode's frame rate.
+    pub fn frame_window(&self) -> usize {
+        (self.target_fps() as usize).div_ceil(4).max(3)
+    }
+    
     pub fn compression_level(&self) -> i32 {
         match self {
             QualityMode::Ultra => 0,    // Best quality, least compression
@@ -52,6 +69,16 @@ impl QualityMode {
         }
     }
     
+    /// Software encoder mode: the top tiers stay lossless so text is readable.
+    pub fn webp_mode(&self) -> WebpMode {
+        match self {
+            QualityMode::Ultra | QualityMode::High => WebpMode::Lossless,
+            QualityMode::Medium => WebpMode::Lossy { quality: 90.0 },
+            QualityMode::Low => WebpMode::Lossy { quality: 80.0 },
+            QualityMode::Minimal => WebpMode::Lossy { quality: 65.0 },
+        }
+    }
+    
     // Estimated bandwidth requirements in Mbps
     pub fn bandwidth_requirement(&self) -> f32 {
         match self {
@@ -62,11 +89,149 @@ impl QualityMode {
             QualityMode::Minimal => 2.0,
         }
     }
+    
+    /// H.264 settings for this mode: its frame rate and keyframe cadence, and a bitrate
+    /// scaled from `settings.bitrate` (High's) by the mode's share of the bandwidth.
+    pub fn encoder_settings(&self, settings: EncoderSettings) -> EncoderSettings {
+        let share = self.bandwidth_requirement() as f64 / QualityMode::High.bandwidth_requirement() as f64;
+        EncoderSettings {
+            fps: self.target_fps(),
+            keyframe_interval: self.keyframe_interval(),
+            bitrate: (settings.bitrate as f64 * share).clamp(1.0, u32::MAX as f64) as u32,
+            ..settings
+        }
+    }
+}
+
+/// What the screen mostly shows, which decides how a quality mode's budget is spent.
+/// Independent of `QualityMode`: text wants exact pixels and can update slowly, while
+/// video wants frame rate and can live with a softer image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ContentProfile {
+    /// Documents, code, slides: lossless, capped at `TEXT_MAX_FPS`, rare keyframes
+    Text,
+    /// The quality mode's settings unchanged
+    #[default]
+    Balanced,
+    /// Video, games, animation: lossy, full rate even when little changes, frequent keyframes
+    Motion,
+}
+
+/// Frame rate cap of the `Text` profile; a page of text gains nothing from more.
+pub const TEXT_MAX_FPS: u32 = 15;
+
+impl ContentProfile {
+    pub const ALL: [ContentProfile; 3] = [ContentProfile::Text, ContentProfile::Balanced, ContentProfile::Motion];
+    
+    pub fn label(&self) -> &'static str {
+        match self {
+            ContentProfile::Text => "Text / presentation",
+            ContentProfile::Balanced => "Balanced",
+            ContentProfile::Motion => "Video / motion",
+        }
+    }
+    
+    /// Whether frames must stay bit-exact, whatever the quality mode would allow.
+    pub fn lossless(&self) -> bool {
+        *self == ContentProfile::Text
+    }
+    
+    /// `settings` adjusted for this profile: frame rate, keyframe cadence and lossless mode.
+    pub fn encoder_settings(&self, mut settings: EncoderSettings) -> EncoderSettings {
+        match self {
+            ContentProfile::Text => {
+                settings.fps = settings.fps.min(TEXT_MAX_FPS);
+                // Deltas of a mostly still page are tiny; keyframes are the expensive part
+                settings.keyframe_interval = settings.keyframe_interval.saturating_mul(2);
+                settings.tuning.webp_mode = WebpMode::Lossless;
+            }
+            ContentProfile::Balanced => {}
+            ContentProfile::Motion => {
+                // Recover quickly from the artifacts a lost or late frame leaves in moving video
+                settings.keyframe_interval = (settings.keyframe_interval / 2).max(1);
+                if settings.tuning.webp_mode == WebpMode::Lossless {
+                    settings.tuning.webp_mode = WebpMode::Lossy { quality: 85.0 };
+                }
+            }
+        }
+        settings
+    }
+    
+    /// Lowest rate capture slows to while little on screen changes. A video playing in
+    /// part of the screen looks nearly static to the tile diff, so `Motion` never slows.
+    pub fn fps_floor(&self, target_fps: u32) -> u32 {
+        match self {
+            ContentProfile::Text | ContentProfile::Balanced => MIN_FPS.min(target_fps),
+            ContentProfile::Motion => target_fps,
+        }
+    }
+}
+
+/// Why the stream quality changed, so the client can explain it to the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QualityChangeReason {
+    /// Quality announced at stream start
+    Initial,
+    /// The user picked a quality mode
+    Manual,
+    LowBandwidth,
+    HighLatency,
+    PacketLoss,
+    /// Adaptive controller stepped back up
+    NetworkImproved,
+}
+
+impl QualityChangeReason {
+    pub fn description(&self) -> &'static str {
+        match self {
+            QualityChangeReason::Initial => "initial setting",
+            QualityChangeReason::Manual => "requested",
+            QualityChangeReason::LowBandwidth => "low bandwidth",
+            QualityChangeReason::HighLatency => "high latency",
+            QualityChangeReason::PacketLoss => "packet loss",
+            QualityChangeReason::NetworkImproved => "improved network conditions",
+        }
+    }
+}
+
+/// The metric costing the most score in `calculate_quality`, i.e. what is holding quality back.
+pub fn dominant_factor(bandwidth: f32, rtt: Duration, packet_loss: f32) -> QualityChangeReason {
+    let (bandwidth_score, rtt_score, loss_score) = metric_scores(bandwidth, rtt, packet_loss);
+    
+    let bandwidth_penalty = (1.0 - bandwidth_score) * BANDWIDTH_WEIGHT;
+    let rtt_penalty = (1.0 - rtt_score) * RTT_WEIGHT;
+    let loss_penalty = (1.0 - loss_score) * LOSS_WEIGHT;
+    
+    if bandwidth_penalty >= rtt_penalty && bandwidth_penalty >= loss_penalty {
+        QualityChangeReason::LowBandwidth
+    } else if rtt_penalty >= loss_penalty {
+        QualityChangeReason::HighLatency
+    } else {
+        QualityChangeReason::PacketLoss
+    }
+}
+
+const BANDWIDTH_WEIGHT: f32 = 0.5;
+const RTT_WEIGHT: f32 = 0.3;
+const LOSS_WEIGHT: f32 = 0.2;
+
+/// Each metric normalized to 0.0 (bad) ..= 1.0 (good)
+fn metric_scores(bandwidth: f32, rtt: Duration, packet_loss: f32) -> (f32, f32, f32) {
+    let bandwidth_score = (bandwidth / 50.0).min(1.0);
+    let rtt_score = 1.0 - (rtt.as_millis() as f32 / 200.0).min(1.0);
+    let loss_score = 1.0 - (packet_loss / 10.0).min(1.0);
+    (bandwidth_score, rtt_score, loss_score)
 }
 
+/// Span of recent samples the bandwidth estimate is taken over. Older samples are
+/// dropped however few there are, so a low frame rate can't leave the estimate stale.
+pub const BANDWIDTH_WINDOW: Duration = Duration::from_secs(2);
+
+// Upper bound on samples kept within the window, for very high message rates
+const MAX_BANDWIDTH_SAMPLES: usize = 1024;
+
 pub struct BandwidthMonitor {
     samples: VecDeque<BandwidthSample>,
-    max_samples: usize,
     last_update: Instant,
 }
 
@@ -79,43 +244,52 @@ struct BandwidthSample {
 impl BandwidthMonitor {
     pub fn new() -> Self {
         Self {
-            samples: VecDeque::with_capacity(30),
-            max_samples: 30,
+            samples: VecDeque::with_capacity(64),
             last_update: Instant::now(),
         }
     }
     
     pub fn add_sample(&mut self, bytes_sent: usize, rtt: Duration) {
-        let sample = BandwidthSample {
-            timestamp: Instant::now(),
+        self.add_sample_at(bytes_sent, rtt, Instant::now());
+    }
+    
+    /// Record `bytes_sent` and `rtt` as measured at `now`, dropping samples that have
+    /// fallen out of `BANDWIDTH_WINDOW`.
+    pub fn add_sample_at(&mut self, bytes_sent: usize, rtt: Duration, now: Instant) {
+        self.samples.push_back(BandwidthSample {
+            timestamp: now,
             bytes_sent,
             rtt,
-        };
+        });
         
-        self.samples.push_back(sample);
-        if self.samples.len() > self.max_samples {
+        while self.samples.front().is_some_and(|oldest| now.duration_since(oldest.timestamp) > BANDWIDTH_WINDOW)
+            || self.samples.len() > MAX_BANDWIDTH_SAMPLES
+        {
             self.samples.pop_front();
         }
         
-        self.last_update = Instant::now();
+        self.last_update = now;
     }
     
     pub fn get_bandwidth_mbps(&self) -> Option<f32> {
-        if self.samples.len() < 2 {
-            return None;
-        }
-        
-        let first = self.samples.front()?;
-        let last = self.samples.back()?;
+        self.bandwidth_mbps_at(Instant::now())
+    }
+    
+    /// Throughput over the samples within `BANDWIDTH_WINDOW` of `now`, measured up to
+    /// `now` so the estimate falls once sending stops. The oldest sample's bytes went out
+    /// before the span starts and aren't counted.
+    pub fn bandwidth_mbps_at(&self, now: Instant) -> Option<f32> {
+        let mut recent = self.samples.iter()
+            .filter(|s| now.saturating_duration_since(s.timestamp) <= BANDWIDTH_WINDOW);
+        let oldest = recent.next()?;
+        let total_bytes: usize = recent.map(|s| s.bytes_sent).sum();
         
-        let duration = last.timestamp.duration_since(first.timestamp);
+        let duration = now.saturating_duration_since(oldest.timestamp);
         if duration.as_secs_f32() < 0.1 {
             return None;
         }
         
-        let total_bytes: usize = self.samples.iter().map(|s| s.bytes_sent).sum();
         let mbps = (total_bytes as f32 * 8.0) / (duration.as_secs_f32() * 1_000_000.0);
-        
         Some(mbps)
     }
     
@@ -157,6 +331,10 @@ pub struct AdaptiveQualityController {
     last_quality_change: Instant,
     quality_change_cooldown: Duration,
     forced_quality: Option<QualityMode>,
+    // Adaptive change not yet announced to the client
+    pending_change: Option<QualityChangeReason>,
+    min_quality: QualityMode,
+    max_quality: QualityMode,
 }
 
 impl AdaptiveQualityController {
@@ -167,11 +345,35 @@ impl AdaptiveQualityController {
             last_quality_change: Instant::now(),
             quality_change_cooldown: Duration::from_secs(2),
             forced_quality: None,
+            pending_change: None,
+            min_quality: QualityMode::Minimal,
+            max_quality: QualityMode::Ultra,
+        }
+    }
+    
+    /// Keep every quality this controller picks, forced or adaptive, within `min..=max`.
+    pub fn set_quality_bounds(&mut self, min: QualityMode, max: QualityMode) {
+        self.min_quality = min;
+        self.max_quality = max;
+        self.current_quality = self.clamp_quality(self.current_quality);
+        self.forced_quality = self.forced_quality.map(|q| self.clamp_quality(q));
+    }
+    
+    fn clamp_quality(&self, quality: QualityMode) -> QualityMode {
+        if quality.bandwidth_requirement() > self.max_quality.bandwidth_requirement() {
+            self.max_quality
+        } else if quality.bandwidth_requirement() < self.min_quality.bandwidth_requirement() {
+            self.min_quality
+        } else {
+            quality
         }
     }
     
     pub fn force_quality(&mut self, quality: Option<QualityMode>) {
+        let quality = quality.map(|q| self.clamp_quality(q));
         self.forced_quality = quality;
+        // The client already knows about its own request
+        self.pending_change = None;
         if let Some(q) = quality {
             self.current_quality = q;
         }
@@ -199,10 +401,16 @@ impl AdaptiveQualityController {
         let packet_loss = self.bandwidth_monitor.get_packet_loss_rate();
         
         // Determine quality based on metrics
-        let recommended = self.calculate_quality(bandwidth, avg_rtt, packet_loss);
+        let recommended = self.clamp_quality(self.calculate_quality(bandwidth, avg_rtt, packet_loss));
         
         // Only change if significantly different
         if recommended != self.current_quality {
+            let reason = if recommended.bandwidth_requirement() < self.current_quality.bandwidth_requirement() {
+                dominant_factor(bandwidth, avg_rtt, packet_loss)
+            } else {
+                QualityChangeReason::NetworkImproved
+            };
+            self.pending_change = Some(reason);
             self.current_quality = recommended;
             self.last_quality_change = Instant::now();
         }
@@ -212,12 +420,10 @@ impl AdaptiveQualityController {
     
     fn calculate_quality(&self, bandwidth: f32, rtt: Duration, packet_loss: f32) -> QualityMode {
         // Score based on multiple factors
-        let bandwidth_score = (bandwidth / 50.0).min(1.0);
-        let rtt_score = 1.0 - (rtt.as_millis() as f32 / 200.0).min(1.0);
-        let loss_score = 1.0 - (packet_loss / 10.0).min(1.0);
+        let (bandwidth_score, rtt_score, loss_score) = metric_scores(bandwidth, rtt, packet_loss);
         
         // Weighted average
-        let total_score = bandwidth_score * 0.5 + rtt_score * 0.3 + loss_score * 0.2;
+        let total_score = bandwidth_score * BANDWIDTH_WEIGHT + rtt_score * RTT_WEIGHT + loss_score * LOSS_WEIGHT;
         
         match total_score {
             s if s >= 0.8 => QualityMode::Ultra,
@@ -228,6 +434,11 @@ impl AdaptiveQualityController {
         }
     }
     
+    /// The adaptive quality change made since the last call, if any.
+    pub fn take_quality_change(&mut self) -> Option<(QualityMode, QualityChangeReason)> {
+        self.pending_change.take().map(|reason| (self.current_quality, reason))
+    }
+    
     pub fn get_current_quality(&self) -> QualityMode {
         self.current_quality
     }
@@ -0,0 +1,127 @@
+use crate::common::protocol::{Message, Modifiers};
+
+/// Key combinations the local OS would normally swallow before the client sees them.
+///
+/// The client synthesizes these as plain `KeyEvent`s, so they reach the remote machine
+/// no matter what the local machine does with the physical keys.
+///
+/// Permissions on the server side:
+/// - Ctrl+Alt+Del on Windows is the Secure Attention Sequence. It can't be injected as
+///   ordinary input; the server calls `SendSAS`, which only works when running as a service
+///   under LocalSystem with the "Disable or enable software Secure Attention Sequence" group
+///   policy set to allow services. Otherwise Windows silently ignores it.
+/// - On macOS every combo needs the Accessibility permission the server already requires
+///   for input injection. Ctrl+Alt+Del has no system meaning there.
+/// - On Linux (X11) combos are delivered as regular key events; whether Ctrl+Alt+Del does
+///   anything depends on the desktop environment.
+/// - Ctrl+Alt+Fn switches virtual terminals on Linux. The X server acts on it unless VT
+///   switching is disabled in its config; Wayland compositors may ignore injected input,
+///   and a server without access to the console (e.g. over SSH) can't switch at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecialKeyCombo {
+    CtrlAltDelete,
+    CtrlShiftEscape,
+    AltTab,
+    AltF4,
+    SuperKey,
+    CmdSpace,
+    CmdTab,
+    PrintScreen,
+    /// Ctrl+Alt+F`n`, to switch a Linux host to virtual terminal `n` (1 to 12)
+    SwitchVirtualTerminal(u8),
+}
+
+/// Virtual terminals offered in the client's menu; the usual text consoles.
+pub const MENU_VIRTUAL_TERMINALS: std::ops::RangeInclusive<u8> = 1..=6;
+
+const FUNCTION_KEYS: [&str; 12] = ["F1", "F2", "F3", "F4", "F5", "F6", "F7", "F8", "F9", "F10", "F11", "F12"];
+
+impl SpecialKeyCombo {
+    pub const ALL: [SpecialKeyCombo; 8] = [
+        SpecialKeyCombo::CtrlAltDelete,
+        SpecialKeyCombo::CtrlShiftEscape,
+        SpecialKeyCombo::AltTab,
+        SpecialKeyCombo::AltF4,
+        SpecialKeyCombo::SuperKey,
+        SpecialKeyCombo::CmdSpace,
+        SpecialKeyCombo::CmdTab,
+        SpecialKeyCombo::PrintScreen,
+    ];
+
+    pub fn label(&self) -> String {
+        match self {
+            SpecialKeyCombo::CtrlAltDelete => "Ctrl+Alt+Del".to_string(),
+            SpecialKeyCombo::CtrlShiftEscape => "Ctrl+Shift+Esc".to_string(),
+            SpecialKeyCombo::AltTab => "Alt+Tab".to_string(),
+            SpecialKeyCombo::AltF4 => "Alt+F4".to_string(),
+            SpecialKeyCombo::SuperKey => "Windows / Super key".to_string(),
+            SpecialKeyCombo::CmdSpace => "Cmd+Space".to_string(),
+            SpecialKeyCombo::CmdTab => "Cmd+Tab".to_string(),
+            SpecialKeyCombo::PrintScreen => "Print Screen".to_string(),
+            SpecialKeyCombo::SwitchVirtualTerminal(n) => format!("Ctrl+Alt+F{} (terminal {})", n, n),
+        }
+    }
+
+    /// Key names in press order, using the names the server's `InputHandler` understands.
+    /// Empty for a virtual terminal outside 1 to 12.
+    pub fn keys(&self) -> Vec<&'static str> {
+        match self {
+            SpecialKeyCombo::CtrlAltDelete => vec!["Control", "Alt", "Delete"],
+            SpecialKeyCombo::CtrlShiftEscape => vec!["Control", "Shift", "Escape"],
+            SpecialKeyCombo::AltTab => vec!["Alt", "Tab"],
+            SpecialKeyCombo::AltF4 => vec!["Alt", "F4"],
+            SpecialKeyCombo::SuperKey => vec!["Meta"],
+            SpecialKeyCombo::CmdSpace => vec!["Meta", "Space"],
+            SpecialKeyCombo::CmdTab => vec!["Meta", "Tab"],
+            SpecialKeyCombo::PrintScreen => vec!["Print"],
+            SpecialKeyCombo::SwitchVirtualTerminal(n) => match (*n as usize).checked_sub(1).and_then(|i| FUNCTION_KEYS.get(i)) {
+                Some(key) => vec!["Control", "Alt", key],
+                None => Vec::new(),
+            },
+        }
+    }
+
+    /// Whether the remote side needs elevated privileges to act on this combo.
+    pub fn requires_elevation(&self) -> bool {
+        matches!(self, SpecialKeyCombo::CtrlAltDelete)
+    }
+
+    /// Press every key in order, then release them in reverse.
+    pub fn key_events(&self) -> Vec<Message> {
+        let keys = self.keys();
+        let mut events = Vec::with_capacity(keys.len() * 2);
+        let mut held = Modifiers::default();
+
+        for key in &keys {
+            events.push(Message::KeyEvent {
+                key: key.to_string(),
+                pressed: true,
+                repeat: false,
+                modifiers: held.clone(),
+            });
+            set_modifier(&mut held, key, true);
+        }
+
+        for key in keys.iter().rev() {
+            set_modifier(&mut held, key, false);
+            events.push(Message::KeyEvent {
+                key: key.to_string(),
+                pressed: false,
+                repeat: false,
+                modifiers: held.clone(),
+            });
+        }
+
+        events
+    }
+}
+
+fn set_modifier(modifiers: &mut Modifiers, key: &str, down: bool) {
+    match key {
+        "Control" => modifiers.ctrl = down,
+        "Alt" => modifiers.alt = down,
+        "Shift" => modifiers.shift = down,
+        "Meta" => modifiers.meta = down,
+        _ => {}
+    }
+}
@@ -0,0 +1,75 @@
+use anyhow::{anyhow, Context, Result};
+use std::net::SocketAddr;
+use tokio::net::{lookup_host, TcpListener, TcpSocket, TcpStream};
+
+/// Connections a listener queues before they are accepted.
+const LISTEN_BACKLOG: u32 = 1024;
+
+/// Kernel buffer sizes for TCP sockets, in bytes; `None` keeps the system default, which
+/// Linux tunes on its own. A link with a high bandwidth-delay product (fast but far away)
+/// needs a send buffer of about bandwidth x round-trip time to stay full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SocketBuffers {
+    pub send: Option<u32>,
+    pub recv: Option<u32>,
+}
+
+impl SocketBuffers {
+    fn apply(&self, socket: &TcpSocket) -> Result<()> {
+        if let Some(size) = self.send {
+            socket.set_send_buffer_size(size).context("Failed to set the socket send buffer size")?;
+        }
+        if let Some(size) = self.recv {
+            socket.set_recv_buffer_size(size).context("Failed to set the socket receive buffer size")?;
+        }
+        Ok(())
+    }
+}
+
+/// Send small messages at once. Nagle's algorithm would hold a click or key press back
+/// until the previous packet is acked, up to ~40ms with delayed acks.
+pub fn configure(stream: &TcpStream) -> Result<()> {
+    stream.set_nodelay(true).context("Failed to set TCP_NODELAY")
+}
+
+/// Connect to `addr` (host:port), trying each address it resolves to, with `buffers`
+/// applied before the handshake so the window scale covers them.
+pub async fn connect(addr: &str, buffers: SocketBuffers) -> Result<TcpStream> {
+    let mut last_error = None;
+    for address in lookup_host(addr).await.with_context(|| format!("Failed to resolve {}", addr))? {
+        match connect_to(address, buffers).await {
+            Ok(stream) => {
+                configure(&stream)?;
+                return Ok(stream);
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| anyhow!("{} resolved to no addresses", addr)))
+}
+
+async fn connect_to(address: SocketAddr, buffers: SocketBuffers) -> Result<TcpStream> {
+    let socket = new_socket(address)?;
+    buffers.apply(&socket)?;
+    Ok(socket.connect(address).await?)
+}
+
+/// Listen on `addr` with `buffers` applied; accepted connections inherit them.
+pub async fn listen(addr: &str, buffers: SocketBuffers) -> Result<TcpListener> {
+    let address = lookup_host(addr).await
+        .with_context(|| format!("Failed to resolve {}", addr))?
+        .next()
+        .ok_or_else(|| anyhow!("{} resolved to no addresses", addr))?;
+    
+    let socket = new_socket(address)?;
+    // Like TcpListener::bind, so a restarted server can take its port back right away
+    #[cfg(unix)]
+    socket.set_reuseaddr(true)?;
+    buffers.apply(&socket)?;
+    socket.bind(address)?;
+    Ok(socket.listen(LISTEN_BACKLOG)?)
+}
+
+fn new_socket(address: SocketAddr) -> Result<TcpSocket> {
+    Ok(if address.is_ipv4() { TcpSocket::new_v4()? } else { TcpSocket::new_v6()? })
+}
@@ -1,10 +1,136 @@
 use anyhow::{Result, Context};
 use quinn::{Endpoint, ServerConfig, ClientConfig, Connection, RecvStream, SendStream};
+use std::fmt;
+use std::future::Future;
 use std::sync::Arc;
 use std::net::SocketAddr;
-use tokio::sync::mpsc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, Mutex};
 use bytes::Bytes;
 
+use crate::common::framing::{frame_len, FrameDecoder, ReadBuffer, DEFAULT_READ_CAPACITY, LENGTH_PREFIX, MAX_FRAME_LEN};
+
+/// How `Transport::send_datagram` got a payload to the peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameRoute {
+    /// One unreliable datagram
+    Datagram,
+    /// A stream of its own, because the payload is bigger than the peer takes in a datagram
+    Stream,
+}
+
+/// What `Transport::recv` fails with once the peer has gone, whether it closed the
+/// connection or just dropped it; any other error means the connection broke.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionClosed;
+
+impl fmt::Display for ConnectionClosed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Connection closed")
+    }
+}
+
+impl std::error::Error for ConnectionClosed {}
+
+/// Whether `error` only means the peer went away.
+pub fn is_closed(error: &anyhow::Error) -> bool {
+    error.is::<ConnectionClosed>()
+}
+
+// Read errors that only mean the peer went away without closing the connection
+fn is_disconnect(error: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+    matches!(
+        error.kind(),
+        ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted | ErrorKind::BrokenPipe | ErrorKind::UnexpectedEof
+    )
+}
+
+/// A connection carrying whole messages, so a session works the same over TCP and QUIC.
+/// `send` and `recv` are reliable and keep messages apart (length-prefixed on the wire).
+pub trait Transport: Send + Sync {
+    fn send(&self, data: &[u8]) -> impl Future<Output = Result<()>> + Send;
+    
+    /// The next message from the peer; `ConnectionClosed` once the peer is gone.
+    fn recv(&self) -> impl Future<Output = Result<Vec<u8>>> + Send;
+    
+    /// Send `data` where losing it beats waiting for it, such as a real-time video frame.
+    /// Transports without datagrams send it reliably instead.
+    fn send_datagram(&self, data: &[u8]) -> impl Future<Output = Result<FrameRoute>> + Send {
+        async move { self.send(data).await.map(|()| FrameRoute::Stream) }
+    }
+    
+    /// The next datagram from the peer, which may have been sent after messages still on
+    /// their way. Transports without datagrams never give one.
+    fn recv_datagram(&self) -> impl Future<Output = Result<Vec<u8>>> + Send {
+        std::future::pending()
+    }
+}
+
+/// A TCP stream of length-prefixed messages. Reading and writing are locked separately,
+/// so one task can wait for messages while others send.
+pub struct TcpConnection {
+    reader: Mutex<TcpReader>,
+    writer: Mutex<OwnedWriteHalf>,
+    remote_address: SocketAddr,
+}
+
+struct TcpReader {
+    stream: OwnedReadHalf,
+    buffer: ReadBuffer,
+    frames: FrameDecoder,
+}
+
+impl TcpConnection {
+    pub fn new(stream: TcpStream) -> Result<Self> {
+        Self::with_read_capacity(stream, DEFAULT_READ_CAPACITY)
+    }
+    
+    /// A connection whose read buffer is sized for `capacity`, e.g. `CONTROL_READ_CAPACITY`
+    /// for a server that only reads input and control messages.
+    pub fn with_read_capacity(stream: TcpStream, capacity: usize) -> Result<Self> {
+        let remote_address = stream.peer_addr()?;
+        let (reader, writer) = stream.into_split();
+        Ok(Self {
+            reader: Mutex::new(TcpReader { stream: reader, buffer: ReadBuffer::with_capacity(capacity), frames: FrameDecoder::new() }),
+            writer: Mutex::new(writer),
+            remote_address,
+        })
+    }
+    
+    pub fn remote_address(&self) -> SocketAddr {
+        self.remote_address
+    }
+}
+
+impl Transport for TcpConnection {
+    async fn send(&self, data: &[u8]) -> Result<()> {
+        let len = data.len() as u32;
+        let mut writer = self.writer.lock().await;
+        writer.write_all(&len.to_be_bytes()).await?;
+        writer.write_all(data).await?;
+        Ok(())
+    }
+    
+    async fn recv(&self) -> Result<Vec<u8>> {
+        let mut reader = self.reader.lock().await;
+        let TcpReader { stream, buffer, frames } = &mut *reader;
+        loop {
+            if let Some(frame) = frames.decode(buffer.received())? {
+                return Ok(frame.into());
+            }
+            match stream.read_buf(buffer.for_read()).await {
+                Ok(0) => return Err(ConnectionClosed.into()),
+                Ok(_) => {}
+                Err(e) if is_disconnect(&e) => return Err(ConnectionClosed.into()),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
 pub struct QuicTransport {
     endpoint: Endpoint,
 }
@@ -12,7 +138,7 @@ pub struct QuicTransport {
 #[derive(Clone)]
 pub struct QuicConnection {
     connection: Connection,
-    incoming: Arc<tokio::sync::Mutex<mpsc::UnboundedReceiver<(SendStream, RecvStream)>>>,
+    incoming: Arc<Mutex<mpsc::UnboundedReceiver<(SendStream, RecvStream)>>>,
 }
 
 impl QuicTransport {
@@ -28,7 +154,7 @@ impl QuicTransport {
         
         let server_config = ServerConfig::with_single_cert(
             vec![cert_der.clone()],
-            priv_key_der.clone(),
+            rustls::pki_types::PrivatePkcs8KeyDer::from(priv_key_der).into(),
         )?;
         let endpoint = Endpoint::server(server_config, addr)?;
         
@@ -39,11 +165,13 @@ impl QuicTransport {
         let mut endpoint = Endpoint::client("0.0.0.0:0".parse()?)?;
         
         // Create client config that accepts any certificate
+        let crypto = rustls::ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_protocol_versions(&[&rustls::version::TLS13])?
+            .dangerous()
+            .with_custom_certificate_verifier(SkipServerVerification::new())
+            .with_no_client_auth();
         let mut transport_config = ClientConfig::new(Arc::new(
-            rustls::ClientConfig::builder()
-                .dangerous()
-                .with_custom_certificate_verifier(SkipServerVerification::new())
-                .with_no_client_auth()
+            quinn::crypto::rustls::QuicClientConfig::try_from(crypto)?
         ));
         transport_config.transport_config(Arc::new(Self::transport_config()));
         
@@ -73,12 +201,16 @@ impl QuicTransport {
         config
     }
     
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.endpoint.local_addr()?)
+    }
+    
     pub async fn accept(&self) -> Result<QuicConnection> {
         let connecting = self.endpoint
             .accept()
             .await
             .context("Failed to accept connection")?;
-            
+        
         let connection = connecting.await?;
         
         let (tx, rx) = mpsc::unbounded_channel();
@@ -93,7 +225,7 @@ impl QuicTransport {
         
         Ok(QuicConnection {
             connection,
-            incoming: Arc::new(tokio::sync::Mutex::new(rx)),
+            incoming: Arc::new(Mutex::new(rx)),
         })
     }
     
@@ -101,7 +233,7 @@ impl QuicTransport {
         let connection = self.endpoint
             .connect(addr, server_name)?
             .await?;
-            
+        
         let (tx, rx) = mpsc::unbounded_channel();
         let conn_clone = connection.clone();
         
@@ -114,7 +246,7 @@ impl QuicTransport {
         
         Ok(QuicConnection {
             connection,
-            incoming: Arc::new(tokio::sync::Mutex::new(rx)),
+            incoming: Arc::new(Mutex::new(rx)),
         })
     }
 }
@@ -142,6 +274,28 @@ impl QuicConnection {
             .context("Failed to send datagram")
     }
     
+    /// Largest datagram the peer accepts on the current path, `None` when it takes none.
+    pub fn max_datagram_size(&self) -> Option<usize> {
+        self.connection.max_datagram_size()
+    }
+    
+    /// Send `data` as a datagram when it fits, otherwise framed on a stream of its own
+    /// (see `send_message`), so a large frame arrives late rather than not at all.
+    pub async fn send_datagram_or_stream(&self, data: Bytes) -> Result<FrameRoute> {
+        if self.max_datagram_size().map_or(false, |max| data.len() <= max) {
+            match self.connection.send_datagram(data.clone()) {
+                Ok(()) => return Ok(FrameRoute::Datagram),
+                // The path MTU can shrink between the check and the send
+                Err(quinn::SendDatagramError::TooLarge) => {}
+                Err(e) => return Err(e).context("Failed to send datagram"),
+            }
+        }
+        
+        let (mut send, _recv) = self.open_stream().await?;
+        send_message(&mut send, &data).await?;
+        Ok(FrameRoute::Stream)
+    }
+    
     pub async fn receive_datagram(&self) -> Result<Bytes> {
         self.connection
             .read_datagram()
@@ -158,6 +312,32 @@ impl QuicConnection {
     }
 }
 
+/// Every message gets a stream of its own, so a lost packet holds up only that message;
+/// datagrams go as QUIC datagrams when they fit.
+impl Transport for QuicConnection {
+    async fn send(&self, data: &[u8]) -> Result<()> {
+        let (mut send, _recv) = self.open_stream().await?;
+        send_message(&mut send, data).await
+    }
+    
+    async fn recv(&self) -> Result<Vec<u8>> {
+        let (_send, mut recv) = self.incoming.lock().await.recv().await.ok_or(ConnectionClosed)?;
+        match receive_message(&mut recv).await {
+            // The stream broke because the whole connection did
+            Err(_) if self.connection.close_reason().is_some() => Err(ConnectionClosed.into()),
+            result => result,
+        }
+    }
+    
+    async fn send_datagram(&self, data: &[u8]) -> Result<FrameRoute> {
+        self.send_datagram_or_stream(Bytes::copy_from_slice(data)).await
+    }
+    
+    async fn recv_datagram(&self) -> Result<Vec<u8>> {
+        self.receive_datagram().await.map(|data| data.to_vec())
+    }
+}
+
 // Helper to skip certificate verification for development
 #[derive(Debug)]
 struct SkipServerVerification;
@@ -223,9 +403,9 @@ pub async fn send_message(stream: &mut SendStream, data: &[u8]) -> Result<()> {
 }
 
 pub async fn receive_message(stream: &mut RecvStream) -> Result<Vec<u8>> {
-    let mut len_buf = [0u8; 4];
+    let mut len_buf = [0u8; LENGTH_PREFIX];
     stream.read_exact(&mut len_buf).await?;
-    let len = u32::from_be_bytes(len_buf) as usize;
+    let len = frame_len(len_buf, MAX_FRAME_LEN)?;
     
     let mut data = vec![0u8; len];
     stream.read_exact(&mut data).await?;
@@ -0,0 +1,101 @@
+use std::time::{Duration, Instant};
+
+/// Frames are never scaled below this fraction of the captured size, however small the
+/// client's window gets.
+pub const MIN_VIEWPORT_SCALE: f32 = 0.25;
+
+/// How long the client's window must keep its size before the server is told, so a
+/// drag-resize doesn't reconfigure the encoder on every step.
+pub const VIEWPORT_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Scale to apply to a `frame` of captured pixels so it fits inside `viewport` physical
+/// pixels. Frames are only ever scaled down; `None` (or an empty viewport) keeps them whole.
+pub fn viewport_scale(frame: (u32, u32), viewport: Option<(u32, u32)>) -> f32 {
+    let Some((width, height)) = viewport.filter(|&(w, h)| w > 0 && h > 0) else {
+        return 1.0;
+    };
+    if frame.0 == 0 || frame.1 == 0 {
+        return 1.0;
+    }
+    
+    let fit = (width as f32 / frame.0 as f32).min(height as f32 / frame.1 as f32);
+    fit.clamp(MIN_VIEWPORT_SCALE, 1.0)
+}
+
+/// Size of a `frame` scaled by `scale`, rounded down to even dimensions for the encoder.
+/// An unscaled frame keeps its exact size.
+pub fn scaled_size(frame: (u32, u32), scale: f32) -> (u32, u32) {
+    if scale >= 1.0 {
+        return frame;
+    }
+    let scale_dimension = |dimension: u32| (((dimension as f32 * scale) as u32) & !1).max(2);
+    (scale_dimension(frame.0), scale_dimension(frame.1))
+}
+
+/// The viewport one capture has to serve for several viewers: the largest width and
+/// height any of them shows. `None` while a viewer hasn't reported one (or there are none),
+/// since that viewer wants frames at full size.
+pub fn combined_viewport(viewports: impl IntoIterator<Item = Option<(u32, u32)>>) -> Option<(u32, u32)> {
+    let mut combined: Option<(u32, u32)> = None;
+    for viewport in viewports {
+        let (width, height) = viewport?;
+        combined = Some(match combined {
+            Some((w, h)) => (w.max(width), h.max(height)),
+            None => (width, height),
+        });
+    }
+    combined
+}
+
+/// Tells the server about the client's viewport once it has held still for
+/// `VIEWPORT_DEBOUNCE`, and only when it differs from what was last sent.
+#[derive(Debug, Default)]
+pub struct ViewportReporter {
+    // Viewport last told to the server; a new session sends frames at full size
+    sent: Option<(u32, u32)>,
+    reported: bool,
+    // Latest viewport seen and when it first appeared
+    pending: Option<(Option<(u32, u32)>, Instant)>,
+}
+
+impl ViewportReporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Record the viewport at `now`, `None` when frames should come at full size. Returns
+    /// the viewport to send once it has settled; an empty one lifts the limit.
+    pub fn update(&mut self, viewport: Option<(u32, u32)>, now: Instant) -> Option<(u32, u32)> {
+        if self.sent == viewport {
+            self.pending = None;
+            return None;
+        }
+        
+        let since = match self.pending {
+            Some((pending, since)) if pending == viewport => since,
+            _ => {
+                self.pending = Some((viewport, now));
+                now
+            }
+        };
+        // Nothing was sent yet: the first size needs no settling
+        if self.reported && now.duration_since(since) < VIEWPORT_DEBOUNCE {
+            return None;
+        }
+        
+        self.sent = viewport;
+        self.reported = true;
+        self.pending = None;
+        Some(viewport.unwrap_or((0, 0)))
+    }
+    
+    /// Whether a viewport is waiting out the debounce, so the caller knows to check again.
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+    
+    /// Forget what was sent, e.g. for a new session that knows nothing yet.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
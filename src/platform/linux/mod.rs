@@ -1,2 +1,5 @@
 // Placeholder for Linux hardware encoder support
-// TODO: Implement VAAPI or NVENC support
\ No newline at end of file
+// TODO: Implement VAAPI or NVENC support
+
+#[cfg(feature = "wayland")]
+pub mod wayland_capture;
\ No newline at end of file
@@ -0,0 +1,265 @@
+use anyhow::{Result, Context, anyhow};
+use ashpd::desktop::screencast::{CursorMode, Screencast, SourceType};
+use ashpd::desktop::{PersistMode, ResponseError};
+use ashpd::WindowIdentifier;
+use parking_lot::{Condvar, Mutex};
+use pipewire as pw;
+use pw::{properties::properties, spa};
+use std::io;
+use std::os::fd::OwnedFd;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::server::frame_source::FrameSource;
+
+/// Screen capture for Wayland sessions through the xdg-desktop-portal ScreenCast
+/// interface. The portal hands us a PipeWire remote which streams BGRx frames.
+///
+/// The compositor only sends a frame when something on screen changed, so a frame
+/// waiting to be taken is the damage signal.
+pub struct PipeWireCapture {
+    latest: Arc<LatestFrame>,
+    width: usize,
+    height: usize,
+}
+
+// The newest frame from the stream, not yet taken, and a wakeup for whoever waits on one
+#[derive(Default)]
+struct LatestFrame {
+    frame: Mutex<Option<Vec<u8>>>,
+    arrived: Condvar,
+}
+
+struct StreamData {
+    format: spa::param::video::VideoInfoRaw,
+    width: usize,
+    height: usize,
+    latest: Arc<LatestFrame>,
+}
+
+impl PipeWireCapture {
+    pub fn new() -> Result<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let (fd, node_id, width, height) = runtime.block_on(open_portal())?;
+        
+        tracing::info!("Portal granted PipeWire node {} ({}x{})", node_id, width, height);
+        
+        let latest = Arc::new(LatestFrame::default());
+        let stream_latest = latest.clone();
+        
+        std::thread::spawn(move || {
+            if let Err(e) = run_stream(fd, node_id, width, height, stream_latest) {
+                tracing::error!("PipeWire stream error: {}", e);
+            }
+        });
+        
+        Ok(Self { latest, width, height })
+    }
+}
+
+impl FrameSource for PipeWireCapture {
+    fn frame(&mut self) -> io::Result<Vec<u8>> {
+        self.latest
+            .frame
+            .lock()
+            .take()
+            .ok_or_else(|| io::ErrorKind::WouldBlock.into())
+    }
+    
+    fn width(&self) -> usize {
+        self.width
+    }
+    
+    fn height(&self) -> usize {
+        self.height
+    }
+    
+    // The portal session is opened with CursorMode::Embedded and can't switch while streaming
+    fn includes_cursor(&self) -> bool {
+        true
+    }
+    
+    fn reports_damage(&self) -> bool {
+        true
+    }
+    
+    fn wait_for_damage(&mut self, timeout: Duration) -> io::Result<bool> {
+        let mut frame = self.latest.frame.lock();
+        if frame.is_none() {
+            self.latest.arrived.wait_for(&mut frame, timeout);
+        }
+        Ok(frame.is_some())
+    }
+}
+
+async fn open_portal() -> Result<(OwnedFd, u32, usize, usize)> {
+    let proxy = Screencast::new()
+        .await
+        .context("xdg-desktop-portal ScreenCast interface is not available")?;
+    let session = proxy.create_session().await?;
+    
+    proxy
+        .select_sources(
+            &session,
+            CursorMode::Embedded,
+            SourceType::Monitor.into(),
+            false,
+            None,
+            PersistMode::DoNot,
+        )
+        .await?;
+    
+    let response = proxy
+        .start(&session, &WindowIdentifier::default())
+        .await?
+        .response()
+        .map_err(|e| match e {
+            ashpd::Error::Response(ResponseError::Cancelled) => anyhow!(
+                "Screen capture permission was denied; accept the screen sharing \
+                 prompt from your desktop to allow remote access"
+            ),
+            other => anyhow!("Screen capture portal request failed: {}", other),
+        })?;
+    
+    let stream = response
+        .streams()
+        .first()
+        .context("Screen capture portal returned no streams")?;
+    let node_id = stream.pipe_wire_node_id();
+    let (width, height) = stream.size().context("Screen capture portal did not report a stream size")?;
+    
+    let fd = proxy.open_pipe_wire_remote(&session).await?;
+    
+    Ok((fd, node_id, width as usize, height as usize))
+}
+
+fn run_stream(
+    fd: OwnedFd,
+    node_id: u32,
+    width: usize,
+    height: usize,
+    latest: Arc<LatestFrame>,
+) -> Result<()> {
+    pw::init();
+    
+    let mainloop = pw::main_loop::MainLoop::new(None)?;
+    let context = pw::context::Context::new(&mainloop)?;
+    let core = context.connect_fd(fd, None)?;
+    
+    let stream = pw::stream::Stream::new(
+        &core,
+        "vox-screen-capture",
+        properties! {
+            *pw::keys::MEDIA_TYPE => "Video",
+            *pw::keys::MEDIA_CATEGORY => "Capture",
+            *pw::keys::MEDIA_ROLE => "Screen",
+        },
+    )?;
+    
+    let data = StreamData {
+        format: Default::default(),
+        width,
+        height,
+        latest,
+    };
+    
+    let _listener = stream
+        .add_local_listener_with_user_data(data)
+        .param_changed(|_, data, id, param| {
+            let Some(param) = param else {
+                return;
+            };
+            if id != spa::param::ParamType::Format.as_raw() {
+                return;
+            }
+            
+            if data.format.parse(param).is_ok() {
+                let size = data.format.size();
+                if size.width as usize != data.width || size.height as usize != data.height {
+                    tracing::warn!(
+                        "PipeWire negotiated {}x{} but portal reported {}x{}",
+                        size.width, size.height, data.width, data.height
+                    );
+                }
+            }
+        })
+        .process(|stream, data| {
+            let Some(mut buffer) = stream.dequeue_buffer() else {
+                return;
+            };
+            let datas = buffer.datas_mut();
+            if datas.is_empty() {
+                return;
+            }
+            
+            let chunk = datas[0].chunk();
+            let offset = chunk.offset() as usize;
+            let size = chunk.size() as usize;
+            let stride = chunk.stride() as usize;
+            let row_len = data.width * 4;
+            
+            let Some(bytes) = datas[0].data() else {
+                return;
+            };
+            if stride < row_len || offset + size > bytes.len() || size < stride * data.height {
+                return;
+            }
+            
+            // Drop any row padding so consumers get tightly packed BGRA
+            let bytes = &bytes[offset..offset + size];
+            let mut frame = Vec::with_capacity(row_len * data.height);
+            for row in bytes.chunks(stride).take(data.height) {
+                frame.extend_from_slice(&row[..row_len]);
+            }
+            
+            *data.latest.frame.lock() = Some(frame);
+            data.latest.arrived.notify_all();
+        })
+        .register()?;
+    
+    let format = spa::pod::object!(
+        spa::utils::SpaTypes::ObjectParamFormat,
+        spa::param::ParamType::EnumFormat,
+        spa::pod::property!(
+            spa::param::format::FormatProperties::MediaType,
+            Id,
+            spa::param::format::MediaType::Video
+        ),
+        spa::pod::property!(
+            spa::param::format::FormatProperties::MediaSubtype,
+            Id,
+            spa::param::format::MediaSubtype::Raw
+        ),
+        spa::pod::property!(
+            spa::param::format::FormatProperties::VideoFormat,
+            Choice,
+            Enum,
+            Id,
+            spa::param::video::VideoFormat::BGRx,
+            spa::param::video::VideoFormat::BGRx,
+            spa::param::video::VideoFormat::BGRA,
+        ),
+    );
+    let values: Vec<u8> = spa::pod::serialize::PodSerializer::serialize(
+        io::Cursor::new(Vec::new()),
+        &spa::pod::Value::Object(format),
+    )
+    .map_err(|e| anyhow!("Failed to build PipeWire format: {:?}", e))?
+    .0
+    .into_inner();
+    
+    let mut params = [spa::pod::Pod::from_bytes(&values).context("Invalid PipeWire format pod")?];
+    
+    stream.connect(
+        spa::utils::Direction::Input,
+        Some(node_id),
+        pw::stream::StreamFlags::AUTOCONNECT | pw::stream::StreamFlags::MAP_BUFFERS,
+        &mut params,
+    )?;
+    
+    mainloop.run();
+    
+    Ok(())
+}
@@ -0,0 +1,10 @@
+#[link(name = "ApplicationServices", kind = "framework")]
+extern "C" {
+    fn AXIsProcessTrusted() -> u8;
+}
+
+/// Whether this process has the Accessibility permission. Without it macOS drops the
+/// mouse and keyboard events it posts without any error.
+pub fn is_trusted() -> bool {
+    unsafe { AXIsProcessTrusted() != 0 }
+}
@@ -0,0 +1,113 @@
+use anyhow::{anyhow, Result};
+use core_graphics::display::CGDisplay;
+use std::ffi::c_void;
+use std::io;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use crate::server::vsync::RefreshClock;
+
+type CVDisplayLinkRef = *mut c_void;
+type CVReturn = i32;
+type CVDisplayLinkOutputCallback = unsafe extern "C" fn(
+    link: CVDisplayLinkRef,
+    now: *const c_void,
+    output_time: *const c_void,
+    flags_in: u64,
+    flags_out: *mut u64,
+    context: *mut c_void,
+) -> CVReturn;
+
+const K_CV_RETURN_SUCCESS: CVReturn = 0;
+
+// A display that went to sleep stops its link; capture then carries on without it
+const REFRESH_TIMEOUT: Duration = Duration::from_millis(100);
+
+#[link(name = "CoreVideo", kind = "framework")]
+extern "C" {
+    fn CVDisplayLinkCreateWithCGDisplay(display_id: u32, link_out: *mut CVDisplayLinkRef) -> CVReturn;
+    fn CVDisplayLinkSetOutputCallback(link: CVDisplayLinkRef, callback: CVDisplayLinkOutputCallback, context: *mut c_void) -> CVReturn;
+    fn CVDisplayLinkStart(link: CVDisplayLinkRef) -> CVReturn;
+    fn CVDisplayLinkStop(link: CVDisplayLinkRef) -> CVReturn;
+    fn CVDisplayLinkRelease(link: CVDisplayLinkRef);
+}
+
+// Refreshes counted by the display link's thread
+type Refreshes = (Mutex<u64>, Condvar);
+
+unsafe extern "C" fn on_refresh(
+    _link: CVDisplayLinkRef,
+    _now: *const c_void,
+    _output_time: *const c_void,
+    _flags_in: u64,
+    _flags_out: *mut u64,
+    context: *mut c_void,
+) -> CVReturn {
+    let refreshes = &*(context as *const Refreshes);
+    *refreshes.0.lock().unwrap() += 1;
+    refreshes.1.notify_all();
+    K_CV_RETURN_SUCCESS
+}
+
+/// Follows a display's refreshes through a CVDisplayLink, which calls back on its own
+/// thread once per refresh.
+pub struct DisplayLinkClock {
+    link: CVDisplayLinkRef,
+    // Read by `on_refresh` until the link is stopped in `drop`
+    refreshes: Arc<Refreshes>,
+}
+
+impl DisplayLinkClock {
+    /// A link to display `index`, in the order scrap lists displays.
+    pub fn new(index: usize) -> Result<Self> {
+        let display_id = CGDisplay::active_displays()
+            .ok()
+            .and_then(|ids| ids.get(index).copied())
+            .ok_or_else(|| anyhow!("no display {}", index))?;
+        
+        let refreshes: Arc<Refreshes> = Arc::new((Mutex::new(0), Condvar::new()));
+        let mut link: CVDisplayLinkRef = std::ptr::null_mut();
+        unsafe {
+            let status = CVDisplayLinkCreateWithCGDisplay(display_id, &mut link);
+            if status != K_CV_RETURN_SUCCESS || link.is_null() {
+                return Err(anyhow!("CVDisplayLinkCreateWithCGDisplay failed: {}", status));
+            }
+            // From here `drop` releases the link
+            let clock = Self { link, refreshes };
+            let context = Arc::as_ptr(&clock.refreshes) as *mut c_void;
+            let status = CVDisplayLinkSetOutputCallback(link, on_refresh, context);
+            if status != K_CV_RETURN_SUCCESS {
+                return Err(anyhow!("CVDisplayLinkSetOutputCallback failed: {}", status));
+            }
+            let status = CVDisplayLinkStart(link);
+            if status != K_CV_RETURN_SUCCESS {
+                return Err(anyhow!("CVDisplayLinkStart failed: {}", status));
+            }
+            Ok(clock)
+        }
+    }
+}
+
+impl RefreshClock for DisplayLinkClock {
+    fn wait_for_refresh(&mut self) -> io::Result<()> {
+        let (count, refreshed) = &*self.refreshes;
+        let guard = count.lock().unwrap();
+        let seen = *guard;
+        let _ = refreshed.wait_timeout_while(guard, REFRESH_TIMEOUT, |count| *count == seen).unwrap();
+        Ok(())
+    }
+    
+    fn name(&self) -> &'static str {
+        "CVDisplayLink"
+    }
+}
+
+impl Drop for DisplayLinkClock {
+    fn drop(&mut self) {
+        // Stopping waits for a callback in progress, after which `refreshes` is unused
+        unsafe {
+            CVDisplayLinkStop(self.link);
+            CVDisplayLinkRelease(self.link);
+        }
+    }
+}
@@ -1,3 +1,5 @@
 pub mod video_toolbox;
 // pub mod video_toolbox_fixed;
-// pub use video_toolbox_fixed as video_toolbox;
\ No newline at end of file
+// pub use video_toolbox_fixed as video_toolbox;
+pub mod accessibility;
+pub mod display_link;
@@ -11,7 +11,7 @@ use core_foundation::boolean::CFBoolean;
 use std::ptr;
 use std::slice;
 
-use crate::common::encoder::{VideoEncoder, EncoderType, EncoderSettings, EncodedFrame};
+use crate::common::encoder::{check_frame_len, VideoEncoder, EncoderType, EncoderSettings, EncodedFrame};
 
 #[link(name = "VideoToolbox", kind = "framework")]
 extern "C" {
@@ -176,6 +176,7 @@ impl Drop for VideoToolboxEncoder {
 impl VideoEncoder for VideoToolboxEncoder {
     fn encode_frame(&mut self, rgb_data: &[u8], force_keyframe: bool) -> Result<EncodedFrame> {
         tracing::debug!("VideoToolbox encode_frame called");
+        check_frame_len(rgb_data, self.settings.width, self.settings.height)?;
         
         // Create session on first use
         let needs_init = self.session.lock().is_none();
@@ -0,0 +1,19 @@
+use std::io;
+use windows::Win32::Graphics::Dwm::DwmFlush;
+
+use crate::server::vsync::RefreshClock;
+
+/// Follows the desktop compositor, which presents once per refresh of the primary
+/// display. Composition is always on since Windows 8.
+pub struct DwmClock;
+
+impl RefreshClock for DwmClock {
+    fn wait_for_refresh(&mut self) -> io::Result<()> {
+        // Returns once the compositor has presented its next frame
+        unsafe { DwmFlush() }.map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+    }
+    
+    fn name(&self) -> &'static str {
+        "DWM"
+    }
+}
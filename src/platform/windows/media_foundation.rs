@@ -11,13 +11,52 @@ use windows::{
 use std::sync::Arc;
 use parking_lot::Mutex;
 
-use crate::common::encoder::{VideoEncoder, EncoderType, EncoderSettings, EncodedFrame};
+use crate::common::encoder::{check_frame_len, VideoEncoder, EncoderType, EncoderSettings, EncodedFrame};
+
+// Encoders (and anything else) holding Media Foundation started. MFStartup and MFShutdown
+// must pair up per process, so only the last one out shuts it down.
+static MEDIA_FOUNDATION_USERS: Mutex<usize> = Mutex::new(0);
+
+/// Keeps Media Foundation started for as long as it lives.
+pub struct MediaFoundationRuntime {
+    _private: (),
+}
+
+impl MediaFoundationRuntime {
+    pub fn acquire() -> Result<Self> {
+        let mut users = MEDIA_FOUNDATION_USERS.lock();
+        if *users == 0 {
+            unsafe { MFStartup(MF_VERSION, MFSTARTUP_FULL) }.context("Failed to start Media Foundation")?;
+        }
+        *users += 1;
+        Ok(Self { _private: () })
+    }
+    
+    /// How many holders keep Media Foundation started; 0 once it has been shut down.
+    pub fn users() -> usize {
+        *MEDIA_FOUNDATION_USERS.lock()
+    }
+}
+
+impl Drop for MediaFoundationRuntime {
+    fn drop(&mut self) {
+        let mut users = MEDIA_FOUNDATION_USERS.lock();
+        *users -= 1;
+        if *users == 0 {
+            unsafe {
+                let _ = MFShutdown();
+            }
+        }
+    }
+}
 
 pub struct MediaFoundationEncoder {
     settings: EncoderSettings,
     transform: Option<IMFTransform>,
     frame_count: u64,
     output_buffer: Arc<Mutex<Vec<u8>>>,
+    // Dropped after the transform has been shut down
+    _runtime: MediaFoundationRuntime,
 }
 
 unsafe impl Send for MediaFoundationEncoder {}
@@ -25,109 +64,116 @@ unsafe impl Sync for MediaFoundationEncoder {}
 
 impl MediaFoundationEncoder {
     pub fn new(settings: EncoderSettings) -> Result<Self> {
-        unsafe {
-            // Initialize Media Foundation
-            MFStartup(MF_VERSION, MFSTARTUP_FULL)?;
-            
-            let encoder = Self {
-                settings,
-                transform: None,
-                frame_count: 0,
-                output_buffer: Arc::new(Mutex::new(Vec::with_capacity(1024 * 1024))),
+        let runtime = MediaFoundationRuntime::acquire()?;
+        let transform = create_transform(&settings)?;
+        
+        Ok(Self {
+            settings,
+            transform: Some(transform),
+            frame_count: 0,
+            output_buffer: Arc::new(Mutex::new(Vec::with_capacity(1024 * 1024))),
+            _runtime: runtime,
+        })
+    }
+}
+
+fn create_transform(settings: &EncoderSettings) -> Result<IMFTransform> {
+    unsafe {
+        // Create H.264 encoder
+        let transform: IMFTransform = CoCreateInstance(
+            &CLSID_MSH264EncoderMFT,
+            None,
+            CLSCTX_INPROC_SERVER,
+        )?;
+        
+        // Configure input type (RGB32)
+        let input_type = create_video_type(
+            &MFVideoFormat_RGB32,
+            settings.width,
+            settings.height,
+            settings.fps,
+        )?;
+        
+        transform.SetInputType(0, &input_type, 0)?;
+        
+        // Configure output type (H.264)
+        let output_type = create_video_type(
+            &MFVideoFormat_H264,
+            settings.width,
+            settings.height,
+            settings.fps,
+        )?;
+        
+        // Set bitrate
+        output_type.SetUINT32(&MF_MT_AVG_BITRATE, settings.bitrate)?;
+        
+        transform.SetOutputType(0, &output_type, 0)?;
+        
+        // Set encoder properties for low latency
+        if let Ok(codec_api) = transform.cast::<ICodecAPI>() {
+            // Enable low latency mode
+            let low_latency = VARIANT {
+                Anonymous: VARIANT_0 {
+                    vt: VT_BOOL,
+                    wReserved1: 0,
+                    wReserved2: 0,
+                    wReserved3: 0,
+                    Anonymous: VARIANT_0_0 {
+                        boolVal: VARIANT_TRUE,
+                    },
+                },
             };
+            let _ = codec_api.SetValue(&CODECAPI_AVLowLatencyMode, &low_latency);
             
-            encoder.create_encoder()?;
-            Ok(encoder)
+            // Set rate control mode
+            let rate_control = VARIANT {
+                Anonymous: VARIANT_0 {
+                    vt: VT_UI4,
+                    wReserved1: 0,
+                    wReserved2: 0,
+                    wReserved3: 0,
+                    Anonymous: VARIANT_0_0 {
+                        ulVal: 3, // CBR
+                    },
+                },
+            };
+            let _ = codec_api.SetValue(&CODECAPI_AVEncCommonRateControlMode, &rate_control);
         }
+        
+        // Start the encoder
+        transform.ProcessMessage(MFT_MESSAGE_NOTIFY_BEGIN_STREAMING, 0)?;
+        transform.ProcessMessage(MFT_MESSAGE_NOTIFY_START_OF_STREAM, 0)?;
+        
+        Ok(transform)
     }
-    
-    fn create_encoder(&self) -> Result<()> {
-        unsafe {
-            // Create H.264 encoder
-            let transform: IMFTransform = CoCreateInstance(
-                &CLSID_MSH264EncoderMFT,
-                None,
-                CLSCTX_INPROC_SERVER,
-            )?;
-            
-            // Configure input type (RGB32)
-            let input_type = create_video_type(
-                &MFVideoFormat_RGB32,
-                self.settings.width,
-                self.settings.height,
-                self.settings.fps,
-            )?;
-            
-            transform.SetInputType(0, &input_type, 0)?;
-            
-            // Configure output type (H.264)
-            let output_type = create_video_type(
-                &MFVideoFormat_H264,
-                self.settings.width,
-                self.settings.height,
-                self.settings.fps,
-            )?;
-            
-            // Set bitrate
-            output_type.SetUINT32(&MF_MT_AVG_BITRATE, self.settings.bitrate)?;
-            
-            transform.SetOutputType(0, &output_type, 0)?;
-            
-            // Set encoder properties for low latency
-            if let Ok(codec_api) = transform.cast::<ICodecAPI>() {
-                // Enable low latency mode
-                let low_latency = VARIANT {
-                    Anonymous: VARIANT_0 {
-                        vt: VT_BOOL,
-                        wReserved1: 0,
-                        wReserved2: 0,
-                        wReserved3: 0,
-                        Anonymous: VARIANT_0_0 {
-                            boolVal: VARIANT_TRUE,
-                        },
-                    },
-                };
-                let _ = codec_api.SetValue(&CODECAPI_AVLowLatencyMode, &low_latency);
-                
-                // Set rate control mode
-                let rate_control = VARIANT {
-                    Anonymous: VARIANT_0 {
-                        vt: VT_UI4,
-                        wReserved1: 0,
-                        wReserved2: 0,
-                        wReserved3: 0,
-                        Anonymous: VARIANT_0_0 {
-                            ulVal: 3, // CBR
-                        },
-                    },
-                };
-                let _ = codec_api.SetValue(&CODECAPI_AVEncCommonRateControlMode, &rate_control);
-            }
-            
-            // Start the encoder
-            transform.ProcessMessage(MFT_MESSAGE_NOTIFY_BEGIN_STREAMING, 0)?;
-            transform.ProcessMessage(MFT_MESSAGE_NOTIFY_START_OF_STREAM, 0)?;
-            
-            *self.transform.lock() = Some(transform);
-            Ok(())
+}
+
+// End the transform's stream, drop whatever it still holds and release it. Frames it
+// buffered were for settings that no longer apply, so nothing is drained out.
+fn shutdown_transform(transform: IMFTransform) {
+    unsafe {
+        let _ = transform.ProcessMessage(MFT_MESSAGE_NOTIFY_END_OF_STREAM, 0);
+        let _ = transform.ProcessMessage(MFT_MESSAGE_COMMAND_FLUSH, 0);
+        let _ = transform.ProcessMessage(MFT_MESSAGE_NOTIFY_END_STREAMING, 0);
+        // Asynchronous (hardware) transforms also hold worker threads until shut down
+        if let Ok(shutdown) = transform.cast::<IMFShutdown>() {
+            let _ = shutdown.Shutdown();
         }
     }
 }
 
 impl Drop for MediaFoundationEncoder {
     fn drop(&mut self) {
-        unsafe {
-            if let Some(transform) = self.transform.take() {
-                let _ = transform.ProcessMessage(MFT_MESSAGE_NOTIFY_END_OF_STREAM, 0);
-                let _ = transform.ProcessMessage(MFT_MESSAGE_COMMAND_FLUSH, 0);
-            }
-            let _ = MFShutdown();
+        if let Some(transform) = self.transform.take() {
+            shutdown_transform(transform);
         }
     }
 }
 
 impl VideoEncoder for MediaFoundationEncoder {
     fn encode_frame(&mut self, rgb_data: &[u8], force_keyframe: bool) -> Result<EncodedFrame> {
+        check_frame_len(rgb_data, self.settings.width, self.settings.height)?;
+        
         let transform = self.transform.as_ref()
             .context("Media Foundation encoder not initialized")?;
         
@@ -218,9 +264,13 @@ impl VideoEncoder for MediaFoundationEncoder {
     
     fn update_settings(&mut self, settings: EncoderSettings) -> Result<()> {
         self.settings = settings;
-        // Recreate encoder with new settings
-        self.transform = None;
-        self.create_encoder()
+        // The old transform is shut down before its replacement takes a hardware session
+        if let Some(transform) = self.transform.take() {
+            shutdown_transform(transform);
+        }
+        self.frame_count = 0;
+        self.transform = Some(create_transform(&self.settings)?);
+        Ok(())
     }
 }
 
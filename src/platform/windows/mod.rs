@@ -1 +1,4 @@
-pub mod media_foundation;
\ No newline at end of file
+pub mod media_foundation;
+pub mod sas;
+pub mod dwm;
+pub mod touch;
\ No newline at end of file
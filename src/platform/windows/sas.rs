@@ -0,0 +1,27 @@
+use anyhow::{Result, anyhow};
+use windows::{
+    core::{s, w},
+    Win32::System::LibraryLoader::{GetProcAddress, LoadLibraryW},
+};
+
+type SendSasFn = unsafe extern "system" fn(as_user: i32);
+
+/// Trigger the Secure Attention Sequence (Ctrl+Alt+Del).
+///
+/// Windows never accepts SAS as injected keystrokes. `SendSAS` only takes effect when the
+/// caller runs as LocalSystem and the SoftwareSASGeneration policy allows services;
+/// otherwise the call is silently ignored.
+pub fn send_sas() -> Result<()> {
+    unsafe {
+        let module = LoadLibraryW(w!("sas.dll"))
+            .map_err(|e| anyhow!("sas.dll is not available: {}", e))?;
+        let proc = GetProcAddress(module, s!("SendSAS"))
+            .ok_or_else(|| anyhow!("SendSAS not found in sas.dll"))?;
+        let send_sas: SendSasFn = std::mem::transmute(proc);
+        
+        // FALSE: we are a service, not an interactive user application
+        send_sas(0);
+    }
+    
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use std::sync::OnceLock;
+use anyhow::Result;
+use windows::Win32::{
+    Foundation::{POINT, RECT},
+    UI::Input::Pointer::{
+        InitializeTouchInjection, InjectTouchInput, POINTER_INFO, POINTER_TOUCH_INFO,
+        POINTER_FLAG_CANCELED, POINTER_FLAG_DOWN, POINTER_FLAG_INCONTACT, POINTER_FLAG_INRANGE,
+        POINTER_FLAG_UP, POINTER_FLAG_UPDATE, TOUCH_FEEDBACK_DEFAULT,
+    },
+    UI::WindowsAndMessaging::{PT_TOUCH, TOUCH_MASK_CONTACTAREA, TOUCH_MASK_PRESSURE},
+};
+
+use crate::common::protocol::TouchPhase;
+
+// Windows takes pressure from 0 to 1024
+const MAX_PRESSURE: f32 = 1024.0;
+
+// Half the width of the contact area of an injected finger, in pixels
+const CONTACT_RADIUS: i32 = 2;
+
+/// Whether touch can be injected on this machine (Windows 8 and later). Set up once per
+/// process, for the single contact clients send at a time.
+pub fn touch_injection_available() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| {
+        let available = unsafe { InitializeTouchInjection(1, TOUCH_FEEDBACK_DEFAULT) };
+        if let Err(e) = &available {
+            tracing::info!("Touch injection is not available, playing touches as the mouse: {}", e);
+        }
+        available.is_ok()
+    })
+}
+
+/// Inject the touch contact at desktop pixel (x, y). Clients send two fingers as gestures,
+/// so there is never more than one contact down.
+pub fn inject_touch(phase: TouchPhase, x: i32, y: i32, pressure: Option<f32>) -> Result<()> {
+    let flags = match phase {
+        TouchPhase::Start => POINTER_FLAG_DOWN | POINTER_FLAG_INRANGE | POINTER_FLAG_INCONTACT,
+        TouchPhase::Move => POINTER_FLAG_UPDATE | POINTER_FLAG_INRANGE | POINTER_FLAG_INCONTACT,
+        TouchPhase::End => POINTER_FLAG_UP,
+        TouchPhase::Cancel => POINTER_FLAG_UP | POINTER_FLAG_CANCELED,
+    };
+    let mask = match pressure {
+        Some(_) => TOUCH_MASK_CONTACTAREA | TOUCH_MASK_PRESSURE,
+        None => TOUCH_MASK_CONTACTAREA,
+    };
+    
+    let contact = POINTER_TOUCH_INFO {
+        pointerInfo: POINTER_INFO {
+            pointerType: PT_TOUCH,
+            pointerId: 0,
+            pointerFlags: flags,
+            ptPixelLocation: POINT { x, y },
+            ..Default::default()
+        },
+        touchMask: mask,
+        rcContact: RECT {
+            left: x - CONTACT_RADIUS,
+            top: y - CONTACT_RADIUS,
+            right: x + CONTACT_RADIUS,
+            bottom: y + CONTACT_RADIUS,
+        },
+        pressure: (pressure.unwrap_or(0.0).clamp(0.0, 1.0) * MAX_PRESSURE) as u32,
+        ..Default::default()
+    };
+    unsafe { InjectTouchInput(&[contact])? };
+    Ok(())
+}
@@ -0,0 +1,101 @@
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tokio::sync::{oneshot, Notify};
+
+/// How long a connection waits for the host to answer before it is turned away.
+pub const DEFAULT_APPROVAL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Whether a client that knows the access code also needs the host's consent to connect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ApprovalPolicy {
+    /// Let every authenticated client in, for unattended access
+    #[default]
+    AutoAccept,
+    /// Hold each authenticated client until the host lets it in, turning it away if the
+    /// host hasn't answered within `timeout`
+    Ask { timeout: Duration },
+}
+
+/// What the host made of a connection waiting for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApprovalOutcome {
+    Approved,
+    Rejected,
+    TimedOut,
+}
+
+impl ApprovalOutcome {
+    pub fn description(self) -> &'static str {
+        match self {
+            ApprovalOutcome::Approved => "approved by the host",
+            ApprovalOutcome::Rejected => "declined by the host",
+            ApprovalOutcome::TimedOut => "the host didn't answer in time",
+        }
+    }
+}
+
+/// An authenticated client waiting to be let in, as the host is asked about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionRequest {
+    pub id: u64,
+    pub peer: Option<SocketAddr>,
+    pub requested_at: Instant,
+}
+
+/// Connections waiting for the host. The server adds one per authenticated client while
+/// `ApprovalPolicy::Ask` is set and holds the client back until it is answered; a control
+/// panel or console prompt answers them with `decide`.
+#[derive(Default)]
+pub struct Approvals {
+    pending: Mutex<Vec<(ConnectionRequest, oneshot::Sender<bool>)>>,
+    next_id: AtomicU64,
+    requested: Notify,
+}
+
+impl Approvals {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Ask the host about a client connecting from `peer` and wait for the answer, at
+    /// most `timeout`. A request that is dropped unanswered counts as rejected.
+    pub async fn request(&self, peer: Option<SocketAddr>, timeout: Duration) -> ApprovalOutcome {
+        let (tx, rx) = oneshot::channel();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let request = ConnectionRequest { id, peer, requested_at: Instant::now() };
+        self.pending.lock().unwrap().push((request, tx));
+        self.requested.notify_one();
+        
+        let outcome = match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(true)) => ApprovalOutcome::Approved,
+            Ok(_) => ApprovalOutcome::Rejected,
+            Err(_) => ApprovalOutcome::TimedOut,
+        };
+        self.pending.lock().unwrap().retain(|(request, _)| request.id != id);
+        outcome
+    }
+    
+    /// Connections waiting for an answer, oldest first.
+    pub fn pending(&self) -> Vec<ConnectionRequest> {
+        self.pending.lock().unwrap().iter().map(|(request, _)| request.clone()).collect()
+    }
+    
+    /// Let request `id` in or turn it away. False if it is no longer waiting, because it
+    /// was answered already or timed out.
+    pub fn decide(&self, id: u64, approve: bool) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        let Some(index) = pending.iter().position(|(request, _)| request.id == id) else {
+            return false;
+        };
+        let (_, tx) = pending.remove(index);
+        tx.send(approve).is_ok()
+    }
+    
+    /// Wait until a connection asks to be let in. One that asked while nobody was waiting
+    /// still wakes the next call.
+    pub async fn requested(&self) {
+        self.requested.notified().await;
+    }
+}
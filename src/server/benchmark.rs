@@ -0,0 +1,124 @@
+use anyhow::Result;
+use crate::common::encoder::{EncoderFactory, EncoderType, EncoderSettings, EncoderPreset, EncoderTuning, WebpMode};
+use crate::common::color::ColorSpace;
+use crate::server::frame_source::{FrameSource, TestPatternSource};
+use crate::server::screen_capture::bgra_to_rgb;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct BenchmarkResult {
+    pub encoder: EncoderType,
+    /// Only the FFmpeg encoders honor presets
+    pub preset: Option<EncoderPreset>,
+    /// Only the software encoder has a WebP mode
+    pub webp_mode: Option<WebpMode>,
+    pub frames: usize,
+    /// Frames swallowed before the first packet came out, i.e. the encoder's buffering delay
+    pub delay_frames: usize,
+    pub avg_encode_time: Duration,
+    pub avg_frame_bytes: usize,
+    pub bitrate_mbps: f64,
+}
+
+/// Encode the same synthetic frame sequence with every available encoder.
+pub fn run_encoder_benchmark(width: u32, height: u32, fps: u32, frames: usize) -> Result<Vec<BenchmarkResult>> {
+    // Encoders need even dimensions
+    let width = width & !1;
+    let height = height & !1;
+    
+    let settings = EncoderSettings {
+        width,
+        height,
+        fps,
+        bitrate: 5_000_000,
+        keyframe_interval: fps * 2,
+        color_space: ColorSpace::for_resolution(width, height),
+        tuning: EncoderTuning::default(),
+    };
+    
+    // Generate the frames up front so pattern generation isn't timed
+    let mut source = TestPatternSource::new(width as usize, height as usize);
+    let mut rgb_frames = Vec::with_capacity(frames);
+    for _ in 0..frames {
+        let bgra = source.frame()?;
+        rgb_frames.push(bgra_to_rgb(&bgra, width as usize, height as usize));
+    }
+    
+    // Lossless keeps text sharp; the lossy run shows what that costs
+    let mut runs = vec![
+        (EncoderType::Software, None, Some(WebpMode::Lossless)),
+        (EncoderType::Software, None, Some(WebpMode::Lossy { quality: 85.0 })),
+    ];
+    if EncoderFactory::is_hardware_available() {
+        runs.push((EncoderType::Hardware, Some(EncoderPreset::LowLatency), None));
+        runs.push((EncoderType::Hardware, Some(EncoderPreset::Quality), None));
+    }
+    
+    let mut results = Vec::new();
+    for (encoder_type, preset, webp_mode) in runs {
+        let mut run_settings = settings;
+        if let Some(preset) = preset {
+            run_settings.tuning.preset = preset;
+        }
+        if let Some(webp_mode) = webp_mode {
+            run_settings.tuning.webp_mode = webp_mode;
+        }
+        let mut encoder = EncoderFactory::create_encoder(encoder_type, run_settings)?;
+        
+        let mut total_time = Duration::ZERO;
+        let mut total_bytes = 0usize;
+        let mut delay_frames = 0usize;
+        let mut first_packet_seen = false;
+        
+        for rgb in &rgb_frames {
+            let timer = crate::common::metrics::Timer::start();
+            let encoded = encoder.encode_frame(rgb, false)?;
+            total_time += timer.elapsed();
+            total_bytes += encoded.data.len();
+            
+            if encoded.data.is_empty() && !first_packet_seen {
+                delay_frames += 1;
+            } else {
+                first_packet_seen = true;
+            }
+        }
+        
+        let count = rgb_frames.len().max(1);
+        let stream_seconds = count as f64 / fps.max(1) as f64;
+        
+        results.push(BenchmarkResult {
+            encoder: encoder.get_type(),
+            // A hardware request may have fallen back to software
+            preset: preset.filter(|_| encoder.get_type() == EncoderType::Hardware),
+            webp_mode: webp_mode.filter(|_| encoder.get_type() == EncoderType::Software),
+            frames: rgb_frames.len(),
+            delay_frames,
+            avg_encode_time: total_time / count as u32,
+            avg_frame_bytes: total_bytes / count,
+            bitrate_mbps: (total_bytes as f64 * 8.0) / (stream_seconds * 1_000_000.0),
+        });
+    }
+    
+    Ok(results)
+}
+
+impl std::fmt::Display for BenchmarkResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:<10} {:>12} {:>8} {:>14.2} {:>14} {:>14} {:>14.1}",
+            format!("{:?}", self.encoder),
+            match (self.preset, self.webp_mode) {
+                (Some(preset), _) => format!("{:?}", preset),
+                (None, Some(WebpMode::Lossless)) => "lossless".to_string(),
+                (None, Some(WebpMode::Lossy { quality })) => format!("lossy-{:.0}", quality),
+                (None, None) => "-".to_string(),
+            },
+            self.frames,
+            self.avg_encode_time.as_secs_f64() * 1000.0,
+            self.delay_frames,
+            self.avg_frame_bytes,
+            self.bitrate_mbps,
+        )
+    }
+}
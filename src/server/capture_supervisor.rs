@@ -0,0 +1,79 @@
+use anyhow::Result;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+
+/// Restart delay that doubles after each consecutive failure, up to `max`.
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    next: Duration,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self { initial, max, next: initial }
+    }
+    
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.next;
+        self.next = (self.next * 2).min(self.max);
+        delay
+    }
+    
+    pub fn reset(&mut self) {
+        self.next = self.initial;
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(500), Duration::from_secs(30))
+    }
+}
+
+/// Run a capture loop on the current thread, restarting it with backoff whenever it
+/// returns an error or panics (e.g. when a display is disconnected mid-capture).
+///
+/// `body` should open the capturer and loop; returning `Ok(())` means nobody wants frames
+/// any more and ends supervision. `on_failure` gets a description of every failure and
+/// the delay before the next attempt, so callers can tell clients what happened.
+pub fn run_supervised<F, E>(mut backoff: Backoff, mut body: F, mut on_failure: E)
+where
+    F: FnMut() -> Result<()>,
+    E: FnMut(&str, Duration),
+{
+    let mut restarts = 0u32;
+    
+    loop {
+        let started = Instant::now();
+        let failure = match catch_unwind(AssertUnwindSafe(&mut body)) {
+            Ok(Ok(())) => return,
+            Ok(Err(e)) => format!("Screen capture failed: {:#}", e),
+            Err(panic) => format!("Screen capture panicked: {}", panic_message(panic.as_ref())),
+        };
+        
+        // A loop that ran for a while before failing isn't part of a crash loop
+        if started.elapsed() > backoff.max {
+            backoff.reset();
+        }
+        
+        let delay = backoff.next_delay();
+        restarts += 1;
+        error!("{}; restart {} in {:?}", failure, restarts, delay);
+        on_failure(&failure, delay);
+        
+        std::thread::sleep(delay);
+        info!("Restarting screen capture");
+    }
+}
+
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> &str {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = panic.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
@@ -0,0 +1,123 @@
+use anyhow::{Result, Context, anyhow};
+use serde::Deserialize;
+use std::path::Path;
+use crate::common::auth::AccessCodes;
+
+/// Server settings read from a `--config` file (TOML, or JSON for `.json` files).
+///
+/// Every field is optional and named like its command-line flag (with underscores);
+/// enum-valued fields take the same values as the flags. Flags given on the command
+/// line override the file.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub address: Option<String>,
+    pub transport: Option<String>,
+    pub metrics: Option<bool>,
+    pub metrics_addr: Option<String>,
+    pub metrics_allow_remote: Option<bool>,
+    pub idle_timeout: Option<u64>,
+    pub access_codes: Option<Vec<String>>,
+    pub require_approval: Option<bool>,
+    pub approval_timeout: Option<u64>,
+    pub capture_backend: Option<String>,
+    pub encoder_preset: Option<String>,
+    pub encoder_threads: Option<usize>,
+    pub encode_queue_depth: Option<usize>,
+    pub color_matrix: Option<String>,
+    pub color_range: Option<String>,
+    pub fps: Option<u32>,
+    pub bitrate: Option<u32>,
+    pub max_sessions: Option<usize>,
+    pub min_quality: Option<String>,
+    pub max_quality: Option<String>,
+    pub foveation_radius: Option<u32>,
+    pub cursor_overlay: Option<bool>,
+    pub power_save: Option<String>,
+    pub simulcast: Option<bool>,
+    pub safe_mode: Option<bool>,
+    pub vsync: Option<bool>,
+    pub send_buffer: Option<u32>,
+    pub recv_buffer: Option<u32>,
+    pub forward_notifications: Option<bool>,
+    pub test_pattern: Option<bool>,
+    pub rekey_messages: Option<u64>,
+    pub rekey_bytes: Option<u64>,
+}
+
+impl ServerConfig {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        
+        let is_json = path.extension().map_or(false, |ext| ext.eq_ignore_ascii_case("json"));
+        let config = if is_json {
+            Self::from_json(&contents)
+        } else {
+            Self::from_toml(&contents)
+        };
+        
+        config.with_context(|| format!("Invalid config file {}", path.display()))
+    }
+    
+    pub fn from_toml(contents: &str) -> Result<Self> {
+        let config: Self = toml::from_str(contents).map_err(|e| anyhow!("{}", e.to_string().trim_end()))?;
+        config.validate()?;
+        Ok(config)
+    }
+    
+    pub fn from_json(contents: &str) -> Result<Self> {
+        let config: Self = serde_json::from_str(contents).map_err(|e| anyhow!("{}", e))?;
+        config.validate()?;
+        Ok(config)
+    }
+    
+    /// Range checks serde can't express; errors name the offending field.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(address) = &self.address {
+            address.parse::<std::net::SocketAddr>()
+                .map_err(|_| anyhow!("address: '{}' is not a valid host:port socket address", address))?;
+        }
+        if let Some(addr) = &self.metrics_addr {
+            addr.parse::<std::net::SocketAddr>()
+                .map_err(|_| anyhow!("metrics_addr: '{}' is not a valid host:port socket address", addr))?;
+        }
+        if self.fps == Some(0) || self.fps.map_or(false, |fps| fps > 240) {
+            return Err(anyhow!("fps: must be between 1 and 240, got {}", self.fps.unwrap()));
+        }
+        if self.bitrate == Some(0) {
+            return Err(anyhow!("bitrate: must be greater than 0"));
+        }
+        if self.max_sessions == Some(0) {
+            return Err(anyhow!("max_sessions: must be at least 1"));
+        }
+        if self.foveation_radius == Some(0) {
+            return Err(anyhow!("foveation_radius: must be at least 1 pixel"));
+        }
+        if self.send_buffer == Some(0) {
+            return Err(anyhow!("send_buffer: must be at least 1 byte"));
+        }
+        if self.recv_buffer == Some(0) {
+            return Err(anyhow!("recv_buffer: must be at least 1 byte"));
+        }
+        if self.rekey_messages == Some(0) {
+            return Err(anyhow!("rekey_messages: must be at least 1"));
+        }
+        if self.rekey_bytes == Some(0) {
+            return Err(anyhow!("rekey_bytes: must be at least 1"));
+        }
+        if self.idle_timeout == Some(0) {
+            return Err(anyhow!("idle_timeout: must be at least 1 second"));
+        }
+        if let Some(codes) = &self.access_codes {
+            let mut parsed = AccessCodes::new();
+            for spec in codes {
+                parsed.add_spec(spec).map_err(|e| anyhow!("access_codes: {}", e))?;
+            }
+        }
+        if self.approval_timeout == Some(0) {
+            return Err(anyhow!("approval_timeout: must be at least 1 second"));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,186 @@
+use anyhow::{anyhow, Result};
+
+use crate::common::protocol::{DisplayAction, DisplaySettings, ErrorKind, Message};
+
+/// Sent to a session whose access code doesn't allow changing the host's display.
+pub const DISPLAY_CONTROL_DENIED: &str = "This session's access code doesn't allow controlling the host display";
+
+/// Queries and changes the host's display. One implementation per platform; actions a
+/// platform can't carry out are refused with an error.
+pub trait DisplayController: Send {
+    fn query(&mut self) -> Result<DisplaySettings>;
+    
+    /// `level` is 0.0 to 1.0.
+    fn set_brightness(&mut self, _level: f32) -> Result<()> {
+        Err(anyhow!("Changing the brightness isn't supported on this host"))
+    }
+    
+    fn set_resolution(&mut self, _width: u32, _height: u32) -> Result<()> {
+        Err(anyhow!("Changing the resolution isn't supported on this host"))
+    }
+    
+    fn set_asleep(&mut self, _asleep: bool) -> Result<()> {
+        Err(anyhow!("Putting the display to sleep isn't supported on this host"))
+    }
+}
+
+/// For hosts with no display control; refuses everything.
+pub struct UnsupportedDisplayController;
+
+impl DisplayController for UnsupportedDisplayController {
+    fn query(&mut self) -> Result<DisplaySettings> {
+        Err(anyhow!("Display control is only supported on Linux hosts running X11"))
+    }
+}
+
+/// The host's own display control, where the platform is supported (Linux with X11,
+/// through `xrandr` and `xset`).
+pub fn open_display_controller() -> Box<dyn DisplayController> {
+    #[cfg(target_os = "linux")]
+    {
+        Box::new(linux::XrandrController)
+    }
+    
+    #[cfg(not(target_os = "linux"))]
+    {
+        Box::new(UnsupportedDisplayController)
+    }
+}
+
+/// Carry out `action` for a session, answering with the display's settings after it, or
+/// with an error the client shows when the session isn't `permitted` or the host can't.
+pub fn display_control_reply(controller: &mut dyn DisplayController, permitted: bool, action: DisplayAction) -> Message {
+    if !permitted {
+        return Message::Error { message: DISPLAY_CONTROL_DENIED.to_string(), kind: ErrorKind::Other };
+    }
+    
+    let result = match action {
+        DisplayAction::Query => Ok(()),
+        DisplayAction::SetBrightness { level } if !(0.0..=1.0).contains(&level) => {
+            Err(anyhow!("Brightness must be between 0 and 1, got {}", level))
+        }
+        DisplayAction::SetBrightness { level } => controller.set_brightness(level),
+        DisplayAction::SetResolution { width, height } => controller.set_resolution(width, height),
+        DisplayAction::Sleep => controller.set_asleep(true),
+        DisplayAction::Wake => controller.set_asleep(false),
+    };
+    
+    match result.and_then(|()| controller.query()) {
+        Ok(settings) => Message::DisplayStatus { settings },
+        Err(e) => Message::Error { message: format!("Display control failed: {:#}", e), kind: ErrorKind::Other },
+    }
+}
+
+/// The output to control in `xrandr --verbose --current` output, the primary one or else
+/// the first connected, with its settings.
+pub fn parse_xrandr_verbose(output: &str) -> Result<(String, DisplaySettings)> {
+    let mut chosen: Option<(String, bool, DisplaySettings)> = None;
+    let mut current: Option<(String, bool, DisplaySettings)> = None;
+    
+    for line in output.lines() {
+        // Output headers start at the margin, their properties and modes are indented
+        if !line.starts_with(char::is_whitespace) {
+            finish(current.take(), &mut chosen);
+            let mut words = line.split_whitespace();
+            if let (Some(name), Some("connected")) = (words.next(), words.next()) {
+                current = Some((name.to_string(), line.contains(" primary "), DisplaySettings::default()));
+            }
+            continue;
+        }
+        let Some((_, _, settings)) = current.as_mut() else {
+            continue;
+        };
+        
+        let line = line.trim();
+        if let Some(level) = line.strip_prefix("Brightness:") {
+            settings.brightness = level.trim().parse().ok();
+        } else if let Some((width, height)) = parse_mode(line) {
+            if !settings.modes.contains(&(width, height)) {
+                settings.modes.push((width, height));
+            }
+            if line.contains("*current") {
+                (settings.width, settings.height) = (width, height);
+            }
+        }
+    }
+    finish(current.take(), &mut chosen);
+    
+    let (name, _, mut settings) = chosen.ok_or_else(|| anyhow!("xrandr lists no connected display"))?;
+    settings.modes.sort_by(|a, b| (b.0 * b.1).cmp(&(a.0 * a.1)));
+    Ok((name, settings))
+}
+
+// Keep the first connected output, unless a later one is the primary
+fn finish(block: Option<(String, bool, DisplaySettings)>, chosen: &mut Option<(String, bool, DisplaySettings)>) {
+    if let Some(block) = block {
+        if chosen.as_ref().map_or(true, |(_, primary, _)| !primary && block.1) {
+            *chosen = Some(block);
+        }
+    }
+}
+
+// A mode line, e.g. `1920x1080 (0x48) 141.000MHz -HSync -VSync *current +preferred`
+fn parse_mode(line: &str) -> Option<(u32, u32)> {
+    let mut words = line.split_whitespace();
+    let (size, id) = (words.next()?, words.next()?);
+    if !id.starts_with("(0x") {
+        return None;
+    }
+    let (width, height) = size.split_once('x')?;
+    Some((width.parse().ok()?, height.parse().ok()?))
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::{parse_xrandr_verbose, DisplayController};
+    use crate::common::protocol::DisplaySettings;
+    use anyhow::{bail, Context, Result};
+    use std::process::Command;
+    
+    /// Controls the X11 display through `xrandr` (brightness is applied as a gamma
+    /// scale, not the backlight) and `xset` (DPMS sleep). Wayland sessions have neither.
+    pub struct XrandrController;
+    
+    impl XrandrController {
+        fn output(&self) -> Result<(String, DisplaySettings)> {
+            parse_xrandr_verbose(&run("xrandr", &["--verbose", "--current"])?)
+        }
+    }
+    
+    fn run(program: &str, args: &[&str]) -> Result<String> {
+        let output = Command::new(program)
+            .args(args)
+            .output()
+            .with_context(|| format!("Failed to start {} (is it installed?)", program))?;
+        if !output.status.success() {
+            bail!("{} failed: {}", program, String::from_utf8_lossy(&output.stderr).trim());
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+    
+    impl DisplayController for XrandrController {
+        fn query(&mut self) -> Result<DisplaySettings> {
+            Ok(self.output()?.1)
+        }
+        
+        fn set_brightness(&mut self, level: f32) -> Result<()> {
+            let (name, _) = self.output()?;
+            run("xrandr", &["--output", &name, "--brightness", &format!("{:.2}", level)])?;
+            Ok(())
+        }
+        
+        fn set_resolution(&mut self, width: u32, height: u32) -> Result<()> {
+            let (name, settings) = self.output()?;
+            if !settings.modes.contains(&(width, height)) {
+                bail!("{} has no {}x{} mode", name, width, height);
+            }
+            run("xrandr", &["--output", &name, "--mode", &format!("{}x{}", width, height)])?;
+            Ok(())
+        }
+        
+        fn set_asleep(&mut self, asleep: bool) -> Result<()> {
+            run("xset", &["dpms", "force", if asleep { "off" } else { "on" }])?;
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How long a frame may go unacknowledged before it is taken as lost, so frames the send
+/// queue dropped (and the client never saw) can't hold the window shut for good.
+pub const FRAME_ACK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Frames sent to one client that it hasn't acknowledged yet. A client that decodes
+/// slower than the server captures stops acking in time, the window fills up and the
+/// server waits for it instead of piling frames into its buffers.
+///
+/// Frames are known by their timestamp, which `FrameAck` echoes. Acks are cumulative:
+/// the client skips frames when it falls behind and only acks the ones it shows, so an
+/// ack also covers every frame sent before the one it names.
+#[derive(Debug, Default)]
+pub struct FrameWindow {
+    in_flight: VecDeque<(u64, Instant)>,
+}
+
+impl FrameWindow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// A frame with `timestamp` went out at `now`.
+    pub fn sent(&mut self, timestamp: u64, now: Instant) {
+        self.in_flight.push_back((timestamp, now));
+    }
+    
+    /// The client acknowledged the frame with `timestamp`, and with it every earlier one.
+    /// False if no such frame is in flight, e.g. because it already timed out.
+    pub fn acked(&mut self, timestamp: u64) -> bool {
+        match self.in_flight.iter().position(|&(sent, _)| sent == timestamp) {
+            Some(index) => {
+                self.in_flight.drain(..=index);
+                true
+            }
+            None => false,
+        }
+    }
+    
+    /// Whether another frame may be sent while at most `limit` may be in flight. Frames
+    /// older than `FRAME_ACK_TIMEOUT` are given up on first.
+    pub fn is_open(&mut self, limit: usize, now: Instant) -> bool {
+        while self.in_flight.front().is_some_and(|&(_, sent)| now.duration_since(sent) >= FRAME_ACK_TIMEOUT) {
+            self.in_flight.pop_front();
+        }
+        self.in_flight.len() < limit
+    }
+    
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+}
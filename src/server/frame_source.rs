@@ -0,0 +1,421 @@
+use anyhow::{Result, Context};
+use scrap::{Capturer, Display};
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::common::display_layout::{DisplayLayout, MonitorRect};
+use crate::server::pixel_format::PixelFormat;
+
+/// A source of raw screen frames, BGRA unless `pixel_format` says otherwise.
+pub trait FrameSource {
+    /// Return the next tightly packed frame, or `WouldBlock` if none is ready yet.
+    fn frame(&mut self) -> io::Result<Vec<u8>>;
+    
+    /// Like `frame`, but into `buffer`, replacing what it held. Backends that can copy
+    /// straight into it reuse its allocation from one frame to the next.
+    fn frame_into(&mut self, buffer: &mut Vec<u8>) -> io::Result<()> {
+        *buffer = self.frame()?;
+        Ok(())
+    }
+    
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+    
+    /// Whether frames have the mouse cursor drawn into them.
+    fn includes_cursor(&self) -> bool {
+        true
+    }
+    
+    /// Ask for frames with or without the cursor. Returns false when the backend
+    /// can't change it, in which case `includes_cursor` still tells what frames hold.
+    fn set_include_cursor(&mut self, include: bool) -> bool {
+        include == self.includes_cursor()
+    }
+    
+    /// Captured pixels per unit of the coordinates the OS takes input in: 2.0 on a
+    /// Retina display, whose frames are twice the size of its logical point space.
+    fn scale_factor(&self) -> f64 {
+        1.0
+    }
+    
+    /// How frames lay out their pixels. Backends that can't tell report BGRA8, and the
+    /// frame size then gives HDR frames away.
+    fn pixel_format(&self) -> PixelFormat {
+        PixelFormat::Bgra8
+    }
+    
+    /// Whether `wait_for_damage` really waits for the screen to change. Sources that
+    /// can't tell are polled for frames on a timer instead.
+    fn reports_damage(&self) -> bool {
+        false
+    }
+    
+    /// Block until part of the screen changes or `timeout` passes, and return whether it
+    /// changed. Sources that can't tell report a change straight away.
+    fn wait_for_damage(&mut self, _timeout: Duration) -> io::Result<bool> {
+        Ok(true)
+    }
+}
+
+/// Opens the frame source for a display index; lets the server run on synthetic sources.
+pub type FrameSourceFactory = Arc<dyn Fn(usize) -> Result<Box<dyn FrameSource>> + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureBackend {
+    Auto,     // PipeWire on Wayland sessions, scrap everywhere else
+    Scrap,    // X11 / DXGI / CoreGraphics via scrap
+    PipeWire, // xdg-desktop-portal + PipeWire (Linux Wayland)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionType {
+    X11,
+    Wayland,
+    Unknown,
+}
+
+pub fn detect_session_type() -> SessionType {
+    match std::env::var("XDG_SESSION_TYPE").as_deref() {
+        Ok("wayland") => return SessionType::Wayland,
+        Ok("x11") => return SessionType::X11,
+        _ => {}
+    }
+    
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        SessionType::Wayland
+    } else if std::env::var_os("DISPLAY").is_some() {
+        SessionType::X11
+    } else {
+        SessionType::Unknown
+    }
+}
+
+/// Reported when the host has nothing to capture.
+pub const NO_DISPLAY_HINT: &str = "no display found; attach a monitor, start a virtual display (e.g. Xvfb), or use --test-pattern";
+
+fn host_session_type() -> SessionType {
+    if cfg!(target_os = "linux") {
+        detect_session_type()
+    } else {
+        SessionType::Unknown
+    }
+}
+
+fn resolve_backend(backend: CaptureBackend, session_type: SessionType) -> CaptureBackend {
+    match backend {
+        CaptureBackend::Auto if session_type == SessionType::Wayland => CaptureBackend::PipeWire,
+        CaptureBackend::Auto => CaptureBackend::Scrap,
+        other => other,
+    }
+}
+
+/// Fail with `NO_DISPLAY_HINT` when `backend` has no display to capture, so a headless
+/// server refuses to start instead of losing its capture thread on the first session.
+pub fn check_display_available(backend: CaptureBackend) -> Result<()> {
+    let session_type = host_session_type();
+    // Without DISPLAY or WAYLAND_DISPLAY there is no X server for scrap to ask
+    if cfg!(target_os = "linux") && session_type == SessionType::Unknown {
+        return Err(anyhow::anyhow!(NO_DISPLAY_HINT));
+    }
+    check_displays(resolve_backend(backend, session_type), || Display::all().map(|displays| displays.len()))
+}
+
+/// The check behind `check_display_available`, with display enumeration passed in.
+/// PipeWire is let through: only the portal knows its monitors, and asking would prompt the user.
+pub fn check_displays(backend: CaptureBackend, count_displays: impl FnOnce() -> io::Result<usize>) -> Result<()> {
+    if backend == CaptureBackend::PipeWire {
+        return Ok(());
+    }
+    match count_displays() {
+        Ok(0) => Err(anyhow::anyhow!(NO_DISPLAY_HINT)),
+        Ok(_) => Ok(()),
+        Err(e) => Err(anyhow::Error::new(e).context(NO_DISPLAY_HINT)),
+    }
+}
+
+/// Open display `display_index` (an index into the host's displays, 0 being the first) with `backend`.
+pub fn open_frame_source(backend: CaptureBackend, display_index: usize) -> Result<Box<dyn FrameSource>> {
+    let session_type = host_session_type();
+    let backend = resolve_backend(backend, session_type);
+    
+    tracing::info!("Using {:?} capture backend for display {} (session type: {:?})", backend, display_index, session_type);
+    
+    match backend {
+        // The portal asks the user which monitor to share, there is nothing to index
+        CaptureBackend::PipeWire if display_index != 0 => Err(anyhow::anyhow!(
+            "PipeWire capture streams the monitor picked in the screen-capture portal; select display 0"
+        )),
+        CaptureBackend::PipeWire => open_pipewire_source(),
+        _ => match ScrapSource::new(display_index) {
+            Ok(source) => Ok(Box::new(source)),
+            Err(e) if session_type == SessionType::Wayland => Err(e.context(
+                "scrap capture does not work on most Wayland sessions; \
+                 use --capture-backend pipewire and grant the screen-capture portal permission",
+            )),
+            Err(e) => Err(e),
+        },
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "wayland"))]
+fn open_pipewire_source() -> Result<Box<dyn FrameSource>> {
+    Ok(Box::new(crate::platform::linux::wayland_capture::PipeWireCapture::new()?))
+}
+
+#[cfg(not(all(target_os = "linux", feature = "wayland")))]
+fn open_pipewire_source() -> Result<Box<dyn FrameSource>> {
+    Err(anyhow::anyhow!(
+        "PipeWire capture requires a Linux build with the `wayland` feature \
+         (cargo build --features wayland)"
+    ))
+}
+
+pub struct ScrapSource {
+    capturer: Capturer,
+    width: usize,
+    height: usize,
+    scale_factor: f64,
+}
+
+impl ScrapSource {
+    /// Capture display `index`, in the order the platform lists displays (the main
+    /// display comes first on Windows and macOS).
+    pub fn new(index: usize) -> Result<Self> {
+        let displays = Display::all().context(NO_DISPLAY_HINT)?;
+        let count = displays.len();
+        if count == 0 {
+            return Err(anyhow::anyhow!(NO_DISPLAY_HINT));
+        }
+        let display = displays
+            .into_iter()
+            .nth(index)
+            .with_context(|| format!("No display {} (this host has {})", index, count))?;
+        
+        let capturer = Capturer::new(display)
+            .context("Failed to create screen capturer")?;
+        
+        let width = capturer.width();
+        let height = capturer.height();
+        let scale_factor = display_scale_factor(index, width);
+        if scale_factor != 1.0 {
+            tracing::info!("Display {} is scaled {}x, input coordinates will be converted", index, scale_factor);
+        }
+        
+        Ok(Self { capturer, width, height, scale_factor })
+    }
+}
+
+/// CGDisplayStream captures physical pixels while input is placed in points, so divide
+/// the captured width by the display's width in points. scrap lists displays in the
+/// same order as the active display list.
+#[cfg(target_os = "macos")]
+fn display_scale_factor(index: usize, captured_width: usize) -> f64 {
+    use core_graphics::display::CGDisplay;
+    
+    let points = CGDisplay::active_displays()
+        .ok()
+        .and_then(|ids| ids.get(index).copied())
+        .map(|id| CGDisplay::new(id).bounds().size.width)
+        .unwrap_or(0.0);
+    if points > 0.0 {
+        captured_width as f64 / points
+    } else {
+        1.0
+    }
+}
+
+// X11 and DXGI capture in the same pixels input is placed in
+#[cfg(not(target_os = "macos"))]
+fn display_scale_factor(_index: usize, _captured_width: usize) -> f64 {
+    1.0
+}
+
+/// Where each display scrap lists sits on the desktop, for mapping input across monitors.
+/// Empty when the displays can't be listed.
+#[cfg(target_os = "macos")]
+pub fn detect_display_layout() -> DisplayLayout {
+    use core_graphics::display::CGDisplay;
+    
+    // Global display coordinates are points, with the main display's top-left at 0,0
+    let monitors = CGDisplay::active_displays()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|id| {
+            let bounds = CGDisplay::new(id).bounds();
+            MonitorRect::new(
+                bounds.origin.x.round() as i32,
+                bounds.origin.y.round() as i32,
+                bounds.size.width.round() as u32,
+                bounds.size.height.round() as u32,
+            )
+        })
+        .collect();
+    DisplayLayout::new(monitors)
+}
+
+/// Where each display scrap lists sits on the desktop, for mapping input across monitors.
+/// Empty when the displays can't be listed.
+#[cfg(target_os = "windows")]
+pub fn detect_display_layout() -> DisplayLayout {
+    use windows::Win32::Foundation::{BOOL, LPARAM, RECT};
+    use windows::Win32::Graphics::Gdi::{
+        EnumDisplayMonitors, GetMonitorInfoW, HDC, HMONITOR, MONITORINFO, MONITORINFOF_PRIMARY,
+    };
+    
+    unsafe extern "system" fn collect(monitor: HMONITOR, _: HDC, _: *mut RECT, data: LPARAM) -> BOOL {
+        let monitors = &mut *(data.0 as *mut Vec<(bool, MonitorRect)>);
+        let mut info = MONITORINFO { cbSize: std::mem::size_of::<MONITORINFO>() as u32, ..Default::default() };
+        if GetMonitorInfoW(monitor, &mut info).as_bool() {
+            let rect = info.rcMonitor;
+            monitors.push((
+                info.dwFlags & MONITORINFOF_PRIMARY != 0,
+                MonitorRect::new(rect.left, rect.top, (rect.right - rect.left) as u32, (rect.bottom - rect.top) as u32),
+            ));
+        }
+        BOOL(1)
+    }
+    
+    // Virtual-screen coordinates, with the primary monitor's top-left at 0,0
+    let mut monitors: Vec<(bool, MonitorRect)> = Vec::new();
+    unsafe {
+        let _ = EnumDisplayMonitors(HDC::default(), None, Some(collect), LPARAM(&mut monitors as *mut _ as isize));
+    }
+    // scrap puts the primary display first
+    monitors.sort_by_key(|(primary, _)| !primary);
+    DisplayLayout::new(monitors.into_iter().map(|(_, rect)| rect).collect())
+}
+
+/// X11 screens each span the whole desktop, so every display starts at 0,0.
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+pub fn detect_display_layout() -> DisplayLayout {
+    let monitors = Display::all()
+        .unwrap_or_default()
+        .iter()
+        .map(|display| MonitorRect::new(0, 0, display.width() as u32, display.height() as u32))
+        .collect();
+    DisplayLayout::new(monitors)
+}
+
+/// Copy a `width`x`height` BGRA frame from scrap into `buffer` without its row padding.
+/// scrap hands over `height` rows of the display's pitch (DXGI and CoreGraphics pad each
+/// row), so every row keeps only its first `width * 4` bytes. A frame with no padding is
+/// copied whole, which leaves HDR frames of 8 bytes a pixel for `PixelFormat::detect`.
+pub fn copy_unpadded_rows(frame: &[u8], width: usize, height: usize, buffer: &mut Vec<u8>) -> io::Result<()> {
+    buffer.clear();
+    let pixels = width * height;
+    if frame.len() == pixels * 4 || frame.len() == pixels * 8 {
+        buffer.extend_from_slice(frame);
+        return Ok(());
+    }
+    
+    let row = width * 4;
+    let stride = frame.len().checked_div(height).unwrap_or(0);
+    if stride < row || stride * height != frame.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}-byte frame isn't {} rows of at least {} bytes", frame.len(), height, row),
+        ));
+    }
+    buffer.reserve(row * height);
+    for line in frame.chunks_exact(stride) {
+        buffer.extend_from_slice(&line[..row]);
+    }
+    Ok(())
+}
+
+impl FrameSource for ScrapSource {
+    fn frame(&mut self) -> io::Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        self.frame_into(&mut buffer)?;
+        Ok(buffer)
+    }
+    
+    // scrap only lends its frame, so it's copied once, into the caller's buffer
+    fn frame_into(&mut self, buffer: &mut Vec<u8>) -> io::Result<()> {
+        let frame = self.capturer.frame()?;
+        copy_unpadded_rows(&frame, self.width, self.height, buffer)
+    }
+    
+    fn width(&self) -> usize {
+        self.width
+    }
+    
+    fn height(&self) -> usize {
+        self.height
+    }
+    
+    // CGDisplayStream draws the cursor; DXGI duplication and X11 images leave it out.
+    // scrap has no switch for either.
+    fn includes_cursor(&self) -> bool {
+        cfg!(target_os = "macos")
+    }
+    
+    fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+}
+
+/// Synthetic moving pattern, used for benchmarking and testing without a display.
+pub struct TestPatternSource {
+    width: usize,
+    height: usize,
+    frame_index: usize,
+}
+
+impl TestPatternSource {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            frame_index: 0,
+        }
+    }
+}
+
+impl FrameSource for TestPatternSource {
+    fn frame(&mut self) -> io::Result<Vec<u8>> {
+        let mut bgra = Vec::new();
+        self.frame_into(&mut bgra)?;
+        Ok(bgra)
+    }
+    
+    fn frame_into(&mut self, bgra: &mut Vec<u8>) -> io::Result<()> {
+        // Every byte is drawn below, so the previous frame needn't be cleared
+        bgra.resize(self.width * self.height * 4, 0);
+        let t = self.frame_index;
+        self.frame_index += 1;
+        
+        // Scrolling gradient background
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let offset = (y * self.width + x) * 4;
+                bgra[offset] = ((x + t * 4) % 256) as u8;
+                bgra[offset + 1] = ((y + t * 2) % 256) as u8;
+                bgra[offset + 2] = ((x + y) % 256) as u8;
+                bgra[offset + 3] = 255;
+            }
+        }
+        
+        // A solid box moving across the screen
+        let box_size = (self.width.min(self.height) / 8).max(1);
+        let box_x = (t * 8) % self.width.saturating_sub(box_size).max(1);
+        let box_y = (t * 4) % self.height.saturating_sub(box_size).max(1);
+        for y in box_y..(box_y + box_size).min(self.height) {
+            for x in box_x..(box_x + box_size).min(self.width) {
+                let offset = (y * self.width + x) * 4;
+                bgra[offset..offset + 4].copy_from_slice(&[255, 255, 255, 255]);
+            }
+        }
+        
+        Ok(())
+    }
+    
+    fn width(&self) -> usize {
+        self.width
+    }
+    
+    fn height(&self) -> usize {
+        self.height
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Tracks the last input event (mouse/keyboard) of each session so idle sessions
+/// can be disconnected. Video activity is deliberately not counted: a session
+/// only watching the screen is considered idle. View-only sessions aren't
+/// registered, since they have no input to send.
+///
+/// Callers pass the current time in, which keeps the monitor clock-agnostic.
+pub struct IdleMonitor {
+    timeout: Duration,
+    last_input: HashMap<String, Instant>,
+}
+
+impl IdleMonitor {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            last_input: HashMap::new(),
+        }
+    }
+    
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+    
+    pub fn register(&mut self, session_id: &str, now: Instant) {
+        self.last_input.insert(session_id.to_string(), now);
+    }
+    
+    pub fn record_input(&mut self, session_id: &str, now: Instant) {
+        if let Some(last) = self.last_input.get_mut(session_id) {
+            *last = now;
+        }
+    }
+    
+    pub fn remove(&mut self, session_id: &str) {
+        self.last_input.remove(session_id);
+    }
+    
+    pub fn is_tracked(&self, session_id: &str) -> bool {
+        self.last_input.contains_key(session_id)
+    }
+    
+    /// Remove and return every session whose last input is older than the timeout.
+    pub fn take_expired(&mut self, now: Instant) -> Vec<String> {
+        let timeout = self.timeout;
+        let expired: Vec<String> = self.last_input
+            .iter()
+            .filter(|(_, last)| now.saturating_duration_since(**last) > timeout)
+            .map(|(id, _)| id.clone())
+            .collect();
+        
+        for id in &expired {
+            self.last_input.remove(id);
+        }
+        
+        expired
+    }
+}
@@ -1,29 +1,273 @@
-use crate::common::protocol::{MouseButton, Modifiers};
-use enigo::{Enigo, Key, Direction, Coordinate, Button, Settings, Keyboard, Mouse};
+use crate::common::protocol::{Message, MouseButton, Modifiers, TouchGesture, TouchPhase};
+use crate::server::scancode::{native_keycode, USAGE_DELETE};
+use enigo::{Enigo, Key, Direction, Coordinate, Button, Settings, Keyboard, Mouse, Axis};
 use anyhow::Result;
+use std::collections::HashSet;
+
+/// Convert a position in captured pixels to the coordinates input is placed in, given
+/// the display's scale factor (captured pixels per input unit).
+pub fn input_position(x: i32, y: i32, scale_factor: f64) -> (i32, i32) {
+    if scale_factor <= 0.0 || scale_factor == 1.0 {
+        return (x, y);
+    }
+    ((x as f64 / scale_factor).round() as i32, (y as f64 / scale_factor).round() as i32)
+}
+
+/// Remote pixels of two-finger scrolling played as one wheel step
+pub const SCROLL_STEP_PIXELS: f32 = 20.0;
+
+/// Change in the distance between two fingers played as one step of zoom
+pub const ZOOM_STEP: f32 = 0.1;
+
+// Held while scrolling to zoom; apps zoom on Ctrl+wheel, and Cmd+wheel on macOS
+#[cfg(target_os = "macos")]
+const ZOOM_MODIFIER: Key = Key::Meta;
+#[cfg(not(target_os = "macos"))]
+const ZOOM_MODIFIER: Key = Key::Control;
+
+// Cmd+Shift+3 saves a screenshot of the whole screen, what Print Screen does elsewhere
+#[cfg(target_os = "macos")]
+const SCREENSHOT_CHORD: [Key; 3] = [Key::Meta, Key::Shift, Key::Unicode('3')];
+
+/// The mouse events a touch is played as on a host that can't inject touch: the finger
+/// holds the left button down.
+pub fn touch_as_mouse(phase: TouchPhase, x: i32, y: i32) -> Vec<Message> {
+    match phase {
+        TouchPhase::Start => vec![
+            Message::MouseMove { x, y },
+            Message::MouseClick { button: MouseButton::Left, pressed: true, x, y },
+        ],
+        TouchPhase::Move => vec![Message::MouseMove { x, y }],
+        TouchPhase::End | TouchPhase::Cancel => vec![Message::MouseClick { button: MouseButton::Left, pressed: false, x, y }],
+    }
+}
+
+/// Horizontal and vertical wheel steps a gesture is played as, and whether the zoom
+/// modifier is held for them. Content follows the fingers, so dragging up scrolls down,
+/// and spreading them zooms in. Any movement makes at least one step.
+pub fn gesture_wheel_steps(gesture: TouchGesture) -> (i32, i32, bool) {
+    fn steps(amount: f32, per_step: f32) -> i32 {
+        let steps = (amount / per_step).round() as i32;
+        if steps == 0 && amount != 0.0 && amount.is_finite() {
+            amount.signum() as i32
+        } else {
+            steps
+        }
+    }
+    
+    match gesture {
+        TouchGesture::Scroll { delta_x, delta_y } => (
+            steps(-delta_x, SCROLL_STEP_PIXELS),
+            steps(-delta_y, SCROLL_STEP_PIXELS),
+            false,
+        ),
+        TouchGesture::Pinch { scale } if scale > 0.0 => (0, steps(-scale.ln(), ZOOM_STEP.ln_1p()), true),
+        TouchGesture::Pinch { .. } => (0, 0, true),
+    }
+}
+
+/// The enigo key for a key name sent by the client (see `format_key`); anything else
+/// types its first character.
+pub fn named_key(name: &str) -> Option<Key> {
+    Some(match name {
+        "Return" | "Enter" => Key::Return,
+        "Tab" => Key::Tab,
+        "Space" | " " => Key::Space,
+        "Escape" => Key::Escape,
+        "BackSpace" => Key::Backspace,
+        "Up" => Key::UpArrow,
+        "Down" => Key::DownArrow,
+        "Left" => Key::LeftArrow,
+        "Right" => Key::RightArrow,
+        "Delete" => Key::Delete,
+        "Control" | "Ctrl" => Key::Control,
+        "Alt" => Key::Alt,
+        "Shift" => Key::Shift,
+        "Meta" | "Super" => Key::Meta,
+        "F1" => Key::F1,
+        "F2" => Key::F2,
+        "F3" => Key::F3,
+        "F4" => Key::F4,
+        "F5" => Key::F5,
+        "F6" => Key::F6,
+        "F7" => Key::F7,
+        "F8" => Key::F8,
+        "F9" => Key::F9,
+        "F10" => Key::F10,
+        "F11" => Key::F11,
+        "F12" => Key::F12,
+        #[cfg(not(target_os = "macos"))]
+        "Print" => Key::Print,
+        // Macs have no Print Screen key; `key_event` sends `SCREENSHOT_CHORD` instead
+        #[cfg(target_os = "macos")]
+        "Print" => return None,
+        // For single characters, use Unicode
+        _ => Key::Unicode(name.chars().next()?),
+    })
+}
+
+/// Input can't be injected because the server lacks the permission for it, as opposed to
+/// an injection that failed. Returned by `InputHandler::new`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputNotPermitted;
+
+impl std::fmt::Display for InputNotPermitted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", INPUT_PERMISSION_HELP)
+    }
+}
+
+impl std::error::Error for InputNotPermitted {}
+
+/// What the user has to do on the server to allow input, shown by the client.
+#[cfg(target_os = "macos")]
+pub const INPUT_PERMISSION_HELP: &str = "The server isn't allowed to control this Mac's mouse and keyboard. \
+    On the server, open System Settings > Privacy & Security > Accessibility, turn on vox_gui \
+    (or the terminal it runs from), then restart the server.";
+#[cfg(not(target_os = "macos"))]
+pub const INPUT_PERMISSION_HELP: &str = "The server isn't allowed to control this machine's mouse and keyboard.";
+
+/// Whether this machine lets the server inject input. Only macOS asks for a permission;
+/// without it the events are silently dropped, so it is checked up front.
+pub fn input_permitted() -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        crate::platform::macos::accessibility::is_trusted()
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        true
+    }
+}
+
+/// Whether `error` came from a missing input permission rather than a failed injection.
+pub fn is_permission_error(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<InputNotPermitted>().is_some()
+}
+
+/// Whether this system repeats an injected key that is held down, as it does for a real
+/// keyboard. The X server does; Windows and macOS only repeat physical keys.
+pub const SYSTEM_REPEATS_INJECTED_KEYS: bool = cfg!(target_os = "linux");
+
+/// A key a client can hold down, by name or by HID usage.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum HeldKey {
+    Named(String),
+    Physical(u16),
+}
+
+/// The keys one client holds down, deciding which of its key events are injected.
+///
+/// Clients forward their own auto-repeats as presses marked `repeat`. Those are injected
+/// as further presses only where the system wouldn't repeat the held key itself, so a
+/// held key repeats exactly once per step either way. A second press of a key that is
+/// already down is dropped, and releases always go through so nothing stays stuck.
+#[derive(Debug)]
+pub struct HeldKeys {
+    held: HashSet<HeldKey>,
+    system_repeats: bool,
+}
+
+impl HeldKeys {
+    pub fn new() -> Self {
+        Self::with_system_repeats(SYSTEM_REPEATS_INJECTED_KEYS)
+    }
+    
+    /// Keys for a system that does (or doesn't) repeat injected keys on its own.
+    pub fn with_system_repeats(system_repeats: bool) -> Self {
+        Self { held: HashSet::new(), system_repeats }
+    }
+    
+    /// Record a key event and return whether it should be injected.
+    pub fn accept(&mut self, key: HeldKey, pressed: bool, repeat: bool) -> bool {
+        if !pressed {
+            self.held.remove(&key);
+            return true;
+        }
+        // A repeat whose press was lost still presses the key
+        let newly_held = self.held.insert(key);
+        newly_held || (repeat && !self.system_repeats)
+    }
+    
+    /// `accept` for a message; anything other than a key event is always injected.
+    pub fn accept_message(&mut self, message: &Message) -> bool {
+        match message {
+            Message::KeyEvent { key, pressed, repeat, .. } => self.accept(HeldKey::Named(key.clone()), *pressed, *repeat),
+            Message::PhysicalKeyEvent { usage, pressed, repeat, .. } => self.accept(HeldKey::Physical(*usage), *pressed, *repeat),
+            _ => true,
+        }
+    }
+    
+    pub fn is_held(&self, key: &HeldKey) -> bool {
+        self.held.contains(key)
+    }
+    
+    /// Releases for every key still down, e.g. when the client goes away mid-press.
+    pub fn release_all(&mut self) -> Vec<Message> {
+        self.held
+            .drain()
+            .map(|key| match key {
+                HeldKey::Named(key) => Message::KeyEvent { key, pressed: false, repeat: false, modifiers: Modifiers::default() },
+                HeldKey::Physical(usage) => Message::PhysicalKeyEvent { usage, pressed: false, repeat: false, modifiers: Modifiers::default() },
+            })
+            .collect()
+    }
+}
+
+impl Default for HeldKeys {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 pub struct InputHandler {
     enigo: Enigo,
+    scale_factor: f64,
+    origin: (i32, i32),
 }
 
 impl InputHandler {
     pub fn new() -> Result<Self> {
+        if !input_permitted() {
+            return Err(InputNotPermitted.into());
+        }
         let enigo = Enigo::new(&Settings::default())?;
-        Ok(InputHandler { enigo })
+        Ok(InputHandler { enigo, scale_factor: 1.0, origin: (0, 0) })
+    }
+    
+    /// Positions given to this handler are in captured pixels of a display scaled by `scale_factor`.
+    pub fn with_scale_factor(mut self, scale_factor: f64) -> Self {
+        self.scale_factor = scale_factor;
+        self
+    }
+    
+    /// Positions are relative to a display whose top-left is at `origin` on the desktop.
+    pub fn with_origin(mut self, origin: (i32, i32)) -> Self {
+        self.origin = origin;
+        self
     }
     
     pub fn mouse_move(&mut self, x: i32, y: i32) -> Result<()> {
-        self.enigo.move_mouse(x, y, Coordinate::Abs)?;
-        Ok(())
+        self.place_pointer(x, y)
     }
     
     pub fn mouse_click(&mut self, button: MouseButton, pressed: bool, x: i32, y: i32) -> Result<()> {
-        self.enigo.move_mouse(x, y, Coordinate::Abs)?;
+        self.place_pointer(x, y)?;
         
         let enigo_button = match button {
             MouseButton::Left => Button::Left,
             MouseButton::Right => Button::Right,
             MouseButton::Middle => Button::Middle,
+            #[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+            MouseButton::Back => Button::Back,
+            #[cfg(any(target_os = "windows", all(unix, not(target_os = "macos"))))]
+            MouseButton::Forward => Button::Forward,
+            // enigo has no extra buttons on macOS
+            #[cfg(not(any(target_os = "windows", all(unix, not(target_os = "macos")))))]
+            MouseButton::Back | MouseButton::Forward => {
+                tracing::warn!("Ignoring {:?} mouse button, it can't be injected on this platform", button);
+                return Ok(());
+            }
         };
         
         let direction = if pressed {
@@ -37,36 +281,143 @@ impl InputHandler {
         Ok(())
     }
     
-    pub fn key_event(&mut self, key_str: &str, pressed: bool, _modifiers: Modifiers) -> Result<()> {
+    /// A finger or pen at (x, y). Injected as touch where the host supports it (Windows),
+    /// with the pen's pressure; played as the left mouse button elsewhere.
+    pub fn touch(&mut self, phase: TouchPhase, x: i32, y: i32, pressure: Option<f32>) -> Result<()> {
+        #[cfg(target_os = "windows")]
+        if crate::platform::windows::touch::touch_injection_available() {
+            let (x, y) = self.desktop_position(x, y);
+            return crate::platform::windows::touch::inject_touch(phase, x, y, pressure);
+        }
+        #[cfg(not(target_os = "windows"))]
+        let _ = pressure;
+        
+        for event in touch_as_mouse(phase, x, y) {
+            match event {
+                Message::MouseMove { x, y } => self.mouse_move(x, y)?,
+                Message::MouseClick { button, pressed, x, y } => self.mouse_click(button, pressed, x, y)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+    
+    /// A two-finger gesture centered on (x, y), played as wheel steps there so the app
+    /// under the fingers scrolls or zooms.
+    pub fn gesture(&mut self, gesture: TouchGesture, x: i32, y: i32) -> Result<()> {
+        self.place_pointer(x, y)?;
+        
+        let (steps_x, steps_y, zoom) = gesture_wheel_steps(gesture);
+        if zoom {
+            self.enigo.key(ZOOM_MODIFIER, Direction::Press)?;
+        }
+        let scrolled = self.scroll(steps_x, steps_y);
+        if zoom {
+            self.enigo.key(ZOOM_MODIFIER, Direction::Release)?;
+        }
+        scrolled
+    }
+    
+    fn scroll(&mut self, steps_x: i32, steps_y: i32) -> Result<()> {
+        if steps_x != 0 {
+            self.enigo.scroll(steps_x, Axis::Horizontal)?;
+        }
+        if steps_y != 0 {
+            self.enigo.scroll(steps_y, Axis::Vertical)?;
+        }
+        Ok(())
+    }
+    
+    // Where (x, y) on the viewed display lies on the desktop, in input units
+    fn desktop_position(&self, x: i32, y: i32) -> (i32, i32) {
+        let (x, y) = input_position(x, y, self.scale_factor);
+        (x + self.origin.0, y + self.origin.1)
+    }
+    
+    fn place_pointer(&mut self, x: i32, y: i32) -> Result<()> {
+        let (x, y) = self.desktop_position(x, y);
+        
+        // enigo scales absolute moves to the primary monitor on Windows, so other monitors
+        // (at negative coordinates too) are reached through the cursor position instead
+        #[cfg(target_os = "windows")]
+        {
+            let (width, height) = self.enigo.main_display()?;
+            if x < 0 || y < 0 || x >= width || y >= height {
+                unsafe { windows::Win32::UI::WindowsAndMessaging::SetCursorPos(x, y)? };
+                return Ok(());
+            }
+        }
+        
+        self.enigo.move_mouse(x, y, Coordinate::Abs)?;
+        Ok(())
+    }
+    
+    pub fn key_event(&mut self, key_str: &str, pressed: bool, modifiers: Modifiers) -> Result<()> {
+        // Ctrl+Alt+Del can't be injected on Windows, it has to go through SendSAS
+        #[cfg(target_os = "windows")]
+        if key_str == "Delete" && modifiers.ctrl && modifiers.alt {
+            if pressed {
+                crate::platform::windows::sas::send_sas()?;
+            }
+            return Ok(());
+        }
+        #[cfg(not(target_os = "windows"))]
+        let _ = modifiers;
+        
         let direction = if pressed {
             Direction::Press
         } else {
             Direction::Release
         };
         
-        // Map common keys
-        let key = match key_str {
-            "Return" | "Enter" => Key::Return,
-            "Tab" => Key::Tab,
-            "Space" | " " => Key::Space,
-            "Escape" => Key::Escape,
-            "BackSpace" => Key::Backspace,
-            "Up" => Key::UpArrow,
-            "Down" => Key::DownArrow,
-            "Left" => Key::LeftArrow,
-            "Right" => Key::RightArrow,
-            _ => {
-                // For single characters, use Unicode
-                if let Some(ch) = key_str.chars().next() {
-                    Key::Unicode(ch)
-                } else {
-                    return Ok(());
+        #[cfg(target_os = "macos")]
+        if key_str == "Print" {
+            if pressed {
+                for key in SCREENSHOT_CHORD {
+                    self.enigo.key(key, Direction::Press)?;
+                }
+            } else {
+                for key in SCREENSHOT_CHORD.into_iter().rev() {
+                    self.enigo.key(key, Direction::Release)?;
                 }
             }
+            return Ok(());
+        }
+        
+        let Some(key) = named_key(key_str) else {
+            return Ok(());
         };
         
         self.enigo.key(key, direction)?;
         
         Ok(())
     }
-}
\ No newline at end of file
+    
+    /// Press or release the key at the position of HID `usage`, leaving the character it
+    /// types to this machine's keyboard layout.
+    pub fn physical_key_event(&mut self, usage: u16, pressed: bool, modifiers: Modifiers) -> Result<()> {
+        #[cfg(target_os = "windows")]
+        if usage == USAGE_DELETE && modifiers.ctrl && modifiers.alt {
+            if pressed {
+                crate::platform::windows::sas::send_sas()?;
+            }
+            return Ok(());
+        }
+        #[cfg(not(target_os = "windows"))]
+        let _ = (modifiers, USAGE_DELETE);
+        
+        let Some(keycode) = native_keycode(usage) else {
+            tracing::debug!("No keycode for HID usage {:#04x}", usage);
+            return Ok(());
+        };
+        
+        let direction = if pressed {
+            Direction::Press
+        } else {
+            Direction::Release
+        };
+        self.enigo.raw(keycode, direction)?;
+        
+        Ok(())
+    }
+}
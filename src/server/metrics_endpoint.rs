@@ -0,0 +1,89 @@
+use anyhow::{anyhow, Context, Result};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info, warn};
+
+use crate::common::metrics::PerformanceMetrics;
+
+// Scrapers send a short GET; anything bigger or slower is dropped
+const MAX_REQUEST_LEN: usize = 8 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Bind the metrics endpoint. Only loopback addresses are accepted unless `allow_remote`
+/// is set, since the metrics include session ids.
+pub async fn bind(addr: &str, allow_remote: bool) -> Result<TcpListener> {
+    let listener = TcpListener::bind(addr).await
+        .with_context(|| format!("Failed to bind metrics endpoint to {}", addr))?;
+    
+    let local_addr = listener.local_addr()?;
+    if !local_addr.ip().is_loopback() {
+        if !allow_remote {
+            return Err(anyhow!(
+                "Metrics endpoint {} is not a loopback address; pass --metrics-allow-remote to expose it", addr
+            ));
+        }
+        warn!("Metrics endpoint is reachable from other hosts on {}", local_addr);
+    }
+    
+    info!("Serving Prometheus metrics on http://{}/metrics", local_addr);
+    Ok(listener)
+}
+
+/// Answer `GET /metrics` with `metrics` in the Prometheus text format.
+pub async fn serve(listener: TcpListener, metrics: Arc<PerformanceMetrics>) -> Result<()> {
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_request(stream, &metrics).await {
+                debug!("Metrics request from {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+async fn handle_request(mut stream: TcpStream, metrics: &PerformanceMetrics) -> Result<()> {
+    let request_line = tokio::time::timeout(REQUEST_TIMEOUT, read_request_line(&mut stream)).await
+        .map_err(|_| anyhow!("Timed out reading request"))??;
+    
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    // Scrapers may add a query string
+    let path = path.split('?').next().unwrap_or("");
+    
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/metrics") => ("200 OK", "text/plain; version=0.0.4; charset=utf-8", metrics.to_prometheus()),
+        ("GET", _) => ("404 Not Found", "text/plain; charset=utf-8", "Not found, try /metrics\n".to_string()),
+        _ => ("405 Method Not Allowed", "text/plain; charset=utf-8", "Only GET is supported\n".to_string()),
+    };
+    
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, content_type, body.len(), body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+// Read up to the end of the request headers and give back the first line
+async fn read_request_line(stream: &mut TcpStream) -> Result<String> {
+    let mut request = Vec::with_capacity(512);
+    let mut buf = [0u8; 1024];
+    
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        let read = stream.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..read]);
+        if request.len() > MAX_REQUEST_LEN {
+            return Err(anyhow!("Request larger than {} bytes", MAX_REQUEST_LEN));
+        }
+    }
+    
+    let request = String::from_utf8_lossy(&request);
+    Ok(request.lines().next().unwrap_or("").to_string())
+}
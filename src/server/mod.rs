@@ -2,4 +2,21 @@ pub mod screen_capture;
 pub mod input_handler;
 pub mod connection;
 pub mod server;
-// pub mod quic_server; // TODO: Fix rustls/quinn version compatibility
\ No newline at end of file
+pub mod idle;
+pub mod frame_source;
+pub mod benchmark;
+pub mod capture_supervisor;
+pub mod config;
+pub mod metrics_endpoint;
+pub mod send_queue;
+pub mod scancode;
+pub mod panel;
+pub mod notifications;
+pub mod power;
+pub mod simulcast;
+pub mod pixel_format;
+pub mod session;
+pub mod approval;
+pub mod flow_control;
+pub mod vsync;
+pub mod display_control;
\ No newline at end of file
@@ -0,0 +1,207 @@
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Notifications allowed through back to back before the limiter starts dropping them.
+pub const NOTIFICATION_BURST: u32 = 5;
+
+/// Once the burst is used up, one notification is let through per this interval.
+pub const NOTIFICATION_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Longest title or body forwarded; the rest is cut off.
+pub const MAX_NOTIFICATION_CHARS: usize = 500;
+
+/// A notification shown on the host's desktop.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostNotification {
+    pub app: String,
+    pub title: String,
+    pub body: String,
+}
+
+impl HostNotification {
+    /// The same notification with every field cut to `MAX_NOTIFICATION_CHARS`.
+    pub fn truncated(self) -> Self {
+        let cut = |text: String| match text.char_indices().nth(MAX_NOTIFICATION_CHARS) {
+            Some((end, _)) => format!("{}…", &text[..end]),
+            None => text,
+        };
+        Self {
+            app: cut(self.app),
+            title: cut(self.title),
+            body: cut(self.body),
+        }
+    }
+}
+
+/// Where the host's notifications come from. One implementation per platform; the
+/// server reads it on a thread of its own.
+pub trait NotificationSource: Send {
+    /// Block until the host shows another notification; `None` once the source has ended.
+    fn next(&mut self) -> Result<Option<HostNotification>>;
+}
+
+/// Opens the notification source; lets the server forward synthetic notifications.
+pub type NotificationSourceFactory = Arc<dyn Fn() -> Result<Box<dyn NotificationSource>> + Send + Sync>;
+
+/// The host's own notifications, where the platform is supported.
+pub fn open_notification_source() -> Result<Box<dyn NotificationSource>> {
+    #[cfg(target_os = "linux")]
+    {
+        Ok(Box::new(linux::DbusNotificationSource::spawn()?))
+    }
+    
+    #[cfg(not(target_os = "linux"))]
+    {
+        Err(anyhow::anyhow!("Notification forwarding is only supported on Linux"))
+    }
+}
+
+/// Token bucket keeping a notification storm from flooding clients: a burst goes
+/// through, after that one per `NOTIFICATION_INTERVAL`.
+#[derive(Debug)]
+pub struct NotificationLimiter {
+    tokens: f64,
+    last_refill: Instant,
+    suppressed: u64,
+}
+
+impl NotificationLimiter {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            tokens: NOTIFICATION_BURST as f64,
+            last_refill: now,
+            suppressed: 0,
+        }
+    }
+    
+    /// Whether a notification arriving at `now` may be forwarded.
+    pub fn allow(&mut self, now: Instant) -> bool {
+        let refilled = now.saturating_duration_since(self.last_refill).as_secs_f64() / NOTIFICATION_INTERVAL.as_secs_f64();
+        self.tokens = (self.tokens + refilled).min(NOTIFICATION_BURST as f64);
+        self.last_refill = now;
+        
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            self.suppressed += 1;
+            false
+        }
+    }
+    
+    /// Notifications dropped so far.
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed
+    }
+}
+
+/// Reads the output of `dbus-monitor` watching `org.freedesktop.Notifications`, turning
+/// each `Notify` call into a notification. Its arguments are printed one per line:
+/// app name, replaced id, icon, summary and body come first.
+#[derive(Debug, Default)]
+pub struct DbusMonitorParser {
+    // String arguments of the Notify call being read, None outside one
+    strings: Option<Vec<String>>,
+    // A string argument with newlines, continued on the next line
+    partial: Option<String>,
+}
+
+impl DbusMonitorParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Feed one line of output; returns the notification once its body has been read.
+    pub fn line(&mut self, line: &str) -> Option<HostNotification> {
+        // Every message starts unindented, ending whatever was being read
+        if !line.starts_with(' ') && self.partial.is_none() {
+            let is_notify = line.starts_with("method call") && line.contains("member=Notify");
+            self.strings = is_notify.then(Vec::new);
+            return None;
+        }
+        
+        let strings = self.strings.as_mut()?;
+        let value = if let Some(mut partial) = self.partial.take() {
+            partial.push('\n');
+            partial.push_str(line);
+            partial
+        } else if let Some(rest) = line.trim_start().strip_prefix("string \"") {
+            rest.to_string()
+        } else {
+            return None;
+        };
+        
+        match value.strip_suffix('"') {
+            Some(complete) => strings.push(complete.to_string()),
+            None => {
+                self.partial = Some(value);
+                return None;
+            }
+        }
+        
+        // App name, icon, summary and body; the number between them isn't a string
+        if strings.len() < 4 {
+            return None;
+        }
+        let strings = self.strings.take()?;
+        Some(HostNotification {
+            app: strings[0].clone(),
+            title: strings[2].clone(),
+            body: strings[3].clone(),
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::{DbusMonitorParser, HostNotification, NotificationSource};
+    use anyhow::{Context, Result};
+    use std::io::{BufRead, BufReader, Lines};
+    use std::process::{Child, ChildStdout, Command, Stdio};
+    
+    const MATCH_RULE: &str = "type='method_call',interface='org.freedesktop.Notifications',member='Notify'";
+    
+    /// Watches the session bus for calls to the notification daemon through `dbus-monitor`.
+    pub struct DbusNotificationSource {
+        child: Child,
+        lines: Lines<BufReader<ChildStdout>>,
+        parser: DbusMonitorParser,
+    }
+    
+    impl DbusNotificationSource {
+        pub fn spawn() -> Result<Self> {
+            let mut child = Command::new("dbus-monitor")
+                .args(["--session", MATCH_RULE])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .context("Failed to start dbus-monitor (is it installed?)")?;
+            let stdout = child.stdout.take().context("dbus-monitor has no stdout")?;
+            
+            Ok(Self {
+                child,
+                lines: BufReader::new(stdout).lines(),
+                parser: DbusMonitorParser::new(),
+            })
+        }
+    }
+    
+    impl NotificationSource for DbusNotificationSource {
+        fn next(&mut self) -> Result<Option<HostNotification>> {
+            for line in &mut self.lines {
+                if let Some(notification) = self.parser.line(&line?) {
+                    return Ok(Some(notification));
+                }
+            }
+            Ok(None)
+        }
+    }
+    
+    impl Drop for DbusNotificationSource {
+        fn drop(&mut self) {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
@@ -0,0 +1,388 @@
+use eframe::egui;
+use egui::{CentralPanel, Context, Margin};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::common::auth::AccessCode;
+use crate::common::link::{shareable_address, ConnectionLink};
+use crate::common::qr::QrCode;
+use crate::common::quality::QualityMode;
+use crate::server::approval::ConnectionRequest;
+use crate::server::server::{Server, ServerControl, SessionSummary};
+
+/// How often the panel reads the server's state again.
+pub const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Megabits per second sent to each session, from how far its byte count moved since
+/// the previous sample.
+#[derive(Debug, Default)]
+pub struct BandwidthMeter {
+    last: HashMap<String, (Instant, u64)>,
+}
+
+impl BandwidthMeter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Rates for `sessions` as of `now`; a session seen for the first time reads 0.
+    /// Sessions that are gone are forgotten.
+    pub fn sample(&mut self, sessions: &[SessionSummary], now: Instant) -> HashMap<String, f64> {
+        let mut rates = HashMap::new();
+        let mut last = HashMap::new();
+        for session in sessions {
+            let bytes = session.stats.bytes_sent;
+            let rate = match self.last.get(&session.id) {
+                Some(&(then, previous)) if now > then => {
+                    bytes.saturating_sub(previous) as f64 * 8.0 / 1_000_000.0 / (now - then).as_secs_f64()
+                }
+                _ => 0.0,
+            };
+            rates.insert(session.id.clone(), rate);
+            last.insert(session.id.clone(), (now, bytes));
+        }
+        self.last = last;
+        rates
+    }
+}
+
+// What the refresh task last read from the server
+#[derive(Default)]
+struct Snapshot {
+    access_code: Option<AccessCode>,
+    // With each session's current rate in Mbps
+    sessions: Vec<(SessionSummary, f64)>,
+    // Clients waiting for the host to let them in
+    pending: Vec<ConnectionRequest>,
+    // Set if the server stopped, which only happens when it fails to start
+    error: Option<String>,
+}
+
+/// A window for hosting the server: shows the access code to read out, who is
+/// connected and how much they are getting, and lets the host end sessions, bound the
+/// quality they may use and let in clients waiting for approval.
+pub struct ServerPanel {
+    control: ServerControl,
+    runtime: Arc<tokio::runtime::Runtime>,
+    address: String,
+    // Where clients should connect, for links; differs from `address` for wildcard binds
+    link_address: String,
+    // QR code of the current link, rebuilt when the code changes
+    qr: Option<(String, QrCode)>,
+    snapshot: Arc<Mutex<Snapshot>>,
+    // Bounds as picked in the panel
+    min_quality: QualityMode,
+    max_quality: QualityMode,
+    notice: Option<(String, Instant)>,
+}
+
+impl ServerPanel {
+    /// Start `server` listening on `address` and show a panel for it.
+    pub fn new(cc: &eframe::CreationContext<'_>, server: Server, address: String, runtime: Arc<tokio::runtime::Runtime>) -> Self {
+        crate::client::theme::apply_custom_theme(&cc.egui_ctx);
+        
+        let control = server.control();
+        let (min_quality, max_quality) = control.quality_bounds();
+        let snapshot = Arc::new(Mutex::new(Snapshot::default()));
+        
+        let failed = snapshot.clone();
+        let bind_address = address.clone();
+        runtime.spawn(async move {
+            if let Err(e) = server.run(&bind_address).await {
+                tracing::error!("Server error: {:#}", e);
+                failed.lock().unwrap().error = Some(format!("{:#}", e));
+            }
+        });
+        runtime.spawn(refresh_loop(control.clone(), snapshot.clone(), cc.egui_ctx.clone()));
+        
+        Self {
+            control,
+            runtime,
+            link_address: shareable_address(&address),
+            qr: None,
+            address,
+            snapshot,
+            min_quality,
+            max_quality,
+            notice: None,
+        }
+    }
+    
+    fn regenerate_code(&self) {
+        let control = self.control.clone();
+        let snapshot = self.snapshot.clone();
+        self.runtime.spawn(async move {
+            let code = control.regenerate_access_code().await;
+            snapshot.lock().unwrap().access_code = Some(code);
+        });
+    }
+    
+    fn disconnect(&self, id: String) {
+        self.snapshot.lock().unwrap().sessions.retain(|(session, _)| session.id != id);
+        let control = self.control.clone();
+        self.runtime.spawn(async move {
+            control.disconnect(&id).await;
+        });
+    }
+    
+    fn answer(&self, id: u64, approve: bool) {
+        self.snapshot.lock().unwrap().pending.retain(|request| request.id != id);
+        if !self.control.answer_connection(id, approve) {
+            tracing::debug!("Connection request {} was no longer waiting", id);
+        }
+    }
+    
+    fn apply_quality_bounds(&mut self) {
+        if self.min_quality.bandwidth_requirement() > self.max_quality.bandwidth_requirement() {
+            self.notice = Some(("Minimum quality can't be above the maximum".to_string(), Instant::now()));
+            (self.min_quality, self.max_quality) = self.control.quality_bounds();
+            return;
+        }
+        
+        let control = self.control.clone();
+        let (min, max) = (self.min_quality, self.max_quality);
+        self.runtime.spawn(async move {
+            if let Err(e) = control.set_quality_bounds(min, max).await {
+                tracing::warn!("Failed to set quality bounds: {}", e);
+            }
+        });
+    }
+    
+    fn show_access_code(&mut self, ui: &mut egui::Ui, code: Option<&AccessCode>) {
+        egui::Frame::none()
+            .fill(egui::Color32::from_rgb(35, 40, 50))
+            .rounding(egui::Rounding::same(12.0))
+            .inner_margin(Margin::same(20.0))
+            .show(ui, |ui| {
+                ui.vertical_centered(|ui| {
+                    ui.label(
+                        egui::RichText::new("Access code")
+                            .size(16.0)
+                            .color(egui::Color32::from_rgb(150, 160, 170))
+                    );
+                    
+                    let Some(code) = code else {
+                        ui.label("No access code");
+                        return;
+                    };
+                    
+                    ui.label(egui::RichText::new(&code.code).size(48.0).monospace().strong());
+                    let left = code.time_left();
+                    if left.is_zero() {
+                        ui.colored_label(egui::Color32::from_rgb(255, 88, 88), "Expired, generate a new one");
+                    } else {
+                        ui.label(format!("Expires in {}:{:02}", left.as_secs() / 60, left.as_secs() % 60));
+                    }
+                    
+                    let link = ConnectionLink::new(self.link_address.clone(), code.code.clone()).to_uri();
+                    if self.qr.as_ref().map(|(encoded, _)| encoded) != Some(&link) {
+                        self.qr = match QrCode::encode(link.as_bytes()) {
+                            Ok(qr) => Some((link.clone(), qr)),
+                            Err(e) => {
+                                tracing::warn!("Can't show a QR code for {}: {:#}", link, e);
+                                None
+                            }
+                        };
+                    }
+                    if let Some((_, qr)) = &self.qr {
+                        ui.add_space(8.0);
+                        show_qr(ui, qr, 4.0);
+                    }
+                    ui.label(egui::RichText::new(&link).monospace().small());
+                    
+                    ui.add_space(8.0);
+                    ui.horizontal(|ui| {
+                        if ui.button("📋 Copy").clicked() {
+                            ui.output_mut(|output| output.copied_text = code.code.clone());
+                            self.notice = Some(("Access code copied".to_string(), Instant::now()));
+                        }
+                        if ui.button("🔗 Copy link").on_hover_text("Paste it into a client's server field to fill in both").clicked() {
+                            ui.output_mut(|output| output.copied_text = link.clone());
+                            self.notice = Some(("Connection link copied".to_string(), Instant::now()));
+                        }
+                        if ui.button("🔄 New code").on_hover_text("The current code stops working; connected sessions stay").clicked() {
+                            self.regenerate_code();
+                        }
+                    });
+                });
+            });
+    }
+    
+    fn show_quality_bounds(&mut self, ui: &mut egui::Ui) {
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Quality from");
+            changed |= quality_combo(ui, "min_quality", &mut self.min_quality);
+            ui.label("to");
+            changed |= quality_combo(ui, "max_quality", &mut self.max_quality);
+        });
+        if changed {
+            self.apply_quality_bounds();
+        }
+    }
+    
+    fn show_pending(&mut self, ui: &mut egui::Ui, pending: &[ConnectionRequest]) {
+        let mut answered = None;
+        for request in pending {
+            egui::Frame::none()
+                .fill(egui::Color32::from_rgb(60, 50, 30))
+                .rounding(egui::Rounding::same(8.0))
+                .inner_margin(Margin::same(12.0))
+                .show(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        let peer = request.peer.map_or_else(|| "an unknown address".to_string(), |peer| peer.to_string());
+                        ui.label(format!("Allow a connection from {}?", peer));
+                        ui.label(
+                            egui::RichText::new(format!("waiting {}s", request.requested_at.elapsed().as_secs()))
+                                .color(egui::Color32::from_rgb(150, 160, 170))
+                        );
+                        if ui.button("Allow").clicked() {
+                            answered = Some((request.id, true));
+                        }
+                        if ui.button("Deny").clicked() {
+                            answered = Some((request.id, false));
+                        }
+                    });
+                });
+            ui.add_space(8.0);
+        }
+        if let Some((id, approve)) = answered {
+            self.answer(id, approve);
+        }
+    }
+    
+    fn show_sessions(&mut self, ui: &mut egui::Ui, sessions: &[(SessionSummary, f64)]) {
+        ui.heading(format!("Sessions ({})", sessions.len()));
+        if sessions.is_empty() {
+            ui.label("Nobody is connected");
+            return;
+        }
+        
+        let mut ended = None;
+        egui::Grid::new("sessions").striped(true).num_columns(6).show(ui, |ui| {
+            for header in ["Client", "Display", "Quality", "Mbps", "Frames", ""] {
+                ui.strong(header);
+            }
+            ui.end_row();
+            
+            for (session, mbps) in sessions {
+                ui.label(session.peer.map_or_else(|| session.id.clone(), |peer| peer.to_string()))
+                    .on_hover_text(&session.id);
+                ui.label(session.display.to_string());
+                ui.label(format!("{:?}", session.quality));
+                ui.label(format!("{:.1}", mbps));
+                ui.label(session.stats.frames_sent.to_string());
+                if ui.button("Disconnect").clicked() {
+                    ended = Some(session.id.clone());
+                }
+                ui.end_row();
+            }
+        });
+        
+        if let Some(id) = ended {
+            self.disconnect(id);
+        }
+    }
+}
+
+impl eframe::App for ServerPanel {
+    fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
+        let (code, sessions, pending, error) = {
+            let snapshot = self.snapshot.lock().unwrap();
+            (snapshot.access_code.clone(), snapshot.sessions.clone(), snapshot.pending.clone(), snapshot.error.clone())
+        };
+        
+        CentralPanel::default().show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                ui.add_space(20.0);
+                ui.label(
+                    egui::RichText::new("Vox Remote Desktop Server")
+                        .size(24.0)
+                        .color(egui::Color32::from_rgb(220, 225, 230))
+                );
+                match &error {
+                    Some(error) => ui.colored_label(egui::Color32::from_rgb(255, 88, 88), format!("Server stopped: {}", error)),
+                    None => ui.label(format!("Listening on {}", self.address)),
+                };
+                ui.add_space(16.0);
+                
+                self.show_pending(ui, &pending);
+                self.show_access_code(ui, code.as_ref());
+                ui.add_space(16.0);
+                self.show_quality_bounds(ui);
+            });
+            
+            ui.add_space(16.0);
+            ui.separator();
+            self.show_sessions(ui, &sessions);
+            
+            if let Some((notice, shown_at)) = &self.notice {
+                if shown_at.elapsed() < Duration::from_secs(3) {
+                    ui.add_space(8.0);
+                    ui.label(notice);
+                } else {
+                    self.notice = None;
+                }
+            }
+        });
+    }
+}
+
+/// Paint `qr` with `module` points per module, on white and with the four-module quiet
+/// zone scanners need.
+fn show_qr(ui: &mut egui::Ui, qr: &QrCode, module: f32) {
+    const QUIET_ZONE: usize = 4;
+    let side = (qr.size() + 2 * QUIET_ZONE) as f32 * module;
+    let (rect, _) = ui.allocate_exact_size(egui::Vec2::splat(side), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 0.0, egui::Color32::WHITE);
+    for y in 0..qr.size() {
+        for x in 0..qr.size() {
+            if qr.module(x, y) {
+                let min = rect.min + egui::Vec2::new((x + QUIET_ZONE) as f32, (y + QUIET_ZONE) as f32) * module;
+                painter.rect_filled(egui::Rect::from_min_size(min, egui::Vec2::splat(module)), 0.0, egui::Color32::BLACK);
+            }
+        }
+    }
+}
+
+fn quality_combo(ui: &mut egui::Ui, id: &str, quality: &mut QualityMode) -> bool {
+    let mut changed = false;
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(format!("{:?}", quality))
+        .show_ui(ui, |ui| {
+            for mode in QualityMode::ALL {
+                changed |= ui.selectable_value(quality, mode, format!("{:?}", mode)).changed();
+            }
+        });
+    changed
+}
+
+async fn refresh_loop(control: ServerControl, snapshot: Arc<Mutex<Snapshot>>, ctx: Context) {
+    let mut meter = BandwidthMeter::new();
+    let mut interval = tokio::time::interval(REFRESH_INTERVAL);
+    loop {
+        // A client asking to be let in is shown at once rather than on the next tick
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = control.connection_requested() => {}
+        }
+        let access_code = control.access_code().await;
+        let sessions = control.sessions().await;
+        let rates = meter.sample(&sessions, Instant::now());
+        
+        {
+            let mut snapshot = snapshot.lock().unwrap();
+            snapshot.access_code = access_code;
+            snapshot.pending = control.pending_connections();
+            snapshot.sessions = sessions.into_iter()
+                .map(|session| {
+                    let rate = rates.get(&session.id).copied().unwrap_or(0.0);
+                    (session, rate)
+                })
+                .collect();
+        }
+        // Also keeps the expiry countdown ticking
+        ctx.request_repaint();
+    }
+}
@@ -0,0 +1,139 @@
+use anyhow::{bail, Result};
+use crate::server::screen_capture::bgra_to_rgb_into;
+
+/// Linear values up to this keep their SDR look; brighter HDR highlights roll off
+/// smoothly towards white instead of clipping.
+const TONE_MAP_KNEE: f32 = 0.8;
+
+/// How a capture backend lays out its pixels. SDR desktops come as 8-bit BGRA on every
+/// platform; HDR and wide-gamut ones can come deeper, and reading those as BGRA gives
+/// washed-out or garbled colours.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// 8 bits per channel, in B, G, R, A order
+    Bgra8,
+    /// 10 bits per colour channel and 2 of alpha in a little-endian u32, blue in the
+    /// lowest bits (the 10-bit desktop format of DXGI and CoreGraphics)
+    Bgr10A2,
+    /// Half floats in R, G, B, A order, linear scRGB with 1.0 as SDR white; Windows
+    /// composes HDR desktops in this
+    Rgba16F,
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            PixelFormat::Bgra8 | PixelFormat::Bgr10A2 => 4,
+            PixelFormat::Rgba16F => 8,
+        }
+    }
+    
+    /// Whether values go past SDR white, so converting them needs tone mapping.
+    pub fn is_hdr(self) -> bool {
+        self == PixelFormat::Rgba16F
+    }
+    
+    pub fn name(self) -> &'static str {
+        match self {
+            PixelFormat::Bgra8 => "8-bit BGRA",
+            PixelFormat::Bgr10A2 => "10-bit BGR",
+            PixelFormat::Rgba16F => "16-bit float RGBA (HDR)",
+        }
+    }
+    
+    /// The format of a `len`-byte frame of `width`x`height` pixels from a source that
+    /// declares `declared`. scrap can't tell and always declares BGRA8, so a frame twice
+    /// that size is taken as the half-float HDR format; any other size is refused rather
+    /// than streamed with wrong colours.
+    pub fn detect(declared: PixelFormat, len: usize, width: usize, height: usize) -> Result<Self> {
+        let pixels = width * height;
+        if len == pixels * declared.bytes_per_pixel() {
+            return Ok(declared);
+        }
+        if len == pixels * PixelFormat::Rgba16F.bytes_per_pixel() {
+            return Ok(PixelFormat::Rgba16F);
+        }
+        bail!(
+            "Unsupported pixel format: the display delivered {} bytes for {}x{} pixels, expected {} bytes of {}. \
+             Turning HDR off for this display may help",
+            len, width, height, pixels * declared.bytes_per_pixel(), declared.name()
+        )
+    }
+}
+
+/// Convert a `width`x`height` frame in `format` to packed 8-bit sRGB, tone-mapping HDR
+/// highlights down to SDR.
+pub fn to_rgb(frame: &[u8], width: usize, height: usize, format: PixelFormat) -> Result<Vec<u8>> {
+    let mut rgb = Vec::new();
+    to_rgb_into(frame, width, height, format, &mut rgb)?;
+    Ok(rgb)
+}
+
+/// Like `to_rgb`, but into `rgb`, replacing what it held; a buffer kept from the last
+/// frame is reused without allocating.
+pub fn to_rgb_into(frame: &[u8], width: usize, height: usize, format: PixelFormat, rgb: &mut Vec<u8>) -> Result<()> {
+    let expected = width * height * format.bytes_per_pixel();
+    if frame.len() != expected {
+        bail!("{}x{} {} frame should be {} bytes, got {}", width, height, format.name(), expected, frame.len());
+    }
+    
+    rgb.clear();
+    match format {
+        PixelFormat::Bgra8 => bgra_to_rgb_into(frame, rgb),
+        PixelFormat::Bgr10A2 => rgb.extend(frame
+            .chunks_exact(4)
+            .flat_map(|pixel| {
+                let value = u32::from_le_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+                [ten_to_eight_bits(value >> 20), ten_to_eight_bits(value >> 10), ten_to_eight_bits(value)]
+            })),
+        PixelFormat::Rgba16F => rgb.extend(frame
+            .chunks_exact(8)
+            .flat_map(|pixel| {
+                [0, 2, 4].map(|i| encode_srgb(tone_map(half_to_f32(u16::from_le_bytes([pixel[i], pixel[i + 1]])))))
+            })),
+    }
+    Ok(())
+}
+
+// Rounds to the nearest 8-bit level, so 10-bit white stays 255 and black 0
+fn ten_to_eight_bits(value: u32) -> u8 {
+    (((value & 0x3ff) * 255 + 511) / 1023) as u8
+}
+
+/// Map linear light, where 1.0 is SDR white, into 0.0..=1.0. Values up to the knee are
+/// kept; above it they approach 1.0 without reaching it. Negative values (colours outside
+/// sRGB's gamut) and NaN go to 0.
+pub fn tone_map(linear: f32) -> f32 {
+    if !(linear > 0.0) {
+        return 0.0;
+    }
+    if linear <= TONE_MAP_KNEE {
+        return linear;
+    }
+    let headroom = 1.0 - TONE_MAP_KNEE;
+    TONE_MAP_KNEE + headroom * (1.0 - (-(linear - TONE_MAP_KNEE) / headroom).exp())
+}
+
+/// Linear 0.0..=1.0 to an 8-bit sRGB value.
+pub fn encode_srgb(linear: f32) -> u8 {
+    let linear = linear.clamp(0.0, 1.0);
+    let encoded = if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0).round() as u8
+}
+
+/// An IEEE 754 half-precision float from its bits.
+pub fn half_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = (bits >> 10) & 0x1f;
+    let mantissa = (bits & 0x3ff) as f32;
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        exponent => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent as i32 - 15),
+    }
+}
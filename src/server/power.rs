@@ -0,0 +1,133 @@
+use std::path::Path;
+use std::time::Duration;
+
+use crate::common::encoder::EncoderSettings;
+
+/// Keyframes come this many times less often while saving power; each one is a full
+/// encode of an unchanged screen.
+pub const POWER_SAVE_KEYFRAME_FACTOR: u32 = 2;
+
+/// How often `PowerSaveMode::Auto` checks whether the machine is on battery.
+pub const POWER_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// When capture trades responsiveness on a quiet screen for battery life.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PowerSaveMode {
+    /// Save power while the machine runs on battery
+    #[default]
+    Auto,
+    On,
+    Off,
+}
+
+impl PowerSaveMode {
+    /// Whether power saving applies while the machine draws power from `source`.
+    pub fn active(&self, source: PowerSource) -> bool {
+        match self {
+            PowerSaveMode::Auto => source == PowerSource::Battery,
+            PowerSaveMode::On => true,
+            PowerSaveMode::Off => false,
+        }
+    }
+    
+    /// Whether power saving applies right now, asking the platform only in `Auto`.
+    pub fn is_active(&self) -> bool {
+        match self {
+            PowerSaveMode::Auto => self.active(power_source()),
+            mode => mode.active(PowerSource::Unknown),
+        }
+    }
+}
+
+/// Where the machine draws its power from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerSource {
+    Ac,
+    Battery,
+    /// No battery found, or the platform can't tell; treated like AC
+    Unknown,
+}
+
+/// Encoder settings for saving power: keyframes `POWER_SAVE_KEYFRAME_FACTOR` times less
+/// often, and a hardware encoder allowed to hold a frame back so it can work in batches.
+pub fn power_save_settings(mut settings: EncoderSettings) -> EncoderSettings {
+    settings.keyframe_interval = settings.keyframe_interval.saturating_mul(POWER_SAVE_KEYFRAME_FACTOR);
+    settings.tuning.queue_depth = settings.tuning.queue_depth.max(1);
+    settings
+}
+
+/// Ask the platform whether the machine is on battery.
+pub fn power_source() -> PowerSource {
+    #[cfg(target_os = "linux")]
+    {
+        power_supply_source(Path::new("/sys/class/power_supply"))
+    }
+    #[cfg(target_os = "macos")]
+    {
+        std::process::Command::new("pmset")
+            .args(["-g", "batt"])
+            .output()
+            .ok()
+            .map_or(PowerSource::Unknown, |output| parse_pmset(&String::from_utf8_lossy(&output.stdout)))
+    }
+    #[cfg(target_os = "windows")]
+    {
+        windows_power_source()
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    {
+        PowerSource::Unknown
+    }
+}
+
+/// Read a Linux `power_supply` class directory: on battery when a battery is discharging
+/// and no mains supply is online.
+pub fn power_supply_source(dir: &Path) -> PowerSource {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return PowerSource::Unknown;
+    };
+    
+    let read = |path: &Path, name: &str| std::fs::read_to_string(path.join(name)).map(|s| s.trim().to_string()).unwrap_or_default();
+    let mut discharging = false;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        match read(&path, "type").as_str() {
+            "Mains" | "USB" if read(&path, "online") == "1" => return PowerSource::Ac,
+            "Battery" => discharging |= read(&path, "status") == "Discharging",
+            _ => {}
+        }
+    }
+    if discharging {
+        PowerSource::Battery
+    } else {
+        PowerSource::Unknown
+    }
+}
+
+/// Parse `pmset -g batt`, whose first line names the source ("Now drawing from 'Battery Power'").
+pub fn parse_pmset(output: &str) -> PowerSource {
+    let first = output.lines().next().unwrap_or_default();
+    if first.contains("'Battery Power'") {
+        PowerSource::Battery
+    } else if first.contains("'AC Power'") {
+        PowerSource::Ac
+    } else {
+        PowerSource::Unknown
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn windows_power_source() -> PowerSource {
+    use windows::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+    
+    let mut status = SYSTEM_POWER_STATUS::default();
+    if unsafe { GetSystemPowerStatus(&mut status) }.is_err() {
+        return PowerSource::Unknown;
+    }
+    // ACLineStatus: 0 offline, 1 online, 255 unknown
+    match status.ACLineStatus {
+        0 => PowerSource::Battery,
+        1 => PowerSource::Ac,
+        _ => PowerSource::Unknown,
+    }
+}
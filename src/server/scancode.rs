@@ -0,0 +1,116 @@
+/// Physical keys, by USB HID usage (keyboard page), with the code each platform injects
+/// for the same key position: Windows set 1 scancodes (0xE0xx for extended keys), macOS
+/// virtual keycodes and X11 keycodes (evdev + 8).
+const KEYS: &[(u16, u16, u16, u16)] = &[
+    // Letters
+    (0x04, 0x1E, 0x00, 38), // A
+    (0x05, 0x30, 0x0B, 56), // B
+    (0x06, 0x2E, 0x08, 54), // C
+    (0x07, 0x20, 0x02, 40), // D
+    (0x08, 0x12, 0x0E, 26), // E
+    (0x09, 0x21, 0x03, 41), // F
+    (0x0A, 0x22, 0x05, 42), // G
+    (0x0B, 0x23, 0x04, 43), // H
+    (0x0C, 0x17, 0x22, 31), // I
+    (0x0D, 0x24, 0x26, 44), // J
+    (0x0E, 0x25, 0x28, 45), // K
+    (0x0F, 0x26, 0x25, 46), // L
+    (0x10, 0x32, 0x2E, 58), // M
+    (0x11, 0x31, 0x2D, 57), // N
+    (0x12, 0x18, 0x1F, 32), // O
+    (0x13, 0x19, 0x23, 33), // P
+    (0x14, 0x10, 0x0C, 24), // Q
+    (0x15, 0x13, 0x0F, 27), // R
+    (0x16, 0x1F, 0x01, 39), // S
+    (0x17, 0x14, 0x11, 28), // T
+    (0x18, 0x16, 0x20, 30), // U
+    (0x19, 0x2F, 0x09, 55), // V
+    (0x1A, 0x11, 0x0D, 25), // W
+    (0x1B, 0x2D, 0x07, 53), // X
+    (0x1C, 0x15, 0x10, 29), // Y
+    (0x1D, 0x2C, 0x06, 52), // Z
+    // Digit row
+    (0x1E, 0x02, 0x12, 10), // 1
+    (0x1F, 0x03, 0x13, 11), // 2
+    (0x20, 0x04, 0x14, 12), // 3
+    (0x21, 0x05, 0x15, 13), // 4
+    (0x22, 0x06, 0x17, 14), // 5
+    (0x23, 0x07, 0x16, 15), // 6
+    (0x24, 0x08, 0x1A, 16), // 7
+    (0x25, 0x09, 0x1C, 17), // 8
+    (0x26, 0x0A, 0x19, 18), // 9
+    (0x27, 0x0B, 0x1D, 19), // 0
+    // Editing and whitespace
+    (0x28, 0x1C, 0x24, 36), // Enter
+    (0x29, 0x01, 0x35, 9),  // Escape
+    (0x2A, 0x0E, 0x33, 22), // Backspace
+    (0x2B, 0x0F, 0x30, 23), // Tab
+    (0x2C, 0x39, 0x31, 65), // Space
+    // Punctuation, named for the US layout
+    (0x2D, 0x0C, 0x1B, 20), // -
+    (0x2E, 0x0D, 0x18, 21), // =
+    (0x2F, 0x1A, 0x21, 34), // [
+    (0x30, 0x1B, 0x1E, 35), // ]
+    (0x31, 0x2B, 0x2A, 51), // backslash
+    (0x33, 0x27, 0x29, 47), // ;
+    (0x34, 0x28, 0x27, 48), // '
+    (0x35, 0x29, 0x32, 49), // `
+    (0x36, 0x33, 0x2B, 59), // ,
+    (0x37, 0x34, 0x2F, 60), // .
+    (0x38, 0x35, 0x2C, 61), // /
+    // Function keys
+    (0x3A, 0x3B, 0x7A, 67), // F1
+    (0x3B, 0x3C, 0x78, 68), // F2
+    (0x3C, 0x3D, 0x63, 69), // F3
+    (0x3D, 0x3E, 0x76, 70), // F4
+    (0x3E, 0x3F, 0x60, 71), // F5
+    (0x3F, 0x40, 0x61, 72), // F6
+    (0x40, 0x41, 0x62, 73), // F7
+    (0x41, 0x42, 0x64, 74), // F8
+    (0x42, 0x43, 0x65, 75), // F9
+    (0x43, 0x44, 0x6D, 76), // F10
+    (0x44, 0x57, 0x67, 95), // F11
+    (0x45, 0x58, 0x6F, 96), // F12
+    // Navigation
+    (0x49, 0xE052, 0x72, 118), // Insert (Help on Mac keyboards)
+    (0x4A, 0xE047, 0x73, 110), // Home
+    (0x4B, 0xE049, 0x74, 112), // Page Up
+    (0x4C, 0xE053, 0x75, 119), // Delete
+    (0x4D, 0xE04F, 0x77, 115), // End
+    (0x4E, 0xE051, 0x79, 117), // Page Down
+    (0x4F, 0xE04D, 0x7C, 114), // Right
+    (0x50, 0xE04B, 0x7B, 113), // Left
+    (0x51, 0xE050, 0x7D, 116), // Down
+    (0x52, 0xE048, 0x7E, 111), // Up
+];
+
+/// HID usage of the Delete key, which with Ctrl+Alt is the secure attention sequence on Windows.
+pub const USAGE_DELETE: u16 = 0x4C;
+
+fn lookup(usage: u16) -> Option<&'static (u16, u16, u16, u16)> {
+    KEYS.iter().find(|(key, ..)| *key == usage)
+}
+
+/// Windows set 1 scancode, with extended keys as 0xE0xx (which enigo flags as extended).
+pub fn windows_scancode(usage: u16) -> Option<u16> {
+    lookup(usage).map(|&(_, windows, _, _)| windows)
+}
+
+pub fn macos_keycode(usage: u16) -> Option<u16> {
+    lookup(usage).map(|&(_, _, macos, _)| macos)
+}
+
+pub fn x11_keycode(usage: u16) -> Option<u16> {
+    lookup(usage).map(|&(_, _, _, x11)| x11)
+}
+
+/// The code `enigo`'s raw key injection takes on this platform for `usage`.
+pub fn native_keycode(usage: u16) -> Option<u16> {
+    if cfg!(target_os = "windows") {
+        windows_scancode(usage)
+    } else if cfg!(target_os = "macos") {
+        macos_keycode(usage)
+    } else {
+        x11_keycode(usage)
+    }
+}
@@ -1,39 +1,104 @@
-use scrap::{Capturer, Display};
 use std::io::ErrorKind::WouldBlock;
 use std::time::{Duration, Instant};
-use anyhow::{Result, Context};
+use anyhow::Result;
 use bytes::Bytes;
-use zstd::stream::encode_all;
-use crate::common::quality::QualityMode;
-use crate::common::frame_processor::FrameProcessor;
-use crate::common::encoder::{VideoEncoder, EncoderFactory, EncoderType, EncoderSettings};
-use crate::common::protocol::EncodingType;
+use crate::common::quality::{ContentProfile, QualityMode};
+use crate::common::frame_processor::{FrameProcessor, FrameType, ProcessedFrame};
+use crate::common::foveation::{Foveation, Region};
+use crate::common::adaptive_fps::AdaptiveFps;
+use crate::common::encoder::{select_encoder, VideoEncoder, EncoderFactory, EncoderType, EncoderSettings, EncoderTuning};
+use crate::common::encoder_watchdog::{EncoderBuilder, WatchdogEncoder, DEFAULT_ENCODE_TIMEOUT};
+use crate::common::color::ColorSpace;
+use crate::common::compression::compress_payload;
+use crate::common::checksum::frame_checksum;
+use crate::server::power::{power_save_settings, POWER_SAVE_KEYFRAME_FACTOR};
+use crate::common::protocol::{EncodingType, Message};
+use crate::common::viewport::{scaled_size, viewport_scale};
+use crate::server::frame_source::{FrameSource, CaptureBackend, open_frame_source};
+use crate::server::simulcast::Simulcast;
+use crate::server::pixel_format::{to_rgb_into, PixelFormat};
+
+/// H.264 target bitrate at High quality when none is configured (5 Mbps); other quality
+/// modes scale it by their bandwidth
+pub const DEFAULT_BITRATE: u32 = 5_000_000;
 
 pub struct ScreenCapture {
-    capturer: Capturer,
+    capturer: Box<dyn FrameSource>,
     width: usize,
     height: usize,
     last_frame_time: Instant,
-    frame_interval: Duration,
+    // Slows capture toward 2 FPS while the screen is static (software path only)
+    pacer: AdaptiveFps,
     quality_mode: QualityMode,
     frame_processor: FrameProcessor,
     frame_count: u64,
     video_encoder: Option<Box<dyn VideoEncoder>>,
     use_hardware_encoding: bool,
+    // Cleared when some client can't decode H.264, forcing the software path
+    hardware_allowed: bool,
+    progressive_keyframes: bool,
+    // Reduced color precision away from the cursor (software path only)
+    foveation: Option<Foveation>,
+    // Software path keyframe cadence, in frames; deltas in between
+    keyframe_interval: u32,
+    frames_since_keyframe: u32,
+    // Same cadence in wall-clock time, which still holds while the pacer has slowed capture
+    keyframe_period: Duration,
+    last_keyframe: Instant,
+    keyframe_requested: bool,
+    // Logged once when the backend can't add or remove the cursor as asked
+    cursor_toggle_unsupported: bool,
+    // Configured size, rate and bitrate, before the quality mode and content profile adjust them
+    base_settings: EncoderSettings,
+    content_profile: ContentProfile,
+    // Frames are scaled down by this to fit the viewers' windows, to output_size
+    output_scale: f32,
+    output_size: (u32, u32),
+    // Slower on a quiet screen, fewer keyframes and a deeper encoder queue
+    power_save: bool,
+    // Lower-quality layers asked for, and their encoders while H.264 is in use
+    simulcast_wanted: Vec<QualityMode>,
+    simulcast: Option<Simulcast>,
+    // Layer frames of the last capture, picked up by `capture_layers`
+    layer_frames: Vec<CapturedFrame>,
+    // Pixel format of the last frame, so a switch to HDR is logged once
+    pixel_format: PixelFormat,
+    // In safe mode, the WebP encoder every frame goes through whole, and the last frame
+    // it was given so an unchanged screen isn't sent again
+    safe_encoder: Option<Box<dyn VideoEncoder>>,
+    safe_last_frame: Option<Vec<u8>>,
+    // The frame as captured and as converted to RGB, kept between captures so each frame
+    // is written into the last one's allocations
+    raw_frame: Vec<u8>,
+    rgb_frame: Vec<u8>,
+    // Send each lossless frame's checksum so clients can spot desync (a debugging aid)
+    frame_checksums: bool,
 }
 
 impl ScreenCapture {
     pub fn new(fps: u32) -> Result<Self> {
-        let display = Display::primary()
-            .context("Failed to get primary display")?;
-        
-        let capturer = Capturer::new(display)
-            .context("Failed to create screen capturer")?;
-        
+        Self::with_options(fps, DEFAULT_BITRATE, CaptureBackend::Auto, EncoderTuning::default())
+    }
+    
+    pub fn with_options(fps: u32, bitrate: u32, backend: CaptureBackend, tuning: EncoderTuning) -> Result<Self> {
+        Self::from_source(open_frame_source(backend, 0)?, fps, bitrate, tuning)
+    }
+    
+    pub fn from_source(capturer: Box<dyn FrameSource>, fps: u32, bitrate: u32, tuning: EncoderTuning) -> Result<Self> {
+        Self::build(capturer, fps, bitrate, tuning, false)
+    }
+    
+    /// Like `from_source`, but every frame is a complete software WebP image: no hardware
+    /// encoder (not even probed for), no delta tiles, no foveation or simulcast. Slow, but
+    /// the fewest moving parts, for when the normal pipeline crashes or corrupts frames.
+    pub fn safe_mode(capturer: Box<dyn FrameSource>, fps: u32, bitrate: u32, tuning: EncoderTuning) -> Result<Self> {
+        Self::build(capturer, fps, bitrate, tuning, true)
+    }
+    
+    fn build(capturer: Box<dyn FrameSource>, fps: u32, bitrate: u32, tuning: EncoderTuning, safe_mode: bool) -> Result<Self> {
         let width = capturer.width();
         let height = capturer.height();
         
-        // Try to create hardware encoder
         // Ensure dimensions are valid and even (required for many encoders)
         let encoder_width = (width as u32) & !1;  // Make even
         let encoder_height = (height as u32) & !1; // Make even
@@ -42,217 +107,818 @@ impl ScreenCapture {
             width: encoder_width,
             height: encoder_height,
             fps,
-            bitrate: 5_000_000, // 5 Mbps default
-            keyframe_interval: fps * 2, // Keyframe every 2 seconds
-        };
-        
-        let (video_encoder, use_hardware) = if EncoderFactory::is_hardware_available() {
-            match EncoderFactory::create_encoder(EncoderType::Hardware, encoder_settings) {
-                Ok(encoder) => (Some(encoder), true),
-                Err(_) => (None, false),
-            }
-        } else {
-            (None, false)
+            bitrate,
+            keyframe_interval: encoder_settings_keyframe_interval(fps),
+            color_space: ColorSpace::preferred(tuning.color, encoder_width, encoder_height),
+            tuning,
         };
         
-        Ok(ScreenCapture {
+        let mut capture = ScreenCapture {
             capturer,
             width,
             height,
             last_frame_time: Instant::now(),
-            frame_interval: Duration::from_millis(1000 / fps as u64),
+            pacer: AdaptiveFps::new(fps),
             quality_mode: QualityMode::High,
             frame_processor: FrameProcessor::new(width as u32, height as u32),
             frame_count: 0,
-            video_encoder,
-            use_hardware_encoding: use_hardware,
-        })
+            video_encoder: None,
+            use_hardware_encoding: false,
+            hardware_allowed: true,
+            progressive_keyframes: false,
+            foveation: None,
+            keyframe_interval: encoder_settings_keyframe_interval(fps),
+            frames_since_keyframe: 0,
+            keyframe_period: Duration::from_secs_f32(encoder_settings_keyframe_interval(fps) as f32 / fps.max(1) as f32),
+            last_keyframe: Instant::now(),
+            keyframe_requested: true,
+            cursor_toggle_unsupported: false,
+            base_settings: encoder_settings,
+            content_profile: ContentProfile::default(),
+            output_scale: 1.0,
+            output_size: (width as u32, height as u32),
+            power_save: false,
+            simulcast_wanted: Vec::new(),
+            simulcast: None,
+            layer_frames: Vec::new(),
+            pixel_format: PixelFormat::Bgra8,
+            safe_encoder: None,
+            safe_last_frame: None,
+            raw_frame: Vec::new(),
+            rgb_frame: Vec::new(),
+            frame_checksums: false,
+        };
+        capture.update_max_delta_run();
+        
+        if safe_mode {
+            let selection = select_encoder(&EncoderFactory::safe_mode_candidates(), capture.encoder_settings())?;
+            tracing::info!("Safe mode: sending every frame whole, {}", selection.summary());
+            capture.safe_encoder = Some(selection.encoder);
+        } else {
+            capture.video_encoder = hardware_encoder(capture.encoder_settings());
+            capture.use_hardware_encoding = capture.video_encoder.is_some();
+        }
+        Ok(capture)
+    }
+    
+    /// Enable (or with `None`, disable) foveated encoding of software frames.
+    pub fn set_foveation(&mut self, foveation: Option<Foveation>) {
+        self.foveation = foveation;
+    }
+    
+    /// Where the remote user is looking, normally the last reported mouse position.
+    pub fn set_focus(&mut self, focus: Option<(u32, u32)>) {
+        if let Some(foveation) = self.foveation.as_mut() {
+            foveation.set_focus(focus);
+        }
+    }
+    
+    /// Regions viewers want sent without loss. Only foveation reduces tiles, so this only
+    /// matters to it; H.264 frames are encoded whole.
+    pub fn set_lossless_regions(&mut self, regions: Vec<Region>) {
+        if let Some(foveation) = self.foveation.as_mut() {
+            foveation.set_lossless_regions(regions);
+        }
+    }
+    
+    /// Scale frames down to fit a `viewport` of physical pixels, or send them at full size
+    /// with `None`. The encoder and delta state start over at the new size with a keyframe.
+    pub fn set_viewport(&mut self, viewport: Option<(u32, u32)>) {
+        let captured = (self.width as u32, self.height as u32);
+        let scale = viewport_scale(captured, viewport);
+        if scale == self.output_scale {
+            return;
+        }
+        
+        let (width, height) = scaled_size(captured, scale);
+        self.output_scale = scale;
+        self.output_size = (width, height);
+        self.frame_processor = FrameProcessor::new(width, height);
+        self.update_max_delta_run();
+        
+        let (encoder_width, encoder_height) = (width & !1, height & !1);
+        self.base_settings = EncoderSettings {
+            width: encoder_width,
+            height: encoder_height,
+            color_space: ColorSpace::preferred(self.base_settings.tuning.color, encoder_width, encoder_height),
+            ..self.base_settings
+        };
+        self.reconfigure_encoder();
+        self.keyframe_requested = true;
+        tracing::info!("Sending frames at {}x{} ({:.0}% of the captured size)", width, height, scale * 100.0);
+    }
+    
+    /// Fraction of the captured size frames are sent at, below 1.0 for small viewers.
+    pub fn output_scale(&self) -> f32 {
+        self.output_scale
+    }
+    
+    /// Size of the frames sent, after scaling to the viewport.
+    pub fn output_size(&self) -> (u32, u32) {
+        self.output_size
+    }
+    
+    /// Whether this capture was built by `safe_mode`.
+    pub fn is_safe_mode(&self) -> bool {
+        self.safe_encoder.is_some()
+    }
+    
+    /// Make the next frame a keyframe, e.g. because a client joined or missed frames.
+    pub fn request_keyframe(&mut self) {
+        self.keyframe_requested = true;
     }
     
     pub fn set_quality(&mut self, quality: QualityMode) {
+        let before = self.encoder_settings();
         self.quality_mode = quality;
-        self.frame_interval = Duration::from_millis(1000 / quality.target_fps() as u64);
+        let settings = self.content_profile.encoder_settings(EncoderSettings { fps: quality.target_fps(), ..self.base_settings });
+        self.pacer.set_target_fps(settings.fps);
+        self.pacer.set_min_fps(self.content_profile.fps_floor(settings.fps));
+        self.update_max_delta_run();
+        if self.encoder_settings() != before {
+            self.reconfigure_encoder();
+        }
+    }
+    
+    /// Settings the H.264 encoder runs with: the quality mode's frame rate, keyframe
+    /// cadence and share of the configured bitrate, adjusted for the content profile and
+    /// power saving.
+    /// Frames are downscaled to the mode's resolution before encoding, so a lower
+    /// bitrate goes to fewer pixels instead of blurring all of them.
+    pub fn encoder_settings(&self) -> EncoderSettings {
+        self.encoder_settings_for(self.quality_mode)
+    }
+    
+    fn encoder_settings_for(&self, quality: QualityMode) -> EncoderSettings {
+        let mut settings = self.content_profile.encoder_settings(quality.encoder_settings(self.base_settings));
+        if self.power_save {
+            settings = power_save_settings(settings);
+        }
+        let (width, height) = scaled_size((settings.width, settings.height), quality.resolution_scale());
+        EncoderSettings {
+            width,
+            height,
+            color_space: ColorSpace::preferred(settings.tuning.color, width, height),
+            ..settings
+        }
+    }
+    
+    /// Fraction of the captured size the frames being sent have: the viewport scale, and
+    /// the quality mode's resolution while H.264 frames are sent.
+    pub fn frame_scale(&self) -> f32 {
+        if self.video_encoder.is_some() && self.hardware_allowed {
+            self.output_scale * self.encoder_settings().width as f32 / self.output_size.0 as f32
+        } else {
+            self.output_scale
+        }
+    }
+    
+    // Encoders can't change size or rate control mid-stream, so the H.264 encoder is
+    // rebuilt and clients start over from its first keyframe
+    fn reconfigure_encoder(&mut self) {
+        if !self.use_hardware_encoding {
+            return;
+        }
+        let settings = self.encoder_settings();
+        self.video_encoder = hardware_encoder(settings);
+        self.use_hardware_encoding = self.video_encoder.is_some();
+        if self.use_hardware_encoding {
+            tracing::info!(
+                "H.264 encoder at {}x{}, {} fps, {} kbps, keyframe every {} frames",
+                settings.width, settings.height, settings.fps, settings.bitrate / 1000, settings.keyframe_interval,
+            );
+        } else {
+            tracing::warn!("Hardware encoder couldn't be recreated, falling back to software");
+        }
+        self.rebuild_simulcast();
+        self.keyframe_requested = true;
+    }
+    
+    /// Also encode every H.264 frame at each of `layers` below the current quality mode,
+    /// for viewers that can't take the main stream. An empty list turns simulcast off.
+    pub fn set_simulcast(&mut self, layers: &[QualityMode]) {
+        if layers == self.simulcast_wanted.as_slice() {
+            return;
+        }
+        self.simulcast_wanted = layers.to_vec();
+        self.rebuild_simulcast();
+        self.keyframe_requested = true;
+    }
+    
+    // Layers share the main stream's frame rate and keyframes, since they are fed the
+    // same frames and viewers switch between them on a keyframe
+    fn rebuild_simulcast(&mut self) {
+        self.simulcast = None;
+        if !self.use_hardware_encoding {
+            return;
+        }
+        let main = self.encoder_settings();
+        let mut layers = Vec::new();
+        for &quality in &self.simulcast_wanted {
+            if quality.bandwidth_requirement() >= self.quality_mode.bandwidth_requirement() {
+                continue;
+            }
+            let settings = EncoderSettings { fps: main.fps, keyframe_interval: main.keyframe_interval, ..self.encoder_settings_for(quality) };
+            let Some(encoder) = hardware_encoder(settings) else {
+                tracing::warn!("No encoder for the {:?} simulcast layer, sending one layer", quality);
+                return;
+            };
+            layers.push((quality, settings, encoder));
+        }
+        if !layers.is_empty() {
+            tracing::info!("Simulcast layers: {:?}", layers.iter().map(|(quality, _, _)| *quality).collect::<Vec<_>>());
+            self.simulcast = Some(Simulcast::new(layers));
+        }
+    }
+    
+    /// Quality and input scale (frame pixels per captured pixel, see `frame_scale`) of each
+    /// layer frames are sent in, the main stream first. Just the main stream unless
+    /// simulcast is on and H.264 frames are being sent.
+    pub fn layers(&self) -> Vec<(QualityMode, f32)> {
+        let mut layers = vec![(self.quality_mode, self.frame_scale())];
+        if let Some(simulcast) = self.simulcast.as_ref().filter(|_| self.video_encoder.is_some() && self.hardware_allowed) {
+            for (quality, settings) in simulcast.qualities().into_iter().zip(simulcast.settings()) {
+                layers.push((quality, self.output_scale * settings.width as f32 / self.output_size.0 as f32));
+            }
+        }
+        layers
+    }
+    
+    /// Capture like `capture_frame`, returning the main stream's frame followed by those of
+    /// the simulcast layers.
+    pub fn capture_layers(&mut self) -> Result<Vec<CapturedFrame>> {
+        let frame = self.capture_frame()?;
+        Ok(frame.into_iter().chain(self.layer_frames.drain(..)).collect())
+    }
+    
+    /// Capture like `capture_layers`, but only once the source reports the screen changed
+    /// or a keyframe is wanted, waiting up to `timeout` for that. A still screen is never
+    /// captured or encoded; a busy one no faster than the pacer allows.
+    pub fn capture_on_damage(&mut self, timeout: Duration) -> Result<Vec<CapturedFrame>> {
+        let ready_in = self.pacer.interval().saturating_sub(self.last_frame_time.elapsed());
+        if ready_in >= timeout {
+            std::thread::sleep(timeout);
+            return Ok(Vec::new());
+        }
+        std::thread::sleep(ready_in);
+        if self.keyframe_requested {
+            let frames = self.capture_layers()?;
+            // Sources that only hand out changed frames may have none to give yet
+            if !frames.is_empty() {
+                return Ok(frames);
+            }
+        }
+        if !self.capturer.wait_for_damage(timeout - ready_in)? {
+            return Ok(Vec::new());
+        }
+        self.capture_layers()
+    }
+    
+    /// Whether the source tells when the screen changes, for `capture_on_damage`.
+    pub fn reports_damage(&self) -> bool {
+        self.capturer.reports_damage()
+    }
+    
+    // Encode the simulcast layers of a frame; a failing layer encoder turns simulcast off
+    fn encode_layers(&mut self, rgb_data: &[u8], width: usize, height: usize, force_keyframe: bool) {
+        let Some(simulcast) = self.simulcast.as_mut() else {
+            return;
+        };
+        match simulcast.encode(rgb_data, width, height, force_keyframe) {
+            Ok(frames) => {
+                for layer_frame in frames {
+                    self.layer_frames.push(CapturedFrame {
+                        width: layer_frame.width,
+                        height: layer_frame.height,
+                        data: layer_frame.frame.data,
+                        timestamp: layer_frame.frame.timestamp,
+                        frame_type: if layer_frame.frame.is_keyframe { FrameType::KeyFrame } else { FrameType::DeltaFrame },
+                        tiles: None,
+                        encoding: EncodingType::H264,
+                        layer: layer_frame.layer,
+                        checksum: None,
+                    });
+                }
+            }
+            Err(e) => {
+                tracing::warn!("Simulcast encoder failed: {:#}, sending one layer", e);
+                self.simulcast = None;
+            }
+        }
+    }
+    
+    // The quality mode's keyframe interval, stretched or shortened by the content profile
+    fn update_max_delta_run(&mut self) {
+        let settings = EncoderSettings { keyframe_interval: self.quality_mode.keyframe_interval(), ..self.base_settings };
+        let frames = self.content_profile.encoder_settings(settings).keyframe_interval;
+        self.frame_processor.set_max_delta_run(frames.saturating_mul(self.keyframe_factor()));
+    }
+    
+    // Software keyframe cadence, in frames and in wall-clock time
+    fn update_keyframe_cadence(&mut self) {
+        let settings = self.content_profile.encoder_settings(self.base_settings);
+        self.keyframe_interval = settings.keyframe_interval.saturating_mul(self.keyframe_factor());
+        self.keyframe_period = Duration::from_secs_f32(self.keyframe_interval as f32 / settings.fps.max(1) as f32);
+    }
+    
+    fn keyframe_factor(&self) -> u32 {
+        if self.power_save { POWER_SAVE_KEYFRAME_FACTOR } else { 1 }
+    }
+    
+    /// Save battery: drop the frame rate further and sooner while little changes, and
+    /// send fewer keyframes. Busy screens still get the full rate.
+    pub fn set_power_save(&mut self, enabled: bool) {
+        if enabled == self.power_save {
+            return;
+        }
+        let before = self.encoder_settings();
+        self.power_save = enabled;
+        self.pacer.set_power_save(enabled);
+        self.update_keyframe_cadence();
+        self.update_max_delta_run();
+        if self.encoder_settings() != before {
+            self.reconfigure_encoder();
+        }
+        tracing::info!("Power saving {}", if enabled { "on" } else { "off" });
+    }
+    
+    pub fn power_save(&self) -> bool {
+        self.power_save
+    }
+    
+    /// Longest run of software delta frames before a keyframe resyncs the clients.
+    pub fn max_delta_run(&self) -> u32 {
+        self.frame_processor.max_delta_run()
+    }
+    
+    /// Tune frame rate, keyframe cadence and lossy encoding for what the screen shows.
+    pub fn set_content_profile(&mut self, profile: ContentProfile) {
+        if profile == self.content_profile {
+            return;
+        }
+        let before = self.encoder_settings();
+        self.content_profile = profile;
+        
+        let settings = profile.encoder_settings(self.base_settings);
+        self.pacer.set_target_fps(settings.fps);
+        self.pacer.set_min_fps(profile.fps_floor(settings.fps));
+        self.update_keyframe_cadence();
+        self.update_max_delta_run();
+        if self.encoder_settings() != before {
+            self.reconfigure_encoder();
+        }
+        tracing::info!(
+            "Content profile {:?}: {} fps (floor {}), keyframe every {} frames",
+            profile, settings.fps, profile.fps_floor(settings.fps), settings.keyframe_interval,
+        );
+    }
+    
+    pub fn content_profile(&self) -> ContentProfile {
+        self.content_profile
+    }
+    
+    /// Software path keyframe cadence, in frames.
+    pub fn keyframe_interval(&self) -> u32 {
+        self.keyframe_interval
+    }
+    
+    /// Capture rate while the screen is busy.
+    pub fn target_fps(&self) -> u32 {
+        self.pacer.target_fps()
+    }
+    
+    /// Draw the cursor into frames, or leave it out for clients that overlay it themselves.
+    /// Returns false when the capture backend can't change it; see `includes_cursor`.
+    pub fn set_include_cursor(&mut self, include: bool) -> bool {
+        if include == self.capturer.includes_cursor() {
+            return true;
+        }
+        if self.capturer.set_include_cursor(include) {
+            tracing::info!("Cursor {} captured frames", if include { "drawn into" } else { "left out of" });
+            // Clients holding the old frame would keep a stale (or miss a) cursor
+            self.keyframe_requested = true;
+            true
+        } else {
+            if !self.cursor_toggle_unsupported {
+                tracing::warn!(
+                    "Capture backend can't {} the cursor; frames {} it",
+                    if include { "draw" } else { "hide" },
+                    if self.capturer.includes_cursor() { "keep" } else { "lack" },
+                );
+                self.cursor_toggle_unsupported = true;
+            }
+            false
+        }
+    }
+    
+    /// Whether captured frames have the cursor drawn into them.
+    pub fn includes_cursor(&self) -> bool {
+        self.capturer.includes_cursor()
+    }
+    
+    /// Captured pixels per input coordinate unit on this display.
+    pub fn scale_factor(&self) -> f64 {
+        self.capturer.scale_factor()
+    }
+    
+    /// When enabled, software keyframes also carry per-tile compressed data so
+    /// they can be streamed to clients that render progressively.
+    pub fn set_progressive_keyframes(&mut self, enabled: bool) {
+        self.progressive_keyframes = enabled;
+    }
+    
+    /// Attach `frame_checksum` of the frame to each software keyframe and delta, for
+    /// clients to compare with what they reconstructed. Costs a pass over every frame.
+    pub fn set_frame_checksums(&mut self, enabled: bool) {
+        self.frame_checksums = enabled;
+    }
+    
+    /// Use the H.264 encoder (when one exists) only while every client can decode it.
+    pub fn set_hardware_encoding(&mut self, allowed: bool) {
+        if allowed != self.hardware_allowed && self.use_hardware_encoding {
+            tracing::info!("Hardware encoding {}", if allowed { "enabled" } else { "paused" });
+            // Clients can't apply deltas from one encoder on top of frames from the other
+            self.keyframe_requested = true;
+        }
+        self.hardware_allowed = allowed;
     }
     
     pub fn capture_frame(&mut self) -> Result<Option<CapturedFrame>> {
         // Check if enough time has passed for next frame
-        if self.last_frame_time.elapsed() < self.frame_interval {
+        if self.last_frame_time.elapsed() < self.pacer.interval() {
             return Ok(None);
         }
         
-        match self.capturer.frame() {
-            Ok(frame) => {
-                self.last_frame_time = Instant::now();
-                self.frame_count += 1;
-                
-                // Clone the frame data to avoid borrow issues
-                let frame_data = frame.to_vec();
-                
-                // Convert BGRA to RGB
-                let mut rgb_data = bgra_to_rgb(&frame_data, self.width, self.height);
-                
-                // Apply quality scaling if needed (disabled for now to avoid pixelation)
-                let scale = self.quality_mode.resolution_scale();
-                if scale < 1.0 && false { // Temporarily disabled
-                    rgb_data = self.scale_frame(&rgb_data, scale)?;
-                }
-                
-                // For now, always send keyframes to avoid artifacts
-                let force_keyframe = true; // TODO: Re-enable delta encoding when client properly handles it
-                
-                // Use hardware encoder if available
-                if let Some(encoder) = &mut self.video_encoder {
-                    // Ensure RGB data matches encoder dimensions
-                    let encoder_width = (self.width as u32) & !1;
-                    let encoder_height = (self.height as u32) & !1;
-                    
-                    // Check if we need to crop the data
-                    let rgb_for_encoder = if self.width as u32 != encoder_width || self.height as u32 != encoder_height {
-                        tracing::debug!("Cropping frame from {}x{} to {}x{}", self.width, self.height, encoder_width, encoder_height);
-                        // Simple crop - just take the top-left portion
-                        let mut cropped = Vec::with_capacity((encoder_width * encoder_height * 3) as usize);
-                        for y in 0..encoder_height as usize {
-                            let src_offset = y * self.width * 3;
-                            let src_end = src_offset + (encoder_width as usize * 3);
-                            cropped.extend_from_slice(&rgb_data[src_offset..src_end]);
-                        }
-                        cropped
-                    } else {
-                        rgb_data.clone()
-                    };
-                    
-                    match encoder.encode_frame(&rgb_for_encoder, force_keyframe) {
-                        Ok(encoded_frame) => {
-                            return Ok(Some(CapturedFrame {
-                                width: self.width as u32,
-                                height: self.height as u32,
-                                data: encoded_frame.data,
-                                timestamp: encoded_frame.timestamp,
-                                frame_type: if encoded_frame.is_keyframe {
-                                    crate::common::frame_processor::FrameType::KeyFrame
-                                } else {
-                                    crate::common::frame_processor::FrameType::DeltaFrame
-                                },
-                                tiles: None,
-                                encoding: EncodingType::H264,
-                            }));
-                        }
-                        Err(e) => {
-                            tracing::warn!("Hardware encoder failed: {}, falling back to software", e);
-                            self.video_encoder = None;
-                            self.use_hardware_encoding = false;
-                        }
-                    }
-                }
-                
-                // Fall back to software processing
-                // Process frame with delta encoding
-                let processed = self.frame_processor.process_frame(&rgb_data, force_keyframe)?;
-                
-                // Compress based on quality mode
-                let compression_level = self.quality_mode.compression_level();
-                let compressed_data = match processed.frame_type {
-                    crate::common::frame_processor::FrameType::KeyFrame => {
-                        encode_all(&processed.data[..], compression_level)?
-                    }
-                    crate::common::frame_processor::FrameType::DeltaFrame => {
-                        // For delta frames, compress tiles individually
-                        if let Some(tiles) = &processed.tiles {
-                            let mut compressed_tiles = Vec::new();
-                            for tile in tiles {
-                                let compressed = encode_all(&tile.data[..], compression_level)?;
-                                compressed_tiles.push(crate::common::frame_processor::TileData {
-                                    x: tile.x,
-                                    y: tile.y,
-                                    width: tile.width,
-                                    height: tile.height,
-                                    data: Bytes::from(compressed),
-                                });
-                            }
-                            // Return delta frame data
-                            return Ok(Some(CapturedFrame {
-                                width: processed.width,
-                                height: processed.height,
-                                data: Bytes::new(), // No full data for delta
-                                timestamp: std::time::SystemTime::now()
-                                    .duration_since(std::time::UNIX_EPOCH)
-                                    .unwrap()
-                                    .as_millis() as u64,
-                                frame_type: processed.frame_type,
-                                tiles: Some(compressed_tiles),
-                                encoding: EncodingType::ZstdCompressed,
-                            }));
-                        }
-                        vec![]
-                    }
-                };
-                
-                Ok(Some(CapturedFrame {
-                    width: processed.width,
-                    height: processed.height,
-                    data: Bytes::from(compressed_data),
-                    timestamp: std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap()
-                        .as_millis() as u64,
-                    frame_type: processed.frame_type,
-                    tiles: None,
-                    encoding: EncodingType::ZstdCompressed,
-                }))
-            }
+        // Both buffers are lent out for the frame and put back after, so the next capture
+        // reuses their allocations instead of making new ones
+        let mut raw = std::mem::take(&mut self.raw_frame);
+        let mut rgb = std::mem::take(&mut self.rgb_frame);
+        let result = match self.capturer.frame_into(&mut raw) {
+            Ok(()) => self.encode_capture(&raw, &mut rgb),
             Err(ref e) if e.kind() == WouldBlock => {
                 // Frame not ready yet
                 Ok(None)
             }
             Err(e) => Err(e.into()),
-        }
+        };
+        self.raw_frame = raw;
+        self.rgb_frame = rgb;
+        result
     }
     
-    fn scale_frame(&self, rgb_data: &[u8], scale: f32) -> Result<Vec<u8>> {
-        let new_width = (self.width as f32 * scale) as u32;
-        let new_height = (self.height as f32 * scale) as u32;
+    // Convert a captured frame into `rgb` and encode it
+    fn encode_capture(&mut self, frame: &[u8], rgb: &mut Vec<u8>) -> Result<Option<CapturedFrame>> {
+        self.last_frame_time = Instant::now();
+        self.frame_count += 1;
+        
+        // Convert to 8-bit RGB, tone-mapping HDR displays down to SDR
+        let format = PixelFormat::detect(self.capturer.pixel_format(), frame.len(), self.width, self.height)?;
+        if format != self.pixel_format {
+            tracing::info!(
+                "Display delivers {} pixels{}",
+                format.name(),
+                if format.is_hdr() { ", tone-mapping to SDR" } else { "" }
+            );
+            self.pixel_format = format;
+        }
+        to_rgb_into(frame, self.width, self.height, format, rgb)?;
         
-        // Simple nearest-neighbor scaling for speed
-        let mut scaled = vec![0u8; (new_width * new_height * 3) as usize];
+        // Down to what the viewers' windows can show
+        let mut scaled = None;
+        if self.output_scale < 1.0 {
+            let (width, height) = self.output_size;
+            scaled = Some(resize_rgb(&rgb[..], self.width, self.height, width as usize, height as usize)?);
+        }
+        let (width, height) = (self.output_size.0 as usize, self.output_size.1 as usize);
         
-        for y in 0..new_height {
-            for x in 0..new_width {
-                let src_x = (x as f32 / scale) as usize;
-                let src_y = (y as f32 / scale) as usize;
-                
-                if src_x < self.width && src_y < self.height {
-                    let src_idx = (src_y * self.width + src_x) * 3;
-                    let dst_idx = ((y * new_width + x) * 3) as usize;
-                    
-                    if src_idx + 3 <= rgb_data.len() && dst_idx + 3 <= scaled.len() {
-                        scaled[dst_idx..dst_idx + 3].copy_from_slice(&rgb_data[src_idx..src_idx + 3]);
+        // Apply quality scaling if needed (disabled for now to avoid pixelation)
+        let scale = self.quality_mode.resolution_scale();
+        if scale < 1.0 && false { // Temporarily disabled
+            scaled = Some(self.scale_frame(scaled.as_deref().unwrap_or(&rgb[..]), scale)?);
+        }
+        let rgb_data: &[u8] = scaled.as_deref().unwrap_or(&rgb[..]);
+        
+        // Keyframes on request and every keyframe_interval frames, deltas otherwise
+        let mut force_keyframe = self.keyframe_requested
+            || self.frames_since_keyframe + 1 >= self.keyframe_interval
+            || self.last_keyframe.elapsed() >= self.keyframe_period;
+        
+        if self.safe_encoder.is_some() {
+            return self.encode_safe_mode(rgb_data, width, height, force_keyframe);
+        }
+        
+        // Use hardware encoder if available and every client can decode it
+        let encoder_settings = self.encoder_settings();
+        if let Some(encoder) = self.video_encoder.as_mut().filter(|_| self.hardware_allowed) {
+            // Down to the quality mode's resolution, and even dimensions for the encoder
+            let (encoder_width, encoder_height) = (encoder_settings.width, encoder_settings.height);
+            let resized;
+            let rgb_for_encoder = if width as u32 != encoder_width || height as u32 != encoder_height {
+                resized = resize_rgb(rgb_data, width, height, encoder_width as usize, encoder_height as usize)?;
+                &resized[..]
+            } else {
+                rgb_data
+            };
+            
+            match encoder.encode_frame(rgb_for_encoder, force_keyframe) {
+                Ok(encoded_frame) => {
+                    // Layers start a GOP wherever the main stream does
+                    self.encode_layers(rgb_data, width, height, force_keyframe || encoded_frame.is_keyframe);
+                    if encoded_frame.data.is_empty() {
+                        // Encoder is buffering; this frame still counts toward pacing
+                        return Ok(None);
                     }
+                    
+                    // H.264 handles static content itself, so keep the full rate
+                    self.pacer.record_full_change();
+                    self.count_frame(encoded_frame.is_keyframe);
+                    let level = self.quality_mode.compression_level();
+                    let (data, encoding) = compress_payload(encoded_frame.data, EncodingType::H264, level)?;
+                    return Ok(Some(CapturedFrame {
+                        width: encoder_width,
+                        height: encoder_height,
+                        data,
+                        timestamp: encoded_frame.timestamp,
+                        frame_type: if encoded_frame.is_keyframe {
+                            crate::common::frame_processor::FrameType::KeyFrame
+                        } else {
+                            crate::common::frame_processor::FrameType::DeltaFrame
+                        },
+                        tiles: None,
+                        encoding,
+                        layer: 0,
+                        checksum: None,
+                    }));
+                }
+                Err(e) => {
+                    tracing::warn!("Hardware encoder failed: {}, falling back to software", e);
+                    self.video_encoder = None;
+                    self.use_hardware_encoding = false;
+                    self.simulcast = None;
+                    // Clients were decoding H.264; software deltas can't build on that
+                    force_keyframe = true;
                 }
             }
         }
         
-        Ok(scaled)
+        // Fall back to software processing
+        // Process frame with delta encoding
+        let processed = self.frame_processor.process_frame(rgb_data, force_keyframe)?;
+        // Forced keyframes say nothing about how much the screen changed
+        match processed.as_ref().map(|processed| &processed.tiles) {
+            Some(Some(tiles)) => self.pacer.record_frame(tiles.len(), self.frame_processor.tile_count()),
+            Some(None) if !force_keyframe => self.pacer.record_full_change(),
+            Some(None) => {}
+            None => self.pacer.record_frame(0, self.frame_processor.tile_count()),
+        }
+        self.count_frame(processed.as_ref().map_or(false, |processed| {
+            processed.frame_type == crate::common::frame_processor::FrameType::KeyFrame
+        }));
+        
+        // Foveation drops color precision, which the text profile doesn't allow
+        let lossless = self.content_profile.lossless();
+        // Frames foveation made lossy can't match what the client reconstructs
+        let checksum = (self.frame_checksums && (self.foveation.is_none() || lossless)).then(|| frame_checksum(rgb_data));
+        // Nothing changed, but foveation may still owe full tiles near the cursor
+        let mut processed = match processed {
+            Some(processed) => processed,
+            None if self.foveation.is_some() && !lossless => self.frame_processor.delta_frame(Vec::new()),
+            None => return Ok(None),
+        };
+        if let Some(foveation) = self.foveation.as_mut().filter(|_| !lossless) {
+            foveate(foveation, &self.frame_processor, &mut processed, rgb_data)?;
+        }
+        
+        // Compress based on quality mode
+        let compression_level = self.quality_mode.compression_level();
+        let (compressed_data, encoding) = match processed.frame_type {
+            crate::common::frame_processor::FrameType::KeyFrame => {
+                compress_payload(processed.data.clone(), EncodingType::Raw, compression_level)?
+            }
+            crate::common::frame_processor::FrameType::DeltaFrame => {
+                // For delta frames, compress tiles individually
+                if let Some(tiles) = &processed.tiles {
+                    // Nothing changed on screen, nothing to send
+                    if tiles.is_empty() {
+                        return Ok(None);
+                    }
+                    let compressed_tiles = compress_tiles(tiles, compression_level)?;
+                    // Return delta frame data
+                    return Ok(Some(CapturedFrame {
+                        width: processed.width,
+                        height: processed.height,
+                        data: Bytes::new(), // No full data for delta
+                        timestamp: std::time::SystemTime::now()
+                            .duration_since(std::time::UNIX_EPOCH)
+                            .unwrap()
+                            .as_millis() as u64,
+                        frame_type: processed.frame_type,
+                        tiles: Some(compressed_tiles),
+                        encoding: EncodingType::ZstdCompressed,
+                        layer: 0,
+                        checksum,
+                    }));
+                }
+                (Bytes::new(), EncodingType::ZstdCompressed)
+            }
+        };
+        
+        // Also provide the keyframe as tiles for progressive clients
+        let keyframe_tiles = if self.progressive_keyframes
+            && processed.frame_type == crate::common::frame_processor::FrameType::KeyFrame
+        {
+            let tiles = self.frame_processor.split_into_tiles(&processed.data)?;
+            Some(compress_tiles(&tiles, compression_level)?)
+        } else {
+            None
+        };
+        
+        Ok(Some(CapturedFrame {
+            width: processed.width,
+            height: processed.height,
+            data: compressed_data,
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as u64,
+            frame_type: processed.frame_type,
+            tiles: keyframe_tiles,
+            encoding,
+            layer: 0,
+            checksum,
+        }))
+    }
+    
+    // The whole frame as one WebP image, unless nothing changed and no keyframe is due
+    fn encode_safe_mode(&mut self, rgb_data: &[u8], width: usize, height: usize, force_keyframe: bool) -> Result<Option<CapturedFrame>> {
+        if !force_keyframe && self.safe_last_frame.as_deref() == Some(rgb_data) {
+            self.pacer.record_frame(0, self.frame_processor.tile_count());
+            return Ok(None);
+        }
+        let settings = EncoderSettings { width: width as u32, height: height as u32, ..self.encoder_settings() };
+        let Some(encoder) = self.safe_encoder.as_mut() else {
+            return Ok(None);
+        };
+        encoder.update_settings(settings)?;
+        let encoded = encoder.encode_frame(rgb_data, true)?;
+        self.pacer.record_full_change();
+        self.count_frame(true);
+        // Copied into last frame's allocation rather than a new one
+        let last = self.safe_last_frame.get_or_insert_with(Vec::new);
+        last.clear();
+        last.extend_from_slice(rgb_data);
+        Ok(Some(CapturedFrame {
+            width: width as u32,
+            height: height as u32,
+            data: encoded.data,
+            timestamp: encoded.timestamp,
+            frame_type: FrameType::KeyFrame,
+            tiles: None,
+            encoding: EncodingType::WebP,
+            layer: 0,
+            checksum: None,
+        }))
+    }
+    
+    fn count_frame(&mut self, is_keyframe: bool) {
+        if is_keyframe {
+            self.frames_since_keyframe = 0;
+            self.last_keyframe = Instant::now();
+            self.keyframe_requested = false;
+        } else {
+            self.frames_since_keyframe += 1;
+        }
     }
     
+    fn scale_frame(&self, rgb_data: &[u8], scale: f32) -> Result<Vec<u8>> {
+        scale_rgb(rgb_data, self.width, self.height, scale)
+    }
+    
+    
+    /// Capture rate the pacer is currently running at.
+    pub fn current_fps(&self) -> f32 {
+        self.pacer.current_fps()
+    }
     
     pub fn dimensions(&self) -> (u32, u32) {
         (self.width as u32, self.height as u32)
     }
 }
 
-fn bgra_to_rgb(bgra: &[u8], width: usize, height: usize) -> Vec<u8> {
-    let mut rgb = Vec::with_capacity((width * height * 3) as usize);
+fn compress_tiles(
+    tiles: &[crate::common::frame_processor::TileData],
+    compression_level: i32,
+) -> Result<Vec<crate::common::frame_processor::TileData>> {
+    let mut compressed_tiles = Vec::with_capacity(tiles.len());
+    for tile in tiles {
+        let (compressed, _) = compress_payload(tile.data.clone(), EncodingType::Raw, compression_level)?;
+        compressed_tiles.push(crate::common::frame_processor::TileData {
+            x: tile.x,
+            y: tile.y,
+            width: tile.width,
+            height: tile.height,
+            data: compressed,
+        });
+    }
+    Ok(compressed_tiles)
+}
+
+/// Nearest-neighbor downscale of a packed RGB frame.
+pub fn scale_rgb(rgb_data: &[u8], width: usize, height: usize, scale: f32) -> Result<Vec<u8>> {
+    if !(scale > 0.0 && scale <= 1.0) {
+        return Err(anyhow::anyhow!("Invalid scale factor {}", scale));
+    }
     
-    for chunk in bgra.chunks_exact(4) {
-        rgb.push(chunk[2]); // R
-        rgb.push(chunk[1]); // G
-        rgb.push(chunk[0]); // B
-        // Skip alpha channel
+    let new_width = ((width as f32 * scale) as usize).max(1);
+    let new_height = ((height as f32 * scale) as usize).max(1);
+    resize_rgb(rgb_data, width, height, new_width, new_height)
+}
+
+/// Nearest-neighbor resize of a packed RGB frame to exactly `new_width` x `new_height`.
+pub fn resize_rgb(rgb_data: &[u8], width: usize, height: usize, new_width: usize, new_height: usize) -> Result<Vec<u8>> {
+    if width == 0 || height == 0 || rgb_data.len() != width * height * 3 {
+        return Err(anyhow::anyhow!(
+            "RGB buffer is {} bytes, expected {} for {}x{}",
+            rgb_data.len(), width * height * 3, width, height
+        ));
+    }
+    if new_width == 0 || new_height == 0 {
+        return Err(anyhow::anyhow!("Can't resize to {}x{}", new_width, new_height));
     }
     
+    // Simple nearest-neighbor scaling for speed
+    let mut scaled = vec![0u8; new_width * new_height * 3];
+    
+    for y in 0..new_height {
+        for x in 0..new_width {
+            let src_x = (x * width / new_width).min(width - 1);
+            let src_y = (y * height / new_height).min(height - 1);
+            
+            let src_idx = (src_y * width + src_x) * 3;
+            let dst_idx = (y * new_width + x) * 3;
+            scaled[dst_idx..dst_idx + 3].copy_from_slice(&rgb_data[src_idx..src_idx + 3]);
+        }
+    }
+    
+    Ok(scaled)
+}
+
+pub(crate) fn bgra_to_rgb(bgra: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let mut rgb = Vec::with_capacity(width * height * 3);
+    bgra_to_rgb_into(bgra, &mut rgb);
     rgb
 }
 
+/// Append `bgra`'s pixels to `rgb` as RGB, growing it only if it's short of room.
+pub(crate) fn bgra_to_rgb_into(bgra: &[u8], rgb: &mut Vec<u8>) {
+    rgb.reserve(bgra.len() / 4 * 3);
+    for chunk in bgra.chunks_exact(4) {
+        rgb.extend_from_slice(&[chunk[2], chunk[1], chunk[0]]); // Skip alpha channel
+    }
+}
+
+/// Reduce the periphery of a processed frame, and add tiles the focus has moved onto
+/// that the client only has at reduced quality.
+fn foveate(
+    foveation: &mut Foveation,
+    processor: &FrameProcessor,
+    processed: &mut ProcessedFrame,
+    frame: &[u8],
+) -> Result<()> {
+    match processed.frame_type {
+        FrameType::KeyFrame => {
+            foveation.reset();
+            let mut tiles = processor.split_into_tiles(&processed.data)?;
+            foveation.apply(&mut tiles);
+            
+            let mut reduced = processed.data.to_vec();
+            processor.apply_delta(&mut reduced, &ProcessedFrame {
+                frame_type: FrameType::KeyFrame,
+                data: Bytes::new(),
+                width: processed.width,
+                height: processed.height,
+                tiles: Some(tiles),
+            })?;
+            processed.data = Bytes::from(reduced);
+        }
+        FrameType::DeltaFrame => {
+            let tiles = processed.tiles.get_or_insert_with(Vec::new);
+            for (x, y) in foveation.tiles_to_refresh() {
+                if !tiles.iter().any(|tile| tile.x == x && tile.y == y) {
+                    tiles.push(processor.tile_at(frame, x, y)?);
+                }
+            }
+            foveation.apply(tiles);
+        }
+    }
+    Ok(())
+}
+
+// Keyframe every 2 seconds
+fn encoder_settings_keyframe_interval(fps: u32) -> u32 {
+    (fps * 2).max(1)
+}
+
 #[derive(Debug, Clone)]
 pub struct CapturedFrame {
     pub width: u32,
@@ -262,4 +928,51 @@ pub struct CapturedFrame {
     pub frame_type: crate::common::frame_processor::FrameType,
     pub tiles: Option<Vec<crate::common::frame_processor::TileData>>,
     pub encoding: crate::common::protocol::EncodingType,
-}
\ No newline at end of file
+    /// Simulcast layer the frame belongs to, 0 for the main stream
+    pub layer: usize,
+    /// `frame_checksum` of the frame before encoding, when frame checksums are on and
+    /// the encoding is lossless
+    pub checksum: Option<u32>,
+}
+
+impl CapturedFrame {
+    /// The message carrying this frame as the session's frame `sequence`, or `None` if there
+    /// is nothing to send (an empty frame from a buffering encoder, or a delta without tiles).
+    pub fn to_message(&self, sequence: u64) -> Option<Message> {
+        match (self.frame_type, &self.tiles) {
+            (crate::common::frame_processor::FrameType::DeltaFrame, Some(tiles)) if !tiles.is_empty() => {
+                Some(Message::DeltaFrame {
+                    sequence,
+                    timestamp: self.timestamp,
+                    tiles: tiles.clone(),
+                    checksum: self.checksum,
+                })
+            }
+            // Software deltas without tiles change nothing; H.264 inter frames go to the decoder whole
+            (crate::common::frame_processor::FrameType::DeltaFrame, Some(_)) => None,
+            (crate::common::frame_processor::FrameType::DeltaFrame, None) if self.encoding != EncodingType::H264 => None,
+            _ if self.data.is_empty() => None,
+            _ => Some(Message::ScreenFrame {
+                sequence,
+                timestamp: self.timestamp,
+                width: self.width,
+                height: self.height,
+                data: self.data.to_vec(),
+                encoding: self.encoding,
+                checksum: self.checksum,
+            }),
+        }
+    }
+}
+
+// H.264 encoder for `settings` when the machine has one
+fn hardware_encoder(settings: EncoderSettings) -> Option<Box<dyn VideoEncoder>> {
+    if !EncoderFactory::is_hardware_available() {
+        return None;
+    }
+    // Hardware encoders can hang; the watchdog replaces one that stops answering
+    let builder: EncoderBuilder = Box::new(|settings| EncoderFactory::create_encoder(EncoderType::Hardware, settings));
+    WatchdogEncoder::new(settings, DEFAULT_ENCODE_TIMEOUT, builder)
+        .ok()
+        .map(|encoder| Box::new(encoder) as Box<dyn VideoEncoder>)
+}
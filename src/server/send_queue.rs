@@ -0,0 +1,208 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Instant;
+use anyhow::{anyhow, Result};
+use tokio::sync::Notify;
+use crate::common::delivery::DeliveryMode;
+
+/// Bytes a session may have waiting for its socket before video is thrown away
+pub const SEND_QUEUE_CAPACITY: usize = 32 * 1024 * 1024;
+
+/// Video messages a session may have waiting before the frame loop stops adding more
+pub const VIDEO_BACKLOG: usize = 4;
+
+/// The same for real-time delivery, where a frame waiting behind another is already late
+pub const REALTIME_VIDEO_BACKLOG: usize = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// Never dropped, and sent ahead of any queued video
+    Control,
+    /// Dropped, oldest first, when the queue is full
+    Video,
+}
+
+#[derive(Default)]
+struct Queues {
+    control: VecDeque<Vec<u8>>,
+    // With the time each was queued, to spot late frames
+    video: VecDeque<(Instant, Vec<u8>)>,
+    bytes: usize,
+    delivery: DeliveryMode,
+    // Video thrown away since the frame loop last asked
+    dropped_video: usize,
+    closed: bool,
+    overflowed: bool,
+}
+
+impl Queues {
+    fn evict_video(&mut self) -> bool {
+        match self.video.pop_front() {
+            Some((_, frame)) => {
+                self.bytes -= frame.len();
+                self.dropped_video += 1;
+                true
+            }
+            None => false,
+        }
+    }
+    
+    fn pop_video(&mut self) -> Option<Vec<u8>> {
+        // A late frame is dropped along with all video queued behind it, which was encoded
+        // against it; the frame loop sees the drops and sends the client a keyframe
+        if let (Some(max_age), Some((queued_at, _))) = (self.delivery.max_frame_age(), self.video.front()) {
+            if queued_at.elapsed() > max_age {
+                while self.evict_video() {}
+                return None;
+            }
+        }
+        self.video.pop_front().map(|(_, frame)| frame)
+    }
+}
+
+/// Outgoing messages of one session, bounded in bytes so a client that stops reading
+/// can't make the server buffer without limit.
+///
+/// Control messages (handshake, input replies, quality changes) jump ahead of video
+/// and are never dropped; if they alone fill the queue the client is considered stuck
+/// and the queue overflows, which ends the session. Video makes room by dropping the
+/// oldest queued video, which the frame loop picks up through `take_dropped_video` to
+/// send the client a keyframe. In real-time delivery, video that waited too long for the
+/// socket is dropped the same way instead of being sent late.
+pub struct SendQueue {
+    queues: Mutex<Queues>,
+    ready: Notify,
+    capacity: usize,
+}
+
+impl SendQueue {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            queues: Mutex::new(Queues::default()),
+            ready: Notify::new(),
+            capacity,
+        }
+    }
+    
+    pub fn push(&self, frame: Vec<u8>, priority: Priority) -> Result<()> {
+        let mut queues = self.queues.lock().unwrap();
+        if queues.closed {
+            return Err(anyhow!("Session send queue is closed"));
+        }
+        
+        // Video too big to fit even in an empty queue is dropped without evicting anything
+        let control_bytes: usize = queues.control.iter().map(Vec::len).sum();
+        if priority == Priority::Video && control_bytes + frame.len() > self.capacity {
+            queues.dropped_video += 1;
+            return Ok(());
+        }
+        
+        while queues.bytes + frame.len() > self.capacity && queues.evict_video() {}
+        
+        if queues.bytes + frame.len() > self.capacity {
+            match priority {
+                Priority::Video => {
+                    queues.dropped_video += 1;
+                    return Ok(());
+                }
+                Priority::Control => {
+                    queues.closed = true;
+                    queues.overflowed = true;
+                    drop(queues);
+                    self.ready.notify_one();
+                    return Err(anyhow!("Session send queue overflowed, client is not reading"));
+                }
+            }
+        }
+        
+        queues.bytes += frame.len();
+        match priority {
+            Priority::Control => queues.control.push_back(frame),
+            Priority::Video => queues.video.push_back((Instant::now(), frame)),
+        }
+        drop(queues);
+        self.ready.notify_one();
+        Ok(())
+    }
+    
+    /// Drop all queued video, e.g. because it was encrypted under a key the client is
+    /// about to stop using.
+    pub fn discard_video(&self) {
+        let mut queues = self.queues.lock().unwrap();
+        while queues.evict_video() {}
+    }
+    
+    /// The next message to write, control first. `None` once the queue is closed and
+    /// drained, or right away if it overflowed.
+    pub async fn pop(&self) -> Option<Vec<u8>> {
+        self.pop_with_priority().await.map(|(frame, _)| frame)
+    }
+    
+    /// Like `pop`, with the priority the message was queued at, for a writer that sends
+    /// video differently.
+    pub async fn pop_with_priority(&self) -> Option<(Vec<u8>, Priority)> {
+        loop {
+            {
+                let mut queues = self.queues.lock().unwrap();
+                if queues.overflowed {
+                    return None;
+                }
+                let next = match queues.control.pop_front() {
+                    Some(frame) => Some((frame, Priority::Control)),
+                    None => queues.pop_video().map(|frame| (frame, Priority::Video)),
+                };
+                if let Some((frame, priority)) = next {
+                    queues.bytes -= frame.len();
+                    return Some((frame, priority));
+                }
+                if queues.closed {
+                    return None;
+                }
+            }
+            // Single consumer, so a notification sent while we weren't waiting is kept for us
+            self.ready.notified().await;
+        }
+    }
+    
+    /// Switch delivery mode; video already queued is judged by the new one.
+    pub fn set_delivery(&self, mode: DeliveryMode) {
+        self.queues.lock().unwrap().delivery = mode;
+    }
+    
+    pub fn delivery(&self) -> DeliveryMode {
+        self.queues.lock().unwrap().delivery
+    }
+    
+    /// Video messages that may wait before the frame loop stops adding more.
+    pub fn video_backlog_limit(&self) -> usize {
+        match self.delivery() {
+            DeliveryMode::Smooth => VIDEO_BACKLOG,
+            DeliveryMode::RealTime => REALTIME_VIDEO_BACKLOG,
+        }
+    }
+    
+    /// Stop accepting messages; the writer still sends what is queued.
+    pub fn close(&self) {
+        self.queues.lock().unwrap().closed = true;
+        self.ready.notify_one();
+    }
+    
+    pub fn is_overflowed(&self) -> bool {
+        self.queues.lock().unwrap().overflowed
+    }
+    
+    /// Bytes waiting to be written.
+    pub fn queued_bytes(&self) -> usize {
+        self.queues.lock().unwrap().bytes
+    }
+    
+    /// Video messages waiting to be written.
+    pub fn video_backlog(&self) -> usize {
+        self.queues.lock().unwrap().video.len()
+    }
+    
+    /// Video dropped since the last call.
+    pub fn take_dropped_video(&self) -> usize {
+        std::mem::take(&mut self.queues.lock().unwrap().dropped_video)
+    }
+}
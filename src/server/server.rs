@@ -1,386 +1,1940 @@
 use crate::common::{
-    auth::{AccessCode, AuthResponse, SessionToken},
-    protocol::Message,
-    crypto::{CryptoSession, KeyExchange},
-    quality::AdaptiveQualityController,
+    auth::{AccessCode, AccessCodes, Permissions},
+    protocol::{ErrorKind, Message, TouchPhase},
+    crypto::{is_corrupt, negotiate_cipher, open_message, seal_message, Cipher, CryptoSession, Encryptor, KeyExchange, NullEncryptor, PlaintextVideo, RekeyPolicy, MAX_CORRUPT_MESSAGES},
+    quality::{ContentProfile, QualityChangeReason, QualityMode},
+    encoder::{EncoderFactory, EncoderTuning, EncoderType},
+    capabilities::{Capabilities, negotiate},
+    protocol::EncodingType,
+    frame_processor::{merge_tiles, FrameType, TileData},
+    h264::ParameterSets,
+    metrics::{PerformanceMetrics, SessionStats},
+    batch::unpack,
+    display_layout::DisplayLayout,
+    framing::{CONTROL_READ_CAPACITY, LENGTH_PREFIX},
+    transport::{is_closed, QuicTransport, TcpConnection, Transport},
+    input_channel::{is_input, QuicInputReceiver},
+    socket::{self, SocketBuffers},
 };
 use crate::server::{
-    screen_capture::ScreenCapture,
-    input_handler::InputHandler,
+    screen_capture::{CapturedFrame, ScreenCapture, DEFAULT_BITRATE},
+    input_handler::{is_permission_error, HeldKey, HeldKeys, InputHandler},
+    idle::IdleMonitor,
+    frame_source::{check_display_available, detect_display_layout, open_frame_source, CaptureBackend, FrameSourceFactory},
+    capture_supervisor::{run_supervised, Backoff},
+    send_queue::{Priority, SendQueue, SEND_QUEUE_CAPACITY},
+    notifications::{HostNotification, NotificationLimiter, NotificationSourceFactory},
+    power::{PowerSaveMode, POWER_CHECK_INTERVAL},
+    simulcast::{layer_for, DEFAULT_LAYERS},
+    session::{authenticate, Session, TransportSender},
+    approval::{ApprovalOutcome, ApprovalPolicy, Approvals, ConnectionRequest},
+    vsync::vsync_sources,
+    display_control::{display_control_reply, open_display_controller, DisplayController},
 };
+use crate::common::foveation::{Foveation, Region, DEFAULT_PERIPHERY_BITS};
+use crate::common::viewport::combined_viewport;
 use std::sync::Arc;
-use tokio::sync::{RwLock, mpsc, Mutex};
-use tokio::net::{TcpListener, TcpStream};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+use tokio::sync::{RwLock, mpsc, Mutex, Notify};
+use tokio::net::TcpListener;
 use anyhow::{Result, Context};
-use tracing::{info, error, debug};
-use std::collections::HashMap;
-use uuid::Uuid;
-use bytes::{BytesMut, Buf};
+use tracing::{info, warn, error, debug};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+// Number of tiles per KeyFrameChunk when streaming keyframes progressively
+const PROGRESSIVE_TILES_PER_CHUNK: usize = 32;
+
+// How often an expired access code is checked for replacement
+const CODE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+// How often cursor overlay positions go out to clients that draw the cursor themselves
+const CURSOR_UPDATE_INTERVAL: Duration = Duration::from_millis(16);
+
+// Longest a capture thread waits for the screen to change before looking at its stop
+// flag and keyframe requests again
+const DAMAGE_WAIT: Duration = Duration::from_millis(100);
 
 pub struct Server {
     access_code: Arc<RwLock<Option<AccessCode>>>,
+    // Fixed codes accepted besides the rotating one, each with its own permissions
+    access_codes: Arc<AccessCodes>,
     sessions: Arc<RwLock<HashMap<String, ClientSession>>>,
+    idle_timeout: Option<Duration>,
+    capture_backend: CaptureBackend,
+    encoder_tuning: EncoderTuning,
+    fps: u32,
+    bitrate: u32,
+    max_sessions: Option<usize>,
+    // Whether the host has to let each authenticated client in, and who is waiting for it
+    approval: ApprovalPolicy,
+    approvals: Arc<Approvals>,
+    // Shared with `ServerControl`, which may change it while sessions are running
+    quality_bounds: Arc<std::sync::Mutex<(QualityMode, QualityMode)>>,
+    allow_unencrypted: bool,
+    // Let authenticated clients take video unencrypted, for networks that encrypt it anyway
+    plaintext_video: bool,
+    rekey_policy: RekeyPolicy,
+    foveation_radius: Option<u32>,
+    // Leave the cursor out of frames when every viewer can draw it from CursorUpdate
+    cursor_overlay: bool,
+    power_save: PowerSaveMode,
+    // Encode lower-quality layers too when viewers of a display differ in bandwidth
+    simulcast: bool,
+    // Send every frame as a whole software WebP image, never touching hardware encoders
+    safe_mode: bool,
+    // Checksum every lossless frame so clients can report where they drifted (debugging)
+    frame_checksums: bool,
+    // Take each frame just after the display refreshes, where the platform signals it
+    vsync: bool,
+    // Capture and encode only when the capture backend reports the screen changed
+    on_damage: bool,
+    // Changes the host's display for sessions allowed to
+    display_controller: Arc<std::sync::Mutex<Box<dyn DisplayController>>>,
+    // Kernel buffer sizes for the listening socket and the connections it accepts
+    socket_buffers: SocketBuffers,
+    // Where the most recent mouse move from any client put the cursor
+    focus: Arc<std::sync::Mutex<Option<(u32, u32)>>>,
+    // Replaces the real screen, e.g. with synthetic displays
+    frame_sources: Option<FrameSourceFactory>,
+    // Where the displays sit on the desktop; detected from the host when not given
+    display_layout: Option<DisplayLayout>,
+    // Host notifications to forward to clients; off unless the host asks for it
+    notification_sources: Option<NotificationSourceFactory>,
+    metrics: Arc<PerformanceMetrics>,
 }
 
 struct ClientSession {
-    #[allow(dead_code)]
-    id: String,
-    #[allow(dead_code)]
-    token: SessionToken,
-    crypto: Arc<Mutex<CryptoSession>>,
-    tx: mpsc::UnboundedSender<Vec<u8>>,
-    quality_controller: Arc<Mutex<AdaptiveQualityController>>,
+    // Id, keys, quality and frame numbering; outgoing messages go through a send queue,
+    // bounded so a client that stops reading can't exhaust memory
+    core: Session<SendQueue>,
+    // Where the client connected from
+    peer: Option<SocketAddr>,
+    // What the code it authenticated with lets it do
+    permissions: Permissions,
     last_frame_time: Arc<Mutex<std::time::Instant>>,
+    progressive_rendering: Arc<AtomicBool>,
+    disconnect: Arc<Notify>,
+    // What the client said it can decode, baseline until it tells us
+    capabilities: Arc<Mutex<Capabilities>>,
+    // Set until the client has a keyframe to apply deltas to
+    needs_keyframe: Arc<AtomicBool>,
+    // Delta tiles held back by frame pacing, sent with the next delta
+    pending_tiles: Arc<Mutex<Vec<TileData>>>,
+    // Index of the display this session streams
+    display: Arc<AtomicUsize>,
+    // The client draws the cursor itself from CursorUpdate messages
+    cursor_overlay: Arc<AtomicBool>,
+    // Cursor position last sent to the client, None while it's hidden
+    cursor_sent: Arc<Mutex<Option<(i32, i32)>>>,
+    // Scale factor (f32 bits) of the streamed display, last sent to the client
+    scale_factor: Arc<AtomicU32>,
+    // Content profile the client asked for, and the one its display last told it about
+    content_profile: Arc<Mutex<ContentProfile>>,
+    profile_sent: Arc<Mutex<Option<ContentProfile>>>,
+    // Physical pixels the client shows frames in, None until it says (full size)
+    viewport: Arc<Mutex<Option<(u32, u32)>>>,
+    // Part of the frame the client wants kept exact under foveation
+    lossless_region: Arc<Mutex<Option<Region>>>,
+    // Simulcast layer of its display the client is sent, 0 for the main stream
+    layer: Arc<AtomicUsize>,
+    // Set between StopStream and StartStream, while the client isn't showing the stream
+    paused: Arc<AtomicBool>,
 }
 
 impl Server {
     pub fn new(access_code: Arc<RwLock<Option<AccessCode>>>) -> Self {
         Server { 
             access_code,
+            access_codes: Arc::new(AccessCodes::new()),
             sessions: Arc::new(RwLock::new(HashMap::new())),
+            idle_timeout: None,
+            capture_backend: CaptureBackend::Auto,
+            encoder_tuning: EncoderTuning::default(),
+            fps: 30,
+            bitrate: DEFAULT_BITRATE,
+            max_sessions: None,
+            approval: ApprovalPolicy::AutoAccept,
+            approvals: Arc::new(Approvals::new()),
+            quality_bounds: Arc::new(std::sync::Mutex::new((QualityMode::Minimal, QualityMode::Ultra))),
+            allow_unencrypted: false,
+            plaintext_video: false,
+            rekey_policy: RekeyPolicy::default(),
+            foveation_radius: None,
+            cursor_overlay: false,
+            power_save: PowerSaveMode::Off,
+            simulcast: false,
+            safe_mode: false,
+            frame_checksums: false,
+            vsync: false,
+            on_damage: false,
+            display_controller: Arc::new(std::sync::Mutex::new(open_display_controller())),
+            socket_buffers: SocketBuffers::default(),
+            focus: Arc::new(std::sync::Mutex::new(None)),
+            frame_sources: None,
+            display_layout: None,
+            notification_sources: None,
+            metrics: Arc::new(PerformanceMetrics::new()),
+        }
+    }
+    
+    /// Record frame, byte and session counts into `metrics` rather than a private instance.
+    pub fn with_metrics(mut self, metrics: Arc<PerformanceMetrics>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+    
+    pub fn metrics(&self) -> Arc<PerformanceMetrics> {
+        self.metrics.clone()
+    }
+    
+    /// Capture display `n` from `factory(n)` instead of the screen.
+    pub fn with_frame_sources(mut self, factory: FrameSourceFactory) -> Self {
+        self.frame_sources = Some(factory);
+        self
+    }
+    
+    /// Place input on the desktop as `layout` arranges the displays, instead of asking the host.
+    pub fn with_display_layout(mut self, layout: DisplayLayout) -> Self {
+        self.display_layout = Some(layout);
+        self
+    }
+    
+    /// Send tiles further than `radius` pixels from the cursor at reduced precision.
+    pub fn with_foveation(mut self, radius: u32) -> Self {
+        self.foveation_radius = Some(radius);
+        self
+    }
+    
+    /// Capture frames without the cursor and have clients draw it, when every client
+    /// watching a display can and the capture backend allows it.
+    pub fn with_cursor_overlay(mut self) -> Self {
+        self.cursor_overlay = true;
+        self
+    }
+    
+    /// When capture saves battery on a quiet screen; off unless asked for.
+    pub fn with_power_save(mut self, mode: PowerSaveMode) -> Self {
+        self.power_save = mode;
+        self
+    }
+    
+    /// While a display has several H.264 viewers, also encode it at the lower qualities of
+    /// `DEFAULT_LAYERS` and send each viewer the layer its bandwidth allows, instead of
+    /// one stream for all of them. Costs an encode per layer.
+    pub fn with_simulcast(mut self) -> Self {
+        self.simulcast = true;
+        self
+    }
+    
+    /// Encode every frame as one software WebP image: no hardware encoder is loaded or
+    /// even probed, and no deltas are sent. The slowest pipeline, and the one to fall back
+    /// to when the others crash.
+    pub fn with_safe_mode(mut self) -> Self {
+        self.safe_mode = true;
+        self
+    }
+    
+    /// Send a checksum of each lossless frame, which clients compare with the frame they
+    /// reconstructed and flag when they differ. For debugging; hashing costs a pass over
+    /// every frame.
+    pub fn with_frame_checksums(mut self) -> Self {
+        self.frame_checksums = true;
+        self
+    }
+    
+    /// Capture each frame right after its display refreshes, so no frame is caught
+    /// mid-update. Follows the real refresh on macOS and Windows (see `vsync_supported`),
+    /// a 60 Hz timer elsewhere.
+    pub fn with_vsync(mut self) -> Self {
+        self.vsync = true;
+        self
+    }
+    
+    /// Capture and encode a frame only when the screen changes, so a still screen costs
+    /// nothing, instead of polling on a timer. Needs a capture backend that reports
+    /// changes (see `FrameSource::reports_damage`); displays whose backend can't are
+    /// polled as before.
+    pub fn with_capture_on_damage(mut self) -> Self {
+        self.on_damage = true;
+        self
+    }
+    
+    /// Carry out display control requests with `controller` instead of the host's own.
+    pub fn with_display_controller(mut self, controller: Box<dyn DisplayController>) -> Self {
+        self.display_controller = Arc::new(std::sync::Mutex::new(controller));
+        self
+    }
+    
+    /// Kernel send and receive buffer sizes for client connections, for links whose
+    /// bandwidth-delay product the system defaults can't keep full.
+    pub fn with_socket_buffers(mut self, buffers: SocketBuffers) -> Self {
+        self.socket_buffers = buffers;
+        self
+    }
+    
+    /// Let loopback clients skip encryption for benchmarking. `run` refuses to start with
+    /// this on anything but a loopback address.
+    pub fn with_unencrypted_sessions(mut self) -> Self {
+        self.allow_unencrypted = true;
+        self
+    }
+    
+    /// Send video unencrypted to authenticated clients that ask for it, for networks that
+    /// are already encrypted such as a VPN. Key exchange, authentication and input stay
+    /// encrypted. Unlike `with_unencrypted_sessions` this works on any address.
+    pub fn with_plaintext_video(mut self) -> Self {
+        self.plaintext_video = true;
+        self
+    }
+    
+    /// How much traffic each session key protects before both sides move to the next one.
+    pub fn with_rekey_policy(mut self, policy: RekeyPolicy) -> Self {
+        self.rekey_policy = policy;
+        self
+    }
+    
+    /// Capture rate and H.264 target bitrate (bits per second).
+    pub fn with_frame_rate(mut self, fps: u32, bitrate: u32) -> Self {
+        self.fps = fps;
+        self.bitrate = bitrate;
+        self
+    }
+    
+    /// Also accept these fixed codes, each granting its own permissions; the rotating
+    /// access code keeps granting full control.
+    pub fn with_access_codes(mut self, codes: AccessCodes) -> Self {
+        self.access_codes = Arc::new(codes);
+        self
+    }
+    
+    /// Refuse authentication once this many sessions are connected.
+    pub fn with_max_sessions(mut self, max_sessions: usize) -> Self {
+        self.max_sessions = Some(max_sessions);
+        self
+    }
+    
+    /// Whether clients with the access code also need the host to let them in, through
+    /// `ServerControl::answer_connection`. They get no frames and send no input until then.
+    pub fn with_approval(mut self, policy: ApprovalPolicy) -> Self {
+        self.approval = policy;
+        self
+    }
+    
+    /// Limit the quality modes sessions may use, whether chosen adaptively or by the client.
+    pub fn with_quality_bounds(self, min: QualityMode, max: QualityMode) -> Self {
+        *self.quality_bounds.lock().unwrap() = (min, max);
+        self
+    }
+    
+    pub fn with_capture_backend(mut self, backend: CaptureBackend) -> Self {
+        self.capture_backend = backend;
+        self
+    }
+    
+    pub fn with_encoder_tuning(mut self, tuning: EncoderTuning) -> Self {
+        self.encoder_tuning = tuning;
+        self
+    }
+    
+    /// Disconnect sessions that send no input events for `timeout`.
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+    
+    /// Forward the notifications `sources` yields to every connected client.
+    pub fn with_notifications(mut self, sources: NotificationSourceFactory) -> Self {
+        self.notification_sources = Some(sources);
+        self
+    }
+    
+    /// A handle for watching and steering this server while it runs.
+    pub fn control(&self) -> ServerControl {
+        ServerControl {
+            access_code: self.access_code.clone(),
+            sessions: self.sessions.clone(),
+            quality_bounds: self.quality_bounds.clone(),
+            approvals: self.approvals.clone(),
+            metrics: self.metrics.clone(),
         }
     }
     
     pub async fn run(&self, addr: &str) -> Result<()> {
-        let listener = TcpListener::bind(addr).await
+        let listener = socket::listen(addr, self.socket_buffers).await
             .context("Failed to bind to address")?;
+        self.serve(listener).await
+    }
+    
+    /// Like `run`, on a listener the caller has already bound.
+    ///
+    /// `run` binds inside the server, so a caller that lets the OS pick the port has no
+    /// way to learn it, nor to know when connections are accepted. Binding first (e.g.
+    /// to port 0) gives the caller the address up front, and clients can connect as soon
+    /// as this is called; the backlog holds them until the accept loop starts. The
+    /// listener is used as is, so `with_socket_buffers` doesn't apply to it.
+    pub async fn serve(&self, listener: TcpListener) -> Result<()> {
+        let clients = self.start(listener.local_addr()?)?;
+        
+        // Accept connections
+        loop {
+            let (socket, addr) = listener.accept().await?;
+            info!("New connection from: {}", addr);
+            if let Err(e) = socket::configure(&socket) {
+                warn!("Input from {} may be delayed: {:#}", addr, e);
+            }
+            let connection = match TcpConnection::with_read_capacity(socket, CONTROL_READ_CAPACITY) {
+                Ok(connection) => Arc::new(connection),
+                Err(e) => {
+                    warn!("Dropping connection from {}: {:#}", addr, e);
+                    continue;
+                }
+            };
+            
+            let clients = clients.clone();
+            tokio::spawn(async move {
+                if let Err(e) = handle_client(connection, Some(addr), clients).await {
+                    error!("Client handler error: {}", e);
+                }
+            });
+        }
+    }
+    
+    /// Like `run`, for clients connecting over QUIC.
+    pub async fn run_quic(&self, addr: SocketAddr) -> Result<()> {
+        let transport = QuicTransport::new_server(addr).await
+            .context("Failed to bind to address")?;
+        self.serve_quic(transport).await
+    }
+    
+    /// Like `serve`, for clients connecting over QUIC. Sessions are the same as over TCP,
+    /// except that real-time video goes out as datagrams and input may come in as them.
+    pub async fn serve_quic(&self, transport: QuicTransport) -> Result<()> {
+        let clients = self.start(transport.local_addr()?)?;
+        info!("Clients connect over QUIC");
+        
+        loop {
+            let connection = match transport.accept().await {
+                Ok(connection) => Arc::new(connection),
+                Err(e) => {
+                    warn!("Failed to accept a QUIC connection: {:#}", e);
+                    continue;
+                }
+            };
+            let addr = connection.remote_address();
+            info!("New connection from: {}", addr);
+            
+            let clients = clients.clone();
+            tokio::spawn(async move {
+                let result = handle_client(connection.clone(), Some(addr), clients).await;
+                // Streams are accepted by a task that keeps the connection open until it is closed
+                connection.close().await;
+                if let Err(e) = result {
+                    error!("Client handler error: {}", e);
+                }
+            });
+        }
+    }
+    
+    // Everything a server does besides accepting connections: capture, notifications,
+    // code refresh and idle checks. Gives what each connection's handler shares.
+    fn start(&self, addr: SocketAddr) -> Result<ClientContext> {
+        // Injected sources bring their own frames; a real backend needs a display
+        if self.frame_sources.is_none() {
+            check_display_available(self.capture_backend)?;
+        }
+        
+        if self.allow_unencrypted {
+            if !addr.ip().is_loopback() {
+                return Err(anyhow::anyhow!(
+                    "--no-encryption is only allowed on loopback addresses (e.g. 127.0.0.1), not {}", addr
+                ));
+            }
+            warn!("Encryption may be disabled by loopback clients; do not use this outside benchmarking");
+        }
         
         info!("Server listening on {}", addr);
         
         // Start screen capture thread
         let (_frame_tx, _frame_rx) = mpsc::unbounded_channel::<Vec<u8>>();
         let sessions = self.sessions.clone();
+        let settings = CaptureSettings {
+            tuning: self.encoder_tuning,
+            fps: self.fps,
+            bitrate: self.bitrate,
+            foveation_radius: self.foveation_radius,
+            cursor_overlay: self.cursor_overlay,
+            power_save: self.power_save,
+            simulcast: self.simulcast,
+            safe_mode: self.safe_mode,
+            frame_checksums: self.frame_checksums,
+            on_damage: self.on_damage,
+        };
+        let focus = self.focus.clone();
+        let mut frame_sources = self.frame_sources.clone().unwrap_or_else(|| {
+            let backend = self.capture_backend;
+            Arc::new(move |display| open_frame_source(backend, display))
+        });
+        if self.vsync {
+            frame_sources = vsync_sources(frame_sources);
+        }
+        
+        // Decide once what we can produce; every session negotiates against this
+        let server_capabilities = Arc::new(server_capabilities(self.safe_mode));
+        info!("Server encodings: {:?}", server_capabilities.encodings);
+        let capture_capabilities = server_capabilities.clone();
+        let metrics = self.metrics.clone();
+        
+        // Injected sources aren't the host's displays, so only the real screen is asked
+        let display_layout = Arc::new(self.display_layout.clone().unwrap_or_else(|| {
+            if self.frame_sources.is_none() {
+                detect_display_layout()
+            } else {
+                DisplayLayout::default()
+            }
+        }));
+        if display_layout.monitors.len() > 1 {
+            info!("Display layout: {:?}", display_layout.monitors);
+        }
         
         // Spawn screen capture task
         tokio::spawn(async move {
-            if let Err(e) = screen_capture_loop(sessions).await {
+            if let Err(e) = screen_capture_loop(sessions, settings, frame_sources, focus, capture_capabilities, metrics).await {
                 error!("Screen capture error: {}", e);
             }
         });
         
-        // Accept connections
-        loop {
-            let (socket, addr) = listener.accept().await?;
-            info!("New connection from: {}", addr);
-            
-            let access_code = self.access_code.clone();
+        if let Some(sources) = self.notification_sources.clone() {
+            let (notification_tx, notification_rx) = mpsc::unbounded_channel();
+            std::thread::spawn(move || read_notifications(sources, notification_tx));
+            tokio::spawn(forward_notifications(notification_rx, self.sessions.clone()));
+        }
+        
+        // An expired code nobody is using is replaced, so new clients can still get in
+        tokio::spawn(code_refresh_loop(self.control()));
+        
+        // Spawn idle session monitor if enabled
+        let idle_monitor = self.idle_timeout.map(|timeout| {
+            info!("Idle sessions will be disconnected after {:?}", timeout);
+            Arc::new(Mutex::new(IdleMonitor::new(timeout)))
+        });
+        
+        if let Some(monitor) = &idle_monitor {
             let sessions = self.sessions.clone();
-            
+            let monitor = monitor.clone();
             tokio::spawn(async move {
-                if let Err(e) = handle_client(socket, access_code, sessions).await {
-                    error!("Client handler error: {}", e);
+                idle_disconnect_loop(sessions, monitor).await;
+            });
+        }
+        
+        Ok(ClientContext {
+            access_code: self.access_code.clone(),
+            sessions: self.sessions.clone(),
+            idle_monitor,
+            server_capabilities,
+            limits: SessionLimits {
+                max_sessions: self.max_sessions,
+                access_codes: self.access_codes.clone(),
+                quality_bounds: self.quality_bounds.clone(),
+                allow_unencrypted: self.allow_unencrypted,
+                plaintext_video: self.plaintext_video,
+                rekey_policy: self.rekey_policy,
+                approval: self.approval,
+                approvals: self.approvals.clone(),
+                display_controller: self.display_controller.clone(),
+            },
+            focus: self.focus.clone(),
+            metrics: self.metrics.clone(),
+            display_layout,
+        })
+    }
+}
+
+/// One connected session, as seen through `ServerControl`.
+#[derive(Debug, Clone)]
+pub struct SessionSummary {
+    pub id: String,
+    pub peer: Option<SocketAddr>,
+    pub permissions: Permissions,
+    pub display: usize,
+    pub quality: QualityMode,
+    pub stats: SessionStats,
+}
+
+/// Shared access to a running server's access code, sessions and quality bounds, for
+/// a control panel or anything else hosting the server in-process.
+#[derive(Clone)]
+pub struct ServerControl {
+    access_code: Arc<RwLock<Option<AccessCode>>>,
+    sessions: Arc<RwLock<HashMap<String, ClientSession>>>,
+    quality_bounds: Arc<std::sync::Mutex<(QualityMode, QualityMode)>>,
+    approvals: Arc<Approvals>,
+    metrics: Arc<PerformanceMetrics>,
+}
+
+impl ServerControl {
+    pub async fn access_code(&self) -> Option<AccessCode> {
+        self.access_code.read().await.clone()
+    }
+    
+    /// Replace the access code; the old one stops working, connected sessions stay.
+    pub async fn regenerate_access_code(&self) -> AccessCode {
+        let code = AccessCode::generate();
+        *self.access_code.write().await = Some(code.clone());
+        info!("=================================");
+        info!("New Access Code: {}", code.code);
+        info!("Code expires in {} minutes", code.time_left().as_secs().div_ceil(60));
+        info!("=================================");
+        code
+    }
+    
+    /// Connected sessions, ordered by id so a listing stays put between calls.
+    pub async fn sessions(&self) -> Vec<SessionSummary> {
+        let stats: HashMap<String, SessionStats> = self.metrics.session_stats().into_iter().collect();
+        let mut summaries = Vec::new();
+        for session in self.sessions.read().await.values() {
+            summaries.push(SessionSummary {
+                id: session.core.id.clone(),
+                peer: session.peer,
+                permissions: session.permissions,
+                display: session.display.load(Ordering::Relaxed),
+                quality: session.core.quality_controller.lock().await.get_current_quality(),
+                stats: stats.get(&session.core.id).cloned().unwrap_or_default(),
+            });
+        }
+        summaries.sort_by(|a, b| a.id.cmp(&b.id));
+        summaries
+    }
+    
+    /// Tell a session's client it is being disconnected and close it. False if there is no such session.
+    pub async fn disconnect(&self, id: &str) -> bool {
+        let Some(session) = self.sessions.write().await.remove(id) else {
+            return false;
+        };
+        info!("Disconnecting session {} from the control panel", id);
+        if let Ok(serialized) = Message::Disconnect.serialize() {
+            session.core.send_serialized(&serialized, Priority::Control).await;
+        }
+        session.disconnect.notify_one();
+        true
+    }
+    
+    /// Authenticated clients waiting for the host to let them in, oldest first.
+    pub fn pending_connections(&self) -> Vec<ConnectionRequest> {
+        self.approvals.pending()
+    }
+    
+    /// Let a waiting client in or turn it away. False if it is no longer waiting.
+    pub fn answer_connection(&self, id: u64, approve: bool) -> bool {
+        let answered = self.approvals.decide(id, approve);
+        if answered {
+            info!("Connection request {} {}", id, if approve { "approved" } else { "declined" });
+        }
+        answered
+    }
+    
+    /// Wait until a client asks to be let in.
+    pub async fn connection_requested(&self) {
+        self.approvals.requested().await;
+    }
+    
+    pub fn quality_bounds(&self) -> (QualityMode, QualityMode) {
+        *self.quality_bounds.lock().unwrap()
+    }
+    
+    /// Bound the quality of new sessions and of every connected one.
+    pub async fn set_quality_bounds(&self, min: QualityMode, max: QualityMode) -> Result<()> {
+        if min.bandwidth_requirement() > max.bandwidth_requirement() {
+            return Err(anyhow::anyhow!("Minimum quality {:?} is higher than maximum {:?}", min, max));
+        }
+        *self.quality_bounds.lock().unwrap() = (min, max);
+        for session in self.sessions.read().await.values() {
+            session.core.quality_controller.lock().await.set_quality_bounds(min, max);
+        }
+        info!("Quality bounds set to {:?}..={:?}", min, max);
+        Ok(())
+    }
+}
+
+// Per-session policy handed to each connection handler
+#[derive(Clone)]
+struct SessionLimits {
+    max_sessions: Option<usize>,
+    access_codes: Arc<AccessCodes>,
+    quality_bounds: Arc<std::sync::Mutex<(QualityMode, QualityMode)>>,
+    allow_unencrypted: bool,
+    plaintext_video: bool,
+    rekey_policy: RekeyPolicy,
+    approval: ApprovalPolicy,
+    approvals: Arc<Approvals>,
+    display_controller: Arc<std::sync::Mutex<Box<dyn DisplayController>>>,
+}
+
+// What every connection's handler shares, cloned for each one
+#[derive(Clone)]
+struct ClientContext {
+    access_code: Arc<RwLock<Option<AccessCode>>>,
+    sessions: Arc<RwLock<HashMap<String, ClientSession>>>,
+    idle_monitor: Option<Arc<Mutex<IdleMonitor>>>,
+    server_capabilities: Arc<Capabilities>,
+    limits: SessionLimits,
+    focus: Arc<std::sync::Mutex<Option<(u32, u32)>>>,
+    metrics: Arc<PerformanceMetrics>,
+    display_layout: Arc<DisplayLayout>,
+}
+
+// What the capture thread is built from
+#[derive(Clone, Copy)]
+struct CaptureSettings {
+    tuning: EncoderTuning,
+    fps: u32,
+    bitrate: u32,
+    foveation_radius: Option<u32>,
+    cursor_overlay: bool,
+    power_save: PowerSaveMode,
+    simulcast: bool,
+    safe_mode: bool,
+    frame_checksums: bool,
+    on_damage: bool,
+}
+
+async fn handle_client<T: Transport + 'static>(
+    transport: Arc<T>,
+    peer: Option<SocketAddr>,
+    context: ClientContext,
+) -> Result<()> {
+    let ClientContext { access_code, sessions, idle_monitor, server_capabilities, limits, focus, metrics, display_layout } = context;
+    let peer_is_loopback = peer.map_or(false, |addr| addr.ip().is_loopback());
+    let mut crypto_session: Option<Arc<Mutex<Box<dyn Encryptor>>>> = None;
+    // Until the client offers something else
+    let mut cipher = Cipher::Aes256Gcm;
+    let mut session_id: Option<String> = None;
+    // Display picked before authentication, so the session never sees another one
+    let mut requested_display = 0usize;
+    // Messages in a row that failed to decrypt; a few are dropped, a run of them ends the session
+    let mut corrupt_messages = 0u32;
+    let queue = Arc::new(SendQueue::new(SEND_QUEUE_CAPACITY));
+    let disconnect = Arc::new(Notify::new());
+    // Input arrives in captured pixels, converted with the streamed display's scale factor
+    let scale_factor = Arc::new(AtomicU32::new(1.0f32.to_bits()));
+    // Keys the client holds down, released again if it goes away mid-press
+    let mut held_keys = HeldKeys::new();
+    // Where the client has a finger down, lifted if it goes away mid-touch
+    let mut touch_down: Option<(i32, i32)> = None;
+    // Set once the client was told the server may not inject its input
+    let mut input_denied = false;
+    // Granted by the code the client authenticates with; nothing before that
+    let mut permissions = Permissions::VIEW_ONLY;
+    // Whether video already goes out unencrypted at the client's request
+    let mut plaintext_video = false;
+    // Takes input sent as datagrams, once there is a session for it
+    let mut input_receiver: Option<QuicInputReceiver<T>> = None;
+    
+    // Writes what the session queues, video as datagrams where the transport has them
+    let sender = TransportSender::spawn(transport.clone(), queue.clone(), disconnect.clone());
+    
+    // Handle incoming messages. Errors end the session like a disconnect does, so the
+    // cleanup below runs however the client went away.
+    let result: Result<()> = async {
+        // Kept between messages, so one half read when a datagram comes in isn't lost
+        let recv = transport.recv();
+        tokio::pin!(recv);
+        // Cleared once the transport has no more datagrams to give
+        let mut datagrams_open = true;
+        
+        'session: loop {
+            // The next message to decrypt, if it came on the connection
+            let mut next: Option<Vec<u8>> = None;
+            
+            // Messages of a batch, or input from a datagram, still to be handled in order
+            let mut unpacked: VecDeque<Message> = VecDeque::new();
+            
+            tokio::select! {
+                result = &mut recv => {
+                    recv.set(transport.recv());
+                    match result {
+                        Ok(data) => {
+                            metrics.add_bytes_received(data.len() + LENGTH_PREFIX);
+                            next = Some(data);
+                        }
+                        Err(e) if is_closed(&e) => {
+                            info!("Client {:?} disconnected {}", peer, handshake_stage(crypto_session.is_some(), session_id.is_some()));
+                            break;
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+                result = transport.recv_datagram(), if datagrams_open => match result {
+                    Ok(data) => {
+                        metrics.add_bytes_received(data.len());
+                        // Only input is taken this way
+                        if let Some(receiver) = &input_receiver {
+                            match receiver.handle_datagram(&data).await {
+                                Ok(events) => unpacked.extend(events.into_iter().filter(is_input)),
+                                Err(e) => debug!("Dropping bad input datagram: {}", e),
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        debug!("No more datagrams from {:?}: {}", peer, e);
+                        datagrams_open = false;
+                    }
+                },
+                _ = disconnect.notified() => {
+                    info!("Closing session on the server's request");
+                    break;
+                }
+            }
+            
+            // Moves queued behind each other are coalesced, only the latest one is executed
+            let mut pending_move: Option<(i32, i32)> = None;
+            
+            loop {
+                let message = if let Some(message) = unpacked.pop_front() {
+                    message
+                } else {
+                    let Some(msg_data) = next.take() else {
+                        break; // Wait for the next message
+                    };
+                    
+                    // Decrypt if we have a crypto session; key rotations are applied without a message
+                    if let Some(crypto) = &crypto_session {
+                        let crypto = crypto.lock().await;
+                        match open_message(&**crypto, &msg_data) {
+                            Ok(opened) => {
+                                corrupt_messages = 0;
+                                match opened {
+                                    Some(message) => message,
+                                    None => continue,
+                                }
+                            }
+                            Err(e) if is_corrupt(&e) && corrupt_messages < MAX_CORRUPT_MESSAGES => {
+                                corrupt_messages += 1;
+                                warn!("Dropping message from client: {}", e);
+                                continue;
+                            }
+                            Err(e) => return Err(e),
+                        }
+                    } else {
+                        Message::deserialize(&msg_data)?
+                    }
+                };
+                
+                if let Message::Batch { .. } = message {
+                    unpacked.extend(unpack(message));
+                    continue;
+                }
+                
+                if let Message::MouseMove { x, y } = message {
+                    record_input(&idle_monitor, &session_id).await;
+                    // A viewer's input keeps its session from idling out but goes no further
+                    if !permissions.input {
+                        continue;
+                    }
+                    if session_id.is_some() {
+                        *focus.lock().unwrap() = Some((x.max(0) as u32, y.max(0) as u32));
+                    }
+                    pending_move = Some((x, y));
+                    continue;
+                }
+                
+                // Anything else runs after the moves queued before it
+                if let Some((x, y)) = pending_move.take() {
+                    let moved = handle_mouse_move(x, y, load_scale(&scale_factor), display_layout.origin(requested_display)).await;
+                    check_input(moved, &mut input_denied, &queue, &crypto_session).await?;
+                }
+                
+                match message {
+                    Message::AuthRequest { code } => {
+                        // Ensure key exchange has happened first
+                        if crypto_session.is_none() {
+                            error!("Authentication attempted before key exchange");
+                            return Err(anyhow::anyhow!("Key exchange must happen before authentication"));
+                        }
+                        
+                        let session_count = sessions.read().await.len();
+                        let mut response = authenticate(&code, &access_code, &limits.access_codes, session_count, limits.max_sessions).await;
+                        
+                        // Held here, with no session to get frames or send input, until the host answers
+                        if let (true, ApprovalPolicy::Ask { timeout }) = (response.success, limits.approval) {
+                            info!("Waiting for the host to approve a connection from {:?}", peer);
+                            let outcome = limits.approvals.request(peer, timeout).await;
+                            if outcome != ApprovalOutcome::Approved {
+                                warn!("Turned away a connection from {:?}: {}", peer, outcome.description());
+                                response.success = false;
+                            }
+                        }
+                        
+                        if response.success {
+                            // Store session with the current crypto session
+                            let bounds = *limits.quality_bounds.lock().unwrap();
+                            let core = Session::new(crypto_session.as_ref().unwrap().clone(), queue.clone(), bounds);
+                            let id = core.id.clone();
+                            let token_string = core.token.token.clone();
+                            session_id = Some(id.clone());
+                            input_receiver = Some(QuicInputReceiver::new(transport.clone(), crypto_session.as_ref().unwrap().clone()));
+                            permissions = response.permissions.unwrap_or(Permissions::VIEW_ONLY);
+                            if !permissions.input {
+                                info!("Session {} is view-only", id);
+                            }
+                            
+                            let session = ClientSession {
+                                core,
+                                peer,
+                                permissions,
+                                last_frame_time: Arc::new(Mutex::new(std::time::Instant::now())),
+                                progressive_rendering: Arc::new(AtomicBool::new(false)),
+                                disconnect: disconnect.clone(),
+                                capabilities: Arc::new(Mutex::new(Capabilities::baseline())),
+                                needs_keyframe: Arc::new(AtomicBool::new(true)),
+                                pending_tiles: Arc::new(Mutex::new(Vec::new())),
+                                display: Arc::new(AtomicUsize::new(requested_display)),
+                                cursor_overlay: Arc::new(AtomicBool::new(false)),
+                                cursor_sent: Arc::new(Mutex::new(None)),
+                                scale_factor: scale_factor.clone(),
+                                content_profile: Arc::new(Mutex::new(ContentProfile::default())),
+                                profile_sent: Arc::new(Mutex::new(None)),
+                                viewport: Arc::new(Mutex::new(None)),
+                                lossless_region: Arc::new(Mutex::new(None)),
+                                layer: Arc::new(AtomicUsize::new(0)),
+                                paused: Arc::new(AtomicBool::new(false)),
+                            };
+                            
+                            // View-only sessions can't send input, so they would always look idle
+                            if let Some(monitor) = idle_monitor.as_ref().filter(|_| permissions.input) {
+                                monitor.lock().await.register(&id, Instant::now());
+                            }
+                            
+                            metrics.session_started(&id);
+                            sessions.write().await.insert(id, session);
+                            
+                            let auth_resp = Message::AuthResponse {
+                                success: true,
+                                session_token: Some(token_string),
+                            };
+                            
+                            send_encrypted(&queue, &auth_resp, &crypto_session).await?;
+                            
+                            if !display_layout.monitors.is_empty() {
+                                let layout = Message::DisplayLayout { monitors: display_layout.monitors.clone() };
+                                send_encrypted(&queue, &layout, &crypto_session).await?;
+                            }
+                        } else {
+                            let auth_resp = Message::AuthResponse {
+                                success: false,
+                                session_token: None,
+                            };
+                            
+                            send_encrypted(&queue, &auth_resp, &crypto_session).await?;
+                            // One guess per connection, so fixed codes can't be tried one after another
+                            warn!("Failed authentication from {:?}, closing the connection", peer);
+                            break 'session;
+                        }
+                    }
+                    
+                    Message::CipherOffer { ciphers } if crypto_session.is_none() => {
+                        cipher = negotiate_cipher(&Cipher::preferred(), &ciphers).unwrap_or_else(|| {
+                            warn!("No common cipher with client {:?}, using AES-256-GCM", ciphers);
+                            Cipher::Aes256Gcm
+                        });
+                        debug!("Negotiated {} encryption", cipher.name());
+                        queue.push(Message::CipherSelected { cipher }.serialize()?, Priority::Control)?;
+                    }
+                    
+                    Message::KeyExchange { public_key } => {
+                        // Perform key exchange
+                        let key_exchange = KeyExchange::new();
+                        let our_public = key_exchange.public_key_bytes();
+                        
+                        // Compute shared secret, refusing a weak key before answering
+                        let shared_secret = match key_exchange.agree(&public_key) {
+                            Ok(shared_secret) => shared_secret,
+                            Err(e) => {
+                                warn!("Refusing key exchange from {:?}: {:#}", peer, e);
+                                break 'session;
+                            }
+                        };
+                        
+                        // Send our public key
+                        let response = Message::KeyExchangeAck {
+                            public_key: our_public.to_vec(),
+                        };
+                        
+                        queue.push(response.serialize()?, Priority::Control)?;
+                        
+                        // Create crypto session
+                        let crypto: Box<dyn Encryptor> = Box::new(
+                            CryptoSession::from_shared_secret(&shared_secret)?
+                                .with_cipher(cipher)
+                                .with_rekey_policy(limits.rekey_policy)
+                        );
+                        crypto_session = Some(Arc::new(Mutex::new(crypto)));
+                        
+                        debug!("Key exchange completed");
+                    }
+                    
+                    Message::DisableEncryption => {
+                        let accepted = limits.allow_unencrypted && peer_is_loopback && crypto_session.is_none();
+                        queue.push(Message::DisableEncryptionAck { accepted }.serialize()?, Priority::Control)?;
+                        
+                        if accepted {
+                            warn!("Session is NOT encrypted (--no-encryption)");
+                            let crypto: Box<dyn Encryptor> = Box::new(NullEncryptor);
+                            crypto_session = Some(Arc::new(Mutex::new(crypto)));
+                        } else {
+                            warn!("Refused a request to disable encryption");
+                        }
+                    }
+                    
+                    Message::PlaintextVideo if session_id.is_some() => {
+                        let accepted = limits.plaintext_video && !plaintext_video;
+                        if let Some(crypto) = &crypto_session {
+                            // Held until switched so no video is sealed the old way after the ack
+                            let mut crypto = crypto.lock().await;
+                            let ack = Message::PlaintextVideoAck { accepted }.serialize()?;
+                            let frames = seal_message(&**crypto, &ack)?;
+                            discard_video_on_rekey(&queue, &frames);
+                            for frame in frames {
+                                queue.push(frame, Priority::Control)?;
+                            }
+                            
+                            if accepted {
+                                warn!("Sending video to {:?} UNENCRYPTED (--plaintext-video)", peer);
+                                // The ack overtakes queued video, which the client would then misread
+                                queue.discard_video();
+                                let inner = std::mem::replace(&mut *crypto, Box::new(NullEncryptor));
+                                *crypto = Box::new(PlaintextVideo::sender(inner));
+                                plaintext_video = true;
+                            } else {
+                                warn!("Refused a request from {:?} for unencrypted video", peer);
+                            }
+                        }
+                    }
+                    
+                    Message::Capabilities { encodings } => {
+                        if let Some(id) = &session_id {
+                            if let Some(session) = sessions.read().await.get(id) {
+                                let client_capabilities = Capabilities::new(encodings);
+                                let encoding = negotiate(&server_capabilities, &client_capabilities)
+                                    .unwrap_or_else(|| {
+                                        warn!("No common encoding with client {:?}, using zstd", client_capabilities.encodings);
+                                        EncodingType::ZstdCompressed
+                                    });
+                                info!("Negotiated {:?} encoding", encoding);
+                                *session.capabilities.lock().await = client_capabilities;
+                                
+                                let msg = Message::EncodingSelected { encoding };
+                                send_encrypted(&queue, &msg, &crypto_session).await?;
+                            }
+                        }
+                    }
+                    
+                    Message::StartStream => {
+                        info!("Client requested stream start");
+                        // Send initial quality mode
+                        if let Some(id) = &session_id {
+                            if let Some(session) = sessions.read().await.get(id) {
+                                // Whatever the client last saw is stale after a pause
+                                if session.paused.swap(false, Ordering::Relaxed) {
+                                    info!("Session {} resumed its stream", id);
+                                    session.pending_tiles.lock().await.clear();
+                                    session.needs_keyframe.store(true, Ordering::Relaxed);
+                                }
+                                let quality = session.core.quality_controller.lock().await.get_current_quality();
+                                let msg = Message::QualityChange { mode: quality, reason: QualityChangeReason::Initial };
+                                send_encrypted(&queue, &msg, &crypto_session).await?;
+                            }
+                        }
+                    }
+                    
+                    Message::StopStream => {
+                        info!("Client requested stream stop");
+                        // No frames until StartStream; a display nobody else watches stops capturing
+                        if let Some(id) = &session_id {
+                            if let Some(session) = sessions.read().await.get(id) {
+                                session.paused.store(true, Ordering::Relaxed);
+                            }
+                        }
+                    }
+                    
+                    Message::SetProgressiveRendering { enabled } => {
+                        if let Some(id) = &session_id {
+                            if let Some(session) = sessions.read().await.get(id) {
+                                debug!("Progressive rendering {}", if enabled { "enabled" } else { "disabled" });
+                                session.progressive_rendering.store(enabled, Ordering::Relaxed);
+                            }
+                        }
+                    }
+                    
+                    Message::SetCursorOverlay { enabled } => {
+                        if let Some(id) = &session_id {
+                            if let Some(session) = sessions.read().await.get(id) {
+                                debug!("Cursor overlay {}", if enabled { "supported" } else { "not supported" });
+                                session.cursor_overlay.store(enabled, Ordering::Relaxed);
+                            }
+                        }
+                    }
+                    
+                    Message::RequestKeyframe => {
+                        if let Some(id) = &session_id {
+                            if let Some(session) = sessions.read().await.get(id) {
+                                if session.core.request_keyframe(Instant::now()) {
+                                    info!("Session {} asked for a full refresh", id);
+                                    // Tiles held back for this client are superseded by the keyframe
+                                    session.pending_tiles.lock().await.clear();
+                                    session.needs_keyframe.store(true, Ordering::Relaxed);
+                                } else {
+                                    debug!("Ignoring a keyframe request that came too soon after the last");
+                                }
+                            }
+                        }
+                    }
+                    
+                    Message::SelectDisplay { index } => {
+                        let index = index as usize;
+                        // Capturing a display that doesn't exist would only fail and retry
+                        if !display_layout.monitors.is_empty() && index >= display_layout.monitors.len() {
+                            warn!("{:?} selected display {}, but there are only {}", peer, index, display_layout.monitors.len());
+                            let message = format!("No display {}; this server has {}", index, display_layout.monitors.len());
+                            send_encrypted(&queue, &Message::Error { message, kind: ErrorKind::Other }, &crypto_session).await?;
+                            continue;
+                        }
+                        requested_display = index;
+                        if let Some(id) = &session_id {
+                            if let Some(session) = sessions.read().await.get(id) {
+                                if session.display.swap(index, Ordering::Relaxed) != index {
+                                    info!("Session {} switched to display {}", id, index);
+                                    // Nothing sent from the old display applies to the new one
+                                    session.pending_tiles.lock().await.clear();
+                                    session.needs_keyframe.store(true, Ordering::Relaxed);
+                                }
+                            }
+                        }
+                    }
+                    
+                    Message::DisplayControl { action } if session_id.is_some() => {
+                        info!("Display control from {:?}: {:?}", peer, action);
+                        let controller = limits.display_controller.clone();
+                        let permitted = permissions.display_control;
+                        let reply = tokio::task::spawn_blocking(move || {
+                            display_control_reply(controller.lock().unwrap().as_mut(), permitted, action)
+                        }).await?;
+                        send_encrypted(&queue, &reply, &crypto_session).await?;
+                    }
+                    
+                    Message::MouseClick { button, pressed, x, y } => {
+                        record_input(&idle_monitor, &session_id).await;
+                        if !permissions.input {
+                            continue;
+                        }
+                        let clicked = handle_mouse_click(button, pressed, x, y, load_scale(&scale_factor), display_layout.origin(requested_display)).await;
+                        check_input(clicked, &mut input_denied, &queue, &crypto_session).await?;
+                    }
+                    
+                    Message::Touch { phase, x, y, pressure, .. } => {
+                        record_input(&idle_monitor, &session_id).await;
+                        if !permissions.input {
+                            continue;
+                        }
+                        touch_down = matches!(phase, TouchPhase::Start | TouchPhase::Move).then_some((x, y));
+                        let touched = handle_touch(phase, x, y, pressure, load_scale(&scale_factor), display_layout.origin(requested_display)).await;
+                        check_input(touched, &mut input_denied, &queue, &crypto_session).await?;
+                    }
+                    
+                    Message::Gesture { gesture, x, y } => {
+                        record_input(&idle_monitor, &session_id).await;
+                        if !permissions.input {
+                            continue;
+                        }
+                        let played = handle_gesture(gesture, x, y, load_scale(&scale_factor), display_layout.origin(requested_display)).await;
+                        check_input(played, &mut input_denied, &queue, &crypto_session).await?;
+                    }
+                    
+                    Message::KeyEvent { key, pressed, repeat, modifiers } => {
+                        record_input(&idle_monitor, &session_id).await;
+                        if !permissions.input {
+                            continue;
+                        }
+                        if held_keys.accept(HeldKey::Named(key.clone()), pressed, repeat) {
+                            let typed = handle_key_event(&key, pressed, modifiers).await;
+                            check_input(typed, &mut input_denied, &queue, &crypto_session).await?;
+                        }
+                    }
+                    
+                    Message::PhysicalKeyEvent { usage, pressed, repeat, modifiers } => {
+                        record_input(&idle_monitor, &session_id).await;
+                        if !permissions.input {
+                            continue;
+                        }
+                        if held_keys.accept(HeldKey::Physical(usage), pressed, repeat) {
+                            let typed = handle_physical_key_event(usage, pressed, modifiers).await;
+                            check_input(typed, &mut input_denied, &queue, &crypto_session).await?;
+                        }
+                    }
+                    
+                    Message::FrameAck { timestamp, received_at } => {
+                        // Update quality metrics
+                        if let Some(id) = &session_id {
+                            if let Some(session) = sessions.read().await.get(id) {
+                                session.core.frame_acked(timestamp);
+                                let rtt = received_at.saturating_sub(timestamp);
+                                let mut controller = session.core.quality_controller.lock().await;
+                                controller.update_metrics(0, std::time::Duration::from_millis(rtt));
+                            }
+                        }
+                    }
+                    
+                    Message::RequestQualityChange { mode } => {
+                        if let Some(id) = &session_id {
+                            if let Some(session) = sessions.read().await.get(id) {
+                                let mut controller = session.core.quality_controller.lock().await;
+                                controller.force_quality(Some(mode));
+                                
+                                // Send confirmation
+                                let msg = Message::QualityChange { mode, reason: QualityChangeReason::Manual };
+                                send_encrypted(&queue, &msg, &crypto_session).await?;
+                            }
+                        }
+                    }
+                    
+                    Message::SetContentProfile { profile } => {
+                        if let Some(id) = &session_id {
+                            if let Some(session) = sessions.read().await.get(id) {
+                                debug!("Session {} asked for the {:?} content profile", id, profile);
+                                *session.content_profile.lock().await = profile;
+                            }
+                        }
+                    }
+                    
+                    Message::ViewportResize { width, height } => {
+                        if let Some(id) = &session_id {
+                            if let Some(session) = sessions.read().await.get(id) {
+                                debug!("Session {} shows frames in {}x{}", id, width, height);
+                                *session.viewport.lock().await = (width > 0 && height > 0).then_some((width, height));
+                            }
+                        }
+                    }
+                    
+                    Message::LosslessRegion { rect } => {
+                        if let Some(id) = &session_id {
+                            if let Some(session) = sessions.read().await.get(id) {
+                                debug!("Session {} asked for lossless region {:?}", id, rect);
+                                *session.lossless_region.lock().await = rect;
+                            }
+                        }
+                    }
+                    
+                    Message::SetDeliveryMode { mode } => {
+                        if session_id.is_some() {
+                            debug!("Client asked for {:?} frame delivery", mode);
+                            queue.set_delivery(mode);
+                            send_encrypted(&queue, &Message::DeliveryModeApplied { mode }, &crypto_session).await?;
+                        }
+                    }
+                    
+                    Message::Disconnect => {
+                        info!("Client disconnecting");
+                        break;
+                    }
+                    
+                    _ => {
+                        debug!("Unhandled message type");
+                    }
+                }
+            }
+            
+            if let Some((x, y)) = pending_move.take() {
+                let moved = handle_mouse_move(x, y, load_scale(&scale_factor), display_layout.origin(requested_display)).await;
+                check_input(moved, &mut input_denied, &queue, &crypto_session).await?;
+            }
+        }
+        Ok(())
+    }.await;
+    let stage = handshake_stage(crypto_session.is_some(), session_id.is_some());
+    
+    // Cleanup
+    for release in held_keys.release_all() {
+        let released = match release {
+            Message::KeyEvent { key, modifiers, .. } => handle_key_event(&key, false, modifiers).await,
+            Message::PhysicalKeyEvent { usage, modifiers, .. } => handle_physical_key_event(usage, false, modifiers).await,
+            _ => Ok(()),
+        };
+        if let Err(e) = released {
+            warn!("Failed to release a held key: {}", e);
+        }
+    }
+    if let Some((x, y)) = touch_down {
+        let lifted = handle_touch(TouchPhase::Cancel, x, y, None, load_scale(&scale_factor), display_layout.origin(requested_display)).await;
+        if let Err(e) = lifted {
+            warn!("Failed to lift a touch: {}", e);
+        }
+    }
+    if let Some(id) = session_id {
+        sessions.write().await.remove(&id);
+        metrics.session_ended(&id);
+        if let Some(monitor) = &idle_monitor {
+            monitor.lock().await.remove(&id);
+        }
+    }
+    
+    // Give the writer a moment to flush anything already queued (e.g. a final Disconnect)
+    sender.close(Duration::from_millis(500)).await;
+    result.with_context(|| format!("Client {:?} dropped {}", peer, stage))
+}
+
+// How far a client got, for logging where it went away
+fn handshake_stage(key_exchanged: bool, authenticated: bool) -> &'static str {
+    match (key_exchanged, authenticated) {
+        (false, _) => "before key exchange",
+        (true, false) => "before authenticating",
+        (true, true) => "after authenticating",
+    }
+}
+
+async fn record_input(idle_monitor: &Option<Arc<Mutex<IdleMonitor>>>, session_id: &Option<String>) {
+    if let (Some(monitor), Some(id)) = (idle_monitor, session_id) {
+        monitor.lock().await.record_input(id, Instant::now());
+    }
+}
+
+// Runs on its own thread, as sources block until the host shows something
+fn read_notifications(sources: NotificationSourceFactory, tx: mpsc::UnboundedSender<HostNotification>) {
+    let mut source = match sources() {
+        Ok(source) => source,
+        Err(e) => {
+            warn!("Notifications won't be forwarded: {:#}", e);
+            return;
+        }
+    };
+    info!("Forwarding host notifications to clients");
+    
+    loop {
+        match source.next() {
+            Ok(Some(notification)) => {
+                if tx.send(notification).is_err() {
+                    return;
                 }
-            });
+            }
+            Ok(None) => {
+                warn!("Notification source ended, no longer forwarding notifications");
+                return;
+            }
+            Err(e) => {
+                warn!("Failed to read host notifications: {:#}", e);
+                return;
+            }
         }
     }
 }
 
-async fn handle_client(
-    socket: TcpStream,
-    access_code: Arc<RwLock<Option<AccessCode>>>,
+async fn forward_notifications(
+    mut rx: mpsc::UnboundedReceiver<HostNotification>,
     sessions: Arc<RwLock<HashMap<String, ClientSession>>>,
-) -> Result<()> {
-    let mut buffer = BytesMut::with_capacity(4096);
-    let mut crypto_session: Option<Arc<Mutex<CryptoSession>>> = None;
-    let mut session_id: Option<String> = None;
-    let (tx, mut rx) = mpsc::unbounded_channel::<Vec<u8>>();
+) {
+    let mut limiter = NotificationLimiter::new(Instant::now());
     
-    // Split socket for concurrent read/write
-    let (mut reader, mut writer) = socket.into_split();
-    
-    // Spawn task to handle outgoing messages
-    let writer_task = tokio::spawn(async move {
-        while let Some(data) = rx.recv().await {
-            if let Err(e) = send_message(&mut writer, &data).await {
-                error!("Failed to send message: {}", e);
-                break;
-            }
+    while let Some(notification) = rx.recv().await {
+        if !limiter.allow(Instant::now()) {
+            debug!("Dropping notification from {}, {} suppressed so far", notification.app, limiter.suppressed());
+            continue;
         }
-    });
+        
+        let HostNotification { app, title, body } = notification.truncated();
+        let Ok(serialized) = (Message::Notification { title, body, app }).serialize() else {
+            continue;
+        };
+        for session in sessions.read().await.values() {
+            session.core.send_serialized(&serialized, Priority::Control).await;
+        }
+    }
+}
+
+async fn code_refresh_loop(control: ServerControl) {
+    let mut interval = tokio::time::interval(CODE_CHECK_INTERVAL);
     
-    // Handle incoming messages
     loop {
-        // Read message length
-        if reader.read_buf(&mut buffer).await? == 0 {
-            break; // Connection closed
+        interval.tick().await;
+        
+        let expired = control.access_code().await.map_or(false, |code| code.is_expired());
+        if expired && control.sessions.read().await.is_empty() {
+            info!("Access code expired with no one connected");
+            control.regenerate_access_code().await;
         }
+    }
+}
+
+async fn idle_disconnect_loop(
+    sessions: Arc<RwLock<HashMap<String, ClientSession>>>,
+    monitor: Arc<Mutex<IdleMonitor>>,
+) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    
+    loop {
+        interval.tick().await;
         
-        while buffer.len() >= 4 {
-            let len = u32::from_be_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]) as usize;
-            
-            if buffer.len() < 4 + len {
-                break; // Wait for more data
-            }
-            
-            buffer.advance(4);
-            let msg_data = buffer.split_to(len).freeze();
-            
-            // Decrypt if we have a crypto session
-            let decrypted = if let Some(crypto) = &crypto_session {
-                let crypto = crypto.lock().await;
-                crypto.decrypt(&msg_data)?
-            } else {
-                msg_data.to_vec()
+        let expired = monitor.lock().await.take_expired(Instant::now());
+        for id in expired {
+            let Some(session) = sessions.write().await.remove(&id) else {
+                continue;
             };
             
-            // Parse message
-            let message = Message::deserialize(&decrypted)?;
+            info!("Session {} had no input for {:?}, disconnecting", id, monitor.lock().await.timeout());
             
-            match message {
-                Message::AuthRequest { code } => {
-                    // Ensure key exchange has happened first
-                    if crypto_session.is_none() {
-                        error!("Authentication attempted before key exchange");
-                        return Err(anyhow::anyhow!("Key exchange must happen before authentication"));
-                    }
-                    
-                    let response = handle_auth(&code, &access_code).await;
-                    
-                    if response.success {
-                        // Generate session
-                        let session_token = SessionToken::generate(24);
-                        let token_string = session_token.token.clone();
-                        let id = Uuid::new_v4().to_string();
-                        session_id = Some(id.clone());
-                        
-                        // Store session with the current crypto session
-                        let session = ClientSession {
-                            id: id.clone(),
-                            token: session_token,
-                            crypto: crypto_session.as_ref().unwrap().clone(),
-                            tx: tx.clone(),
-                            quality_controller: Arc::new(Mutex::new(AdaptiveQualityController::new())),
-                            last_frame_time: Arc::new(Mutex::new(std::time::Instant::now())),
-                        };
-                        
-                        sessions.write().await.insert(id, session);
-                        
-                        let auth_resp = Message::AuthResponse {
-                            success: true,
-                            session_token: Some(token_string),
-                        };
-                        
-                        send_encrypted(&tx, &auth_resp, &crypto_session).await?;
-                    } else {
-                        let auth_resp = Message::AuthResponse {
-                            success: false,
-                            session_token: None,
-                        };
-                        
-                        send_encrypted(&tx, &auth_resp, &crypto_session).await?;
-                    }
+            // Tell the client why before closing the connection
+            if let Ok(serialized) = Message::Disconnect.serialize() {
+                session.core.send_serialized(&serialized, Priority::Control).await;
+            }
+            
+            session.disconnect.notify_one();
+        }
+    }
+}
+
+// A capture thread for one display, with the flags the frame loop steers it by
+struct DisplayCapture {
+    // Set while at least one session wants keyframes split into tiles
+    progressive_wanted: Arc<AtomicBool>,
+    // Set while every session negotiated H.264; otherwise frames go out zstd-compressed
+    hardware_wanted: Arc<AtomicBool>,
+    // Set when a session is waiting for a keyframe (just joined, or missed frames)
+    keyframe_wanted: Arc<AtomicBool>,
+    // Set while every session can overlay the cursor and the server may leave it out of frames
+    cursor_hidden_wanted: Arc<AtomicBool>,
+    // Whether frames actually carry the cursor, as reported by the capture thread
+    cursor_in_frames: Arc<AtomicBool>,
+    // Frame pixels per input unit (f32 bits): the display's scale factor times the
+    // viewport scaling, as reported by the capture thread
+    scale_factor: Arc<AtomicU32>,
+    // What every viewer asked for, or Balanced when they differ
+    profile_wanted: Arc<std::sync::Mutex<ContentProfile>>,
+    // Largest window any viewer shows frames in, None for full size
+    viewport_wanted: Arc<std::sync::Mutex<Option<(u32, u32)>>>,
+    // Every region a viewer wants kept exact
+    lossless_wanted: Arc<std::sync::Mutex<Vec<Region>>>,
+    // Set while simulcast is on and several sessions watch the display
+    simulcast_wanted: Arc<AtomicBool>,
+    // Quality and input scale factor of each layer frames are sent in, as reported by
+    // the capture thread; just the main stream without simulcast
+    layers: Arc<std::sync::Mutex<Vec<(QualityMode, f32)>>>,
+    // Latest H.264 SPS/PPS of each layer, for viewers that start on a frame without them
+    parameter_sets: HashMap<usize, ParameterSets>,
+    // Set once no session watches this display any more
+    stop: Arc<AtomicBool>,
+}
+
+fn spawn_display_capture(
+    display_index: usize,
+    settings: CaptureSettings,
+    frame_sources: FrameSourceFactory,
+    focus: Arc<std::sync::Mutex<Option<(u32, u32)>>>,
+    sessions: Arc<RwLock<HashMap<String, ClientSession>>>,
+    tx: mpsc::UnboundedSender<(usize, CapturedFrame)>,
+) -> DisplayCapture {
+    let capture = DisplayCapture {
+        progressive_wanted: Arc::new(AtomicBool::new(false)),
+        hardware_wanted: Arc::new(AtomicBool::new(false)),
+        keyframe_wanted: Arc::new(AtomicBool::new(false)),
+        cursor_hidden_wanted: Arc::new(AtomicBool::new(false)),
+        cursor_in_frames: Arc::new(AtomicBool::new(true)),
+        scale_factor: Arc::new(AtomicU32::new(1.0f32.to_bits())),
+        profile_wanted: Arc::new(std::sync::Mutex::new(ContentProfile::default())),
+        viewport_wanted: Arc::new(std::sync::Mutex::new(None)),
+        lossless_wanted: Arc::new(std::sync::Mutex::new(Vec::new())),
+        simulcast_wanted: Arc::new(AtomicBool::new(false)),
+        layers: Arc::new(std::sync::Mutex::new(Vec::new())),
+        parameter_sets: HashMap::new(),
+        stop: Arc::new(AtomicBool::new(false)),
+    };
+    let capture_progressive = capture.progressive_wanted.clone();
+    let capture_hardware = capture.hardware_wanted.clone();
+    let capture_keyframe = capture.keyframe_wanted.clone();
+    let cursor_hidden_wanted = capture.cursor_hidden_wanted.clone();
+    let cursor_in_frames = capture.cursor_in_frames.clone();
+    let scale_factor = capture.scale_factor.clone();
+    let profile_wanted = capture.profile_wanted.clone();
+    let viewport_wanted = capture.viewport_wanted.clone();
+    let lossless_wanted = capture.lossless_wanted.clone();
+    let simulcast_wanted = capture.simulcast_wanted.clone();
+    let layers = capture.layers.clone();
+    let stop = capture.stop.clone();
+    
+    // Capture failures are reported to clients from the async side
+    let runtime = tokio::runtime::Handle::current();
+    
+    info!("Starting capture of display {}", display_index);
+    std::thread::spawn(move || {
+        run_supervised(
+            Backoff::default(),
+            || {
+                let source = frame_sources(display_index)?;
+                let mut capture = if settings.safe_mode {
+                    ScreenCapture::safe_mode(source, settings.fps, settings.bitrate, settings.tuning)?
+                } else {
+                    ScreenCapture::from_source(source, settings.fps, settings.bitrate, settings.tuning)?
+                };
+                if let Some(radius) = settings.foveation_radius {
+                    capture.set_foveation(Some(Foveation::new(radius, DEFAULT_PERIPHERY_BITS)));
                 }
-                
-                Message::KeyExchange { public_key } => {
-                    // Perform key exchange
-                    let key_exchange = KeyExchange::new();
-                    let our_public = key_exchange.public_key_bytes();
-                    
-                    // Send our public key
-                    let response = Message::KeyExchangeAck {
-                        public_key: our_public.to_vec(),
-                    };
-                    
-                    tx.send(response.serialize()?)?;
-                    
-                    // Compute shared secret
-                    let their_public = x25519_dalek::PublicKey::from(
-                        <[u8; 32]>::try_from(&public_key[..]).context("Invalid public key")?
-                    );
-                    let shared_secret = key_exchange.compute_shared_secret(&their_public);
-                    
-                    // Create crypto session
-                    let crypto = Arc::new(Mutex::new(CryptoSession::from_shared_secret(&shared_secret)?));
-                    crypto_session = Some(crypto);
-                    
-                    debug!("Key exchange completed");
+                capture.set_frame_checksums(settings.frame_checksums);
+                let on_damage = settings.on_damage && capture.reports_damage();
+                if settings.on_damage && !on_damage {
+                    warn!("Capture of display {} can't tell when the screen changes, polling it instead", display_index);
                 }
+                // Whether the machine is on battery, rechecked every POWER_CHECK_INTERVAL
+                let mut power_checked: Option<Instant> = None;
                 
-                Message::StartStream => {
-                    info!("Client requested stream start");
-                    // Send initial quality mode
-                    if let Some(id) = &session_id {
-                        if let Some(session) = sessions.read().await.get(id) {
-                            let quality = session.quality_controller.lock().await.get_current_quality();
-                            let msg = Message::QualityChange { mode: quality };
-                            send_encrypted(&tx, &msg, &crypto_session).await?;
-                        }
+                loop {
+                    if stop.load(Ordering::Relaxed) {
+                        info!("Stopped capture of display {}", display_index);
+                        return Ok(());
                     }
-                }
-                
-                Message::StopStream => {
-                    info!("Client requested stream stop");
-                    // Could implement pausing logic here
-                }
-                
-                Message::MouseMove { x, y } => {
-                    handle_mouse_move(x, y).await?;
-                }
-                
-                Message::MouseClick { button, pressed, x, y } => {
-                    handle_mouse_click(button, pressed, x, y).await?;
-                }
-                
-                Message::KeyEvent { key, pressed, modifiers } => {
-                    handle_key_event(&key, pressed, modifiers).await?;
-                }
-                
-                Message::FrameAck { timestamp, received_at } => {
-                    // Update quality metrics
-                    if let Some(id) = &session_id {
-                        if let Some(session) = sessions.read().await.get(id) {
-                            let rtt = received_at.saturating_sub(timestamp);
-                            let mut controller = session.quality_controller.lock().await;
-                            controller.update_metrics(0, std::time::Duration::from_millis(rtt));
-                        }
+                    capture.set_focus(*focus.lock().unwrap());
+                    capture.set_lossless_regions(lossless_wanted.lock().unwrap().clone());
+                    capture.set_progressive_keyframes(capture_progressive.load(Ordering::Relaxed));
+                    capture.set_hardware_encoding(capture_hardware.load(Ordering::Relaxed));
+                    capture.set_content_profile(*profile_wanted.lock().unwrap());
+                    capture.set_viewport(*viewport_wanted.lock().unwrap());
+                    if power_checked.map_or(true, |at| at.elapsed() >= POWER_CHECK_INTERVAL) {
+                        capture.set_power_save(settings.power_save.is_active());
+                        power_checked = Some(Instant::now());
                     }
-                }
-                
-                Message::RequestQualityChange { mode } => {
-                    if let Some(id) = &session_id {
-                        if let Some(session) = sessions.read().await.get(id) {
-                            let mut controller = session.quality_controller.lock().await;
-                            controller.force_quality(Some(mode));
-                            
-                            // Send confirmation
-                            let msg = Message::QualityChange { mode };
-                            send_encrypted(&tx, &msg, &crypto_session).await?;
+                    // Input arrives in frame pixels, which scaling to the viewport and
+                    // the quality mode's resolution shrinks
+                    capture.set_simulcast(if simulcast_wanted.load(Ordering::Relaxed) { DEFAULT_LAYERS } else { &[] });
+                    let frame_scale = capture.scale_factor() as f32 * capture.frame_scale();
+                    scale_factor.store(frame_scale.to_bits(), Ordering::Relaxed);
+                    *layers.lock().unwrap() = capture.layers().into_iter()
+                        .map(|(quality, scale)| (quality, capture.scale_factor() as f32 * scale))
+                        .collect();
+                    if capture_keyframe.swap(false, Ordering::Relaxed) {
+                        capture.request_keyframe();
+                    }
+                    capture.set_include_cursor(!cursor_hidden_wanted.load(Ordering::Relaxed));
+                    cursor_in_frames.store(capture.includes_cursor(), Ordering::Relaxed);
+                    // A frame that can't be captured or encoded (a display gone, a pixel
+                    // format we can't read) goes to the supervisor, which tells the clients
+                    // and retries with backoff rather than spinning on the failure
+                    let frames = if on_damage {
+                        capture.capture_on_damage(DAMAGE_WAIT)?
+                    } else {
+                        capture.capture_layers()?
+                    };
+                    for frame in frames {
+                        if tx.send((display_index, frame)).is_err() {
+                            return Ok(()); // Frame loop is gone, stop capturing
                         }
                     }
+                    if !on_damage {
+                        std::thread::sleep(std::time::Duration::from_millis(16));
+                    }
                 }
-                
-                Message::Disconnect => {
-                    info!("Client disconnecting");
-                    break;
-                }
-                
-                _ => {
-                    debug!("Unhandled message type");
-                }
-            }
+            },
+            |failure, retry_in| {
+                let message = format!("{}. Retrying in {:.1}s", failure, retry_in.as_secs_f32());
+                runtime.spawn(broadcast_error(sessions.clone(), display_index, message));
+            },
+        );
+    });
+    
+    capture
+}
+
+/// Start capturing displays sessions have just moved to, stop those nobody watches.
+/// Paused sessions don't count as watching.
+async fn update_display_captures(
+    captures: &mut HashMap<usize, DisplayCapture>,
+    sessions: &Arc<RwLock<HashMap<String, ClientSession>>>,
+    start: impl Fn(usize) -> DisplayCapture,
+) {
+    let sessions = sessions.read().await;
+    let watched: HashSet<usize> = sessions.values()
+        .filter(|session| !session.paused.load(Ordering::Relaxed))
+        .map(|session| session.display.load(Ordering::Relaxed))
+        .collect();
+    
+    captures.retain(|display, capture| {
+        let keep = watched.contains(display);
+        if !keep {
+            capture.stop.store(true, Ordering::Relaxed);
         }
-    }
+        keep
+    });
     
-    // Cleanup
-    if let Some(id) = session_id {
-        sessions.write().await.remove(&id);
+    for display in watched {
+        captures.entry(display).or_insert_with(|| start(display));
     }
     
-    writer_task.abort();
-    Ok(())
+    // A still screen produces no frames, so waiting sessions would otherwise wait for the keyframe interval
+    for session in sessions.values().filter(|s| s.needs_keyframe.load(Ordering::Relaxed) && !s.paused.load(Ordering::Relaxed)) {
+        if let Some(capture) = captures.get(&session.display.load(Ordering::Relaxed)) {
+            capture.keyframe_wanted.store(true, Ordering::Relaxed);
+        }
+    }
 }
 
-async fn handle_auth(
-    code: &str,
-    access_code: &Arc<RwLock<Option<AccessCode>>>,
-) -> AuthResponse {
-    let code_guard = access_code.read().await;
-    
-    if let Some(stored_code) = &*code_guard {
-        if stored_code.verify(code) {
-            return AuthResponse {
-                success: true,
-                session_token: None,
-                message: "Authentication successful".to_string(),
-            };
+/// Give each session its display's scale factor for converting input, and tell the
+/// client when it changes so the cursor overlay matches the frames.
+async fn update_display_scales(
+    captures: &HashMap<usize, DisplayCapture>,
+    sessions: &Arc<RwLock<HashMap<String, ClientSession>>>,
+) {
+    let sessions = sessions.read().await;
+    for session in sessions.values() {
+        let Some(capture) = captures.get(&session.display.load(Ordering::Relaxed)) else {
+            continue;
+        };
+        // Lower simulcast layers are smaller, so input from their viewers scales further
+        let layer_scale = capture.layers.lock().unwrap()
+            .get(session.layer.load(Ordering::Relaxed))
+            .map(|(_, scale)| scale.to_bits());
+        let scale = layer_scale.unwrap_or_else(|| capture.scale_factor.load(Ordering::Relaxed));
+        if session.scale_factor.swap(scale, Ordering::Relaxed) == scale {
+            continue;
+        }
+        
+        let message = Message::DisplayScale { factor: f32::from_bits(scale) };
+        if let Ok(serialized) = message.serialize() {
+            session.core.send_serialized(&serialized, Priority::Control).await;
+        }
+    }
+}
+
+/// Run each display with the content profile its viewers agree on (Balanced when they
+/// don't), and tell viewers whose display changed profile.
+async fn update_content_profiles(
+    captures: &HashMap<usize, DisplayCapture>,
+    sessions: &Arc<RwLock<HashMap<String, ClientSession>>>,
+) {
+    let sessions = sessions.read().await;
+    for (display, capture) in captures {
+        let viewers: Vec<&ClientSession> = sessions.values()
+            .filter(|session| session.display.load(Ordering::Relaxed) == *display)
+            .collect();
+        let mut wanted = Vec::with_capacity(viewers.len());
+        for session in &viewers {
+            wanted.push(*session.content_profile.lock().await);
         }
+        let profile = match wanted.first() {
+            Some(first) if wanted.iter().all(|profile| profile == first) => *first,
+            _ => ContentProfile::Balanced,
+        };
+        *capture.profile_wanted.lock().unwrap() = profile;
+        
+        for session in viewers {
+            let mut sent = session.profile_sent.lock().await;
+            if *sent == Some(profile) {
+                continue;
+            }
+            *sent = Some(profile);
+            if let Ok(serialized) = (Message::ContentProfileApplied { profile }).serialize() {
+                session.core.send_serialized(&serialized, Priority::Control).await;
+            }
+        }
+    }
+}
+
+/// Leave the cursor out of a display's frames only when the server allows it and every
+/// viewer can draw it, then tell the viewers that can where to draw it. When frames
+/// already show the cursor the overlay is hidden, so nobody sees two.
+/// Scale each display's frames down to the largest window any of its viewers shows
+/// them in, or keep them whole while a viewer hasn't reported one.
+async fn update_viewports(
+    captures: &HashMap<usize, DisplayCapture>,
+    sessions: &Arc<RwLock<HashMap<String, ClientSession>>>,
+) {
+    let sessions = sessions.read().await;
+    for (display, capture) in captures {
+        let mut viewports = Vec::new();
+        for session in sessions.values().filter(|s| s.display.load(Ordering::Relaxed) == *display) {
+            viewports.push(*session.viewport.lock().await);
+        }
+        *capture.viewport_wanted.lock().unwrap() = combined_viewport(viewports);
+    }
+}
+
+/// Keep every region a display's viewers marked lossless exact in its frames.
+async fn update_lossless_regions(
+    captures: &HashMap<usize, DisplayCapture>,
+    sessions: &Arc<RwLock<HashMap<String, ClientSession>>>,
+) {
+    let sessions = sessions.read().await;
+    for (display, capture) in captures {
+        let mut regions = Vec::new();
+        for session in sessions.values().filter(|s| s.display.load(Ordering::Relaxed) == *display) {
+            regions.extend(*session.lossless_region.lock().await);
+        }
+        *capture.lossless_wanted.lock().unwrap() = regions;
     }
+}
+
+async fn update_cursor_overlays(
+    captures: &HashMap<usize, DisplayCapture>,
+    sessions: &Arc<RwLock<HashMap<String, ClientSession>>>,
+    focus: &Arc<std::sync::Mutex<Option<(u32, u32)>>>,
+    overlay_allowed: bool,
+) {
+    let sessions = sessions.read().await;
+    let position = focus.lock().unwrap().map(|(x, y)| (x as i32, y as i32));
     
-    AuthResponse {
-        success: false,
-        session_token: None,
-        message: "Invalid or expired code".to_string(),
+    for (display, capture) in captures {
+        let viewers: Vec<&ClientSession> = sessions.values()
+            .filter(|session| session.display.load(Ordering::Relaxed) == *display)
+            .collect();
+        let all_overlay = !viewers.is_empty()
+            && viewers.iter().all(|session| session.cursor_overlay.load(Ordering::Relaxed));
+        capture.cursor_hidden_wanted.store(overlay_allowed && all_overlay, Ordering::Relaxed);
+        
+        let overlay_position = position.filter(|_| !capture.cursor_in_frames.load(Ordering::Relaxed));
+        for session in viewers.into_iter().filter(|s| s.cursor_overlay.load(Ordering::Relaxed)) {
+            let mut sent = session.cursor_sent.lock().await;
+            if *sent == overlay_position {
+                continue;
+            }
+            *sent = overlay_position;
+            
+            let (x, y) = overlay_position.unwrap_or_default();
+            let message = Message::CursorUpdate { x, y, visible: overlay_position.is_some() };
+            if let Ok(serialized) = message.serialize() {
+                session.core.send_serialized(&serialized, Priority::Control).await;
+            }
+        }
     }
 }
 
 async fn screen_capture_loop(
     sessions: Arc<RwLock<HashMap<String, ClientSession>>>,
+    settings: CaptureSettings,
+    frame_sources: FrameSourceFactory,
+    focus: Arc<std::sync::Mutex<Option<(u32, u32)>>>,
+    server_capabilities: Arc<Capabilities>,
+    metrics: Arc<PerformanceMetrics>,
 ) -> Result<()> {
-    use crate::common::encoder::{EncoderFactory, EncoderType};
-    
-    // Check if hardware encoding is available
-    let _encoder_type = if EncoderFactory::is_hardware_available() {
-        info!("Hardware encoder available - using hardware acceleration");
-        EncoderType::Hardware
-    } else {
-        info!("Hardware encoder not available - using software encoding");
-        EncoderType::Software
+    // Capture threads, one per display being watched, all feed this channel
+    let (tx, mut rx) = mpsc::unbounded_channel::<(usize, CapturedFrame)>();
+    let mut captures: HashMap<usize, DisplayCapture> = HashMap::new();
+    let start_capture = |display: usize| {
+        spawn_display_capture(display, settings, frame_sources.clone(), focus.clone(), sessions.clone(), tx.clone())
     };
     
-    // Run screen capture in a separate thread
-    let (tx, mut rx) = mpsc::unbounded_channel::<crate::server::screen_capture::CapturedFrame>();
+    // Sessions come, go and switch displays between frames
+    let mut reconcile = tokio::time::interval(Duration::from_millis(250));
+    // The cursor moves whether or not the screen behind it changes
+    let mut cursor_tick = tokio::time::interval(CURSOR_UPDATE_INTERVAL);
+    cursor_tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
     
-    std::thread::spawn(move || {
-        let mut capture = match ScreenCapture::new(30) {
-            Ok(c) => c,
-            Err(e) => {
-                error!("Failed to initialize screen capture: {}", e);
-                return;
+    // Process frames in async context
+    loop {
+        let (display, frame) = tokio::select! {
+            received = rx.recv() => match received {
+                Some(received) => received,
+                None => break,
+            },
+            _ = reconcile.tick() => {
+                update_display_captures(&mut captures, &sessions, &start_capture).await;
+                update_display_scales(&captures, &sessions).await;
+                update_content_profiles(&captures, &sessions).await;
+                update_viewports(&captures, &sessions).await;
+                update_lossless_regions(&captures, &sessions).await;
+                continue;
+            }
+            _ = cursor_tick.tick() => {
+                update_cursor_overlays(&captures, &sessions, &focus, settings.cursor_overlay).await;
+                continue;
             }
         };
         
-        loop {
-            if let Ok(Some(frame)) = capture.capture_frame() {
-                let _ = tx.send(frame);
-            }
-            std::thread::sleep(std::time::Duration::from_millis(16));
+        // Frames still queued from a capture that has since been stopped
+        let Some(capture) = captures.get_mut(&display) else {
+            continue;
+        };
+        // Simulcast layers are extra encodes of a frame already counted
+        if frame.layer == 0 {
+            metrics.frame_captured();
+            metrics.frame_encoded();
         }
-    });
-    
-    // Process frames in async context
-    while let Some(frame) = rx.recv().await {
+        
         let sessions_guard = sessions.read().await;
+        let viewers: Vec<&ClientSession> = sessions_guard.values()
+            .filter(|session| session.display.load(Ordering::Relaxed) == display && !session.paused.load(Ordering::Relaxed))
+            .collect();
+        
+        capture.progressive_wanted.store(
+            viewers.iter().any(|s| s.progressive_rendering.load(Ordering::Relaxed)),
+            Ordering::Relaxed,
+        );
+        
+        let mut all_h264 = !viewers.is_empty();
+        for session in &viewers {
+            let client_capabilities = session.capabilities.lock().await;
+            all_h264 &= negotiate(&server_capabilities, &client_capabilities) == Some(EncodingType::H264);
+        }
+        capture.hardware_wanted.store(all_h264, Ordering::Relaxed);
+        capture.simulcast_wanted.store(settings.simulcast && viewers.len() > 1, Ordering::Relaxed);
+        let layers: Vec<QualityMode> = capture.layers.lock().unwrap().iter().map(|(quality, _)| *quality).collect();
+        
+        if frame.encoding == EncodingType::H264 {
+            capture.parameter_sets.entry(frame.layer).or_insert_with(ParameterSets::new).observe(&frame.data);
+        }
+        let keyframe_wanted = &capture.keyframe_wanted;
+        let is_keyframe = frame.frame_type == FrameType::KeyFrame;
         
-        for (_, session) in sessions_guard.iter() {
+        for session in viewers {
+            // Frames encoded before the capture thread caught up with a new session may not suit it
+            if !session.capabilities.lock().await.supports(frame.encoding) {
+                session.needs_keyframe.store(true, Ordering::Relaxed);
+                keyframe_wanted.store(true, Ordering::Relaxed);
+                continue;
+            }
+            
+            // Each viewer gets the layer its link can take, moving to another on its next keyframe
+            let current_quality = session.core.quality_controller.lock().await.get_current_quality();
+            let layer = layer_for(&layers, current_quality);
+            if session.layer.swap(layer, Ordering::Relaxed) != layer {
+                debug!("Session {} moves to simulcast layer {} ({:?})", session.core.id, layer, layers.get(layer));
+                session.needs_keyframe.store(true, Ordering::Relaxed);
+                keyframe_wanted.store(true, Ordering::Relaxed);
+            }
+            if frame.layer != layer {
+                continue;
+            }
+            
+            // Video the send queue had to throw away leaves the client needing a keyframe
+            let dropped = session.core.transport.take_dropped_video();
+            if dropped > 0 {
+                debug!("Session {} fell behind, {} queued frames dropped", session.core.id, dropped);
+                (0..dropped).for_each(|_| metrics.frame_dropped());
+                session.pending_tiles.lock().await.clear();
+                session.needs_keyframe.store(true, Ordering::Relaxed);
+            }
+            
+            // Deltas are useless until the client has seen a keyframe
+            let needs_keyframe = session.needs_keyframe.load(Ordering::Relaxed);
+            if needs_keyframe && !is_keyframe {
+                keyframe_wanted.store(true, Ordering::Relaxed);
+                continue;
+            }
+            
             // Check quality settings for this client
-            let mut quality_controller = session.quality_controller.lock().await;
+            let mut quality_controller = session.core.quality_controller.lock().await;
             let quality = quality_controller.get_recommended_quality();
             
+            // Tell the client when and why the adaptive controller moved quality
+            if let Some((mode, reason)) = quality_controller.take_quality_change() {
+                info!("Quality changed to {:?} due to {}", mode, reason.description());
+                if let Ok(serialized) = (Message::QualityChange { mode, reason }).serialize() {
+                    session.core.send_serialized(&serialized, Priority::Control).await;
+                }
+            }
+            
             // Update frame time and metrics
             let now = std::time::Instant::now();
             let last_time = *session.last_frame_time.lock().await;
             let frame_time = now.duration_since(last_time);
-            *session.last_frame_time.lock().await = now;
             
-            // Skip frame if it's too soon for this quality level
+            // Skip frame if it's too soon for this quality level. Skipped deltas are merged into
+            // the next one; a skipped keyframe means waiting for another. Keyframes the client is
+            // waiting for and H.264 frames (which the decoder needs in sequence) always go out.
+            // A client whose queue still holds several frames, or that hasn't acknowledged the
+            // quality mode's window of frames, gets nothing more until it catches up.
             let target_interval = std::time::Duration::from_millis(1000 / quality.target_fps() as u64);
-            if frame_time < target_interval {
+            let backlogged = session.core.transport.video_backlog() >= session.core.transport.video_backlog_limit()
+                || !session.core.can_send_frame(quality, now);
+            if backlogged || (frame_time < target_interval && !needs_keyframe && frame.encoding != EncodingType::H264) {
+                match &frame.tiles {
+                    Some(tiles) if !is_keyframe => merge_tiles(&mut *session.pending_tiles.lock().await, tiles),
+                    _ => {
+                        session.needs_keyframe.store(true, Ordering::Relaxed);
+                        // A stalled client would have every frame be a keyframe; the reconcile tick asks again
+                        if !backlogged {
+                            keyframe_wanted.store(true, Ordering::Relaxed);
+                        }
+                    }
+                }
+                metrics.frame_dropped();
                 continue;
             }
+            *session.last_frame_time.lock().await = now;
             
-            // Create appropriate message based on frame type
-            let message = match frame.frame_type {
-                crate::common::frame_processor::FrameType::KeyFrame => {
-                    Message::ScreenFrame {
-                        timestamp: frame.timestamp,
-                        width: frame.width,
-                        height: frame.height,
-                        data: frame.data.to_vec(),
-                        encoding: frame.encoding,
+            if is_keyframe {
+                session.needs_keyframe.store(false, Ordering::Relaxed);
+                session.pending_tiles.lock().await.clear();
+            }
+            
+            // A decoder starting on this frame can't initialize unless it carries the parameter sets
+            if needs_keyframe && frame.encoding == EncodingType::H264 {
+                if let Some(config) = capture.parameter_sets.get(&frame.layer).and_then(|sets| sets.config_for(&frame.data)) {
+                    if let Ok(serialized) = config.serialize() {
+                        session.core.send_serialized(&serialized, Priority::Control).await;
                     }
                 }
-                crate::common::frame_processor::FrameType::DeltaFrame => {
-                    if let Some(tiles) = &frame.tiles {
-                        Message::DeltaFrame {
+            }
+            
+            // Stream keyframes as tile chunks to clients rendering progressively
+            if is_keyframe
+                && session.progressive_rendering.load(Ordering::Relaxed)
+            {
+                if let Some(tiles) = &frame.tiles {
+                    let sequence = session.core.next_sequence();
+                    let chunk_count = tiles.chunks(PROGRESSIVE_TILES_PER_CHUNK).len();
+                    let mut frame_bytes = 0;
+                    for (index, chunk) in tiles.chunks(PROGRESSIVE_TILES_PER_CHUNK).enumerate() {
+                        let message = Message::KeyFrameChunk {
+                            sequence,
                             timestamp: frame.timestamp,
-                            tiles: tiles.clone(),
+                            width: frame.width,
+                            height: frame.height,
+                            tiles: chunk.to_vec(),
+                            is_last: index + 1 == chunk_count,
+                        };
+                        
+                        if let Ok(serialized) = message.serialize() {
+                            if let Some(sent) = session.core.send_serialized(&serialized, Priority::Video).await {
+                                quality_controller.update_metrics(sent, frame_time);
+                                frame_bytes += sent;
+                            }
                         }
-                    } else {
+                    }
+                    session.core.frame_sent(frame.timestamp, now);
+                    metrics.frame_sent();
+                    metrics.add_bytes_sent(frame_bytes);
+                    metrics.session_frame_sent(&session.core.id, quality, frame_bytes);
+                    continue;
+                }
+            }
+            
+            // Create appropriate message based on frame type; the frame still counted toward pacing above
+            let message = match (&frame.tiles, is_keyframe) {
+                (Some(tiles), false) => {
+                    let mut pending = session.pending_tiles.lock().await;
+                    merge_tiles(&mut pending, tiles);
+                    if pending.is_empty() {
                         continue;
                     }
+                    // Held-back tiles included, these bring the client to this frame
+                    Message::DeltaFrame {
+                        sequence: session.core.next_sequence(),
+                        timestamp: frame.timestamp,
+                        tiles: std::mem::take(&mut *pending),
+                        checksum: frame.checksum,
+                    }
                 }
+                // A number skipped for a frame with nothing to send leaves a harmless gap
+                _ => match frame.to_message(session.core.next_sequence()) {
+                    Some(message) => message,
+                    None => continue,
+                },
             };
             
             // Serialize and encrypt
             if let Ok(serialized) = message.serialize() {
-                let crypto = session.crypto.lock().await;
-                if let Ok(encrypted) = crypto.encrypt(&serialized) {
-                    // Update metrics with frame size
-                    quality_controller.update_metrics(encrypted.len(), frame_time);
-                    
-                    // Send frame
-                    let _ = session.tx.send(encrypted);
+                // Send frame, updating metrics with its size
+                if let Some(sent) = session.core.send_serialized(&serialized, Priority::Video).await {
+                    session.core.frame_sent(frame.timestamp, now);
+                    quality_controller.update_metrics(sent, frame_time);
+                    metrics.frame_sent();
+                    metrics.add_bytes_sent(sent);
+                    metrics.session_frame_sent(&session.core.id, quality, sent);
                 }
             }
         }
@@ -389,10 +1943,47 @@ async fn screen_capture_loop(
     Ok(())
 }
 
-async fn handle_mouse_move(x: i32, y: i32) -> Result<()> {
+/// Tell the clients watching `display` about a server-side failure.
+async fn broadcast_error(sessions: Arc<RwLock<HashMap<String, ClientSession>>>, display: usize, message: String) {
+    let Ok(serialized) = (Message::Error { message, kind: ErrorKind::Other }).serialize() else {
+        return;
+    };
+    
+    let sessions = sessions.read().await;
+    for session in sessions.values().filter(|s| s.display.load(Ordering::Relaxed) == display) {
+        session.core.send_serialized(&serialized, Priority::Control).await;
+    }
+}
+
+/// Input the server isn't permitted to inject leaves the session view-only, and the client
+/// is told once why its mouse and keyboard do nothing. Other failures end the session.
+async fn check_input(
+    result: Result<()>,
+    reported: &mut bool,
+    queue: &SendQueue,
+    crypto: &Option<Arc<Mutex<Box<dyn Encryptor>>>>,
+) -> Result<()> {
+    match result {
+        Err(e) if is_permission_error(&e) => {
+            if !std::mem::replace(reported, true) {
+                warn!("Ignoring input from the client: {}", e);
+                let message = Message::Error { message: e.to_string(), kind: ErrorKind::InputNotPermitted };
+                send_encrypted(queue, &message, crypto).await?;
+            }
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+fn load_scale(scale_factor: &AtomicU32) -> f64 {
+    f32::from_bits(scale_factor.load(Ordering::Relaxed)) as f64
+}
+
+async fn handle_mouse_move(x: i32, y: i32, scale_factor: f64, origin: (i32, i32)) -> Result<()> {
     // Run input handling in blocking task
     tokio::task::spawn_blocking(move || {
-        let mut handler = InputHandler::new()?;
+        let mut handler = InputHandler::new()?.with_scale_factor(scale_factor).with_origin(origin);
         handler.mouse_move(x, y)
     }).await?
 }
@@ -402,13 +1993,42 @@ async fn handle_mouse_click(
     pressed: bool,
     x: i32,
     y: i32,
+    scale_factor: f64,
+    origin: (i32, i32),
 ) -> Result<()> {
     tokio::task::spawn_blocking(move || {
-        let mut handler = InputHandler::new()?;
+        let mut handler = InputHandler::new()?.with_scale_factor(scale_factor).with_origin(origin);
         handler.mouse_click(button, pressed, x, y)
     }).await?
 }
 
+async fn handle_touch(
+    phase: TouchPhase,
+    x: i32,
+    y: i32,
+    pressure: Option<f32>,
+    scale_factor: f64,
+    origin: (i32, i32),
+) -> Result<()> {
+    tokio::task::spawn_blocking(move || {
+        let mut handler = InputHandler::new()?.with_scale_factor(scale_factor).with_origin(origin);
+        handler.touch(phase, x, y, pressure)
+    }).await?
+}
+
+async fn handle_gesture(
+    gesture: crate::common::protocol::TouchGesture,
+    x: i32,
+    y: i32,
+    scale_factor: f64,
+    origin: (i32, i32),
+) -> Result<()> {
+    tokio::task::spawn_blocking(move || {
+        let mut handler = InputHandler::new()?.with_scale_factor(scale_factor).with_origin(origin);
+        handler.gesture(gesture, x, y)
+    }).await?
+}
+
 async fn handle_key_event(
     key: &str,
     pressed: bool,
@@ -421,28 +2041,64 @@ async fn handle_key_event(
     }).await?
 }
 
-async fn send_message(writer: &mut tokio::net::tcp::OwnedWriteHalf, data: &[u8]) -> Result<()> {
-    let len = data.len() as u32;
-    writer.write_all(&len.to_be_bytes()).await?;
-    writer.write_all(data).await?;
-    writer.flush().await?;
-    Ok(())
+async fn handle_physical_key_event(
+    usage: u16,
+    pressed: bool,
+    modifiers: crate::common::protocol::Modifiers,
+) -> Result<()> {
+    tokio::task::spawn_blocking(move || {
+        let mut handler = InputHandler::new()?;
+        handler.physical_key_event(usage, pressed, modifiers)
+    }).await?
 }
 
 async fn send_encrypted(
-    tx: &mpsc::UnboundedSender<Vec<u8>>,
+    queue: &SendQueue,
     message: &Message,
-    crypto: &Option<Arc<Mutex<CryptoSession>>>,
+    crypto: &Option<Arc<Mutex<Box<dyn Encryptor>>>>,
 ) -> Result<()> {
     let serialized = message.serialize()?;
     
-    let data = if let Some(crypto) = crypto {
-        let crypto = crypto.lock().await;
-        crypto.encrypt(&serialized)?
-    } else {
-        serialized
-    };
-    
-    tx.send(data)?;
+    match crypto {
+        Some(crypto) => {
+            // Held until queued so a key rotation can't be overtaken by other messages
+            let crypto = crypto.lock().await;
+            let frames = seal_message(&**crypto, &serialized)?;
+            discard_video_on_rekey(queue, &frames);
+            for frame in frames {
+                queue.push(frame, Priority::Control)?;
+            }
+        }
+        None => queue.push(serialized, Priority::Control)?,
+    }
     Ok(())
+}
+
+// A Rekey from `seal_message` jumps ahead of queued video like any control message, so
+// video still queued under the old key would no longer decrypt; drop it instead
+fn discard_video_on_rekey(queue: &SendQueue, frames: &[Vec<u8>]) {
+    if frames.len() > 1 {
+        queue.discard_video();
+    }
+}
+
+/// Encodings this server can produce, most preferred first. Safe mode only produces WebP.
+fn server_capabilities(safe_mode: bool) -> Capabilities {
+    if safe_mode {
+        info!("Encoder: safe mode, software WebP only");
+        return Capabilities::new(vec![EncodingType::WebP]);
+    }
+    let mut encodings = Vec::new();
+    // Says which encoder sessions will get, and why hardware isn't used when it isn't
+    match EncoderFactory::probe() {
+        Ok(selection) => {
+            info!("Encoder: {}", selection.summary());
+            if selection.encoder.get_type() == EncoderType::Hardware {
+                encodings.push(EncodingType::H264);
+            }
+        }
+        Err(e) => warn!("{:#}", e),
+    }
+    encodings.push(EncodingType::ZstdCompressed);
+    Capabilities::new(encodings)
 }
\ No newline at end of file
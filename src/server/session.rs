@@ -0,0 +1,263 @@
+use anyhow::Result;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, Notify, RwLock};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, warn};
+use uuid::Uuid;
+
+use crate::common::auth::{AccessCode, AccessCodes, AuthResponse, Permissions, SessionToken};
+use crate::common::crypto::{seal_message, seal_video, Encryptor};
+use crate::common::delivery::DeliveryMode;
+use crate::common::protocol::Message;
+use crate::common::quality::{AdaptiveQualityController, QualityMode};
+use crate::common::transport::{FrameRoute, Transport};
+use crate::server::flow_control::FrameWindow;
+use crate::server::send_queue::{Priority, SendQueue};
+
+/// Least time between two honoured keyframe requests from one client. Clients limit
+/// themselves too; this keeps a misbehaving one from turning the stream into keyframes.
+pub const KEYFRAME_REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Hours a session token stays valid.
+const SESSION_TOKEN_HOURS: u64 = 24;
+
+/// Where a session's encrypted messages go: its send queue, drained to the client's
+/// connection by a `TransportSender`.
+pub trait SessionTransport: Send + Sync {
+    /// Queue one encrypted message. Video may be dropped when the link falls behind,
+    /// control messages never are.
+    fn send(&self, data: Vec<u8>, priority: Priority) -> Result<()>;
+    
+    /// Drop video still waiting to go out, which a key rotation just made undecryptable.
+    fn discard_video(&self) {}
+}
+
+impl SessionTransport for SendQueue {
+    fn send(&self, data: Vec<u8>, priority: Priority) -> Result<()> {
+        self.push(data, priority)
+    }
+    
+    fn discard_video(&self) {
+        SendQueue::discard_video(self);
+    }
+}
+
+/// Writes a session's send queue to a `Transport` from a task of its own, as transports
+/// can't be written from the synchronous `SessionTransport::send`. Video goes as datagrams
+/// while the queue's delivery is real-time, everything else reliably.
+pub struct TransportSender {
+    queue: Arc<SendQueue>,
+    task: JoinHandle<()>,
+}
+
+impl TransportSender {
+    /// Start writing what is pushed to `queue`. `stuck` is notified if the queue overflows
+    /// because the client stopped reading.
+    pub fn spawn<T: Transport + 'static>(transport: Arc<T>, queue: Arc<SendQueue>, stuck: Arc<Notify>) -> Self {
+        let writer_queue = queue.clone();
+        let task = tokio::spawn(async move {
+            while let Some((data, priority)) = writer_queue.pop_with_priority().await {
+                let sent = if priority == Priority::Video && writer_queue.delivery() == DeliveryMode::RealTime {
+                    transport.send_datagram(&data).await.map(|route| {
+                        if route == FrameRoute::Stream {
+                            debug!("Frame of {} bytes didn't fit in a datagram, sent reliably", data.len());
+                        }
+                    })
+                } else {
+                    transport.send(&data).await
+                };
+                if let Err(e) = sent {
+                    error!("Failed to send message: {}", e);
+                    break;
+                }
+            }
+            if writer_queue.is_overflowed() {
+                warn!("Client stopped reading, closing its session");
+                stuck.notify_one();
+            }
+        });
+        Self { queue, task }
+    }
+    
+    /// Stop taking messages, and give those already queued (e.g. a final `Disconnect`)
+    /// up to `timeout` to go out.
+    pub async fn close(mut self, timeout: Duration) {
+        self.queue.close();
+        if tokio::time::timeout(timeout, &mut self.task).await.is_err() {
+            self.task.abort();
+        }
+    }
+}
+
+impl SessionTransport for TransportSender {
+    fn send(&self, data: Vec<u8>, priority: Priority) -> Result<()> {
+        self.queue.push(data, priority)
+    }
+    
+    fn discard_video(&self) {
+        self.queue.discard_video();
+    }
+}
+
+/// What an authenticated client has whichever server it connected to: its identity, the
+/// keys its messages are sealed with, its quality controller and frame numbering.
+pub struct Session<T: SessionTransport> {
+    pub id: String,
+    pub token: SessionToken,
+    pub crypto: Arc<Mutex<Box<dyn Encryptor>>>,
+    pub transport: Arc<T>,
+    pub quality_controller: Arc<Mutex<AdaptiveQualityController>>,
+    // Sequence number of the next frame sent to the client
+    frame_sequence: AtomicU64,
+    // When the client last had a keyframe request honoured
+    last_keyframe_request: std::sync::Mutex<Option<Instant>>,
+    // Frames sent that the client hasn't acknowledged yet
+    frame_window: std::sync::Mutex<FrameWindow>,
+}
+
+impl<T: SessionTransport> Session<T> {
+    /// A new session with a fresh id and token, its quality kept within `quality_bounds`.
+    pub fn new(crypto: Arc<Mutex<Box<dyn Encryptor>>>, transport: Arc<T>, quality_bounds: (QualityMode, QualityMode)) -> Self {
+        let mut quality_controller = AdaptiveQualityController::new();
+        quality_controller.set_quality_bounds(quality_bounds.0, quality_bounds.1);
+        Self {
+            id: Uuid::new_v4().to_string(),
+            token: SessionToken::generate(SESSION_TOKEN_HOURS),
+            crypto,
+            transport,
+            quality_controller: Arc::new(Mutex::new(quality_controller)),
+            frame_sequence: AtomicU64::new(0),
+            last_keyframe_request: std::sync::Mutex::new(None),
+            frame_window: std::sync::Mutex::new(FrameWindow::new()),
+        }
+    }
+    
+    /// Serialize, encrypt and send `message`, giving the bytes sent. Errors are logged
+    /// rather than returned; a session that went away is cleaned up by its handler.
+    pub async fn send(&self, message: &Message, priority: Priority) -> Option<usize> {
+        match message.serialize() {
+            Ok(serialized) => self.send_serialized(&serialized, priority).await,
+            Err(e) => {
+                error!("Failed to serialize a message: {}", e);
+                None
+            }
+        }
+    }
+    
+    /// Like `send`, for a message serialized once and sent to several sessions.
+    pub async fn send_serialized(&self, serialized: &[u8], priority: Priority) -> Option<usize> {
+        // Held until sent so a key rotation can't be overtaken by other messages
+        let crypto = self.crypto.lock().await;
+        // Only screen data is sent as video, so only it may go out in the clear
+        let sealed = if priority == Priority::Video {
+            seal_video(&**crypto, serialized)
+        } else {
+            seal_message(&**crypto, serialized)
+        };
+        let frames = match sealed {
+            Ok(frames) => frames,
+            Err(e) => {
+                error!("Encryption error: {}", e);
+                return None;
+            }
+        };
+        
+        // Video queued under the old key can't be decrypted after a Rekey overtakes it
+        if frames.len() > 1 {
+            self.transport.discard_video();
+        }
+        let last = frames.len() - 1;
+        let mut sent = 0;
+        for (index, frame) in frames.into_iter().enumerate() {
+            sent += frame.len();
+            // The Rekey ahead of the message itself must never be dropped
+            let priority = if index < last { Priority::Control } else { priority };
+            if let Err(e) = self.transport.send(frame, priority) {
+                debug!("Message not sent: {}", e);
+                return None;
+            }
+        }
+        Some(sent)
+    }
+    
+    /// Sequence number for the next frame; all chunks of one keyframe share it.
+    pub fn next_sequence(&self) -> u64 {
+        self.frame_sequence.fetch_add(1, Ordering::Relaxed)
+    }
+    
+    /// Whether a frame may go out at `now` without more than `quality`'s frame window
+    /// unacknowledged.
+    pub fn can_send_frame(&self, quality: QualityMode, now: Instant) -> bool {
+        self.frame_window.lock().unwrap().is_open(quality.frame_window(), now)
+    }
+    
+    /// The frame with `timestamp` was sent at `now` and counts against the window until acked.
+    pub fn frame_sent(&self, timestamp: u64, now: Instant) {
+        self.frame_window.lock().unwrap().sent(timestamp, now);
+    }
+    
+    /// The client acknowledged the frame with `timestamp` and every one before it.
+    pub fn frame_acked(&self, timestamp: u64) {
+        self.frame_window.lock().unwrap().acked(timestamp);
+    }
+    
+    /// Frames sent that the client hasn't acknowledged.
+    pub fn frames_in_flight(&self) -> usize {
+        self.frame_window.lock().unwrap().in_flight()
+    }
+    
+    /// Whether to honour a keyframe request at `now`, at most one per `KEYFRAME_REQUEST_INTERVAL`.
+    pub fn request_keyframe(&self, now: Instant) -> bool {
+        let mut last = self.last_keyframe_request.lock().unwrap();
+        if last.is_some_and(|at| now.duration_since(at) < KEYFRAME_REQUEST_INTERVAL) {
+            return false;
+        }
+        *last = Some(now);
+        true
+    }
+}
+
+/// Check `code` against the current access code, which grants full control, then against
+/// the host's fixed `codes`. Everyone is refused while `session_count` sessions are
+/// connected and that reaches `max_sessions`.
+pub async fn authenticate(
+    code: &str,
+    access_code: &RwLock<Option<AccessCode>>,
+    codes: &AccessCodes,
+    session_count: usize,
+    max_sessions: Option<usize>,
+) -> AuthResponse {
+    if max_sessions.is_some_and(|max| session_count >= max) {
+        warn!("Rejecting authentication: {} sessions already connected", session_count);
+        return AuthResponse {
+            success: false,
+            session_token: None,
+            message: "Server is at its session limit".to_string(),
+            permissions: None,
+        };
+    }
+    
+    let permissions = if access_code.read().await.as_ref().is_some_and(|stored| stored.verify(code)) {
+        Some(Permissions::FULL_CONTROL)
+    } else {
+        codes.permissions_for(code)
+    };
+    
+    if permissions.is_some() {
+        return AuthResponse {
+            success: true,
+            session_token: None,
+            message: "Authentication successful".to_string(),
+            permissions,
+        };
+    }
+    
+    AuthResponse {
+        success: false,
+        session_token: None,
+        message: "Invalid or expired code".to_string(),
+        permissions: None,
+    }
+}
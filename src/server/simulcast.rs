@@ -0,0 +1,82 @@
+use anyhow::{Context, Result};
+use crate::common::encoder::{EncodedFrame, EncoderSettings, VideoEncoder};
+use crate::common::quality::QualityMode;
+use crate::server::screen_capture::resize_rgb;
+
+/// Layers encoded below the main stream when simulcast is on: half resolution, at Low's
+/// share of the bitrate.
+pub const DEFAULT_LAYERS: &[QualityMode] = &[QualityMode::Low];
+
+/// Extra H.264 encodes of every frame at lower qualities, so viewers on slow links get a
+/// smaller stream without dragging everyone else down to it. The main stream is layer 0
+/// and these follow as layers 1, 2, ...; each viewer is sent only the layer `layer_for`
+/// picks for it.
+pub struct Simulcast {
+    layers: Vec<SimulcastLayer>,
+}
+
+struct SimulcastLayer {
+    quality: QualityMode,
+    settings: EncoderSettings,
+    encoder: Box<dyn VideoEncoder>,
+}
+
+/// One frame of one simulcast layer.
+pub struct LayerFrame {
+    /// Position in the layer list, counting the main stream as 0
+    pub layer: usize,
+    pub width: u32,
+    pub height: u32,
+    pub frame: EncodedFrame,
+}
+
+impl Simulcast {
+    /// Layers in the order given, each with the encoder built for its settings.
+    pub fn new(layers: Vec<(QualityMode, EncoderSettings, Box<dyn VideoEncoder>)>) -> Self {
+        Self {
+            layers: layers.into_iter()
+                .map(|(quality, settings, encoder)| SimulcastLayer { quality, settings, encoder })
+                .collect(),
+        }
+    }
+    
+    pub fn qualities(&self) -> Vec<QualityMode> {
+        self.layers.iter().map(|layer| layer.quality).collect()
+    }
+    
+    pub fn settings(&self) -> Vec<EncoderSettings> {
+        self.layers.iter().map(|layer| layer.settings).collect()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+    
+    /// Encode a `width`x`height` RGB frame in every layer, scaled to each layer's size.
+    /// A layer whose encoder is still buffering adds no frame.
+    pub fn encode(&mut self, rgb: &[u8], width: usize, height: usize, force_keyframe: bool) -> Result<Vec<LayerFrame>> {
+        let mut frames = Vec::with_capacity(self.layers.len());
+        for (index, layer) in self.layers.iter_mut().enumerate() {
+            let (layer_width, layer_height) = (layer.settings.width, layer.settings.height);
+            let scaled = resize_rgb(rgb, width, height, layer_width as usize, layer_height as usize)?;
+            let frame = layer.encoder.encode_frame(&scaled, force_keyframe)
+                .with_context(|| format!("{:?} simulcast layer", layer.quality))?;
+            if !frame.data.is_empty() {
+                frames.push(LayerFrame { layer: index + 1, width: layer_width, height: layer_height, frame });
+            }
+        }
+        Ok(frames)
+    }
+}
+
+/// The layer of `layers` to send a viewer whose quality controller recommends `recommended`:
+/// the best one that needs no more bandwidth, or the leanest when all of them need more.
+pub fn layer_for(layers: &[QualityMode], recommended: QualityMode) -> usize {
+    let budget = recommended.bandwidth_requirement();
+    let by_bandwidth = |&(_, quality): &(usize, &QualityMode)| quality.bandwidth_requirement();
+    layers.iter().enumerate()
+        .filter(|(_, quality)| quality.bandwidth_requirement() <= budget)
+        .max_by(|a, b| by_bandwidth(a).total_cmp(&by_bandwidth(b)))
+        .or_else(|| layers.iter().enumerate().min_by(|a, b| by_bandwidth(a).total_cmp(&by_bandwidth(b))))
+        .map_or(0, |(index, _)| index)
+}
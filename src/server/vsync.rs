@@ -0,0 +1,152 @@
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::server::frame_source::{FrameSource, FrameSourceFactory};
+use crate::server::pixel_format::PixelFormat;
+
+/// Refresh rate assumed where the platform can't signal the real one.
+pub const DEFAULT_REFRESH_HZ: u32 = 60;
+
+/// Signals a display's refreshes, so a frame can be captured just after one completes
+/// instead of while the next is being drawn.
+pub trait RefreshClock {
+    /// Block until the display next refreshes.
+    fn wait_for_refresh(&mut self) -> io::Result<()>;
+    
+    /// What the refreshes come from, for the log.
+    fn name(&self) -> &'static str;
+}
+
+/// Ticks at a fixed rate, not tied to the display; the fallback where there's no
+/// vsync signal to follow.
+pub struct TimerClock {
+    interval: Duration,
+    next: Option<Instant>,
+}
+
+impl TimerClock {
+    pub fn new(refresh_hz: u32) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / refresh_hz.max(1),
+            next: None,
+        }
+    }
+}
+
+impl RefreshClock for TimerClock {
+    fn wait_for_refresh(&mut self) -> io::Result<()> {
+        let now = Instant::now();
+        let tick = self.next.unwrap_or(now);
+        if tick > now {
+            std::thread::sleep(tick - now);
+        }
+        // A caller that fell behind waits for the next tick rather than catching up
+        self.next = Some((tick + self.interval).max(now));
+        Ok(())
+    }
+    
+    fn name(&self) -> &'static str {
+        "timer"
+    }
+}
+
+/// Whether `open_refresh_clock` follows the display's real refreshes on this platform:
+/// CVDisplayLink on macOS and DWM on Windows. Elsewhere it falls back to a timer.
+pub fn vsync_supported() -> bool {
+    cfg!(any(target_os = "macos", target_os = "windows"))
+}
+
+/// The refresh signal of display `display_index`, or a `DEFAULT_REFRESH_HZ` timer where
+/// the platform has none or it can't be opened.
+#[cfg(target_os = "macos")]
+pub fn open_refresh_clock(display_index: usize) -> Box<dyn RefreshClock> {
+    match crate::platform::macos::display_link::DisplayLinkClock::new(display_index) {
+        Ok(clock) => Box::new(clock),
+        Err(e) => {
+            tracing::warn!("No display link for display {}, capturing on a timer: {}", display_index, e);
+            Box::new(TimerClock::new(DEFAULT_REFRESH_HZ))
+        }
+    }
+}
+
+/// The refresh signal of display `display_index`, or a `DEFAULT_REFRESH_HZ` timer where
+/// the platform has none or it can't be opened.
+#[cfg(target_os = "windows")]
+pub fn open_refresh_clock(_display_index: usize) -> Box<dyn RefreshClock> {
+    // DWM composes every monitor on one clock, the primary display's
+    Box::new(crate::platform::windows::dwm::DwmClock)
+}
+
+/// The refresh signal of display `display_index`, or a `DEFAULT_REFRESH_HZ` timer where
+/// the platform has none or it can't be opened.
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+pub fn open_refresh_clock(_display_index: usize) -> Box<dyn RefreshClock> {
+    Box::new(TimerClock::new(DEFAULT_REFRESH_HZ))
+}
+
+/// Waits for a refresh of `clock` before taking each frame from `inner`, so a frame is
+/// a whole display frame rather than one caught mid-update.
+pub struct VsyncSource {
+    inner: Box<dyn FrameSource>,
+    clock: Box<dyn RefreshClock>,
+}
+
+impl VsyncSource {
+    pub fn new(inner: Box<dyn FrameSource>, clock: Box<dyn RefreshClock>) -> Self {
+        Self { inner, clock }
+    }
+}
+
+impl FrameSource for VsyncSource {
+    fn frame(&mut self) -> io::Result<Vec<u8>> {
+        self.clock.wait_for_refresh()?;
+        self.inner.frame()
+    }
+    
+    fn frame_into(&mut self, buffer: &mut Vec<u8>) -> io::Result<()> {
+        self.clock.wait_for_refresh()?;
+        self.inner.frame_into(buffer)
+    }
+    
+    fn width(&self) -> usize {
+        self.inner.width()
+    }
+    
+    fn height(&self) -> usize {
+        self.inner.height()
+    }
+    
+    fn includes_cursor(&self) -> bool {
+        self.inner.includes_cursor()
+    }
+    
+    fn set_include_cursor(&mut self, include: bool) -> bool {
+        self.inner.set_include_cursor(include)
+    }
+    
+    fn scale_factor(&self) -> f64 {
+        self.inner.scale_factor()
+    }
+    
+    fn pixel_format(&self) -> PixelFormat {
+        self.inner.pixel_format()
+    }
+    
+    fn reports_damage(&self) -> bool {
+        self.inner.reports_damage()
+    }
+    
+    fn wait_for_damage(&mut self, timeout: Duration) -> io::Result<bool> {
+        self.inner.wait_for_damage(timeout)
+    }
+}
+
+/// Sources from `inner`, each capturing in step with its display's refreshes.
+pub fn vsync_sources(inner: FrameSourceFactory) -> FrameSourceFactory {
+    Arc::new(move |display| {
+        let clock = open_refresh_clock(display);
+        tracing::info!("Capturing display {} in step with its refresh ({})", display, clock.name());
+        Ok(Box::new(VsyncSource::new(inner(display)?, clock)) as Box<dyn FrameSource>)
+    })
+}
@@ -0,0 +1,145 @@
+mod common;
+
+use std::time::Duration;
+use tokio::sync::RwLock;
+use vox_gui::client::connection::Connection;
+use vox_gui::common::auth::{AccessCode, AccessCodes, Permissions};
+use vox_gui::server::server::ServerControl;
+use vox_gui::server::session::authenticate;
+
+async fn start_server(access_code: AccessCode) -> (String, ServerControl) {
+    start_server_with_codes(access_code, AccessCodes::new()).await
+}
+
+async fn start_server_with_codes(access_code: AccessCode, codes: AccessCodes) -> (String, ServerControl) {
+    let server = common::test_server(access_code)
+        .with_access_codes(codes);
+    let control = server.control();
+    let addr = common::serve(server).await;
+    
+    (addr, control)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_regenerated_code_authenticates_and_old_one_is_rejected() {
+    let access_code = AccessCode::generate();
+    let old_code = access_code.code.clone();
+    let (addr, control) = start_server(access_code).await;
+    
+    // An established session outlives the code it used
+    let (mut first, _, _) = Connection::new();
+    let (_rx, first_tx) = first.connect(&addr, &old_code).await.unwrap();
+    
+    // Six random digits can repeat; keep going until they don't
+    let mut new_code = control.regenerate_access_code().await;
+    while new_code.code == old_code {
+        new_code = control.regenerate_access_code().await;
+    }
+    
+    let (mut stale, _, _) = Connection::new();
+    assert!(stale.connect(&addr, &old_code).await.is_err());
+    
+    let (mut fresh, _, _) = Connection::new();
+    fresh.connect(&addr, &new_code.code).await.unwrap();
+    
+    assert!(!first_tx.is_closed());
+    assert_eq!(control.sessions().await.len(), 2);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_expired_unused_code_is_replaced() {
+    let mut access_code = AccessCode::generate();
+    access_code.expires_at = access_code.created_at - 1;
+    assert!(access_code.is_expired());
+    assert_eq!(access_code.time_left(), Duration::ZERO);
+    let (addr, control) = start_server(access_code).await;
+    
+    let replaced = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let code = control.access_code().await.unwrap();
+            if !code.is_expired() {
+                return code;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .unwrap();
+    assert!(replaced.time_left() > Duration::from_secs(200));
+    
+    let (mut connection, _, _) = Connection::new();
+    connection.connect(&addr, &replaced.code).await.unwrap();
+}
+
+#[test]
+fn test_permissions_parse() {
+    assert_eq!(Permissions::parse("view-only").unwrap(), Permissions::VIEW_ONLY);
+    assert_eq!(Permissions::parse("Full-Control").unwrap(), Permissions::FULL_CONTROL);
+    assert_eq!(
+        Permissions::parse("view-only+file-transfer").unwrap(),
+        Permissions { input: false, file_transfer: true, display_control: false }
+    );
+    assert_eq!(Permissions::parse("input").unwrap(), Permissions { input: true, file_transfer: false, display_control: false });
+    assert!(Permissions::parse("771204").is_err());
+    
+    let mut codes = AccessCodes::new();
+    assert!(codes.add_spec("482913").is_err());
+    assert!(codes.add_spec(":view-only").is_err());
+    assert!(codes.add_spec("482913:everything").is_err());
+    // Only codes the client can type are taken
+    assert!(codes.add_spec("48291377:view-only").is_err());
+    assert!(codes.add_spec("4829:view-only").is_err());
+    assert!(codes.add_spec("audience:view-only").is_err());
+    assert!(codes.add_spec("482-913:view-only").is_err());
+    assert!(codes.is_empty());
+}
+
+#[tokio::test]
+async fn test_each_code_grants_its_own_permissions() {
+    let rotating = AccessCode::generate();
+    let host_code = rotating.code.clone();
+    let access_code = RwLock::new(Some(rotating));
+    
+    let mut codes = AccessCodes::new();
+    codes.add_spec("482913:view-only").unwrap();
+    codes.add_spec("771204:full-control").unwrap();
+    codes.add_spec("305518:view-only+file-transfer").unwrap();
+    assert_eq!(codes.len(), 3);
+    
+    for (code, expected) in [
+        ("482913", Permissions::VIEW_ONLY),
+        ("771204", Permissions::FULL_CONTROL),
+        ("305518", Permissions { input: false, file_transfer: true, display_control: false }),
+        (host_code.as_str(), Permissions::FULL_CONTROL),
+    ] {
+        let response = authenticate(code, &access_code, &codes, 0, None).await;
+        assert!(response.success, "{}", code);
+        assert_eq!(response.permissions, Some(expected), "{}", code);
+    }
+    
+    let unknown = authenticate("intruder", &access_code, &codes, 0, None).await;
+    assert!(!unknown.success);
+    assert_eq!(unknown.permissions, None);
+    
+    // Giving a code again replaces what it grants
+    codes.add("482913", Permissions::FULL_CONTROL);
+    assert_eq!(codes.len(), 3);
+    assert_eq!(codes.permissions_for("482913"), Some(Permissions::FULL_CONTROL));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_session_keeps_the_permissions_of_its_code() {
+    let mut codes = AccessCodes::new();
+    codes.add("135790", Permissions::VIEW_ONLY);
+    let (addr, control) = start_server_with_codes(AccessCode::generate(), codes).await;
+    
+    let (mut viewer, _, _) = Connection::new();
+    viewer.connect(&addr, "135790").await.unwrap();
+    
+    let (mut intruder, _, _) = Connection::new();
+    assert!(intruder.connect(&addr, "999999x").await.is_err());
+    
+    let sessions = control.sessions().await;
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].permissions, Permissions::VIEW_ONLY);
+}
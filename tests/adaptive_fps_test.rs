@@ -0,0 +1,77 @@
+use std::time::Duration;
+use vox_gui::common::adaptive_fps::{AdaptiveFps, MIN_FPS};
+
+const TOTAL_TILES: usize = 400;
+
+fn run(pacer: &mut AdaptiveFps, frames: usize, changed_tiles: usize) -> Vec<f32> {
+    (0..frames)
+        .map(|_| {
+            pacer.record_frame(changed_tiles, TOTAL_TILES);
+            pacer.current_fps()
+        })
+        .collect()
+}
+
+#[test]
+fn test_starts_at_target_rate() {
+    let pacer = AdaptiveFps::new(30);
+    assert_eq!(pacer.current_fps(), 30.0);
+    assert_eq!(pacer.interval(), Duration::from_secs_f32(1.0 / 30.0));
+}
+
+#[test]
+fn test_alternating_static_and_active_periods() {
+    let mut pacer = AdaptiveFps::new(30);
+    
+    for _ in 0..3 {
+        // Static screen: the rate falls steadily toward the floor
+        let static_rates = run(&mut pacer, 60, 0);
+        assert!(static_rates.windows(2).all(|w| w[1] <= w[0]));
+        // ...without jumping straight there
+        assert!(static_rates[0] > 25.0);
+        let floor = *static_rates.last().unwrap();
+        assert!(floor < MIN_FPS as f32 + 0.1, "static rate {}", floor);
+        assert!(floor >= MIN_FPS as f32);
+        assert!(pacer.interval() > Duration::from_millis(450));
+        
+        // Activity: the rate ramps back up within a few frames
+        let active_rates = run(&mut pacer, 10, 40);
+        assert!(active_rates.windows(2).all(|w| w[1] >= w[0]));
+        assert!(active_rates[0] < 20.0);
+        assert!(active_rates[5] > 29.0, "active rate {}", active_rates[5]);
+        assert!(*active_rates.last().unwrap() <= 30.0);
+    }
+}
+
+#[test]
+fn test_a_few_changed_tiles_keep_a_reduced_rate() {
+    let mut pacer = AdaptiveFps::new(30);
+    
+    // A blinking cursor touches one tile; not enough for the full rate
+    run(&mut pacer, 100, 1);
+    let rate = pacer.current_fps();
+    assert!(rate > MIN_FPS as f32 + 1.0 && rate < 15.0, "cursor rate {}", rate);
+}
+
+#[test]
+fn test_full_change_counts_as_active() {
+    let mut pacer = AdaptiveFps::new(60);
+    run(&mut pacer, 100, 0);
+    
+    for _ in 0..10 {
+        pacer.record_full_change();
+    }
+    assert!(pacer.current_fps() > 59.0);
+}
+
+#[test]
+fn test_target_follows_quality_mode() {
+    let mut pacer = AdaptiveFps::new(60);
+    pacer.set_target_fps(15);
+    assert_eq!(pacer.target_fps(), 15);
+    assert_eq!(pacer.current_fps(), 15.0);
+    
+    // Targets below the floor are clamped to it
+    pacer.set_target_fps(1);
+    assert_eq!(pacer.current_fps(), MIN_FPS as f32);
+}
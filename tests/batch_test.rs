@@ -0,0 +1,120 @@
+mod common;
+
+use std::time::Duration;
+use tokio::sync::mpsc;
+use vox_gui::client::connection::Connection;
+use vox_gui::common::auth::AccessCode;
+use vox_gui::common::batch::{collect_batch, pack, unpack, BatchPolicy};
+use vox_gui::common::crypto::{open_message, seal_message, CryptoSession};
+use vox_gui::common::protocol::{Message, Modifiers, MouseButton};
+use vox_gui::common::quality::{QualityChangeReason, QualityMode};
+
+fn describe(messages: &[Message]) -> Vec<String> {
+    messages.iter().map(|message| format!("{:?}", message)).collect()
+}
+
+fn mouse_move(x: i32) -> Message {
+    Message::MouseMove { x, y: 0 }
+}
+
+#[test]
+fn test_batch_round_trips_in_order() {
+    let messages = vec![
+        mouse_move(1),
+        Message::FrameAck { timestamp: 10, received_at: 12 },
+        mouse_move(2),
+        Message::KeyEvent { key: "a".to_string(), pressed: true, repeat: false, modifiers: Modifiers::default() },
+    ];
+    let batch = pack(messages.clone());
+    assert!(matches!(batch, Message::Batch { .. }));
+    
+    // Through the wire format and encryption as a single frame
+    let crypto = CryptoSession::from_shared_secret(&[7u8; 32]).unwrap();
+    let frames = seal_message(&crypto, &batch.serialize().unwrap()).unwrap();
+    assert_eq!(frames.len(), 1);
+    let received = open_message(&crypto, &frames[0]).unwrap().unwrap();
+    
+    assert_eq!(describe(&unpack(received)), describe(&messages));
+}
+
+#[test]
+fn test_single_message_is_not_wrapped() {
+    let message = pack(vec![mouse_move(5)]);
+    assert!(matches!(message, Message::MouseMove { x: 5, .. }));
+    assert_eq!(unpack(message).len(), 1);
+    
+    let nested = Message::Batch { messages: vec![mouse_move(1), pack(vec![mouse_move(2), mouse_move(3)])] };
+    assert_eq!(describe(&unpack(nested)), describe(&[mouse_move(1), mouse_move(2), mouse_move(3)]));
+}
+
+#[tokio::test]
+async fn test_queued_messages_are_coalesced_until_an_urgent_one() {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    for x in 1..=3 {
+        tx.send(mouse_move(x)).unwrap();
+    }
+    tx.send(Message::MouseClick { button: MouseButton::Left, pressed: true, x: 3, y: 0 }).unwrap();
+    tx.send(mouse_move(4)).unwrap();
+    
+    let policy = BatchPolicy::default();
+    let batch = collect_batch(mouse_move(0), &mut rx, &policy).await;
+    
+    // The click closes the batch; the move after it starts the next one
+    assert_eq!(batch.len(), 5);
+    assert!(matches!(batch.last(), Some(Message::MouseClick { .. })));
+    let next = collect_batch(rx.recv().await.unwrap(), &mut rx, &policy).await;
+    assert_eq!(describe(&next), describe(&[mouse_move(4)]));
+}
+
+#[tokio::test]
+async fn test_batch_size_and_window_are_bounded() {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    for x in 0..10 {
+        tx.send(mouse_move(x)).unwrap();
+    }
+    let capped = BatchPolicy { max_messages: 4, ..BatchPolicy::default() };
+    assert_eq!(collect_batch(mouse_move(-1), &mut rx, &capped).await.len(), 4);
+    
+    // A window picks up a message sent shortly after the first, then gives up waiting
+    let mut rx = {
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            tx.send(mouse_move(1)).unwrap();
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            drop(tx);
+        });
+        rx
+    };
+    let windowed = BatchPolicy { window: Duration::from_millis(200), ..BatchPolicy::default() };
+    let started = tokio::time::Instant::now();
+    let batch = collect_batch(mouse_move(0), &mut rx, &windowed).await;
+    assert_eq!(batch.len(), 2);
+    assert!(started.elapsed() < Duration::from_secs(2));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_server_handles_batched_messages_in_order() {
+    let access_code = AccessCode::generate();
+    let code = access_code.code.clone();
+    
+    let server = common::test_server(access_code);
+    let addr = common::serve(server).await;
+    
+    let (mut connection, _, _) = Connection::new();
+    let (mut rx, tx) = connection.connect(&addr, &code).await.unwrap();
+    let modes = [QualityMode::Low, QualityMode::Medium, QualityMode::Minimal];
+    tx.send(Message::Batch {
+        messages: modes.iter().map(|&mode| Message::RequestQualityChange { mode }).collect(),
+    })
+    .unwrap();
+    
+    let mut confirmed = Vec::new();
+    while confirmed.len() < modes.len() {
+        let message = tokio::time::timeout(Duration::from_secs(5), rx.recv()).await.unwrap().unwrap();
+        if let Message::QualityChange { mode, reason: QualityChangeReason::Manual } = message {
+            confirmed.push(mode);
+        }
+    }
+    assert_eq!(confirmed, modes);
+}
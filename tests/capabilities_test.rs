@@ -0,0 +1,52 @@
+use vox_gui::common::capabilities::{negotiate, Capabilities};
+use vox_gui::common::protocol::EncodingType;
+
+fn server_with_hardware() -> Capabilities {
+    Capabilities::new(vec![EncodingType::H264, EncodingType::ZstdCompressed])
+}
+
+fn server_without_hardware() -> Capabilities {
+    Capabilities::new(vec![EncodingType::ZstdCompressed])
+}
+
+#[test]
+fn test_h264_when_both_sides_support_it() {
+    let client = Capabilities::new(vec![EncodingType::H264, EncodingType::ZstdCompressed, EncodingType::Raw]);
+    assert_eq!(negotiate(&server_with_hardware(), &client), Some(EncodingType::H264));
+}
+
+#[test]
+fn test_client_without_decoder_gets_zstd() {
+    let client = Capabilities::new(vec![EncodingType::ZstdCompressed, EncodingType::Raw]);
+    assert_eq!(negotiate(&server_with_hardware(), &client), Some(EncodingType::ZstdCompressed));
+}
+
+#[test]
+fn test_server_without_encoder_never_picks_h264() {
+    let client = Capabilities::new(vec![EncodingType::H264, EncodingType::ZstdCompressed]);
+    assert_eq!(negotiate(&server_without_hardware(), &client), Some(EncodingType::ZstdCompressed));
+}
+
+#[test]
+fn test_server_preference_wins() {
+    // Client prefers zstd but the server would rather send H.264
+    let client = Capabilities::new(vec![EncodingType::ZstdCompressed, EncodingType::H264]);
+    assert_eq!(negotiate(&server_with_hardware(), &client), Some(EncodingType::H264));
+}
+
+#[test]
+fn test_no_common_encoding() {
+    let client = Capabilities::new(vec![EncodingType::WebP]);
+    assert_eq!(negotiate(&server_with_hardware(), &client), None);
+    
+    let empty = Capabilities::new(Vec::new());
+    assert_eq!(negotiate(&server_without_hardware(), &empty), None);
+}
+
+#[test]
+fn test_baseline_is_zstd() {
+    let baseline = Capabilities::baseline();
+    assert!(baseline.supports(EncodingType::ZstdCompressed));
+    assert!(!baseline.supports(EncodingType::H264));
+    assert_eq!(Capabilities::default(), baseline);
+}
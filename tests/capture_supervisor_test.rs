@@ -0,0 +1,111 @@
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use vox_gui::common::encoder::EncoderTuning;
+use vox_gui::server::capture_supervisor::{run_supervised, Backoff};
+use vox_gui::server::frame_source::FrameSource;
+use vox_gui::server::screen_capture::{ScreenCapture, DEFAULT_BITRATE};
+
+/// Panics on its first frame, like a capturer whose display went away.
+struct FlakySource {
+    panics: bool,
+}
+
+impl FrameSource for FlakySource {
+    fn frame(&mut self) -> io::Result<Vec<u8>> {
+        if self.panics {
+            panic!("display disconnected");
+        }
+        Ok(vec![0x40; 64 * 64 * 4])
+    }
+    
+    fn width(&self) -> usize {
+        64
+    }
+    
+    fn height(&self) -> usize {
+        64
+    }
+}
+
+#[test]
+fn test_backoff_doubles_up_to_max_and_resets() {
+    let mut backoff = Backoff::new(Duration::from_millis(100), Duration::from_millis(350));
+    
+    assert_eq!(backoff.next_delay(), Duration::from_millis(100));
+    assert_eq!(backoff.next_delay(), Duration::from_millis(200));
+    assert_eq!(backoff.next_delay(), Duration::from_millis(350));
+    assert_eq!(backoff.next_delay(), Duration::from_millis(350));
+    
+    backoff.reset();
+    assert_eq!(backoff.next_delay(), Duration::from_millis(100));
+}
+
+#[test]
+fn test_panicking_frame_source_is_restarted() {
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let mut failures = Vec::new();
+    let mut frames = 0;
+    
+    run_supervised(
+        Backoff::new(Duration::from_millis(1), Duration::from_secs(1)),
+        || {
+            // The first two capturers panic, the third one works
+            let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+            let source = FlakySource { panics: attempt < 2 };
+            let mut capture = ScreenCapture::from_source(Box::new(source), 30, DEFAULT_BITRATE, EncoderTuning::default())?;
+            
+            loop {
+                if capture.capture_frame()?.is_some() {
+                    frames += 1;
+                    return Ok(());
+                }
+                std::thread::sleep(Duration::from_millis(5));
+            }
+        },
+        |failure, retry_in| failures.push((failure.to_string(), retry_in)),
+    );
+    
+    assert_eq!(frames, 1);
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    assert_eq!(failures.len(), 2);
+    assert!(failures[0].0.contains("panicked: display disconnected"), "{}", failures[0].0);
+    assert_eq!(failures[0].1, Duration::from_millis(1));
+    assert_eq!(failures[1].1, Duration::from_millis(2));
+}
+
+#[test]
+fn test_errors_are_reported_and_retried() {
+    let mut calls = 0;
+    let mut reported = Vec::new();
+    
+    run_supervised(
+        Backoff::new(Duration::from_millis(1), Duration::from_millis(1)),
+        || {
+            calls += 1;
+            if calls == 1 {
+                anyhow::bail!("no display found");
+            }
+            Ok(())
+        },
+        |failure, _| reported.push(failure.to_string()),
+    );
+    
+    assert_eq!(calls, 2);
+    assert_eq!(reported, vec!["Screen capture failed: no display found".to_string()]);
+}
+
+#[test]
+fn test_error_message_round_trip() {
+    use vox_gui::common::protocol::{ErrorKind, Message};
+    
+    let msg = Message::Error { message: "Screen capture panicked".to_string(), kind: ErrorKind::Other };
+    match Message::deserialize(&msg.serialize().unwrap()).unwrap() {
+        Message::Error { message, kind } => {
+            assert_eq!(message, "Screen capture panicked");
+            assert_eq!(kind, ErrorKind::Other);
+        }
+        other => panic!("unexpected message {:?}", other),
+    }
+}
@@ -0,0 +1,54 @@
+use bytes::Bytes;
+use vox_gui::common::frame_processor::{FrameType, TileData};
+use vox_gui::common::protocol::{EncodingType, Message};
+use vox_gui::server::screen_capture::CapturedFrame;
+
+fn frame(data: Bytes, frame_type: FrameType) -> CapturedFrame {
+    CapturedFrame {
+        width: 64,
+        height: 64,
+        data,
+        timestamp: 1,
+        frame_type,
+        tiles: None,
+        encoding: EncodingType::H264,
+        layer: 0,
+        checksum: None,
+    }
+}
+
+#[test]
+fn test_empty_encoder_output_is_not_sent() {
+    assert!(frame(Bytes::new(), FrameType::KeyFrame).to_message(0).is_none());
+    assert!(frame(Bytes::new(), FrameType::DeltaFrame).to_message(0).is_none());
+}
+
+#[test]
+fn test_encoded_frame_is_sent() {
+    let message = frame(Bytes::from_static(&[0, 0, 0, 1, 0x65]), FrameType::KeyFrame).to_message(0);
+    
+    match message {
+        Some(Message::ScreenFrame { data, encoding, .. }) => {
+            assert_eq!(data, vec![0, 0, 0, 1, 0x65]);
+            assert_eq!(encoding, EncodingType::H264);
+        }
+        other => panic!("unexpected message {:?}", other),
+    }
+}
+
+#[test]
+fn test_delta_with_tiles_is_sent() {
+    let mut delta = frame(Bytes::new(), FrameType::DeltaFrame);
+    delta.tiles = Some(vec![TileData { x: 0, y: 0, width: 1, height: 1, data: Bytes::from_static(&[1, 2, 3]) }]);
+    
+    assert!(matches!(delta.to_message(0), Some(Message::DeltaFrame { .. })));
+}
+
+#[test]
+fn test_delta_without_tiles_is_not_sent() {
+    let mut delta = frame(Bytes::new(), FrameType::DeltaFrame);
+    delta.tiles = Some(Vec::new());
+    delta.encoding = EncodingType::ZstdCompressed;
+    
+    assert!(delta.to_message(0).is_none());
+}
@@ -0,0 +1,51 @@
+use vox_gui::client::code_entry::{is_code_separator, CodeEntry};
+use vox_gui::common::auth::CODE_LENGTH;
+
+#[test]
+fn test_pasted_formatted_codes_are_accepted() {
+    for pasted in ["123-456", "123 456", " 123456\n", "12 34 56", "123.456", "123–456", "123\u{a0}456"] {
+        let entry = CodeEntry::parse(pasted);
+        assert_eq!(entry.digits, "123456", "pasted {:?}", pasted);
+        assert!(entry.is_complete());
+        assert_eq!(entry.hint(), None);
+    }
+}
+
+#[test]
+fn test_extra_digits_are_kept_and_flagged() {
+    let entry = CodeEntry::parse("1234-5678");
+    // Nothing is cut off, so the user sees which digits to remove
+    assert_eq!(entry.digits, "12345678");
+    assert!(!entry.is_complete());
+    let hint = entry.hint().unwrap();
+    assert!(hint.contains(&CODE_LENGTH.to_string()));
+    assert!(hint.contains("this has 8"));
+}
+
+#[test]
+fn test_other_characters_are_dropped_with_a_hint() {
+    let entry = CodeEntry::parse("12a3-45b6");
+    assert_eq!(entry.digits, "123456");
+    assert!(entry.is_complete());
+    assert!(entry.hint().unwrap().contains("digits only"));
+}
+
+#[test]
+fn test_partial_code_has_no_hint() {
+    let entry = CodeEntry::parse("123-4");
+    assert_eq!(entry.digits, "1234");
+    assert!(!entry.is_complete());
+    assert_eq!(entry.hint(), None);
+    
+    assert_eq!(CodeEntry::parse("").hint(), None);
+}
+
+#[test]
+fn test_separators() {
+    for c in [' ', '-', '.', '_', '\t', '\u{a0}'] {
+        assert!(is_code_separator(c), "{:?}", c);
+    }
+    for c in ['a', '#', '/', '+'] {
+        assert!(!is_code_separator(c), "{:?}", c);
+    }
+}
@@ -0,0 +1,97 @@
+use vox_gui::common::color::{ColorMatrix, ColorPreference, ColorRange, ColorSpace};
+use vox_gui::common::encoder::EncoderTuning;
+use vox_gui::server::frame_source::TestPatternSource;
+use vox_gui::server::screen_capture::{ScreenCapture, DEFAULT_BITRATE};
+
+const SAMPLES: [(u8, u8, u8); 10] = [
+    (0, 0, 0),
+    (255, 255, 255),
+    (255, 0, 0),
+    (0, 255, 0),
+    (0, 0, 255),
+    (128, 128, 128),
+    (255, 255, 0),
+    (0, 255, 255),
+    (200, 100, 50),
+    (17, 180, 240),
+];
+
+fn assert_round_trip(color_space: ColorSpace, tolerance: u8) {
+    for &(r, g, b) in SAMPLES.iter() {
+        let (y, u, v) = color_space.rgb_to_yuv(r, g, b);
+        let (r2, g2, b2) = color_space.yuv_to_rgb(y, u, v);
+        
+        for (orig, decoded) in [(r, r2), (g, g2), (b, b2)] {
+            assert!(
+                orig.abs_diff(decoded) <= tolerance,
+                "{:?}: ({}, {}, {}) decoded as ({}, {}, {})",
+                color_space, r, g, b, r2, g2, b2
+            );
+        }
+    }
+}
+
+#[test]
+fn test_round_trip_bt601() {
+    assert_round_trip(ColorSpace::new(ColorMatrix::Bt601, ColorRange::Limited), 3);
+    assert_round_trip(ColorSpace::new(ColorMatrix::Bt601, ColorRange::Full), 2);
+}
+
+#[test]
+fn test_round_trip_bt709() {
+    assert_round_trip(ColorSpace::new(ColorMatrix::Bt709, ColorRange::Limited), 3);
+    assert_round_trip(ColorSpace::new(ColorMatrix::Bt709, ColorRange::Full), 2);
+}
+
+#[test]
+fn test_limited_range_levels() {
+    let limited = ColorSpace::new(ColorMatrix::Bt709, ColorRange::Limited);
+    assert_eq!(limited.rgb_to_yuv(0, 0, 0), (16, 128, 128));
+    assert_eq!(limited.rgb_to_yuv(255, 255, 255), (235, 128, 128));
+    
+    let full = ColorSpace::new(ColorMatrix::Bt709, ColorRange::Full);
+    assert_eq!(full.rgb_to_yuv(0, 0, 0), (0, 128, 128));
+    assert_eq!(full.rgb_to_yuv(255, 255, 255), (255, 128, 128));
+}
+
+#[test]
+fn test_matrices_differ() {
+    let bt601 = ColorSpace::new(ColorMatrix::Bt601, ColorRange::Limited);
+    let bt709 = ColorSpace::new(ColorMatrix::Bt709, ColorRange::Limited);
+    
+    // Pure green carries very different luma weight in the two matrices
+    assert_ne!(bt601.luma(0, 255, 0), bt709.luma(0, 255, 0));
+}
+
+#[test]
+fn test_default_matrix_by_resolution() {
+    assert_eq!(ColorSpace::for_resolution(1280, 720).matrix, ColorMatrix::Bt709);
+    assert_eq!(ColorSpace::for_resolution(3840, 2160).matrix, ColorMatrix::Bt709);
+    assert_eq!(ColorSpace::for_resolution(640, 480).matrix, ColorMatrix::Bt601);
+}
+
+#[test]
+fn test_preference_overrides_the_choice_by_resolution() {
+    // Nothing chosen keeps the default
+    assert_eq!(ColorSpace::preferred(ColorPreference::default(), 640, 480), ColorSpace::for_resolution(640, 480));
+    
+    let full = ColorPreference { matrix: None, range: Some(ColorRange::Full) };
+    assert_eq!(ColorSpace::preferred(full, 1920, 1080), ColorSpace::new(ColorMatrix::Bt709, ColorRange::Full));
+    assert_eq!(ColorSpace::preferred(full, 640, 480), ColorSpace::new(ColorMatrix::Bt601, ColorRange::Full));
+    
+    let bt601 = ColorPreference { matrix: Some(ColorMatrix::Bt601), range: None };
+    assert_eq!(ColorSpace::preferred(bt601, 1920, 1080), ColorSpace::new(ColorMatrix::Bt601, ColorRange::Limited));
+}
+
+#[test]
+fn test_capture_encodes_with_the_preferred_color_space() {
+    let color = ColorPreference { matrix: Some(ColorMatrix::Bt601), range: Some(ColorRange::Full) };
+    let tuning = EncoderTuning { color, ..EncoderTuning::default() };
+    let mut capture = ScreenCapture::from_source(Box::new(TestPatternSource::new(1280, 720)), 30, DEFAULT_BITRATE, tuning).unwrap();
+    let expected = ColorSpace::new(ColorMatrix::Bt601, ColorRange::Full);
+    assert_eq!(capture.encoder_settings().color_space, expected);
+    
+    // Still chosen after the frames are scaled down to fit a viewer
+    capture.set_viewport(Some((640, 360)));
+    assert_eq!(capture.encoder_settings().color_space, expected);
+}
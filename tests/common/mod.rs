@@ -0,0 +1,71 @@
+// Shared by the integration tests; each test crate uses only some of it
+#![allow(dead_code)]
+
+use std::collections::VecDeque;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::RwLock;
+use vox_gui::common::auth::AccessCode;
+use vox_gui::common::transport::QuicTransport;
+use vox_gui::server::frame_source::{FrameSource, FrameSourceFactory, TestPatternSource};
+use vox_gui::server::server::Server;
+
+/// A 64x64 test pattern for every display.
+pub fn test_pattern() -> FrameSourceFactory {
+    Arc::new(|_| Ok(Box::new(TestPatternSource::new(64, 64)) as Box<dyn FrameSource>))
+}
+
+/// A server streaming the test pattern to clients with `access_code`.
+pub fn test_server(access_code: AccessCode) -> Server {
+    Server::new(Arc::new(RwLock::new(Some(access_code)))).with_frame_sources(test_pattern())
+}
+
+/// Run `server` on a free loopback port and return its address. The port is bound
+/// before this returns, so clients can connect straight away.
+pub async fn serve(server: Server) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move { server.serve(listener).await });
+    addr
+}
+
+/// Like `serve`, with clients connecting over QUIC.
+pub async fn serve_quic(server: Server) -> SocketAddr {
+    let transport = QuicTransport::new_server("127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let addr = transport.local_addr().unwrap();
+    tokio::spawn(async move { server.serve_quic(transport).await });
+    addr
+}
+
+/// Replays BGRA frames of `width` x `height`, repeating the last one.
+pub struct ScriptedSource {
+    width: usize,
+    height: usize,
+    frames: VecDeque<Vec<u8>>,
+}
+
+impl ScriptedSource {
+    pub fn new(width: usize, height: usize, frames: Vec<Vec<u8>>) -> Self {
+        Self { width, height, frames: frames.into() }
+    }
+}
+
+impl FrameSource for ScriptedSource {
+    fn frame(&mut self) -> io::Result<Vec<u8>> {
+        if self.frames.len() > 1 {
+            Ok(self.frames.pop_front().unwrap())
+        } else {
+            Ok(self.frames[0].clone())
+        }
+    }
+    
+    fn width(&self) -> usize {
+        self.width
+    }
+    
+    fn height(&self) -> usize {
+        self.height
+    }
+}
@@ -0,0 +1,67 @@
+use bytes::Bytes;
+use vox_gui::common::compression::{compress_payload, should_compress};
+use vox_gui::common::encoder::{EncoderTuning, EncoderType};
+use vox_gui::common::protocol::EncodingType;
+use vox_gui::server::frame_source::TestPatternSource;
+use vox_gui::server::screen_capture::{ScreenCapture, DEFAULT_BITRATE};
+
+// An H.264 access unit: start code, SPS header, then payload bytes
+fn h264_payload() -> Bytes {
+    let mut data = vec![0, 0, 0, 1, 0x67, 0x42, 0x00, 0x1f];
+    data.extend((0..256u32).map(|i| (i * 7) as u8));
+    Bytes::from(data)
+}
+
+#[test]
+fn test_h264_frames_are_never_passed_through_zstd() {
+    let data = h264_payload();
+    for level in [0, 3, 9] {
+        let (sent, encoding) = compress_payload(data.clone(), EncodingType::H264, level).unwrap();
+        assert_eq!(encoding, EncodingType::H264);
+        assert_eq!(sent, data);
+        // Not a zstd frame either
+        assert!(zstd::stream::decode_all(&sent[..]).is_err());
+    }
+}
+
+#[test]
+fn test_only_raw_frames_are_compressed() {
+    assert!(should_compress(EncodingType::Raw));
+    for encoding in [EncodingType::ZstdCompressed, EncodingType::H264, EncodingType::WebP] {
+        assert!(!should_compress(encoding), "{:?}", encoding);
+        let (sent, sent_as) = compress_payload(h264_payload(), encoding, 3).unwrap();
+        assert_eq!((sent, sent_as), (h264_payload(), encoding));
+    }
+    
+    let rgb = Bytes::from(vec![40u8; 64 * 64 * 3]);
+    let (sent, encoding) = compress_payload(rgb.clone(), EncodingType::Raw, 3).unwrap();
+    assert_eq!(encoding, EncodingType::ZstdCompressed);
+    assert!(sent.len() < rgb.len());
+    assert_eq!(zstd::stream::decode_all(&sent[..]).unwrap(), rgb.to_vec());
+    
+    // Compressing the result again is a no-op
+    let (again, encoding) = compress_payload(sent.clone(), encoding, 3).unwrap();
+    assert_eq!((again, encoding), (sent, EncodingType::ZstdCompressed));
+}
+
+#[test]
+fn test_encoders_name_their_encoding() {
+    assert_eq!(EncoderType::Hardware.encoding(), EncodingType::H264);
+    assert_eq!(EncoderType::Software.encoding(), EncodingType::WebP);
+    assert!(!should_compress(EncoderType::Hardware.encoding()));
+}
+
+#[test]
+fn test_software_frames_are_compressed_once() {
+    let source = Box::new(TestPatternSource::new(64, 64));
+    let mut capture = ScreenCapture::from_source(source, 60, DEFAULT_BITRATE, EncoderTuning::default()).unwrap();
+    let frame = loop {
+        if let Some(frame) = capture.capture_frame().unwrap() {
+            break frame;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(5));
+    };
+    assert_eq!(frame.encoding, EncodingType::ZstdCompressed);
+    // One zstd pass undoes it all, back to raw RGB
+    assert_eq!(zstd::stream::decode_all(&frame.data[..]).unwrap().len(), 64 * 64 * 3);
+}
@@ -0,0 +1,129 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+use vox_gui::client::connection::Connection;
+use vox_gui::common::auth::AccessCode;
+use vox_gui::server::approval::{ApprovalOutcome, ApprovalPolicy, Approvals, ConnectionRequest};
+use vox_gui::server::server::ServerControl;
+
+async fn start_server(policy: ApprovalPolicy) -> (String, String, ServerControl) {
+    let access_code = AccessCode::generate();
+    let code = access_code.code.clone();
+    
+    let server = common::test_server(access_code)
+        .with_approval(policy);
+    let control = server.control();
+    let addr = common::serve(server).await;
+    
+    (addr, code, control)
+}
+
+async fn wait_for_request(control: &ServerControl) -> ConnectionRequest {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            if let Some(request) = control.pending_connections().into_iter().next() {
+                return request;
+            }
+            control.connection_requested().await;
+        }
+    })
+    .await
+    .expect("no client asked to be let in")
+}
+
+fn ask(timeout: Duration) -> ApprovalPolicy {
+    ApprovalPolicy::Ask { timeout }
+}
+
+#[tokio::test]
+async fn test_requests_are_answered_once() {
+    let approvals = Arc::new(Approvals::new());
+    let waiting = approvals.clone();
+    let outcome = tokio::spawn(async move { waiting.request(None, Duration::from_secs(5)).await });
+    
+    approvals.requested().await;
+    let pending = approvals.pending();
+    assert_eq!(pending.len(), 1);
+    assert!(!approvals.decide(pending[0].id + 1, true));
+    assert!(approvals.decide(pending[0].id, true));
+    assert!(!approvals.decide(pending[0].id, false));
+    
+    assert_eq!(outcome.await.unwrap(), ApprovalOutcome::Approved);
+    assert!(approvals.pending().is_empty());
+}
+
+#[tokio::test]
+async fn test_a_rejected_request_is_not_approved() {
+    let approvals = Arc::new(Approvals::new());
+    let waiting = approvals.clone();
+    let outcome = tokio::spawn(async move { waiting.request(None, Duration::from_secs(5)).await });
+    
+    approvals.requested().await;
+    assert!(approvals.decide(approvals.pending()[0].id, false));
+    assert_eq!(outcome.await.unwrap(), ApprovalOutcome::Rejected);
+}
+
+#[tokio::test]
+async fn test_an_unanswered_request_times_out_and_is_withdrawn() {
+    let approvals = Approvals::new();
+    let outcome = approvals.request(None, Duration::from_millis(50)).await;
+    assert_eq!(outcome, ApprovalOutcome::TimedOut);
+    assert!(approvals.pending().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_an_approved_client_gets_a_session() {
+    let (addr, code, control) = start_server(ask(Duration::from_secs(10))).await;
+    
+    let connecting = tokio::spawn(async move {
+        let (mut connection, _, _) = Connection::new();
+        connection.connect(&addr, &code).await.map(|channels| (connection, channels))
+    });
+    
+    let request = wait_for_request(&control).await;
+    assert!(request.peer.unwrap().ip().is_loopback());
+    // Authenticated but not let in yet: no session, so no frames and no input
+    assert!(control.sessions().await.is_empty());
+    
+    assert!(control.answer_connection(request.id, true));
+    assert!(connecting.await.unwrap().is_ok());
+    assert_eq!(control.sessions().await.len(), 1);
+    assert!(control.pending_connections().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_a_declined_client_is_turned_away() {
+    let (addr, code, control) = start_server(ask(Duration::from_secs(10))).await;
+    
+    let connecting = tokio::spawn(async move {
+        let (mut connection, _, _) = Connection::new();
+        connection.connect(&addr, &code).await.map(|_| ())
+    });
+    
+    let request = wait_for_request(&control).await;
+    assert!(control.answer_connection(request.id, false));
+    assert!(connecting.await.unwrap().is_err());
+    assert!(control.sessions().await.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_a_client_nobody_answers_is_turned_away_after_the_timeout() {
+    let (addr, code, control) = start_server(ask(Duration::from_millis(300))).await;
+    
+    let (mut connection, _, _) = Connection::new();
+    let result = tokio::time::timeout(Duration::from_secs(5), connection.connect(&addr, &code)).await.unwrap();
+    assert!(result.is_err());
+    assert!(control.sessions().await.is_empty());
+    assert!(control.pending_connections().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_clients_get_in_at_once_when_approval_is_off() {
+    let (addr, code, control) = start_server(ApprovalPolicy::AutoAccept).await;
+    
+    let (mut connection, _, _) = Connection::new();
+    connection.connect(&addr, &code).await.unwrap();
+    assert_eq!(control.sessions().await.len(), 1);
+    assert!(control.pending_connections().is_empty());
+}
@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use vox_gui::client::history::{ConnectionHistory, HISTORY_KEY, MAX_HISTORY};
+
+#[derive(Default)]
+struct MemoryStorage {
+    values: HashMap<String, String>,
+}
+
+impl eframe::Storage for MemoryStorage {
+    fn get_string(&self, key: &str) -> Option<String> {
+        self.values.get(key).cloned()
+    }
+    
+    fn set_string(&mut self, key: &str, value: String) {
+        self.values.insert(key.to_string(), value);
+    }
+    
+    fn flush(&mut self) {}
+}
+
+#[test]
+fn most_recent_address_comes_first_without_duplicates() {
+    let mut history = ConnectionHistory::default();
+    history.record("10.0.0.1:8080");
+    history.record("10.0.0.2:8080");
+    history.record(" 10.0.0.1:8080 ");
+    history.record("");
+    
+    assert_eq!(history.addresses(), ["10.0.0.1:8080", "10.0.0.2:8080"]);
+    assert_eq!(history.most_recent(), Some("10.0.0.1:8080"));
+}
+
+#[test]
+fn oldest_addresses_fall_off_past_the_cap() {
+    let mut history = ConnectionHistory::default();
+    for i in 0..MAX_HISTORY + 3 {
+        history.record(&format!("host{}:8080", i));
+    }
+    
+    assert_eq!(history.addresses().len(), MAX_HISTORY);
+    assert_eq!(history.most_recent(), Some(format!("host{}:8080", MAX_HISTORY + 2).as_str()));
+    assert!(!history.addresses().contains(&"host2:8080".to_string()));
+    assert!(history.addresses().contains(&"host3:8080".to_string()));
+}
+
+#[test]
+fn history_survives_a_save_and_load() {
+    let mut storage = MemoryStorage::default();
+    let mut history = ConnectionHistory::default();
+    history.record("192.168.1.5:8080");
+    history.record("example.com:9000");
+    history.save(&mut storage);
+    
+    let loaded = ConnectionHistory::load(Some(&storage));
+    assert_eq!(loaded, history);
+    
+    history.clear();
+    assert!(history.is_empty());
+    history.save(&mut storage);
+    assert!(ConnectionHistory::load(Some(&storage)).is_empty());
+}
+
+#[test]
+fn missing_or_corrupt_storage_gives_an_empty_history() {
+    assert!(ConnectionHistory::load(None).is_empty());
+    
+    let mut storage = MemoryStorage::default();
+    eframe::Storage::set_string(&mut storage, HISTORY_KEY, "not json".to_string());
+    assert!(ConnectionHistory::load(Some(&storage)).is_empty());
+}
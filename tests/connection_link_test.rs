@@ -0,0 +1,136 @@
+use vox_gui::common::link::{shareable_address, ConnectionLink};
+use vox_gui::common::qr::{add_error_correction, format_bits, gf_mul, QrCode, MAX_BYTES};
+
+#[test]
+fn test_link_round_trips() {
+    for address in ["192.168.1.20:8080", "localhost:9000", "[::1]:8080", "[fe80::1]:443", "vox.example.com:65535"] {
+        let link = ConnectionLink::new(address, "042917");
+        let uri = link.to_uri();
+        assert!(uri.starts_with("vox://"));
+        assert_eq!(uri.parse::<ConnectionLink>().unwrap(), link, "{}", uri);
+    }
+}
+
+#[test]
+fn test_link_parsing_is_lenient_about_presentation() {
+    let expected = ConnectionLink::new("10.0.0.5:8080", "123456");
+    for uri in [
+        "  vox://10.0.0.5:8080?code=123456\n",
+        "VOX://10.0.0.5:8080?code=123456",
+        "vox://10.0.0.5:8080/?code=123456",
+        "vox://10.0.0.5:8080?display=1&code=123456",
+    ] {
+        assert_eq!(ConnectionLink::parse(uri).unwrap(), expected, "{:?}", uri);
+    }
+}
+
+#[test]
+fn test_malformed_links_are_rejected() {
+    for uri in [
+        "",
+        "10.0.0.5:8080",
+        "http://10.0.0.5:8080?code=123456",
+        "vox://10.0.0.5:8080",
+        "vox://10.0.0.5:8080?code=",
+        "vox://10.0.0.5:8080?code=12345",
+        "vox://10.0.0.5:8080?code=1234567",
+        "vox://10.0.0.5:8080?code=12a456",
+        "vox://10.0.0.5?code=123456",
+        "vox://10.0.0.5:?code=123456",
+        "vox://10.0.0.5:0?code=123456",
+        "vox://10.0.0.5:70000?code=123456",
+        "vox://:8080?code=123456",
+        "vox://::1:8080?code=123456",
+        "vox://[::1]?code=123456",
+        "vox://[not-v6]:8080?code=123456",
+        "vox://user@host:8080?code=123456",
+        "vox://host/path:8080?code=123456",
+    ] {
+        assert!(ConnectionLink::parse(uri).is_err(), "accepted {:?}", uri);
+    }
+}
+
+#[test]
+fn test_looks_like_link() {
+    assert!(ConnectionLink::looks_like_link("vox://10.0.0.5:8080?code=123456"));
+    assert!(ConnectionLink::looks_like_link(" Vox://broken"));
+    assert!(!ConnectionLink::looks_like_link("10.0.0.5:8080"));
+    assert!(!ConnectionLink::looks_like_link("vox.example.com:8080"));
+}
+
+#[test]
+fn test_shareable_address_replaces_wildcard_binds() {
+    assert_eq!(shareable_address("192.168.1.20:8080"), "192.168.1.20:8080");
+    assert_eq!(shareable_address("example.com:8080"), "example.com:8080");
+    
+    let shared = shareable_address("0.0.0.0:8080");
+    let addr: std::net::SocketAddr = shared.parse().unwrap();
+    assert!(!addr.ip().is_unspecified());
+    assert_eq!(addr.port(), 8080);
+    assert!(ConnectionLink::parse(&ConnectionLink::new(shared, "123456").to_uri()).is_ok());
+}
+
+#[test]
+fn test_qr_version_follows_length() {
+    assert_eq!(QrCode::encode(b"vox://1.2.3.4:80?code=1").unwrap().version(), 2);
+    let link = ConnectionLink::new("192.168.100.200:65535", "123456").to_uri();
+    let qr = QrCode::encode(link.as_bytes()).unwrap();
+    assert_eq!(qr.version(), 3);
+    assert_eq!(qr.size(), 29);
+    
+    assert_eq!(QrCode::encode(&[b'a'; MAX_BYTES]).unwrap().version(), 5);
+    assert!(QrCode::encode(&[b'a'; MAX_BYTES + 1]).is_err());
+}
+
+#[test]
+fn test_qr_has_finder_and_timing_patterns() {
+    let qr = QrCode::encode(b"vox://10.0.0.5:8080?code=123456").unwrap();
+    let size = qr.size();
+    for (ox, oy) in [(0, 0), (size - 7, 0), (0, size - 7)] {
+        for y in 0..7 {
+            for x in 0..7 {
+                let ring = (x as i32 - 3).abs().max((y as i32 - 3).abs());
+                assert_eq!(qr.module(ox + x, oy + y), ring != 2, "finder at ({}, {})", ox, oy);
+            }
+        }
+    }
+    for i in 8..size - 8 {
+        assert_eq!(qr.module(i, 6), i % 2 == 0);
+        assert_eq!(qr.module(6, i), i % 2 == 0);
+    }
+    assert!(qr.module(8, size - 8));
+}
+
+#[test]
+fn test_qr_format_bits_are_readable() {
+    let qr = QrCode::encode(b"vox://10.0.0.5:8080?code=123456").unwrap();
+    let size = qr.size();
+    // Second copy of the format bits: along row 8 on the right, then down column 8
+    let mut bits = 0u32;
+    for i in 0..8 {
+        bits |= (qr.module(size - 1 - i, 8) as u32) << i;
+    }
+    for i in 8..15 {
+        bits |= (qr.module(8, size - 15 + i) as u32) << i;
+    }
+    let mask = (0..8).find(|&mask| format_bits(mask) == bits);
+    assert!(mask.is_some(), "format bits {:015b} match no mask", bits);
+    // Level L
+    assert_eq!((bits ^ 0x5412) >> 13, 0b01);
+}
+
+#[test]
+fn test_reed_solomon_codewords_have_no_syndrome() {
+    // A codeword polynomial is a multiple of the generator, so it vanishes at α^0..α^(n-1)
+    let data: Vec<u8> = b"vox://10.0.0.5:8080?code=123456".to_vec();
+    let codewords = add_error_correction(&data, 15);
+    assert_eq!(codewords.len(), data.len() + 15);
+    assert_eq!(&codewords[..data.len()], &data[..]);
+    
+    let mut root = 1u8;
+    for _ in 0..15 {
+        let value = codewords.iter().fold(0u8, |acc, &c| gf_mul(acc, root) ^ c);
+        assert_eq!(value, 0);
+        root = gf_mul(root, 2);
+    }
+}
@@ -0,0 +1,135 @@
+mod common;
+
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedReceiver;
+use vox_gui::client::connection::Connection;
+use vox_gui::common::adaptive_fps::{AdaptiveFps, MIN_FPS};
+use vox_gui::common::auth::AccessCode;
+use vox_gui::common::color::ColorSpace;
+use vox_gui::common::encoder::{EncoderSettings, EncoderTuning, WebpMode};
+use vox_gui::common::protocol::Message;
+use vox_gui::common::quality::{ContentProfile, QualityMode, TEXT_MAX_FPS};
+use vox_gui::server::frame_source::TestPatternSource;
+use vox_gui::server::screen_capture::{ScreenCapture, DEFAULT_BITRATE};
+
+fn settings(fps: u32, keyframe_interval: u32, webp_mode: WebpMode) -> EncoderSettings {
+    EncoderSettings {
+        width: 1920,
+        height: 1080,
+        fps,
+        bitrate: DEFAULT_BITRATE,
+        keyframe_interval,
+        color_space: ColorSpace::for_resolution(1920, 1080),
+        tuning: EncoderTuning { webp_mode, ..EncoderTuning::default() },
+    }
+}
+
+#[test]
+fn test_balanced_leaves_settings_alone() {
+    let base = settings(30, 60, WebpMode::Lossy { quality: 80.0 });
+    let tuned = ContentProfile::Balanced.encoder_settings(base);
+    assert_eq!((tuned.fps, tuned.keyframe_interval, tuned.tuning), (30, 60, base.tuning));
+    assert_eq!(ContentProfile::default(), ContentProfile::Balanced);
+}
+
+#[test]
+fn test_text_is_lossless_slow_and_keyframes_rarely() {
+    let tuned = ContentProfile::Text.encoder_settings(settings(60, 120, WebpMode::Lossy { quality: 65.0 }));
+    assert_eq!(tuned.fps, TEXT_MAX_FPS);
+    assert_eq!(tuned.keyframe_interval, 240);
+    assert_eq!(tuned.tuning.webp_mode, WebpMode::Lossless);
+    assert!(ContentProfile::Text.lossless());
+    
+    // A rate already below the cap is kept
+    assert_eq!(ContentProfile::Text.encoder_settings(settings(10, 20, WebpMode::Lossless)).fps, 10);
+    assert_eq!(ContentProfile::Text.fps_floor(15), MIN_FPS);
+}
+
+#[test]
+fn test_motion_keeps_its_rate_and_keyframes_often() {
+    let tuned = ContentProfile::Motion.encoder_settings(settings(60, 120, WebpMode::Lossless));
+    assert_eq!(tuned.fps, 60);
+    assert_eq!(tuned.keyframe_interval, 60);
+    assert!(matches!(tuned.tuning.webp_mode, WebpMode::Lossy { .. }));
+    assert!(!ContentProfile::Motion.lossless());
+    
+    // An already lossy mode keeps its quality; the interval never reaches zero
+    let lossy = ContentProfile::Motion.encoder_settings(settings(10, 1, WebpMode::Lossy { quality: 65.0 }));
+    assert_eq!(lossy.tuning.webp_mode, WebpMode::Lossy { quality: 65.0 });
+    assert_eq!(lossy.keyframe_interval, 1);
+    
+    assert_eq!(ContentProfile::Motion.fps_floor(30), 30);
+}
+
+#[test]
+fn test_floor_keeps_the_pacer_from_slowing() {
+    let mut pacer = AdaptiveFps::new(30);
+    for _ in 0..100 {
+        pacer.record_frame(0, 100);
+    }
+    assert!(pacer.current_fps() < 3.0);
+    
+    pacer.set_min_fps(ContentProfile::Motion.fps_floor(30));
+    assert_eq!(pacer.current_fps(), 30.0);
+    
+    // The floor never goes below MIN_FPS or above the target
+    pacer.set_min_fps(0);
+    assert!(pacer.current_fps() >= MIN_FPS as f32);
+    pacer.set_min_fps(100);
+    assert_eq!(pacer.current_fps(), 30.0);
+}
+
+#[test]
+fn test_capture_applies_the_profile() {
+    let source = Box::new(TestPatternSource::new(64, 64));
+    let mut capture = ScreenCapture::from_source(source, 30, DEFAULT_BITRATE, EncoderTuning::default()).unwrap();
+    assert_eq!((capture.target_fps(), capture.keyframe_interval()), (30, 60));
+    
+    capture.set_content_profile(ContentProfile::Text);
+    assert_eq!(capture.content_profile(), ContentProfile::Text);
+    assert_eq!((capture.target_fps(), capture.keyframe_interval()), (TEXT_MAX_FPS, 120));
+    
+    // A quality change keeps the profile's cap
+    capture.set_quality(QualityMode::Ultra);
+    assert_eq!(capture.target_fps(), TEXT_MAX_FPS);
+    
+    capture.set_content_profile(ContentProfile::Motion);
+    assert_eq!((capture.target_fps(), capture.keyframe_interval()), (30, 30));
+    
+    capture.set_content_profile(ContentProfile::Balanced);
+    assert_eq!((capture.target_fps(), capture.keyframe_interval()), (30, 60));
+}
+
+async fn wait_for(rx: &mut UnboundedReceiver<Message>, profile: ContentProfile) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match rx.recv().await {
+                Some(Message::ContentProfileApplied { profile: applied }) if applied == profile => return,
+                Some(_) => {}
+                None => panic!("connection closed"),
+            }
+        }
+    })
+    .await
+    .unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_server_confirms_the_requested_profile() {
+    let access_code = AccessCode::generate();
+    let code = access_code.code.clone();
+    
+    let server = common::test_server(access_code);
+    let addr = common::serve(server).await;
+    
+    let (mut connection, _, _) = Connection::new();
+    connection.set_content_profile(ContentProfile::Text);
+    let (mut rx, tx) = connection.connect(&addr, &code).await.unwrap();
+    
+    // Asked for on connect
+    wait_for(&mut rx, ContentProfile::Text).await;
+    
+    // And changed mid-session
+    tx.send(Message::SetContentProfile { profile: ContentProfile::Motion }).unwrap();
+    wait_for(&mut rx, ContentProfile::Motion).await;
+}
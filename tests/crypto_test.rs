@@ -1,4 +1,8 @@
-use vox_gui::common::crypto::{CryptoSession, KeyExchange};
+use vox_gui::common::crypto::{
+    is_corrupt, negotiate_cipher, open_message, parse_public_key, seal_message, Cipher, CryptoSession, Encryptor, KeyExchange, RekeyPolicy,
+    NONCE_LEN, TAG_LEN,
+};
+use vox_gui::common::protocol::Message;
 
 #[test]
 fn test_encryption_decryption() {
@@ -54,4 +58,212 @@ fn test_nonce_uniqueness() {
     let decrypted2 = crypto.decrypt(&encrypted2).unwrap();
     assert_eq!(decrypted1, decrypted2);
     assert_eq!(plaintext, &decrypted1[..]);
-}
\ No newline at end of file
+}
+#[test]
+fn test_encryptor_trait_objects() {
+    use vox_gui::common::crypto::{Encryptor, NullEncryptor};
+    
+    let plaintext = b"frame data";
+    
+    let aes: Box<dyn Encryptor> = Box::new(CryptoSession::from_shared_secret(&[7u8; 32]).unwrap());
+    let encrypted = aes.encrypt(plaintext).unwrap();
+    assert_ne!(&encrypted[12..], &plaintext[..]);
+    assert_eq!(aes.decrypt(&encrypted).unwrap(), plaintext);
+    
+    // Null passes data through untouched in both directions
+    let null: Box<dyn Encryptor> = Box::new(NullEncryptor);
+    assert_eq!(null.encrypt(plaintext).unwrap(), plaintext);
+    assert_eq!(null.decrypt(plaintext).unwrap(), plaintext);
+}
+
+
+fn session_pair(policy: RekeyPolicy) -> (CryptoSession, CryptoSession) {
+    let client = KeyExchange::new();
+    let server = KeyExchange::new();
+    let client_public = client.public_key().clone();
+    let server_public = server.public_key().clone();
+    
+    let client_crypto = CryptoSession::from_shared_secret(&client.compute_shared_secret(&server_public)).unwrap();
+    let server_crypto = CryptoSession::from_shared_secret(&server.compute_shared_secret(&client_public)).unwrap();
+    (client_crypto.with_rekey_policy(policy), server_crypto.with_rekey_policy(policy))
+}
+
+#[test]
+fn session_survives_rekeys_mid_stream() {
+    let policy = RekeyPolicy { max_messages: 3, max_bytes: u64::MAX };
+    let (client, server) = session_pair(policy);
+    
+    // Both directions interleaved, as on a live session
+    let mut rekeys_seen = 0;
+    for i in 0..20u64 {
+        let frame = Message::Ping { timestamp: i }.serialize().unwrap();
+        let wire = seal_message(&server, &frame).unwrap();
+        rekeys_seen += wire.len() - 1;
+        let received: Vec<Message> = wire.iter()
+            .filter_map(|data| open_message(&client, data).unwrap())
+            .collect();
+        assert!(matches!(received[..], [Message::Ping { timestamp }] if timestamp == i));
+        
+        let ack = Message::Pong { timestamp: i }.serialize().unwrap();
+        for data in seal_message(&client, &ack).unwrap() {
+            if let Some(message) = open_message(&server, &data).unwrap() {
+                assert!(matches!(message, Message::Pong { timestamp } if timestamp == i));
+            }
+        }
+    }
+    
+    assert!(rekeys_seen >= 5, "expected several rotations, saw {}", rekeys_seen);
+    assert_eq!(server.send_epoch(), rekeys_seen as u32);
+}
+
+#[test]
+fn rotated_key_differs_from_the_handshake_key() {
+    let policy = RekeyPolicy { max_messages: 1, max_bytes: u64::MAX };
+    let shared_secret = [7u8; 32];
+    let sender = CryptoSession::from_shared_secret(&shared_secret).unwrap().with_rekey_policy(policy);
+    let stale = CryptoSession::from_shared_secret(&shared_secret).unwrap();
+    
+    let payload = Message::Ping { timestamp: 1 }.serialize().unwrap();
+    let first = seal_message(&sender, &payload).unwrap();
+    assert_eq!(first.len(), 1);
+    assert!(open_message(&stale, &first[0]).unwrap().is_some());
+    
+    // The next message is due a rotation: the announcement still opens with the old key,
+    // the message behind it doesn't
+    let second = seal_message(&sender, &payload).unwrap();
+    assert_eq!(second.len(), 2);
+    assert!(stale.decrypt(&second[0]).is_ok());
+    assert!(stale.decrypt(&second[1]).is_err());
+}
+
+#[test]
+fn out_of_sequence_rekey_is_rejected() {
+    let (client, server) = session_pair(RekeyPolicy::default());
+    let skip = Message::Rekey { epoch: 2 }.serialize().unwrap();
+    let data = server.encrypt(&skip).unwrap();
+    assert!(open_message(&client, &data).is_err());
+}
+
+#[test]
+fn messages_shorter_than_nonce_and_tag_are_rejected() {
+    let crypto = CryptoSession::from_shared_secret(&[3u8; 32]).unwrap();
+    
+    for len in [0, NONCE_LEN, NONCE_LEN + TAG_LEN - 1] {
+        let err = crypto.decrypt(&vec![0u8; len]).unwrap_err();
+        assert!(is_corrupt(&err), "{}", err);
+        assert!(err.to_string().contains("shorter"), "{}", err);
+    }
+    
+    // An empty plaintext is exactly nonce + tag and still fine
+    let empty = crypto.encrypt(b"").unwrap();
+    assert_eq!(empty.len(), NONCE_LEN + TAG_LEN);
+    assert!(crypto.decrypt(&empty).unwrap().is_empty());
+}
+
+#[test]
+fn corrupt_tag_or_truncated_ciphertext_only_loses_that_message() {
+    let (sender, receiver) = session_pair(RekeyPolicy::default());
+    let serialized = Message::Ping { timestamp: 7 }.serialize().unwrap();
+    
+    let mut tampered = sender.encrypt(&serialized).unwrap();
+    *tampered.last_mut().unwrap() ^= 0x01;
+    let err = open_message(&receiver, &tampered).unwrap_err();
+    assert!(is_corrupt(&err), "{}", err);
+    
+    let mut truncated = sender.encrypt(&serialized).unwrap();
+    truncated.pop();
+    assert!(is_corrupt(&receiver.decrypt(&truncated).unwrap_err()));
+    
+    let mut flipped_body = sender.encrypt(&serialized).unwrap();
+    flipped_body[NONCE_LEN] ^= 0x80;
+    assert!(is_corrupt(&receiver.decrypt(&flipped_body).unwrap_err()));
+    
+    // The session itself is unharmed
+    let good = sender.encrypt(&serialized).unwrap();
+    assert!(matches!(open_message(&receiver, &good).unwrap(), Some(Message::Ping { timestamp: 7 })));
+}
+
+#[test]
+fn undecodable_plaintext_is_not_reported_as_corruption() {
+    let (sender, receiver) = session_pair(RekeyPolicy::default());
+    let garbage = sender.encrypt(&[0xFF; 8]).unwrap();
+    
+    let err = open_message(&receiver, &garbage).unwrap_err();
+    assert!(!is_corrupt(&err), "{}", err);
+}
+
+#[test]
+fn chacha_session_round_trips_and_rekeys() {
+    let policy = RekeyPolicy { max_messages: 2, max_bytes: u64::MAX };
+    let (client, server) = session_pair(policy);
+    let (client, server) = (client.with_cipher(Cipher::ChaCha20Poly1305), server.with_cipher(Cipher::ChaCha20Poly1305));
+    assert_eq!(client.cipher(), Cipher::ChaCha20Poly1305);
+    
+    for i in 0..10u64 {
+        let frame = Message::Ping { timestamp: i }.serialize().unwrap();
+        let received: Vec<Message> = seal_message(&server, &frame).unwrap().iter()
+            .filter_map(|data| open_message(&client, data).unwrap())
+            .collect();
+        assert!(matches!(received[..], [Message::Ping { timestamp }] if timestamp == i));
+    }
+    
+    // Same framing as AES-GCM
+    assert_eq!(client.encrypt(b"").unwrap().len(), NONCE_LEN + TAG_LEN);
+}
+
+#[test]
+fn sessions_with_different_ciphers_cannot_talk() {
+    let aes = CryptoSession::from_shared_secret(&[9u8; 32]).unwrap();
+    let chacha = CryptoSession::from_shared_secret(&[9u8; 32]).unwrap().with_cipher(Cipher::ChaCha20Poly1305);
+    assert_eq!(aes.cipher(), Cipher::Aes256Gcm);
+    
+    let err = chacha.decrypt(&aes.encrypt(b"frame data").unwrap()).unwrap_err();
+    assert!(is_corrupt(&err), "{}", err);
+}
+
+#[test]
+fn cipher_negotiation() {
+    let aes_first = [Cipher::Aes256Gcm, Cipher::ChaCha20Poly1305];
+    let chacha_first = [Cipher::ChaCha20Poly1305, Cipher::Aes256Gcm];
+    
+    assert_eq!(negotiate_cipher(&aes_first, &aes_first), Some(Cipher::Aes256Gcm));
+    // Either side without AES instructions gets ChaCha20
+    assert_eq!(negotiate_cipher(&aes_first, &chacha_first), Some(Cipher::ChaCha20Poly1305));
+    assert_eq!(negotiate_cipher(&chacha_first, &aes_first), Some(Cipher::ChaCha20Poly1305));
+    // Unless the other side can't do it
+    assert_eq!(negotiate_cipher(&chacha_first, &[Cipher::Aes256Gcm]), Some(Cipher::Aes256Gcm));
+    assert_eq!(negotiate_cipher(&[Cipher::Aes256Gcm], &chacha_first), Some(Cipher::Aes256Gcm));
+    assert_eq!(negotiate_cipher(&[Cipher::Aes256Gcm], &[Cipher::ChaCha20Poly1305]), None);
+    
+    assert_eq!(Cipher::preferred().len(), 2);
+}
+
+#[test]
+fn test_small_order_public_keys_are_refused() {
+    let mut order_eight = [0u8; 32];
+    order_eight.copy_from_slice(&[
+        0xe0, 0xeb, 0x7a, 0x7c, 0x3b, 0x41, 0xb8, 0xae, 0x16, 0x56, 0xe3, 0xfa, 0xf1, 0x9f, 0xc4, 0x6a,
+        0xda, 0x09, 0x8d, 0xeb, 0x9c, 0x32, 0xb1, 0xfd, 0x86, 0x62, 0x05, 0x16, 0x5f, 0x49, 0xb8, 0x00,
+    ]);
+    // The same point with the ignored top bit set
+    let mut order_eight_high = order_eight;
+    order_eight_high[31] |= 0x80;
+    
+    for key in [[0u8; 32], order_eight, order_eight_high] {
+        assert!(parse_public_key(&key).is_err(), "{:02x?}", key);
+        assert!(KeyExchange::new().agree(&key).is_err(), "{:02x?}", key);
+    }
+    
+    assert!(parse_public_key(&[7u8; 31]).is_err());
+    assert!(KeyExchange::new().agree(&[7u8; 33]).is_err());
+}
+
+#[test]
+fn test_agree_matches_on_both_sides() {
+    let client = KeyExchange::new();
+    let server = KeyExchange::new();
+    let client_public = client.public_key_bytes();
+    let server_public = server.public_key_bytes();
+    
+    assert_eq!(client.agree(&server_public).unwrap(), server.agree(&client_public).unwrap());
+}
@@ -0,0 +1,148 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use egui::{Pos2, Rect};
+use vox_gui::client::view::{local_position, remote_position};
+use vox_gui::common::encoder::EncoderTuning;
+use vox_gui::common::frame_processor::FrameType;
+use vox_gui::common::protocol::Message;
+use vox_gui::server::frame_source::FrameSource;
+use vox_gui::server::screen_capture::{CapturedFrame, ScreenCapture, DEFAULT_BITRATE};
+
+const SIZE: usize = 64;
+
+/// A static screen whose backend may or may not be able to toggle the cursor.
+struct CursorSource {
+    cursor: Arc<AtomicBool>,
+    togglable: bool,
+}
+
+impl FrameSource for CursorSource {
+    fn frame(&mut self) -> io::Result<Vec<u8>> {
+        Ok(vec![40; SIZE * SIZE * 4])
+    }
+    
+    fn width(&self) -> usize {
+        SIZE
+    }
+    
+    fn height(&self) -> usize {
+        SIZE
+    }
+    
+    fn includes_cursor(&self) -> bool {
+        self.cursor.load(Ordering::Relaxed)
+    }
+    
+    fn set_include_cursor(&mut self, include: bool) -> bool {
+        if self.togglable {
+            self.cursor.store(include, Ordering::Relaxed);
+        }
+        include == self.includes_cursor()
+    }
+}
+
+fn open_capture(cursor: bool, togglable: bool) -> (ScreenCapture, Arc<AtomicBool>) {
+    let state = Arc::new(AtomicBool::new(cursor));
+    let source = CursorSource { cursor: state.clone(), togglable };
+    let mut capture = ScreenCapture::from_source(Box::new(source), 100, DEFAULT_BITRATE, EncoderTuning::default()).unwrap();
+    capture.set_hardware_encoding(false);
+    (capture, state)
+}
+
+fn next_frame(capture: &mut ScreenCapture) -> CapturedFrame {
+    for _ in 0..1000 {
+        if let Some(frame) = capture.capture_frame().unwrap() {
+            return frame;
+        }
+        std::thread::sleep(Duration::from_millis(2));
+    }
+    panic!("no frame captured");
+}
+
+#[test]
+fn test_togglable_backend_drops_the_cursor() {
+    let (mut capture, state) = open_capture(true, true);
+    assert!(capture.includes_cursor());
+    
+    // First frame is the initial keyframe
+    assert_eq!(next_frame(&mut capture).frame_type, FrameType::KeyFrame);
+    
+    assert!(capture.set_include_cursor(false));
+    assert!(!capture.includes_cursor());
+    assert!(!state.load(Ordering::Relaxed));
+    
+    // Clients need a fresh frame without the cursor, even though the screen didn't change
+    assert_eq!(next_frame(&mut capture).frame_type, FrameType::KeyFrame);
+    
+    // Asking again is a no-op
+    assert!(capture.set_include_cursor(false));
+}
+
+#[test]
+fn test_fixed_backend_reports_what_frames_hold() {
+    // Like PipeWire's embedded cursor: always drawn in
+    let (mut capture, _) = open_capture(true, false);
+    assert!(!capture.set_include_cursor(false));
+    assert!(capture.includes_cursor());
+    assert!(capture.set_include_cursor(true));
+    
+    // Like scrap on Windows and X11: never drawn in
+    let (mut capture, _) = open_capture(false, false);
+    assert!(!capture.set_include_cursor(true));
+    assert!(!capture.includes_cursor());
+    assert!(capture.set_include_cursor(false));
+}
+
+#[test]
+fn test_default_sources_keep_the_cursor() {
+    struct Plain;
+    impl FrameSource for Plain {
+        fn frame(&mut self) -> io::Result<Vec<u8>> {
+            Ok(vec![0; SIZE * SIZE * 4])
+        }
+        fn width(&self) -> usize {
+            SIZE
+        }
+        fn height(&self) -> usize {
+            SIZE
+        }
+    }
+    
+    let mut source = Plain;
+    assert!(source.includes_cursor());
+    assert!(!source.set_include_cursor(false));
+    assert!(source.set_include_cursor(true));
+}
+
+#[test]
+fn test_cursor_messages_round_trip() {
+    let update = Message::CursorUpdate { x: 120, y: 45, visible: true };
+    match Message::deserialize(&update.serialize().unwrap()).unwrap() {
+        Message::CursorUpdate { x, y, visible } => {
+            assert_eq!((x, y), (120, 45));
+            assert!(visible);
+        }
+        other => panic!("unexpected message {:?}", other),
+    }
+    
+    let overlay = Message::SetCursorOverlay { enabled: true };
+    assert!(matches!(
+        Message::deserialize(&overlay.serialize().unwrap()).unwrap(),
+        Message::SetCursorOverlay { enabled: true }
+    ));
+}
+
+#[test]
+fn test_local_position_is_inverse_of_remote_position() {
+    let image_rect = Rect::from_min_size(Pos2::new(100.0, 50.0), egui::vec2(960.0, 540.0));
+    let remote_size = (1920, 1080);
+    
+    assert_eq!(local_position((0, 0), image_rect, remote_size), Pos2::new(100.0, 50.0));
+    assert_eq!(local_position((960, 540), image_rect, remote_size), Pos2::new(580.0, 320.0));
+    
+    // Half a remote pixel in, to stay clear of the edge between two pixels
+    let local = local_position((1000, 600), image_rect, remote_size) + egui::vec2(0.25, 0.25);
+    assert_eq!(remote_position(local, image_rect, remote_size), Some((1000, 600)));
+}
@@ -0,0 +1,208 @@
+mod common;
+
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use vox_gui::client::connection::Connection;
+use vox_gui::common::auth::AccessCode;
+use vox_gui::common::encoder::EncoderTuning;
+use vox_gui::common::frame_processor::FrameType;
+use vox_gui::common::protocol::Message;
+use vox_gui::server::frame_source::{FrameSource, FrameSourceFactory, TestPatternSource};
+use vox_gui::server::screen_capture::{ScreenCapture, DEFAULT_BITRATE};
+use vox_gui::server::vsync::{TimerClock, VsyncSource};
+
+const WIDTH: usize = 64;
+const HEIGHT: usize = 64;
+
+// A screen that changes only when the test damages it, counting the frames read from it
+struct DamageSource {
+    damaged: Arc<AtomicBool>,
+    // Every wait reports a change, like a screen playing video
+    always_damaged: bool,
+    frames: Arc<AtomicUsize>,
+}
+
+impl FrameSource for DamageSource {
+    fn frame(&mut self) -> io::Result<Vec<u8>> {
+        self.damaged.store(false, Ordering::SeqCst);
+        let shade = self.frames.fetch_add(1, Ordering::SeqCst) as u8;
+        Ok(vec![shade; WIDTH * HEIGHT * 4])
+    }
+    
+    fn width(&self) -> usize {
+        WIDTH
+    }
+    
+    fn height(&self) -> usize {
+        HEIGHT
+    }
+    
+    fn reports_damage(&self) -> bool {
+        true
+    }
+    
+    fn wait_for_damage(&mut self, timeout: Duration) -> io::Result<bool> {
+        if self.always_damaged {
+            return Ok(true);
+        }
+        if !self.damaged.load(Ordering::SeqCst) {
+            std::thread::sleep(timeout);
+        }
+        Ok(self.damaged.load(Ordering::SeqCst))
+    }
+}
+
+struct Screen {
+    capture: ScreenCapture,
+    damaged: Arc<AtomicBool>,
+    frames: Arc<AtomicUsize>,
+}
+
+fn screen(fps: u32, always_damaged: bool) -> Screen {
+    let damaged = Arc::new(AtomicBool::new(false));
+    let frames = Arc::new(AtomicUsize::new(0));
+    let source = DamageSource { damaged: damaged.clone(), always_damaged, frames: frames.clone() };
+    let mut capture = ScreenCapture::from_source(Box::new(source), fps, DEFAULT_BITRATE, EncoderTuning::default()).unwrap();
+    capture.set_hardware_encoding(false);
+    Screen { capture, damaged, frames }
+}
+
+const WAIT: Duration = Duration::from_millis(20);
+
+#[test]
+fn test_still_screen_is_never_captured() {
+    let mut screen = screen(100, false);
+    
+    // The first keyframe goes out without any change
+    let first = screen.capture.capture_on_damage(WAIT).unwrap();
+    assert_eq!(first.len(), 1);
+    assert_eq!(first[0].frame_type, FrameType::KeyFrame);
+    
+    for _ in 0..10 {
+        assert!(screen.capture.capture_on_damage(WAIT).unwrap().is_empty());
+    }
+    assert_eq!(screen.frames.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_each_change_is_captured_once() {
+    let mut screen = screen(100, false);
+    screen.capture.capture_on_damage(WAIT).unwrap();
+    
+    for change in 1..=3 {
+        screen.damaged.store(true, Ordering::SeqCst);
+        let frames = screen.capture.capture_on_damage(WAIT).unwrap();
+        assert_eq!(frames.len(), 1);
+        assert!(screen.capture.capture_on_damage(WAIT).unwrap().is_empty());
+        assert_eq!(screen.frames.load(Ordering::SeqCst), 1 + change);
+    }
+}
+
+#[test]
+fn test_keyframe_request_is_captured_on_a_still_screen() {
+    let mut screen = screen(100, false);
+    screen.capture.capture_on_damage(WAIT).unwrap();
+    
+    screen.capture.request_keyframe();
+    let frames = screen.capture.capture_on_damage(WAIT).unwrap();
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].frame_type, FrameType::KeyFrame);
+}
+
+#[test]
+fn test_busy_screen_is_capped_at_the_frame_rate() {
+    // 10 FPS: one frame every 100 ms however often the screen changes
+    let mut screen = screen(10, true);
+    let started = Instant::now();
+    let mut captured = 0;
+    while started.elapsed() < Duration::from_millis(500) {
+        captured += screen.capture.capture_on_damage(WAIT).unwrap().len();
+    }
+    assert!((2..=6).contains(&captured), "captured {} frames in 500 ms", captured);
+    assert_eq!(screen.frames.load(Ordering::SeqCst), captured);
+}
+
+#[test]
+fn test_sources_without_damage_report_a_change_at_once() {
+    let mut source = TestPatternSource::new(WIDTH, HEIGHT);
+    assert!(!source.reports_damage());
+    
+    let started = Instant::now();
+    assert!(source.wait_for_damage(Duration::from_secs(5)).unwrap());
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn test_vsync_keeps_the_damage_signal() {
+    let source = DamageSource {
+        damaged: Arc::new(AtomicBool::new(false)),
+        always_damaged: false,
+        frames: Arc::new(AtomicUsize::new(0)),
+    };
+    let mut vsync = VsyncSource::new(Box::new(source), Box::new(TimerClock::new(60)));
+    assert!(vsync.reports_damage());
+    assert!(!vsync.wait_for_damage(Duration::from_millis(5)).unwrap());
+}
+
+// A screen whose damage tracking has broken, counting how often it is asked
+struct BrokenDamageSource {
+    waits: Arc<AtomicUsize>,
+}
+
+impl FrameSource for BrokenDamageSource {
+    fn frame(&mut self) -> io::Result<Vec<u8>> {
+        Ok(vec![0x40; WIDTH * HEIGHT * 4])
+    }
+    
+    fn width(&self) -> usize {
+        WIDTH
+    }
+    
+    fn height(&self) -> usize {
+        HEIGHT
+    }
+    
+    fn reports_damage(&self) -> bool {
+        true
+    }
+    
+    fn wait_for_damage(&mut self, _timeout: Duration) -> io::Result<bool> {
+        self.waits.fetch_add(1, Ordering::SeqCst);
+        Err(io::Error::new(io::ErrorKind::BrokenPipe, "damage events stopped"))
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_failing_damage_wait_is_retried_with_backoff() {
+    let access_code = AccessCode::generate();
+    let code = access_code.code.clone();
+    let waits = Arc::new(AtomicUsize::new(0));
+    let counted = waits.clone();
+    let broken: FrameSourceFactory = Arc::new(move |_| Ok(Box::new(BrokenDamageSource { waits: counted.clone() }) as Box<dyn FrameSource>));
+    let server = common::test_server(access_code).with_frame_sources(broken).with_capture_on_damage();
+    let addr = common::serve(server).await;
+    
+    let (mut connection, _, _) = Connection::new();
+    let (mut rx, tx) = connection.connect(&addr, &code).await.unwrap();
+    tx.send(Message::StartStream).unwrap();
+    
+    let error = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match rx.recv().await {
+                Some(Message::Error { message, .. }) => return message,
+                Some(_) => continue,
+                None => panic!("connection closed without an error"),
+            }
+        }
+    })
+    .await
+    .expect("the failure never reached the client");
+    assert!(error.contains("damage events stopped"), "{}", error);
+    
+    // Restarts back off from half a second, so a spinning loop would have waited thousands of times
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    let waited = waits.load(Ordering::SeqCst);
+    assert!((1..=5).contains(&waited), "waited for damage {} times", waited);
+}
@@ -0,0 +1,101 @@
+mod common;
+
+use std::time::Duration;
+use vox_gui::client::connection::Connection;
+use vox_gui::common::auth::AccessCode;
+use vox_gui::common::delivery::{DeliveryMode, LATE_FRAME_AGE};
+use vox_gui::common::protocol::Message;
+use vox_gui::server::send_queue::{Priority, SendQueue, REALTIME_VIDEO_BACKLOG, VIDEO_BACKLOG};
+
+const CAPACITY: usize = 64 * 1024;
+
+fn frame(tag: u8) -> Vec<u8> {
+    vec![tag; 100]
+}
+
+// Frames queued while the socket is stalled, e.g. by retransmissions after packet loss
+async fn queue_during_stall(queue: &SendQueue) {
+    for tag in 1..=3 {
+        queue.push(frame(tag), Priority::Video).unwrap();
+    }
+    tokio::time::sleep(LATE_FRAME_AGE + Duration::from_millis(50)).await;
+}
+
+#[tokio::test]
+async fn test_smooth_delivers_every_frame_late() {
+    let queue = SendQueue::new(CAPACITY);
+    assert_eq!(queue.delivery(), DeliveryMode::Smooth);
+    queue_during_stall(&queue).await;
+    
+    for tag in 1..=3 {
+        assert_eq!(queue.pop().await.unwrap()[0], tag);
+    }
+    assert_eq!(queue.take_dropped_video(), 0);
+}
+
+#[tokio::test]
+async fn test_realtime_drops_late_frames() {
+    let queue = SendQueue::new(CAPACITY);
+    queue.set_delivery(DeliveryMode::RealTime);
+    queue_during_stall(&queue).await;
+    queue.push(frame(9), Priority::Control).unwrap();
+    
+    // Control messages are never late
+    assert_eq!(queue.pop().await.unwrap()[0], 9);
+    assert!(tokio::time::timeout(Duration::from_millis(50), queue.pop()).await.is_err());
+    assert_eq!(queue.take_dropped_video(), 3);
+    assert_eq!(queue.queued_bytes(), 0);
+    
+    // Once the link recovers, fresh frames go out
+    queue.push(frame(4), Priority::Video).unwrap();
+    assert_eq!(queue.pop().await.unwrap()[0], 4);
+    assert_eq!(queue.take_dropped_video(), 0);
+}
+
+#[tokio::test]
+async fn test_switching_to_smooth_keeps_queued_frames() {
+    let queue = SendQueue::new(CAPACITY);
+    queue.set_delivery(DeliveryMode::RealTime);
+    queue_during_stall(&queue).await;
+    
+    queue.set_delivery(DeliveryMode::Smooth);
+    assert_eq!(queue.pop().await.unwrap()[0], 1);
+    assert_eq!(queue.take_dropped_video(), 0);
+}
+
+#[test]
+fn test_realtime_queues_fewer_frames() {
+    let queue = SendQueue::new(CAPACITY);
+    assert_eq!(queue.video_backlog_limit(), VIDEO_BACKLOG);
+    queue.set_delivery(DeliveryMode::RealTime);
+    assert_eq!(queue.video_backlog_limit(), REALTIME_VIDEO_BACKLOG);
+    
+    assert_eq!(DeliveryMode::Smooth.max_frame_age(), None);
+    assert_eq!(DeliveryMode::RealTime.max_frame_age(), Some(LATE_FRAME_AGE));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_mode_is_negotiated_at_connect() {
+    let access_code = AccessCode::generate();
+    let code = access_code.code.clone();
+    
+    let server = common::test_server(access_code);
+    let addr = common::serve(server).await;
+    
+    let (mut connection, _, _) = Connection::new();
+    connection.set_delivery(DeliveryMode::RealTime);
+    let (mut rx, _tx) = connection.connect(&addr, &code).await.unwrap();
+    
+    let applied = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match rx.recv().await {
+                Some(Message::DeliveryModeApplied { mode }) => return mode,
+                Some(_) => {}
+                None => panic!("connection closed"),
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(applied, DeliveryMode::RealTime);
+}
@@ -0,0 +1,100 @@
+mod common;
+
+use std::time::Duration;
+use zstd::stream::decode_all;
+use vox_gui::common::encoder::EncoderTuning;
+use vox_gui::common::frame_processor::{merge_tiles, FrameProcessor, FrameType, ProcessedFrame, TileData};
+use vox_gui::server::screen_capture::{CapturedFrame, ScreenCapture, DEFAULT_BITRATE};
+
+const WIDTH: usize = 256;
+const HEIGHT: usize = 128;
+
+fn bgra_frame(shade: u8, marked_pixel: Option<(usize, usize)>) -> Vec<u8> {
+    let mut frame = vec![shade; WIDTH * HEIGHT * 4];
+    if let Some((x, y)) = marked_pixel {
+        let offset = (y * WIDTH + x) * 4;
+        frame[offset..offset + 3].copy_from_slice(&[255, 0, 0]);
+    }
+    frame
+}
+
+fn capture(frames: Vec<Vec<u8>>) -> ScreenCapture {
+    let source = common::ScriptedSource::new(WIDTH, HEIGHT, frames);
+    let mut capture = ScreenCapture::from_source(Box::new(source), 100, DEFAULT_BITRATE, EncoderTuning::default()).unwrap();
+    capture.set_hardware_encoding(false);
+    capture
+}
+
+fn next_frame(capture: &mut ScreenCapture) -> CapturedFrame {
+    loop {
+        if let Some(frame) = capture.capture_frame().unwrap() {
+            return frame;
+        }
+        std::thread::sleep(Duration::from_millis(2));
+    }
+}
+
+fn decompress(tiles: &[TileData]) -> Vec<TileData> {
+    tiles.iter()
+        .map(|tile| TileData { data: decode_all(&tile.data[..]).unwrap().into(), ..tile.clone() })
+        .collect()
+}
+
+#[test]
+fn test_keyframe_then_deltas_reconstruct_the_screen() {
+    let mut capture = capture(vec![
+        bgra_frame(40, None),
+        bgra_frame(40, Some((10, 10))),
+        bgra_frame(40, Some((200, 100))),
+    ]);
+    
+    // A fresh capturer always starts with a keyframe
+    let keyframe = next_frame(&mut capture);
+    assert_eq!(keyframe.frame_type, FrameType::KeyFrame);
+    let mut client_frame = decode_all(&keyframe.data[..]).unwrap();
+    let client = FrameProcessor::new(WIDTH as u32, HEIGHT as u32);
+    
+    // Then only changed tiles are sent: the marked one, then the old and new mark
+    for (expected, changed_tiles) in [(bgra_frame(40, Some((10, 10))), 1), (bgra_frame(40, Some((200, 100))), 2)] {
+        let delta = next_frame(&mut capture);
+        assert_eq!(delta.frame_type, FrameType::DeltaFrame);
+        let tiles = decompress(delta.tiles.as_ref().unwrap());
+        assert_eq!(tiles.len(), changed_tiles);
+        
+        client.apply_delta(&mut client_frame, &ProcessedFrame {
+            frame_type: FrameType::DeltaFrame,
+            data: Default::default(),
+            width: WIDTH as u32,
+            height: HEIGHT as u32,
+            tiles: Some(tiles),
+        }).unwrap();
+        
+        let expected_rgb: Vec<u8> = expected.chunks_exact(4).flat_map(|p| [p[2], p[1], p[0]]).collect();
+        assert_eq!(client_frame, expected_rgb);
+    }
+}
+
+#[test]
+fn test_unchanged_screen_sends_nothing_and_keyframe_on_request() {
+    let mut capture = capture(vec![bgra_frame(40, None)]);
+    assert_eq!(next_frame(&mut capture).frame_type, FrameType::KeyFrame);
+    
+    // A static screen produces no deltas at all
+    std::thread::sleep(Duration::from_millis(15));
+    assert!(capture.capture_frame().unwrap().is_none());
+    
+    // A newly joined client asks for a keyframe
+    capture.request_keyframe();
+    assert_eq!(next_frame(&mut capture).frame_type, FrameType::KeyFrame);
+}
+
+#[test]
+fn test_merged_tiles_keep_the_newest_version() {
+    let tile = |x: u32, value: u8| TileData { x, y: 0, width: 1, height: 1, data: vec![value; 3].into() };
+    
+    let mut pending = vec![tile(0, 1), tile(64, 1)];
+    merge_tiles(&mut pending, &[tile(64, 2), tile(128, 2)]);
+    
+    let summary: Vec<(u32, u8)> = pending.iter().map(|t| (t.x, t.data[0])).collect();
+    assert_eq!(summary, vec![(0, 1), (64, 2), (128, 2)]);
+}
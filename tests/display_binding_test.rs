@@ -0,0 +1,149 @@
+mod common;
+
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::RwLock;
+use vox_gui::common::auth::AccessCode;
+use vox_gui::common::crypto::{CryptoSession, KeyExchange};
+use vox_gui::common::display_layout::{DisplayLayout, MonitorRect};
+use vox_gui::common::protocol::{EncodingType, Message};
+use vox_gui::server::frame_source::{FrameSource, FrameSourceFactory};
+use vox_gui::server::server::Server;
+
+const SIZE: usize = 64;
+
+/// A display showing a single BGRA color.
+struct SolidDisplay {
+    bgra: [u8; 4],
+}
+
+impl FrameSource for SolidDisplay {
+    fn frame(&mut self) -> io::Result<Vec<u8>> {
+        Ok(self.bgra.repeat(SIZE * SIZE))
+    }
+    
+    fn width(&self) -> usize {
+        SIZE
+    }
+    
+    fn height(&self) -> usize {
+        SIZE
+    }
+}
+
+// Display 0 is red, display 1 is blue
+fn two_displays() -> FrameSourceFactory {
+    Arc::new(|display| {
+        let bgra = match display {
+            0 => [0, 0, 255, 255],
+            1 => [255, 0, 0, 255],
+            _ => return Err(anyhow::anyhow!("No display {}", display)),
+        };
+        Ok(Box::new(SolidDisplay { bgra }) as Box<dyn FrameSource>)
+    })
+}
+
+async fn send(stream: &mut TcpStream, data: &[u8]) {
+    stream.write_all(&(data.len() as u32).to_be_bytes()).await.unwrap();
+    stream.write_all(data).await.unwrap();
+}
+
+async fn read(stream: &mut TcpStream) -> Vec<u8> {
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len).await.unwrap();
+    let mut data = vec![0u8; u32::from_be_bytes(len) as usize];
+    stream.read_exact(&mut data).await.unwrap();
+    data
+}
+
+/// Minimal client: key exchange, display selection, auth, zstd-only capabilities.
+async fn join(addr: &str, code: &str, display: u32) -> (TcpStream, CryptoSession) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    
+    let key_exchange = KeyExchange::new();
+    let hello = Message::KeyExchange { public_key: key_exchange.public_key_bytes().to_vec() };
+    send(&mut stream, &hello.serialize().unwrap()).await;
+    let Message::KeyExchangeAck { public_key } = Message::deserialize(&read(&mut stream).await).unwrap() else {
+        panic!("expected KeyExchangeAck");
+    };
+    let their_public = x25519_dalek::PublicKey::from(<[u8; 32]>::try_from(&public_key[..]).unwrap());
+    let crypto = CryptoSession::from_shared_secret(&key_exchange.compute_shared_secret(&their_public)).unwrap();
+    
+    for message in [
+        Message::SelectDisplay { index: display },
+        Message::AuthRequest { code: code.to_string() },
+        Message::Capabilities { encodings: vec![EncodingType::ZstdCompressed] },
+        Message::StartStream,
+    ] {
+        send(&mut stream, &crypto.encrypt(&message.serialize().unwrap()).unwrap()).await;
+    }
+    
+    (stream, crypto)
+}
+
+/// The RGB pixels of the next full frame the client receives.
+async fn next_screen(stream: &mut TcpStream, crypto: &CryptoSession) -> Vec<u8> {
+    loop {
+        let message = Message::deserialize(&crypto.decrypt(&read(stream).await).unwrap()).unwrap();
+        match message {
+            Message::AuthResponse { success, .. } => assert!(success),
+            Message::ScreenFrame { data, encoding, .. } => {
+                assert_eq!(encoding, EncodingType::ZstdCompressed);
+                return zstd::stream::decode_all(&data[..]).unwrap();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sessions_on_different_displays_get_their_own_frames() {
+    let access_code = AccessCode::generate();
+    let code = access_code.code.clone();
+    
+    let server = Server::new(Arc::new(RwLock::new(Some(access_code))))
+        .with_frame_sources(two_displays());
+    let addr = common::serve(server).await;
+    
+    let (mut red_stream, red_crypto) = join(&addr, &code, 0).await;
+    let (mut blue_stream, blue_crypto) = join(&addr, &code, 1).await;
+    
+    let timeout = Duration::from_secs(10);
+    for _ in 0..2 {
+        let red = tokio::time::timeout(timeout, next_screen(&mut red_stream, &red_crypto)).await.unwrap();
+        let blue = tokio::time::timeout(timeout, next_screen(&mut blue_stream, &blue_crypto)).await.unwrap();
+        
+        assert_eq!(red.len(), SIZE * SIZE * 3);
+        assert!(red.chunks(3).all(|rgb| rgb == [255, 0, 0]));
+        assert_eq!(blue.len(), SIZE * SIZE * 3);
+        assert!(blue.chunks(3).all(|rgb| rgb == [0, 0, 255]));
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn selecting_a_display_that_does_not_exist_is_refused() {
+    let access_code = AccessCode::generate();
+    let code = access_code.code.clone();
+    
+    let layout = DisplayLayout::new(vec![MonitorRect::new(0, 0, 64, 64), MonitorRect::new(64, 0, 64, 64)]);
+    let server = Server::new(Arc::new(RwLock::new(Some(access_code))))
+        .with_frame_sources(two_displays())
+        .with_display_layout(layout);
+    let addr = common::serve(server).await;
+    
+    let (mut stream, crypto) = join(&addr, &code, 5).await;
+    
+    let refusal = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            match Message::deserialize(&crypto.decrypt(&read(&mut stream).await).unwrap()).unwrap() {
+                Message::Error { message, .. } => return message,
+                Message::ScreenFrame { .. } => panic!("streamed a display that doesn't exist"),
+                _ => {}
+            }
+        }
+    }).await.unwrap();
+    assert!(refusal.contains("No display 5"), "{}", refusal);
+}
@@ -0,0 +1,173 @@
+mod common;
+
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use vox_gui::client::connection::Connection;
+use vox_gui::common::auth::{AccessCode, AccessCodes, Permissions};
+use vox_gui::common::protocol::{DisplayAction, DisplaySettings, ErrorKind, Message};
+use vox_gui::server::display_control::{
+    display_control_reply, parse_xrandr_verbose, DisplayController, UnsupportedDisplayController, DISPLAY_CONTROL_DENIED,
+};
+
+// A display that remembers what it was asked to do
+#[derive(Clone)]
+struct FakeDisplay(Arc<std::sync::Mutex<DisplaySettings>>);
+
+impl FakeDisplay {
+    fn new() -> Self {
+        FakeDisplay(Arc::new(std::sync::Mutex::new(DisplaySettings {
+            width: 1920,
+            height: 1080,
+            brightness: Some(1.0),
+            modes: vec![(1920, 1080), (1280, 720)],
+        })))
+    }
+}
+
+impl DisplayController for FakeDisplay {
+    fn query(&mut self) -> Result<DisplaySettings> {
+        Ok(self.0.lock().unwrap().clone())
+    }
+    
+    fn set_brightness(&mut self, level: f32) -> Result<()> {
+        self.0.lock().unwrap().brightness = Some(level);
+        Ok(())
+    }
+    
+    fn set_resolution(&mut self, width: u32, height: u32) -> Result<()> {
+        let mut settings = self.0.lock().unwrap();
+        (settings.width, settings.height) = (width, height);
+        Ok(())
+    }
+}
+
+async fn reply(rx: &mut mpsc::UnboundedReceiver<Message>) -> Message {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match rx.recv().await {
+                Some(message @ (Message::DisplayStatus { .. } | Message::Error { .. })) => return message,
+                Some(_) => {}
+                None => panic!("connection closed"),
+            }
+        }
+    })
+    .await
+    .unwrap()
+}
+
+#[test]
+fn test_display_control_round_trip() {
+    let settings = DisplaySettings { width: 2560, height: 1440, brightness: Some(0.8), modes: vec![(2560, 1440), (1920, 1080)] };
+    let messages = [
+        Message::DisplayControl { action: DisplayAction::Query },
+        Message::DisplayControl { action: DisplayAction::SetBrightness { level: 0.5 } },
+        Message::DisplayControl { action: DisplayAction::SetResolution { width: 1920, height: 1080 } },
+        Message::DisplayControl { action: DisplayAction::Sleep },
+        Message::DisplayControl { action: DisplayAction::Wake },
+        Message::DisplayStatus { settings: settings.clone() },
+    ];
+    for message in messages {
+        let decoded = Message::deserialize(&message.serialize().unwrap()).unwrap();
+        match (&message, decoded) {
+            (Message::DisplayControl { action }, Message::DisplayControl { action: decoded }) => assert_eq!(*action, decoded),
+            (Message::DisplayStatus { .. }, Message::DisplayStatus { settings: decoded }) => assert_eq!(decoded, settings),
+            (_, other) => panic!("unexpected message {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn test_actions_need_the_permission() {
+    let mut display = FakeDisplay::new();
+    
+    let denied = display_control_reply(&mut display, false, DisplayAction::SetBrightness { level: 0.2 });
+    assert!(matches!(denied, Message::Error { ref message, kind: ErrorKind::Other } if message == DISPLAY_CONTROL_DENIED));
+    assert_eq!(display.0.lock().unwrap().brightness, Some(1.0));
+    
+    match display_control_reply(&mut display, true, DisplayAction::SetBrightness { level: 0.2 }) {
+        Message::DisplayStatus { settings } => assert_eq!(settings.brightness, Some(0.2)),
+        other => panic!("unexpected message {:?}", other),
+    }
+    
+    // Out of range is refused before reaching the display
+    for level in [-0.1, 1.5, f32::NAN] {
+        assert!(matches!(display_control_reply(&mut display, true, DisplayAction::SetBrightness { level }), Message::Error { .. }));
+    }
+    assert_eq!(display.0.lock().unwrap().brightness, Some(0.2));
+}
+
+#[test]
+fn test_unsupported_actions_are_errors() {
+    // The fake can't sleep
+    let mut display = FakeDisplay::new();
+    assert!(matches!(display_control_reply(&mut display, true, DisplayAction::Sleep), Message::Error { .. }));
+    
+    let mut unsupported = UnsupportedDisplayController;
+    match display_control_reply(&mut unsupported, true, DisplayAction::Query) {
+        Message::Error { message, .. } => assert!(message.contains("only supported on Linux"), "{}", message),
+        other => panic!("unexpected message {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_xrandr_verbose() {
+    let output = "\
+Screen 0: minimum 8 x 8, current 1920 x 1080, maximum 32767 x 32767
+HDMI-1 disconnected (normal left inverted right x axis y axis)
+	Brightness: 0.30
+DP-1 connected 1920x1080+0+0 (0x48) normal (normal left inverted right x axis y axis) 527mm x 296mm
+	Brightness: 0.75
+	Gamma:      1.0:1.0:1.0
+  1280x720 (0x4e) 74.250MHz +HSync +VSync
+        h: width  1280 start 1390 end 1430 total 1650 skew    0 clock  45.00KHz
+  1920x1080 (0x48) 148.500MHz +HSync +VSync *current +preferred
+        v: height 1080 start 1084 end 1089 total 1125           clock  60.00Hz
+  1920x1080 (0x49) 148.352MHz +HSync +VSync
+eDP-1 connected primary 2560x1600+1920+0 (0x50) normal (normal left inverted right x axis y axis) 302mm x 189mm
+	Brightness: 1.0
+  2560x1600 (0x50) 268.500MHz -HSync +VSync *current +preferred
+";
+    let (name, settings) = parse_xrandr_verbose(output).unwrap();
+    assert_eq!(name, "eDP-1");
+    assert_eq!((settings.width, settings.height), (2560, 1600));
+    assert_eq!(settings.brightness, Some(1.0));
+    
+    // Without a primary the first connected output is used, each size listed once
+    let (name, settings) = parse_xrandr_verbose(&output.replace(" primary", "")).unwrap();
+    assert_eq!(name, "DP-1");
+    assert_eq!((settings.width, settings.height), (1920, 1080));
+    assert_eq!(settings.brightness, Some(0.75));
+    assert_eq!(settings.modes, vec![(1920, 1080), (1280, 720)]);
+    
+    assert!(parse_xrandr_verbose("Screen 0: minimum 8 x 8\nHDMI-1 disconnected\n").is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_server_answers_only_permitted_sessions() {
+    let access_code = AccessCode::generate();
+    let code = access_code.code.clone();
+    let mut codes = AccessCodes::new();
+    codes.add("viewer", Permissions::VIEW_ONLY);
+    
+    let display = FakeDisplay::new();
+    let server = common::test_server(access_code)
+        .with_access_codes(codes)
+        .with_display_controller(Box::new(display.clone()));
+    let addr = common::serve(server).await;
+    
+    let (mut viewer, _, _) = Connection::new();
+    let (mut rx, tx) = viewer.connect(&addr, "viewer").await.unwrap();
+    tx.send(Message::DisplayControl { action: DisplayAction::SetResolution { width: 1280, height: 720 } }).unwrap();
+    assert!(matches!(reply(&mut rx).await, Message::Error { ref message, .. } if message == DISPLAY_CONTROL_DENIED));
+    assert_eq!(display.0.lock().unwrap().width, 1920);
+    
+    let (mut host, _, _) = Connection::new();
+    let (mut rx, tx) = host.connect(&addr, &code).await.unwrap();
+    tx.send(Message::DisplayControl { action: DisplayAction::SetResolution { width: 1280, height: 720 } }).unwrap();
+    match reply(&mut rx).await {
+        Message::DisplayStatus { settings } => assert_eq!((settings.width, settings.height), (1280, 720)),
+        other => panic!("unexpected message {:?}", other),
+    }
+}
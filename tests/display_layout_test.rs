@@ -0,0 +1,115 @@
+use egui::{Pos2, Vec2};
+use vox_gui::client::view::move_locked_pointer;
+use vox_gui::common::display_layout::{DisplayLayout, MonitorRect};
+use vox_gui::common::protocol::Message;
+
+/// A 2560x1440 primary, a 1920x1080 monitor to its left sitting 360 lower, and a
+/// 1440x900 Retina laptop (2880x1800 captured) above the primary.
+fn three_monitors() -> DisplayLayout {
+    DisplayLayout::new(vec![
+        MonitorRect::new(0, 0, 2560, 1440),
+        MonitorRect::new(-1920, 360, 1920, 1080),
+        MonitorRect::new(560, -900, 1440, 900),
+    ])
+}
+
+#[test]
+fn test_bounds_cover_monitors_left_of_and_above_the_primary() {
+    let bounds = three_monitors().bounds().unwrap();
+    assert_eq!(bounds, MonitorRect::new(-1920, -900, 4480, 2340));
+    assert_eq!((bounds.right(), bounds.bottom()), (2560, 1440));
+    
+    assert!(DisplayLayout::default().bounds().is_none());
+}
+
+#[test]
+fn test_positions_map_onto_the_desktop_through_each_monitor() {
+    let layout = three_monitors();
+    
+    assert_eq!(layout.to_desktop(0, (100, 200), 1.0), (100, 200));
+    // The left monitor's top-left corner is at negative x
+    assert_eq!(layout.to_desktop(1, (0, 0), 1.0), (-1920, 360));
+    assert_eq!(layout.to_desktop(1, (1919, 1079), 1.0), (-1, 1439));
+    // Retina pixels become points before the offset is applied
+    assert_eq!(layout.to_desktop(2, (2880, 1800), 2.0), (2000, 0));
+    assert_eq!(layout.to_desktop(2, (0, 0), 2.0), (560, -900));
+    
+    // An unknown display has no offset
+    assert_eq!(layout.to_desktop(7, (10, 20), 1.0), (10, 20));
+}
+
+#[test]
+fn test_desktop_points_map_back_to_display_pixels() {
+    let layout = three_monitors();
+    
+    for (display, scale) in [(0, 1.0), (1, 1.0), (2, 2.0)] {
+        for position in [(0, 0), (640, 480), (1200, 700)] {
+            let desktop = layout.to_desktop(display, position, scale);
+            assert_eq!(layout.to_display(display, desktop, scale), position, "display {}", display);
+        }
+    }
+    
+    // Viewing the primary, a point on the left monitor is off its left edge
+    assert_eq!(layout.to_display(0, (-960, 900), 1.0), (-960, 900));
+    // Viewing the Retina laptop, the primary's bottom-right is far below it, in its pixels
+    assert_eq!(layout.to_display(2, (2559, 1439), 2.0), (3998, 4678));
+}
+
+#[test]
+fn test_monitor_at_finds_the_monitor_under_a_point() {
+    let layout = three_monitors();
+    
+    assert_eq!(layout.monitor_at((0, 0)), Some(0));
+    assert_eq!(layout.monitor_at((-1, 400)), Some(1));
+    assert_eq!(layout.monitor_at((600, -1)), Some(2));
+    // Inside the bounds but in the gap above the left monitor
+    assert_eq!(layout.monitor_at((-100, 100)), None);
+    assert_eq!(layout.monitor_at((2560, 0)), None);
+}
+
+#[test]
+fn test_clamp_keeps_points_on_the_desktop() {
+    let layout = three_monitors();
+    
+    assert_eq!(layout.clamp((-5000, -5000)), (-1920, -900));
+    assert_eq!(layout.clamp((9000, 9000)), (2559, 1439));
+    assert_eq!(layout.clamp((-100, 100)), (-100, 100));
+    
+    assert_eq!(DisplayLayout::default().clamp((-5, 5)), (-5, 5));
+}
+
+#[test]
+fn test_locked_pointer_crosses_monitors_and_stops_at_the_edges() {
+    let layout = three_monitors();
+    
+    // From the primary across to the left monitor
+    let pointer = move_locked_pointer(Pos2::new(10.0, 800.0), Vec2::new(-500.0, 0.0), &layout);
+    assert_eq!(pointer, Pos2::new(-490.0, 800.0));
+    assert_eq!(layout.monitor_at((pointer.x as i32, pointer.y as i32)), Some(1));
+    
+    // Up onto the laptop
+    let pointer = move_locked_pointer(Pos2::new(1000.0, 10.0), Vec2::new(0.0, -400.0), &layout);
+    assert_eq!(layout.monitor_at((pointer.x as i32, pointer.y as i32)), Some(2));
+    
+    // Pushing past the virtual desktop stops at its edge
+    let pointer = move_locked_pointer(Pos2::new(-1900.0, 500.0), Vec2::new(-300.0, -3000.0), &layout);
+    assert_eq!(pointer, Pos2::new(-1920.0, -900.0));
+    let pointer = move_locked_pointer(Pos2::new(2500.0, 1400.0), Vec2::new(300.0, 300.0), &layout);
+    assert_eq!(pointer, Pos2::new(2559.0, 1439.0));
+    
+    // Sub-unit motion accumulates instead of being lost
+    let mut pointer = Pos2::new(100.0, 100.0);
+    for _ in 0..4 {
+        pointer = move_locked_pointer(pointer, Vec2::new(0.25, 0.0), &layout);
+    }
+    assert_eq!(pointer, Pos2::new(101.0, 100.0));
+}
+
+#[test]
+fn test_layout_message_round_trips() {
+    let message = Message::DisplayLayout { monitors: three_monitors().monitors };
+    match Message::deserialize(&message.serialize().unwrap()).unwrap() {
+        Message::DisplayLayout { monitors } => assert_eq!(DisplayLayout::new(monitors), three_monitors()),
+        other => panic!("unexpected {:?}", other),
+    }
+}
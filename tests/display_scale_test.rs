@@ -0,0 +1,132 @@
+mod common;
+
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::RwLock;
+use vox_gui::common::auth::AccessCode;
+use vox_gui::common::crypto::{CryptoSession, KeyExchange};
+use vox_gui::common::encoder::EncoderTuning;
+use vox_gui::common::protocol::{EncodingType, Message};
+use vox_gui::server::frame_source::{FrameSource, FrameSourceFactory};
+use vox_gui::server::input_handler::input_position;
+use vox_gui::server::screen_capture::{ScreenCapture, DEFAULT_BITRATE};
+use vox_gui::server::server::Server;
+
+const SIZE: usize = 64;
+
+/// A display reporting the given scale factor, like a Retina screen at 2.0.
+struct ScaledDisplay {
+    scale_factor: f64,
+}
+
+impl FrameSource for ScaledDisplay {
+    fn frame(&mut self) -> io::Result<Vec<u8>> {
+        Ok(vec![90; SIZE * SIZE * 4])
+    }
+    
+    fn width(&self) -> usize {
+        SIZE
+    }
+    
+    fn height(&self) -> usize {
+        SIZE
+    }
+    
+    fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+}
+
+#[test]
+fn test_retina_pixels_map_to_points() {
+    // A 2880x1800 capture of a 1440x900 point display
+    assert_eq!(input_position(2880, 1800, 2.0), (1440, 900));
+    assert_eq!(input_position(1000, 601, 2.0), (500, 301));
+    assert_eq!(input_position(0, 0, 2.0), (0, 0));
+    
+    // Fractional scaling rounds to the nearest point
+    assert_eq!(input_position(150, 300, 1.5), (100, 200));
+}
+
+#[test]
+fn test_unscaled_positions_pass_through() {
+    assert_eq!(input_position(1234, 567, 1.0), (1234, 567));
+    // A bogus factor is ignored rather than sending the cursor to 0,0 or infinity
+    assert_eq!(input_position(1234, 567, 0.0), (1234, 567));
+}
+
+#[test]
+fn test_capture_reports_its_source_scale() {
+    let source = ScaledDisplay { scale_factor: 2.0 };
+    let capture = ScreenCapture::from_source(Box::new(source), 30, DEFAULT_BITRATE, EncoderTuning::default()).unwrap();
+    assert_eq!(capture.scale_factor(), 2.0);
+    
+    struct Plain;
+    impl FrameSource for Plain {
+        fn frame(&mut self) -> io::Result<Vec<u8>> {
+            Ok(vec![0; SIZE * SIZE * 4])
+        }
+        fn width(&self) -> usize {
+            SIZE
+        }
+        fn height(&self) -> usize {
+            SIZE
+        }
+    }
+    assert_eq!(Plain.scale_factor(), 1.0);
+}
+
+async fn send(stream: &mut TcpStream, data: &[u8]) {
+    stream.write_all(&(data.len() as u32).to_be_bytes()).await.unwrap();
+    stream.write_all(data).await.unwrap();
+}
+
+async fn read(stream: &mut TcpStream) -> Vec<u8> {
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len).await.unwrap();
+    let mut data = vec![0u8; u32::from_be_bytes(len) as usize];
+    stream.read_exact(&mut data).await.unwrap();
+    data
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_client_is_told_the_display_scale() {
+    let access_code = AccessCode::generate();
+    let code = access_code.code.clone();
+    
+    let retina: FrameSourceFactory = Arc::new(|_| Ok(Box::new(ScaledDisplay { scale_factor: 2.0 }) as Box<dyn FrameSource>));
+    let server = Server::new(Arc::new(RwLock::new(Some(access_code)))).with_frame_sources(retina);
+    let addr = common::serve(server).await;
+    
+    let mut stream = TcpStream::connect(&addr).await.unwrap();
+    let key_exchange = KeyExchange::new();
+    let hello = Message::KeyExchange { public_key: key_exchange.public_key_bytes().to_vec() };
+    send(&mut stream, &hello.serialize().unwrap()).await;
+    let Message::KeyExchangeAck { public_key } = Message::deserialize(&read(&mut stream).await).unwrap() else {
+        panic!("expected KeyExchangeAck");
+    };
+    let their_public = x25519_dalek::PublicKey::from(<[u8; 32]>::try_from(&public_key[..]).unwrap());
+    let crypto = CryptoSession::from_shared_secret(&key_exchange.compute_shared_secret(&their_public)).unwrap();
+    
+    for message in [
+        Message::AuthRequest { code },
+        Message::Capabilities { encodings: vec![EncodingType::ZstdCompressed] },
+        Message::StartStream,
+    ] {
+        send(&mut stream, &crypto.encrypt(&message.serialize().unwrap()).unwrap()).await;
+    }
+    
+    let factor = tokio::time::timeout(Duration::from_secs(10), async {
+        loop {
+            if let Message::DisplayScale { factor } = Message::deserialize(&crypto.decrypt(&read(&mut stream).await).unwrap()).unwrap() {
+                return factor;
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(factor, 2.0);
+}
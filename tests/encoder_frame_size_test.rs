@@ -0,0 +1,88 @@
+use std::time::Duration;
+use vox_gui::common::color::ColorSpace;
+use vox_gui::common::encoder::{check_frame_len, EncoderSettings, EncoderTuning, SoftwareEncoder, VideoEncoder};
+use vox_gui::common::encoder_watchdog::{EncoderBuilder, WatchdogEncoder};
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 48;
+
+fn settings() -> EncoderSettings {
+    EncoderSettings {
+        width: WIDTH,
+        height: HEIGHT,
+        fps: 30,
+        bitrate: 1_000_000,
+        keyframe_interval: 60,
+        color_space: ColorSpace::for_resolution(WIDTH, HEIGHT),
+        tuning: EncoderTuning::default(),
+    }
+}
+
+fn frame() -> Vec<u8> {
+    vec![0x80; (WIDTH * HEIGHT * 3) as usize]
+}
+
+/// A frame one row short, as captured just after the display shrank.
+fn short_frame() -> Vec<u8> {
+    vec![0x80; (WIDTH * (HEIGHT - 1) * 3) as usize]
+}
+
+/// Feed `encoder` a short buffer, then check it still encodes a good frame.
+fn rejects_short_buffer(encoder: &mut dyn VideoEncoder) {
+    let err = encoder.encode_frame(&short_frame(), false).unwrap_err();
+    assert!(err.to_string().contains("expected"), "{}", err);
+    assert!(encoder.encode_frame(&[], true).is_err());
+    
+    encoder.encode_frame(&frame(), true).unwrap();
+}
+
+#[test]
+fn frame_length_must_match_exactly() {
+    assert!(check_frame_len(&frame(), WIDTH, HEIGHT).is_ok());
+    assert!(check_frame_len(&short_frame(), WIDTH, HEIGHT).is_err());
+    assert!(check_frame_len(&vec![0; frame().len() + 3], WIDTH, HEIGHT).is_err());
+    assert!(check_frame_len(&[], 0, 0).is_ok());
+    
+    let err = check_frame_len(&[0; 3], u32::MAX, u32::MAX).unwrap_err();
+    assert!(err.to_string().contains("4294967295x4294967295"), "{}", err);
+}
+
+#[test]
+fn software_encoder_rejects_short_buffer() {
+    rejects_short_buffer(&mut SoftwareEncoder::new(settings()).unwrap());
+}
+
+#[test]
+fn watchdog_passes_the_error_on_without_recovering() {
+    let builder: EncoderBuilder = Box::new(|settings| Ok(Box::new(SoftwareEncoder::new(settings)?) as Box<dyn VideoEncoder>));
+    let mut encoder = WatchdogEncoder::new(settings(), Duration::from_secs(5), builder).unwrap();
+    rejects_short_buffer(&mut encoder);
+    assert_eq!(encoder.recoveries(), 0);
+}
+
+#[test]
+fn ffmpeg_encoder_rejects_short_buffer() {
+    // Needs an FFmpeg build with a usable H.264 encoder
+    let Ok(mut encoder) = vox_gui::common::ffmpeg_encoder::FFmpegHardwareEncoder::new(settings()) else {
+        return;
+    };
+    rejects_short_buffer(&mut encoder);
+}
+
+#[cfg(target_os = "macos")]
+#[test]
+fn video_toolbox_encoder_rejects_short_buffer() {
+    let Ok(mut encoder) = vox_gui::platform::macos::video_toolbox::VideoToolboxEncoder::new(settings()) else {
+        return;
+    };
+    rejects_short_buffer(&mut encoder);
+}
+
+#[cfg(target_os = "windows")]
+#[test]
+fn media_foundation_encoder_rejects_short_buffer() {
+    let Ok(mut encoder) = vox_gui::platform::windows::media_foundation::MediaFoundationEncoder::new(settings()) else {
+        return;
+    };
+    rejects_short_buffer(&mut encoder);
+}
@@ -0,0 +1,89 @@
+use anyhow::anyhow;
+use vox_gui::common::color::ColorSpace;
+use vox_gui::common::encoder::{
+    select_encoder, EncoderCandidate, EncoderFactory, EncoderRejection, EncoderSettings, EncoderTuning, EncoderType,
+    SoftwareEncoder, VideoEncoder,
+};
+
+fn settings() -> EncoderSettings {
+    EncoderSettings {
+        width: 64,
+        height: 48,
+        fps: 30,
+        bitrate: 1_000_000,
+        keyframe_interval: 60,
+        color_space: ColorSpace::for_resolution(64, 48),
+        tuning: EncoderTuning::default(),
+    }
+}
+
+const NO_DEVICE: EncoderCandidate = EncoderCandidate {
+    name: "hardware H.264",
+    build: |_| Err(anyhow!("no encoder device found")),
+};
+
+const DRIVER_TOO_OLD: EncoderCandidate = EncoderCandidate {
+    name: "vendor H.264",
+    build: |_| Err(anyhow!("driver too old")),
+};
+
+const SOFTWARE: EncoderCandidate = EncoderCandidate {
+    name: "software WebP",
+    build: |settings| Ok(Box::new(SoftwareEncoder::new(settings)?) as Box<dyn VideoEncoder>),
+};
+
+#[test]
+fn test_the_first_working_candidate_is_selected() {
+    let selection = select_encoder(&[SOFTWARE, NO_DEVICE], settings()).unwrap();
+    assert_eq!(selection.name, "software WebP");
+    assert!(!selection.fell_back());
+    assert_eq!(selection.summary(), "using software WebP");
+}
+
+#[test]
+fn test_rejections_are_recorded_with_their_reasons() {
+    let mut selection = select_encoder(&[NO_DEVICE, DRIVER_TOO_OLD, SOFTWARE], settings()).unwrap();
+    assert_eq!(selection.name, "software WebP");
+    assert!(selection.fell_back());
+    assert_eq!(
+        selection.rejected,
+        vec![
+            EncoderRejection { name: "hardware H.264", reason: "no encoder device found".to_string() },
+            EncoderRejection { name: "vendor H.264", reason: "driver too old".to_string() },
+        ]
+    );
+    assert_eq!(
+        selection.summary(),
+        "hardware H.264 unavailable (no encoder device found); vendor H.264 unavailable (driver too old); using software WebP"
+    );
+    
+    // The selected encoder is the one handed back
+    assert_eq!(selection.encoder.get_type(), EncoderType::Software);
+    selection.encoder.encode_frame(&vec![0u8; 64 * 48 * 3], true).unwrap();
+}
+
+#[test]
+fn test_every_reason_is_reported_when_nothing_works() {
+    let err = select_encoder(&[NO_DEVICE, DRIVER_TOO_OLD], settings()).err().unwrap();
+    let message = err.to_string();
+    assert!(message.contains("hardware H.264: no encoder device found"), "{}", message);
+    assert!(message.contains("vendor H.264: driver too old"), "{}", message);
+}
+
+#[test]
+fn test_factory_falls_back_from_hardware_to_software() {
+    assert_eq!(EncoderFactory::candidates(EncoderType::Software).len(), 1);
+    let names: Vec<_> = EncoderFactory::candidates(EncoderType::Hardware).iter().map(|c| c.name).collect();
+    assert_eq!(names, vec![EncoderFactory::HARDWARE.name, EncoderFactory::SOFTWARE.name]);
+    
+    // Whichever this machine ends up with, a hardware miss says why
+    let selection = EncoderFactory::select(EncoderType::Hardware, settings()).unwrap();
+    if selection.encoder.get_type() == EncoderType::Software {
+        assert_eq!(selection.name, EncoderFactory::SOFTWARE.name);
+        assert_eq!(selection.rejected.len(), 1);
+        assert_eq!(selection.rejected[0].name, EncoderFactory::HARDWARE.name);
+        assert!(!selection.rejected[0].reason.is_empty());
+    } else {
+        assert!(!selection.fell_back());
+    }
+}
@@ -0,0 +1,159 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use anyhow::Result;
+use bytes::Bytes;
+use vox_gui::common::color::ColorSpace;
+use vox_gui::common::encoder::{EncodedFrame, EncoderSettings, EncoderTuning, EncoderType, VideoEncoder};
+use vox_gui::common::encoder_watchdog::{EncoderBuilder, WatchdogEncoder};
+
+/// Takes `delay` per frame and marks frames as keyframes only when forced.
+struct SlowEncoder {
+    delay: Duration,
+}
+
+impl VideoEncoder for SlowEncoder {
+    fn encode_frame(&mut self, _rgb_data: &[u8], force_keyframe: bool) -> Result<EncodedFrame> {
+        std::thread::sleep(self.delay);
+        Ok(EncodedFrame {
+            data: Bytes::from_static(b"frame"),
+            is_keyframe: force_keyframe,
+            timestamp: 0,
+        })
+    }
+    
+    fn get_type(&self) -> EncoderType {
+        EncoderType::Hardware
+    }
+    
+    fn update_settings(&mut self, _settings: EncoderSettings) -> Result<()> {
+        Ok(())
+    }
+}
+
+fn settings() -> EncoderSettings {
+    EncoderSettings {
+        width: 64,
+        height: 64,
+        fps: 30,
+        bitrate: 1_000_000,
+        keyframe_interval: 60,
+        color_space: ColorSpace::for_resolution(64, 64),
+        tuning: EncoderTuning::default(),
+    }
+}
+
+/// The first encoder built hangs for `stall`, every later one is fast.
+fn stall_first(stall: Duration, created: Arc<AtomicUsize>) -> EncoderBuilder {
+    Box::new(move |_settings| {
+        let delay = if created.fetch_add(1, Ordering::SeqCst) == 0 { stall } else { Duration::ZERO };
+        Ok(Box::new(SlowEncoder { delay }) as Box<dyn VideoEncoder>)
+    })
+}
+
+#[test]
+fn stalled_encoder_is_replaced_and_forces_a_keyframe() {
+    let created = Arc::new(AtomicUsize::new(0));
+    let builder = stall_first(Duration::from_secs(5), created.clone());
+    let mut encoder = WatchdogEncoder::new(settings(), Duration::from_millis(100), builder).unwrap();
+    let frame = vec![0u8; 64 * 64 * 3];
+    
+    // The stalled call gives up after the timeout instead of blocking for the full stall
+    let started = Instant::now();
+    let stalled = encoder.encode_frame(&frame, false).unwrap();
+    assert!(started.elapsed() < Duration::from_secs(2));
+    assert!(stalled.data.is_empty());
+    assert_eq!(encoder.recoveries(), 1);
+    assert_eq!(created.load(Ordering::SeqCst), 2);
+    
+    // The replacement starts with a keyframe, then carries on with deltas
+    let first = encoder.encode_frame(&frame, false).unwrap();
+    assert!(first.is_keyframe);
+    assert_eq!(&first.data[..], b"frame");
+    
+    let second = encoder.encode_frame(&frame, false).unwrap();
+    assert!(!second.is_keyframe);
+    assert_eq!(encoder.recoveries(), 1);
+    assert_eq!(encoder.get_type(), EncoderType::Hardware);
+}
+
+#[test]
+fn slow_but_timely_encoder_is_left_alone() {
+    let created = Arc::new(AtomicUsize::new(0));
+    let builder = stall_first(Duration::from_millis(20), created.clone());
+    let mut encoder = WatchdogEncoder::new(settings(), Duration::from_secs(1), builder).unwrap();
+    let frame = vec![0u8; 64 * 64 * 3];
+    
+    for _ in 0..3 {
+        let encoded = encoder.encode_frame(&frame, false).unwrap();
+        assert_eq!(&encoded.data[..], b"frame");
+    }
+    encoder.update_settings(settings()).unwrap();
+    
+    assert_eq!(encoder.recoveries(), 0);
+    assert_eq!(created.load(Ordering::SeqCst), 1);
+}
+
+/// Takes `delay` per frame and returns the frame's number as its data.
+struct NumberingEncoder {
+    delay: Duration,
+    next: u8,
+}
+
+impl VideoEncoder for NumberingEncoder {
+    fn encode_frame(&mut self, _rgb_data: &[u8], _force_keyframe: bool) -> Result<EncodedFrame> {
+        std::thread::sleep(self.delay);
+        self.next += 1;
+        Ok(EncodedFrame {
+            data: Bytes::from(vec![self.next - 1]),
+            is_keyframe: false,
+            timestamp: 0,
+        })
+    }
+    
+    fn get_type(&self) -> EncoderType {
+        EncoderType::Hardware
+    }
+    
+    fn update_settings(&mut self, _settings: EncoderSettings) -> Result<()> {
+        Ok(())
+    }
+}
+
+fn queued_encoder(queue_depth: usize) -> WatchdogEncoder {
+    let mut settings = settings();
+    settings.tuning.queue_depth = queue_depth;
+    let builder: EncoderBuilder = Box::new(|_settings| {
+        Ok(Box::new(NumberingEncoder { delay: Duration::from_millis(30), next: 0 }) as Box<dyn VideoEncoder>)
+    });
+    WatchdogEncoder::new(settings, Duration::from_secs(1), builder).unwrap()
+}
+
+#[test]
+fn queue_depth_bounds_frames_in_flight() {
+    let frame = vec![0u8; 64 * 64 * 3];
+    
+    for depth in [0usize, 1, 3] {
+        let mut encoder = queued_encoder(depth);
+        let mut outputs = Vec::new();
+        let mut most_in_flight = 0;
+        
+        for _ in 0..8 {
+            let encoded = encoder.encode_frame(&frame, false).unwrap();
+            most_in_flight = most_in_flight.max(encoder.in_flight());
+            if !encoded.data.is_empty() {
+                outputs.push(encoded.data[0]);
+            }
+        }
+        
+        // The queue fills before anything comes back, then frames come out oldest first
+        assert_eq!(most_in_flight, depth, "depth {}", depth);
+        assert_eq!(encoder.in_flight(), depth, "depth {}", depth);
+        assert_eq!(outputs, (0..(8 - depth) as u8).collect::<Vec<_>>(), "depth {}", depth);
+        
+        // A settings change waits out the queue and starts empty
+        encoder.update_settings(settings()).unwrap();
+        assert_eq!(encoder.in_flight(), 0);
+        assert_eq!(encoder.recoveries(), 0);
+    }
+}
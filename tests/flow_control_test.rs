@@ -0,0 +1,119 @@
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use vox_gui::client::connection::Connection;
+use vox_gui::common::auth::AccessCode;
+use vox_gui::common::protocol::Message;
+use vox_gui::common::quality::QualityMode;
+use vox_gui::server::flow_control::{FrameWindow, FRAME_ACK_TIMEOUT};
+
+#[test]
+fn test_the_window_closes_when_full_and_reopens_on_acks() {
+    let now = Instant::now();
+    let mut window = FrameWindow::new();
+    for timestamp in 1..=3 {
+        assert!(window.is_open(3, now));
+        window.sent(timestamp, now);
+    }
+    assert!(!window.is_open(3, now));
+    
+    assert!(window.acked(1));
+    assert!(window.is_open(3, now));
+    assert_eq!(window.in_flight(), 2);
+}
+
+#[test]
+fn test_an_ack_covers_every_earlier_frame() {
+    let now = Instant::now();
+    let mut window = FrameWindow::new();
+    for timestamp in [100, 133, 166, 200] {
+        window.sent(timestamp, now);
+    }
+    // The client skipped 100 to 166 and only showed 200
+    assert!(window.acked(200));
+    assert_eq!(window.in_flight(), 0);
+    assert!(!window.acked(133));
+}
+
+#[test]
+fn test_frames_never_acked_stop_counting_after_the_timeout() {
+    let start = Instant::now();
+    let mut window = FrameWindow::new();
+    window.sent(1, start);
+    window.sent(2, start + Duration::from_secs(1));
+    assert!(!window.is_open(2, start + Duration::from_secs(1)));
+    
+    assert!(window.is_open(2, start + FRAME_ACK_TIMEOUT));
+    assert_eq!(window.in_flight(), 1);
+}
+
+#[test]
+fn test_the_window_is_a_quarter_second_of_frames() {
+    assert_eq!(QualityMode::Ultra.frame_window(), 15);
+    assert_eq!(QualityMode::High.frame_window(), 8);
+    assert_eq!(QualityMode::Minimal.frame_window(), 3);
+    for mode in QualityMode::ALL {
+        assert!(mode.frame_window() >= 3, "{:?}", mode);
+    }
+}
+
+fn frame_timestamp(message: &Message) -> Option<u64> {
+    match message {
+        Message::ScreenFrame { timestamp, .. } | Message::DeltaFrame { timestamp, .. } => Some(*timestamp),
+        Message::KeyFrameChunk { timestamp, is_last: true, .. } => Some(*timestamp),
+        _ => None,
+    }
+}
+
+// Count the frames `rx` delivers, acknowledging each on `tx` if `ack` is set
+fn count_frames(mut rx: mpsc::UnboundedReceiver<Message>, tx: mpsc::UnboundedSender<Message>, ack: bool) -> Arc<AtomicUsize> {
+    let frames = Arc::new(AtomicUsize::new(0));
+    let counted = frames.clone();
+    tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            let Some(timestamp) = frame_timestamp(&message) else {
+                continue;
+            };
+            counted.fetch_add(1, Ordering::Relaxed);
+            if ack {
+                let _ = tx.send(Message::FrameAck { timestamp, received_at: timestamp });
+            }
+        }
+    });
+    frames
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_a_client_that_stops_acking_stops_getting_frames_but_others_dont() {
+    let access_code = AccessCode::generate();
+    let code = access_code.code.clone();
+    let server = common::test_server(access_code);
+    let addr = common::serve(server).await;
+    
+    let (mut acking, _, _) = Connection::new();
+    let (rx, tx) = acking.connect(&addr, &code).await.unwrap();
+    tx.send(Message::StartStream).unwrap();
+    let acked_frames = count_frames(rx, tx, true);
+    
+    let (mut silent, _, _) = Connection::new();
+    let (rx, tx) = silent.connect(&addr, &code).await.unwrap();
+    tx.send(Message::StartStream).unwrap();
+    let unacked_frames = count_frames(rx, tx, false);
+    
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    let unacked = unacked_frames.load(Ordering::Relaxed);
+    let acked = acked_frames.load(Ordering::Relaxed);
+    
+    // The silent client gets a window's worth and then nothing, well before frames time out
+    assert!(unacked > 0);
+    assert!(unacked <= QualityMode::Ultra.frame_window(), "{} frames sent without acks", unacked);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(unacked_frames.load(Ordering::Relaxed), unacked);
+    
+    // The other keeps streaming
+    assert!(acked > 2 * QualityMode::Ultra.frame_window(), "only {} frames to the acking client", acked);
+    assert!(acked_frames.load(Ordering::Relaxed) > acked);
+}
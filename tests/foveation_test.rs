@@ -0,0 +1,84 @@
+use bytes::Bytes;
+use vox_gui::common::foveation::{quantize, Foveation, TileQuality};
+use vox_gui::common::frame_processor::TileData;
+
+fn tile(x: u32, y: u32, value: u8) -> TileData {
+    TileData {
+        x,
+        y,
+        width: 64,
+        height: 64,
+        data: Bytes::from(vec![value; 64 * 64 * 3]),
+    }
+}
+
+#[test]
+fn tiles_near_cursor_keep_more_detail_than_distant_ones() {
+    let mut foveation = Foveation::new(128, 4);
+    foveation.set_focus(Some((100, 100)));
+    
+    let mut tiles = vec![tile(64, 64, 0x5B), tile(1024, 768, 0x5B)];
+    foveation.apply(&mut tiles);
+    
+    assert_eq!(foveation.tile_quality(&tiles[0]), TileQuality::Full);
+    assert_eq!(foveation.tile_quality(&tiles[1]), TileQuality::Reduced);
+    
+    // The tile under the cursor is untouched, the distant one lost its low bits
+    assert!(tiles[0].data.iter().all(|&v| v == 0x5B));
+    assert!(tiles[1].data.iter().all(|&v| v == 0x58));
+}
+
+#[test]
+fn radius_is_measured_to_the_nearest_edge_of_the_tile() {
+    let mut foveation = Foveation::new(64, 4);
+    foveation.set_focus(Some((0, 0)));
+    
+    assert_eq!(foveation.tile_quality(&tile(64, 0, 0)), TileQuality::Full);
+    assert_eq!(foveation.tile_quality(&tile(128, 0, 0)), TileQuality::Reduced);
+}
+
+#[test]
+fn no_cursor_means_full_quality_everywhere() {
+    let mut foveation = Foveation::new(16, 2);
+    let mut tiles = vec![tile(0, 0, 0x77), tile(4096, 4096, 0x77)];
+    foveation.apply(&mut tiles);
+    
+    assert!(tiles.iter().all(|t| foveation.tile_quality(t) == TileQuality::Full));
+    assert!(tiles.iter().all(|t| t.data.iter().all(|&v| v == 0x77)));
+    assert!(foveation.tiles_to_refresh().is_empty());
+}
+
+#[test]
+fn reduced_tiles_are_refreshed_when_the_cursor_arrives() {
+    let mut foveation = Foveation::new(100, 4);
+    foveation.set_focus(Some((0, 0)));
+    
+    let mut tiles = vec![tile(0, 0, 1), tile(640, 0, 1), tile(1280, 0, 1)];
+    foveation.apply(&mut tiles);
+    assert!(foveation.tiles_to_refresh().is_empty());
+    
+    foveation.set_focus(Some((660, 20)));
+    assert_eq!(foveation.tiles_to_refresh(), vec![(640, 0)]);
+    
+    // Once re-sent at full quality it no longer needs refreshing
+    let mut refreshed = vec![tile(640, 0, 1)];
+    foveation.apply(&mut refreshed);
+    assert!(foveation.tiles_to_refresh().is_empty());
+    
+    foveation.set_focus(Some((1300, 10)));
+    assert_eq!(foveation.tiles_to_refresh(), vec![(1280, 0)]);
+    
+    foveation.reset();
+    assert!(foveation.tiles_to_refresh().is_empty());
+}
+
+#[test]
+fn quantize_keeps_high_bits_and_centres_the_rest() {
+    let mut data = [0x00, 0xFF, 0x5B];
+    quantize(&mut data, 4);
+    assert_eq!(data, [0x08, 0xF8, 0x58]);
+    
+    let mut exact = [0x00, 0xFF, 0x5B];
+    quantize(&mut exact, 8);
+    assert_eq!(exact, [0x00, 0xFF, 0x5B]);
+}
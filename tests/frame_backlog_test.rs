@@ -0,0 +1,166 @@
+use bytes::Bytes;
+use vox_gui::client::backlog::skip_stale_frames;
+use vox_gui::common::frame_processor::{decompress_tiles, FrameProcessor, FrameType, ProcessedFrame, TileData};
+use vox_gui::common::protocol::{EncodingType, Message};
+use vox_gui::common::quality::{QualityChangeReason, QualityMode};
+
+const WIDTH: u32 = 128;
+const HEIGHT: u32 = 64;
+
+fn full_frame(sequence: u64, shade: u8) -> Message {
+    let rgb = vec![shade; (WIDTH * HEIGHT * 3) as usize];
+    Message::ScreenFrame {
+        sequence,
+        timestamp: sequence,
+        width: WIDTH,
+        height: HEIGHT,
+        data: zstd::stream::encode_all(&rgb[..], 3).unwrap(),
+        encoding: EncodingType::ZstdCompressed,
+        checksum: None,
+    }
+}
+
+// Tiles are 64x64, so x picks the left or right half of the frame
+fn delta(sequence: u64, tiles: &[(u32, u8)]) -> Message {
+    Message::DeltaFrame {
+        sequence,
+        timestamp: sequence,
+        tiles: tiles.iter()
+            .map(|&(x, shade)| TileData {
+                x,
+                y: 0,
+                width: 64,
+                height: 64,
+                data: Bytes::from(zstd::stream::encode_all(&vec![shade; 64 * 64 * 3][..], 3).unwrap()),
+            })
+            .collect(),
+        checksum: None,
+    }
+}
+
+fn h264_frame(sequence: u64, idr: bool) -> Message {
+    Message::ScreenFrame {
+        sequence,
+        timestamp: sequence,
+        width: WIDTH,
+        height: HEIGHT,
+        data: vec![0, 0, 0, 1, if idr { 0x65 } else { 0x41 }, 0x88, 0x84],
+        encoding: EncodingType::H264,
+        checksum: None,
+    }
+}
+
+fn quality_change() -> Message {
+    Message::QualityChange { mode: QualityMode::Low, reason: QualityChangeReason::Initial }
+}
+
+fn sequences(messages: &[Message]) -> Vec<u64> {
+    messages.iter()
+        .filter_map(|message| match message {
+            Message::ScreenFrame { sequence, .. } | Message::DeltaFrame { sequence, .. } => Some(*sequence),
+            _ => None,
+        })
+        .collect()
+}
+
+// What the client shows after handling `messages` in order
+fn render(messages: Vec<Message>) -> Vec<u8> {
+    let processor = FrameProcessor::new(WIDTH, HEIGHT);
+    let mut current = Vec::new();
+    for message in messages {
+        match message {
+            Message::ScreenFrame { data, .. } => current = zstd::stream::decode_all(&data[..]).unwrap(),
+            Message::DeltaFrame { tiles, .. } => {
+                let delta = ProcessedFrame {
+                    frame_type: FrameType::DeltaFrame,
+                    data: Bytes::new(),
+                    width: WIDTH,
+                    height: HEIGHT,
+                    tiles: Some(decompress_tiles(tiles, WIDTH, HEIGHT)),
+                };
+                processor.apply_delta(&mut current, &delta).unwrap();
+            }
+            _ => {}
+        }
+    }
+    current
+}
+
+fn backlog() -> Vec<Message> {
+    vec![
+        full_frame(1, 10),
+        delta(2, &[(0, 20)]),
+        full_frame(3, 30),
+        delta(4, &[(0, 40), (64, 41)]),
+        quality_change(),
+        delta(5, &[(0, 50)]),
+    ]
+}
+
+#[test]
+fn test_a_backlog_renders_the_newest_frame() {
+    let (kept, skipped) = skip_stale_frames(backlog());
+    
+    // The newest full frame and one delta merged from the two after it
+    assert_eq!(sequences(&kept), vec![3, 5]);
+    assert_eq!(skipped, 3);
+    assert_eq!(render(kept), render(backlog()));
+    
+    let mut expected = vec![50; (WIDTH * HEIGHT * 3) as usize];
+    for row in expected.chunks_exact_mut((WIDTH * 3) as usize) {
+        row[64 * 3..].fill(41);
+    }
+    assert_eq!(render(skip_stale_frames(backlog()).0), expected);
+}
+
+#[test]
+fn test_messages_other_than_frames_are_kept_in_order() {
+    let (kept, _) = skip_stale_frames(vec![quality_change(), full_frame(1, 1), Message::Disconnect, full_frame(2, 2)]);
+    assert!(matches!(kept[0], Message::QualityChange { .. }));
+    assert!(matches!(kept[1], Message::Disconnect));
+    assert_eq!(sequences(&kept), vec![2]);
+}
+
+#[test]
+fn test_a_single_frame_or_an_empty_batch_is_left_alone() {
+    assert_eq!(skip_stale_frames(Vec::new()).1, 0);
+    let (kept, skipped) = skip_stale_frames(vec![delta(7, &[(0, 1)])]);
+    assert_eq!((sequences(&kept), skipped), (vec![7], 0));
+}
+
+#[test]
+fn test_deltas_without_a_newer_full_frame_are_merged() {
+    let deltas = vec![delta(1, &[(0, 1)]), delta(2, &[(64, 2)]), delta(3, &[(0, 3)])];
+    let (kept, skipped) = skip_stale_frames(deltas.clone());
+    assert_eq!((sequences(&kept), skipped), (vec![3], 2));
+    
+    let mut base = vec![full_frame(0, 0)];
+    base.extend(kept);
+    let mut all = vec![full_frame(0, 0)];
+    all.extend(deltas);
+    assert_eq!(render(base), render(all));
+}
+
+#[test]
+fn test_h264_frames_are_skipped_only_up_to_the_newest_idr() {
+    let messages = vec![
+        h264_frame(1, true),
+        h264_frame(2, false),
+        Message::CodecConfig { sps: vec![0x67], pps: vec![0x68] },
+        h264_frame(3, true),
+        h264_frame(4, false),
+        h264_frame(5, false),
+    ];
+    let (kept, skipped) = skip_stale_frames(messages);
+    assert_eq!(skipped, 2);
+    assert!(matches!(kept[0], Message::CodecConfig { .. }));
+    // Every frame after the IDR builds on the one before it
+    assert_eq!(sequences(&kept), vec![3, 4, 5]);
+}
+
+#[test]
+fn test_h264_frames_without_an_idr_are_all_kept() {
+    let messages = vec![h264_frame(1, false), h264_frame(2, false), h264_frame(3, false)];
+    let (kept, skipped) = skip_stale_frames(messages);
+    assert_eq!((sequences(&kept), skipped), (vec![1, 2, 3], 0));
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use std::time::Duration;
+use zstd::stream::decode_all;
+use vox_gui::client::desync::DesyncDetector;
+use vox_gui::common::checksum::frame_checksum;
+use vox_gui::common::encoder::EncoderTuning;
+use vox_gui::common::frame_processor::{FrameProcessor, FrameType, ProcessedFrame, TileData};
+use vox_gui::server::screen_capture::{CapturedFrame, ScreenCapture, DEFAULT_BITRATE};
+
+const WIDTH: usize = 128;
+const HEIGHT: usize = 64;
+
+fn bgra_frame(shade: u8, marked_pixel: Option<(usize, usize)>) -> Vec<u8> {
+    let mut frame = vec![shade; WIDTH * HEIGHT * 4];
+    if let Some((x, y)) = marked_pixel {
+        let offset = (y * WIDTH + x) * 4;
+        frame[offset..offset + 3].copy_from_slice(&[255, 0, 0]);
+    }
+    frame
+}
+
+fn capture(frames: Vec<Vec<u8>>, checksums: bool) -> ScreenCapture {
+    let source = common::ScriptedSource::new(WIDTH, HEIGHT, frames);
+    let mut capture = ScreenCapture::from_source(Box::new(source), 100, DEFAULT_BITRATE, EncoderTuning::default()).unwrap();
+    capture.set_hardware_encoding(false);
+    capture.set_frame_checksums(checksums);
+    capture
+}
+
+fn next_frame(capture: &mut ScreenCapture) -> CapturedFrame {
+    loop {
+        if let Some(frame) = capture.capture_frame().unwrap() {
+            return frame;
+        }
+        std::thread::sleep(Duration::from_millis(2));
+    }
+}
+
+fn decompress(tiles: &[TileData]) -> Vec<TileData> {
+    tiles.iter()
+        .map(|tile| TileData { data: decode_all(&tile.data[..]).unwrap().into(), ..tile.clone() })
+        .collect()
+}
+
+fn apply(client_frame: &mut Vec<u8>, tiles: Vec<TileData>) {
+    FrameProcessor::new(WIDTH as u32, HEIGHT as u32).apply_delta(client_frame, &ProcessedFrame {
+        frame_type: FrameType::DeltaFrame,
+        data: Default::default(),
+        width: WIDTH as u32,
+        height: HEIGHT as u32,
+        tiles: Some(tiles),
+    }).unwrap();
+}
+
+#[test]
+fn test_reconstructed_frames_match_the_servers_checksums() {
+    let mut capture = capture(vec![bgra_frame(10, None), bgra_frame(10, Some((100, 40)))], true);
+    let mut detector = DesyncDetector::new();
+    
+    let keyframe = next_frame(&mut capture);
+    assert_eq!(keyframe.frame_type, FrameType::KeyFrame);
+    let mut client_frame = decode_all(&keyframe.data[..]).unwrap();
+    assert_eq!(detector.check(0, keyframe.checksum, &client_frame), Some(true));
+    
+    let delta = next_frame(&mut capture);
+    assert_eq!(delta.frame_type, FrameType::DeltaFrame);
+    apply(&mut client_frame, decompress(delta.tiles.as_ref().unwrap()));
+    assert_eq!(detector.check(1, delta.checksum, &client_frame), Some(true));
+    
+    assert!(!detector.is_desynced());
+    assert_eq!(detector.checked(), 2);
+}
+
+#[test]
+fn test_a_corrupted_delta_is_reported_as_desync() {
+    let mut capture = capture(vec![bgra_frame(10, None), bgra_frame(10, Some((100, 40)))], true);
+    let mut detector = DesyncDetector::new();
+    
+    let keyframe = next_frame(&mut capture);
+    let mut client_frame = decode_all(&keyframe.data[..]).unwrap();
+    
+    // One pixel of the changed tile goes wrong on the way
+    let delta = next_frame(&mut capture);
+    let mut tiles = decompress(delta.tiles.as_ref().unwrap());
+    let mut data = tiles[0].data.to_vec();
+    data[0] ^= 1;
+    tiles[0].data = data.into();
+    apply(&mut client_frame, tiles);
+    
+    assert_eq!(detector.check(7, delta.checksum, &client_frame), Some(false));
+    assert!(detector.is_desynced());
+    assert_eq!(detector.mismatches(), 1);
+    assert_eq!(detector.first_mismatch(), Some(7));
+    
+    detector.reset();
+    assert!(!detector.is_desynced());
+}
+
+#[test]
+fn test_frames_carry_no_checksum_unless_enabled() {
+    let mut capture = capture(vec![bgra_frame(10, None)], false);
+    let keyframe = next_frame(&mut capture);
+    assert_eq!(keyframe.checksum, None);
+    
+    // Without a checksum there is nothing to compare
+    let mut detector = DesyncDetector::new();
+    assert_eq!(detector.check(0, None, &decode_all(&keyframe.data[..]).unwrap()), None);
+    assert_eq!(detector.checked(), 0);
+}
+
+#[test]
+fn test_checksum_is_deterministic() {
+    let frame = vec![42u8; WIDTH * HEIGHT * 3];
+    assert_eq!(frame_checksum(&frame), frame_checksum(&frame.clone()));
+    
+    let mut changed = frame.clone();
+    changed[WIDTH * HEIGHT * 3 - 1] = 43;
+    assert_ne!(frame_checksum(&frame), frame_checksum(&changed));
+}
@@ -0,0 +1,51 @@
+use std::time::{Duration, Instant};
+use vox_gui::client::interpolation::FrameInterpolator;
+
+#[test]
+fn test_blends_between_frames() {
+    let mut interpolator = FrameInterpolator::new(10);
+    let start = Instant::now();
+    let interval = interpolator.frame_interval();
+    
+    interpolator.push(2, 1, vec![0; 6], start);
+    // First frame has nothing to blend from and is shown as-is
+    assert_eq!(interpolator.next_frame(start).unwrap().2, vec![0; 6]);
+    assert!(interpolator.next_frame(start).is_none());
+    
+    let second = start + interval;
+    interpolator.push(2, 1, vec![200; 6], second);
+    
+    let (_, _, halfway) = interpolator.next_frame(second + interpolator.frame_interval() / 2).unwrap();
+    assert!(halfway.iter().all(|&v| (90..=110).contains(&v)), "{:?}", halfway);
+    
+    // Once the fade is over the latest frame is shown exactly, then nothing until the next push
+    let done = second + interpolator.frame_interval() + Duration::from_millis(1);
+    assert_eq!(interpolator.next_frame(done).unwrap().2, vec![200; 6]);
+    assert!(interpolator.next_frame(done).is_none());
+}
+
+#[test]
+fn test_resolution_change_skips_blend() {
+    let mut interpolator = FrameInterpolator::new(10);
+    let start = Instant::now();
+    
+    interpolator.push(2, 1, vec![0; 6], start);
+    interpolator.push(1, 1, vec![255; 3], start + Duration::from_millis(10));
+    
+    let (width, height, frame) = interpolator.next_frame(start + Duration::from_millis(11)).unwrap();
+    assert_eq!((width, height), (1, 1));
+    assert_eq!(frame, vec![255; 3]);
+}
+
+#[test]
+fn test_take_latest_returns_newest_frame() {
+    let mut interpolator = FrameInterpolator::new(10);
+    let start = Instant::now();
+    
+    interpolator.push(1, 1, vec![0; 3], start);
+    interpolator.push(1, 1, vec![90; 3], start + Duration::from_millis(100));
+    
+    assert_eq!(interpolator.take_latest().unwrap().2, vec![90; 3]);
+    assert!(interpolator.take_latest().is_none());
+    assert!(interpolator.next_frame(start + Duration::from_millis(120)).is_none());
+}
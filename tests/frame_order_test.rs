@@ -0,0 +1,83 @@
+use vox_gui::client::frame_order::FrameOrder;
+use vox_gui::common::protocol::{EncodingType, Message};
+
+fn screen_frame(sequence: u64, timestamp: u64) -> Message {
+    Message::ScreenFrame {
+        sequence,
+        timestamp,
+        width: 1,
+        height: 1,
+        data: vec![sequence as u8; 3],
+        encoding: EncodingType::Raw,
+        checksum: None,
+    }
+}
+
+/// The sequence numbers of the frames a client following `order` would render.
+fn rendered(order: &mut FrameOrder, arrivals: &[Message]) -> Vec<u64> {
+    arrivals
+        .iter()
+        .filter_map(|message| match message {
+            Message::ScreenFrame { sequence, .. } | Message::DeltaFrame { sequence, .. } => {
+                order.admit(*sequence).then_some(*sequence)
+            }
+            Message::KeyFrameChunk { sequence, .. } => order.admit_chunk(*sequence).then_some(*sequence),
+            other => panic!("unexpected message {:?}", other),
+        })
+        .collect()
+}
+
+#[test]
+fn test_out_of_order_frames_are_dropped() {
+    let mut order = FrameOrder::new();
+    let arrivals: Vec<Message> = [0, 2, 1, 3, 5, 4, 6].iter().map(|&seq| screen_frame(seq, 1000 + seq)).collect();
+    
+    assert_eq!(rendered(&mut order, &arrivals), vec![0, 2, 3, 5, 6]);
+    assert_eq!(order.latest(), Some(6));
+}
+
+#[test]
+fn test_duplicate_frame_is_dropped() {
+    let mut order = FrameOrder::new();
+    let arrivals = [screen_frame(4, 0), screen_frame(4, 0)];
+    
+    assert_eq!(rendered(&mut order, &arrivals), vec![4]);
+}
+
+#[test]
+fn test_clock_going_backwards_does_not_matter() {
+    // The server's clock stepped back between frames 1 and 2
+    let mut order = FrameOrder::new();
+    let arrivals = [screen_frame(1, 5000), screen_frame(2, 4000), screen_frame(3, 4016)];
+    
+    assert_eq!(rendered(&mut order, &arrivals), vec![1, 2, 3]);
+}
+
+#[test]
+fn test_keyframe_chunks_share_a_sequence() {
+    let chunk = |sequence, is_last| Message::KeyFrameChunk {
+        sequence,
+        timestamp: 0,
+        width: 64,
+        height: 64,
+        tiles: Vec::new(),
+        is_last,
+    };
+    let delta = |sequence| Message::DeltaFrame { sequence, timestamp: 0, tiles: Vec::new(), checksum: None };
+    
+    let mut order = FrameOrder::new();
+    let arrivals = [chunk(3, false), chunk(3, false), delta(2), chunk(3, true), delta(4), chunk(3, false)];
+    
+    // Every chunk of keyframe 3 lands, the stale delta and the straggling chunk after 4 don't
+    assert_eq!(rendered(&mut order, &arrivals), vec![3, 3, 3, 4]);
+}
+
+#[test]
+fn test_reset_accepts_a_new_connection_numbering() {
+    let mut order = FrameOrder::new();
+    assert!(order.admit(500));
+    
+    order.reset();
+    assert_eq!(order.latest(), None);
+    assert!(order.admit(0));
+}
@@ -0,0 +1,399 @@
+use bytes::Bytes;
+use proptest::prelude::*;
+use vox_gui::common::encoder::EncoderTuning;
+use vox_gui::common::frame_processor::{decompress_tiles, rgb_frame_len, FrameProcessor, FrameType, ProcessedFrame, TileData, DEFAULT_MAX_DELTA_RUN};
+use vox_gui::common::quality::{ContentProfile, QualityMode};
+use vox_gui::server::frame_source::{FrameSource, TestPatternSource};
+use vox_gui::server::screen_capture::{scale_rgb, ScreenCapture, DEFAULT_BITRATE};
+
+// Random frame dimensions (including sizes that aren't multiples of the tile size),
+// a random previous frame, and a list of random byte changes to apply to it
+fn frame_pair() -> impl Strategy<Value = (u32, u32, Vec<u8>, Vec<(usize, u8)>)> {
+    (1u32..200, 1u32..200).prop_flat_map(|(width, height)| {
+        let len = (width * height * 3) as usize;
+        (
+            Just(width),
+            Just(height),
+            prop::collection::vec(any::<u8>(), len),
+            prop::collection::vec((0..len, any::<u8>()), 0..64),
+        )
+    })
+}
+
+fn reconstruct(processor: &FrameProcessor, previous: &[u8], current: &[u8]) -> Vec<u8> {
+    let Some(processed) = processor.process_frame(current, false).unwrap() else {
+        // Nothing changed, so the client keeps what it has
+        return previous.to_vec();
+    };
+    let mut reconstructed = previous.to_vec();
+    
+    match processed.frame_type {
+        FrameType::KeyFrame => reconstructed.copy_from_slice(&processed.data),
+        FrameType::DeltaFrame => processor.apply_delta(&mut reconstructed, &processed).unwrap(),
+    }
+    
+    reconstructed
+}
+
+proptest! {
+    #[test]
+    fn delta_round_trip_is_bit_exact((width, height, previous, changes) in frame_pair()) {
+        let processor = FrameProcessor::new(width, height);
+        processor.process_frame(&previous, true).unwrap();
+        
+        let mut current = previous.clone();
+        for (index, value) in changes {
+            current[index] = value;
+        }
+        
+        let reconstructed = reconstruct(&processor, &previous, &current);
+        prop_assert_eq!(reconstructed, current);
+    }
+    
+    #[test]
+    fn delta_sequence_stays_in_sync(
+        (width, height, first, changes) in frame_pair(),
+        steps in 2usize..6,
+    ) {
+        let processor = FrameProcessor::new(width, height);
+        processor.process_frame(&first, true).unwrap();
+        
+        // Spread the changes over several frames and keep a client-side copy in sync
+        let mut client_frame = first.clone();
+        let mut current = first;
+        let per_step = (changes.len() / steps).max(1);
+        
+        for step_changes in changes.chunks(per_step) {
+            for &(index, value) in step_changes {
+                current[index] = value;
+            }
+            client_frame = reconstruct(&processor, &client_frame, &current);
+            prop_assert_eq!(&client_frame, &current);
+        }
+    }
+}
+
+#[test]
+fn edge_tile_change_is_applied() {
+    // 70x70 leaves a 6-pixel-wide column and row of clipped edge tiles
+    let (width, height) = (70u32, 70u32);
+    let processor = FrameProcessor::new(width, height);
+    let previous = vec![0u8; (width * height * 3) as usize];
+    processor.process_frame(&previous, true).unwrap();
+    
+    // Change only the bottom-right pixel
+    let mut current = previous.clone();
+    let last = current.len() - 3;
+    current[last..].copy_from_slice(&[255, 128, 64]);
+    
+    let processed = processor.process_frame(&current, false).unwrap().unwrap();
+    assert_eq!(processed.frame_type, FrameType::DeltaFrame);
+    assert_eq!(processed.tiles.as_ref().map(|t| t.len()), Some(1));
+    
+    let mut reconstructed = previous;
+    processor.apply_delta(&mut reconstructed, &processed).unwrap();
+    assert_eq!(reconstructed, current);
+}
+
+#[test]
+fn flickering_tile_is_the_only_one_resent() {
+    // A cursor blinking in the second of eight tiles
+    let (width, height) = (256u32, 128u32);
+    let processor = FrameProcessor::new(width, height);
+    let off: Vec<u8> = (0..width * height * 3).map(|i| (i % 251) as u8).collect();
+    let mut on = off.clone();
+    for y in 10..30usize {
+        let row = (y * width as usize + 70) * 3;
+        on[row..row + 6].fill(255);
+    }
+    processor.process_frame(&off, true).unwrap();
+    
+    let mut client_frame = off.clone();
+    for (step, current) in [&on, &off, &on, &off].into_iter().enumerate() {
+        let processed = processor.process_frame(current, false).unwrap().unwrap();
+        let positions: Vec<_> = processed.tiles.as_ref().unwrap().iter().map(|t| (t.x, t.y)).collect();
+        assert_eq!(positions, vec![(64, 0)], "step {}", step);
+        
+        processor.apply_delta(&mut client_frame, &processed).unwrap();
+        assert_eq!(&client_frame, current);
+    }
+    
+    // Back where the client already is: nothing to send
+    assert!(processor.process_frame(&off, false).unwrap().is_none());
+}
+
+// Arbitrary (possibly invalid) tile geometry and payload size
+fn arbitrary_tile() -> impl Strategy<Value = TileData> {
+    (any::<u32>(), any::<u32>(), 0u32..300, 0u32..300, 0usize..4096).prop_map(|(x, y, width, height, len)| {
+        TileData {
+            x: x % 400,
+            y: y % 400,
+            width,
+            height,
+            data: Bytes::from(vec![0xAB; len]),
+        }
+    })
+}
+
+proptest! {
+    #[test]
+    fn mismatched_frame_sizes_are_rejected(width in 1u32..200, height in 1u32..200, len in 0usize..200_000) {
+        let processor = FrameProcessor::new(width, height);
+        let frame = vec![0u8; len];
+        let result = processor.process_frame(&frame, true);
+        prop_assert_eq!(result.is_ok(), len == (width * height * 3) as usize);
+    }
+    
+    #[test]
+    fn malformed_tiles_never_panic(tiles in prop::collection::vec(arbitrary_tile(), 1..8)) {
+        let processor = FrameProcessor::new(128, 96);
+        let mut frame = vec![0u8; 128 * 96 * 3];
+        let delta = ProcessedFrame {
+            frame_type: FrameType::DeltaFrame,
+            data: Bytes::new(),
+            width: 128,
+            height: 96,
+            tiles: Some(tiles.clone()),
+        };
+        
+        let all_valid = tiles.iter().all(|t| {
+            t.x + t.width <= 128 && t.y + t.height <= 96 && t.data.len() == (t.width * t.height * 3) as usize
+        });
+        prop_assert_eq!(processor.apply_delta(&mut frame, &delta).is_ok(), all_valid);
+    }
+    
+    #[test]
+    fn scale_rgb_checks_buffer_size(width in 1usize..100, height in 1usize..100, extra in 0usize..10, scale in 0.1f32..1.0) {
+        let exact = vec![0u8; width * height * 3];
+        let scaled = scale_rgb(&exact, width, height, scale).unwrap();
+        prop_assert_eq!(scaled.len() % 3, 0);
+        
+        if extra > 0 {
+            let oversized = vec![0u8; width * height * 3 + extra];
+            prop_assert!(scale_rgb(&oversized, width, height, scale).is_err());
+            let undersized = vec![0u8; (width * height * 3).saturating_sub(extra)];
+            prop_assert!(scale_rgb(&undersized, width, height, scale).is_err());
+        }
+    }
+}
+
+#[test]
+fn tile_overflowing_frame_is_rejected() {
+    let processor = FrameProcessor::new(64, 64);
+    let mut frame = vec![0u8; 64 * 64 * 3];
+    let delta = ProcessedFrame {
+        frame_type: FrameType::DeltaFrame,
+        data: Bytes::new(),
+        width: 64,
+        height: 64,
+        tiles: Some(vec![TileData {
+            x: u32::MAX,
+            y: 0,
+            width: 2,
+            height: 1,
+            data: Bytes::from(vec![0u8; 6]),
+        }]),
+    };
+    
+    assert!(processor.apply_delta(&mut frame, &delta).is_err());
+    assert!(frame.iter().all(|&b| b == 0));
+}
+
+fn compressed_tile(x: u32, y: u32, width: u32, height: u32, raw_len: usize) -> TileData {
+    TileData {
+        x,
+        y,
+        width,
+        height,
+        data: Bytes::from(zstd::stream::encode_all(&vec![0x7Fu8; raw_len][..], 3).unwrap()),
+    }
+}
+
+#[test]
+fn malformed_incoming_tiles_are_dropped() {
+    let tiles = vec![
+        compressed_tile(0, 0, 8, 8, 8 * 8 * 3),
+        // Outside the frame
+        compressed_tile(60, 0, 8, 8, 8 * 8 * 3),
+        compressed_tile(u32::MAX, 0, 2, 2, 2 * 2 * 3),
+        // Too little and too much data for the tile's size
+        compressed_tile(8, 0, 8, 8, 8 * 8 * 3 - 1),
+        compressed_tile(16, 0, 8, 8, 8 * 8 * 3 + 1),
+        // Decompresses to far more than its size (a zstd bomb)
+        compressed_tile(24, 0, 1, 1, 16 << 20),
+        // Not zstd at all
+        TileData { x: 32, y: 0, width: 1, height: 1, data: Bytes::from_static(b"junk") },
+        compressed_tile(56, 56, 8, 8, 8 * 8 * 3),
+    ];
+    
+    let valid = decompress_tiles(tiles, 64, 64);
+    let positions: Vec<_> = valid.iter().map(|t| (t.x, t.y)).collect();
+    assert_eq!(positions, vec![(0, 0), (56, 56)]);
+    assert!(valid.iter().all(|t| t.data.len() == 8 * 8 * 3 && t.data.iter().all(|&b| b == 0x7F)));
+    
+    // What survives always applies cleanly
+    let processor = FrameProcessor::new(64, 64);
+    let mut frame = vec![0u8; 64 * 64 * 3];
+    let delta = ProcessedFrame {
+        frame_type: FrameType::DeltaFrame,
+        data: Bytes::new(),
+        width: 64,
+        height: 64,
+        tiles: Some(valid),
+    };
+    processor.apply_delta(&mut frame, &delta).unwrap();
+    assert_eq!(frame[0], 0x7F);
+    assert_eq!(frame[64 * 64 * 3 - 1], 0x7F);
+}
+
+#[test]
+fn tile_validation_checks_position_and_size() {
+    let tile = TileData { x: 60, y: 60, width: 4, height: 4, data: Bytes::from(vec![0u8; 48]) };
+    assert!(tile.validate(64, 64).is_ok());
+    assert!(tile.validate(63, 64).is_err());
+    assert!(tile.check_bounds(64, 63).is_err());
+    
+    let short = TileData { data: Bytes::from(vec![0u8; 47]), ..tile.clone() };
+    assert!(short.check_bounds(64, 64).is_ok());
+    assert!(short.validate(64, 64).is_err());
+    
+    let huge = TileData { x: 0, y: 0, width: u32::MAX, height: u32::MAX, data: Bytes::new() };
+    assert!(huge.validate(u32::MAX, u32::MAX).is_err());
+}
+
+#[test]
+fn static_screen_still_gets_keyframes_at_the_interval() {
+    let (width, height) = (128u32, 128u32);
+    let mut processor = FrameProcessor::new(width, height);
+    assert_eq!(processor.max_delta_run(), DEFAULT_MAX_DELTA_RUN);
+    processor.set_max_delta_run(5);
+    
+    let frame = vec![0x40u8; (width * height * 3) as usize];
+    let types: Vec<Option<FrameType>> = (0..13)
+        .map(|_| processor.process_frame(&frame, false).unwrap().map(|processed| processed.frame_type))
+        .collect();
+    
+    // The first frame, then one after every five unchanged frames, which send nothing
+    let keyframes: Vec<usize> = types.iter().enumerate()
+        .filter(|(_, frame_type)| **frame_type == Some(FrameType::KeyFrame))
+        .map(|(index, _)| index)
+        .collect();
+    assert_eq!(keyframes, vec![0, 6, 12]);
+    assert!(types.iter().all(|frame_type| *frame_type != Some(FrameType::DeltaFrame)));
+}
+
+#[test]
+fn any_keyframe_restarts_the_delta_run() {
+    let (width, height) = (128u32, 128u32);
+    let mut processor = FrameProcessor::new(width, height);
+    processor.set_max_delta_run(4);
+    
+    let still = vec![0u8; (width * height * 3) as usize];
+    let busy = vec![0xFFu8; still.len()];
+    processor.process_frame(&still, false).unwrap();
+    processor.process_frame(&still, false).unwrap();
+    processor.process_frame(&still, false).unwrap();
+    assert_eq!(processor.delta_run(), 2);
+    
+    // Most of the screen changed: a keyframe on its own, which counts as the resync
+    assert_eq!(processor.process_frame(&busy, false).unwrap().unwrap().frame_type, FrameType::KeyFrame);
+    assert_eq!(processor.delta_run(), 0);
+    // Unchanged frames send nothing but still count toward the run
+    for _ in 0..4 {
+        assert!(processor.process_frame(&busy, false).unwrap().is_none());
+    }
+    assert_eq!(processor.process_frame(&busy, false).unwrap().unwrap().frame_type, FrameType::KeyFrame);
+    
+    // Forced ones too
+    processor.process_frame(&busy, false).unwrap();
+    processor.process_frame(&busy, true).unwrap();
+    assert_eq!(processor.delta_run(), 0);
+    
+    // Zero would mean never sending a delta at all
+    processor.set_max_delta_run(0);
+    assert_eq!(processor.max_delta_run(), 1);
+}
+
+#[test]
+fn capture_ties_the_delta_run_to_the_quality_mode() {
+    let source = Box::new(TestPatternSource::new(64, 64));
+    let mut capture = ScreenCapture::from_source(source, 30, DEFAULT_BITRATE, EncoderTuning::default()).unwrap();
+    assert_eq!(capture.max_delta_run(), QualityMode::High.keyframe_interval());
+    
+    capture.set_quality(QualityMode::Minimal);
+    assert_eq!(capture.max_delta_run(), QualityMode::Minimal.keyframe_interval());
+    
+    // Stretched for text, shortened for motion, like the keyframe cadence
+    capture.set_content_profile(ContentProfile::Text);
+    assert_eq!(capture.max_delta_run(), QualityMode::Minimal.keyframe_interval() * 2);
+    capture.set_content_profile(ContentProfile::Motion);
+    assert_eq!(capture.max_delta_run(), QualityMode::Minimal.keyframe_interval() / 2);
+}
+
+#[test]
+fn identical_frames_produce_no_delta() {
+    let (width, height) = (128u32, 96u32);
+    let processor = FrameProcessor::new(width, height);
+    let frame: Vec<u8> = (0..width * height * 3).map(|i| (i % 253) as u8).collect();
+    assert_eq!(processor.process_frame(&frame, false).unwrap().unwrap().frame_type, FrameType::KeyFrame);
+    
+    for _ in 0..3 {
+        assert!(processor.process_frame(&frame.clone(), false).unwrap().is_none());
+    }
+    
+    // Changing something still gets through
+    let mut changed = frame.clone();
+    changed[0] ^= 0xFF;
+    let processed = processor.process_frame(&changed, false).unwrap().unwrap();
+    assert_eq!(processed.tiles.map(|tiles| tiles.len()), Some(1));
+}
+
+// The same frame every time
+struct StillSource;
+
+impl FrameSource for StillSource {
+    fn frame(&mut self) -> std::io::Result<Vec<u8>> {
+        Ok((0..64 * 64 * 4).map(|i| (i % 255) as u8).collect())
+    }
+    
+    fn width(&self) -> usize {
+        64
+    }
+    
+    fn height(&self) -> usize {
+        64
+    }
+}
+
+#[test]
+fn static_capture_sends_nothing_after_the_first_frame() {
+    let source = Box::new(StillSource);
+    let mut capture = ScreenCapture::from_source(source, 30, DEFAULT_BITRATE, EncoderTuning::default()).unwrap();
+    // Paced at 30 fps, so poll like the capture thread does
+    let first = loop {
+        if let Some(frame) = capture.capture_frame().unwrap() {
+            break frame;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(5));
+    };
+    assert_eq!(first.frame_type, FrameType::KeyFrame);
+    
+    for _ in 0..5 {
+        std::thread::sleep(std::time::Duration::from_millis(40));
+        if let Some(frame) = capture.capture_frame().unwrap() {
+            assert!(frame.to_message(1).is_none(), "sent {:?} for an unchanged screen", frame.frame_type);
+        }
+    }
+}
+
+#[test]
+fn test_rgb_frame_len_refuses_sizes_no_message_can_carry() {
+    assert_eq!(rgb_frame_len(1920, 1080), Some(1920 * 1080 * 3));
+    assert_eq!(rgb_frame_len(7680, 4320), Some(7680 * 4320 * 3));
+    
+    // Fits in a usize, but is past MAX_FRAME_LEN
+    assert_eq!(rgb_frame_len(16384, 16384), None);
+    
+    // Overflows even a 64-bit usize
+    assert_eq!(rgb_frame_len(u32::MAX, u32::MAX), None);
+}
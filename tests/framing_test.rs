@@ -0,0 +1,123 @@
+mod common;
+
+use bytes::BytesMut;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use vox_gui::common::auth::AccessCode;
+use vox_gui::common::crypto::KeyExchange;
+use vox_gui::common::framing::{frame_len, FrameDecoder, MAX_FRAME_LEN};
+use vox_gui::common::protocol::Message;
+
+fn framed(bodies: &[&[u8]]) -> Vec<u8> {
+    let mut stream = Vec::new();
+    for body in bodies {
+        stream.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        stream.extend_from_slice(body);
+    }
+    stream
+}
+
+#[test]
+fn test_one_byte_at_a_time_reassembles_every_message() {
+    let bodies: [&[u8]; 4] = [b"first", b"", &[7; 300], b"last"];
+    let stream = framed(&bodies);
+    
+    let mut decoder = FrameDecoder::new();
+    let mut buffer = BytesMut::new();
+    let mut decoded = Vec::new();
+    for byte in stream {
+        buffer.extend_from_slice(&[byte]);
+        while let Some(body) = decoder.decode(&mut buffer).unwrap() {
+            decoded.push(body.to_vec());
+        }
+    }
+    
+    assert_eq!(decoded, bodies.iter().map(|body| body.to_vec()).collect::<Vec<_>>());
+    assert!(buffer.is_empty());
+}
+
+#[test]
+fn test_split_length_prefix_waits_for_the_rest() {
+    let stream = framed(&[b"hello"]);
+    let mut decoder = FrameDecoder::new();
+    let mut buffer = BytesMut::from(&stream[..3]);
+    
+    assert!(decoder.decode(&mut buffer).unwrap().is_none());
+    // Nothing is consumed until the whole prefix is there
+    assert_eq!(buffer.len(), 3);
+    
+    buffer.extend_from_slice(&stream[3..6]);
+    assert!(decoder.decode(&mut buffer).unwrap().is_none());
+    buffer.extend_from_slice(&stream[6..]);
+    assert_eq!(&decoder.decode(&mut buffer).unwrap().unwrap()[..], b"hello");
+}
+
+#[test]
+fn test_several_messages_in_one_read() {
+    let mut buffer = BytesMut::from(&framed(&[b"a", b"bc", b"def"])[..]);
+    buffer.extend_from_slice(&[0, 0]);
+    let mut decoder = FrameDecoder::new();
+    
+    let mut decoded = Vec::new();
+    while let Some(body) = decoder.decode(&mut buffer).unwrap() {
+        decoded.push(body.to_vec());
+    }
+    assert_eq!(decoded, vec![b"a".to_vec(), b"bc".to_vec(), b"def".to_vec()]);
+    // The start of the next prefix stays for the next read
+    assert_eq!(&buffer[..], &[0, 0]);
+}
+
+#[test]
+fn test_oversized_lengths_are_rejected() {
+    assert_eq!(frame_len(1024u32.to_be_bytes(), 1024).unwrap(), 1024);
+    assert!(frame_len(1025u32.to_be_bytes(), 1024).is_err());
+    assert!(frame_len(u32::MAX.to_be_bytes(), MAX_FRAME_LEN).is_err());
+    
+    let mut decoder = FrameDecoder::with_max_frame_len(16);
+    let mut buffer = BytesMut::from(&framed(&[&[0; 17]])[..]);
+    assert!(decoder.decode(&mut buffer).is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_server_reassembles_a_message_sent_one_byte_at_a_time() {
+    let server = common::test_server(AccessCode::generate());
+    let addr = common::serve(server).await;
+    
+    let mut stream = TcpStream::connect(&addr).await.unwrap();
+    stream.set_nodelay(true).unwrap();
+    let hello = Message::KeyExchange { public_key: KeyExchange::new().public_key_bytes().to_vec() };
+    for byte in framed(&[&hello.serialize().unwrap()]) {
+        stream.write_all(&[byte]).await.unwrap();
+        stream.flush().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(1)).await;
+    }
+    
+    let reply = tokio::time::timeout(Duration::from_secs(5), async {
+        let mut len = [0u8; 4];
+        stream.read_exact(&mut len).await.unwrap();
+        let mut data = vec![0u8; u32::from_be_bytes(len) as usize];
+        stream.read_exact(&mut data).await.unwrap();
+        Message::deserialize(&data).unwrap()
+    })
+    .await
+    .unwrap();
+    assert!(matches!(reply, Message::KeyExchangeAck { .. }), "{:?}", reply);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_server_refuses_a_small_order_public_key() {
+    let server = common::test_server(AccessCode::generate());
+    let addr = common::serve(server).await;
+    
+    for public_key in [vec![0u8; 32], { let mut one = vec![0u8; 32]; one[0] = 1; one }] {
+        let mut stream = TcpStream::connect(&addr).await.unwrap();
+        let hello = Message::KeyExchange { public_key };
+        stream.write_all(&framed(&[&hello.serialize().unwrap()])).await.unwrap();
+        
+        // The connection is closed without a KeyExchangeAck
+        let mut len = [0u8; 4];
+        let read = tokio::time::timeout(Duration::from_secs(5), stream.read_exact(&mut len)).await.unwrap();
+        assert!(read.is_err(), "server answered a weak key");
+    }
+}
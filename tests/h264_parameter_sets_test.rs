@@ -0,0 +1,85 @@
+use vox_gui::common::h264::{annex_b, has_parameter_sets, nal_type, nal_units, ParameterSets, NAL_IDR, NAL_PPS, NAL_SPS};
+use vox_gui::common::protocol::Message;
+
+const SPS: &[u8] = &[0x67, 0x42, 0xc0, 0x1f, 0xda];
+const PPS: &[u8] = &[0x68, 0xce, 0x3c, 0x80];
+const IDR: &[u8] = &[0x65, 0x88, 0x84, 0x21];
+const P_SLICE: &[u8] = &[0x41, 0x9a, 0x02];
+
+fn stream(units: &[&[u8]]) -> Vec<u8> {
+    let mut data = Vec::new();
+    for (index, unit) in units.iter().enumerate() {
+        // Mix four- and three-byte start codes, as encoders do
+        if index == 0 {
+            data.extend_from_slice(&[0, 0, 0, 1]);
+        } else {
+            data.extend_from_slice(&[0, 0, 1]);
+        }
+        data.extend_from_slice(unit);
+    }
+    data
+}
+
+#[test]
+fn test_nal_units_are_split_on_start_codes() {
+    let data = stream(&[SPS, PPS, IDR]);
+    let units = nal_units(&data);
+    
+    assert_eq!(units, vec![SPS, PPS, IDR]);
+    let types: Vec<u8> = units.iter().map(|unit| nal_type(unit)).collect();
+    assert_eq!(types, vec![NAL_SPS, NAL_PPS, NAL_IDR]);
+    
+    // Four-byte start codes between units don't leave a zero on the previous one
+    let mut four_byte = annex_b(SPS, PPS);
+    four_byte.extend_from_slice(&[0, 0, 0, 1]);
+    four_byte.extend_from_slice(IDR);
+    assert_eq!(nal_units(&four_byte), vec![SPS, PPS, IDR]);
+    
+    assert!(nal_units(&[]).is_empty());
+}
+
+#[test]
+fn test_mid_stream_join_gets_parameter_sets_first() {
+    let idr = stream(&[SPS, PPS, IDR]);
+    let p_frame = stream(&[P_SLICE]);
+    
+    // The server watches the stream from the start
+    let mut cache = ParameterSets::new();
+    assert!(cache.config_for(&p_frame).is_none(), "nothing to send before any SPS was seen");
+    assert!(cache.observe(&idr));
+    assert!(!cache.observe(&p_frame));
+    assert!(cache.is_complete());
+    
+    // A viewer starting on the IDR frame needs nothing extra
+    assert!(has_parameter_sets(&idr));
+    assert!(cache.config_for(&idr).is_none());
+    
+    // One that joins on a frame without them is sent the cached ones ahead of it
+    let config = cache.config_for(&p_frame).expect("joiner needs the parameter sets");
+    let decoded = Message::deserialize(&config.serialize().unwrap()).unwrap();
+    let Message::CodecConfig { sps, pps } = decoded else {
+        panic!("expected CodecConfig, got {:?}", decoded);
+    };
+    
+    // What the joining client's decoder is fed: parameter sets, then the frame
+    let mut decoder_input = annex_b(&sps, &pps);
+    decoder_input.extend_from_slice(&p_frame);
+    let types: Vec<u8> = nal_units(&decoder_input).iter().map(|unit| nal_type(unit)).collect();
+    assert_eq!(types, vec![NAL_SPS, NAL_PPS, 1]);
+}
+
+#[test]
+fn test_new_parameter_sets_replace_old_ones() {
+    let mut cache = ParameterSets::new();
+    cache.observe(&stream(&[SPS, PPS, IDR]));
+    
+    // A resolution change comes with a new SPS
+    let new_sps: &[u8] = &[0x67, 0x64, 0x00, 0x28];
+    assert!(cache.observe(&stream(&[new_sps, PPS, IDR])));
+    
+    let Some(Message::CodecConfig { sps, pps }) = cache.config_for(&stream(&[P_SLICE])) else {
+        panic!("expected CodecConfig");
+    };
+    assert_eq!(sps, new_sps);
+    assert_eq!(pps, PPS);
+}
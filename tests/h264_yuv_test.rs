@@ -0,0 +1,96 @@
+use vox_gui::client::h264_decoder::{nv12_to_rgb, yuv420p_to_rgb};
+use vox_gui::common::color::ColorSpace;
+
+const WIDTH: usize = 6;
+const HEIGHT: usize = 4;
+
+fn color_space() -> ColorSpace {
+    ColorSpace::for_resolution(WIDTH as u32, HEIGHT as u32)
+}
+
+// A plane of `rows` rows of `row` bytes from `value(x, y)`, each padded to `stride`
+fn plane(row: usize, rows: usize, stride: usize, value: impl Fn(usize, usize) -> u8) -> Vec<u8> {
+    let mut data = vec![0xEE; stride * rows];
+    for y in 0..rows {
+        for x in 0..row {
+            data[y * stride + x] = value(x, y);
+        }
+    }
+    data
+}
+
+fn luma(x: usize, y: usize) -> u8 {
+    (16 + x * 30 + y * 10) as u8
+}
+
+fn cb(x: usize, y: usize) -> u8 {
+    (100 + x * 10 + y * 5) as u8
+}
+
+fn cr(x: usize, y: usize) -> u8 {
+    (150 - x * 10 - y * 5) as u8
+}
+
+// What every pixel should come out as, computed one at a time
+fn expected() -> Vec<u8> {
+    let color_space = color_space();
+    let mut rgb = Vec::new();
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let (r, g, b) = color_space.yuv_to_rgb(luma(x, y), cb(x / 2, y / 2), cr(x / 2, y / 2));
+            rgb.extend_from_slice(&[r, g, b]);
+        }
+    }
+    rgb
+}
+
+#[test]
+fn test_padded_yuv420p() {
+    let (chroma_width, chroma_height) = (WIDTH / 2, HEIGHT / 2);
+    // Hardware decoders align rows to 32 or 64 bytes
+    let strides = [64, 32, 48];
+    let y = plane(WIDTH, HEIGHT, strides[0], luma);
+    let u = plane(chroma_width, chroma_height, strides[1], cb);
+    let v = plane(chroma_width, chroma_height, strides[2], cr);
+    let rgb = yuv420p_to_rgb([&y, &u, &v], strides, WIDTH, HEIGHT, &color_space()).unwrap();
+    assert_eq!(rgb, expected());
+    
+    // The last row needs no padding after it
+    let trimmed = &u[..strides[1] + chroma_width];
+    assert!(yuv420p_to_rgb([&y, trimmed, &v], strides, WIDTH, HEIGHT, &color_space()).is_ok());
+}
+
+#[test]
+fn test_nv12() {
+    let stride = 16;
+    let y = plane(WIDTH, HEIGHT, stride, luma);
+    let uv = plane(WIDTH, HEIGHT / 2, stride, |x, y| if x % 2 == 0 { cb(x / 2, y) } else { cr(x / 2, y) });
+    let rgb = nv12_to_rgb([&y, &uv], [stride, stride], WIDTH, HEIGHT, &color_space()).unwrap();
+    assert_eq!(rgb, expected());
+}
+
+#[test]
+fn test_odd_sizes_round_chroma_up() {
+    let (width, height) = (5, 3);
+    let y = vec![128; width * height];
+    let chroma = vec![128; 3 * 2];
+    assert!(yuv420p_to_rgb([&y, &chroma, &chroma], [width, 3, 3], width, height, &color_space()).is_ok());
+    assert!(nv12_to_rgb([&y, &vec![128; 6 * 2]], [width, 6], width, height, &color_space()).is_ok());
+    // One chroma column short
+    assert!(nv12_to_rgb([&y, &vec![128; 4 * 2]], [width, 4], width, height, &color_space()).is_err());
+}
+
+#[test]
+fn test_short_planes_are_errors_not_panics() {
+    let y = vec![0; WIDTH * HEIGHT];
+    let chroma = vec![128; (WIDTH / 2) * (HEIGHT / 2)];
+    let space = color_space();
+    
+    // Chroma read as if it were NV12-sized or 4:2:2
+    assert!(yuv420p_to_rgb([&y, &chroma[1..], &chroma], [WIDTH, WIDTH / 2, WIDTH / 2], WIDTH, HEIGHT, &space).is_err());
+    assert!(yuv420p_to_rgb([&y, &chroma, &chroma], [WIDTH, WIDTH, WIDTH], WIDTH, HEIGHT, &space).is_err());
+    assert!(nv12_to_rgb([&y, &chroma], [WIDTH, WIDTH], WIDTH, HEIGHT, &space).is_err());
+    // A stride shorter than the row would read into the next one
+    assert!(yuv420p_to_rgb([&y, &chroma, &chroma], [WIDTH - 1, WIDTH / 2, WIDTH / 2], WIDTH, HEIGHT, &space).is_err());
+    assert!(yuv420p_to_rgb([&[], &[], &[]], [0, 0, 0], 0, 0, &space).is_err());
+}
@@ -0,0 +1,167 @@
+mod common;
+
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use vox_gui::client::connection::Connection;
+use vox_gui::common::auth::AccessCode;
+use vox_gui::common::crypto::{open_message, seal_message, CryptoSession, KeyExchange, MAX_CORRUPT_MESSAGES};
+use vox_gui::common::protocol::Message;
+use vox_gui::common::transport::{TcpConnection, Transport};
+use vox_gui::server::server::ServerControl;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+async fn start_server() -> (String, String, ServerControl) {
+    let access_code = AccessCode::generate();
+    let code = access_code.code.clone();
+    
+    let server = common::test_server(access_code);
+    let control = server.control();
+    let addr = common::serve(server).await;
+    
+    (addr, code, control)
+}
+
+async fn wait_for_sessions(control: &ServerControl, count: usize) {
+    for _ in 0..50 {
+        if control.sessions().await.len() == count {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("expected {} sessions, have {}", count, control.sessions().await.len());
+}
+
+/// After any abrupt close, the server still takes new clients and keeps no stale session.
+async fn assert_still_serving(addr: &str, code: &str, control: &ServerControl) {
+    wait_for_sessions(control, 0).await;
+    let (mut connection, _, _) = Connection::new();
+    let _session = connection.connect(addr, code).await.unwrap();
+    wait_for_sessions(control, 1).await;
+}
+
+async fn connect(addr: &str) -> TcpConnection {
+    TcpConnection::new(TcpStream::connect(addr).await.unwrap()).unwrap()
+}
+
+async fn key_exchange(connection: &TcpConnection) -> CryptoSession {
+    let ours = KeyExchange::new();
+    let offer = Message::KeyExchange { public_key: ours.public_key_bytes().to_vec() };
+    connection.send(&offer.serialize().unwrap()).await.unwrap();
+    
+    let reply = tokio::time::timeout(TIMEOUT, connection.recv()).await.unwrap().unwrap();
+    let Message::KeyExchangeAck { public_key } = Message::deserialize(&reply).unwrap() else {
+        panic!("expected KeyExchangeAck");
+    };
+    CryptoSession::from_shared_secret(&ours.agree(&public_key).unwrap()).unwrap()
+}
+
+async fn authenticate(connection: &TcpConnection, crypto: &CryptoSession, code: &str) {
+    let request = Message::AuthRequest { code: code.to_string() }.serialize().unwrap();
+    for frame in seal_message(crypto, &request).unwrap() {
+        connection.send(&frame).await.unwrap();
+    }
+    loop {
+        let data = tokio::time::timeout(TIMEOUT, connection.recv()).await.unwrap().unwrap();
+        if let Some(Message::AuthResponse { success, .. }) = open_message(crypto, &data).unwrap() {
+            assert!(success);
+            return;
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_close_before_key_exchange() {
+    let (addr, code, control) = start_server().await;
+    
+    drop(TcpStream::connect(&addr).await.unwrap());
+    
+    // Half a length prefix, then gone
+    let mut stream = TcpStream::connect(&addr).await.unwrap();
+    stream.write_all(&[0, 0]).await.unwrap();
+    drop(stream);
+    
+    assert_still_serving(&addr, &code, &control).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_close_between_key_exchange_and_auth() {
+    let (addr, code, control) = start_server().await;
+    
+    let connection = connect(&addr).await;
+    key_exchange(&connection).await;
+    drop(connection);
+    
+    assert_still_serving(&addr, &code, &control).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_auth_before_key_exchange_is_refused() {
+    let (addr, code, control) = start_server().await;
+    
+    let connection = connect(&addr).await;
+    let request = Message::AuthRequest { code: code.clone() };
+    connection.send(&request.serialize().unwrap()).await.unwrap();
+    
+    // The server hangs up without a session
+    assert!(tokio::time::timeout(TIMEOUT, connection.recv()).await.unwrap().is_err());
+    assert_still_serving(&addr, &code, &control).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_close_after_auth_ends_the_session() {
+    let (addr, code, control) = start_server().await;
+    
+    let connection = connect(&addr).await;
+    let crypto = key_exchange(&connection).await;
+    authenticate(&connection, &crypto, &code).await;
+    wait_for_sessions(&control, 1).await;
+    
+    drop(connection);
+    assert_still_serving(&addr, &code, &control).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_broken_stream_after_auth_ends_the_session() {
+    let (addr, code, control) = start_server().await;
+    
+    let connection = connect(&addr).await;
+    let crypto = key_exchange(&connection).await;
+    authenticate(&connection, &crypto, &code).await;
+    wait_for_sessions(&control, 1).await;
+    
+    // Frames that never decrypt, more than the server forgives in a row. The socket stays
+    // open, but the session ends all the same
+    for _ in 0..=MAX_CORRUPT_MESSAGES {
+        connection.send(&[0xEE; 64]).await.unwrap();
+    }
+    assert!(tokio::time::timeout(TIMEOUT, async { while connection.recv().await.is_ok() {} }).await.is_ok());
+    
+    wait_for_sessions(&control, 0).await;
+    drop(connection);
+    assert_still_serving(&addr, &code, &control).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_failed_auth_closes_the_connection() {
+    let (addr, code, control) = start_server().await;
+    let wrong = if code == "000000" { "111111" } else { "000000" };
+    
+    let connection = connect(&addr).await;
+    let crypto = key_exchange(&connection).await;
+    let request = Message::AuthRequest { code: wrong.to_string() }.serialize().unwrap();
+    for frame in seal_message(&crypto, &request).unwrap() {
+        connection.send(&frame).await.unwrap();
+    }
+    let data = tokio::time::timeout(TIMEOUT, connection.recv()).await.unwrap().unwrap();
+    assert!(matches!(open_message(&crypto, &data).unwrap(), Some(Message::AuthResponse { success: false, .. })));
+    
+    // No second guess on the same connection: the right code now gets no answer
+    let request = Message::AuthRequest { code: code.clone() }.serialize().unwrap();
+    for frame in seal_message(&crypto, &request).unwrap() {
+        let _ = connection.send(&frame).await;
+    }
+    assert!(tokio::time::timeout(TIMEOUT, connection.recv()).await.unwrap().is_err());
+    assert_still_serving(&addr, &code, &control).await;
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use std::io;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+use vox_gui::client::connection::Connection;
+use vox_gui::common::auth::AccessCode;
+use vox_gui::common::protocol::Message;
+use vox_gui::server::frame_source::{FrameSource, FrameSourceFactory, TestPatternSource};
+use vox_gui::server::server::Server;
+
+// Counts every frame read from the pattern behind it
+struct CountingSource {
+    inner: TestPatternSource,
+    frames: Arc<AtomicUsize>,
+}
+
+impl FrameSource for CountingSource {
+    fn frame(&mut self) -> io::Result<Vec<u8>> {
+        self.frames.fetch_add(1, Ordering::SeqCst);
+        self.inner.frame()
+    }
+    
+    fn width(&self) -> usize {
+        self.inner.width()
+    }
+    
+    fn height(&self) -> usize {
+        self.inner.height()
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_nothing_is_captured_without_sessions() {
+    let access_code = AccessCode::generate();
+    let code = access_code.code.clone();
+    
+    let opened = Arc::new(AtomicUsize::new(0));
+    let frames = Arc::new(AtomicUsize::new(0));
+    let (opens, reads) = (opened.clone(), frames.clone());
+    let counting: FrameSourceFactory = Arc::new(move |_| {
+        opens.fetch_add(1, Ordering::SeqCst);
+        Ok(Box::new(CountingSource { inner: TestPatternSource::new(64, 64), frames: reads.clone() }) as Box<dyn FrameSource>)
+    });
+    let server = Server::new(Arc::new(RwLock::new(Some(access_code)))).with_frame_sources(counting);
+    let control = server.control();
+    let addr = common::serve(server).await;
+    
+    // An empty session map never opens the display
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert_eq!(opened.load(Ordering::SeqCst), 0);
+    assert_eq!(frames.load(Ordering::SeqCst), 0);
+    
+    let (mut connection, _, _) = Connection::new();
+    let (mut rx, tx) = connection.connect(&addr, &code).await.unwrap();
+    tx.send(Message::StartStream).unwrap();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match rx.recv().await {
+                Some(Message::ScreenFrame { .. }) => return,
+                Some(_) => {}
+                None => panic!("connection closed"),
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(opened.load(Ordering::SeqCst), 1);
+    assert!(frames.load(Ordering::SeqCst) > 0);
+    
+    // Once the last session is gone capture stops again
+    for session in control.sessions().await {
+        control.disconnect(&session.id).await;
+    }
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    let stopped_at = frames.load(Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(frames.load(Ordering::SeqCst), stopped_at);
+    assert_eq!(opened.load(Ordering::SeqCst), 1);
+}
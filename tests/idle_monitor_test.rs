@@ -0,0 +1,64 @@
+mod common;
+
+use std::time::{Duration, Instant};
+use vox_gui::client::connection::Connection;
+use vox_gui::common::auth::{AccessCode, AccessCodes, Permissions};
+use vox_gui::server::idle::IdleMonitor;
+
+#[test]
+fn test_idle_session_is_removed_after_timeout() {
+    let mut monitor = IdleMonitor::new(Duration::from_secs(60));
+    let start = Instant::now();
+    
+    monitor.register("session", start);
+    
+    // Still within the timeout
+    assert!(monitor.take_expired(start + Duration::from_secs(59)).is_empty());
+    assert!(monitor.is_tracked("session"));
+    
+    // Advance the clock past the timeout
+    let expired = monitor.take_expired(start + Duration::from_secs(61));
+    assert_eq!(expired, vec!["session".to_string()]);
+    assert!(!monitor.is_tracked("session"));
+    
+    // Expired sessions are only reported once
+    assert!(monitor.take_expired(start + Duration::from_secs(120)).is_empty());
+}
+
+#[test]
+fn test_input_resets_idle_timer() {
+    let mut monitor = IdleMonitor::new(Duration::from_secs(60));
+    let start = Instant::now();
+    
+    monitor.register("active", start);
+    monitor.register("idle", start);
+    monitor.record_input("active", start + Duration::from_secs(50));
+    
+    let expired = monitor.take_expired(start + Duration::from_secs(90));
+    assert_eq!(expired, vec!["idle".to_string()]);
+    assert!(monitor.is_tracked("active"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_view_only_session_outlives_the_idle_timeout() {
+    let access_code = AccessCode::generate();
+    let code = access_code.code.clone();
+    let mut codes = AccessCodes::new();
+    codes.add("135790", Permissions::VIEW_ONLY);
+    let server = common::test_server(access_code)
+        .with_access_codes(codes)
+        .with_idle_timeout(Duration::from_secs(1));
+    let control = server.control();
+    let addr = common::serve(server).await;
+    
+    let (mut viewer, _, _) = Connection::new();
+    let _viewer = viewer.connect(&addr, "135790").await.unwrap();
+    let (mut controller, _, _) = Connection::new();
+    let _controller = controller.connect(&addr, &code).await.unwrap();
+    
+    // Neither sends input; only the session that could have is disconnected
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    let sessions = control.sessions().await;
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].permissions, Permissions::VIEW_ONLY);
+}
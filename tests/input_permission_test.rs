@@ -0,0 +1,36 @@
+use anyhow::anyhow;
+use vox_gui::common::protocol::{ErrorKind, Message};
+use vox_gui::server::input_handler::{input_permitted, is_permission_error, InputNotPermitted, INPUT_PERMISSION_HELP};
+
+#[test]
+fn test_permission_errors_are_told_apart() {
+    let denied: anyhow::Error = InputNotPermitted.into();
+    assert!(is_permission_error(&denied));
+    assert_eq!(denied.to_string(), INPUT_PERMISSION_HELP);
+    
+    // Context added on the way up doesn't hide it
+    assert!(is_permission_error(&denied.context("Failed to move the mouse")));
+    
+    // An injection that failed for another reason is not a permission problem
+    assert!(!is_permission_error(&anyhow!("simulating input failed: (could not post event)")));
+}
+
+#[test]
+fn test_only_macos_asks_for_a_permission() {
+    if !cfg!(target_os = "macos") {
+        assert!(input_permitted());
+    }
+}
+
+#[test]
+fn test_input_denied_error_round_trip() {
+    let message = Message::Error { message: INPUT_PERMISSION_HELP.to_string(), kind: ErrorKind::InputNotPermitted };
+    match Message::deserialize(&message.serialize().unwrap()).unwrap() {
+        Message::Error { message, kind } => {
+            assert_eq!(kind, ErrorKind::InputNotPermitted);
+            assert_eq!(message, INPUT_PERMISSION_HELP);
+        }
+        other => panic!("unexpected message {:?}", other),
+    }
+    assert_eq!(ErrorKind::default(), ErrorKind::Other);
+}
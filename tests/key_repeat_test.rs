@@ -0,0 +1,76 @@
+use vox_gui::common::protocol::{Message, Modifiers};
+use vox_gui::server::input_handler::{HeldKey, HeldKeys};
+
+fn named(key: &str) -> HeldKey {
+    HeldKey::Named(key.to_string())
+}
+
+// (pressed, repeat) for each event of `a` held down for three repeats
+const HELD_A: [(bool, bool); 5] = [(true, false), (true, true), (true, true), (true, true), (false, false)];
+
+#[test]
+fn test_held_key_repeats_once_per_step_without_system_repeats() {
+    let mut keys = HeldKeys::with_system_repeats(false);
+    let injected: Vec<_> = HELD_A.iter().map(|&(pressed, repeat)| keys.accept(named("a"), pressed, repeat)).collect();
+    // The system won't repeat an injected key, so the client's repeats are pressed again
+    assert_eq!(injected, vec![true, true, true, true, true]);
+    assert!(!keys.is_held(&named("a")));
+}
+
+#[test]
+fn test_held_key_is_left_to_system_repeats() {
+    let mut keys = HeldKeys::with_system_repeats(true);
+    let injected: Vec<_> = HELD_A.iter().map(|&(pressed, repeat)| keys.accept(named("a"), pressed, repeat)).collect();
+    // One press and one release; the system repeats the held key itself
+    assert_eq!(injected, vec![true, false, false, false, true]);
+}
+
+#[test]
+fn test_second_press_of_a_held_key_is_dropped() {
+    for system_repeats in [false, true] {
+        let mut keys = HeldKeys::with_system_repeats(system_repeats);
+        assert!(keys.accept(named("a"), true, false));
+        assert!(!keys.accept(named("a"), true, false));
+        assert!(keys.is_held(&named("a")));
+        
+        // Other keys are held independently, by name and by position
+        assert!(keys.accept(named("b"), true, false));
+        assert!(keys.accept(HeldKey::Physical(0x04), true, false));
+        assert!(keys.accept(named("a"), false, false));
+        assert!(keys.is_held(&named("b")));
+    }
+}
+
+#[test]
+fn test_repeat_after_a_lost_press_presses_the_key() {
+    let mut keys = HeldKeys::with_system_repeats(true);
+    assert!(keys.accept(HeldKey::Physical(0x04), true, true));
+    assert!(keys.is_held(&HeldKey::Physical(0x04)));
+    // Releases always go through, held or not
+    assert!(keys.accept(HeldKey::Physical(0x04), false, false));
+    assert!(keys.accept(HeldKey::Physical(0x04), false, false));
+}
+
+#[test]
+fn test_messages_are_filtered_and_released() {
+    let mut keys = HeldKeys::with_system_repeats(true);
+    let press = |repeat| Message::KeyEvent { key: "a".to_string(), pressed: true, repeat, modifiers: Modifiers::default() };
+    assert!(keys.accept_message(&press(false)));
+    assert!(!keys.accept_message(&press(true)));
+    assert!(keys.accept_message(&Message::PhysicalKeyEvent { usage: 0x05, pressed: true, repeat: false, modifiers: Modifiers::default() }));
+    assert!(keys.accept_message(&Message::MouseMove { x: 1, y: 2 }));
+    
+    // A client leaving mid-press releases everything it held
+    let mut released: Vec<_> = keys
+        .release_all()
+        .into_iter()
+        .map(|message| match message {
+            Message::KeyEvent { key, pressed: false, .. } => named(&key),
+            Message::PhysicalKeyEvent { usage, pressed: false, .. } => HeldKey::Physical(usage),
+            other => panic!("unexpected {:?}", other),
+        })
+        .collect();
+    released.sort_by_key(|key| format!("{:?}", key));
+    assert_eq!(released, vec![named("a"), HeldKey::Physical(0x05)]);
+    assert!(keys.release_all().is_empty());
+}
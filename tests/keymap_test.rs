@@ -0,0 +1,103 @@
+use egui::Key;
+use vox_gui::client::keymap::{format_key, hid_usage, KeyboardMode};
+use vox_gui::client::view::ClientPreferences;
+use vox_gui::server::input_handler::named_key;
+use vox_gui::server::scancode::{macos_keycode, native_keycode, windows_scancode, x11_keycode};
+
+#[test]
+fn test_keys_map_to_hid_usages() {
+    assert_eq!(hid_usage(Key::A), Some(0x04));
+    assert_eq!(hid_usage(Key::Z), Some(0x1D));
+    assert_eq!(hid_usage(Key::Num1), Some(0x1E));
+    assert_eq!(hid_usage(Key::Num0), Some(0x27));
+    assert_eq!(hid_usage(Key::Enter), Some(0x28));
+    assert_eq!(hid_usage(Key::Semicolon), Some(0x33));
+    assert_eq!(hid_usage(Key::ArrowUp), Some(0x52));
+    
+    // Shifted symbols are never physical keys
+    assert_eq!(hid_usage(Key::Colon), None);
+    assert_eq!(hid_usage(Key::Questionmark), None);
+}
+
+#[test]
+fn test_layout_dependent_keys_keep_their_position() {
+    // The key right of Tab types 'a' on AZERTY and 'q' on QWERTY; either way it is Q's position
+    let q = hid_usage(Key::Q).unwrap();
+    assert_eq!(windows_scancode(q), Some(0x10));
+    assert_eq!(macos_keycode(q), Some(0x0C));
+    assert_eq!(x11_keycode(q), Some(24));
+    
+    // The key left of Enter on the home row, whatever it types
+    let semicolon = hid_usage(Key::Semicolon).unwrap();
+    assert_eq!(windows_scancode(semicolon), Some(0x27));
+    assert_eq!(macos_keycode(semicolon), Some(0x29));
+    assert_eq!(x11_keycode(semicolon), Some(47));
+}
+
+#[test]
+fn test_navigation_keys_are_extended_on_windows() {
+    for key in [Key::ArrowLeft, Key::Home, Key::Delete, Key::PageDown] {
+        let scancode = windows_scancode(hid_usage(key).unwrap()).unwrap();
+        assert_eq!(scancode & 0xFF00, 0xE000, "{:?}", key);
+    }
+    assert_eq!(windows_scancode(hid_usage(Key::A).unwrap()), Some(0x1E));
+}
+
+#[test]
+fn test_every_mapped_key_reaches_every_platform() {
+    let mut usages = Vec::new();
+    for &key in Key::ALL {
+        let Some(usage) = hid_usage(key) else {
+            continue;
+        };
+        assert!(windows_scancode(usage).is_some(), "{:?}", key);
+        assert!(macos_keycode(usage).is_some(), "{:?}", key);
+        assert!(x11_keycode(usage).is_some(), "{:?}", key);
+        assert!(native_keycode(usage).is_some());
+        usages.push(usage);
+    }
+    
+    // No two keys share a position
+    let count = usages.len();
+    usages.sort();
+    usages.dedup();
+    assert_eq!(usages.len(), count);
+    assert_eq!(count, 74);
+}
+
+#[test]
+fn test_keyboard_mode_defaults_to_characters() {
+    assert_eq!(KeyboardMode::default(), KeyboardMode::Characters);
+    
+    let preferences = ClientPreferences { keyboard_mode: KeyboardMode::Scancodes, ..ClientPreferences::default() };
+    let json = serde_json::to_string(&preferences).unwrap();
+    assert_eq!(serde_json::from_str::<ClientPreferences>(&json).unwrap(), preferences);
+    assert_eq!(serde_json::from_str::<ClientPreferences>(r#"{"view_mode":"Fill"}"#).unwrap().keyboard_mode, KeyboardMode::Characters);
+}
+
+#[test]
+fn test_function_keys_reach_enigo() {
+    let keys = [
+        (Key::F1, enigo::Key::F1),
+        (Key::F2, enigo::Key::F2),
+        (Key::F3, enigo::Key::F3),
+        (Key::F4, enigo::Key::F4),
+        (Key::F5, enigo::Key::F5),
+        (Key::F6, enigo::Key::F6),
+        (Key::F7, enigo::Key::F7),
+        (Key::F8, enigo::Key::F8),
+        (Key::F9, enigo::Key::F9),
+        (Key::F10, enigo::Key::F10),
+        (Key::F11, enigo::Key::F11),
+        (Key::F12, enigo::Key::F12),
+    ];
+    for (key, expected) in keys {
+        let name = format_key(key).unwrap();
+        assert_eq!(named_key(&name), Some(expected), "{:?}", key);
+    }
+    
+    // Letters still type themselves, and nothing types nothing
+    assert_eq!(named_key(&format_key(Key::Q).unwrap()), Some(enigo::Key::Unicode('q')));
+    assert_eq!(named_key(""), None);
+    assert_eq!(format_key(Key::Colon), None);
+}
@@ -0,0 +1,73 @@
+mod common;
+
+use std::time::Duration;
+use vox_gui::client::loadtest::{frame_payload_bytes, run_connection, LatencySummary};
+use vox_gui::common::auth::AccessCode;
+use vox_gui::common::protocol::{EncodingType, Message};
+
+fn millis(values: &[u64]) -> Vec<Duration> {
+    values.iter().map(|&ms| Duration::from_millis(ms)).collect()
+}
+
+#[test]
+fn test_latency_percentiles() {
+    let summary = LatencySummary::from_samples(millis(&(1..=100).rev().collect::<Vec<_>>())).unwrap();
+    assert_eq!(summary.count, 100);
+    assert_eq!(summary.p50, Duration::from_millis(50));
+    assert_eq!(summary.p90, Duration::from_millis(90));
+    assert_eq!(summary.p99, Duration::from_millis(99));
+    assert_eq!(summary.max, Duration::from_millis(100));
+    
+    let single = LatencySummary::from_samples(millis(&[7])).unwrap();
+    assert_eq!((single.p50, single.p99, single.max), (Duration::from_millis(7), Duration::from_millis(7), Duration::from_millis(7)));
+    
+    assert!(LatencySummary::from_samples(Vec::new()).is_none());
+}
+
+#[test]
+fn test_only_frames_count_as_payload() {
+    let frame = Message::ScreenFrame {
+        sequence: 0,
+        timestamp: 0,
+        width: 2,
+        height: 2,
+        data: vec![0; 12],
+        encoding: EncodingType::Raw,
+        checksum: None,
+    };
+    assert_eq!(frame_payload_bytes(&frame), Some(12));
+    assert_eq!(frame_payload_bytes(&Message::DeltaFrame { sequence: 1, timestamp: 0, tiles: Vec::new(), checksum: None }), Some(0));
+    assert_eq!(frame_payload_bytes(&Message::Pong { timestamp: 0 }), None);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_sessions_past_the_limit_are_reported() {
+    let access_code = AccessCode::generate();
+    let code = access_code.code.clone();
+    
+    let server = common::test_server(access_code)
+        .with_max_sessions(2);
+    let addr = common::serve(server).await;
+    
+    let sessions = (0..3).map(|_| {
+        let (addr, code) = (addr.clone(), code.clone());
+        tokio::spawn(async move { run_connection(&addr, &code, Duration::from_secs(2), true).await })
+    });
+    let mut reports = Vec::new();
+    for session in sessions.collect::<Vec<_>>() {
+        reports.push(session.await.unwrap());
+    }
+    
+    let served: Vec<_> = reports.iter().filter(|report| report.error.is_none()).collect();
+    assert_eq!(served.len(), 2, "{:?}", reports);
+    for report in served {
+        assert!(report.connect.is_some());
+        assert!(report.first_frame.unwrap() >= report.connect.unwrap());
+        assert!(report.frames > 0);
+        assert!(report.bytes > 0);
+    }
+    
+    let refused = reports.iter().find(|report| report.error.is_some()).unwrap();
+    assert!(refused.connect.is_none());
+    assert!(refused.error.as_deref().unwrap().contains("Authentication failed"));
+}
@@ -0,0 +1,95 @@
+use bytes::Bytes;
+use vox_gui::client::region::RegionPicker;
+use vox_gui::common::foveation::{Foveation, Region, TileQuality};
+use vox_gui::common::frame_processor::TileData;
+use vox_gui::common::protocol::Message;
+
+fn tile(x: u32, y: u32, value: u8) -> TileData {
+    TileData {
+        x,
+        y,
+        width: 64,
+        height: 64,
+        data: Bytes::from(vec![value; 64 * 64 * 3]),
+    }
+}
+
+fn editor() -> Region {
+    Region { x: 600, y: 100, width: 300, height: 200 }
+}
+
+#[test]
+fn test_tiles_in_the_region_stay_lossless_away_from_the_cursor() {
+    let mut foveation = Foveation::new(64, 4);
+    foveation.set_focus(Some((0, 0)));
+    foveation.set_lossless_regions(vec![editor()]);
+    
+    // Inside, straddling the edge, and outside the region
+    let mut tiles = vec![tile(640, 128, 0x5B), tile(576, 64, 0x5B), tile(1280, 640, 0x5B)];
+    foveation.apply(&mut tiles);
+    
+    assert_eq!(foveation.tile_quality(&tiles[0]), TileQuality::Full);
+    assert_eq!(foveation.tile_quality(&tiles[1]), TileQuality::Full);
+    assert_eq!(foveation.tile_quality(&tiles[2]), TileQuality::Reduced);
+    assert!(tiles[0].data.iter().chain(tiles[1].data.iter()).all(|&v| v == 0x5B));
+    assert!(tiles[2].data.iter().all(|&v| v == 0x58));
+}
+
+#[test]
+fn test_reduced_tiles_are_refreshed_once_marked_lossless() {
+    let mut foveation = Foveation::new(64, 4);
+    foveation.set_focus(Some((0, 0)));
+    
+    let mut tiles = vec![tile(640, 128, 1), tile(1280, 640, 1)];
+    foveation.apply(&mut tiles);
+    assert!(foveation.tiles_to_refresh().is_empty());
+    
+    foveation.set_lossless_regions(vec![editor()]);
+    assert_eq!(foveation.tiles_to_refresh(), vec![(640, 128)]);
+    
+    // Clearing the region lets foveation reduce it again
+    foveation.set_lossless_regions(Vec::new());
+    assert_eq!(foveation.tile_quality(&tile(640, 128, 1)), TileQuality::Reduced);
+}
+
+#[test]
+fn test_region_from_corners() {
+    assert_eq!(Region::from_corners((900, 300), (600, 100)), Some(editor()));
+    assert_eq!(Region::from_corners((600, 100), (900, 300)), Some(editor()));
+    assert_eq!(Region::from_corners((600, 100), (600, 300)), None);
+    
+    let region = editor();
+    assert!(region.intersects(899, 299, 1, 1));
+    assert!(!region.intersects(900, 100, 64, 64));
+    assert!(!region.intersects(536, 100, 64, 64));
+    assert!(!region.intersects(u32::MAX - 1, 0, 2, 2));
+}
+
+#[test]
+fn test_picker_marks_the_dragged_rectangle() {
+    let mut picker = RegionPicker::new();
+    picker.press((10, 10));
+    assert!(picker.preview((50, 50)).is_none(), "not armed");
+    
+    picker.arm();
+    picker.press((900, 300));
+    assert_eq!(picker.preview((800, 200)), Region::from_corners((900, 300), (800, 200)));
+    
+    // A click without a drag keeps drawing
+    picker.press((5, 5));
+    assert_eq!(picker.release((5, 5)), None);
+    assert!(picker.is_armed());
+    
+    picker.press((600, 100));
+    assert_eq!(picker.release((900, 300)), Some(editor()));
+    assert!(!picker.is_armed());
+}
+
+#[test]
+fn test_region_message_round_trips() {
+    for rect in [Some(editor()), None] {
+        let message = Message::LosslessRegion { rect };
+        let decoded = Message::deserialize(&message.serialize().unwrap()).unwrap();
+        assert!(matches!(decoded, Message::LosslessRegion { rect: decoded } if decoded == rect));
+    }
+}
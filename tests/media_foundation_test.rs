@@ -0,0 +1,56 @@
+#![cfg(target_os = "windows")]
+
+use vox_gui::common::color::ColorSpace;
+use vox_gui::common::encoder::{EncoderSettings, EncoderTuning, VideoEncoder};
+use vox_gui::platform::windows::media_foundation::{MediaFoundationEncoder, MediaFoundationRuntime};
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 48;
+
+fn settings() -> EncoderSettings {
+    EncoderSettings {
+        width: WIDTH,
+        height: HEIGHT,
+        fps: 30,
+        bitrate: 1_000_000,
+        keyframe_interval: 60,
+        color_space: ColorSpace::for_resolution(WIDTH, HEIGHT),
+        tuning: EncoderTuning::default(),
+    }
+}
+
+fn frame(width: u32, height: u32) -> Vec<u8> {
+    vec![0x80; (width * height * 3) as usize]
+}
+
+// The only test touching Media Foundation in this binary, so the user count is its own
+#[test]
+fn encoders_share_one_media_foundation_startup() {
+    // Needs the Media Foundation H.264 encoder (missing on N editions of Windows)
+    let Ok(first) = MediaFoundationEncoder::new(settings()) else {
+        return;
+    };
+    let mut second = MediaFoundationEncoder::new(settings()).unwrap();
+    let mut third = MediaFoundationEncoder::new(settings()).unwrap();
+    assert_eq!(MediaFoundationRuntime::users(), 3);
+    
+    // Dropping one leaves Media Foundation running for the others
+    drop(first);
+    assert_eq!(MediaFoundationRuntime::users(), 2);
+    second.encode_frame(&frame(WIDTH, HEIGHT), true).unwrap();
+    
+    // Reconfiguring shuts the old transform down without touching the runtime
+    third.update_settings(EncoderSettings { width: 32, height: 32, ..settings() }).unwrap();
+    assert_eq!(MediaFoundationRuntime::users(), 2);
+    third.encode_frame(&frame(32, 32), true).unwrap();
+    assert!(third.encode_frame(&frame(WIDTH, HEIGHT), false).is_err());
+    
+    drop(second);
+    drop(third);
+    assert_eq!(MediaFoundationRuntime::users(), 0);
+    
+    // And it starts up again for the next encoder
+    let mut again = MediaFoundationEncoder::new(settings()).unwrap();
+    assert_eq!(MediaFoundationRuntime::users(), 1);
+    again.encode_frame(&frame(WIDTH, HEIGHT), true).unwrap();
+}
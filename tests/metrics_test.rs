@@ -0,0 +1,99 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use vox_gui::common::metrics::{PerformanceMetrics, SessionStats};
+use vox_gui::common::quality::QualityMode;
+use vox_gui::server::metrics_endpoint;
+
+fn sample_line<'a>(text: &'a str, name: &str) -> Option<&'a str> {
+    text.lines().find(|line| !line.starts_with('#') && line.split(' ').next() == Some(name))
+}
+
+#[test]
+fn prometheus_output_has_counters_and_session_gauges() {
+    let metrics = PerformanceMetrics::new();
+    metrics.frame_captured();
+    metrics.frame_captured();
+    metrics.frame_dropped();
+    metrics.frame_sent();
+    metrics.add_bytes_sent(1500);
+    metrics.add_bytes_received(42);
+    metrics.session_started("a");
+    metrics.session_started("b");
+    metrics.session_frame_sent("a", QualityMode::High, 1000);
+    metrics.session_frame_sent("a", QualityMode::Medium, 500);
+    
+    let text = metrics.to_prometheus();
+    assert_eq!(sample_line(&text, "vox_frames_captured_total"), Some("vox_frames_captured_total 2"));
+    assert_eq!(sample_line(&text, "vox_frames_dropped_total"), Some("vox_frames_dropped_total 1"));
+    assert_eq!(sample_line(&text, "vox_frames_sent_total"), Some("vox_frames_sent_total 1"));
+    assert_eq!(sample_line(&text, "vox_bytes_sent_total"), Some("vox_bytes_sent_total 1500"));
+    assert_eq!(sample_line(&text, "vox_bytes_received_total"), Some("vox_bytes_received_total 42"));
+    assert_eq!(sample_line(&text, "vox_sessions_active"), Some("vox_sessions_active 2"));
+    assert!(text.contains("# TYPE vox_frames_sent_total counter\n"));
+    assert!(text.contains("# TYPE vox_sessions_active gauge\n"));
+    
+    // Session "b" has no frames yet, so no quality to report
+    assert!(text.contains("vox_session_quality{session=\"a\",mode=\"Medium\"} 2\n"));
+    assert!(!text.contains("vox_session_quality{session=\"b\""));
+    assert!(text.contains("vox_session_bytes_sent_total{session=\"a\"} 1500\n"));
+    assert!(text.contains("vox_session_frames_sent_total{session=\"b\"} 0\n"));
+}
+
+#[test]
+fn ended_sessions_disappear_but_stay_counted() {
+    let metrics = PerformanceMetrics::new();
+    metrics.session_started("a");
+    metrics.session_frame_sent("a", QualityMode::Ultra, 10);
+    assert_eq!(
+        metrics.session_stats(),
+        vec![("a".to_string(), SessionStats { quality: Some(QualityMode::Ultra), frames_sent: 1, bytes_sent: 10 })]
+    );
+    
+    metrics.session_ended("a");
+    metrics.session_frame_sent("a", QualityMode::Ultra, 10);
+    assert_eq!(metrics.active_sessions(), 0);
+    
+    let text = metrics.to_prometheus();
+    assert_eq!(sample_line(&text, "vox_sessions_started_total"), Some("vox_sessions_started_total 1"));
+    assert!(!text.contains("session=\"a\""));
+}
+
+async fn get(addr: std::net::SocketAddr, request: &str) -> String {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+#[tokio::test]
+async fn endpoint_serves_metrics_over_http() {
+    let metrics = Arc::new(PerformanceMetrics::new());
+    metrics.session_started("viewer");
+    
+    let listener = metrics_endpoint::bind("127.0.0.1:0", false).await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(metrics_endpoint::serve(listener, metrics.clone()));
+    
+    let response = tokio::time::timeout(
+        Duration::from_secs(5),
+        get(addr, "GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n"),
+    ).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("Content-Type: text/plain; version=0.0.4"));
+    assert!(response.contains("\r\n\r\n# HELP "));
+    assert!(response.contains("\nvox_sessions_active 1\n"));
+    
+    let missing = get(addr, "GET /other HTTP/1.1\r\n\r\n").await;
+    assert!(missing.starts_with("HTTP/1.1 404"));
+    let post = get(addr, "POST /metrics HTTP/1.1\r\n\r\n").await;
+    assert!(post.starts_with("HTTP/1.1 405"));
+}
+
+#[tokio::test]
+async fn endpoint_refuses_non_loopback_addresses_by_default() {
+    assert!(metrics_endpoint::bind("0.0.0.0:0", false).await.is_err());
+    assert!(metrics_endpoint::bind("0.0.0.0:0", true).await.is_ok());
+}
@@ -0,0 +1,83 @@
+use egui::Modifiers as EguiModifiers;
+use vox_gui::client::modifiers::ModifierTracker;
+use vox_gui::common::protocol::Message;
+
+fn key_events(messages: &[Message]) -> Vec<(String, bool)> {
+    messages
+        .iter()
+        .map(|message| match message {
+            Message::KeyEvent { key, pressed, .. } => (key.clone(), *pressed),
+            other => panic!("unexpected message {:?}", other),
+        })
+        .collect()
+}
+
+#[test]
+fn test_standalone_shift_press_is_transmitted() {
+    let mut tracker = ModifierTracker::new();
+    
+    let pressed = tracker.update(EguiModifiers::SHIFT);
+    assert_eq!(key_events(&pressed), vec![("Shift".to_string(), true)]);
+    assert!(tracker.held().shift);
+    
+    // The press survives the trip to the server
+    let decoded = Message::deserialize(&pressed[0].serialize().unwrap()).unwrap();
+    match decoded {
+        Message::KeyEvent { key, pressed, modifiers, .. } => {
+            assert_eq!(key, "Shift");
+            assert!(pressed);
+            assert!(modifiers.shift);
+        }
+        other => panic!("unexpected message {:?}", other),
+    }
+    
+    // Holding it sends nothing more
+    assert!(tracker.update(EguiModifiers::SHIFT).is_empty());
+    
+    let released = tracker.update(EguiModifiers::NONE);
+    assert_eq!(key_events(&released), vec![("Shift".to_string(), false)]);
+    assert!(!tracker.held().shift);
+}
+
+#[test]
+fn test_switching_modifiers_releases_before_pressing() {
+    let mut tracker = ModifierTracker::new();
+    tracker.update(EguiModifiers::SHIFT);
+    
+    let events = key_events(&tracker.update(EguiModifiers::CTRL | EguiModifiers::ALT));
+    assert_eq!(
+        events,
+        vec![
+            ("Shift".to_string(), false),
+            ("Control".to_string(), true),
+            ("Alt".to_string(), true),
+        ]
+    );
+}
+
+#[test]
+fn test_release_all_lets_go_of_everything_held() {
+    let mut tracker = ModifierTracker::new();
+    tracker.update(EguiModifiers::SHIFT | EguiModifiers::ALT);
+    
+    let mut events = key_events(&tracker.release_all());
+    events.sort();
+    assert_eq!(events, vec![("Alt".to_string(), false), ("Shift".to_string(), false)]);
+    assert!(tracker.release_all().is_empty());
+}
+
+#[test]
+fn test_mac_command_maps_to_meta() {
+    let mut tracker = ModifierTracker::new();
+    
+    assert_eq!(key_events(&tracker.update(EguiModifiers::MAC_CMD)), vec![("Meta".to_string(), true)]);
+}
+
+#[test]
+fn test_command_alongside_ctrl_is_not_a_second_key() {
+    // Off macOS egui sets command alongside ctrl; only Control goes to the remote side
+    let mut tracker = ModifierTracker::new();
+    let ctrl = EguiModifiers { ctrl: true, command: true, ..EguiModifiers::NONE };
+    
+    assert_eq!(key_events(&tracker.update(ctrl)), vec![("Control".to_string(), true)]);
+}
@@ -0,0 +1,40 @@
+use egui::PointerButton;
+use vox_gui::client::view::remote_button;
+use vox_gui::common::protocol::{Message, MouseButton};
+
+fn click(button: MouseButton) -> Vec<u8> {
+    Message::MouseClick { button, pressed: true, x: 10, y: 20 }.serialize().unwrap()
+}
+
+#[test]
+fn existing_buttons_keep_their_wire_encoding() {
+    // Variant indices are what bincode puts on the wire; new buttons must not shift these
+    assert_eq!(bincode::serialize(&MouseButton::Left).unwrap(), 0u32.to_le_bytes());
+    assert_eq!(bincode::serialize(&MouseButton::Right).unwrap(), 1u32.to_le_bytes());
+    assert_eq!(bincode::serialize(&MouseButton::Middle).unwrap(), 2u32.to_le_bytes());
+    assert_eq!(bincode::serialize(&MouseButton::Back).unwrap(), 3u32.to_le_bytes());
+    assert_eq!(bincode::serialize(&MouseButton::Forward).unwrap(), 4u32.to_le_bytes());
+}
+
+#[test]
+fn extra_buttons_round_trip() {
+    for button in [MouseButton::Back, MouseButton::Forward] {
+        match Message::deserialize(&click(button)).unwrap() {
+            Message::MouseClick { button: decoded, pressed, x, y } => {
+                assert_eq!(format!("{:?}", decoded), format!("{:?}", button));
+                assert!(pressed);
+                assert_eq!((x, y), (10, 20));
+            }
+            other => panic!("unexpected message {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn side_buttons_map_to_back_and_forward() {
+    assert!(matches!(remote_button(PointerButton::Primary), MouseButton::Left));
+    assert!(matches!(remote_button(PointerButton::Secondary), MouseButton::Right));
+    assert!(matches!(remote_button(PointerButton::Middle), MouseButton::Middle));
+    assert!(matches!(remote_button(PointerButton::Extra1), MouseButton::Back));
+    assert!(matches!(remote_button(PointerButton::Extra2), MouseButton::Forward));
+}
@@ -0,0 +1,38 @@
+use std::time::{Duration, Instant};
+use vox_gui::client::mouse_throttle::MouseMoveThrottle;
+
+#[test]
+fn test_burst_is_coalesced() {
+    let mut throttle = MouseMoveThrottle::new(120);
+    let start = Instant::now();
+    let mut sent = Vec::new();
+    
+    // 1000 moves over 100ms, i.e. a 10kHz burst
+    for i in 0..1000 {
+        let now = start + Duration::from_micros(i * 100);
+        if let Some(position) = throttle.move_to(i as i32, i as i32, now) {
+            sent.push(position);
+        }
+    }
+    
+    // 120Hz over 100ms allows at most 13 moves
+    assert!(sent.len() <= 13, "sent {} moves", sent.len());
+    assert!(sent.len() >= 10, "sent {} moves", sent.len());
+    
+    // The last position isn't lost, it goes out on the next tick
+    let flushed = throttle.poll(start + Duration::from_millis(200));
+    assert_eq!(flushed, Some((999, 999)));
+    assert_eq!(throttle.poll(start + Duration::from_millis(300)), None);
+}
+
+#[test]
+fn test_first_move_is_sent_immediately() {
+    let mut throttle = MouseMoveThrottle::new(120);
+    let now = Instant::now();
+    
+    assert_eq!(throttle.move_to(10, 20, now), Some((10, 20)));
+    assert_eq!(throttle.move_to(11, 21, now), None);
+    
+    throttle.cancel_pending();
+    assert_eq!(throttle.poll(now + Duration::from_secs(1)), None);
+}
@@ -0,0 +1,33 @@
+use std::io;
+use vox_gui::server::frame_source::{check_displays, CaptureBackend, NO_DISPLAY_HINT};
+
+#[test]
+fn test_enumeration_failure_reports_no_display() {
+    let error = check_displays(CaptureBackend::Scrap, || {
+        Err(io::Error::new(io::ErrorKind::ConnectionRefused, "cannot open display"))
+    })
+    .unwrap_err();
+    
+    assert_eq!(error.to_string(), NO_DISPLAY_HINT);
+    assert!(format!("{:#}", error).contains("cannot open display"));
+}
+
+#[test]
+fn test_zero_displays_reports_no_display() {
+    let error = check_displays(CaptureBackend::Scrap, || Ok(0)).unwrap_err();
+    assert_eq!(error.to_string(), NO_DISPLAY_HINT);
+    assert!(NO_DISPLAY_HINT.contains("--test-pattern"));
+}
+
+#[test]
+fn test_a_display_passes() {
+    assert!(check_displays(CaptureBackend::Scrap, || Ok(1)).is_ok());
+    assert!(check_displays(CaptureBackend::Scrap, || Ok(3)).is_ok());
+}
+
+#[test]
+fn test_pipewire_is_not_enumerated() {
+    // The portal would prompt the user, so PipeWire is left to report its own errors
+    let checked = check_displays(CaptureBackend::PipeWire, || panic!("enumerated displays"));
+    assert!(checked.is_ok());
+}
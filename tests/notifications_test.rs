@@ -0,0 +1,169 @@
+mod common;
+
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant};
+use vox_gui::client::connection::Connection;
+use vox_gui::client::toasts::{Toasts, MAX_TOASTS, TOAST_DURATION};
+use vox_gui::common::auth::AccessCode;
+use vox_gui::common::protocol::Message;
+use vox_gui::server::notifications::{
+    DbusMonitorParser, HostNotification, NotificationLimiter, NotificationSource, NotificationSourceFactory,
+    MAX_NOTIFICATION_CHARS, NOTIFICATION_BURST, NOTIFICATION_INTERVAL,
+};
+
+const DBUS_MONITOR_OUTPUT: &str = r#"signal time=1697040000.000001 sender=org.freedesktop.DBus -> destination=:1.90 serial=2 path=/org/freedesktop/DBus; interface=org.freedesktop.DBus; member=NameAcquired
+   string ":1.90"
+method call time=1697040001.000002 sender=:1.91 -> destination=:1.30 serial=7 path=/org/freedesktop/Notifications; interface=org.freedesktop.Notifications; member=Notify
+   string "Thunderbird"
+   uint32 0
+   string "mail-unread"
+   string "New message"
+   string "From: Alice
+Subject: Lunch?"
+   array [
+   ]
+   array [
+      dict entry(
+         string "urgency"
+         variant             byte 1
+      )
+   ]
+   int32 -1
+method call time=1697040002.000003 sender=:1.91 -> destination=:1.30 serial=8 path=/org/freedesktop/Notifications; interface=org.freedesktop.Notifications; member=GetServerInformation
+method call time=1697040003.000004 sender=:1.92 -> destination=:1.30 serial=9 path=/org/freedesktop/Notifications; interface=org.freedesktop.Notifications; member=Notify
+   string "notify-send"
+   uint32 0
+   string ""
+   string "Build finished"
+   string ""
+   array [
+   ]
+"#;
+
+#[test]
+fn test_dbus_monitor_output_is_parsed_into_notifications() {
+    let mut parser = DbusMonitorParser::new();
+    let parsed: Vec<HostNotification> = DBUS_MONITOR_OUTPUT.lines().filter_map(|line| parser.line(line)).collect();
+    
+    assert_eq!(parsed, vec![
+        HostNotification {
+            app: "Thunderbird".to_string(),
+            title: "New message".to_string(),
+            body: "From: Alice\nSubject: Lunch?".to_string(),
+        },
+        HostNotification {
+            app: "notify-send".to_string(),
+            title: "Build finished".to_string(),
+            body: String::new(),
+        },
+    ]);
+}
+
+#[test]
+fn test_limiter_lets_a_burst_through_then_slows_down() {
+    let start = Instant::now();
+    let mut limiter = NotificationLimiter::new(start);
+    
+    // A storm: only the burst gets through
+    let allowed = (0..50).filter(|_| limiter.allow(start)).count();
+    assert_eq!(allowed, NOTIFICATION_BURST as usize);
+    assert_eq!(limiter.suppressed(), 50 - NOTIFICATION_BURST as u64);
+    
+    // Then one per interval
+    assert!(!limiter.allow(start + NOTIFICATION_INTERVAL / 2));
+    assert!(limiter.allow(start + NOTIFICATION_INTERVAL));
+    assert!(!limiter.allow(start + NOTIFICATION_INTERVAL));
+    
+    // A quiet spell refills the burst, but no further
+    let later = start + NOTIFICATION_INTERVAL * 100;
+    assert_eq!((0..50).filter(|_| limiter.allow(later)).count(), NOTIFICATION_BURST as usize);
+}
+
+#[test]
+fn test_long_notifications_are_cut() {
+    let notification = HostNotification {
+        app: "app".to_string(),
+        title: "é".repeat(MAX_NOTIFICATION_CHARS + 10),
+        body: "short".to_string(),
+    }
+    .truncated();
+    
+    assert_eq!(notification.title.chars().count(), MAX_NOTIFICATION_CHARS + 1);
+    assert!(notification.title.ends_with('…'));
+    assert_eq!(notification.body, "short");
+}
+
+#[test]
+fn test_toasts_expire_and_never_pile_up() {
+    let start = Instant::now();
+    let mut toasts = Toasts::new();
+    for i in 0..MAX_TOASTS + 2 {
+        toasts.push(format!("toast {}", i), String::new(), String::new(), start);
+    }
+    
+    // The newest stay up
+    assert_eq!(toasts.len(), MAX_TOASTS);
+    assert_eq!(toasts.overflowed(), 2);
+    assert_eq!(toasts.iter().next().unwrap().title, "toast 2");
+    
+    let newest = toasts.iter().last().unwrap().id;
+    toasts.dismiss(newest);
+    assert_eq!(toasts.len(), MAX_TOASTS - 1);
+    
+    toasts.expire(start + TOAST_DURATION - Duration::from_millis(1));
+    assert_eq!(toasts.len(), MAX_TOASTS - 1);
+    toasts.expire(start + TOAST_DURATION);
+    assert!(toasts.is_empty());
+}
+
+// Notifications fed by the test instead of the host's desktop
+struct ChannelSource(mpsc::Receiver<HostNotification>);
+
+impl NotificationSource for ChannelSource {
+    fn next(&mut self) -> anyhow::Result<Option<HostNotification>> {
+        Ok(self.0.recv().ok())
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_server_forwards_host_notifications() {
+    let access_code = AccessCode::generate();
+    let code = access_code.code.clone();
+    
+    let (notify, receiver) = mpsc::channel();
+    let receiver = Arc::new(Mutex::new(Some(receiver)));
+    let sources: NotificationSourceFactory = Arc::new(move || {
+        let receiver = receiver.lock().unwrap().take().expect("source opened once");
+        Ok(Box::new(ChannelSource(receiver)) as Box<dyn NotificationSource>)
+    });
+    
+    let server = common::test_server(access_code)
+        .with_notifications(sources);
+    let control = server.control();
+    let addr = common::serve(server).await;
+    
+    let (mut connection, _, _) = Connection::new();
+    let (mut rx, _tx) = connection.connect(&addr, &code).await.unwrap();
+    while control.sessions().await.is_empty() {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    
+    notify.send(HostNotification {
+        app: "notify-send".to_string(),
+        title: "Backup done".to_string(),
+        body: "42 files".to_string(),
+    }).unwrap();
+    
+    let forwarded = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match rx.recv().await {
+                Some(Message::Notification { title, body, app }) => return (title, body, app),
+                Some(_) => {}
+                None => panic!("connection closed"),
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(forwarded, ("Backup done".to_string(), "42 files".to_string(), "notify-send".to_string()));
+}
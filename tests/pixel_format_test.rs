@@ -0,0 +1,159 @@
+mod common;
+
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use vox_gui::client::connection::Connection;
+use vox_gui::common::auth::AccessCode;
+use vox_gui::common::encoder::EncoderTuning;
+use vox_gui::common::protocol::Message;
+use vox_gui::server::frame_source::{copy_unpadded_rows, FrameSource, FrameSourceFactory};
+use vox_gui::server::pixel_format::{encode_srgb, half_to_f32, to_rgb, tone_map, PixelFormat};
+use vox_gui::server::screen_capture::{ScreenCapture, DEFAULT_BITRATE};
+
+// Pack 10-bit channels the way DXGI's R10G10B10A2 desktop format does
+fn pixel_10bit(r: u32, g: u32, b: u32) -> [u8; 4] {
+    ((0b11 << 30) | (r << 20) | (g << 10) | b).to_le_bytes()
+}
+
+fn pixel_half(r: u16, g: u16, b: u16) -> Vec<u8> {
+    [r, g, b, 0x3c00].iter().flat_map(|c| c.to_le_bytes()).collect()
+}
+
+const HALF_ONE: u16 = 0x3c00;
+const HALF_FOUR: u16 = 0x4400;
+const HALF_MINUS_HALF: u16 = 0xb800;
+
+#[test]
+fn test_10bit_input_keeps_its_colours() {
+    let frame: Vec<u8> = [
+        pixel_10bit(1023, 0, 0),
+        pixel_10bit(0, 1023, 0),
+        pixel_10bit(0, 0, 1023),
+        pixel_10bit(512, 256, 4),
+    ]
+    .concat();
+    
+    let rgb = to_rgb(&frame, 2, 2, PixelFormat::Bgr10A2).unwrap();
+    assert_eq!(rgb, vec![255, 0, 0, 0, 255, 0, 0, 0, 255, 128, 64, 1]);
+    
+    // Read as 8-bit BGRA, the same pixels come out as noise
+    assert_ne!(to_rgb(&frame, 2, 2, PixelFormat::Bgra8).unwrap(), rgb);
+}
+
+#[test]
+fn test_hdr_highlights_are_tone_mapped_instead_of_clipped() {
+    assert_eq!(half_to_f32(HALF_ONE), 1.0);
+    assert_eq!(half_to_f32(HALF_FOUR), 4.0);
+    assert_eq!(half_to_f32(HALF_MINUS_HALF), -0.5);
+    
+    // SDR range is left alone, brighter values stay below white and keep their order
+    assert_eq!(tone_map(0.5), 0.5);
+    assert!(tone_map(1.0) < tone_map(2.0));
+    assert!(tone_map(2.0) < 1.0);
+    assert_eq!(tone_map(f32::INFINITY), 1.0);
+    assert_eq!(tone_map(-0.5), 0.0);
+    assert_eq!(tone_map(f32::NAN), 0.0);
+    
+    assert_eq!(encode_srgb(0.0), 0);
+    assert_eq!(encode_srgb(1.0), 255);
+    assert_eq!(encode_srgb(0.216), 128);
+    
+    let frame = [pixel_half(HALF_ONE, 0, HALF_MINUS_HALF), pixel_half(HALF_FOUR, HALF_FOUR, HALF_FOUR)].concat();
+    let rgb = to_rgb(&frame, 2, 1, PixelFormat::Rgba16F).unwrap();
+    assert_eq!(&rgb[1..3], &[0, 0]);
+    assert!(rgb[0] > 240 && rgb[0] < rgb[3], "{:?}", rgb);
+    assert!(rgb[3] == rgb[4] && rgb[4] == rgb[5]);
+}
+
+#[test]
+fn test_format_is_detected_from_the_frame_size() {
+    assert_eq!(PixelFormat::detect(PixelFormat::Bgra8, 64 * 4, 8, 8).unwrap(), PixelFormat::Bgra8);
+    assert_eq!(PixelFormat::detect(PixelFormat::Bgr10A2, 64 * 4, 8, 8).unwrap(), PixelFormat::Bgr10A2);
+    assert_eq!(PixelFormat::detect(PixelFormat::Bgra8, 64 * 8, 8, 8).unwrap(), PixelFormat::Rgba16F);
+    
+    let error = PixelFormat::detect(PixelFormat::Bgra8, 64 * 6, 8, 8).unwrap_err().to_string();
+    assert!(error.contains("Unsupported pixel format"), "{}", error);
+    
+    assert!(to_rgb(&[0; 12], 2, 2, PixelFormat::Bgr10A2).is_err());
+}
+
+// 48-bit RGB, which no converter handles
+struct DeepSource;
+
+impl FrameSource for DeepSource {
+    fn frame(&mut self) -> io::Result<Vec<u8>> {
+        Ok(vec![0; 16 * 16 * 6])
+    }
+    
+    fn width(&self) -> usize {
+        16
+    }
+    
+    fn height(&self) -> usize {
+        16
+    }
+}
+
+#[test]
+fn test_capture_refuses_frames_it_cannot_convert() {
+    let mut capture = ScreenCapture::from_source(Box::new(DeepSource), 30, DEFAULT_BITRATE, EncoderTuning::default()).unwrap();
+    // Paced at 30 fps, so poll like the capture thread does
+    let error = loop {
+        match capture.capture_frame() {
+            Ok(None) => std::thread::sleep(std::time::Duration::from_millis(5)),
+            Ok(Some(_)) => panic!("a 6-byte pixel format was streamed"),
+            Err(e) => break e.to_string(),
+        }
+    };
+    assert!(error.contains("Unsupported pixel format"), "{}", error);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_clients_are_told_their_display_cannot_be_converted() {
+    let access_code = AccessCode::generate();
+    let code = access_code.code.clone();
+    let deep: FrameSourceFactory = Arc::new(|_| Ok(Box::new(DeepSource) as Box<dyn FrameSource>));
+    let server = common::test_server(access_code).with_frame_sources(deep);
+    let addr = common::serve(server).await;
+    
+    let (mut connection, _, _) = Connection::new();
+    let (mut rx, tx) = connection.connect(&addr, &code).await.unwrap();
+    tx.send(Message::StartStream).unwrap();
+    
+    let error = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match rx.recv().await {
+                Some(Message::Error { message, .. }) => return message,
+                Some(_) => continue,
+                None => panic!("connection closed without an error"),
+            }
+        }
+    })
+    .await
+    .expect("no error reached the client");
+    assert!(error.contains("Unsupported pixel format"), "{}", error);
+}
+
+#[test]
+fn test_padded_scrap_rows_are_packed_before_detection() {
+    // Two 3-pixel rows, each padded to a 16-byte pitch
+    let mut padded = Vec::new();
+    for shade in [1u8, 2] {
+        padded.extend_from_slice(&[shade; 12]);
+        padded.extend_from_slice(&[0xee; 4]);
+    }
+    
+    let mut frame = Vec::new();
+    copy_unpadded_rows(&padded, 3, 2, &mut frame).unwrap();
+    assert_eq!(frame, [[1u8; 12], [2u8; 12]].concat());
+    assert_eq!(PixelFormat::detect(PixelFormat::Bgra8, frame.len(), 3, 2).unwrap(), PixelFormat::Bgra8);
+    
+    // Packed frames, HDR ones included, come through whole
+    let hdr = vec![7u8; 3 * 2 * 8];
+    copy_unpadded_rows(&hdr, 3, 2, &mut frame).unwrap();
+    assert_eq!(frame, hdr);
+    
+    // Rows shorter than the width can't be a frame of it
+    assert!(copy_unpadded_rows(&[0u8; 20], 3, 2, &mut frame).is_err());
+}
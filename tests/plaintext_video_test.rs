@@ -0,0 +1,120 @@
+mod common;
+
+use std::time::Duration;
+use vox_gui::client::connection::Connection;
+use vox_gui::common::auth::AccessCode;
+use vox_gui::common::crypto::{is_corrupt, open_message, seal_message, seal_video, CryptoSession, Encryptor, KeyExchange, PlaintextVideo};
+use vox_gui::common::protocol::{EncodingType, Message};
+
+// The server's side, which sends video, and the client's, which receives it
+fn plaintext_video_pair() -> (PlaintextVideo, PlaintextVideo) {
+    let client = KeyExchange::new();
+    let server = KeyExchange::new();
+    let client_public = client.public_key().clone();
+    let server_public = server.public_key().clone();
+    
+    let client_crypto = CryptoSession::from_shared_secret(&client.compute_shared_secret(&server_public)).unwrap();
+    let server_crypto = CryptoSession::from_shared_secret(&server.compute_shared_secret(&client_public)).unwrap();
+    (PlaintextVideo::sender(Box::new(server_crypto)), PlaintextVideo::receiver(Box::new(client_crypto)))
+}
+
+fn screen_frame() -> Message {
+    Message::ScreenFrame {
+        sequence: 1,
+        timestamp: 2,
+        width: 4,
+        height: 4,
+        data: b"visible pixels".to_vec(),
+        encoding: EncodingType::Raw,
+        checksum: None,
+    }
+}
+
+async fn start_server(plaintext_video: bool) -> (String, String) {
+    let access_code = AccessCode::generate();
+    let code = access_code.code.clone();
+    
+    let mut server = common::test_server(access_code);
+    if plaintext_video {
+        server = server.with_plaintext_video();
+    }
+    let addr = common::serve(server).await;
+    
+    (addr, code)
+}
+
+async fn receives_frames(connection: &mut Connection, addr: &str, code: &str) {
+    let (mut rx, tx) = connection.connect(addr, code).await.unwrap();
+    tx.send(Message::StartStream).unwrap();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match rx.recv().await {
+                Some(message) if message.is_video() => return,
+                Some(_) => {}
+                None => panic!("connection closed"),
+            }
+        }
+    })
+    .await
+    .unwrap();
+}
+
+#[test]
+fn test_only_video_is_sent_in_the_clear() {
+    let (server, client) = plaintext_video_pair();
+    
+    let video = seal_video(&server, &screen_frame().serialize().unwrap()).unwrap();
+    assert!(video[0].windows(14).any(|w| w == b"visible pixels"));
+    assert!(matches!(open_message(&client, &video[0]).unwrap(), Some(Message::ScreenFrame { .. })));
+    
+    // Control messages stay sealed, in both directions
+    let control = seal_message(&server, &Message::AuthResponse { success: true, session_token: Some("secret".into()) }.serialize().unwrap()).unwrap();
+    assert!(!control[0].windows(6).any(|w| w == b"secret"));
+    assert!(matches!(open_message(&client, &control[0]).unwrap(), Some(Message::AuthResponse { .. })));
+    
+    let input = seal_message(&client, &Message::KeyEvent { key: "a".into(), pressed: true, repeat: false, modifiers: Default::default() }.serialize().unwrap()).unwrap();
+    assert!(matches!(open_message(&server, &input[0]).unwrap(), Some(Message::KeyEvent { .. })));
+}
+
+#[test]
+fn test_injected_plaintext_is_refused() {
+    let (server, client) = plaintext_video_pair();
+    
+    // Someone on the path can't pass off control messages as cleartext video...
+    let forged = client.encrypt_video(&Message::Disconnect.serialize().unwrap()).unwrap();
+    let error = open_message(&client, &forged).unwrap_err();
+    assert!(is_corrupt(&error));
+    
+    // ...nor send the server anything unencrypted
+    let forged = server.encrypt_video(&Message::MouseMove { x: 1, y: 1 }.serialize().unwrap()).unwrap();
+    assert!(is_corrupt(&server.decrypt(&forged).unwrap_err()));
+    let forged = server.encrypt_video(&screen_frame().serialize().unwrap()).unwrap();
+    assert!(server.decrypt(&forged).is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_plaintext_video_is_negotiated_after_authenticating() {
+    let (addr, code) = start_server(true).await;
+    
+    let (mut connection, _, _) = Connection::new();
+    connection.set_plaintext_video(true);
+    receives_frames(&mut connection, &addr, &code).await;
+    let info = connection.security_info().unwrap();
+    assert!(info.encrypted);
+    assert!(info.plaintext_video);
+    
+    // Clients that don't ask keep encrypted video on the same server
+    let (mut connection, _, _) = Connection::new();
+    receives_frames(&mut connection, &addr, &code).await;
+    assert!(!connection.security_info().unwrap().plaintext_video);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_server_without_the_option_refuses() {
+    let (addr, code) = start_server(false).await;
+    
+    let (mut connection, _, _) = Connection::new();
+    connection.set_plaintext_video(true);
+    receives_frames(&mut connection, &addr, &code).await;
+    assert!(!connection.security_info().unwrap().plaintext_video);
+}
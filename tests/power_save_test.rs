@@ -0,0 +1,167 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use vox_gui::common::adaptive_fps::{AdaptiveFps, MIN_FPS, POWER_SAVE_MIN_FPS};
+use vox_gui::common::encoder::EncoderTuning;
+use vox_gui::server::frame_source::FrameSource;
+use vox_gui::server::power::{parse_pmset, power_supply_source, PowerSaveMode, PowerSource, POWER_SAVE_KEYFRAME_FACTOR};
+use vox_gui::server::screen_capture::{ScreenCapture, DEFAULT_BITRATE};
+
+// The same gray frame every time, counting how often it is read
+struct StaticSource {
+    reads: Arc<AtomicUsize>,
+}
+
+impl FrameSource for StaticSource {
+    fn frame(&mut self) -> io::Result<Vec<u8>> {
+        self.reads.fetch_add(1, Ordering::SeqCst);
+        Ok(vec![128; 64 * 64 * 4])
+    }
+    
+    fn width(&self) -> usize {
+        64
+    }
+    
+    fn height(&self) -> usize {
+        64
+    }
+}
+
+// Frames read from a static screen over `measured`, once capture has settled for `warmup`,
+// polling like the capture thread
+fn frames_read(power_save: bool, warmup: Duration, measured: Duration) -> usize {
+    let reads = Arc::new(AtomicUsize::new(0));
+    let source = Box::new(StaticSource { reads: reads.clone() });
+    let mut capture = ScreenCapture::from_source(source, 30, DEFAULT_BITRATE, EncoderTuning::default()).unwrap();
+    capture.set_power_save(power_save);
+    
+    let start = Instant::now();
+    let mut settled = None;
+    while start.elapsed() < warmup + measured {
+        if settled.is_none() && start.elapsed() >= warmup {
+            settled = Some(reads.load(Ordering::SeqCst));
+        }
+        capture.capture_frame().unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    reads.load(Ordering::SeqCst) - settled.unwrap_or(0)
+}
+
+#[test]
+fn test_power_save_lowers_the_frame_rate_on_a_static_screen() {
+    let (warmup, measured) = (Duration::from_secs(1), Duration::from_secs(2));
+    let normal = std::thread::spawn(move || frames_read(false, warmup, measured));
+    let saving = frames_read(true, warmup, measured);
+    let normal = normal.join().unwrap();
+    assert!(saving * 2 <= normal, "{} frames saving power, {} without", saving, normal);
+    // About POWER_SAVE_MIN_FPS by now
+    assert!(saving <= 4, "{} frames in {:?}", saving, measured);
+}
+
+#[test]
+fn test_power_save_pacer_drops_further_and_sooner() {
+    let mut normal = AdaptiveFps::new(30);
+    let mut saving = AdaptiveFps::new(30);
+    saving.set_power_save(true);
+    for _ in 0..10 {
+        normal.record_frame(0, 100);
+        saving.record_frame(0, 100);
+    }
+    assert!(saving.current_fps() < normal.current_fps());
+    
+    for _ in 0..100 {
+        normal.record_frame(0, 100);
+        saving.record_frame(0, 100);
+    }
+    assert!((normal.current_fps() - MIN_FPS as f32).abs() < 0.1);
+    assert!((saving.current_fps() - POWER_SAVE_MIN_FPS as f32).abs() < 0.1);
+    
+    // A blinking caret wakes the normal pacer but not a power-saving one
+    for _ in 0..5 {
+        normal.record_frame(2, 100);
+        saving.record_frame(2, 100);
+    }
+    assert!(normal.current_fps() > 25.0);
+    assert!(saving.current_fps() < 15.0);
+    
+    // Real activity still gets the full rate
+    for _ in 0..10 {
+        saving.record_full_change();
+    }
+    assert!(saving.current_fps() > 29.0);
+}
+
+#[test]
+fn test_power_save_sends_fewer_keyframes() {
+    let source = Box::new(StaticSource { reads: Arc::new(AtomicUsize::new(0)) });
+    let mut capture = ScreenCapture::from_source(source, 30, DEFAULT_BITRATE, EncoderTuning::default()).unwrap();
+    let before = capture.encoder_settings();
+    let run = capture.max_delta_run();
+    
+    capture.set_power_save(true);
+    let settings = capture.encoder_settings();
+    assert_eq!(settings.keyframe_interval, before.keyframe_interval * POWER_SAVE_KEYFRAME_FACTOR);
+    assert!(settings.tuning.queue_depth >= 1);
+    assert_eq!(capture.max_delta_run(), run * POWER_SAVE_KEYFRAME_FACTOR);
+    
+    capture.set_power_save(false);
+    assert_eq!(capture.encoder_settings(), before);
+    assert_eq!(capture.max_delta_run(), run);
+}
+
+#[test]
+fn test_power_save_modes() {
+    assert!(PowerSaveMode::Auto.active(PowerSource::Battery));
+    assert!(!PowerSaveMode::Auto.active(PowerSource::Ac));
+    assert!(!PowerSaveMode::Auto.active(PowerSource::Unknown));
+    assert!(PowerSaveMode::On.active(PowerSource::Ac));
+    assert!(!PowerSaveMode::Off.active(PowerSource::Battery));
+    assert!(PowerSaveMode::On.is_active());
+    assert!(!PowerSaveMode::Off.is_active());
+}
+
+#[test]
+fn test_pmset_output() {
+    let battery = "Now drawing from 'Battery Power'\n -InternalBattery-0 (id=1234)\t85%; discharging; 5:12 remaining present: true\n";
+    assert_eq!(parse_pmset(battery), PowerSource::Battery);
+    assert_eq!(parse_pmset("Now drawing from 'AC Power'\n"), PowerSource::Ac);
+    assert_eq!(parse_pmset(""), PowerSource::Unknown);
+}
+
+fn power_supply(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("vox_power_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    for (supply, contents) in files {
+        let (supply, file) = supply.split_once('/').unwrap();
+        fs::create_dir_all(dir.join(supply)).unwrap();
+        fs::write(dir.join(supply).join(file), format!("{}\n", contents)).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn test_linux_power_supply() {
+    let on_battery = power_supply("battery", &[
+        ("AC/type", "Mains"), ("AC/online", "0"),
+        ("BAT0/type", "Battery"), ("BAT0/status", "Discharging"),
+    ]);
+    assert_eq!(power_supply_source(&on_battery), PowerSource::Battery);
+    
+    let plugged_in = power_supply("ac", &[
+        ("AC/type", "Mains"), ("AC/online", "1"),
+        ("BAT0/type", "Battery"), ("BAT0/status", "Charging"),
+    ]);
+    assert_eq!(power_supply_source(&plugged_in), PowerSource::Ac);
+    
+    // Desktops have no battery to run from
+    let desktop = power_supply("desktop", &[("AC/type", "Mains"), ("AC/online", "0")]);
+    assert_eq!(power_supply_source(&desktop), PowerSource::Unknown);
+    assert_eq!(power_supply_source(&desktop.join("missing")), PowerSource::Unknown);
+    
+    for dir in [on_battery, plugged_in, desktop] {
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
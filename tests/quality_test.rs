@@ -0,0 +1,181 @@
+use std::time::{Duration, Instant};
+use vox_gui::common::color::ColorSpace;
+use vox_gui::common::encoder::{EncoderSettings, EncoderTuning};
+use vox_gui::common::protocol::Message;
+use vox_gui::common::quality::{dominant_factor, BandwidthMonitor, ContentProfile, QualityChangeReason, QualityMode, BANDWIDTH_WINDOW};
+use vox_gui::server::frame_source::TestPatternSource;
+use vox_gui::server::screen_capture::{ScreenCapture, DEFAULT_BITRATE};
+
+#[test]
+fn test_dominant_factor_bandwidth() {
+    let reason = dominant_factor(2.0, Duration::from_millis(20), 0.0);
+    assert_eq!(reason, QualityChangeReason::LowBandwidth);
+}
+
+#[test]
+fn test_dominant_factor_latency() {
+    let reason = dominant_factor(50.0, Duration::from_millis(300), 0.0);
+    assert_eq!(reason, QualityChangeReason::HighLatency);
+}
+
+#[test]
+fn test_dominant_factor_packet_loss() {
+    let reason = dominant_factor(50.0, Duration::from_millis(10), 15.0);
+    assert_eq!(reason, QualityChangeReason::PacketLoss);
+}
+
+#[test]
+fn test_quality_change_round_trip() {
+    let msg = Message::QualityChange {
+        mode: QualityMode::Low,
+        reason: QualityChangeReason::PacketLoss,
+    };
+    
+    match Message::deserialize(&msg.serialize().unwrap()).unwrap() {
+        Message::QualityChange { mode, reason } => {
+            assert_eq!(mode, QualityMode::Low);
+            assert_eq!(reason, QualityChangeReason::PacketLoss);
+        }
+        other => panic!("unexpected message {:?}", other),
+    }
+}
+
+fn base_settings() -> EncoderSettings {
+    EncoderSettings {
+        width: 1920,
+        height: 1080,
+        fps: 30,
+        bitrate: DEFAULT_BITRATE,
+        keyframe_interval: 60,
+        color_space: ColorSpace::for_resolution(1920, 1080),
+        tuning: EncoderTuning::default(),
+    }
+}
+
+#[test]
+fn test_each_mode_maps_to_encoder_settings() {
+    let expected = [
+        (QualityMode::Ultra, 60, 120, 12_500_000),
+        (QualityMode::High, 30, 60, 5_000_000),
+        (QualityMode::Medium, 30, 30, 2_500_000),
+        (QualityMode::Low, 15, 15, 1_250_000),
+        (QualityMode::Minimal, 10, 10, 500_000),
+    ];
+    for (mode, fps, keyframe_interval, bitrate) in expected {
+        let settings = mode.encoder_settings(base_settings());
+        assert_eq!((settings.fps, settings.keyframe_interval, settings.bitrate), (fps, keyframe_interval, bitrate), "{:?}", mode);
+        // Size, color and tuning are left alone
+        assert_eq!((settings.width, settings.height, settings.tuning), (1920, 1080, EncoderTuning::default()));
+    }
+    
+    // Bitrates follow the configured one
+    let doubled = QualityMode::Low.encoder_settings(EncoderSettings { bitrate: 2 * DEFAULT_BITRATE, ..base_settings() });
+    assert_eq!(doubled.bitrate, 2_500_000);
+}
+
+#[test]
+fn test_capture_follows_the_quality_mode() {
+    let source = Box::new(TestPatternSource::new(64, 64));
+    let mut capture = ScreenCapture::from_source(source, 30, DEFAULT_BITRATE, EncoderTuning::default()).unwrap();
+    assert_eq!(capture.encoder_settings().bitrate, DEFAULT_BITRATE);
+    
+    for mode in QualityMode::ALL {
+        capture.set_quality(mode);
+        let settings = capture.encoder_settings();
+        let expected = mode.encoder_settings(EncoderSettings { width: 64, height: 64, ..base_settings() });
+        assert_eq!((settings.fps, settings.keyframe_interval, settings.bitrate), (expected.fps, expected.keyframe_interval, expected.bitrate));
+    }
+    
+    // The content profile still adjusts the mode's settings
+    capture.set_quality(QualityMode::Ultra);
+    capture.set_content_profile(ContentProfile::Text);
+    let settings = capture.encoder_settings();
+    assert_eq!((settings.fps, settings.keyframe_interval, settings.bitrate), (15, 240, 12_500_000));
+}
+
+#[test]
+fn test_encoder_input_follows_the_quality_mode_resolution() {
+    let source = Box::new(TestPatternSource::new(64, 64));
+    let mut capture = ScreenCapture::from_source(source, 30, DEFAULT_BITRATE, EncoderTuning::default()).unwrap();
+    
+    let expected = [
+        (QualityMode::Ultra, 64),
+        (QualityMode::High, 64),
+        (QualityMode::Medium, 48),
+        (QualityMode::Low, 32),
+        (QualityMode::Minimal, 16),
+    ];
+    for (mode, size) in expected {
+        capture.set_quality(mode);
+        let settings = capture.encoder_settings();
+        assert_eq!((settings.width, settings.height), (size, size), "{:?}", mode);
+        assert_eq!(settings.color_space, ColorSpace::for_resolution(size, size));
+    }
+    
+    // Applied on top of the viewport scaling
+    capture.set_viewport(Some((32, 32)));
+    capture.set_quality(QualityMode::Low);
+    let settings = capture.encoder_settings();
+    assert_eq!((settings.width, settings.height), (16, 16));
+    
+    // Without an H.264 encoder frames only shrink to the viewport
+    assert_eq!(capture.frame_scale(), capture.output_scale());
+}
+
+// Feed `rate` samples a second of `bytes` each for `seconds`, returning when the last went in
+fn feed(monitor: &mut BandwidthMonitor, start: Instant, rate: u32, bytes: usize, seconds: u32) -> Instant {
+    let interval = Duration::from_secs(1) / rate;
+    let mut at = start;
+    for _ in 0..rate * seconds {
+        at += interval;
+        monitor.add_sample_at(bytes, Duration::from_millis(20), at);
+    }
+    at
+}
+
+#[test]
+fn test_bandwidth_is_measured_at_any_sample_rate() {
+    // 1 Mbps as 60 small messages a second, and as 2 large ones
+    for (rate, bytes) in [(60, 125_000 / 60), (2, 125_000 / 2)] {
+        let mut monitor = BandwidthMonitor::new();
+        let end = feed(&mut monitor, Instant::now(), rate, bytes, 10);
+        let mbps = monitor.bandwidth_mbps_at(end).unwrap();
+        assert!((mbps - 1.0).abs() < 0.1, "{} samples/s: {} Mbps", rate, mbps);
+    }
+}
+
+#[test]
+fn test_bandwidth_follows_a_rate_change_within_the_window() {
+    // A slow stream whose old samples would have lingered under a count-only limit
+    let mut monitor = BandwidthMonitor::new();
+    let start = Instant::now();
+    let slowed = feed(&mut monitor, start, 2, 625_000, 10); // 10 Mbps
+    let end = feed(&mut monitor, slowed, 2, 62_500, 3); // 1 Mbps
+    
+    let mbps = monitor.bandwidth_mbps_at(end).unwrap();
+    assert!((mbps - 1.0).abs() < 0.1, "{} Mbps", mbps);
+}
+
+#[test]
+fn test_bandwidth_estimate_expires_once_sending_stops() {
+    let mut monitor = BandwidthMonitor::new();
+    let end = feed(&mut monitor, Instant::now(), 30, 4_000, 5);
+    let busy = monitor.bandwidth_mbps_at(end).unwrap();
+    
+    // Measured up to now, the same bytes are spread over a longer span
+    let quieter = monitor.bandwidth_mbps_at(end + BANDWIDTH_WINDOW / 2).unwrap();
+    assert!(quieter < busy, "{} >= {}", quieter, busy);
+    
+    assert_eq!(monitor.bandwidth_mbps_at(end + BANDWIDTH_WINDOW + Duration::from_millis(1)), None);
+}
+
+#[test]
+fn test_bandwidth_needs_a_measurable_span() {
+    let mut monitor = BandwidthMonitor::new();
+    let start = Instant::now();
+    assert_eq!(monitor.bandwidth_mbps_at(start), None);
+    
+    monitor.add_sample_at(10_000, Duration::ZERO, start);
+    monitor.add_sample_at(10_000, Duration::ZERO, start + Duration::from_millis(20));
+    assert_eq!(monitor.bandwidth_mbps_at(start + Duration::from_millis(20)), None);
+}
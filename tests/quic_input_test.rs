@@ -0,0 +1,174 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use vox_gui::common::crypto::{CryptoSession, Encryptor, KeyExchange};
+use vox_gui::common::input_channel::{
+    is_sequenced, InputReceiver, InputSender, QuicInputReceiver, QuicInputSender, RETRANSMIT_AFTER,
+};
+use vox_gui::common::protocol::{Message, Modifiers, MouseButton};
+use vox_gui::common::transport::{QuicConnection, QuicTransport};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn key(key: &str, pressed: bool) -> Message {
+    Message::KeyEvent {
+        key: key.to_string(),
+        pressed,
+        repeat: false,
+        modifiers: Modifiers { shift: false, ctrl: false, alt: false, meta: false },
+    }
+}
+
+fn key_events(messages: &[Message]) -> Vec<(String, bool)> {
+    messages
+        .iter()
+        .map(|message| match message {
+            Message::KeyEvent { key, pressed, .. } => (key.clone(), *pressed),
+            other => panic!("unexpected message {:?}", other),
+        })
+        .collect()
+}
+
+fn unwrap_datagram(message: Message) -> (Option<u64>, Message) {
+    match message {
+        Message::InputDatagram { seq, event } => (seq, *event),
+        other => panic!("unexpected message {:?}", other),
+    }
+}
+
+fn ack_of(ack: Option<Message>) -> u64 {
+    match ack {
+        Some(Message::InputAck { next }) => next,
+        other => panic!("expected an ack, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_only_state_changes_are_sequenced() {
+    assert!(is_sequenced(&key("a", false)));
+    assert!(is_sequenced(&Message::MouseClick { button: MouseButton::Left, pressed: false, x: 0, y: 0 }));
+    assert!(!is_sequenced(&Message::MouseMove { x: 1, y: 2 }));
+    assert!(!is_sequenced(&Message::MouseScroll { delta_x: 0.0, delta_y: 1.0 }));
+    
+    let mut sender = InputSender::new();
+    let (seq, _) = unwrap_datagram(sender.wrap(Message::MouseMove { x: 1, y: 2 }, Instant::now()));
+    assert_eq!(seq, None);
+    assert_eq!(sender.unacked(), 0);
+}
+
+#[test]
+fn test_lost_release_is_retransmitted_until_acked() {
+    let start = Instant::now();
+    let mut sender = InputSender::new();
+    let mut receiver = InputReceiver::new();
+    
+    let press = unwrap_datagram(sender.wrap(key("a", true), start));
+    let _lost_release = sender.wrap(key("a", false), start);
+    
+    let (events, ack) = receiver.receive(press.0, press.1);
+    assert_eq!(key_events(&events), vec![("a".to_string(), true)]);
+    sender.acked(ack_of(ack));
+    assert_eq!(sender.unacked(), 1);
+    
+    // Nothing is resent before the timeout
+    assert!(sender.retransmits(start + RETRANSMIT_AFTER / 2).is_empty());
+    
+    let resent = sender.retransmits(start + RETRANSMIT_AFTER);
+    assert_eq!(resent.len(), 1);
+    let (seq, event) = unwrap_datagram(resent.into_iter().next().unwrap());
+    let (events, ack) = receiver.receive(seq, event);
+    assert_eq!(key_events(&events), vec![("a".to_string(), false)]);
+    
+    sender.acked(ack_of(ack));
+    assert_eq!(sender.unacked(), 0);
+    assert!(sender.retransmits(start + RETRANSMIT_AFTER * 4).is_empty());
+}
+
+#[test]
+fn test_reordered_events_are_delivered_in_order() {
+    let now = Instant::now();
+    let mut sender = InputSender::new();
+    let mut receiver = InputReceiver::new();
+    let datagrams: Vec<_> = ["a", "b", "c"].iter().map(|k| unwrap_datagram(sender.wrap(key(k, true), now))).collect();
+    
+    // "c" overtakes the others: held back, and the ack still asks for 0
+    let (events, ack) = receiver.receive(datagrams[2].0, datagrams[2].1.clone());
+    assert!(events.is_empty());
+    assert_eq!(ack_of(ack), 0);
+    
+    let (events, _) = receiver.receive(datagrams[0].0, datagrams[0].1.clone());
+    assert_eq!(key_events(&events), vec![("a".to_string(), true)]);
+    
+    let (events, ack) = receiver.receive(datagrams[1].0, datagrams[1].1.clone());
+    assert_eq!(key_events(&events), vec![("b".to_string(), true), ("c".to_string(), true)]);
+    assert_eq!(ack_of(ack), 3);
+}
+
+#[test]
+fn test_duplicates_are_applied_once_but_acked_again() {
+    let mut sender = InputSender::new();
+    let mut receiver = InputReceiver::new();
+    let (seq, event) = unwrap_datagram(sender.wrap(key("a", false), Instant::now()));
+    
+    let (events, _) = receiver.receive(seq, event.clone());
+    assert_eq!(events.len(), 1);
+    
+    // The first ack was lost, so the sender tried again
+    let (events, ack) = receiver.receive(seq, event);
+    assert!(events.is_empty());
+    assert_eq!(ack_of(ack), 1);
+}
+
+async fn connected_pair() -> (QuicConnection, QuicConnection) {
+    let server = QuicTransport::new_server("127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let addr = server.local_addr().unwrap();
+    let accepted = tokio::spawn(async move { server.accept().await.unwrap() });
+    
+    let client = QuicTransport::new_client().await.unwrap();
+    let client_connection = tokio::time::timeout(TIMEOUT, client.connect(addr, "localhost"))
+        .await
+        .unwrap()
+        .unwrap();
+    let server_connection = tokio::time::timeout(TIMEOUT, accepted).await.unwrap().unwrap();
+    
+    (server_connection, client_connection)
+}
+
+fn shared_crypto() -> (Arc<Mutex<Box<dyn Encryptor>>>, Arc<Mutex<Box<dyn Encryptor>>>) {
+    let ours = KeyExchange::new();
+    let theirs = KeyExchange::new();
+    let ours_public = x25519_dalek::PublicKey::from(ours.public_key_bytes());
+    let theirs_public = x25519_dalek::PublicKey::from(theirs.public_key_bytes());
+    let a: Box<dyn Encryptor> = Box::new(CryptoSession::from_shared_secret(&ours.compute_shared_secret(&theirs_public)).unwrap());
+    let b: Box<dyn Encryptor> = Box::new(CryptoSession::from_shared_secret(&theirs.compute_shared_secret(&ours_public)).unwrap());
+    (Arc::new(Mutex::new(a)), Arc::new(Mutex::new(b)))
+}
+
+#[tokio::test]
+async fn test_input_round_trips_over_quic_datagrams() {
+    let (server, client) = connected_pair().await;
+    let (client_crypto, server_crypto) = shared_crypto();
+    let sender = QuicInputSender::new(client.clone(), client_crypto.clone());
+    let receiver = QuicInputReceiver::new(Arc::new(server.clone()), server_crypto);
+    
+    sender.send(Message::MouseMove { x: 5, y: 6 }).await.unwrap();
+    sender.send(key("Shift", true)).await.unwrap();
+    assert_eq!(sender.unacked(), 1);
+    
+    let mut applied = Vec::new();
+    while applied.len() < 2 {
+        let data = tokio::time::timeout(TIMEOUT, server.receive_datagram()).await.unwrap().unwrap();
+        applied.extend(receiver.handle_datagram(&data).await.unwrap());
+    }
+    assert!(matches!(applied[0], Message::MouseMove { x: 5, y: 6 }));
+    assert_eq!(key_events(&applied[1..]), vec![("Shift".to_string(), true)]);
+    
+    // The server's ack comes back as a datagram and clears the pending press
+    let data = tokio::time::timeout(TIMEOUT, client.receive_datagram()).await.unwrap().unwrap();
+    let ack = Message::deserialize(&client_crypto.lock().await.decrypt(&data).unwrap()).unwrap();
+    let Message::InputAck { next } = ack else {
+        panic!("expected an ack, got {:?}", ack);
+    };
+    sender.handle_ack(next);
+    assert_eq!(sender.unacked(), 0);
+}
@@ -0,0 +1,204 @@
+mod common;
+
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use vox_gui::common::auth::{AccessCode, AccessCodes, Permissions};
+use vox_gui::common::crypto::{open_message, seal_message, CryptoSession, Encryptor, KeyExchange};
+use vox_gui::common::input_channel::QuicInputSender;
+use vox_gui::common::protocol::{EncodingType, Message, MouseButton};
+use vox_gui::common::quality::QualityMode;
+use vox_gui::common::transport::{QuicConnection, QuicTransport, Transport};
+use vox_gui::server::approval::ApprovalPolicy;
+use vox_gui::server::frame_source::{FrameSource, FrameSourceFactory};
+use vox_gui::server::server::ServerControl;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+async fn connect(addr: SocketAddr) -> QuicConnection {
+    let client = QuicTransport::new_client().await.unwrap();
+    tokio::time::timeout(TIMEOUT, client.connect(addr, "localhost")).await.unwrap().unwrap()
+}
+
+async fn key_exchange(connection: &QuicConnection) -> CryptoSession {
+    let ours = KeyExchange::new();
+    let offer = Message::KeyExchange { public_key: ours.public_key_bytes().to_vec() };
+    connection.send(&offer.serialize().unwrap()).await.unwrap();
+    
+    let reply = tokio::time::timeout(TIMEOUT, connection.recv()).await.unwrap().unwrap();
+    let Message::KeyExchangeAck { public_key } = Message::deserialize(&reply).unwrap() else {
+        panic!("expected KeyExchangeAck");
+    };
+    CryptoSession::from_shared_secret(&ours.agree(&public_key).unwrap()).unwrap()
+}
+
+async fn send(connection: &QuicConnection, crypto: &CryptoSession, message: &Message) {
+    for frame in seal_message(crypto, &message.serialize().unwrap()).unwrap() {
+        connection.send(&frame).await.unwrap();
+    }
+}
+
+/// Key exchange and authentication with `code`; whether the server let the client in.
+async fn join(connection: &QuicConnection, code: &str) -> (CryptoSession, bool) {
+    let crypto = key_exchange(connection).await;
+    send(connection, &crypto, &Message::AuthRequest { code: code.to_string() }).await;
+    loop {
+        let data = tokio::time::timeout(TIMEOUT, connection.recv()).await.unwrap().unwrap();
+        if let Some(Message::AuthResponse { success, .. }) = open_message(&crypto, &data).unwrap() {
+            return (crypto, success);
+        }
+    }
+}
+
+async fn wait_for_sessions(control: &ServerControl, count: usize) {
+    for _ in 0..50 {
+        if control.sessions().await.len() == count {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("expected {} sessions, have {}", count, control.sessions().await.len());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_quic_sessions_follow_the_server_settings() {
+    let access_code = AccessCode::generate();
+    let code = access_code.code.clone();
+    let server = common::test_server(access_code).with_quality_bounds(QualityMode::Low, QualityMode::Low);
+    let control = server.control();
+    let addr = common::serve_quic(server).await;
+    
+    let connection = connect(addr).await;
+    let (_crypto, success) = join(&connection, &code).await;
+    assert!(success);
+    
+    wait_for_sessions(&control, 1).await;
+    let session = control.sessions().await.remove(0);
+    assert_eq!(session.quality, QualityMode::Low);
+    assert!(session.permissions.input);
+    
+    // Closing the connection ends the session, as a dropped TCP connection does
+    connection.close().await;
+    wait_for_sessions(&control, 0).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_quic_clients_with_a_wrong_code_are_refused() {
+    let server = common::test_server(AccessCode::generate());
+    let control = server.control();
+    let addr = common::serve_quic(server).await;
+    
+    let connection = connect(addr).await;
+    let (_crypto, success) = join(&connection, "not-the-code").await;
+    assert!(!success);
+    assert!(control.sessions().await.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_quic_clients_wait_for_the_host_to_approve_them() {
+    let access_code = AccessCode::generate();
+    let code = access_code.code.clone();
+    let server = common::test_server(access_code)
+        .with_approval(ApprovalPolicy::Ask { timeout: Duration::from_secs(10) });
+    let control = server.control();
+    let addr = common::serve_quic(server).await;
+    
+    let mut connections = Vec::new();
+    for approve in [false, true] {
+        let code = code.clone();
+        let joining = tokio::spawn(async move {
+            let connection = connect(addr).await;
+            let (_crypto, success) = join(&connection, &code).await;
+            (connection, success)
+        });
+        
+        let request = tokio::time::timeout(TIMEOUT, async {
+            loop {
+                if let Some(request) = control.pending_connections().into_iter().next() {
+                    return request;
+                }
+                control.connection_requested().await;
+            }
+        }).await.expect("no QUIC client asked to be let in");
+        assert!(request.peer.is_some());
+        assert!(control.sessions().await.is_empty());
+        
+        assert!(control.answer_connection(request.id, approve));
+        let (connection, success) = tokio::time::timeout(TIMEOUT, joining).await.unwrap().unwrap();
+        assert_eq!(success, approve);
+        connections.push(connection);
+    }
+    wait_for_sessions(&control, 1).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_quic_honours_fixed_access_codes_and_their_permissions() {
+    let mut codes = AccessCodes::new();
+    codes.add("246810", Permissions::VIEW_ONLY);
+    let server = common::test_server(AccessCode::generate()).with_access_codes(codes);
+    let control = server.control();
+    let addr = common::serve_quic(server).await;
+    
+    let connection = connect(addr).await;
+    let (crypto, success) = join(&connection, "246810").await;
+    assert!(success);
+    wait_for_sessions(&control, 1).await;
+    assert!(!control.sessions().await[0].permissions.input);
+    
+    // Input datagrams reach the session, which acks them but doesn't inject a viewer's input
+    let crypto: Box<dyn Encryptor> = Box::new(crypto);
+    let crypto = Arc::new(Mutex::new(crypto));
+    let input = QuicInputSender::new(connection.clone(), crypto.clone());
+    input.send(Message::MouseClick { button: MouseButton::Left, pressed: true, x: 10, y: 10 }).await.unwrap();
+    let data = tokio::time::timeout(TIMEOUT, connection.receive_datagram()).await.unwrap().unwrap();
+    let ack = Message::deserialize(&crypto.lock().await.decrypt(&data).unwrap()).unwrap();
+    assert!(matches!(ack, Message::InputAck { next: 1 }), "{:?}", ack);
+}
+
+const SIZE: usize = 64;
+
+/// A display showing nothing but blue.
+struct BlueDisplay;
+
+impl FrameSource for BlueDisplay {
+    fn frame(&mut self) -> io::Result<Vec<u8>> {
+        Ok([255, 0, 0, 255].repeat(SIZE * SIZE))
+    }
+    
+    fn width(&self) -> usize {
+        SIZE
+    }
+    
+    fn height(&self) -> usize {
+        SIZE
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_quic_frames_are_encoded_once() {
+    let access_code = AccessCode::generate();
+    let code = access_code.code.clone();
+    let blue: FrameSourceFactory = Arc::new(|_| Ok(Box::new(BlueDisplay) as Box<dyn FrameSource>));
+    let addr = common::serve_quic(common::test_server(access_code).with_frame_sources(blue)).await;
+    
+    let connection = connect(addr).await;
+    let (crypto, success) = join(&connection, &code).await;
+    assert!(success);
+    send(&connection, &crypto, &Message::Capabilities { encodings: vec![EncodingType::ZstdCompressed] }).await;
+    send(&connection, &crypto, &Message::StartStream).await;
+    
+    // One zstd pass gives back the screen's pixels, not another encoding of them
+    let pixels = tokio::time::timeout(TIMEOUT, async {
+        loop {
+            let data = connection.recv().await.unwrap();
+            if let Some(Message::ScreenFrame { data, encoding, .. }) = open_message(&crypto, &data).unwrap() {
+                assert_eq!(encoding, EncodingType::ZstdCompressed);
+                return zstd::stream::decode_all(&data[..]).unwrap();
+            }
+        }
+    }).await.unwrap();
+    assert_eq!(pixels.len(), SIZE * SIZE * 3);
+    assert!(pixels.chunks(3).all(|rgb| rgb == [0, 0, 255]));
+}
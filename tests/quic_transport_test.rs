@@ -0,0 +1,92 @@
+use bytes::Bytes;
+use std::time::Duration;
+use vox_gui::common::transport::{receive_message, send_message, FrameRoute, QuicConnection, QuicTransport};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A connected loopback pair: (server side, client side).
+async fn connected_pair() -> (QuicConnection, QuicConnection) {
+    let server = QuicTransport::new_server("127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let addr = server.local_addr().unwrap();
+    let accepted = tokio::spawn(async move { server.accept().await.unwrap() });
+    
+    let client = QuicTransport::new_client().await.unwrap();
+    let client_connection = tokio::time::timeout(TIMEOUT, client.connect(addr, "localhost"))
+        .await
+        .unwrap()
+        .unwrap();
+    let server_connection = tokio::time::timeout(TIMEOUT, accepted).await.unwrap().unwrap();
+    
+    (server_connection, client_connection)
+}
+
+#[tokio::test]
+async fn message_framing_round_trips_over_a_stream() {
+    let (mut server, client) = connected_pair().await;
+    
+    let (mut send, mut recv) = client.open_stream().await.unwrap();
+    send_message(&mut send, b"hello over quic").await.unwrap();
+    
+    let (mut server_send, mut server_recv) = tokio::time::timeout(TIMEOUT, server.accept_stream()).await.unwrap().unwrap();
+    let request = tokio::time::timeout(TIMEOUT, receive_message(&mut server_recv)).await.unwrap().unwrap();
+    assert_eq!(request, b"hello over quic");
+    
+    // The reply travels back on the same bidirectional stream
+    let reply = vec![0xA5u8; 256 * 1024];
+    send_message(&mut server_send, &reply).await.unwrap();
+    let received = tokio::time::timeout(TIMEOUT, receive_message(&mut recv)).await.unwrap().unwrap();
+    assert_eq!(received, reply);
+}
+
+#[tokio::test]
+async fn each_stream_carries_exactly_one_message() {
+    let (mut server, client) = connected_pair().await;
+    
+    // send_message finishes the stream, so a second message needs a new stream
+    let (mut send, _recv) = client.open_stream().await.unwrap();
+    send_message(&mut send, b"first").await.unwrap();
+    assert!(send_message(&mut send, b"second").await.is_err());
+    
+    let (mut next_send, _next_recv) = client.open_stream().await.unwrap();
+    send_message(&mut next_send, b"second").await.unwrap();
+    
+    for expected in [&b"first"[..], &b"second"[..]] {
+        let (_send, mut recv) = tokio::time::timeout(TIMEOUT, server.accept_stream()).await.unwrap().unwrap();
+        let message = tokio::time::timeout(TIMEOUT, receive_message(&mut recv)).await.unwrap().unwrap();
+        assert_eq!(message, expected);
+    }
+}
+
+#[tokio::test]
+async fn datagrams_are_delivered_whole() {
+    let (server, client) = connected_pair().await;
+    
+    let payload = Bytes::from_static(b"cursor at 640,480");
+    client.send_datagram(payload.clone()).await.unwrap();
+    let received = tokio::time::timeout(TIMEOUT, server.receive_datagram()).await.unwrap().unwrap();
+    assert_eq!(received, payload);
+    
+    server.send_datagram(Bytes::from_static(b"ack")).await.unwrap();
+    let received = tokio::time::timeout(TIMEOUT, client.receive_datagram()).await.unwrap().unwrap();
+    assert_eq!(&received[..], b"ack");
+}
+
+#[tokio::test]
+async fn oversized_datagrams_fall_back_to_a_stream() {
+    let (mut server, client) = connected_pair().await;
+    let max = client.max_datagram_size().expect("peer accepts datagrams");
+    
+    // A payload that fits still goes as a datagram
+    let small = Bytes::from(vec![0x11u8; 64]);
+    assert_eq!(client.send_datagram_or_stream(small.clone()).await.unwrap(), FrameRoute::Datagram);
+    let received = tokio::time::timeout(TIMEOUT, server.receive_datagram()).await.unwrap().unwrap();
+    assert_eq!(received, small);
+    
+    // One too big for a datagram is refused as such, and arrives whole on a stream instead
+    let frame = Bytes::from(vec![0x5Au8; max * 4]);
+    assert!(client.send_datagram(frame.clone()).await.is_err());
+    assert_eq!(client.send_datagram_or_stream(frame.clone()).await.unwrap(), FrameRoute::Stream);
+    let (_send, mut recv) = tokio::time::timeout(TIMEOUT, server.accept_stream()).await.unwrap().unwrap();
+    let received = tokio::time::timeout(TIMEOUT, receive_message(&mut recv)).await.unwrap().unwrap();
+    assert_eq!(received, frame);
+}
@@ -0,0 +1,173 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use bytes::BytesMut;
+use tokio::io::AsyncReadExt;
+use vox_gui::client::connection::frame_format;
+use vox_gui::common::display_layout::MonitorRect;
+use vox_gui::common::framing::{frame_buffer_capacity, FrameDecoder, ReadBuffer, DEFAULT_READ_CAPACITY};
+use vox_gui::common::protocol::{EncodingType, Message};
+
+const WIDTH: u32 = 1920;
+const HEIGHT: u32 = 1080;
+
+// What one read off the socket brings in
+const READ_SIZE: usize = 64 * 1024;
+
+// Allocations this big can only be the read buffer
+const LARGE: usize = 1024 * 1024;
+
+// Counts the large allocations and reallocations of the current thread, so tests running
+// alongside don't disturb the count
+struct CountingAllocator;
+
+thread_local! {
+    static LARGE_ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn note(size: usize) {
+    if size >= LARGE {
+        let _ = LARGE_ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+    }
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        note(layout.size());
+        System.alloc(layout)
+    }
+    
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+    
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        note(new_size);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn large_allocations() -> usize {
+    LARGE_ALLOCATIONS.with(Cell::get)
+}
+
+// Raw 1080p keyframes, each after a small message, as length-prefixed bodies
+fn keyframe_stream(keyframes: usize) -> Vec<u8> {
+    let mut stream = Vec::new();
+    for i in 0..keyframes {
+        for body in [vec![1u8; 32], vec![i as u8; (WIDTH * HEIGHT * 3) as usize]] {
+            stream.extend_from_slice(&(body.len() as u32).to_be_bytes());
+            stream.extend_from_slice(&body);
+        }
+    }
+    stream
+}
+
+// The two ways of holding the bytes read off a connection
+trait ReadTarget {
+    fn for_read(&mut self) -> &mut BytesMut;
+    fn received(&mut self) -> &mut BytesMut;
+}
+
+impl ReadTarget for BytesMut {
+    fn for_read(&mut self) -> &mut BytesMut {
+        self
+    }
+    
+    fn received(&mut self) -> &mut BytesMut {
+        self
+    }
+}
+
+impl ReadTarget for ReadBuffer {
+    fn for_read(&mut self) -> &mut BytesMut {
+        ReadBuffer::for_read(self)
+    }
+    
+    fn received(&mut self) -> &mut BytesMut {
+        ReadBuffer::received(self)
+    }
+}
+
+// Feed `stream` to the decoder a socket read at a time, as the read loops do. Returns
+// the number of messages decoded.
+async fn receive(stream: &[u8], buffer: &mut impl ReadTarget) -> usize {
+    let mut decoder = FrameDecoder::new();
+    let mut messages = 0;
+    for mut chunk in stream.chunks(READ_SIZE) {
+        while !chunk.is_empty() {
+            chunk.read_buf(buffer.for_read()).await.unwrap();
+            while let Some(message) = decoder.decode(buffer.received()).unwrap() {
+                messages += 1;
+                drop(message);
+            }
+        }
+    }
+    messages
+}
+
+#[tokio::test]
+async fn test_sized_buffer_receives_keyframes_without_reallocating() {
+    let stream = keyframe_stream(3);
+    
+    // A fixed 64 KiB buffer grows when the first keyframe arrives
+    let mut fixed = BytesMut::with_capacity(DEFAULT_READ_CAPACITY);
+    let before = large_allocations();
+    let messages = receive(&stream, &mut fixed).await;
+    let fixed_allocations = large_allocations() - before;
+    assert_eq!(messages, 6);
+    assert!(fixed_allocations >= 1);
+    
+    // One sized for the stream has room from the start and reuses it for every keyframe
+    let mut sized = ReadBuffer::for_frames(WIDTH, HEIGHT, EncodingType::Raw);
+    let before = large_allocations();
+    let messages = receive(&stream, &mut sized).await;
+    let sized_allocations = large_allocations() - before;
+    assert_eq!(messages, 6);
+    assert_eq!(sized_allocations, 0, "the sized buffer reallocated ({} for the fixed one)", fixed_allocations);
+}
+
+#[test]
+fn test_capacity_follows_resolution_and_encoding() {
+    let raw = frame_buffer_capacity(WIDTH, HEIGHT, EncodingType::Raw);
+    assert!(raw >= (WIDTH * HEIGHT * 3) as usize);
+    assert!(raw < (WIDTH * HEIGHT * 3) as usize + 64 * 1024);
+    
+    // Compressed keyframes are much smaller
+    for encoding in [EncodingType::ZstdCompressed, EncodingType::H264, EncodingType::WebP] {
+        assert!(frame_buffer_capacity(WIDTH, HEIGHT, encoding) < raw / 2);
+    }
+    
+    // Tiny or unknown frames keep the default
+    assert_eq!(frame_buffer_capacity(0, 0, EncodingType::Raw), DEFAULT_READ_CAPACITY);
+    assert_eq!(frame_buffer_capacity(32, 32, EncodingType::ZstdCompressed), DEFAULT_READ_CAPACITY);
+}
+
+#[test]
+fn test_fit_frames_only_grows() {
+    let mut buffer = ReadBuffer::with_capacity(DEFAULT_READ_CAPACITY);
+    buffer.fit_frames(WIDTH, HEIGHT, EncodingType::Raw);
+    let fitted = buffer.capacity();
+    assert_eq!(fitted, frame_buffer_capacity(WIDTH, HEIGHT, EncodingType::Raw));
+    assert!(buffer.for_read().capacity() >= fitted);
+    
+    // A smaller stream later, e.g. after the window shrank, keeps the room
+    buffer.fit_frames(640, 480, EncodingType::ZstdCompressed);
+    assert_eq!(buffer.capacity(), fitted);
+}
+
+#[test]
+fn test_frame_format_comes_from_layout_encoding_and_frames() {
+    let layout = Message::DisplayLayout {
+        monitors: vec![MonitorRect::new(0, 0, 1920, 1080), MonitorRect::new(1920, 0, 2560, 1440)],
+    };
+    assert_eq!(frame_format(&layout, 1), (Some((2560, 1440)), None));
+    assert_eq!(frame_format(&layout, 5), (None, None));
+    
+    let selected = Message::EncodingSelected { encoding: EncodingType::H264 };
+    assert_eq!(frame_format(&selected, 0), (None, Some(EncodingType::H264)));
+    
+    assert_eq!(frame_format(&Message::StartStream, 0), (None, None));
+}
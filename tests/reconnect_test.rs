@@ -0,0 +1,53 @@
+use std::time::Duration;
+use vox_gui::client::reconnect::{ReconnectAttempt, ReconnectBudget, DEFAULT_MAX_ATTEMPTS};
+
+/// Drive the budget the way the client's connect task does, with every attempt failing.
+fn fail_until_exhausted(budget: &mut ReconnectBudget) -> Vec<ReconnectAttempt> {
+    let mut attempts = Vec::new();
+    while let Some(attempt) = budget.next_attempt() {
+        attempts.push(attempt);
+        assert!(attempts.len() <= 100, "budget never ran out");
+    }
+    attempts
+}
+
+#[test]
+fn test_failed_connects_exhaust_the_budget() {
+    let mut budget = ReconnectBudget::new(4);
+    let attempts = fail_until_exhausted(&mut budget);
+    
+    let numbers: Vec<u32> = attempts.iter().map(|attempt| attempt.number).collect();
+    assert_eq!(numbers, vec![1, 2, 3, 4]);
+    assert!(budget.is_exhausted());
+    assert_eq!(budget.next_attempt(), None);
+}
+
+#[test]
+fn test_delays_double_up_to_the_cap() {
+    let mut budget = ReconnectBudget::new(6).with_delays(Duration::from_millis(100), Duration::from_millis(500));
+    let delays: Vec<u64> = fail_until_exhausted(&mut budget)
+        .iter()
+        .map(|attempt| attempt.delay.as_millis() as u64)
+        .collect();
+    
+    assert_eq!(delays, vec![100, 200, 400, 500, 500, 500]);
+}
+
+#[test]
+fn test_successful_reconnect_restores_the_budget() {
+    let mut budget = ReconnectBudget::new(3);
+    budget.next_attempt();
+    budget.next_attempt();
+    
+    budget.reset();
+    assert!(!budget.is_exhausted());
+    assert_eq!(fail_until_exhausted(&mut budget).len(), 3);
+}
+
+#[test]
+fn test_zero_attempts_gives_up_at_once() {
+    let mut budget = ReconnectBudget::new(0);
+    assert_eq!(budget.next_attempt(), None);
+    
+    assert_eq!(ReconnectBudget::new(DEFAULT_MAX_ATTEMPTS).max_attempts(), DEFAULT_MAX_ATTEMPTS);
+}
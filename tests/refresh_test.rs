@@ -0,0 +1,50 @@
+use std::time::{Duration, Instant};
+use vox_gui::client::refresh::{RefreshRequest, REFRESH_INTERVAL, REFRESH_TIMEOUT};
+use vox_gui::common::h264::is_idr;
+
+#[test]
+fn test_refresh_is_rate_limited() {
+    let mut refresh = RefreshRequest::new();
+    let start = Instant::now();
+    assert!(refresh.can_request(start));
+    assert!(refresh.request(start));
+    
+    // Hammering the hotkey sends nothing more until the interval has passed
+    assert!(!refresh.request(start + Duration::from_millis(100)));
+    assert!(!refresh.can_request(start + REFRESH_INTERVAL - Duration::from_millis(1)));
+    assert!(refresh.request(start + REFRESH_INTERVAL));
+}
+
+#[test]
+fn test_spinner_shows_until_a_complete_frame_arrives() {
+    let mut refresh = RefreshRequest::new();
+    let start = Instant::now();
+    assert!(!refresh.is_waiting(start));
+    
+    refresh.request(start);
+    assert!(refresh.is_waiting(start + Duration::from_millis(50)));
+    refresh.frame_received();
+    assert!(!refresh.is_waiting(start + Duration::from_millis(60)));
+    
+    // Frames arriving while nothing was requested change nothing
+    refresh.frame_received();
+    assert!(!refresh.is_waiting(start + Duration::from_millis(70)));
+}
+
+#[test]
+fn test_spinner_gives_up_when_no_frame_comes() {
+    let mut refresh = RefreshRequest::new();
+    let start = Instant::now();
+    refresh.request(start);
+    assert!(refresh.is_waiting(start + REFRESH_TIMEOUT - Duration::from_millis(1)));
+    assert!(!refresh.is_waiting(start + REFRESH_TIMEOUT));
+}
+
+#[test]
+fn test_idr_frames_are_recognised() {
+    let idr = [0, 0, 0, 1, 0x67, 0x42, 0, 0, 0, 1, 0x68, 0xce, 0, 0, 0, 1, 0x65, 0x88];
+    let p_frame = [0, 0, 0, 1, 0x41, 0x9a];
+    assert!(is_idr(&idr));
+    assert!(!is_idr(&p_frame));
+    assert!(!is_idr(&[]));
+}
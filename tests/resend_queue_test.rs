@@ -0,0 +1,155 @@
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use vox_gui::client::connection::Connection;
+use vox_gui::client::resend::{is_droppable, ResendQueue, RESEND_CAPACITY, RESEND_WINDOW};
+use vox_gui::common::batch::unpack;
+use vox_gui::common::crypto::{Cipher, CryptoSession, KeyExchange};
+use vox_gui::common::protocol::{Message, Modifiers, MouseButton};
+
+fn key(key: &str, pressed: bool) -> Message {
+    Message::KeyEvent { key: key.to_string(), pressed, repeat: false, modifiers: Modifiers::default() }
+}
+
+fn describe(messages: &[Message]) -> Vec<String> {
+    messages.iter().map(|m| format!("{:?}", m)).collect()
+}
+
+#[test]
+fn moves_and_acks_are_droppable_but_keys_and_clicks_are_not() {
+    assert!(is_droppable(&Message::MouseMove { x: 1, y: 2 }));
+    assert!(is_droppable(&Message::FrameAck { timestamp: 1, received_at: 2 }));
+    assert!(!is_droppable(&key("A", false)));
+    assert!(!is_droppable(&Message::MouseClick { button: MouseButton::Left, pressed: true, x: 0, y: 0 }));
+    assert!(!is_droppable(&Message::Disconnect));
+    
+    let mut queue = ResendQueue::new();
+    let now = Instant::now();
+    queue.push(Message::MouseMove { x: 1, y: 2 }, now);
+    queue.push(key("A", true), now);
+    queue.push(Message::FrameAck { timestamp: 1, received_at: 2 }, now);
+    queue.push(key("A", false), now);
+    
+    assert_eq!(queue.len(), 2);
+    assert_eq!(describe(&queue.take_replay(now)), describe(&[key("A", true), key("A", false)]));
+    assert!(queue.is_empty());
+}
+
+#[test]
+fn stale_presses_expire_but_releases_are_always_replayed() {
+    let mut queue = ResendQueue::new();
+    let then = Instant::now();
+    queue.push(key("Shift", true), then);
+    queue.push(Message::MouseClick { button: MouseButton::Left, pressed: true, x: 5, y: 5 }, then);
+    queue.push(Message::MouseClick { button: MouseButton::Left, pressed: false, x: 5, y: 5 }, then);
+    queue.push(key("Shift", false), then);
+    
+    let replay = queue.take_replay(then + RESEND_WINDOW + Duration::from_secs(1));
+    assert_eq!(
+        describe(&replay),
+        describe(&[
+            Message::MouseClick { button: MouseButton::Left, pressed: false, x: 5, y: 5 },
+            key("Shift", false),
+        ])
+    );
+}
+
+#[test]
+fn full_queue_drops_oldest_press_first() {
+    let mut queue = ResendQueue::new();
+    let now = Instant::now();
+    queue.push(key("Ctrl", false), now);
+    for i in 0..RESEND_CAPACITY {
+        queue.push(key(&format!("K{}", i), true), now);
+    }
+    
+    assert_eq!(queue.len(), RESEND_CAPACITY);
+    let replay = describe(&queue.take_replay(now));
+    assert_eq!(replay[0], format!("{:?}", key("Ctrl", false)));
+    assert!(!replay.contains(&format!("{:?}", key("K0", true))));
+    assert_eq!(replay.last(), Some(&format!("{:?}", key(&format!("K{}", RESEND_CAPACITY - 1), true))));
+}
+
+async fn send(stream: &mut TcpStream, data: &[u8]) {
+    stream.write_all(&(data.len() as u32).to_be_bytes()).await.unwrap();
+    stream.write_all(data).await.unwrap();
+}
+
+async fn read(stream: &mut TcpStream) -> Vec<u8> {
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len).await.unwrap();
+    let mut data = vec![0u8; u32::from_be_bytes(len) as usize];
+    stream.read_exact(&mut data).await.unwrap();
+    data
+}
+
+/// Server side of the handshake: cipher choice, key exchange and an accepted AuthRequest.
+async fn accept_session(listener: &TcpListener) -> (TcpStream, CryptoSession) {
+    let (mut stream, _) = listener.accept().await.unwrap();
+    
+    let Message::CipherOffer { ciphers } = Message::deserialize(&read(&mut stream).await).unwrap() else {
+        panic!("expected CipherOffer");
+    };
+    assert!(ciphers.contains(&Cipher::Aes256Gcm));
+    send(&mut stream, &Message::CipherSelected { cipher: Cipher::Aes256Gcm }.serialize().unwrap()).await;
+    
+    let Message::KeyExchange { public_key } = Message::deserialize(&read(&mut stream).await).unwrap() else {
+        panic!("expected KeyExchange");
+    };
+    let key_exchange = KeyExchange::new();
+    let ack = Message::KeyExchangeAck { public_key: key_exchange.public_key_bytes().to_vec() };
+    send(&mut stream, &ack.serialize().unwrap()).await;
+    let their_public = x25519_dalek::PublicKey::from(<[u8; 32]>::try_from(&public_key[..]).unwrap());
+    let crypto = CryptoSession::from_shared_secret(&key_exchange.compute_shared_secret(&their_public)).unwrap();
+    
+    let auth = Message::deserialize(&crypto.decrypt(&read(&mut stream).await).unwrap()).unwrap();
+    assert!(matches!(auth, Message::AuthRequest { .. }));
+    let response = Message::AuthResponse { success: true, session_token: None };
+    send(&mut stream, &crypto.encrypt(&response.serialize().unwrap()).unwrap()).await;
+    
+    (stream, crypto)
+}
+
+#[tokio::test]
+async fn key_release_survives_a_dropped_connection() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    
+    let server = tokio::spawn(async move {
+        // The first connection dies right after authenticating
+        let (first, _) = accept_session(&listener).await;
+        drop(first);
+        
+        // Collect what the second one receives up to StartStream
+        let (mut stream, crypto) = accept_session(&listener).await;
+        let mut received = Vec::new();
+        loop {
+            let message = Message::deserialize(&crypto.decrypt(&read(&mut stream).await).unwrap()).unwrap();
+            for message in unpack(message) {
+                let done = matches!(message, Message::StartStream);
+                received.push(message);
+                if done {
+                    return received;
+                }
+            }
+        }
+    });
+    
+    let (mut connection, _, _) = Connection::new();
+    let (mut rx, tx) = connection.connect(&addr, "123456").await.unwrap();
+    
+    // Wait for the client to notice the server is gone, then let go of a key
+    let timeout = Duration::from_secs(5);
+    while tokio::time::timeout(timeout, rx.recv()).await.unwrap().is_some() {}
+    tx.send(Message::MouseMove { x: 10, y: 10 }).unwrap();
+    tx.send(key("Shift", false)).unwrap();
+    drop(tx);
+    
+    let (_rx, _tx) = connection.connect(&addr, "123456").await.unwrap();
+    let received = tokio::time::timeout(timeout, server).await.unwrap().unwrap();
+    
+    // The release goes out first; the stale move is not replayed
+    assert_eq!(format!("{:?}", received[0]), format!("{:?}", key("Shift", false)));
+    assert!(!received.iter().any(|m| matches!(m, Message::MouseMove { .. })));
+    assert!(received.iter().any(|m| matches!(m, Message::Capabilities { .. })));
+}
@@ -0,0 +1,91 @@
+mod common;
+
+use std::time::Duration;
+use vox_gui::common::encoder::{decode_webp, EncoderFactory, EncoderTuning, WebpMode};
+use vox_gui::common::frame_processor::FrameType;
+use vox_gui::common::protocol::EncodingType;
+use vox_gui::server::screen_capture::{CapturedFrame, ScreenCapture, DEFAULT_BITRATE};
+
+const WIDTH: usize = 96;
+const HEIGHT: usize = 64;
+
+fn bgra_frame(shade: u8, marked_pixel: Option<(usize, usize)>) -> Vec<u8> {
+    let mut frame = vec![shade; WIDTH * HEIGHT * 4];
+    if let Some((x, y)) = marked_pixel {
+        let offset = (y * WIDTH + x) * 4;
+        frame[offset..offset + 3].copy_from_slice(&[255, 0, 0]);
+    }
+    frame
+}
+
+fn safe_capture(frames: Vec<Vec<u8>>) -> ScreenCapture {
+    let source = common::ScriptedSource::new(WIDTH, HEIGHT, frames);
+    let tuning = EncoderTuning { webp_mode: WebpMode::Lossless, ..EncoderTuning::default() };
+    ScreenCapture::safe_mode(Box::new(source), 100, DEFAULT_BITRATE, tuning).unwrap()
+}
+
+fn next_frame(capture: &mut ScreenCapture) -> CapturedFrame {
+    loop {
+        if let Some(frame) = capture.capture_frame().unwrap() {
+            return frame;
+        }
+        std::thread::sleep(Duration::from_millis(2));
+    }
+}
+
+fn pixel(rgb: &[u8], x: usize, y: usize) -> [u8; 3] {
+    let offset = (y * WIDTH + x) * 3;
+    [rgb[offset], rgb[offset + 1], rgb[offset + 2]]
+}
+
+#[test]
+fn test_safe_mode_only_tries_the_software_encoder() {
+    let names: Vec<&str> = EncoderFactory::safe_mode_candidates().iter().map(|candidate| candidate.name).collect();
+    assert_eq!(names, vec![EncoderFactory::SOFTWARE.name]);
+}
+
+#[test]
+fn test_safe_mode_sends_whole_webp_frames() {
+    let mut capture = safe_capture(vec![bgra_frame(0x40, None)]);
+    assert!(capture.is_safe_mode());
+    // Asking for H.264 changes nothing, there is no hardware encoder to use
+    capture.set_hardware_encoding(true);
+    
+    let frame = next_frame(&mut capture);
+    assert_eq!(frame.encoding, EncodingType::WebP);
+    assert_eq!(frame.frame_type, FrameType::KeyFrame);
+    assert!(frame.tiles.is_none());
+    assert_eq!((frame.width, frame.height), (WIDTH as u32, HEIGHT as u32));
+    
+    let (rgb, width, height) = decode_webp(&frame.data).unwrap();
+    assert_eq!((width, height), (WIDTH as u32, HEIGHT as u32));
+    assert!(rgb.iter().all(|&v| v == 0x40));
+}
+
+#[test]
+fn test_changes_are_sent_whole_and_repeats_are_skipped() {
+    let still = bgra_frame(0x40, None);
+    let changed = bgra_frame(0x40, Some((10, 20)));
+    let mut capture = safe_capture(vec![still.clone(), still.clone(), still, changed]);
+    next_frame(&mut capture);
+    
+    // The repeated frames in between aren't sent again
+    let frame = next_frame(&mut capture);
+    assert_eq!(frame.encoding, EncodingType::WebP);
+    assert_eq!(frame.frame_type, FrameType::KeyFrame);
+    assert!(frame.tiles.is_none());
+    let (rgb, ..) = decode_webp(&frame.data).unwrap();
+    assert_eq!(pixel(&rgb, 10, 20), [0, 0, 255]);
+    assert_eq!(pixel(&rgb, 11, 20), [0x40; 3]);
+}
+
+#[test]
+fn test_a_requested_keyframe_resends_an_unchanged_screen() {
+    let mut capture = safe_capture(vec![bgra_frame(0x40, None)]);
+    next_frame(&mut capture);
+    
+    capture.request_keyframe();
+    let frame = next_frame(&mut capture);
+    let (rgb, ..) = decode_webp(&frame.data).unwrap();
+    assert!(rgb.iter().all(|&v| v == 0x40));
+}
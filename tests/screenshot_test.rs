@@ -0,0 +1,34 @@
+use std::time::{Duration, UNIX_EPOCH};
+use vox_gui::client::screenshot::{save_screenshot, screenshot_filename};
+
+fn temp_dir(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("vox_screenshot_test_{}_{}", name, std::process::id()))
+}
+
+#[test]
+fn test_screenshot_round_trip() {
+    let dir = temp_dir("round_trip");
+    let rgb: Vec<u8> = (0..4 * 3 * 3).map(|i| (i * 7) as u8).collect();
+    
+    let path = save_screenshot(&dir, 4, 3, &rgb).unwrap();
+    assert_eq!(path.extension().unwrap(), "png");
+    
+    let saved = image::open(&path).unwrap().to_rgb8();
+    assert_eq!(saved.dimensions(), (4, 3));
+    assert_eq!(saved.into_raw(), rgb);
+    
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_screenshot_rejects_wrong_size() {
+    let dir = temp_dir("wrong_size");
+    assert!(save_screenshot(&dir, 4, 4, &[0u8; 10]).is_err());
+    assert!(!dir.exists());
+}
+
+#[test]
+fn test_filename_is_timestamped() {
+    let time = UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+    assert_eq!(screenshot_filename(time), "vox_screenshot_1700000000123.png");
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use std::time::Duration;
+use vox_gui::client::connection::Connection;
+use vox_gui::client::security::{fingerprint, SecurityInfo, CIPHER, KEY_EXCHANGE};
+use vox_gui::common::auth::AccessCode;
+use vox_gui::common::crypto::Cipher;
+use vox_gui::common::protocol::Message;
+use vox_gui::server::server::Server;
+
+fn row<'a>(rows: &'a [(&'static str, String)], label: &str) -> &'a str {
+    &rows.iter().find(|(l, _)| *l == label).unwrap().1
+}
+
+#[test]
+fn test_fingerprint_format() {
+    let fp = fingerprint(&[7u8; 32]);
+    let parts: Vec<&str> = fp.split(':').collect();
+    assert_eq!(parts.len(), 32);
+    assert!(parts.iter().all(|p| p.len() == 2 && p.chars().all(|c| c.is_ascii_hexdigit())));
+    
+    assert_eq!(fp, fingerprint(&[7u8; 32]));
+    assert_ne!(fp, fingerprint(&[8u8; 32]));
+}
+
+#[test]
+fn test_encrypted_session_rows() {
+    let mut info = SecurityInfo::encrypted(&[1u8; 32]);
+    info.authenticated = true;
+    let rows = info.rows();
+    
+    assert_eq!(row(&rows, "Cipher"), CIPHER);
+    assert_eq!(row(&rows, "Key exchange"), KEY_EXCHANGE);
+    assert_eq!(row(&rows, "Server key"), fingerprint(&[1u8; 32]));
+    assert_eq!(row(&rows, "Authentication"), "Access code");
+}
+
+#[test]
+fn test_plaintext_session_rows() {
+    let rows = SecurityInfo::plaintext().rows();
+    
+    assert!(row(&rows, "Cipher").starts_with("None"));
+    assert_eq!(row(&rows, "Server key"), "-");
+    assert_eq!(row(&rows, "Authentication"), "Not authenticated");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_connection_reports_security_after_connecting() {
+    let access_code = AccessCode::generate();
+    let code = access_code.code.clone();
+    
+    let server = common::test_server(access_code);
+    let addr = common::serve(server).await;
+    
+    let (mut connection, _, _) = Connection::new();
+    assert!(connection.security_info().is_none());
+    
+    let _channels = connection.connect(&addr, &code).await.unwrap();
+    let info = connection.security_info().unwrap();
+    assert!(info.encrypted);
+    assert!(info.authenticated);
+    assert!(info.server_key_fingerprint.is_some());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_connection_negotiates_chacha20() {
+    let access_code = AccessCode::generate();
+    let code = access_code.code.clone();
+    
+    let server = common::test_server(access_code);
+    let addr = common::serve(server).await;
+    
+    // A client that only speaks ChaCha20 still gets a working session
+    let (mut connection, _, _) = Connection::new();
+    connection.set_ciphers(vec![Cipher::ChaCha20Poly1305]);
+    let (mut rx, tx) = connection.connect(&addr, &code).await.unwrap();
+    let info = connection.security_info().unwrap();
+    assert_eq!(info.cipher, Cipher::ChaCha20Poly1305);
+    assert_eq!(row(&info.rows(), "Cipher"), "ChaCha20-Poly1305");
+    
+    tx.send(Message::StartStream).unwrap();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            match rx.recv().await {
+                Some(Message::QualityChange { .. }) => return,
+                Some(_) => {}
+                None => panic!("connection closed"),
+            }
+        }
+    })
+    .await
+    .unwrap();
+}
@@ -0,0 +1,122 @@
+use std::time::Duration;
+use vox_gui::server::send_queue::{Priority, SendQueue};
+
+const CAPACITY: usize = 64 * 1024;
+
+fn frame(tag: u8, len: usize) -> Vec<u8> {
+    vec![tag; len]
+}
+
+#[test]
+fn test_never_draining_consumer_stays_bounded() {
+    let queue = SendQueue::new(CAPACITY);
+    
+    // A client that never reads while the frame loop keeps producing
+    for i in 0..10_000u32 {
+        queue.push(frame(i as u8, 4096), Priority::Video).unwrap();
+        assert!(queue.queued_bytes() <= CAPACITY);
+    }
+    
+    assert_eq!(queue.queued_bytes(), CAPACITY);
+    assert_eq!(queue.video_backlog(), CAPACITY / 4096);
+    assert_eq!(queue.take_dropped_video(), 10_000 - CAPACITY / 4096);
+    assert_eq!(queue.take_dropped_video(), 0);
+}
+
+#[tokio::test]
+async fn test_oldest_video_is_dropped_first() {
+    let queue = SendQueue::new(3 * 100);
+    for tag in 0..5 {
+        queue.push(frame(tag, 100), Priority::Video).unwrap();
+    }
+    
+    assert_eq!(queue.take_dropped_video(), 2);
+    for tag in 2..5 {
+        assert_eq!(queue.pop().await.unwrap()[0], tag);
+    }
+}
+
+#[tokio::test]
+async fn test_control_goes_ahead_of_video() {
+    let queue = SendQueue::new(CAPACITY);
+    queue.push(frame(1, 100), Priority::Video).unwrap();
+    queue.push(frame(2, 100), Priority::Video).unwrap();
+    queue.push(frame(9, 10), Priority::Control).unwrap();
+    
+    assert_eq!(queue.pop().await.unwrap()[0], 9);
+    assert_eq!(queue.pop().await.unwrap()[0], 1);
+    assert_eq!(queue.pop().await.unwrap()[0], 2);
+}
+
+#[tokio::test]
+async fn test_control_evicts_video_when_full() {
+    let queue = SendQueue::new(200);
+    queue.push(frame(1, 100), Priority::Video).unwrap();
+    queue.push(frame(2, 100), Priority::Video).unwrap();
+    
+    queue.push(frame(9, 50), Priority::Control).unwrap();
+    assert_eq!(queue.take_dropped_video(), 1);
+    assert_eq!(queue.pop().await.unwrap()[0], 9);
+    assert_eq!(queue.pop().await.unwrap()[0], 2);
+}
+
+#[tokio::test]
+async fn test_oversized_video_frame_is_dropped() {
+    let queue = SendQueue::new(100);
+    queue.push(frame(1, 50), Priority::Video).unwrap();
+    queue.push(frame(2, 500), Priority::Video).unwrap();
+    
+    // No amount of evicting would make room, so only the big frame is lost
+    assert_eq!(queue.take_dropped_video(), 1);
+    assert_eq!(queue.pop().await.unwrap()[0], 1);
+}
+
+#[tokio::test]
+async fn test_control_overflow_ends_the_queue() {
+    let queue = SendQueue::new(100);
+    queue.push(frame(1, 60), Priority::Control).unwrap();
+    
+    assert!(queue.push(frame(2, 60), Priority::Control).is_err());
+    assert!(queue.is_overflowed());
+    assert!(queue.pop().await.is_none());
+    assert!(queue.push(frame(3, 1), Priority::Video).is_err());
+}
+
+#[tokio::test]
+async fn test_close_drains_before_ending() {
+    let queue = SendQueue::new(CAPACITY);
+    queue.push(frame(1, 10), Priority::Video).unwrap();
+    queue.push(frame(2, 10), Priority::Control).unwrap();
+    queue.close();
+    
+    assert!(queue.push(frame(3, 10), Priority::Control).is_err());
+    assert_eq!(queue.pop().await.unwrap()[0], 2);
+    assert_eq!(queue.pop().await.unwrap()[0], 1);
+    assert!(queue.pop().await.is_none());
+}
+
+#[tokio::test]
+async fn test_discard_video_keeps_control() {
+    let queue = SendQueue::new(CAPACITY);
+    queue.push(frame(1, 10), Priority::Video).unwrap();
+    queue.push(frame(2, 10), Priority::Control).unwrap();
+    
+    queue.discard_video();
+    assert_eq!(queue.video_backlog(), 0);
+    assert_eq!(queue.take_dropped_video(), 1);
+    assert_eq!(queue.queued_bytes(), 10);
+}
+
+#[tokio::test]
+async fn test_pop_waits_for_a_push() {
+    let queue = std::sync::Arc::new(SendQueue::new(CAPACITY));
+    let consumer = queue.clone();
+    let popped = tokio::spawn(async move { consumer.pop().await });
+    
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    assert!(!popped.is_finished());
+    
+    queue.push(frame(7, 10), Priority::Video).unwrap();
+    let frame = tokio::time::timeout(Duration::from_secs(1), popped).await.unwrap().unwrap();
+    assert_eq!(frame.unwrap()[0], 7);
+}
@@ -0,0 +1,92 @@
+use vox_gui::server::config::ServerConfig;
+use vox_gui::common::quality::{AdaptiveQualityController, QualityMode};
+
+#[test]
+fn test_toml_config_parses_every_field() {
+    let config = ServerConfig::from_toml(r#"
+        address = "127.0.0.1:9000"
+        transport = "tcp"
+        metrics = true
+        metrics_addr = "127.0.0.1:9100"
+        metrics_allow_remote = false
+        idle_timeout = 600
+        capture_backend = "scrap"
+        encoder_preset = "quality"
+        encoder_threads = 4
+        color_matrix = "bt709"
+        color_range = "full"
+        fps = 60
+        bitrate = 8000000
+        max_sessions = 2
+        min_quality = "low"
+        max_quality = "high"
+    "#).unwrap();
+    
+    assert_eq!(config.address.as_deref(), Some("127.0.0.1:9000"));
+    assert_eq!(config.metrics, Some(true));
+    assert_eq!(config.metrics_addr.as_deref(), Some("127.0.0.1:9100"));
+    assert_eq!(config.fps, Some(60));
+    assert_eq!(config.color_range.as_deref(), Some("full"));
+    assert_eq!(config.bitrate, Some(8_000_000));
+    assert_eq!(config.max_sessions, Some(2));
+    assert_eq!(config.max_quality.as_deref(), Some("high"));
+}
+
+#[test]
+fn test_json_config_and_missing_fields() {
+    let config = ServerConfig::from_json(r#"{ "fps": 15 }"#).unwrap();
+    assert_eq!(config.fps, Some(15));
+    assert_eq!(config.address, None);
+    
+    assert_eq!(ServerConfig::from_toml("").unwrap(), ServerConfig::default());
+}
+
+#[test]
+fn test_errors_name_the_offending_field() {
+    let unknown = ServerConfig::from_toml("frame_rate = 30").unwrap_err().to_string();
+    assert!(unknown.contains("frame_rate"), "{}", unknown);
+    
+    let wrong_type = ServerConfig::from_toml("fps = \"fast\"").unwrap_err().to_string();
+    assert!(wrong_type.contains("fps"), "{}", wrong_type);
+    
+    let out_of_range = ServerConfig::from_toml("fps = 0").unwrap_err().to_string();
+    assert!(out_of_range.starts_with("fps"), "{}", out_of_range);
+    
+    let bad_address = ServerConfig::from_json(r#"{ "address": "localhost" }"#).unwrap_err().to_string();
+    assert!(bad_address.starts_with("address"), "{}", bad_address);
+    
+    let bad_metrics_addr = ServerConfig::from_toml("metrics_addr = \"9100\"").unwrap_err().to_string();
+    assert!(bad_metrics_addr.starts_with("metrics_addr"), "{}", bad_metrics_addr);
+    
+    let empty_buffer = ServerConfig::from_toml("send_buffer = 0").unwrap_err().to_string();
+    assert!(empty_buffer.starts_with("send_buffer"), "{}", empty_buffer);
+}
+
+#[test]
+fn test_load_picks_format_from_extension() {
+    let dir = std::env::temp_dir().join(format!("vox_config_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    
+    let toml_path = dir.join("server.toml");
+    std::fs::write(&toml_path, "max_sessions = 3").unwrap();
+    assert_eq!(ServerConfig::load(&toml_path).unwrap().max_sessions, Some(3));
+    
+    let json_path = dir.join("server.json");
+    std::fs::write(&json_path, r#"{ "max_sessions": 5 }"#).unwrap();
+    assert_eq!(ServerConfig::load(&json_path).unwrap().max_sessions, Some(5));
+    
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_quality_bounds_clamp_forced_quality() {
+    let mut controller = AdaptiveQualityController::new();
+    controller.set_quality_bounds(QualityMode::Low, QualityMode::Medium);
+    assert_eq!(controller.get_current_quality(), QualityMode::Medium);
+    
+    controller.force_quality(Some(QualityMode::Ultra));
+    assert_eq!(controller.get_recommended_quality(), QualityMode::Medium);
+    
+    controller.force_quality(Some(QualityMode::Minimal));
+    assert_eq!(controller.get_recommended_quality(), QualityMode::Low);
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use std::time::{Duration, Instant};
+use vox_gui::client::connection::Connection;
+use vox_gui::common::auth::AccessCode;
+use vox_gui::common::metrics::SessionStats;
+use vox_gui::common::protocol::Message;
+use vox_gui::common::quality::QualityMode;
+use vox_gui::server::panel::BandwidthMeter;
+use vox_gui::server::server::{ServerControl, SessionSummary};
+
+async fn start_server() -> (String, String, ServerControl) {
+    let access_code = AccessCode::generate();
+    let code = access_code.code.clone();
+    
+    let server = common::test_server(access_code);
+    let control = server.control();
+    let addr = common::serve(server).await;
+    
+    (addr, code, control)
+}
+
+async fn wait_for_sessions(control: &ServerControl, count: usize) -> Vec<SessionSummary> {
+    for _ in 0..50 {
+        let sessions = control.sessions().await;
+        if sessions.len() == count {
+            return sessions;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("expected {} sessions, have {}", count, control.sessions().await.len());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_sessions_are_listed_and_can_be_disconnected() {
+    let (addr, code, control) = start_server().await;
+    assert!(control.sessions().await.is_empty());
+    
+    let (mut connection, _, _) = Connection::new();
+    let (mut rx, _tx) = connection.connect(&addr, &code).await.unwrap();
+    
+    let sessions = wait_for_sessions(&control, 1).await;
+    let session = &sessions[0];
+    assert!(session.peer.unwrap().ip().is_loopback());
+    assert_eq!(session.display, 0);
+    
+    assert!(!control.disconnect("no-such-session").await);
+    assert!(control.disconnect(&session.id).await);
+    assert!(control.sessions().await.is_empty());
+    
+    // The client is told before the connection closes
+    let told = tokio::time::timeout(Duration::from_secs(5), async {
+        while let Some(message) = rx.recv().await {
+            if matches!(message, Message::Disconnect) {
+                return true;
+            }
+        }
+        false
+    })
+    .await
+    .unwrap();
+    assert!(told);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_regenerated_code_replaces_the_old_one() {
+    let (addr, old_code, control) = start_server().await;
+    assert_eq!(control.access_code().await.unwrap().code, old_code);
+    
+    let new_code = control.regenerate_access_code().await;
+    assert_eq!(control.access_code().await.unwrap().code, new_code.code);
+    assert!(new_code.time_left() > Duration::from_secs(200));
+    
+    if old_code != new_code.code {
+        let (mut connection, _, _) = Connection::new();
+        assert!(connection.connect(&addr, &old_code).await.is_err());
+    }
+    let (mut connection, _, _) = Connection::new();
+    connection.connect(&addr, &new_code.code).await.unwrap();
+    wait_for_sessions(&control, 1).await;
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_quality_bounds_apply_to_connected_sessions() {
+    let (addr, code, control) = start_server().await;
+    assert_eq!(control.quality_bounds(), (QualityMode::Minimal, QualityMode::Ultra));
+    
+    let (mut connection, _, _) = Connection::new();
+    let _session = connection.connect(&addr, &code).await.unwrap();
+    wait_for_sessions(&control, 1).await;
+    
+    assert!(control.set_quality_bounds(QualityMode::High, QualityMode::Low).await.is_err());
+    assert_eq!(control.quality_bounds(), (QualityMode::Minimal, QualityMode::Ultra));
+    
+    control.set_quality_bounds(QualityMode::Minimal, QualityMode::Low).await.unwrap();
+    assert_eq!(control.quality_bounds(), (QualityMode::Minimal, QualityMode::Low));
+    let quality = control.sessions().await[0].quality;
+    assert!(quality.bandwidth_requirement() <= QualityMode::Low.bandwidth_requirement());
+}
+
+fn summary(id: &str, bytes_sent: u64) -> SessionSummary {
+    SessionSummary {
+        id: id.to_string(),
+        peer: None,
+        display: 0,
+        quality: QualityMode::High,
+        stats: SessionStats { bytes_sent, ..SessionStats::default() },
+    }
+}
+
+#[test]
+fn test_bandwidth_meter_reports_rate_between_samples() {
+    let mut meter = BandwidthMeter::new();
+    let start = Instant::now();
+    
+    let rates = meter.sample(&[summary("a", 1_000_000)], start);
+    assert_eq!(rates["a"], 0.0);
+    
+    // 1 MB more in half a second is 16 Mbps
+    let rates = meter.sample(&[summary("a", 2_000_000), summary("b", 500)], start + Duration::from_millis(500));
+    assert!((rates["a"] - 16.0).abs() < 1e-9);
+    assert_eq!(rates["b"], 0.0);
+    
+    // A session that left is forgotten
+    let rates = meter.sample(&[summary("b", 500)], start + Duration::from_secs(1));
+    assert!(!rates.contains_key("a"));
+    assert_eq!(rates["b"], 0.0);
+}
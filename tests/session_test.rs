@@ -0,0 +1,102 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
+use vox_gui::common::auth::{AccessCode, AccessCodes};
+use vox_gui::common::crypto::{open_message, CryptoSession, Encryptor, RekeyPolicy};
+use vox_gui::common::protocol::Message;
+use vox_gui::common::quality::QualityMode;
+use vox_gui::server::send_queue::Priority;
+use vox_gui::server::session::{authenticate, Session, SessionTransport, KEYFRAME_REQUEST_INTERVAL};
+
+// Keeps what a session sends, standing in for its send queue
+#[derive(Default)]
+struct RecordingTransport {
+    sent: std::sync::Mutex<Vec<(Vec<u8>, Priority)>>,
+    discarded: std::sync::Mutex<usize>,
+}
+
+impl SessionTransport for RecordingTransport {
+    fn send(&self, data: Vec<u8>, priority: Priority) -> anyhow::Result<()> {
+        self.sent.lock().unwrap().push((data, priority));
+        Ok(())
+    }
+    
+    fn discard_video(&self) {
+        *self.discarded.lock().unwrap() += 1;
+    }
+}
+
+fn session(policy: RekeyPolicy) -> (Session<RecordingTransport>, CryptoSession) {
+    let secret = [9u8; 32];
+    let crypto: Box<dyn Encryptor> = Box::new(CryptoSession::from_shared_secret(&secret).unwrap().with_rekey_policy(policy));
+    let transport = Arc::new(RecordingTransport::default());
+    let session = Session::new(Arc::new(Mutex::new(crypto)), transport, (QualityMode::Low, QualityMode::High));
+    (session, CryptoSession::from_shared_secret(&secret).unwrap())
+}
+
+#[tokio::test]
+async fn test_messages_are_sealed_for_the_peer() {
+    let (session, peer) = session(RekeyPolicy::default());
+    let sent = session.send(&Message::Ping { timestamp: 42 }, Priority::Control).await.unwrap();
+    
+    let frames = session.transport.sent.lock().unwrap().clone();
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].0.len(), sent);
+    assert_eq!(frames[0].1, Priority::Control);
+    assert!(matches!(open_message(&peer, &frames[0].0).unwrap(), Some(Message::Ping { timestamp: 42 })));
+}
+
+#[tokio::test]
+async fn test_rekey_goes_as_control_and_drops_stale_video() {
+    let (session, peer) = session(RekeyPolicy { max_messages: 1, max_bytes: u64::MAX });
+    session.send(&Message::Ping { timestamp: 1 }, Priority::Video).await.unwrap();
+    session.send(&Message::Ping { timestamp: 2 }, Priority::Video).await.unwrap();
+    
+    let frames = session.transport.sent.lock().unwrap().clone();
+    let priorities: Vec<Priority> = frames.iter().map(|(_, priority)| *priority).collect();
+    assert_eq!(priorities, vec![Priority::Video, Priority::Control, Priority::Video]);
+    assert_eq!(*session.transport.discarded.lock().unwrap(), 1);
+    
+    // The peer follows the rotation and reads everything
+    let opened: Vec<Option<Message>> = frames.iter().map(|(data, _)| open_message(&peer, data).unwrap()).collect();
+    assert!(matches!(opened[..], [Some(Message::Ping { timestamp: 1 }), None, Some(Message::Ping { timestamp: 2 })]));
+}
+
+#[tokio::test]
+async fn test_sessions_start_within_the_quality_bounds() {
+    let (session, _) = session(RekeyPolicy::default());
+    assert!(!session.id.is_empty());
+    assert!(!session.token.token.is_empty());
+    assert_eq!(session.quality_controller.lock().await.get_current_quality(), QualityMode::High);
+    
+    assert_eq!(session.next_sequence(), 0);
+    assert_eq!(session.next_sequence(), 1);
+}
+
+#[test]
+fn test_keyframe_requests_are_rate_limited() {
+    let (session, _) = session(RekeyPolicy::default());
+    let start = Instant::now();
+    assert!(session.request_keyframe(start));
+    assert!(!session.request_keyframe(start + Duration::from_millis(10)));
+    assert!(session.request_keyframe(start + KEYFRAME_REQUEST_INTERVAL));
+}
+
+#[tokio::test]
+async fn test_authentication_checks_code_and_session_limit() {
+    let code = AccessCode::generate();
+    let valid = code.code.clone();
+    let access_code = RwLock::new(Some(code));
+    let codes = AccessCodes::new();
+    
+    assert!(authenticate(&valid, &access_code, &codes, 0, None).await.success);
+    assert!(!authenticate("000000x", &access_code, &codes, 0, None).await.success);
+    
+    let full = authenticate(&valid, &access_code, &codes, 2, Some(2)).await;
+    assert!(!full.success);
+    assert!(full.message.contains("session limit"), "{}", full.message);
+    assert!(authenticate(&valid, &access_code, &codes, 1, Some(2)).await.success);
+    
+    // No code at all, e.g. while the host regenerates it
+    assert!(!authenticate(&valid, &RwLock::new(None), &codes, 0, None).await.success);
+}
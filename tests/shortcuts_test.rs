@@ -0,0 +1,89 @@
+use vox_gui::common::protocol::Message;
+use vox_gui::common::shortcuts::{SpecialKeyCombo, MENU_VIRTUAL_TERMINALS};
+
+fn key_sequence(combo: SpecialKeyCombo) -> Vec<(String, bool)> {
+    combo
+        .key_events()
+        .into_iter()
+        .map(|event| match event {
+            Message::KeyEvent { key, pressed, .. } => (key, pressed),
+            other => panic!("unexpected message {:?}", other),
+        })
+        .collect()
+}
+
+#[test]
+fn test_ctrl_alt_delete_sequence() {
+    let sequence = key_sequence(SpecialKeyCombo::CtrlAltDelete);
+    let expected = [
+        ("Control", true),
+        ("Alt", true),
+        ("Delete", true),
+        ("Delete", false),
+        ("Alt", false),
+        ("Control", false),
+    ];
+    
+    assert_eq!(sequence.len(), expected.len());
+    for ((key, pressed), (expected_key, expected_pressed)) in sequence.iter().zip(expected) {
+        assert_eq!(key, expected_key);
+        assert_eq!(*pressed, expected_pressed);
+    }
+}
+
+#[test]
+fn test_modifiers_reflect_held_keys() {
+    let events = SpecialKeyCombo::CtrlAltDelete.key_events();
+    
+    // The Delete press must carry Ctrl+Alt so the server can recognize the SAS
+    match &events[2] {
+        Message::KeyEvent { key, pressed: true, modifiers, .. } => {
+            assert_eq!(key, "Delete");
+            assert!(modifiers.ctrl && modifiers.alt);
+            assert!(!modifiers.shift && !modifiers.meta);
+        }
+        other => panic!("unexpected message {:?}", other),
+    }
+    
+    // Everything is released at the end
+    match events.last() {
+        Some(Message::KeyEvent { modifiers, .. }) => {
+            assert!(!modifiers.ctrl && !modifiers.alt && !modifiers.shift && !modifiers.meta);
+        }
+        other => panic!("unexpected message {:?}", other),
+    }
+}
+
+#[test]
+fn test_every_combo_is_balanced() {
+    for combo in SpecialKeyCombo::ALL {
+        let sequence = key_sequence(combo);
+        let presses = sequence.iter().filter(|(_, pressed)| *pressed).count();
+        assert_eq!(presses * 2, sequence.len(), "{:?}", combo);
+    }
+    
+    assert!(SpecialKeyCombo::CtrlAltDelete.requires_elevation());
+    assert!(!SpecialKeyCombo::CmdSpace.requires_elevation());
+}
+
+#[test]
+fn test_virtual_terminal_switch() {
+    let sequence = key_sequence(SpecialKeyCombo::SwitchVirtualTerminal(3));
+    let keys: Vec<&str> = sequence.iter().map(|(key, _)| key.as_str()).collect();
+    assert_eq!(keys, ["Control", "Alt", "F3", "F3", "Alt", "Control"]);
+    
+    // The F-key press carries both modifiers, like the SAS
+    match &SpecialKeyCombo::SwitchVirtualTerminal(3).key_events()[2] {
+        Message::KeyEvent { modifiers, .. } => assert!(modifiers.ctrl && modifiers.alt),
+        other => panic!("unexpected message {:?}", other),
+    }
+    
+    for terminal in MENU_VIRTUAL_TERMINALS {
+        assert_eq!(key_sequence(SpecialKeyCombo::SwitchVirtualTerminal(terminal)).len(), 6);
+    }
+    assert_eq!(SpecialKeyCombo::SwitchVirtualTerminal(12).keys(), ["Control", "Alt", "F12"]);
+    
+    // There is no F0 or F13
+    assert!(SpecialKeyCombo::SwitchVirtualTerminal(0).key_events().is_empty());
+    assert!(SpecialKeyCombo::SwitchVirtualTerminal(13).key_events().is_empty());
+}
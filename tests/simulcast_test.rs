@@ -0,0 +1,105 @@
+use anyhow::Result;
+use bytes::Bytes;
+use std::sync::{Arc, Mutex};
+use vox_gui::common::color::ColorSpace;
+use vox_gui::common::encoder::{EncodedFrame, EncoderSettings, EncoderTuning, EncoderType, VideoEncoder};
+use vox_gui::common::quality::{AdaptiveQualityController, QualityMode};
+use vox_gui::server::simulcast::{layer_for, Simulcast, DEFAULT_LAYERS};
+
+// Remembers the size and keyframe flag of every frame it is given
+struct RecordingEncoder {
+    settings: EncoderSettings,
+    frames: Arc<Mutex<Vec<(usize, bool)>>>,
+}
+
+impl VideoEncoder for RecordingEncoder {
+    fn encode_frame(&mut self, rgb_data: &[u8], force_keyframe: bool) -> Result<EncodedFrame> {
+        vox_gui::common::encoder::check_frame_len(rgb_data, self.settings.width, self.settings.height)?;
+        self.frames.lock().unwrap().push((rgb_data.len(), force_keyframe));
+        Ok(EncodedFrame { data: Bytes::from(vec![self.settings.width as u8]), is_keyframe: force_keyframe, timestamp: 0 })
+    }
+    
+    fn get_type(&self) -> EncoderType {
+        EncoderType::Hardware
+    }
+    
+    fn update_settings(&mut self, settings: EncoderSettings) -> Result<()> {
+        self.settings = settings;
+        Ok(())
+    }
+}
+
+fn settings(width: u32, height: u32) -> EncoderSettings {
+    EncoderSettings {
+        width,
+        height,
+        fps: 30,
+        bitrate: 1_000_000,
+        keyframe_interval: 60,
+        color_space: ColorSpace::for_resolution(width, height),
+        tuning: EncoderTuning::default(),
+    }
+}
+
+fn layer(quality: QualityMode, width: u32, height: u32) -> (QualityMode, EncoderSettings, Box<dyn VideoEncoder>, Arc<Mutex<Vec<(usize, bool)>>>) {
+    let frames = Arc::new(Mutex::new(Vec::new()));
+    let encoder = RecordingEncoder { settings: settings(width, height), frames: frames.clone() };
+    (quality, settings(width, height), Box::new(encoder), frames)
+}
+
+#[test]
+fn test_layer_for_picks_the_best_layer_within_the_bandwidth() {
+    let layers = [QualityMode::High, QualityMode::Low];
+    assert_eq!(layer_for(&layers, QualityMode::Ultra), 0);
+    assert_eq!(layer_for(&layers, QualityMode::High), 0);
+    assert_eq!(layer_for(&layers, QualityMode::Medium), 1);
+    assert_eq!(layer_for(&layers, QualityMode::Low), 1);
+    // Nothing is lean enough: the leanest there is
+    assert_eq!(layer_for(&layers, QualityMode::Minimal), 1);
+    
+    // Without simulcast everyone gets the main stream
+    assert_eq!(layer_for(&[QualityMode::High], QualityMode::Minimal), 0);
+    assert_eq!(layer_for(&[], QualityMode::High), 0);
+    assert!(DEFAULT_LAYERS.iter().all(|quality| quality.resolution_scale() < 1.0));
+}
+
+#[test]
+fn test_each_layer_encodes_its_own_size() {
+    let (quality, settings, encoder, frames) = layer(QualityMode::Low, 32, 16);
+    let mut simulcast = Simulcast::new(vec![(quality, settings, encoder)]);
+    assert_eq!(simulcast.qualities(), vec![QualityMode::Low]);
+    
+    let frame = vec![100u8; 64 * 32 * 3];
+    let encoded = simulcast.encode(&frame, 64, 32, true).unwrap();
+    assert_eq!(encoded.len(), 1);
+    assert_eq!((encoded[0].layer, encoded[0].width, encoded[0].height), (1, 32, 16));
+    assert!(encoded[0].frame.is_keyframe);
+    assert_eq!(*frames.lock().unwrap(), vec![(32 * 16 * 3, true)]);
+}
+
+#[test]
+fn test_two_viewers_are_routed_to_different_layers() {
+    let (low, low_settings, low_encoder, _) = layer(QualityMode::Low, 32, 16);
+    let mut simulcast = Simulcast::new(vec![(low, low_settings, low_encoder)]);
+    let layers = [QualityMode::High, QualityMode::Low];
+    
+    // One viewer on a fast link, one on a slow one
+    let mut fast = AdaptiveQualityController::new();
+    fast.force_quality(Some(QualityMode::High));
+    let mut slow = AdaptiveQualityController::new();
+    slow.force_quality(Some(QualityMode::Low));
+    let fast_layer = layer_for(&layers, fast.get_current_quality());
+    let slow_layer = layer_for(&layers, slow.get_current_quality());
+    assert_ne!(fast_layer, slow_layer);
+    
+    // The main stream is layer 0, at full size; the simulcast layers follow
+    let frame = vec![7u8; 64 * 32 * 3];
+    let mut sent: Vec<(usize, u32)> = vec![(0, 64)];
+    sent.extend(simulcast.encode(&frame, 64, 32, false).unwrap().iter().map(|layer| (layer.layer, layer.width)));
+    
+    let received = |viewer_layer: usize| -> Vec<u32> {
+        sent.iter().filter(|(layer, _)| *layer == viewer_layer).map(|(_, width)| *width).collect()
+    };
+    assert_eq!(received(fast_layer), vec![64]);
+    assert_eq!(received(slow_layer), vec![32]);
+}
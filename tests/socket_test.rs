@@ -0,0 +1,41 @@
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use vox_gui::common::socket::{self, SocketBuffers};
+
+#[tokio::test]
+async fn test_established_connections_disable_nagle() {
+    let listener = socket::listen("127.0.0.1:0", SocketBuffers::default()).await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    
+    let client = socket::connect(&addr, SocketBuffers::default()).await.unwrap();
+    let (accepted, _) = listener.accept().await.unwrap();
+    socket::configure(&accepted).unwrap();
+    
+    assert!(client.nodelay().unwrap());
+    assert!(accepted.nodelay().unwrap());
+}
+
+#[tokio::test]
+async fn test_tuned_buffers_still_carry_data() {
+    let buffers = SocketBuffers { send: Some(256 * 1024), recv: Some(256 * 1024) };
+    let listener = socket::listen("127.0.0.1:0", buffers).await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    
+    let mut client = socket::connect(&addr, buffers).await.unwrap();
+    let (mut accepted, _) = listener.accept().await.unwrap();
+    
+    let payload = vec![7u8; 1024 * 1024];
+    let sent = payload.clone();
+    let writer = tokio::spawn(async move { client.write_all(&sent).await });
+    let mut received = vec![0u8; payload.len()];
+    accepted.read_exact(&mut received).await.unwrap();
+    writer.await.unwrap().unwrap();
+    assert_eq!(received, payload);
+}
+
+#[tokio::test]
+async fn test_connect_reports_unreachable_servers() {
+    // Bound but not listening, so the connection is refused
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    assert!(socket::connect(&format!("127.0.0.1:{}", port), SocketBuffers::default()).await.is_err());
+    assert!(socket::connect("not an address", SocketBuffers::default()).await.is_err());
+}
@@ -0,0 +1,48 @@
+use bytes::Bytes;
+use vox_gui::client::stream_decoder::StreamDecoder;
+use vox_gui::common::frame_processor::{FrameProcessor, FrameType, ProcessedFrame};
+
+fn frame(width: u32, height: u32, shade: u8) -> Vec<u8> {
+    vec![shade; width as usize * height as usize * 3]
+}
+
+// Plays a keyframe through the decoder the way the client applies a stream
+fn receive_keyframe(stream: &mut StreamDecoder, width: u32, height: u32, shade: u8) {
+    let tiles = FrameProcessor::new(width, height).split_into_tiles(&frame(width, height, shade)).unwrap();
+    let keyframe = ProcessedFrame { frame_type: FrameType::KeyFrame, data: Bytes::new(), width, height, tiles: Some(tiles) };
+    stream.resize(width, height);
+    let current = stream.current_frame.get_or_insert_with(|| frame(width, height, 0));
+    stream.frame_processor.as_ref().unwrap().apply_delta(current, &keyframe).unwrap();
+}
+
+#[test]
+fn test_reconnect_at_another_resolution_starts_clean() {
+    let mut stream = StreamDecoder::new();
+    assert!(stream.is_empty());
+    
+    receive_keyframe(&mut stream, 128, 64, 10);
+    assert_eq!(stream.frame_processor.as_ref().unwrap().dimensions(), (128, 64));
+    
+    // Disconnect
+    stream.reset();
+    assert!(stream.is_empty());
+    
+    // The next server streams a smaller display
+    receive_keyframe(&mut stream, 64, 32, 200);
+    assert_eq!(stream.frame_processor.as_ref().unwrap().dimensions(), (64, 32));
+    assert_eq!(stream.current_frame.as_deref(), Some(&frame(64, 32, 200)[..]));
+}
+
+#[test]
+fn test_resize_replaces_only_a_mismatched_processor() {
+    let mut stream = StreamDecoder::new();
+    stream.resize(128, 64);
+    assert_eq!(stream.frame_processor.as_ref().unwrap().dimensions(), (128, 64));
+    
+    stream.resize(128, 64);
+    assert_eq!(stream.frame_processor.as_ref().unwrap().tile_count(), 2);
+    
+    stream.resize(256, 64);
+    assert_eq!(stream.frame_processor.as_ref().unwrap().dimensions(), (256, 64));
+    assert_eq!(stream.frame_processor.as_ref().unwrap().tile_count(), 4);
+}
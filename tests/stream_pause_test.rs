@@ -0,0 +1,93 @@
+mod common;
+
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use vox_gui::client::connection::Connection;
+use vox_gui::client::stream_pause::{StreamPause, PAUSE_DELAY};
+use vox_gui::common::auth::AccessCode;
+use vox_gui::common::protocol::Message;
+
+#[test]
+fn test_minimized_window_pauses_after_the_delay() {
+    let mut pause = StreamPause::new();
+    let start = Instant::now();
+    assert!(pause.observe(true, start).is_none());
+    assert_eq!(pause.pause_due_in(start), None);
+    
+    assert!(pause.observe(false, start).is_none());
+    assert_eq!(pause.pause_due_in(start + Duration::from_millis(500)), Some(PAUSE_DELAY - Duration::from_millis(500)));
+    assert!(pause.observe(false, start + PAUSE_DELAY - Duration::from_millis(1)).is_none());
+    assert!(matches!(pause.observe(false, start + PAUSE_DELAY), Some(Message::StopStream)));
+    assert!(pause.is_paused());
+    
+    // Said once, however long it stays minimized
+    assert!(pause.observe(false, start + PAUSE_DELAY * 10).is_none());
+    assert_eq!(pause.pause_due_in(start + PAUSE_DELAY * 10), None);
+    
+    assert!(matches!(pause.observe(true, start + PAUSE_DELAY * 11), Some(Message::StartStream)));
+    assert!(!pause.is_paused());
+    assert!(pause.observe(true, start + PAUSE_DELAY * 12).is_none());
+}
+
+#[test]
+fn test_brief_minimizing_does_not_pause() {
+    let mut pause = StreamPause::new();
+    let mut now = Instant::now();
+    
+    // Flicking the window away and back repeatedly never stops the stream
+    for _ in 0..10 {
+        assert!(pause.observe(false, now).is_none());
+        now += PAUSE_DELAY / 2;
+        assert!(pause.observe(true, now).is_none());
+        now += Duration::from_millis(100);
+    }
+    assert!(!pause.is_paused());
+    
+    // And the delay starts over after each restore
+    assert!(pause.observe(false, now).is_none());
+    assert!(pause.observe(false, now + PAUSE_DELAY / 2).is_none());
+    
+    pause.reset();
+    assert_eq!(pause.pause_due_in(now), None);
+}
+
+async fn next_video(rx: &mut mpsc::UnboundedReceiver<Message>, within: Duration) -> Option<Message> {
+    tokio::time::timeout(within, async {
+        loop {
+            match rx.recv().await {
+                Some(message) if message.is_video() => return message,
+                Some(_) => {}
+                None => panic!("connection closed"),
+            }
+        }
+    })
+    .await
+    .ok()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_paused_session_gets_no_frames_then_a_keyframe() {
+    let access_code = AccessCode::generate();
+    let code = access_code.code.clone();
+    
+    let server = common::test_server(access_code);
+    let addr = common::serve(server).await;
+    
+    let (mut connection, _, _) = Connection::new();
+    let (mut rx, tx) = connection.connect(&addr, &code).await.unwrap();
+    assert!(next_video(&mut rx, Duration::from_secs(5)).await.is_some());
+    
+    // Frames already on their way still arrive, then nothing
+    tx.send(Message::StopStream).unwrap();
+    let drained = tokio::time::timeout(Duration::from_secs(3), async {
+        while next_video(&mut rx, Duration::from_millis(300)).await.is_some() {}
+    });
+    assert!(drained.await.is_ok(), "frames kept coming after StopStream");
+    assert!(next_video(&mut rx, Duration::from_secs(1)).await.is_none());
+    
+    tx.send(Message::StartStream).unwrap();
+    match next_video(&mut rx, Duration::from_secs(5)).await {
+        Some(Message::ScreenFrame { .. } | Message::KeyFrameChunk { .. }) => {}
+        other => panic!("expected a keyframe, got {:?}", other),
+    }
+}
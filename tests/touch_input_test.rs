@@ -0,0 +1,165 @@
+use vox_gui::client::resend::is_droppable;
+use vox_gui::client::touch::{TouchInput, PINCH_STEP};
+use vox_gui::client::view::remote_touch_phase;
+use vox_gui::common::input_channel::is_sequenced;
+use vox_gui::common::protocol::{Message, MouseButton, TouchGesture, TouchPhase};
+use vox_gui::server::input_handler::{gesture_wheel_steps, touch_as_mouse};
+
+#[test]
+fn test_one_finger_is_sent_as_a_touch() {
+    let mut touch = TouchInput::new();
+    
+    let down = touch.event(1, TouchPhase::Start, (100, 50), Some(0.4));
+    assert!(matches!(down[..], [Message::Touch { id: 1, phase: TouchPhase::Start, x: 100, y: 50, pressure: Some(p) }] if p == 0.4));
+    assert!(touch.is_touching());
+    
+    let moved = touch.event(1, TouchPhase::Move, (120, 60), Some(1.5));
+    // Pressure beyond the device's range is clamped
+    assert!(matches!(moved[..], [Message::Touch { phase: TouchPhase::Move, x: 120, y: 60, pressure: Some(p), .. }] if p == 1.0));
+    
+    let up = touch.event(1, TouchPhase::End, (120, 60), None);
+    assert!(matches!(up[..], [Message::Touch { phase: TouchPhase::End, pressure: None, .. }]));
+    assert!(!touch.is_touching());
+}
+
+#[test]
+fn test_second_finger_cancels_the_first_and_starts_a_gesture() {
+    let mut touch = TouchInput::new();
+    touch.event(1, TouchPhase::Start, (100, 100), None);
+    
+    let second = touch.event(2, TouchPhase::Start, (200, 100), None);
+    assert!(matches!(second[..], [Message::Touch { id: 1, phase: TouchPhase::Cancel, x: 100, y: 100, .. }]));
+    
+    // Small movements are gathered until they make a step
+    assert!(touch.event(1, TouchPhase::Move, (100, 105), None).is_empty());
+    
+    // Both fingers moving up scroll, reported at their center
+    touch.event(1, TouchPhase::Move, (100, 70), None);
+    let scrolled = touch.event(2, TouchPhase::Move, (200, 70), None);
+    match scrolled[..] {
+        [Message::Gesture { gesture: TouchGesture::Scroll { delta_x, delta_y }, x, y }] => {
+            assert_eq!(delta_x, 0.0);
+            assert_eq!(delta_y, -30.0);
+            assert_eq!((x, y), (150, 70));
+        }
+        ref other => panic!("unexpected messages {:?}", other),
+    }
+}
+
+#[test]
+fn test_spreading_fingers_pinches() {
+    let mut touch = TouchInput::new();
+    touch.event(1, TouchPhase::Start, (100, 100), None);
+    touch.event(2, TouchPhase::Start, (200, 100), None);
+    
+    // Moving apart symmetrically keeps the center still, so only pinches go out
+    let mut messages = touch.event(1, TouchPhase::Move, (75, 100), None);
+    messages.extend(touch.event(2, TouchPhase::Move, (225, 100), None));
+    let scales: Vec<f32> = messages.iter()
+        .map(|message| match message {
+            Message::Gesture { gesture: TouchGesture::Pinch { scale }, .. } => *scale,
+            other => panic!("unexpected message {:?}", other),
+        })
+        .collect();
+    assert_eq!(scales.len(), 2);
+    assert!(scales.iter().all(|scale| scale - 1.0 >= PINCH_STEP));
+    assert!((scales.iter().product::<f32>() - 1.5).abs() < 1e-5);
+    assert!(matches!(messages.last(), Some(Message::Gesture { x: 150, y: 100, .. })));
+}
+
+#[test]
+fn test_gesture_lasts_until_every_finger_lifts() {
+    let mut touch = TouchInput::new();
+    touch.event(1, TouchPhase::Start, (100, 100), None);
+    touch.event(2, TouchPhase::Start, (200, 100), None);
+    
+    // Neither lifting one finger nor moving the other taps or drags
+    assert!(touch.event(2, TouchPhase::End, (200, 100), None).is_empty());
+    assert!(touch.event(1, TouchPhase::Move, (20, 20), None).is_empty());
+    assert!(touch.event(3, TouchPhase::Start, (50, 50), None).is_empty());
+    assert!(touch.event(1, TouchPhase::End, (20, 20), None).is_empty());
+    assert!(touch.event(3, TouchPhase::End, (50, 50), None).is_empty());
+    assert!(!touch.is_touching());
+    
+    // Then a single finger touches again
+    assert!(matches!(touch.event(4, TouchPhase::Start, (10, 10), None)[..], [Message::Touch { id: 4, phase: TouchPhase::Start, .. }]));
+}
+
+#[test]
+fn test_fingers_that_never_started_are_ignored() {
+    let mut touch = TouchInput::new();
+    assert!(touch.event(9, TouchPhase::Move, (10, 10), None).is_empty());
+    assert!(touch.event(9, TouchPhase::End, (10, 10), None).is_empty());
+}
+
+#[test]
+fn test_release_all_cancels_the_touch_down() {
+    let mut touch = TouchInput::new();
+    touch.event(1, TouchPhase::Start, (5, 6), None);
+    touch.event(1, TouchPhase::Move, (7, 8), None);
+    
+    let released = touch.release_all();
+    assert!(matches!(released[..], [Message::Touch { id: 1, phase: TouchPhase::Cancel, x: 7, y: 8, .. }]));
+    assert!(!touch.is_touching());
+    assert!(touch.release_all().is_empty());
+}
+
+#[test]
+fn test_touch_plays_as_the_left_button_without_touch_injection() {
+    assert!(matches!(
+        touch_as_mouse(TouchPhase::Start, 3, 4)[..],
+        [Message::MouseMove { x: 3, y: 4 }, Message::MouseClick { button: MouseButton::Left, pressed: true, x: 3, y: 4 }]
+    ));
+    assert!(matches!(touch_as_mouse(TouchPhase::Move, 5, 6)[..], [Message::MouseMove { x: 5, y: 6 }]));
+    for phase in [TouchPhase::End, TouchPhase::Cancel] {
+        assert!(matches!(
+            touch_as_mouse(phase, 5, 6)[..],
+            [Message::MouseClick { button: MouseButton::Left, pressed: false, x: 5, y: 6 }]
+        ));
+    }
+}
+
+#[test]
+fn test_gestures_play_as_wheel_steps() {
+    // Dragging up scrolls down, like on a touchscreen
+    assert_eq!(gesture_wheel_steps(TouchGesture::Scroll { delta_x: 0.0, delta_y: -40.0 }), (0, 2, false));
+    assert_eq!(gesture_wheel_steps(TouchGesture::Scroll { delta_x: 20.0, delta_y: 0.0 }), (-1, 0, false));
+    // Any movement makes at least one step
+    assert_eq!(gesture_wheel_steps(TouchGesture::Scroll { delta_x: 0.0, delta_y: 3.0 }), (0, -1, false));
+    
+    // Spreading zooms in (wheel up with the zoom modifier), pinching zooms out
+    let (_, zoom_in, modified) = gesture_wheel_steps(TouchGesture::Pinch { scale: 1.21 });
+    assert_eq!((zoom_in, modified), (-2, true));
+    let (_, zoom_out, _) = gesture_wheel_steps(TouchGesture::Pinch { scale: 0.9 });
+    assert_eq!(zoom_out, 1);
+    assert_eq!(gesture_wheel_steps(TouchGesture::Pinch { scale: 0.0 }), (0, 0, true));
+}
+
+#[test]
+fn test_touch_delivery() {
+    let touch = |phase| Message::Touch { id: 1, phase, x: 0, y: 0, pressure: None };
+    
+    // Putting a finger down and lifting it must arrive; the moves in between may be lost
+    assert!(is_sequenced(&touch(TouchPhase::Start)));
+    assert!(is_sequenced(&touch(TouchPhase::End)));
+    assert!(is_sequenced(&touch(TouchPhase::Cancel)));
+    assert!(!is_sequenced(&touch(TouchPhase::Move)));
+    assert!(is_droppable(&touch(TouchPhase::Move)));
+    assert!(!is_droppable(&touch(TouchPhase::Start)));
+    assert!(is_droppable(&Message::Gesture { gesture: TouchGesture::Pinch { scale: 2.0 }, x: 0, y: 0 }));
+}
+
+#[test]
+fn test_egui_touch_phases_map_one_to_one() {
+    assert_eq!(remote_touch_phase(egui::TouchPhase::Start), TouchPhase::Start);
+    assert_eq!(remote_touch_phase(egui::TouchPhase::Move), TouchPhase::Move);
+    assert_eq!(remote_touch_phase(egui::TouchPhase::End), TouchPhase::End);
+    assert_eq!(remote_touch_phase(egui::TouchPhase::Cancel), TouchPhase::Cancel);
+}
+
+#[test]
+fn test_touch_round_trips() {
+    let message = Message::Touch { id: 7, phase: TouchPhase::Move, x: 11, y: 12, pressure: Some(0.25) };
+    let decoded = Message::deserialize(&message.serialize().unwrap()).unwrap();
+    assert!(matches!(decoded, Message::Touch { id: 7, phase: TouchPhase::Move, x: 11, y: 12, pressure: Some(p) } if p == 0.25));
+}
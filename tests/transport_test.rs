@@ -0,0 +1,103 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{Mutex, Notify};
+use vox_gui::common::crypto::{open_message, CryptoSession, Encryptor, RekeyPolicy};
+use vox_gui::common::protocol::{EncodingType, Message};
+use vox_gui::common::quality::QualityMode;
+use vox_gui::common::transport::{is_closed, FrameRoute, QuicConnection, QuicTransport, TcpConnection, Transport};
+use vox_gui::server::send_queue::{Priority, SendQueue, SEND_QUEUE_CAPACITY};
+use vox_gui::server::session::{Session, TransportSender};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A connected loopback pair: (server side, client side).
+async fn tcp_pair() -> (TcpConnection, TcpConnection) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let client = TcpStream::connect(listener.local_addr().unwrap()).await.unwrap();
+    let (server, _) = listener.accept().await.unwrap();
+    (TcpConnection::new(server).unwrap(), TcpConnection::new(client).unwrap())
+}
+
+async fn quic_pair() -> (QuicConnection, QuicConnection) {
+    let server = QuicTransport::new_server("127.0.0.1:0".parse().unwrap()).await.unwrap();
+    let addr = server.local_addr().unwrap();
+    let accepted = tokio::spawn(async move { server.accept().await.unwrap() });
+    
+    let client = QuicTransport::new_client().await.unwrap();
+    let client_connection = tokio::time::timeout(TIMEOUT, client.connect(addr, "localhost"))
+        .await
+        .unwrap()
+        .unwrap();
+    let server_connection = tokio::time::timeout(TIMEOUT, accepted).await.unwrap().unwrap();
+    
+    (server_connection, client_connection)
+}
+
+async fn recv<T: Transport>(transport: &T) -> Vec<u8> {
+    tokio::time::timeout(TIMEOUT, transport.recv()).await.unwrap().unwrap()
+}
+
+/// Messages sent by a session arrive whole and in order, Rekey announcements included,
+/// whichever transport carries them.
+async fn session_round_trip<T: Transport + 'static>(server: T, client: T) {
+    let secret = [3u8; 32];
+    let policy = RekeyPolicy { max_messages: 2, max_bytes: u64::MAX };
+    let crypto: Box<dyn Encryptor> = Box::new(CryptoSession::from_shared_secret(&secret).unwrap().with_rekey_policy(policy));
+    let peer = CryptoSession::from_shared_secret(&secret).unwrap();
+    
+    let queue = Arc::new(SendQueue::new(SEND_QUEUE_CAPACITY));
+    let sender = TransportSender::spawn(Arc::new(server), queue, Arc::new(Notify::new()));
+    let session = Session::new(Arc::new(Mutex::new(crypto)), Arc::new(sender), (QualityMode::Low, QualityMode::High));
+    
+    let frame = vec![0x5Au8; 300 * 1024];
+    session.send(&Message::Ping { timestamp: 1 }, Priority::Control).await.unwrap();
+    session.send(&Message::Ping { timestamp: 2 }, Priority::Control).await.unwrap();
+    let screen = Message::ScreenFrame { sequence: 0, timestamp: 3, width: 640, height: 480, data: frame.clone(), encoding: EncodingType::H264, checksum: None };
+    session.send(&screen, Priority::Video).await.unwrap();
+    
+    let mut received = Vec::new();
+    while received.len() < 3 {
+        if let Some(message) = open_message(&peer, &recv(&client).await).unwrap() {
+            received.push(message);
+        }
+    }
+    assert!(matches!(received[0], Message::Ping { timestamp: 1 }));
+    assert!(matches!(received[1], Message::Ping { timestamp: 2 }));
+    assert!(matches!(&received[2], Message::ScreenFrame { data, timestamp: 3, .. } if *data == frame));
+}
+
+#[tokio::test]
+async fn test_session_over_tcp() {
+    let (server, client) = tcp_pair().await;
+    session_round_trip(server, client).await;
+}
+
+#[tokio::test]
+async fn test_session_over_quic() {
+    let (server, client) = quic_pair().await;
+    session_round_trip(server, client).await;
+}
+
+#[tokio::test]
+async fn test_tcp_sends_datagrams_reliably() {
+    let (server, client) = tcp_pair().await;
+    assert_eq!(server.send_datagram(b"frame").await.unwrap(), FrameRoute::Stream);
+    assert_eq!(recv(&client).await, b"frame");
+    
+    // Both directions work, and a closed peer ends recv
+    client.send(b"reply").await.unwrap();
+    assert_eq!(recv(&server).await, b"reply");
+    drop(client);
+    let closed = tokio::time::timeout(TIMEOUT, server.recv()).await.unwrap().unwrap_err();
+    assert!(is_closed(&closed), "{:#}", closed);
+}
+
+#[tokio::test]
+async fn test_quic_sends_small_datagrams_unreliably() {
+    let (server, client) = quic_pair().await;
+    let route = Transport::send_datagram(&server, b"frame").await.unwrap();
+    assert_eq!(route, FrameRoute::Datagram);
+    let datagram = tokio::time::timeout(TIMEOUT, client.receive_datagram()).await.unwrap().unwrap();
+    assert_eq!(&datagram[..], b"frame");
+}
@@ -0,0 +1,47 @@
+use egui::{Pos2, Rect, Vec2};
+use vox_gui::client::view::{remote_position, ClientPreferences, ViewMode};
+
+#[test]
+fn test_scale_per_mode() {
+    let image = Vec2::new(3840.0, 2160.0);
+    let window = Vec2::new(1920.0, 1200.0);
+    
+    // Fit is limited by width, Fill by height
+    assert_eq!(ViewMode::Fit.scale(image, window, 1.0), 0.5);
+    assert!((ViewMode::Fill.scale(image, window, 1.0) - 1200.0 / 2160.0).abs() < 1e-6);
+    
+    // Actual size maps one remote pixel to one physical pixel
+    assert_eq!(ViewMode::ActualSize.scale(image, window, 1.0), 1.0);
+    assert_eq!(ViewMode::ActualSize.scale(image, window, 2.0), 0.5);
+}
+
+#[test]
+fn test_remote_position_scaled_image() {
+    // 1920x1080 remote screen drawn at half size, offset by the toolbar
+    let rect = Rect::from_min_size(Pos2::new(0.0, 40.0), Vec2::new(960.0, 540.0));
+    
+    assert_eq!(remote_position(Pos2::new(0.0, 40.0), rect, (1920, 1080)), Some((0, 0)));
+    assert_eq!(remote_position(Pos2::new(480.0, 310.0), rect, (1920, 1080)), Some((960, 540)));
+    assert_eq!(remote_position(Pos2::new(960.0, 580.0), rect, (1920, 1080)), Some((1919, 1079)));
+    assert_eq!(remote_position(Pos2::new(961.0, 100.0), rect, (1920, 1080)), None);
+}
+
+#[test]
+fn test_remote_position_scrolled_actual_size() {
+    // Scrolled 500 points right and 300 down: the image starts off-screen
+    let rect = Rect::from_min_size(Pos2::new(-500.0, -300.0), Vec2::new(2560.0, 1440.0));
+    
+    assert_eq!(remote_position(Pos2::new(10.5, 20.25), rect, (2560, 1440)), Some((510, 320)));
+}
+
+#[test]
+fn test_preferences_default_to_fit_and_round_trip() {
+    assert_eq!(ClientPreferences::default().view_mode, ViewMode::Fit);
+    
+    let preferences = ClientPreferences { view_mode: ViewMode::ActualSize, ..ClientPreferences::default() };
+    let json = serde_json::to_string(&preferences).unwrap();
+    assert_eq!(serde_json::from_str::<ClientPreferences>(&json).unwrap(), preferences);
+    
+    // Files from older versions without the field still load
+    assert_eq!(serde_json::from_str::<ClientPreferences>("{}").unwrap(), ClientPreferences::default());
+}
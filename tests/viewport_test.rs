@@ -0,0 +1,133 @@
+use std::time::{Duration, Instant};
+use egui::Vec2;
+use vox_gui::client::view::ViewMode;
+use vox_gui::common::encoder::EncoderTuning;
+use vox_gui::common::frame_processor::FrameType;
+use vox_gui::common::viewport::{combined_viewport, scaled_size, viewport_scale, ViewportReporter, MIN_VIEWPORT_SCALE, VIEWPORT_DEBOUNCE};
+use vox_gui::server::frame_source::TestPatternSource;
+use vox_gui::server::screen_capture::{resize_rgb, CapturedFrame, ScreenCapture, DEFAULT_BITRATE};
+
+#[test]
+fn test_frames_shrink_to_fit_the_viewport() {
+    // Limited by the narrower fit, keeping the aspect ratio
+    assert_eq!(viewport_scale((1920, 1080), Some((960, 1080))), 0.5);
+    assert_eq!(viewport_scale((1920, 1080), Some((1920, 540))), 0.5);
+    assert_eq!(scaled_size((1920, 1080), 0.5), (960, 540));
+    
+    // Never scaled up, and a missing or empty viewport keeps them whole
+    assert_eq!(viewport_scale((1280, 720), Some((3840, 2160))), 1.0);
+    assert_eq!(viewport_scale((1280, 720), None), 1.0);
+    assert_eq!(viewport_scale((1280, 720), Some((0, 0))), 1.0);
+    assert_eq!(scaled_size((1281, 721), 1.0), (1281, 721));
+    
+    // A tiny window doesn't turn frames into thumbnails
+    assert_eq!(viewport_scale((1920, 1080), Some((16, 9))), MIN_VIEWPORT_SCALE);
+}
+
+#[test]
+fn test_scaled_sizes_are_even() {
+    let (width, height) = scaled_size((1366, 768), viewport_scale((1366, 768), Some((1000, 1000))));
+    assert_eq!((width % 2, height % 2), (0, 0));
+    assert!(width <= 1000 && height <= 1000);
+    assert_eq!(scaled_size((3, 3), 0.25), (2, 2));
+}
+
+#[test]
+fn test_shared_display_fits_the_largest_viewer() {
+    assert_eq!(combined_viewport([Some((800, 600)), Some((1024, 500))]), Some((1024, 600)));
+    // One viewer without a viewport wants full frames
+    assert_eq!(combined_viewport([Some((800, 600)), None]), None);
+    assert_eq!(combined_viewport(Vec::new()), None);
+}
+
+#[test]
+fn test_reporter_debounces_resizes() {
+    let start = Instant::now();
+    let mut reporter = ViewportReporter::new();
+    
+    // Full size is what a new session gets anyway
+    assert_eq!(reporter.update(None, start), None);
+    // The first size goes out right away
+    assert_eq!(reporter.update(Some((800, 600)), start), Some((800, 600)));
+    assert_eq!(reporter.update(Some((800, 600)), start), None);
+    
+    // A drag-resize is only reported once it stops
+    let mut now = start;
+    for width in [820, 840, 860] {
+        now += Duration::from_millis(50);
+        assert_eq!(reporter.update(Some((width, 600)), now), None);
+    }
+    assert!(reporter.is_pending());
+    assert_eq!(reporter.update(Some((860, 600)), now + VIEWPORT_DEBOUNCE), Some((860, 600)));
+    assert!(!reporter.is_pending());
+    
+    // Going back to full size lifts the limit with an empty viewport
+    now += VIEWPORT_DEBOUNCE;
+    assert_eq!(reporter.update(None, now), None);
+    assert_eq!(reporter.update(None, now + VIEWPORT_DEBOUNCE), Some((0, 0)));
+    
+    // Resizing back before the debounce ends sends nothing
+    now += VIEWPORT_DEBOUNCE * 2;
+    assert_eq!(reporter.update(Some((640, 480)), now), None);
+    assert_eq!(reporter.update(None, now + Duration::from_millis(10)), None);
+    assert!(!reporter.is_pending());
+    
+    reporter.reset();
+    assert_eq!(reporter.update(Some((640, 480)), now), Some((640, 480)));
+}
+
+#[test]
+fn test_only_fit_mode_reports_a_viewport() {
+    let available = Vec2::new(800.0, 450.0);
+    assert_eq!(ViewMode::Fit.viewport(available, 2.0), Some((1600, 900)));
+    assert_eq!(ViewMode::ActualSize.viewport(available, 2.0), None);
+    assert_eq!(ViewMode::Fill.viewport(available, 2.0), None);
+}
+
+#[test]
+fn test_resize_rgb_samples_the_source() {
+    // 4x2: left half red, right half blue
+    let mut rgb = Vec::new();
+    for _ in 0..2 {
+        for x in 0..4 {
+            rgb.extend_from_slice(if x < 2 { &[255, 0, 0] } else { &[0, 0, 255] });
+        }
+    }
+    let resized = resize_rgb(&rgb, 4, 2, 2, 1).unwrap();
+    assert_eq!(resized, vec![255, 0, 0, 0, 0, 255]);
+    assert!(resize_rgb(&rgb, 4, 2, 0, 1).is_err());
+    assert!(resize_rgb(&rgb[1..], 4, 2, 2, 1).is_err());
+}
+
+fn next_frame(capture: &mut ScreenCapture) -> CapturedFrame {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline {
+        if let Some(frame) = capture.capture_frame().unwrap() {
+            return frame;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    panic!("no frame captured");
+}
+
+#[test]
+fn test_capture_sends_frames_at_the_viewport_size() {
+    let source = Box::new(TestPatternSource::new(64, 64));
+    let mut capture = ScreenCapture::from_source(source, 60, DEFAULT_BITRATE, EncoderTuning::default()).unwrap();
+    let frame = next_frame(&mut capture);
+    assert_eq!((frame.width, frame.height), (64, 64));
+    
+    // Scaled down, starting over with a keyframe the client can size its buffer from
+    capture.set_viewport(Some((32, 48)));
+    assert_eq!(capture.output_scale(), 0.5);
+    assert_eq!(capture.output_size(), (32, 32));
+    let frame = next_frame(&mut capture);
+    assert_eq!((frame.width, frame.height), (32, 32));
+    assert_eq!(frame.frame_type, FrameType::KeyFrame);
+    
+    // And back to full size
+    capture.set_viewport(None);
+    let frame = next_frame(&mut capture);
+    assert_eq!((frame.width, frame.height), (64, 64));
+    assert_eq!(frame.frame_type, FrameType::KeyFrame);
+}
@@ -0,0 +1,119 @@
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use vox_gui::server::frame_source::FrameSource;
+use vox_gui::server::vsync::{vsync_supported, RefreshClock, TimerClock, VsyncSource};
+
+const WIDTH: usize = 4;
+const HEIGHT: usize = 8;
+
+// A display that draws each frame top to bottom: every row of frame n is filled with n.
+// Rows are drawn as time passes, so a frame read between refreshes is part old, part new.
+struct Scanout {
+    frame: u8,
+    rows_drawn: usize,
+    pixels: Vec<u8>,
+}
+
+impl Scanout {
+    fn new() -> Rc<RefCell<Self>> {
+        Rc::new(RefCell::new(Self { frame: 0, rows_drawn: HEIGHT, pixels: vec![0; WIDTH * HEIGHT * 4] }))
+    }
+    
+    // Draw `rows` more rows, starting the next frame once this one is finished
+    fn advance(&mut self, mut rows: usize) {
+        while rows > 0 {
+            if self.rows_drawn == HEIGHT {
+                self.frame = self.frame.wrapping_add(1);
+                self.rows_drawn = 0;
+            }
+            let row = self.rows_drawn * WIDTH * 4;
+            self.pixels[row..row + WIDTH * 4].fill(self.frame);
+            self.rows_drawn += 1;
+            rows -= 1;
+        }
+    }
+}
+
+// Reads whatever is on the display; the display moves on by half a frame meanwhile
+struct ScanoutSource(Rc<RefCell<Scanout>>);
+
+impl FrameSource for ScanoutSource {
+    fn frame(&mut self) -> io::Result<Vec<u8>> {
+        let mut scanout = self.0.borrow_mut();
+        let pixels = scanout.pixels.clone();
+        scanout.advance(HEIGHT / 2);
+        Ok(pixels)
+    }
+    
+    fn width(&self) -> usize {
+        WIDTH
+    }
+    
+    fn height(&self) -> usize {
+        HEIGHT
+    }
+}
+
+// Signals the display's vblank: it returns once the frame being drawn is complete
+struct ScanoutClock(Rc<RefCell<Scanout>>);
+
+impl RefreshClock for ScanoutClock {
+    fn wait_for_refresh(&mut self) -> io::Result<()> {
+        let mut scanout = self.0.borrow_mut();
+        let remaining = HEIGHT - scanout.rows_drawn;
+        scanout.advance(remaining);
+        Ok(())
+    }
+    
+    fn name(&self) -> &'static str {
+        "scanout"
+    }
+}
+
+// Every row comes from the same display frame
+fn is_complete(frame: &[u8]) -> bool {
+    frame.iter().all(|&value| value == frame[0])
+}
+
+#[test]
+fn test_unaligned_capture_tears() {
+    let mut source = ScanoutSource(Scanout::new());
+    source.frame().unwrap();
+    let torn = (0..10).filter(|_| !is_complete(&source.frame().unwrap())).count();
+    assert!(torn > 0);
+}
+
+#[test]
+fn test_vsync_aligned_frames_are_complete() {
+    let scanout = Scanout::new();
+    let mut source = VsyncSource::new(Box::new(ScanoutSource(scanout.clone())), Box::new(ScanoutClock(scanout)));
+    assert_eq!((source.width(), source.height()), (WIDTH, HEIGHT));
+    
+    let mut last = None;
+    let mut buffer = Vec::new();
+    for _ in 0..10 {
+        source.frame_into(&mut buffer).unwrap();
+        assert!(is_complete(&buffer), "torn frame {:?}", buffer);
+        // And each is a newer display frame than the last
+        assert_ne!(last, Some(buffer[0]));
+        last = Some(buffer[0]);
+    }
+}
+
+#[test]
+fn test_timer_clock_paces_to_its_rate() {
+    let mut clock = TimerClock::new(100);
+    let start = Instant::now();
+    for _ in 0..6 {
+        clock.wait_for_refresh().unwrap();
+    }
+    // The first tick is immediate, five more at 10 ms each
+    assert!(start.elapsed() >= Duration::from_millis(50), "{:?}", start.elapsed());
+}
+
+#[test]
+fn test_vsync_support_by_platform() {
+    assert_eq!(vsync_supported(), cfg!(any(target_os = "macos", target_os = "windows")));
+}
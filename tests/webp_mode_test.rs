@@ -0,0 +1,113 @@
+use std::time::{Duration, Instant};
+use vox_gui::common::color::ColorSpace;
+use vox_gui::common::encoder::{EncoderSettings, EncoderTuning, SoftwareEncoder, VideoEncoder, WebpMode};
+use vox_gui::common::quality::QualityMode;
+
+const WIDTH: u32 = 256;
+const HEIGHT: u32 = 128;
+
+fn settings(webp_mode: WebpMode) -> EncoderSettings {
+    EncoderSettings {
+        width: WIDTH,
+        height: HEIGHT,
+        fps: 30,
+        bitrate: 5_000_000,
+        keyframe_interval: 60,
+        color_space: ColorSpace::for_resolution(WIDTH, HEIGHT),
+        tuning: EncoderTuning { webp_mode, ..EncoderTuning::default() },
+    }
+}
+
+// One-pixel dark strokes on a white background, like small text in an editor
+fn text_frame() -> Vec<u8> {
+    let mut rgb = vec![255u8; (WIDTH * HEIGHT * 3) as usize];
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let glyph = (x / 6 + y / 10) % 3 != 0 && (x % 6 == 1 || y % 10 == 4 || (x + y) % 7 == 0);
+            if glyph && y % 10 < 8 {
+                let i = ((y * WIDTH + x) * 3) as usize;
+                rgb[i..i + 3].copy_from_slice(&[30, 30, 40]);
+            }
+        }
+    }
+    rgb
+}
+
+// Smooth gradients with a little noise, like a photo or video
+fn photo_frame() -> Vec<u8> {
+    let mut seed = 0x2545F491u32;
+    let mut rgb = Vec::with_capacity((WIDTH * HEIGHT * 3) as usize);
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            seed ^= seed << 13;
+            seed ^= seed >> 17;
+            seed ^= seed << 5;
+            let noise = (seed % 16) as u8;
+            rgb.extend_from_slice(&[(x as u8).wrapping_add(noise), (y * 2) as u8 ^ noise, ((x + y) / 3) as u8 + noise]);
+        }
+    }
+    rgb
+}
+
+fn encode(webp_mode: WebpMode, rgb: &[u8]) -> (Vec<u8>, Duration) {
+    let mut encoder = SoftwareEncoder::new(settings(webp_mode)).unwrap();
+    let started = Instant::now();
+    let frame = encoder.encode_frame(rgb, true).unwrap();
+    (frame.data.to_vec(), started.elapsed())
+}
+
+fn decode(data: &[u8]) -> Vec<u8> {
+    let image = webp::Decoder::new(data).decode().expect("valid WebP");
+    assert_eq!((image.width(), image.height()), (WIDTH, HEIGHT));
+    image.to_image().to_rgb8().into_raw()
+}
+
+#[test]
+fn lossless_keeps_text_exact_and_lossy_does_not() {
+    let text = text_frame();
+    let (lossless, lossless_time) = encode(WebpMode::Lossless, &text);
+    let (lossy, lossy_time) = encode(WebpMode::Lossy { quality: 85.0 }, &text);
+    
+    println!(
+        "text: lossless {} bytes in {:?}, lossy-85 {} bytes in {:?}",
+        lossless.len(), lossless_time, lossy.len(), lossy_time
+    );
+    
+    assert_eq!(decode(&lossless), text);
+    assert_ne!(decode(&lossy), text);
+}
+
+#[test]
+fn lossy_is_smaller_on_photographic_content() {
+    let photo = photo_frame();
+    let (lossless, lossless_time) = encode(WebpMode::Lossless, &photo);
+    let (lossy, lossy_time) = encode(WebpMode::Lossy { quality: 85.0 }, &photo);
+    
+    println!(
+        "photo: lossless {} bytes in {:?}, lossy-85 {} bytes in {:?}",
+        lossless.len(), lossless_time, lossy.len(), lossy_time
+    );
+    
+    assert_eq!(decode(&lossless), photo);
+    assert!(lossy.len() < lossless.len(), "lossy {} >= lossless {}", lossy.len(), lossless.len());
+}
+
+#[test]
+fn mode_follows_settings_updates() {
+    let photo = photo_frame();
+    let mut encoder = SoftwareEncoder::new(settings(WebpMode::Lossless)).unwrap();
+    let lossless = encoder.encode_frame(&photo, true).unwrap();
+    
+    encoder.update_settings(settings(QualityMode::Minimal.webp_mode())).unwrap();
+    let lossy = encoder.encode_frame(&photo, true).unwrap();
+    assert!(lossy.data.len() < lossless.data.len());
+}
+
+#[test]
+fn top_quality_tiers_are_lossless() {
+    assert_eq!(EncoderTuning::default().webp_mode, WebpMode::Lossless);
+    assert_eq!(QualityMode::Ultra.webp_mode(), WebpMode::Lossless);
+    assert_eq!(QualityMode::High.webp_mode(), WebpMode::Lossless);
+    assert!(matches!(QualityMode::Medium.webp_mode(), WebpMode::Lossy { .. }));
+    assert!(matches!(QualityMode::Minimal.webp_mode(), WebpMode::Lossy { .. }));
+}